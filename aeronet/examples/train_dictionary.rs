@@ -0,0 +1,48 @@
+//! Trains a zstd dictionary from traffic dumps written by
+//! `aeronet::DumpWriter`.
+//!
+//! ```sh
+//! cargo run --example train_dictionary --features zstd -- game.dict c2s.dump s2c.dump
+//! ```
+//!
+//! Set the `DICT_SIZE` environment variable to change the maximum size of the
+//! dictionary in bytes.
+
+use std::{env, fs::File, io::BufReader};
+
+use aeronet::DEFAULT_DICTIONARY_SIZE;
+use anyhow::{bail, Context, Result};
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let Some(output) = args.next() else {
+        bail!("usage: train_dictionary <output> <dump>...");
+    };
+    let max_size = match env::var("DICT_SIZE") {
+        Ok(size) => size.parse().context("invalid DICT_SIZE")?,
+        Err(_) => DEFAULT_DICTIONARY_SIZE,
+    };
+
+    let mut samples = Vec::new();
+    for path in args {
+        let file = File::open(&path).with_context(|| format!("failed to open {path}"))?;
+        let msgs = aeronet::read_dump(BufReader::new(file))
+            .with_context(|| format!("failed to read dump {path}"))?;
+        println!("Read {} messages from {path}", msgs.len());
+        samples.extend(msgs);
+    }
+    if samples.is_empty() {
+        bail!("no messages to train on");
+    }
+
+    let total_len = samples.iter().map(Vec::len).sum::<usize>();
+    println!(
+        "Training dictionary of up to {max_size} bytes on {} messages ({total_len} bytes)",
+        samples.len()
+    );
+    let dict =
+        aeronet::train_dictionary(&samples, max_size).context("failed to train dictionary")?;
+    std::fs::write(&output, &dict).with_context(|| format!("failed to write {output}"))?;
+    println!("Wrote {} byte dictionary to {output}", dict.len());
+    Ok(())
+}
@@ -0,0 +1,483 @@
+//! Declarative per-lane bandwidth budgets.
+//!
+//! Apps usually throttle what they send ad-hoc, e.g. by only sending position
+//! updates every few ticks, which makes it hard to tell how much bandwidth a
+//! connection will use, or which subsystem is using too much of it. Instead,
+//! the budget of every lane can be declared up front in a [`BudgetConfig`],
+//! using a target rate in bytes per second and the largest message allowed on
+//! the lane. Since the config is plain data, the client and server can share
+//! the same one.
+//!
+//! [`BudgetConfig::plan`] turns the config into a [`BudgetPlan`] for a given
+//! tick rate, which lays out how many bytes each lane may send per tick. Use
+//! [`BudgetPlan::total_per_tick`] to check the plan against the expected
+//! bandwidth of a connection.
+//!
+//! At runtime, a [`BudgetTracker`] enforces the plan. Every message is checked
+//! against its lane's budget using [`BudgetTracker::try_spend`] before it is
+//! sent, and [`BudgetTracker::tick`] refills the budgets at the end of every
+//! tick. The tracker also records [`LaneBudgetStats`] for each lane, showing
+//! how much of its budget the lane uses and how often it runs out.
+//!
+//! ```ignore
+//! let config = BudgetConfig::new(|lane: &AppChannel| match lane {
+//!     AppChannel::State => Some(LaneBudget::new(16 * 1024, 1200)),
+//!     AppChannel::Chat => Some(LaneBudget::new(1024, 512)),
+//! });
+//! let plan = config.plan(60)?;
+//! let mut budget = BudgetTracker::new(plan);
+//!
+//! // every tick
+//! for msg in outgoing {
+//!     let size = msg.try_into_bytes()?.as_ref().len();
+//!     if budget.try_spend(&msg.channel(), size).is_ok() {
+//!         client.send(msg)?;
+//!     }
+//! }
+//! budget.tick();
+//! ```
+//!
+//! # Bursts
+//!
+//! A lane's budget is a token bucket which is refilled by
+//! [`TickBudget::per_tick`] bytes every tick, up to [`TickBudget::burst`]
+//! bytes. Unused budget carries over to later ticks up to the burst size, so a
+//! lane can send a message larger than its per-tick budget by saving up for
+//! it over several ticks.
+
+use std::marker::PhantomData;
+
+use derivative::Derivative;
+
+use crate::ChannelKey;
+
+/// Budget of a single lane, declared as a rate.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LaneBudget {
+    /// Target number of bytes sent on this lane per second.
+    pub bytes_per_sec: usize,
+    /// Size in bytes of the largest message allowed on this lane.
+    pub max_msg_size: usize,
+}
+
+impl LaneBudget {
+    /// Creates a budget from a target rate and a maximum message size.
+    #[must_use]
+    pub fn new(bytes_per_sec: usize, max_msg_size: usize) -> Self {
+        Self {
+            bytes_per_sec,
+            max_msg_size,
+        }
+    }
+}
+
+/// Declared [`LaneBudget`] of every lane of a protocol.
+///
+/// Lanes without a budget are not limited.
+///
+/// See the [module-level docs](self).
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub struct BudgetConfig<C> {
+    budgets: Box<[Option<LaneBudget>]>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> C>,
+}
+
+/// Error that occurs when planning a [`BudgetConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PlanError<C> {
+    /// The tick rate was zero.
+    #[error("tick rate must be greater than zero")]
+    ZeroTickRate,
+    /// A lane's maximum message size is larger than its budget for a whole
+    /// second, so a message of that size could not be sent without going
+    /// over the target rate.
+    #[error("max message size of {max_msg_size} bytes on {lane:?} is larger than its rate")]
+    MessageTooLarge {
+        /// The lane.
+        lane: C,
+        /// The declared maximum message size.
+        max_msg_size: usize,
+    },
+}
+
+impl<C: ChannelKey> BudgetConfig<C> {
+    /// Creates a configuration from a function giving the budget of each
+    /// lane, or [`None`] to not limit the lane.
+    pub fn new(budget: impl Fn(&C) -> Option<LaneBudget>) -> Self {
+        Self {
+            budgets: C::ALL.iter().map(budget).collect(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets the budget of a lane.
+    #[must_use]
+    pub fn budget(&self, lane: &C) -> Option<LaneBudget> {
+        self.budgets[lane.index()]
+    }
+
+    /// Computes the budget of every lane per tick, for the given number of
+    /// ticks per second.
+    ///
+    /// The per-tick budget is rounded up, so a lane may send slightly more
+    /// than its target rate if the rate is not divisible by the tick rate.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the tick rate is zero, or if a lane's maximum message size is
+    /// larger than its rate.
+    pub fn plan(&self, tick_rate: u32) -> Result<BudgetPlan<C>, PlanError<C>> {
+        let ticks = usize::try_from(tick_rate).unwrap_or(usize::MAX);
+        if ticks == 0 {
+            return Err(PlanError::ZeroTickRate);
+        }
+
+        let lanes = C::ALL
+            .iter()
+            .zip(self.budgets.iter())
+            .map(|(lane, budget)| {
+                let Some(budget) = budget else {
+                    return Ok(None);
+                };
+                if budget.max_msg_size > budget.bytes_per_sec {
+                    return Err(PlanError::MessageTooLarge {
+                        lane: lane.clone(),
+                        max_msg_size: budget.max_msg_size,
+                    });
+                }
+                let per_tick = budget.bytes_per_sec.div_ceil(ticks);
+                Ok(Some(TickBudget {
+                    per_tick,
+                    burst: per_tick.max(budget.max_msg_size),
+                    max_msg_size: budget.max_msg_size,
+                }))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(BudgetPlan {
+            tick_rate,
+            lanes,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Budget of a single lane per tick, computed by [`BudgetConfig::plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TickBudget {
+    /// Number of bytes added to the lane's budget every tick.
+    pub per_tick: usize,
+    /// Maximum number of bytes that the lane's budget can save up.
+    pub burst: usize,
+    /// Size in bytes of the largest message allowed on the lane.
+    pub max_msg_size: usize,
+}
+
+/// Per-tick budget of every lane of a protocol, computed by
+/// [`BudgetConfig::plan`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub struct BudgetPlan<C> {
+    tick_rate: u32,
+    lanes: Box<[Option<TickBudget>]>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<C: ChannelKey> BudgetPlan<C> {
+    /// Gets the number of ticks per second that this plan was computed for.
+    #[must_use]
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    /// Gets the budget of a lane per tick, or [`None`] if the lane is not
+    /// limited.
+    #[must_use]
+    pub fn lane(&self, lane: &C) -> Option<TickBudget> {
+        self.lanes[lane.index()]
+    }
+
+    /// Gets the total number of bytes that all limited lanes may send per
+    /// tick.
+    #[must_use]
+    pub fn total_per_tick(&self) -> usize {
+        self.lanes.iter().flatten().map(|lane| lane.per_tick).sum()
+    }
+}
+
+/// Error returned by [`BudgetTracker::try_spend`] when a message does not fit
+/// in its lane's budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum BudgetError {
+    /// The message is larger than the lane's maximum message size.
+    #[error("message of {size} bytes is larger than the max of {max} bytes")]
+    TooLarge {
+        /// Size of the message in bytes.
+        size: usize,
+        /// Maximum message size of the lane in bytes.
+        max: usize,
+    },
+    /// The lane does not have enough budget left in this tick.
+    #[error("message of {size} bytes does not fit in the remaining {available} bytes")]
+    Exhausted {
+        /// Size of the message in bytes.
+        size: usize,
+        /// Remaining budget of the lane in bytes.
+        available: usize,
+    },
+}
+
+/// Usage of a single lane's budget, recorded by a [`BudgetTracker`].
+///
+/// Values are measured over the period since the stats were last taken using
+/// [`BudgetTracker::take_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LaneBudgetStats {
+    /// Number of ticks which have ended.
+    pub ticks: u64,
+    /// Number of messages which fit in the budget.
+    pub msgs: u64,
+    /// Number of bytes of messages which fit in the budget.
+    pub bytes: u64,
+    /// Number of messages which were rejected, either for being too large or
+    /// for not fitting in the remaining budget.
+    pub rejected_msgs: u64,
+    /// Number of bytes of messages which were rejected.
+    pub rejected_bytes: u64,
+    /// Most bytes spent in a single tick.
+    pub peak_tick_bytes: usize,
+    /// Number of ticks in which at least one message was rejected because the
+    /// budget was exhausted.
+    pub exhausted_ticks: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct LaneState {
+    available: usize,
+    tick_bytes: usize,
+    exhausted: bool,
+    stats: LaneBudgetStats,
+}
+
+/// Enforces a [`BudgetPlan`] at runtime, and records how each lane uses its
+/// budget.
+///
+/// See the [module-level docs](self).
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub struct BudgetTracker<C> {
+    plan: BudgetPlan<C>,
+    lanes: Box<[LaneState]>,
+}
+
+impl<C: ChannelKey> BudgetTracker<C> {
+    /// Creates a tracker for a plan, with every lane's budget starting full.
+    #[must_use]
+    pub fn new(plan: BudgetPlan<C>) -> Self {
+        let lanes = plan
+            .lanes
+            .iter()
+            .map(|budget| LaneState {
+                available: budget.map_or(0, |budget| budget.burst),
+                ..LaneState::default()
+            })
+            .collect();
+        Self { plan, lanes }
+    }
+
+    /// Gets the plan that this tracker enforces.
+    #[must_use]
+    pub fn plan(&self) -> &BudgetPlan<C> {
+        &self.plan
+    }
+
+    /// Gets the remaining budget of a lane in bytes, or [`None`] if the lane
+    /// is not limited.
+    #[must_use]
+    pub fn available(&self, lane: &C) -> Option<usize> {
+        self.plan
+            .lane(lane)
+            .map(|_| self.lanes[lane.index()].available)
+    }
+
+    /// Takes the budget for a message of `size` bytes out of its lane's
+    /// budget.
+    ///
+    /// Messages on lanes which are not limited always fit, but are still
+    /// counted in the lane's stats.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message does not fit in the budget, in which case it
+    /// should not be sent.
+    pub fn try_spend(&mut self, lane: &C, size: usize) -> Result<(), BudgetError> {
+        let budget = self.plan.lane(lane);
+        let state = &mut self.lanes[lane.index()];
+        let result = match budget {
+            None => Ok(()),
+            Some(budget) if size > budget.max_msg_size => Err(BudgetError::TooLarge {
+                size,
+                max: budget.max_msg_size,
+            }),
+            Some(_) if size > state.available => {
+                state.exhausted = true;
+                Err(BudgetError::Exhausted {
+                    size,
+                    available: state.available,
+                })
+            }
+            Some(_) => {
+                state.available -= size;
+                Ok(())
+            }
+        };
+
+        let size_u64 = u64::try_from(size).unwrap_or(u64::MAX);
+        if result.is_ok() {
+            state.tick_bytes += size;
+            state.stats.msgs += 1;
+            state.stats.bytes += size_u64;
+        } else {
+            state.stats.rejected_msgs += 1;
+            state.stats.rejected_bytes += size_u64;
+        }
+        result
+    }
+
+    /// Ends the current tick, refilling the budget of every lane.
+    pub fn tick(&mut self) {
+        for (budget, state) in self.plan.lanes.iter().zip(self.lanes.iter_mut()) {
+            if let Some(budget) = budget {
+                state.available = (state.available + budget.per_tick).min(budget.burst);
+            }
+            state.stats.ticks += 1;
+            state.stats.peak_tick_bytes = state.stats.peak_tick_bytes.max(state.tick_bytes);
+            if state.exhausted {
+                state.stats.exhausted_ticks += 1;
+            }
+            state.tick_bytes = 0;
+            state.exhausted = false;
+        }
+    }
+
+    /// Gets the stats of a lane since they were last taken.
+    #[must_use]
+    pub fn stats(&self, lane: &C) -> LaneBudgetStats {
+        self.lanes[lane.index()].stats
+    }
+
+    /// Takes the stats of every lane, resetting them.
+    pub fn take_stats(&mut self) -> impl Iterator<Item = (C, LaneBudgetStats)> + '_ {
+        C::ALL
+            .iter()
+            .zip(self.lanes.iter_mut())
+            .map(|(lane, state)| (lane.clone(), std::mem::take(&mut state.stats)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChannelKind;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Lane {
+        State,
+        Free,
+    }
+
+    unsafe impl ChannelKey for Lane {
+        const ALL: &'static [Self] = &[Self::State, Self::Free];
+
+        fn index(&self) -> usize {
+            match self {
+                Self::State => 0,
+                Self::Free => 1,
+            }
+        }
+
+        fn kind(&self) -> ChannelKind {
+            ChannelKind::Unreliable
+        }
+    }
+
+    fn config() -> BudgetConfig<Lane> {
+        BudgetConfig::new(|lane| match lane {
+            Lane::State => Some(LaneBudget::new(1000, 150)),
+            Lane::Free => None,
+        })
+    }
+
+    #[test]
+    fn plan() {
+        let plan = config().plan(30).unwrap();
+        assert_eq!(
+            Some(TickBudget {
+                per_tick: 34,
+                burst: 150,
+                max_msg_size: 150,
+            }),
+            plan.lane(&Lane::State)
+        );
+        assert_eq!(None, plan.lane(&Lane::Free));
+        assert_eq!(34, plan.total_per_tick());
+
+        assert!(matches!(config().plan(0), Err(PlanError::ZeroTickRate)));
+        let config = BudgetConfig::new(|_: &Lane| Some(LaneBudget::new(100, 200)));
+        assert!(matches!(
+            config.plan(10),
+            Err(PlanError::MessageTooLarge {
+                lane: Lane::State,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn enforce() {
+        let mut budget = BudgetTracker::new(config().plan(10).unwrap());
+        assert_eq!(Some(150), budget.available(&Lane::State));
+        assert_eq!(
+            Err(BudgetError::TooLarge {
+                size: 200,
+                max: 150
+            }),
+            budget.try_spend(&Lane::State, 200)
+        );
+        assert_eq!(Ok(()), budget.try_spend(&Lane::State, 120));
+        assert_eq!(
+            Err(BudgetError::Exhausted {
+                size: 50,
+                available: 30
+            }),
+            budget.try_spend(&Lane::State, 50)
+        );
+        assert_eq!(Ok(()), budget.try_spend(&Lane::Free, 10_000));
+
+        budget.tick();
+        assert_eq!(Some(130), budget.available(&Lane::State));
+        budget.tick();
+        assert_eq!(Some(150), budget.available(&Lane::State));
+
+        let stats = budget.take_stats().collect::<Vec<_>>();
+        assert_eq!(
+            LaneBudgetStats {
+                ticks: 2,
+                msgs: 1,
+                bytes: 120,
+                rejected_msgs: 2,
+                rejected_bytes: 250,
+                peak_tick_bytes: 120,
+                exhausted_ticks: 1,
+            },
+            stats[0].1
+        );
+        assert_eq!(10_000, stats[1].1.bytes);
+        assert_eq!(LaneBudgetStats::default(), budget.stats(&Lane::State));
+    }
+}
@@ -1,84 +1,6 @@
 use std::fmt::Debug;
 
-/// Represents what kind of method is used to transport data along a connection.
-///
-/// A connection may support different methods for transporting messages, where
-/// each different method is called a channel. A channel provides guarantees on:
-/// * **reliablity** - ensuring that the message reaches the other side without
-///   being lost
-/// * **ordering** - ensuring that messages are received in the same order that
-///   they are sent
-///
-/// Although it is not a part of the guarantees laid out by the channel kinds,
-/// **head-of-line blocking** is also an important factor to consider when
-/// choosing which kind of channel to use. A channel kind with head-of-line
-/// blocking may block when it is awaiting a message sent earlier, in order to
-/// maintain ordering; others may not.
-///
-/// The transport implementation is guaranteed to provide these channel kinds,
-/// either by using a feature of the underlying transport mechanism (i.e. QUIC
-/// streams on WebTransport) or via a custom layer implemented on top of the
-/// transport mechanism.
-///
-/// Note that channel kinds provide a *minimum* guarantee of reliability and
-/// ordering - a transport may provide some guarantees even if using a less
-/// reliable channel kind.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ChannelKind {
-    /// No guarantees given on **reliability** or **ordering**.
-    ///
-    /// This is useful for messages which should be sent in a fire-and-forget
-    /// manner: that is, you don't expect to get a response for this message,
-    /// and it is OK if a few messages are lost in transit.
-    ///
-    /// This channel kind typically has the best performance, as it does not
-    /// require any sort of handshaking to ensure that messages have arrived
-    /// from one side to the other.
-    ///
-    /// An example of a message using this channel kind is a player positional
-    /// update, sent to the server whenever a client moves in a game world.
-    /// Since the game client will constantly be sending positional update
-    /// messages at a high rate, it is OK if a few are lost in transit, as the
-    /// server will hopefully catch the next messages.
-    Unreliable,
-    /// Messages are sent **reliably** but the **ordering** is not guaranteed.
-    ///
-    /// This is useful for important one-off events where you need a guarantee
-    /// that the message will be delivered, but the order in which it is
-    /// delivered is not important.
-    ///
-    /// This channel kind is typically slower to send and receive than an
-    /// unreliable message, but is still faster than an ordered channel because
-    /// the implementation may be able to avoid head-of-line blocking.
-    ///
-    /// An example of a message using this channel kind is sending level data
-    /// from a server to a client. It is not important what order the different
-    /// parts of the level are received in, but it is important that they are
-    /// all received.
-    ReliableUnordered,
-    /// Messages are sent **reliablity** and **ordered**.
-    ///
-    /// This is useful for important one-off events where you need a guarantee
-    /// that the message will be delivered, and the order in which it's
-    /// delivered is important.
-    ///
-    /// This channel kind offers the most guarantees, but is typically slower to
-    /// send and receive than other channel kinds. Most notably, implementations
-    /// may suffer from head-of-line blocking.
-    ///
-    /// Implementations may suffer from head-of-line blocking if a reliable
-    /// channel is used, where messages cannot be received because they are
-    /// being held up by a message sent earlier. To avoid this, you may use
-    /// multiple different instances of this kind of channel, all of which hold
-    /// their own message queues.
-    ///
-    /// An example of a message using this channel kind is sending chat messages
-    /// from the server to the client. Since the server aggregates chat messages
-    /// from different sources (system, other players, etc.) in a specific
-    /// order, it must then tell its clients about the chat messages in that
-    /// specific order as well.
-    ReliableOrdered,
-}
+pub use aeronet_proto::ChannelKind;
 
 /// Represents a finite set of channels that may be opened by an app.
 ///
@@ -91,8 +13,8 @@ pub enum ChannelKind {
 /// # Safety
 ///
 /// This should be derived rather than implemented manually - see
-/// [`aeronet_derive::ChannelKey`]. Otherwise, transport implementations may
-/// panic.
+/// [`aeronet_derive::ChannelKey`], or define the key using [`channels!`].
+/// Otherwise, transport implementations may panic.
 pub unsafe trait ChannelKey: Send + Sync + Sized + Debug + Clone + 'static {
     /// The set of all valid variants of [`ChannelKey`].
     ///
@@ -127,3 +49,122 @@ pub trait OnChannel {
     /// The channel key along which this message is sent.
     fn channel(&self) -> Self::Channel;
 }
+
+/// Defines a [`ChannelKey`] enum, and which messages are sent along each of its
+/// channels, in a single place.
+///
+/// Both the client and server should use the same invocation of this macro
+/// (e.g. in a crate shared between them). Transports lay out their lanes based
+/// on the order of [`ChannelKey::ALL`], so defining the channels once means
+/// that the two sides can not drift apart.
+///
+/// Each variant is followed by its kind, which is one of:
+/// * `unreliable` - [`ChannelKind::Unreliable`]
+/// * `reliable unordered` - [`ChannelKind::ReliableUnordered`]
+/// * `reliable ordered` - [`ChannelKind::ReliableOrdered`]
+///
+/// and optionally a list of message types which are always sent along that
+/// channel, for which [`OnChannel`] is implemented. Each message type may only
+/// appear once across the whole invocation.
+///
+/// The generated enum derives `Debug`, `Clone`, `Copy`, `PartialEq`, `Eq` and
+/// `Hash`, and its [`ChannelKey`] impl matches exhaustively on its variants, so
+/// adding a variant without a kind is a compile error.
+///
+/// # Usage
+///
+/// ```
+/// # use aeronet::{ChannelKey, ChannelKind, OnChannel};
+/// pub struct Chat(pub String);
+///
+/// pub struct Move(pub f32, pub f32);
+///
+/// pub struct Teleport(pub f32, pub f32);
+///
+/// aeronet::channels! {
+///     /// Channels used by the app.
+///     pub enum AppChannel {
+///         /// Chat messages, which must arrive in order.
+///         Chat: reliable ordered => [Chat],
+///         /// Position updates, where only the latest one is relevant.
+///         Movement: unreliable => [Move, Teleport],
+///         /// Level data, which may arrive in any order.
+///         Level: reliable unordered,
+///     }
+/// }
+///
+/// assert_eq!(
+///     &[AppChannel::Chat, AppChannel::Movement, AppChannel::Level],
+///     AppChannel::ALL
+/// );
+/// assert_eq!(1, AppChannel::Movement.index());
+/// assert_eq!(ChannelKind::Unreliable, Teleport(0.0, 0.0).channel().kind());
+/// ```
+#[macro_export]
+macro_rules! channels {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident : $($kind:ident)+ $(=> [$($msg:ty),* $(,)?])?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $(
+                $(#[$variant_attr])*
+                $variant,
+            )*
+        }
+
+        // SAFETY: `ALL` contains every variant in declaration order, which is
+        // also the order of the discriminants returned by `index`
+        unsafe impl $crate::ChannelKey for $name {
+            const ALL: &'static [Self] = &[$(Self::$variant),*];
+
+            fn index(&self) -> usize {
+                *self as usize
+            }
+
+            fn kind(&self) -> $crate::ChannelKind {
+                match self {
+                    $(Self::$variant => $crate::__channel_kind!($($kind)+),)*
+                }
+            }
+        }
+
+        $($($(
+            impl $crate::OnChannel for $msg {
+                type Channel = $name;
+
+                fn channel(&self) -> Self::Channel {
+                    $name::$variant
+                }
+            }
+        )*)?)*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __channel_kind {
+    (unreliable) => {
+        $crate::ChannelKind::Unreliable
+    };
+    (reliable unordered) => {
+        $crate::ChannelKind::ReliableUnordered
+    };
+    (reliable ordered) => {
+        $crate::ChannelKind::ReliableOrdered
+    };
+    ($($kind:ident)+) => {
+        compile_error!(concat!(
+            "invalid channel kind `",
+            stringify!($($kind)+),
+            "`, expected `unreliable`, `reliable unordered` or `reliable ordered`"
+        ))
+    };
+}
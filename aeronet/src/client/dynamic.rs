@@ -0,0 +1,206 @@
+use std::error::Error;
+
+use crate::{ClientEvent, DynConnectionInfo, DynError, TransportClient, TransportProtocol};
+
+/// A [`DynTransportClient`] behind a [`Box`], which itself implements
+/// [`TransportClient`].
+pub type BoxedTransportClient<P> = Box<dyn DynTransportClient<P>>;
+
+/// Object-safe version of [`TransportClient`].
+///
+/// This is automatically implemented for all [`TransportClient`]s whose errors
+/// implement [`Error`]. This allows choosing a transport implementation at
+/// runtime, e.g. based on a launch flag, without making the rest of your app
+/// generic over the transport.
+///
+/// All type-specific information is erased:
+/// * errors are boxed as a [`DynError`]
+/// * connection info is boxed as a [`DynConnectionInfo`]
+/// * transport-specific events which do not map to a [`ClientEvent`] are
+///   discarded
+///
+/// A [`BoxedTransportClient`] implements [`TransportClient`], so it can be used
+/// anywhere that a concrete transport can.
+pub trait DynTransportClient<P>: Send + Sync
+where
+    P: TransportProtocol,
+{
+    /// See [`TransportClient::TRANSPORT_NAME`].
+    fn transport_name(&self) -> &'static str;
+
+    /// See [`TransportClient::connection_info`].
+    fn connection_info(&self) -> Option<DynConnectionInfo>;
+
+    /// See [`TransportClient::send`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportClient::send`].
+    fn send(&mut self, msg: P::C2S) -> Result<(), DynError>;
+
+    /// See [`TransportClient::recv`].
+    fn recv(&mut self) -> Vec<ClientEvent<P, BoxedTransportClient<P>>>;
+
+    /// See [`TransportClient::disconnect`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportClient::disconnect`].
+    fn disconnect(&mut self) -> Result<(), DynError>;
+}
+
+impl<P, T> DynTransportClient<P> for T
+where
+    P: TransportProtocol,
+    T: TransportClient<P> + Send + Sync,
+    T::Error: Error,
+    T::ConnectionInfo: Send + Sync + 'static,
+{
+    fn transport_name(&self) -> &'static str {
+        T::TRANSPORT_NAME
+    }
+
+    fn connection_info(&self) -> Option<DynConnectionInfo> {
+        TransportClient::connection_info(self).map(|info| Box::new(info) as DynConnectionInfo)
+    }
+
+    fn send(&mut self, msg: P::C2S) -> Result<(), DynError> {
+        TransportClient::send(self, msg).map_err(DynError::from)
+    }
+
+    fn recv(&mut self) -> Vec<ClientEvent<P, BoxedTransportClient<P>>> {
+        TransportClient::recv(self)
+            .filter_map(Into::into)
+            .map(|event| match event {
+                ClientEvent::Connected => ClientEvent::Connected,
+                ClientEvent::Recv { msg } => ClientEvent::Recv { msg },
+                ClientEvent::Disconnected { cause } => ClientEvent::Disconnected {
+                    cause: DynError::from(cause),
+                },
+            })
+            .collect()
+    }
+
+    fn disconnect(&mut self) -> Result<(), DynError> {
+        TransportClient::disconnect(self).map_err(DynError::from)
+    }
+}
+
+impl<P> TransportClient<P> for BoxedTransportClient<P>
+where
+    P: TransportProtocol,
+{
+    /// The name of the underlying transport is only known at runtime, and can
+    /// be accessed using [`DynTransportClient::transport_name`].
+    const TRANSPORT_NAME: &'static str = "dyn";
+
+    type Error = DynError;
+
+    type ConnectionInfo = DynConnectionInfo;
+
+    type Event = ClientEvent<P, Self>;
+
+    fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+        DynTransportClient::connection_info(&**self)
+    }
+
+    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
+        DynTransportClient::send(&mut **self, msg.into())
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        DynTransportClient::recv(&mut **self).into_iter()
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        DynTransportClient::disconnect(&mut **self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, mem};
+
+    use super::*;
+
+    struct Protocol;
+
+    impl TransportProtocol for Protocol {
+        type C2S = u32;
+        type S2C = u32;
+    }
+
+    #[derive(Default)]
+    struct MockClient {
+        sent: Option<Vec<u32>>,
+        events: Vec<ClientEvent<Protocol, Self>>,
+    }
+
+    impl TransportClient<Protocol> for MockClient {
+        const TRANSPORT_NAME: &'static str = "mock";
+
+        type Error = io::Error;
+
+        type ConnectionInfo = usize;
+
+        type Event = ClientEvent<Protocol, Self>;
+
+        fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+            self.sent.as_ref().map(Vec::len)
+        }
+
+        fn send(&mut self, msg: impl Into<u32>) -> Result<(), Self::Error> {
+            let sent = self
+                .sent
+                .as_mut()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            sent.push(msg.into());
+            Ok(())
+        }
+
+        fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+            mem::take(&mut self.events).into_iter()
+        }
+
+        fn disconnect(&mut self) -> Result<(), Self::Error> {
+            self.sent
+                .take()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            self.events.push(ClientEvent::Disconnected {
+                cause: io::Error::from(io::ErrorKind::ConnectionAborted),
+            });
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn boxed_client() {
+        let inner = MockClient {
+            sent: Some(Vec::new()),
+            events: vec![ClientEvent::Connected, ClientEvent::Recv { msg: 3 }],
+        };
+        let mut client: BoxedTransportClient<Protocol> = Box::new(inner);
+
+        assert_eq!("mock", DynTransportClient::transport_name(&*client));
+        TransportClient::send(&mut client, 5u32).unwrap();
+        let info = TransportClient::connection_info(&client).unwrap();
+        assert_eq!(Some(&1), info.downcast_ref::<usize>());
+
+        let events = TransportClient::recv(&mut client).collect::<Vec<_>>();
+        assert!(matches!(
+            events.as_slice(),
+            [ClientEvent::Connected, ClientEvent::Recv { msg: 3 }]
+        ));
+
+        TransportClient::disconnect(&mut client).unwrap();
+        assert!(TransportClient::send(&mut client, 6u32).is_err());
+        let events = TransportClient::recv(&mut client).collect::<Vec<_>>();
+        let [ClientEvent::Disconnected { cause }] = events.as_slice() else {
+            panic!("expected a single disconnect event");
+        };
+        assert_eq!(
+            Some(io::ErrorKind::ConnectionAborted),
+            cause.downcast_ref::<io::Error>().map(io::Error::kind)
+        );
+    }
+}
@@ -2,7 +2,14 @@
 mod plugin;
 
 #[cfg(feature = "bevy")]
-pub use plugin::*;
+mod replay;
+
+#[cfg(feature = "bevy")]
+pub use {plugin::*, replay::*};
+
+mod dynamic;
+
+pub use dynamic::*;
 
 use crate::TransportProtocol;
 
@@ -13,6 +20,10 @@ pub trait TransportClient<P>
 where
     P: TransportProtocol,
 {
+    /// Human-readable name of this transport implementation, e.g. for use in
+    /// logs and diagnostics.
+    const TRANSPORT_NAME: &'static str;
+
     /// Error returned from operations on this client.
     type Error: Send + Sync + 'static;
 
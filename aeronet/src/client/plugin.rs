@@ -1,9 +1,12 @@
-use std::marker::PhantomData;
+use std::{fmt::Display, marker::PhantomData, mem};
 
 use bevy::prelude::*;
 use derivative::Derivative;
 
-use crate::{ClientEvent, TransportClient, TransportProtocol};
+use crate::{
+    BoxedTransportClient, ClientEvent, ClientNetworkError, ClientOp, DynConnectionInfo, DynError,
+    DynTransportClient, NetworkErrorPolicy, TransportClient, TransportProtocol,
+};
 
 /// Provides systems to send commands to, and receive events from, a
 /// [`TransportClient`].
@@ -26,13 +29,19 @@ use crate::{ClientEvent, TransportClient, TransportProtocol};
 /// To connect the client to a server, you will have to know the concrete type
 /// of the client transport, and call the function on it manually.
 ///
-/// Note that errors during operation will be silently ignored, e.g. if you
-/// attempt to send a message while the client is not connected.
+/// To be able to replace the transport at runtime, use [`DynClientTransport`]
+/// as the transport type `T`.
+///
+/// Errors during operation, e.g. if you attempt to send a message while the
+/// client is not connected, are handled according to the
+/// [`NetworkErrorPolicy`] resource. By default, a [`ClientNetworkError`] is
+/// sent.
 pub fn transport_client_plugin<P, T>(app: &mut App)
 where
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
     app.configure_sets(PreUpdate, TransportClientSet::Recv)
         .configure_sets(PostUpdate, TransportClientSet::Send)
@@ -41,6 +50,8 @@ where
         .add_event::<LocalClientDisconnected<P, T>>()
         .add_event::<ToServer<P>>()
         .add_event::<DisconnectLocalClient>()
+        .add_event::<ClientNetworkError<P, T>>()
+        .init_resource::<NetworkErrorPolicy>()
         .add_systems(PreUpdate, recv::<P, T>.in_set(TransportClientSet::Recv))
         .add_systems(
             PostUpdate,
@@ -64,6 +75,7 @@ where
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
     #[derivative(Debug = "ignore")]
     _phantom_p: PhantomData<P>,
@@ -76,6 +88,7 @@ where
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
     fn build(&self, app: &mut App) {
         transport_client_plugin::<P, T>(app);
@@ -148,6 +161,152 @@ where
 #[derive(Debug, Clone, Event)]
 pub struct DisconnectLocalClient;
 
+/// Client transport [`Resource`] whose underlying transport can be replaced at
+/// runtime.
+///
+/// Use this as the transport type of a [`TransportClientPlugin`] so that the
+/// transport is not fixed in the plugin's generics, e.g. to use an in-memory
+/// channel transport in a tutorial, and switch to a networked transport for
+/// online play:
+///
+/// ```ignore
+/// app.add_plugins(TransportClientPlugin::<MyProtocol, DynClientTransport<MyProtocol>>::default())
+///     .insert_resource(DynClientTransport::new(channel_client));
+///
+/// fn go_online(mut client: ResMut<DynClientTransport<MyProtocol>>) {
+///     client.replace(webtransport_client);
+/// }
+/// ```
+///
+/// If the transport is replaced while connected, the old transport is
+/// disconnected, and the next [`TransportClient::recv`] raises a
+/// [`ClientEvent::Disconnected`] caused by [`TransportReplaced`]. This means
+/// that a [`LocalClientDisconnected`] is still sent for every
+/// [`LocalClientConnected`]. Events which the old transport had not been polled
+/// for yet are discarded.
+#[derive(Derivative, Resource)]
+#[derivative(Debug(bound = ""))]
+pub struct DynClientTransport<P>
+where
+    P: TransportProtocol,
+{
+    #[derivative(Debug = "ignore")]
+    inner: BoxedTransportClient<P>,
+    connected: bool,
+    replaced: bool,
+}
+
+/// The transport of a [`DynClientTransport`] was replaced while it was
+/// connected.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("transport replaced")]
+pub struct TransportReplaced;
+
+impl<P> DynClientTransport<P>
+where
+    P: TransportProtocol,
+{
+    /// Creates a resource wrapping the given transport.
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: DynTransportClient<P> + 'static,
+    {
+        Self::from_boxed(Box::new(transport))
+    }
+
+    /// Creates a resource wrapping the given boxed transport.
+    #[must_use]
+    pub fn from_boxed(transport: BoxedTransportClient<P>) -> Self {
+        Self {
+            inner: transport,
+            connected: false,
+            replaced: false,
+        }
+    }
+
+    /// Gets the transport currently in use.
+    #[must_use]
+    pub fn transport(&self) -> &dyn DynTransportClient<P> {
+        &*self.inner
+    }
+
+    /// Gets the transport currently in use.
+    pub fn transport_mut(&mut self) -> &mut dyn DynTransportClient<P> {
+        &mut *self.inner
+    }
+
+    /// Replaces the transport in use, disconnecting and returning the old one.
+    ///
+    /// See [`DynClientTransport`] for the events raised.
+    pub fn replace<T>(&mut self, transport: T) -> BoxedTransportClient<P>
+    where
+        T: DynTransportClient<P> + 'static,
+    {
+        self.replace_boxed(Box::new(transport))
+    }
+
+    /// Replaces the transport in use with a boxed transport, disconnecting and
+    /// returning the old one.
+    ///
+    /// See [`DynClientTransport`] for the events raised.
+    pub fn replace_boxed(&mut self, transport: BoxedTransportClient<P>) -> BoxedTransportClient<P> {
+        let mut old = mem::replace(&mut self.inner, transport);
+        let _ = DynTransportClient::disconnect(&mut *old);
+        if mem::take(&mut self.connected) {
+            self.replaced = true;
+        }
+        old
+    }
+}
+
+impl<P> TransportClient<P> for DynClientTransport<P>
+where
+    P: TransportProtocol,
+{
+    /// The name of the underlying transport is only known at runtime, and can
+    /// be accessed using [`DynTransportClient::transport_name`].
+    const TRANSPORT_NAME: &'static str = "dyn";
+
+    type Error = DynError;
+
+    type ConnectionInfo = DynConnectionInfo;
+
+    type Event = ClientEvent<P, Self>;
+
+    fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+        DynTransportClient::connection_info(&*self.inner)
+    }
+
+    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
+        DynTransportClient::send(&mut *self.inner, msg.into())
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let replaced = mem::take(&mut self.replaced).then(|| ClientEvent::Disconnected {
+            cause: DynError::from(TransportReplaced),
+        });
+        let events = DynTransportClient::recv(&mut *self.inner)
+            .into_iter()
+            .map(|event| match event {
+                ClientEvent::Connected => {
+                    self.connected = true;
+                    ClientEvent::Connected
+                }
+                ClientEvent::Recv { msg } => ClientEvent::Recv { msg },
+                ClientEvent::Disconnected { cause } => {
+                    self.connected = false;
+                    ClientEvent::Disconnected { cause }
+                }
+            })
+            .collect::<Vec<_>>();
+        replaced.into_iter().chain(events)
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        DynTransportClient::disconnect(&mut *self.inner)
+    }
+}
+
 // systems
 
 fn recv<P, T>(
@@ -159,6 +318,7 @@ fn recv<P, T>(
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
     for event in client.recv() {
         match event.into() {
@@ -172,22 +332,37 @@ fn recv<P, T>(
     }
 }
 
-fn send<P, T>(mut client: ResMut<T>, mut send: EventReader<ToServer<P>>)
-where
+#[allow(clippy::needless_pass_by_value)] // system params are passed by value
+fn send<P, T>(
+    mut client: ResMut<T>,
+    mut send: EventReader<ToServer<P>>,
+    policy: Res<NetworkErrorPolicy>,
+    mut errors: EventWriter<ClientNetworkError<P, T>>,
+) where
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
     for ToServer { msg } in send.read() {
-        let _ = client.send(msg.clone());
+        if let Err(error) = client.send(msg.clone()) {
+            policy.handle(&mut errors, T::TRANSPORT_NAME, ClientOp::Send, error);
+        }
     }
 }
 
-fn disconnect<P, T>(mut client: ResMut<T>)
-where
+#[allow(clippy::needless_pass_by_value)] // system params are passed by value
+fn disconnect<P, T>(
+    mut client: ResMut<T>,
+    policy: Res<NetworkErrorPolicy>,
+    mut errors: EventWriter<ClientNetworkError<P, T>>,
+) where
     P: TransportProtocol,
     P::C2S: Clone,
     T: TransportClient<P> + Resource,
+    T::Error: Display,
 {
-    let _ = client.disconnect();
+    if let Err(error) = client.disconnect() {
+        policy.handle(&mut errors, T::TRANSPORT_NAME, ClientOp::Disconnect, error);
+    }
 }
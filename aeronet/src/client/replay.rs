@@ -0,0 +1,510 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use bevy::prelude::*;
+use derivative::Derivative;
+
+use crate::{
+    ClientEvent, FromServer, LocalClientConnected, LocalClientDisconnected, ToServer,
+    TransportClient, TransportClientSet, TransportProtocol, TryFromBytes, TryIntoBytes,
+};
+
+/// Bytes at the start of a file written by [`ClientRecording::write_to`].
+const RECORDING_MAGIC: &[u8; 8] = b"aeronet\0";
+
+/// Version of the format written by [`ClientRecording::write_to`].
+const RECORDING_VERSION: u32 = 1;
+
+const TAG_CONNECTED: u8 = 0;
+const TAG_RECV: u8 = 1;
+const TAG_DISCONNECTED: u8 = 2;
+const TAG_SEND: u8 = 3;
+
+/// Records the events raised by a client transport and the messages sent
+/// through it into a [`ClientRecording`], so that a session can be replayed
+/// offline using a [`ReplayClient`].
+///
+/// To use a struct version of this plugin, see [`ClientRecorderPlugin`].
+///
+/// This must be added alongside a [`TransportClientPlugin`] for the same `P`
+/// and `T`. Every frame, the events emitted by that plugin on [`PreUpdate`],
+/// and the [`ToServer`] events that it consumes on [`PostUpdate`], are added
+/// to the [`ClientRecording`] resource together with the frame number.
+///
+/// When a bug is hit, write the recording to a file using
+/// [`ClientRecording::write_to`]. To replay it, read it back using
+/// [`ClientRecording::read_from`], and use a [`ReplayClient`] as the
+/// transport:
+///
+/// ```ignore
+/// // while playing
+/// app.add_plugins((
+///     TransportClientPlugin::<MyProtocol, MyTransport>::default(),
+///     ClientRecorderPlugin::<MyProtocol, MyTransport>::default(),
+/// ));
+///
+/// fn save_recording(recording: Res<ClientRecording<MyProtocol>>) {
+///     recording.write_to(File::create("session.rec")?)?;
+/// }
+///
+/// // when debugging
+/// let recording = ClientRecording::<MyProtocol>::read_from(File::open("session.rec")?)?;
+/// app.add_plugins(TransportClientPlugin::<MyProtocol, DynClientTransport<MyProtocol>>::default())
+///     .insert_resource(DynClientTransport::new(ReplayClient::new(recording)));
+/// ```
+///
+/// Since the replayed events have a different transport type than the
+/// original ones, systems which read [`LocalClientDisconnected`] should use a
+/// [`DynClientTransport`] as the transport type, both when recording and when
+/// replaying.
+///
+/// Messages are recorded in their serialized form. Messages which fail to
+/// serialize are not recorded.
+///
+/// [`TransportClientPlugin`]: crate::TransportClientPlugin
+/// [`DynClientTransport`]: crate::DynClientTransport
+pub fn client_recorder_plugin<P, T>(app: &mut App)
+where
+    P: TransportProtocol,
+    P::C2S: Clone + TryIntoBytes,
+    P::S2C: TryIntoBytes,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    app.init_resource::<ClientRecording<P>>()
+        .add_systems(
+            PreUpdate,
+            record_recv::<P, T>.after(TransportClientSet::Recv),
+        )
+        .add_systems(
+            PostUpdate,
+            record_send::<P>.before(TransportClientSet::Send),
+        );
+}
+
+/// Records the events raised by a client transport.
+///
+/// See [`client_recorder_plugin`].
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+pub struct ClientRecorderPlugin<P, T>
+where
+    P: TransportProtocol,
+    P::C2S: Clone + TryIntoBytes,
+    P::S2C: TryIntoBytes,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    #[derivative(Debug = "ignore")]
+    _phantom_p: PhantomData<P>,
+    #[derivative(Debug = "ignore")]
+    _phantom_t: PhantomData<T>,
+}
+
+impl<P, T> Plugin for ClientRecorderPlugin<P, T>
+where
+    P: TransportProtocol,
+    P::C2S: Clone + TryIntoBytes,
+    P::S2C: TryIntoBytes,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    fn build(&self, app: &mut App) {
+        client_recorder_plugin::<P, T>(app);
+    }
+}
+
+/// Single entry of a [`ClientRecording`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEntry {
+    /// Number of the frame that this entry was recorded on, starting from 0
+    /// when recording started.
+    pub frame: u64,
+    /// What was recorded.
+    pub kind: RecordedKind,
+}
+
+/// What was recorded in a [`RecordedEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedKind {
+    /// See [`ClientEvent::Connected`].
+    Connected,
+    /// See [`ClientEvent::Recv`].
+    Recv {
+        /// The serialized message.
+        msg: Vec<u8>,
+    },
+    /// See [`ClientEvent::Disconnected`].
+    Disconnected {
+        /// The error chain of the cause, formatted using
+        /// [`pretty_error`](crate::error::pretty_error).
+        cause: String,
+    },
+    /// The app sent a message to the server.
+    Send {
+        /// The serialized message.
+        msg: Vec<u8>,
+    },
+}
+
+/// Transport events and sent messages recorded by a
+/// [`client_recorder_plugin`].
+///
+/// See [`client_recorder_plugin`].
+#[derive(Derivative, Resource)]
+#[derivative(Debug(bound = ""), Clone(bound = ""), Default(bound = ""))]
+pub struct ClientRecording<P> {
+    frame: u64,
+    entries: Vec<RecordedEntry>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> P>,
+}
+
+impl<P> ClientRecording<P> {
+    /// Gets the number of the frame currently being recorded.
+    #[must_use]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Gets all entries recorded so far, in the order they were recorded.
+    #[must_use]
+    pub fn entries(&self) -> &[RecordedEntry] {
+        &self.entries
+    }
+
+    /// Discards all entries recorded so far, and restarts counting frames
+    /// from 0.
+    pub fn clear(&mut self) {
+        self.frame = 0;
+        self.entries.clear();
+    }
+
+    fn push(&mut self, kind: RecordedKind) {
+        self.entries.push(RecordedEntry {
+            frame: self.frame,
+            kind,
+        });
+    }
+
+    /// Writes this recording in a compact binary format.
+    ///
+    /// # Errors
+    ///
+    /// Errors if writing fails.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(RECORDING_MAGIC)?;
+        writer.write_all(&RECORDING_VERSION.to_be_bytes())?;
+        for entry in &self.entries {
+            let (tag, payload) = match &entry.kind {
+                RecordedKind::Connected => (TAG_CONNECTED, &[][..]),
+                RecordedKind::Recv { msg } => (TAG_RECV, msg.as_slice()),
+                RecordedKind::Disconnected { cause } => (TAG_DISCONNECTED, cause.as_bytes()),
+                RecordedKind::Send { msg } => (TAG_SEND, msg.as_slice()),
+            };
+            let len = u32::try_from(payload.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "entry too large"))?;
+            writer.write_all(&entry.frame.to_be_bytes())?;
+            writer.write_all(&[tag])?;
+            writer.write_all(&len.to_be_bytes())?;
+            writer.write_all(payload)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a recording written by [`ClientRecording::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if reading fails, or if the data is not a valid recording.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; RECORDING_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        if &magic != RECORDING_MAGIC || u32::from_be_bytes(version) != RECORDING_VERSION {
+            return Err(invalid_data("not a recording of a supported version"));
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            let mut frame = [0; 8];
+            match reader.read_exact(&mut frame) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+            let mut header = [0; 5];
+            reader.read_exact(&mut header)?;
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+            let mut payload = Vec::new();
+            reader
+                .by_ref()
+                .take(u64::from(len))
+                .read_to_end(&mut payload)?;
+            if u32::try_from(payload.len()).ok() != Some(len) {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let kind = match header[0] {
+                TAG_CONNECTED => RecordedKind::Connected,
+                TAG_RECV => RecordedKind::Recv { msg: payload },
+                TAG_DISCONNECTED => RecordedKind::Disconnected {
+                    cause: String::from_utf8(payload)
+                        .map_err(|_| invalid_data("disconnect cause is not UTF-8"))?,
+                },
+                TAG_SEND => RecordedKind::Send { msg: payload },
+                _ => return Err(invalid_data("unknown entry tag")),
+            };
+            entries.push(RecordedEntry {
+                frame: u64::from_be_bytes(frame),
+                kind,
+            });
+        }
+
+        let frame = entries.last().map_or(0, |entry| entry.frame + 1);
+        Ok(Self {
+            frame,
+            entries,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Error raised by a [`ReplayClient`].
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ReplayError {
+    /// The recorded client was disconnected with this cause.
+    #[error("{0}")]
+    Recorded(String),
+    /// A message sent to the client failed to serialize.
+    #[error("failed to serialize message")]
+    Serialize,
+}
+
+/// Info on the playback of a [`ReplayClient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReplayInfo {
+    /// Number of the recorded frame currently being replayed.
+    pub frame: u64,
+}
+
+/// Client transport which replays the events of a [`ClientRecording`].
+///
+/// Every call to [`TransportClient::recv`] replays the events of the next
+/// recorded frame, so when used in a [`TransportClientPlugin`], the same
+/// events are sent on the same frames as when the session was recorded.
+///
+/// Messages sent through this client are not sent anywhere, but are compared
+/// against the messages sent in the recording. If the app sends different
+/// messages than it did when recording, e.g. because a fix changed its
+/// behaviour, [`ReplayClient::divergence`] gives the frame at which this first
+/// happened.
+///
+/// See [`client_recorder_plugin`].
+///
+/// [`TransportClientPlugin`]: crate::TransportClientPlugin
+#[derive(Derivative, Resource)]
+#[derivative(Debug(bound = ""))]
+pub struct ReplayClient<P> {
+    events: VecDeque<RecordedEntry>,
+    sends: VecDeque<RecordedEntry>,
+    next_frame: u64,
+    frame: u64,
+    connected: bool,
+    divergence: Option<u64>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> P>,
+}
+
+impl<P> ReplayClient<P> {
+    /// Creates a client which replays the given recording from its first
+    /// frame.
+    #[must_use]
+    pub fn new(recording: ClientRecording<P>) -> Self {
+        let (sends, events) = recording
+            .entries
+            .into_iter()
+            .partition(|entry| matches!(entry.kind, RecordedKind::Send { .. }));
+        Self {
+            events,
+            sends,
+            next_frame: 0,
+            frame: 0,
+            connected: false,
+            divergence: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets if all recorded events have been replayed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Gets the first frame on which a message sent through this client did
+    /// not match the message sent on the same frame in the recording.
+    #[must_use]
+    pub fn divergence(&self) -> Option<u64> {
+        self.divergence
+    }
+}
+
+impl<P> TransportClient<P> for ReplayClient<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    const TRANSPORT_NAME: &'static str = "replay";
+
+    type Error = ReplayError;
+
+    type ConnectionInfo = ReplayInfo;
+
+    type Event = ClientEvent<P, Self>;
+
+    fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+        self.connected.then_some(ReplayInfo { frame: self.frame })
+    }
+
+    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
+        let msg = msg.into();
+        let bytes = msg.try_into_bytes().map_err(|_| ReplayError::Serialize)?;
+        let expected = self.sends.pop_front();
+        let matches = expected.is_some_and(|expected| {
+            expected.frame == self.frame
+                && matches!(&expected.kind, RecordedKind::Send { msg } if msg == bytes.as_ref())
+        });
+        if !matches && self.divergence.is_none() {
+            self.divergence = Some(self.frame);
+        }
+        Ok(())
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        self.frame = self.next_frame;
+        self.next_frame += 1;
+
+        let mut events = Vec::new();
+        while let Some(entry) = self.events.front() {
+            if entry.frame > self.frame {
+                break;
+            }
+            let Some(entry) = self.events.pop_front() else {
+                break;
+            };
+            match entry.kind {
+                RecordedKind::Connected => {
+                    self.connected = true;
+                    events.push(ClientEvent::Connected);
+                }
+                RecordedKind::Recv { msg } => {
+                    // the message was serialized by the same protocol, so this
+                    // only fails if the message types changed since recording
+                    if let Ok(msg) = P::S2C::try_from_bytes(&msg) {
+                        events.push(ClientEvent::Recv { msg });
+                    } else if self.divergence.is_none() {
+                        self.divergence = Some(self.frame);
+                    }
+                }
+                RecordedKind::Disconnected { cause } => {
+                    self.connected = false;
+                    events.push(ClientEvent::Disconnected {
+                        cause: ReplayError::Recorded(cause),
+                    });
+                }
+                RecordedKind::Send { .. } => {}
+            }
+        }
+        events.into_iter()
+    }
+
+    /// Does nothing, since the recording already contains the disconnect
+    /// raised by the original transport.
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+// systems
+
+fn record_recv<P, T>(
+    mut recording: ResMut<ClientRecording<P>>,
+    mut connected: EventReader<LocalClientConnected>,
+    mut recv: EventReader<FromServer<P>>,
+    mut disconnected: EventReader<LocalClientDisconnected<P, T>>,
+) where
+    P: TransportProtocol,
+    P::S2C: TryIntoBytes,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    // events of different kinds on the same frame are recorded in the order
+    // of a connection's lifecycle
+    for _ in connected.read() {
+        recording.push(RecordedKind::Connected);
+    }
+    for FromServer { msg } in recv.read() {
+        if let Ok(bytes) = msg.try_into_bytes() {
+            recording.push(RecordedKind::Recv {
+                msg: bytes.as_ref().to_vec(),
+            });
+        }
+    }
+    for LocalClientDisconnected { cause } in disconnected.read() {
+        recording.push(RecordedKind::Disconnected {
+            cause: crate::error::pretty_error(cause),
+        });
+    }
+}
+
+fn record_send<P>(mut recording: ResMut<ClientRecording<P>>, mut send: EventReader<ToServer<P>>)
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+{
+    for ToServer { msg } in send.read() {
+        if let Ok(bytes) = msg.try_into_bytes() {
+            recording.push(RecordedKind::Send {
+                msg: bytes.as_ref().to_vec(),
+            });
+        }
+    }
+    recording.frame += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read_recording() {
+        let mut recording = ClientRecording::<()>::default();
+        recording.push(RecordedKind::Connected);
+        recording.push(RecordedKind::Send { msg: vec![1, 2] });
+        recording.frame += 1;
+        recording.push(RecordedKind::Recv { msg: vec![3] });
+        recording.push(RecordedKind::Disconnected {
+            cause: "timed out".into(),
+        });
+
+        let mut buf = Vec::new();
+        recording.write_to(&mut buf).unwrap();
+        let read = ClientRecording::<()>::read_from(buf.as_slice()).unwrap();
+        assert_eq!(recording.entries(), read.entries());
+        assert_eq!(2, read.frame());
+
+        buf.truncate(buf.len() - 1);
+        assert!(ClientRecording::<()>::read_from(buf.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Source of the current time for a transport.
+///
+/// Transports which have time-based behaviour, such as timeouts or periodic
+/// statistics, read the time from a clock instead of calling [`Instant::now`]
+/// directly. By default this is the [`SystemClock`], but tests can swap in a
+/// [`MockClock`] to step time forward manually, and trigger this behaviour
+/// deterministically without having to actually sleep.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Gets the current time.
+    fn now(&self) -> Instant;
+}
+
+/// [`Clock`] which reads the real time using [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [`Clock`] whose time only changes when it is explicitly advanced.
+///
+/// Clones of a mock clock share the same time, so one clone can be given to a
+/// transport while the test keeps another to advance it:
+///
+/// ```
+/// # use std::{sync::Arc, time::Duration};
+/// # use aeronet::{Clock, MockClock};
+/// let clock = MockClock::new();
+/// let transport_clock: Arc<dyn Clock> = Arc::new(clock.clone());
+///
+/// let start = transport_clock.now();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(Duration::from_secs(5), transport_clock.now() - start);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// Creates a mock clock which starts at the current real time.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the time of this clock, and all of its clones, forward.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock on the time is poisoned.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().expect("lock should not be poisoned") += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("lock should not be poisoned")
+    }
+}
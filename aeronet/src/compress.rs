@@ -0,0 +1,421 @@
+use std::{
+    fmt::Debug,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use derivative::Derivative;
+
+use crate::{TryFromBytes, TryIntoBytes};
+
+/// Default value of [`ZstdConfig::LEVEL`].
+pub const DEFAULT_ZSTD_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// Default value of [`ZstdConfig::MAX_LEN`].
+pub const DEFAULT_ZSTD_MAX_LEN: usize = 1024 * 1024;
+
+/// Default maximum size in bytes of a dictionary created by
+/// [`train_dictionary`].
+pub const DEFAULT_DICTIONARY_SIZE: usize = 16 * 1024;
+
+/// A zstd dictionary prepared for both compressing and decompressing messages.
+///
+/// Small messages, such as most game messages, compress poorly on their own
+/// since there is too little data to learn any patterns from. A dictionary
+/// trained on a sample of your app's traffic (see [`train_dictionary`])
+/// contains these patterns up front, and must be the same on both sides of the
+/// connection.
+pub struct ZstdDictionary {
+    encoder: zstd::dict::EncoderDictionary<'static>,
+    decoder: zstd::dict::DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    /// Prepares a dictionary from its raw bytes, compressing at the given
+    /// level.
+    #[must_use]
+    pub fn new(dictionary: &[u8], level: i32) -> Self {
+        Self {
+            encoder: zstd::dict::EncoderDictionary::copy(dictionary, level),
+            decoder: zstd::dict::DecoderDictionary::copy(dictionary),
+        }
+    }
+}
+
+impl Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZstdDictionary").finish_non_exhaustive()
+    }
+}
+
+/// Settings used by [`Zstd<T, C>`] to compress and decompress messages.
+///
+/// This is implemented on a marker type, which is the same on both sides of
+/// the connection:
+///
+/// ```ignore
+/// use std::sync::OnceLock;
+///
+/// use aeronet::{ZstdConfig, ZstdDictionary, DEFAULT_ZSTD_LEVEL};
+///
+/// struct GameZstd;
+///
+/// impl ZstdConfig for GameZstd {
+///     fn dictionary() -> Option<&'static ZstdDictionary> {
+///         static DICT: OnceLock<ZstdDictionary> = OnceLock::new();
+///         Some(DICT.get_or_init(|| {
+///             ZstdDictionary::new(include_bytes!("game.dict"), DEFAULT_ZSTD_LEVEL)
+///         }))
+///     }
+/// }
+///
+/// type C2S = Zstd<ClientMessage, GameZstd>;
+/// ```
+pub trait ZstdConfig: Send + Sync + 'static {
+    /// Compression level used if there is no dictionary.
+    ///
+    /// If there is a dictionary, the level that it was prepared with is used.
+    const LEVEL: i32 = DEFAULT_ZSTD_LEVEL;
+
+    /// Maximum size in bytes of a decompressed message.
+    ///
+    /// Messages which decompress to more than this are rejected, so that a
+    /// peer can not make this side allocate an unbounded amount of memory.
+    const MAX_LEN: usize = DEFAULT_ZSTD_MAX_LEN;
+
+    /// Gets the dictionary used to compress and decompress messages, if any.
+    #[must_use]
+    fn dictionary() -> Option<&'static ZstdDictionary> {
+        None
+    }
+}
+
+/// [`ZstdConfig`] which compresses without a dictionary at the default level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NoDictionary;
+
+impl ZstdConfig for NoDictionary {}
+
+/// Wrapper around a message which compresses its serialized form using
+/// [`zstd`].
+///
+/// The message is first converted to bytes using its own [`TryIntoBytes`]
+/// implementation, then compressed according to the [`ZstdConfig`] `C`.
+/// Receiving does the same in reverse.
+///
+/// # Channels
+///
+/// [`Zstd<T, C>`] implements [`OnChannel`] if `T` does.
+///
+/// [`OnChannel`]: crate::OnChannel
+#[derive(Derivative)]
+#[derivative(
+    Debug(bound = "T: Debug"),
+    Clone(bound = "T: Clone"),
+    Default(bound = "T: Default"),
+    PartialEq(bound = "T: PartialEq")
+)]
+pub struct Zstd<T, C = NoDictionary> {
+    /// The wrapped message.
+    pub msg: T,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<C>,
+}
+
+impl<T, C> Zstd<T, C> {
+    /// Wraps a message.
+    #[must_use]
+    pub fn new(msg: T) -> Self {
+        Self {
+            msg,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Takes the wrapped message out of this wrapper.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.msg
+    }
+}
+
+impl<T, C> From<T> for Zstd<T, C> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T, C> std::ops::Deref for Zstd<T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.msg
+    }
+}
+
+impl<T, C> std::ops::DerefMut for Zstd<T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.msg
+    }
+}
+
+/// Error that occurs when converting a [`Zstd<T, C>`] to or from bytes.
+#[derive(Debug, thiserror::Error)]
+pub enum ZstdError<E> {
+    /// The wrapped message failed to convert to or from bytes.
+    #[error("failed to convert inner message")]
+    Inner(#[source] E),
+    /// Failed to compress or decompress the bytes.
+    #[error("failed to compress or decompress")]
+    Zstd(#[source] io::Error),
+    /// The message decompressed to more than [`ZstdConfig::MAX_LEN`] bytes.
+    #[error("decompressed message is larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+impl<T, C> TryIntoBytes for Zstd<T, C>
+where
+    T: TryIntoBytes,
+    C: ZstdConfig,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = ZstdError<T::Error>;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let bytes = self.msg.try_into_bytes().map_err(ZstdError::Inner)?;
+        let mut compressor = match C::dictionary() {
+            Some(dict) => zstd::bulk::Compressor::with_prepared_dictionary(&dict.encoder),
+            None => zstd::bulk::Compressor::new(C::LEVEL),
+        }
+        .map_err(ZstdError::Zstd)?;
+        compressor.compress(bytes.as_ref()).map_err(ZstdError::Zstd)
+    }
+}
+
+impl<T, C> TryFromBytes for Zstd<T, C>
+where
+    T: TryFromBytes,
+    C: ZstdConfig,
+{
+    type Error = ZstdError<T::Error>;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let decoder = match C::dictionary() {
+            Some(dict) => zstd::stream::read::Decoder::with_prepared_dictionary(buf, &dict.decoder),
+            None => zstd::stream::read::Decoder::with_buffer(buf),
+        }
+        .map_err(ZstdError::Zstd)?;
+
+        // read one byte past the limit, to tell if the message is too large
+        let mut bytes = Vec::new();
+        decoder
+            .take(C::MAX_LEN as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(ZstdError::Zstd)?;
+        if bytes.len() > C::MAX_LEN {
+            return Err(ZstdError::TooLarge(C::MAX_LEN));
+        }
+
+        T::try_from_bytes(&bytes)
+            .map(Self::new)
+            .map_err(ZstdError::Inner)
+    }
+}
+
+impl<T, C> crate::OnChannel for Zstd<T, C>
+where
+    T: crate::OnChannel,
+{
+    type Channel = T::Channel;
+
+    fn channel(&self) -> Self::Channel {
+        self.msg.channel()
+    }
+}
+
+// dictionary training
+
+/// Writes a dump of serialized messages, which can be used to train a
+/// dictionary using [`train_dictionary`].
+///
+/// Record the *uncompressed* bytes of the messages, e.g. the output of the
+/// inner message's [`TryIntoBytes`] implementation. Each message is written as
+/// its length as a big-endian `u32`, followed by the message bytes.
+#[derive(Debug)]
+pub struct DumpWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> DumpWriter<W> {
+    /// Creates a dump writer which writes into the given writer.
+    #[must_use]
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Records a single serialized message.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message is longer than [`u32::MAX`] bytes, or if writing
+    /// fails.
+    pub fn record(&mut self, msg: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(msg.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(msg)
+    }
+
+    /// Takes the underlying writer out of this dump writer.
+    #[must_use]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads all messages from a dump written by a [`DumpWriter`].
+///
+/// # Errors
+///
+/// Errors if reading fails, or if the dump ends in the middle of a message.
+pub fn read_dump(mut reader: impl Read) -> io::Result<Vec<Vec<u8>>> {
+    let mut msgs = Vec::new();
+    loop {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(msgs),
+            Err(err) => return Err(err),
+        }
+        let mut msg = vec![0; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut msg)?;
+        msgs.push(msg);
+    }
+}
+
+/// Trains a zstd dictionary on a sample of serialized messages, e.g. read from
+/// traffic dumps using [`read_dump`].
+///
+/// The resulting bytes can be saved to a file and loaded on both sides of the
+/// connection using [`ZstdDictionary::new`]. The dictionary is at most
+/// `max_size` bytes long; see [`DEFAULT_DICTIONARY_SIZE`].
+///
+/// zstd needs a reasonable number of samples to train on - as a rule of thumb,
+/// the total size of the samples should be about 100 times `max_size`.
+///
+/// # Errors
+///
+/// Errors if zstd fails to train a dictionary, e.g. if there are too few
+/// samples.
+pub fn train_dictionary<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, sync::OnceLock};
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Value(Vec<u8>);
+
+    impl TryIntoBytes for Value {
+        type Output<'a>
+            = &'a [u8]
+        where
+            Self: 'a;
+
+        type Error = Infallible;
+
+        fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+            Ok(&self.0)
+        }
+    }
+
+    impl TryFromBytes for Value {
+        type Error = Infallible;
+
+        fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Self(buf.to_vec()))
+        }
+    }
+
+    fn sample(i: usize) -> Vec<u8> {
+        format!(
+            "{{\"player\":{i},\"pos\":[{},{}],\"state\":\"walking\"}}",
+            i * 3,
+            i * 7
+        )
+        .into_bytes()
+    }
+
+    struct Trained;
+
+    impl ZstdConfig for Trained {
+        fn dictionary() -> Option<&'static ZstdDictionary> {
+            static DICT: OnceLock<ZstdDictionary> = OnceLock::new();
+            Some(DICT.get_or_init(|| {
+                let samples = (0..1000).map(sample).collect::<Vec<_>>();
+                let dict = train_dictionary(&samples, 1024).unwrap();
+                ZstdDictionary::new(&dict, DEFAULT_ZSTD_LEVEL)
+            }))
+        }
+    }
+
+    struct Small;
+
+    impl ZstdConfig for Small {
+        const MAX_LEN: usize = 8;
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = Zstd::<_, NoDictionary>::new(Value(sample(1)));
+        let bytes = value.try_into_bytes().unwrap();
+        let value = Zstd::<Value, NoDictionary>::try_from_bytes(&bytes).unwrap();
+        assert_eq!(Value(sample(1)), value.msg);
+    }
+
+    #[test]
+    fn round_trip_with_dictionary() {
+        let value = Zstd::<_, Trained>::new(Value(sample(5000)));
+        let with_dict = value.try_into_bytes().unwrap();
+        let without_dict = Zstd::<_, NoDictionary>::new(Value(sample(5000)))
+            .try_into_bytes()
+            .unwrap();
+        assert!(with_dict.len() < without_dict.len());
+
+        let value = Zstd::<Value, Trained>::try_from_bytes(&with_dict).unwrap();
+        assert_eq!(Value(sample(5000)), value.msg);
+    }
+
+    #[test]
+    fn reject_too_large() {
+        let bytes = Zstd::<_, NoDictionary>::new(Value(vec![0; 9]))
+            .try_into_bytes()
+            .unwrap();
+        assert!(matches!(
+            Zstd::<Value, Small>::try_from_bytes(&bytes),
+            Err(ZstdError::TooLarge(8))
+        ));
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        let mut writer = DumpWriter::new(Vec::new());
+        writer.record(b"hello").unwrap();
+        writer.record(b"").unwrap();
+        writer.record(b"world").unwrap();
+        let dump = writer.into_inner();
+
+        let msgs = read_dump(dump.as_slice()).unwrap();
+        assert_eq!(vec![b"hello".to_vec(), vec![], b"world".to_vec()], msgs);
+
+        assert!(read_dump(&dump[..dump.len() - 1]).is_err());
+    }
+}
@@ -4,7 +4,8 @@
 //! you may only have access to an error behind a shared reference. Use
 //! [`as_pretty`] to wrap that reference a [`PrettyError`], making the
 //! alternative [`fmt::Display`] impl format the entire error chain, in the
-//! same style as [`anyhow`](https://docs.rs/anyhow).
+//! same style as [`anyhow`](https://docs.rs/anyhow). If you just need the
+//! formatted chain as a string, use [`pretty_error`].
 
 use std::{error::Error, fmt};
 
@@ -42,3 +43,38 @@ where
 {
     PrettyError(err)
 }
+
+/// Formats an error and its entire [`Error::source`] chain on a single line,
+/// with each cause separated by `: `.
+///
+/// ```
+/// # use std::io;
+/// let err = io::Error::other("connection reset");
+/// assert_eq!("connection reset", aeronet::error::pretty_error(&err));
+/// ```
+pub fn pretty_error<E>(err: &E) -> String
+where
+    E: Error,
+{
+    format!("{:#}", as_pretty(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("outer")]
+    struct Outer(#[source] Middle);
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("middle")]
+    struct Middle(#[source] std::io::Error);
+
+    #[test]
+    fn full_chain() {
+        let err = Outer(Middle(std::io::Error::other("inner")));
+        assert_eq!("outer", format!("{}", as_pretty(&err)));
+        assert_eq!("outer: middle: inner", pretty_error(&err));
+    }
+}
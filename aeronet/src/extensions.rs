@@ -0,0 +1,195 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+};
+
+/// Type map of transport-specific data attached to a connection.
+///
+/// Each transport can expose data which only makes sense for that transport,
+/// such as the stable ID of a QUIC connection or the negotiated subprotocol of
+/// a WebSocket. Generic code can retrieve this data by its type, without
+/// knowing which transport it is running on:
+///
+/// ```
+/// use aeronet::Extensions;
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// struct Subprotocol(String);
+///
+/// let mut extensions = Extensions::new();
+/// extensions.insert(Subprotocol("game.v2".into()));
+///
+/// assert_eq!(
+///     Some(&Subprotocol("game.v2".into())),
+///     extensions.get::<Subprotocol>()
+/// );
+/// assert_eq!(None, extensions.get::<u64>());
+/// ```
+///
+/// At most one value of each type is stored, so transports should wrap their
+/// data in a newtype instead of inserting primitives directly.
+///
+/// See [`ConnectionExtensions`].
+///
+/// [`ConnectionExtensions`]: crate::ConnectionExtensions
+#[derive(Debug, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Extension>>,
+}
+
+/// Value which can be stored in [`Extensions`].
+trait Extension: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+
+    fn clone_boxed(&self) -> Box<dyn Extension>;
+
+    fn eq_dyn(&self, other: &dyn Extension) -> bool;
+}
+
+impl<T> Extension for T
+where
+    T: Debug + Clone + PartialEq + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn clone_boxed(&self) -> Box<dyn Extension> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn Extension) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+impl Clone for Extensions {
+    fn clone(&self) -> Self {
+        let map = self
+            .map
+            .iter()
+            .map(|(type_id, value)| (*type_id, value.clone_boxed()))
+            .collect();
+        Self { map }
+    }
+}
+
+impl PartialEq for Extensions {
+    fn eq(&self, other: &Self) -> bool {
+        self.map.len() == other.map.len()
+            && self.map.iter().all(|(type_id, value)| {
+                other
+                    .map
+                    .get(type_id)
+                    .is_some_and(|other| value.eq_dyn(&**other))
+            })
+    }
+}
+
+impl Extensions {
+    /// Creates an empty map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type if
+    /// there was one.
+    pub fn insert<T>(&mut self, value: T) -> Option<T>
+    where
+        T: Debug + Clone + PartialEq + Send + Sync + 'static,
+    {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.into_any().downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Gets a reference to the value of type `T`, if there is one.
+    #[must_use]
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.as_any().downcast_ref())
+    }
+
+    /// Gets a mutable reference to the value of type `T`, if there is one.
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.as_any_mut().downcast_mut())
+    }
+
+    /// Removes and returns the value of type `T`, if there is one.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.into_any().downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Gets if there is a value of type `T`.
+    #[must_use]
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Gets the number of values in this map.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Gets if this map has no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct StableId(usize);
+
+    #[test]
+    fn insert_get_remove() {
+        let mut extensions = Extensions::new();
+        assert_eq!(None, extensions.insert(StableId(1)));
+        assert_eq!(Some(StableId(1)), extensions.insert(StableId(2)));
+        assert_eq!(Some(&StableId(2)), extensions.get::<StableId>());
+
+        extensions.get_mut::<StableId>().unwrap().0 = 3;
+        assert_eq!(Some(StableId(3)), extensions.remove::<StableId>());
+        assert!(extensions.is_empty());
+    }
+
+    #[test]
+    fn eq_compares_values() {
+        let mut a = Extensions::new();
+        a.insert(StableId(1));
+        let mut b = a.clone();
+        assert_eq!(a, b);
+
+        b.insert(StableId(2));
+        assert_ne!(a, b);
+        b.insert(StableId(1));
+        b.insert(String::from("subprotocol"));
+        assert_ne!(a, b);
+    }
+}
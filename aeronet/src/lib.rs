@@ -2,18 +2,53 @@
 
 pub use aeronet_derive::*;
 
+pub mod budget;
 pub mod error;
+pub mod mux;
+pub mod relay;
+pub mod tick;
+pub mod voice;
 
 mod channel;
 mod client;
+mod clock;
+mod extensions;
 mod message;
+mod raw;
 mod server;
 mod transport;
+mod versioned;
 
+#[cfg(feature = "zstd")]
+mod compress;
+#[cfg(feature = "bevy")]
+mod net_error;
+#[cfg(feature = "debug_overlay")]
+mod overlay;
 #[cfg(feature = "bevy-tokio-rt")]
 mod runtime;
+#[cfg(feature = "bincode")]
+mod tagged;
 
-pub use {channel::*, client::*, message::*, server::*, transport::*};
+pub use {
+    channel::*, client::*, clock::*, extensions::*, message::*, raw::*, server::*, transport::*,
+    versioned::*,
+};
 
+#[cfg(feature = "zstd")]
+pub use compress::*;
+#[cfg(feature = "bevy")]
+pub use net_error::*;
+#[cfg(feature = "debug_overlay")]
+pub use overlay::*;
 #[cfg(feature = "bevy-tokio-rt")]
 pub use runtime::*;
+#[cfg(feature = "bincode")]
+pub use tagged::*;
+
+// used by the code generated by `aeronet_derive`
+#[cfg(feature = "bincode")]
+#[doc(hidden)]
+pub mod __private {
+    pub use bincode;
+}
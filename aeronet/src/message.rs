@@ -40,6 +40,16 @@ With the `bincode` feature enabled, this trait will automatically be implemented
 implement [`serde::Serialize`].
 "##
 )]
+#[cfg_attr(
+    feature = "prost",
+    doc = r##"
+
+# [`prost`] support
+
+With the `prost` feature enabled, this trait is implemented for [`Proto<T>`] where `T` implements
+[`prost::Message`].
+"##
+)]
 pub trait TryIntoBytes {
     /// Output type of [`TryIntoBytes::try_into_bytes`], which can be
     /// converted into a slice of bytes.
@@ -80,6 +90,16 @@ With the `bincode` feature enabled, this trait will automatically be implemented
 implement [`serde::de::DeserializeOwned`].
 "##
 )]
+#[cfg_attr(
+    feature = "prost",
+    doc = r##"
+
+# [`prost`] support
+
+With the `prost` feature enabled, this trait is implemented for [`Proto<T>`] where `T` implements
+[`prost::Message`] and [`Default`].
+"##
+)]
 pub trait TryFromBytes: Sized {
     /// Error type for [`TryFromBytes::try_from_bytes`].
     type Error: Error + Send + Sync + 'static;
@@ -92,6 +112,32 @@ pub trait TryFromBytes: Sized {
     fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error>;
 }
 
+/// Policy for what a transport does when a single message fails to be
+/// converted to or from bytes.
+///
+/// Transports which serialize messages let the user choose a policy for
+/// serialization and deserialization failures separately. See the transport's
+/// documentation for the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OnMessageError {
+    /// Disconnect the peer that the message was sent to or received from.
+    ///
+    /// Use this if a malformed message means that the peer can no longer be
+    /// trusted, e.g. because it is running an incompatible version.
+    DisconnectClient,
+    /// Discard the message and keep the connection open, without reporting
+    /// the error to the app.
+    ///
+    /// The error is still logged at the debug level.
+    DropMessage,
+    /// Discard the message and keep the connection open, but report the error
+    /// to the app.
+    ///
+    /// For received messages, the transport raises an event with the error.
+    /// For sent messages, the error is returned from the send function.
+    EmitEventOnly,
+}
+
 #[cfg(feature = "bincode")]
 impl<T> TryIntoBytes for T
 where
@@ -118,6 +164,101 @@ where
     }
 }
 
+/// Wrapper around a [`prost::Message`] which allows it to be sent across a
+/// transport using the Protocol Buffers wire format.
+///
+/// This is useful if your app already has a set of protobuf schemas (e.g. to
+/// communicate with backend services written in other languages), and you want
+/// to use the types generated by [`prost`] as your messages directly.
+///
+/// A wrapper is used instead of implementing [`TryIntoBytes`] and
+/// [`TryFromBytes`] for all [`prost::Message`] types directly, so that this can
+/// be used alongside the `bincode` feature.
+///
+/// # Channels
+///
+/// [`Proto<T>`] implements [`OnChannel`] if `T` does, so you can map proto
+/// messages to channels by deriving [`OnChannel`] on the generated types. Use
+/// `prost_build::Config::type_attribute` to add the derive and attributes to
+/// the generated code:
+///
+/// ```ignore
+/// prost_build::Config::new()
+///     .type_attribute("game.PlayerInput", "#[derive(aeronet::OnChannel)]")
+///     .type_attribute("game.PlayerInput", "#[channel_type(crate::AppChannel)]")
+///     .type_attribute("game.PlayerInput", "#[on_channel(crate::AppChannel::Input)]")
+///     .compile_protos(&["proto/game.proto"], &["proto/"])?;
+/// ```
+///
+/// [`OnChannel`]: crate::OnChannel
+#[cfg(feature = "prost")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Proto<T>(pub T);
+
+#[cfg(feature = "prost")]
+impl<T> From<T> for Proto<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<T> std::ops::Deref for Proto<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<T> std::ops::DerefMut for Proto<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<T> TryIntoBytes for Proto<T>
+where
+    T: prost::Message,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = std::convert::Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        Ok(self.0.encode_to_vec())
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<T> TryFromBytes for Proto<T>
+where
+    T: prost::Message + Default,
+{
+    type Error = prost::DecodeError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        T::decode(buf).map(Self)
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<T> crate::OnChannel for Proto<T>
+where
+    T: crate::OnChannel,
+{
+    type Channel = T::Channel;
+
+    fn channel(&self) -> Self::Channel {
+        self.0.channel()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +285,22 @@ mod tests {
         let value = Value::try_from_bytes(&bytes).unwrap();
         assert_eq!(Value { x: 4, y: -2 }, value);
     }
+
+    #[cfg(feature = "prost")]
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct ProtoValue {
+        #[prost(uint32, tag = "1")]
+        x: u32,
+        #[prost(sint32, tag = "2")]
+        y: i32,
+    }
+
+    #[test]
+    #[cfg(feature = "prost")]
+    fn prost_proto() {
+        let value = Proto(ProtoValue { x: 4, y: -2 });
+        let bytes = value.try_into_bytes().unwrap();
+        let value = Proto::<ProtoValue>::try_from_bytes(&bytes).unwrap();
+        assert_eq!(ProtoValue { x: 4, y: -2 }, value.0);
+    }
 }
@@ -0,0 +1,536 @@
+//! Multiplexing of independent sub-protocols over a single connection.
+//!
+//! Large apps often have several subsystems which talk over the network, such
+//! as chat, voice signaling and gameplay. Putting all of their messages into
+//! one giant `C2S`/`S2C` enum couples these subsystems together, and means
+//! every change to one of them touches the shared protocol.
+//!
+//! Instead, each subsystem can define its own [`SubProtocol`] with its own
+//! message types. The transport's [`TransportProtocol`] then uses
+//! [`MuxMessage`] as both its `C2S` and `S2C` type, which tags every message
+//! with the ID of the sub-protocol that it belongs to. Wrap the transport in a
+//! [`MuxServer`] or [`MuxClient`] to send messages of a specific sub-protocol
+//! using `send_for`, and receive them using `recv_for`.
+//!
+//! ```ignore
+//! struct ChatProtocol;
+//!
+//! impl SubProtocol for ChatProtocol {
+//!     const ID: u16 = 1;
+//!     type C2S = ChatMessage;
+//!     type S2C = ChatMessage;
+//! }
+//!
+//! struct AppProtocol;
+//!
+//! impl TransportProtocol for AppProtocol {
+//!     type C2S = MuxMessage<AppChannel>;
+//!     type S2C = MuxMessage<AppChannel>;
+//! }
+//!
+//! let mut server = MuxServer::new(server);
+//! server.register::<ChatProtocol>();
+//! for event in server.recv() { /* connections, disconnections */ }
+//! for (client, msg) in server.recv_for::<ChatProtocol>() { /* chat messages */ }
+//! ```
+//!
+//! # Lanes
+//!
+//! All sub-protocols share the connection's [`ChannelKey`], but each
+//! sub-protocol's messages choose their own lanes out of it using
+//! [`OnChannel`]. Give each sub-protocol its own variants of the channel key
+//! so that e.g. a burst of chat messages does not hold up gameplay messages.
+//!
+//! # Wire format
+//!
+//! A [`MuxMessage`] is encoded as a [`HEADER_LEN`]-byte header, followed by
+//! the serialized message of the sub-protocol. The header consists of the
+//! sub-protocol's [`SubProtocol::ID`] and the [`ChannelKey::index`] of the
+//! message's lane, both as big-endian `u16`s.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    error::Error,
+    fmt::Debug,
+    marker::PhantomData,
+};
+
+use derivative::Derivative;
+
+use crate::{
+    ChannelKey, ClientEvent, Message, OnChannel, ServerEvent, TransportClient, TransportProtocol,
+    TransportServer, TryFromBytes, TryIntoBytes,
+};
+
+/// Length in bytes of the header of an encoded [`MuxMessage`].
+pub const HEADER_LEN: usize = 4;
+
+/// A set of message types which is multiplexed with other sub-protocols over a
+/// single connection.
+///
+/// See the [module-level docs](self).
+pub trait SubProtocol: Send + Sync + 'static {
+    /// Unique identifier of this sub-protocol.
+    ///
+    /// Every sub-protocol used over the same connection must have a different
+    /// ID, and both sides of the connection must agree on the IDs.
+    const ID: u16;
+
+    /// The type of message sent from the client to the server.
+    type C2S: Message;
+
+    /// The type of message sent from the server to the client.
+    type S2C: Message;
+}
+
+/// A message of any [`SubProtocol`], tagged with the ID of the sub-protocol
+/// that it belongs to.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuxMessage<C> {
+    lane: C,
+    /// The encoded message, including the header.
+    bytes: Vec<u8>,
+}
+
+impl<C> MuxMessage<C>
+where
+    C: ChannelKey,
+{
+    /// Creates a message of the sub-protocol with the given ID from an already
+    /// serialized payload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index of `lane` does not fit into a `u16`.
+    #[must_use]
+    pub fn new(id: u16, lane: C, payload: &[u8]) -> Self {
+        let lane_index =
+            u16::try_from(lane.index()).expect("channel key should have at most 65536 variants");
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&id.to_be_bytes());
+        bytes.extend_from_slice(&lane_index.to_be_bytes());
+        bytes.extend_from_slice(payload);
+        Self { lane, bytes }
+    }
+
+    /// Serializes a message of the sub-protocol `S`, sending it on the lane
+    /// given by its [`OnChannel`] impl.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message could not be serialized.
+    pub fn encode<S, M>(msg: &M) -> Result<Self, M::Error>
+    where
+        S: SubProtocol,
+        M: TryIntoBytes + OnChannel<Channel = C>,
+    {
+        let payload = msg.try_into_bytes()?;
+        Ok(Self::new(S::ID, msg.channel(), payload.as_ref()))
+    }
+
+    /// Gets the ID of the sub-protocol that this message belongs to.
+    #[must_use]
+    pub fn id(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[0], self.bytes[1]])
+    }
+
+    /// Gets the lane that this message is sent on.
+    #[must_use]
+    pub fn lane(&self) -> &C {
+        &self.lane
+    }
+
+    /// Gets the serialized message of the sub-protocol.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.bytes[HEADER_LEN..]
+    }
+
+    /// Deserializes the message of the sub-protocol.
+    ///
+    /// This does not check that this message actually belongs to the
+    /// sub-protocol that `M` is from.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message could not be deserialized.
+    pub fn decode<M>(&self) -> Result<M, M::Error>
+    where
+        M: TryFromBytes,
+    {
+        M::try_from_bytes(self.payload())
+    }
+}
+
+impl<C> TryIntoBytes for MuxMessage<C> {
+    type Output<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    type Error = Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        Ok(&self.bytes)
+    }
+}
+
+impl<C> TryFromBytes for MuxMessage<C>
+where
+    C: ChannelKey,
+{
+    type Error = MuxDecodeError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let Some(header) = buf.get(..HEADER_LEN) else {
+            return Err(MuxDecodeError::TooShort(buf.len()));
+        };
+        let lane_index = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        let lane = C::ALL
+            .get(lane_index)
+            .ok_or(MuxDecodeError::InvalidLane(lane_index))?
+            .clone();
+        Ok(Self {
+            lane,
+            bytes: buf.to_vec(),
+        })
+    }
+}
+
+impl<C> OnChannel for MuxMessage<C>
+where
+    C: ChannelKey,
+{
+    type Channel = C;
+
+    fn channel(&self) -> Self::Channel {
+        self.lane.clone()
+    }
+}
+
+/// Error that occurs when decoding a [`MuxMessage`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MuxDecodeError {
+    /// The message was too short to contain a header.
+    #[error("message of {0} bytes is too short to contain a header")]
+    TooShort(usize),
+    /// The header referred to a lane which does not exist.
+    #[error("no lane with index {0}")]
+    InvalidLane(usize),
+}
+
+/// Error that occurs when sending a message of a [`SubProtocol`].
+#[derive(Debug, thiserror::Error)]
+pub enum MuxSendError<S, E>
+where
+    S: Error + 'static,
+{
+    /// Failed to serialize the message.
+    #[error("failed to serialize message")]
+    Serialize(#[source] S),
+    /// The underlying transport failed to send the message.
+    #[error("failed to send message")]
+    Transport(E),
+}
+
+/// Result of decoding a received message of type `M`.
+type DecodeResult<M> = Result<M, <M as TryFromBytes>::Error>;
+
+/// Messages received from clients which are waiting to be taken out by a
+/// single sub-protocol.
+type MuxQueue<K, C> = VecDeque<(K, MuxMessage<C>)>;
+
+/// Wrapper around a [`TransportServer`] which routes received messages by the
+/// [`SubProtocol`] that they belong to.
+///
+/// See the [module-level docs](self).
+#[derive(Derivative)]
+#[derivative(Debug(bound = "T: Debug"), Default(bound = "T: Default"))]
+pub struct MuxServer<P, T, C>
+where
+    P: TransportProtocol<C2S = MuxMessage<C>, S2C = MuxMessage<C>>,
+    T: TransportServer<P>,
+    C: ChannelKey,
+{
+    inner: T,
+    #[derivative(Debug = "ignore")]
+    queues: HashMap<u16, MuxQueue<T::Client, C>>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<P>,
+}
+
+impl<P, T, C> MuxServer<P, T, C>
+where
+    P: TransportProtocol<C2S = MuxMessage<C>, S2C = MuxMessage<C>>,
+    T: TransportServer<P>,
+    C: ChannelKey,
+{
+    /// Wraps a server, with no sub-protocols registered.
+    #[must_use]
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            queues: HashMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets a reference to the wrapped server.
+    #[must_use]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the wrapped server.
+    ///
+    /// Events must not be received from the wrapped server directly, as they
+    /// would bypass the routing done by [`MuxServer::recv`].
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the wrapped server, dropping all messages which have not been
+    /// taken out using [`MuxServer::recv_for`] yet.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Starts routing messages of the sub-protocol `S` into their own queue,
+    /// which can be drained using [`MuxServer::recv_for`].
+    ///
+    /// Messages of sub-protocols which are not registered are raised as
+    /// regular [`ServerEvent::Recv`] events from [`MuxServer::recv`] instead.
+    pub fn register<S: SubProtocol>(&mut self) {
+        self.queues.entry(S::ID).or_default();
+    }
+
+    /// Sends a message of the sub-protocol `S` to a client.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message could not be serialized, or if the wrapped server
+    /// failed to send it.
+    pub fn send_for<S>(
+        &mut self,
+        client: T::Client,
+        msg: impl Into<S::S2C>,
+    ) -> Result<(), MuxSendError<<S::S2C as TryIntoBytes>::Error, T::Error>>
+    where
+        S: SubProtocol,
+        S::S2C: TryIntoBytes + OnChannel<Channel = C>,
+    {
+        let msg = MuxMessage::encode::<S, _>(&msg.into()).map_err(MuxSendError::Serialize)?;
+        self.inner
+            .send(client, msg)
+            .map_err(MuxSendError::Transport)
+    }
+
+    /// Polls events from the wrapped server, routing received messages of
+    /// registered sub-protocols into their queues.
+    ///
+    /// All other events are returned.
+    pub fn recv(&mut self) -> impl Iterator<Item = ServerEvent<P, T>> + '_ {
+        self.inner
+            .recv()
+            .filter_map(Into::<Option<ServerEvent<P, T>>>::into)
+            .filter_map(|event| match event {
+                ServerEvent::Recv { client, msg } => match self.queues.get_mut(&msg.id()) {
+                    Some(queue) => {
+                        queue.push_back((client, msg));
+                        None
+                    }
+                    None => Some(ServerEvent::Recv { client, msg }),
+                },
+                event => Some(event),
+            })
+    }
+
+    /// Takes all received messages of the sub-protocol `S` out of its queue,
+    /// deserializing them.
+    ///
+    /// Messages are only routed into the queue when [`MuxServer::recv`] is
+    /// called, and only if `S` has been [registered](MuxServer::register).
+    pub fn recv_for<S>(&mut self) -> impl Iterator<Item = (T::Client, DecodeResult<S::C2S>)> + '_
+    where
+        S: SubProtocol,
+        S::C2S: TryFromBytes,
+    {
+        self.queues
+            .get_mut(&S::ID)
+            .into_iter()
+            .flat_map(|queue| queue.drain(..))
+            .map(|(client, msg)| (client, msg.decode::<S::C2S>()))
+    }
+}
+
+/// Wrapper around a [`TransportClient`] which routes received messages by the
+/// [`SubProtocol`] that they belong to.
+///
+/// See the [module-level docs](self).
+#[derive(Derivative)]
+#[derivative(Debug(bound = "T: Debug"), Default(bound = "T: Default"))]
+pub struct MuxClient<P, T, C>
+where
+    P: TransportProtocol<C2S = MuxMessage<C>, S2C = MuxMessage<C>>,
+    T: TransportClient<P>,
+    C: ChannelKey,
+{
+    inner: T,
+    #[derivative(Debug = "ignore")]
+    queues: HashMap<u16, VecDeque<MuxMessage<C>>>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<P>,
+}
+
+impl<P, T, C> MuxClient<P, T, C>
+where
+    P: TransportProtocol<C2S = MuxMessage<C>, S2C = MuxMessage<C>>,
+    T: TransportClient<P>,
+    C: ChannelKey,
+{
+    /// Wraps a client, with no sub-protocols registered.
+    #[must_use]
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            queues: HashMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets a reference to the wrapped client.
+    #[must_use]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the wrapped client.
+    ///
+    /// Events must not be received from the wrapped client directly, as they
+    /// would bypass the routing done by [`MuxClient::recv`].
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the wrapped client, dropping all messages which have not been
+    /// taken out using [`MuxClient::recv_for`] yet.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Starts routing messages of the sub-protocol `S` into their own queue.
+    ///
+    /// See [`MuxServer::register`].
+    pub fn register<S: SubProtocol>(&mut self) {
+        self.queues.entry(S::ID).or_default();
+    }
+
+    /// Sends a message of the sub-protocol `S` to the server.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message could not be serialized, or if the wrapped client
+    /// failed to send it.
+    pub fn send_for<S>(
+        &mut self,
+        msg: impl Into<S::C2S>,
+    ) -> Result<(), MuxSendError<<S::C2S as TryIntoBytes>::Error, T::Error>>
+    where
+        S: SubProtocol,
+        S::C2S: TryIntoBytes + OnChannel<Channel = C>,
+    {
+        let msg = MuxMessage::encode::<S, _>(&msg.into()).map_err(MuxSendError::Serialize)?;
+        self.inner.send(msg).map_err(MuxSendError::Transport)
+    }
+
+    /// Polls events from the wrapped client, routing received messages of
+    /// registered sub-protocols into their queues.
+    ///
+    /// See [`MuxServer::recv`].
+    pub fn recv(&mut self) -> impl Iterator<Item = ClientEvent<P, T>> + '_ {
+        self.inner
+            .recv()
+            .filter_map(Into::<Option<ClientEvent<P, T>>>::into)
+            .filter_map(|event| match event {
+                ClientEvent::Recv { msg } => match self.queues.get_mut(&msg.id()) {
+                    Some(queue) => {
+                        queue.push_back(msg);
+                        None
+                    }
+                    None => Some(ClientEvent::Recv { msg }),
+                },
+                event => Some(event),
+            })
+    }
+
+    /// Takes all received messages of the sub-protocol `S` out of its queue,
+    /// deserializing them.
+    ///
+    /// See [`MuxServer::recv_for`].
+    pub fn recv_for<S>(&mut self) -> impl Iterator<Item = DecodeResult<S::S2C>> + '_
+    where
+        S: SubProtocol,
+        S::S2C: TryFromBytes,
+    {
+        self.queues
+            .get_mut(&S::ID)
+            .into_iter()
+            .flat_map(|queue| queue.drain(..))
+            .map(|msg| msg.decode::<S::S2C>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChannelKind;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Lane {
+        A,
+        B,
+    }
+
+    unsafe impl ChannelKey for Lane {
+        const ALL: &'static [Self] = &[Self::A, Self::B];
+
+        fn index(&self) -> usize {
+            match self {
+                Self::A => 0,
+                Self::B => 1,
+            }
+        }
+
+        fn kind(&self) -> ChannelKind {
+            ChannelKind::ReliableOrdered
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let msg = MuxMessage::new(7, Lane::B, b"hello");
+        let bytes = msg.try_into_bytes().unwrap().to_vec();
+        let msg = MuxMessage::<Lane>::try_from_bytes(&bytes).unwrap();
+        assert_eq!(7, msg.id());
+        assert_eq!(&Lane::B, msg.lane());
+        assert_eq!(b"hello", msg.payload());
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(
+            Err(MuxDecodeError::TooShort(3)),
+            MuxMessage::<Lane>::try_from_bytes(&[0, 1, 0])
+        );
+        assert_eq!(
+            Err(MuxDecodeError::InvalidLane(2)),
+            MuxMessage::<Lane>::try_from_bytes(&[0, 1, 0, 2])
+        );
+    }
+}
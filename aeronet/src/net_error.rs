@@ -0,0 +1,122 @@
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{TransportClient, TransportServer};
+
+/// How the transport plugins handle an error returned by an operation on the
+/// transport, e.g. sending a message while not connected.
+///
+/// Insert this as a resource to change the policy of the
+/// [`TransportClientPlugin`] and [`TransportServerPlugin`]. If no policy is
+/// inserted, [`NetworkErrorPolicy::Event`] is used.
+///
+/// [`TransportClientPlugin`]: crate::TransportClientPlugin
+/// [`TransportServerPlugin`]: crate::TransportServerPlugin
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Resource)]
+pub enum NetworkErrorPolicy {
+    /// The error is discarded.
+    Ignore,
+    /// The error is logged as a warning.
+    Log,
+    /// A [`NetworkError`] event is sent, which can be read as a
+    /// [`ClientNetworkError`] or [`ServerNetworkError`].
+    #[default]
+    Event,
+    /// The app panics in debug builds, and the error is logged as a warning in
+    /// release builds.
+    ///
+    /// Use this to catch errors which should never happen, e.g. sending a
+    /// message to a client which is known to be connected.
+    Panic,
+}
+
+impl NetworkErrorPolicy {
+    pub(crate) fn handle<O, E>(
+        self,
+        errors: &mut EventWriter<NetworkError<O, E>>,
+        transport: &str,
+        op: O,
+        error: E,
+    ) where
+        O: fmt::Display + Send + Sync + 'static,
+        E: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Self::Ignore => {}
+            Self::Event => errors.send(NetworkError { op, error }),
+            Self::Panic if cfg!(debug_assertions) => {
+                panic!("Failed to {op} on {transport} transport: {error}")
+            }
+            Self::Log | Self::Panic => warn!("Failed to {op} on {transport} transport: {error}"),
+        }
+    }
+}
+
+/// An operation requested by the app through a transport plugin failed.
+///
+/// This is only sent if the [`NetworkErrorPolicy`] is
+/// [`NetworkErrorPolicy::Event`]. Read this using the [`ClientNetworkError`]
+/// or [`ServerNetworkError`] alias.
+#[derive(Debug, Clone, Event)]
+pub struct NetworkError<O, E> {
+    /// The operation which failed.
+    pub op: O,
+    /// The error returned by the transport.
+    pub error: E,
+}
+
+/// [`NetworkError`] sent by a [`TransportClientPlugin`].
+///
+/// [`TransportClientPlugin`]: crate::TransportClientPlugin
+pub type ClientNetworkError<P, T> = NetworkError<ClientOp, <T as TransportClient<P>>::Error>;
+
+/// [`NetworkError`] sent by a [`TransportServerPlugin`].
+///
+/// [`TransportServerPlugin`]: crate::TransportServerPlugin
+pub type ServerNetworkError<P, T> =
+    NetworkError<ServerOp<<T as TransportServer<P>>::Client>, <T as TransportServer<P>>::Error>;
+
+/// Operation on a [`TransportClient`] which can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientOp {
+    /// Sending a [`ToServer`](crate::ToServer) message.
+    Send,
+    /// Disconnecting because of a
+    /// [`DisconnectLocalClient`](crate::DisconnectLocalClient).
+    Disconnect,
+}
+
+impl fmt::Display for ClientOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Send => write!(f, "send message"),
+            Self::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// Operation on a [`TransportServer`] which can fail.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ServerOp<K> {
+    /// Sending a [`ToClient`](crate::ToClient) message.
+    Send {
+        /// The key of the client which the message was sent to.
+        client: K,
+    },
+    /// Disconnecting a client because of a
+    /// [`DisconnectRemoteClient`](crate::DisconnectRemoteClient).
+    Disconnect {
+        /// The key of the client to disconnect.
+        client: K,
+    },
+}
+
+impl<K> fmt::Display for ServerOp<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Send { .. } => write!(f, "send message to client"),
+            Self::Disconnect { .. } => write!(f, "disconnect client"),
+        }
+    }
+}
@@ -0,0 +1,532 @@
+// bevy systems take their resources by value
+#![allow(clippy::needless_pass_by_value)]
+
+use std::{
+    collections::VecDeque, error::Error, fmt::Debug, marker::PhantomData, mem, time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use derivative::Derivative;
+
+use crate::{
+    error::as_pretty, ChannelKey, FromClient, FromServer, LocalClientConnected,
+    LocalClientDisconnected, OnChannel, RemoteClientConnected, RemoteClientDisconnected, Rtt,
+    ToClient, ToServer, TransportClient, TransportClientSet, TransportProtocol, TransportServer,
+    TransportServerSet,
+};
+
+/// Settings for the windows drawn by [`ClientDebugOverlayPlugin`] and
+/// [`ServerDebugOverlayPlugin`].
+///
+/// This is shared between all overlay plugins in the app, so a single key
+/// toggles all of them.
+#[derive(Debug, Clone, Resource)]
+pub struct DebugOverlayConfig {
+    /// Key which shows or hides the overlay.
+    pub toggle_key: KeyCode,
+    /// Whether the overlay is currently shown.
+    pub visible: bool,
+    /// How often a point is added to the graphs.
+    pub sample_interval: Duration,
+    /// Maximum number of points in each graph.
+    pub history_len: usize,
+    /// Maximum number of entries in the event log.
+    pub log_len: usize,
+}
+
+impl Default for DebugOverlayConfig {
+    fn default() -> Self {
+        Self {
+            toggle_key: KeyCode::F3,
+            visible: false,
+            sample_interval: Duration::from_secs(1),
+            history_len: 60,
+            log_len: 100,
+        }
+    }
+}
+
+/// Draws an [`egui`] window showing the state of a [`TransportClient`].
+///
+/// The window shows whether the client is connected, the rate of messages sent
+/// and received, and a log of recent connection events. Use
+/// [`ClientDebugOverlayPlugin::with_rtt`] and
+/// [`ClientDebugOverlayPlugin::with_lanes`] to also graph the round-trip time,
+/// and split the sent messages by lane, if the transport and protocol support
+/// it.
+///
+/// This only observes the events of the [`TransportClientPlugin`], so it works
+/// for any transport, and the plugin must be added alongside it. The overlay is
+/// toggled using [`DebugOverlayConfig::toggle_key`].
+///
+/// [`TransportClientPlugin`]: crate::TransportClientPlugin
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct ClientDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportClient<P>,
+{
+    #[derivative(Debug = "ignore")]
+    rtt: Option<fn(&T::ConnectionInfo) -> Duration>,
+    lanes: Option<Lanes<P::C2S>>,
+}
+
+impl<P, T> ClientDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportClient<P>,
+{
+    /// Graphs the round-trip time of the connection.
+    #[must_use]
+    pub fn with_rtt(mut self) -> Self
+    where
+        T::ConnectionInfo: Rtt,
+    {
+        self.rtt = Some(rtt_of::<T::ConnectionInfo>);
+        self
+    }
+
+    /// Graphs the messages sent on each lane separately.
+    #[must_use]
+    pub fn with_lanes(mut self) -> Self
+    where
+        P::C2S: OnChannel,
+    {
+        self.lanes = Some(Lanes::of());
+        self
+    }
+}
+
+impl<P, T> Plugin for ClientDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    fn build(&self, app: &mut App) {
+        add_common(app);
+        app.insert_resource(ClientOverlay::<P, T> {
+            history: History::new(self.lanes.as_ref()),
+            rtt: self.rtt,
+            lane_of: self.lanes.as_ref().map(|lanes| lanes.index),
+            _phantom: PhantomData,
+        })
+        .add_systems(
+            PostUpdate,
+            collect_client::<P, T>.before(TransportClientSet::Send),
+        )
+        .add_systems(Update, draw_client::<P, T>.run_if(overlay_visible));
+    }
+}
+
+/// Draws an [`egui`] window showing the state of a [`TransportServer`].
+///
+/// The window shows the connected clients, the rate of messages sent and
+/// received, and a log of recent connection events. Use
+/// [`ServerDebugOverlayPlugin::with_rtt`] and
+/// [`ServerDebugOverlayPlugin::with_lanes`] to also graph the average
+/// round-trip time of the clients, and split the sent messages by lane, if the
+/// transport and protocol support it.
+///
+/// See [`ClientDebugOverlayPlugin`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct ServerDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    #[derivative(Debug = "ignore")]
+    rtt: Option<fn(&T::ConnectionInfo) -> Duration>,
+    lanes: Option<Lanes<P::S2C>>,
+}
+
+impl<P, T> ServerDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    /// Graphs the average round-trip time of the connected clients, and shows
+    /// the round-trip time of each client.
+    #[must_use]
+    pub fn with_rtt(mut self) -> Self
+    where
+        T::ConnectionInfo: Rtt,
+    {
+        self.rtt = Some(rtt_of::<T::ConnectionInfo>);
+        self
+    }
+
+    /// Graphs the messages sent on each lane separately.
+    #[must_use]
+    pub fn with_lanes(mut self) -> Self
+    where
+        P::S2C: OnChannel,
+    {
+        self.lanes = Some(Lanes::of());
+        self
+    }
+}
+
+impl<P, T> Plugin for ServerDebugOverlayPlugin<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P> + Resource,
+    T::Client: Debug,
+    T::Error: Error,
+{
+    fn build(&self, app: &mut App) {
+        add_common(app);
+        app.insert_resource(ServerOverlay::<P, T> {
+            history: History::new(self.lanes.as_ref()),
+            rtt: self.rtt,
+            lane_of: self.lanes.as_ref().map(|lanes| lanes.index),
+            _phantom: PhantomData,
+        })
+        .add_systems(
+            PostUpdate,
+            collect_server::<P, T>.before(TransportServerSet::Send),
+        )
+        .add_systems(Update, draw_server::<P, T>.run_if(overlay_visible));
+    }
+}
+
+fn add_common(app: &mut App) {
+    if !app.is_plugin_added::<EguiPlugin>() {
+        app.add_plugins(EguiPlugin);
+    }
+    // only add the toggle once, otherwise each overlay would flip it back
+    if !app.world.contains_resource::<DebugOverlayConfig>() {
+        app.init_resource::<DebugOverlayConfig>().add_systems(
+            Update,
+            toggle_overlay.run_if(resource_exists::<Input<KeyCode>>()),
+        );
+    }
+}
+
+fn rtt_of<I: Rtt>(info: &I) -> Duration {
+    info.rtt()
+}
+
+fn lane_of<M: OnChannel>(msg: &M) -> usize {
+    msg.channel().index()
+}
+
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+struct Lanes<M> {
+    names: Vec<String>,
+    #[derivative(Debug = "ignore")]
+    index: fn(&M) -> usize,
+}
+
+impl<M: OnChannel> Lanes<M> {
+    fn of() -> Self {
+        Self {
+            names: M::Channel::ALL
+                .iter()
+                .map(|lane| format!("{lane:?}"))
+                .collect(),
+            index: lane_of::<M>,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ClientOverlay<P, T>
+where
+    P: TransportProtocol,
+    T: TransportClient<P>,
+{
+    history: History,
+    rtt: Option<fn(&T::ConnectionInfo) -> Duration>,
+    lane_of: Option<fn(&P::C2S) -> usize>,
+    _phantom: PhantomData<(P, T)>,
+}
+
+#[derive(Resource)]
+struct ServerOverlay<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    history: History,
+    rtt: Option<fn(&T::ConnectionInfo) -> Duration>,
+    lane_of: Option<fn(&P::S2C) -> usize>,
+    _phantom: PhantomData<(P, T)>,
+}
+
+/// Samples of the statistics shown in an overlay.
+#[derive(Debug)]
+struct History {
+    lane_names: Vec<String>,
+    /// Messages sent on each lane since the last sample.
+    sent: Vec<u32>,
+    /// Messages received since the last sample.
+    recv: u32,
+    since_sample: Duration,
+    /// Messages sent per second on each lane.
+    sent_history: Vec<VecDeque<f64>>,
+    /// Messages received per second.
+    recv_history: VecDeque<f64>,
+    /// Round-trip time in milliseconds.
+    rtt_history: VecDeque<f64>,
+    log: VecDeque<String>,
+}
+
+impl History {
+    fn new<M>(lanes: Option<&Lanes<M>>) -> Self {
+        let lane_names = lanes.map_or_else(|| vec!["All".to_owned()], |lanes| lanes.names.clone());
+        Self {
+            sent: vec![0; lane_names.len()],
+            sent_history: vec![VecDeque::new(); lane_names.len()],
+            lane_names,
+            recv: 0,
+            since_sample: Duration::ZERO,
+            recv_history: VecDeque::new(),
+            rtt_history: VecDeque::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    fn on_sent(&mut self, lane: usize) {
+        if let Some(sent) = self.sent.get_mut(lane) {
+            *sent = sent.saturating_add(1);
+        }
+    }
+
+    fn on_recv(&mut self) {
+        self.recv = self.recv.saturating_add(1);
+    }
+
+    fn log(&mut self, config: &DebugOverlayConfig, time: &Time, entry: impl Into<String>) {
+        let entry = format!("[{:>8.2}] {}", time.elapsed_seconds(), entry.into());
+        push_bounded(&mut self.log, entry, config.log_len);
+    }
+
+    fn tick(&mut self, config: &DebugOverlayConfig, delta: Duration, rtt: Option<Duration>) {
+        self.since_sample += delta;
+        if self.since_sample < config.sample_interval {
+            return;
+        }
+        let secs = mem::take(&mut self.since_sample).as_secs_f64();
+
+        for (sent, history) in self.sent.iter_mut().zip(&mut self.sent_history) {
+            let rate = f64::from(mem::take(sent)) / secs;
+            push_bounded(history, rate, config.history_len);
+        }
+        let rate = f64::from(mem::take(&mut self.recv)) / secs;
+        push_bounded(&mut self.recv_history, rate, config.history_len);
+        if let Some(rtt) = rtt {
+            let millis = rtt.as_secs_f64() * 1000.0;
+            push_bounded(&mut self.rtt_history, millis, config.history_len);
+        }
+    }
+
+    fn show(&self, ui: &mut egui::Ui, history_len: usize) {
+        if let Some(rtt) = self.rtt_history.back() {
+            ui.label(format!("RTT: {rtt:.1} ms"));
+            sparkline(ui, &self.rtt_history, history_len);
+        }
+
+        ui.separator();
+        for (name, history) in self.lane_names.iter().zip(&self.sent_history) {
+            let rate = history.back().copied().unwrap_or_default();
+            ui.label(format!("Sent on {name}: {rate:.1} msg/s"));
+            sparkline(ui, history, history_len);
+        }
+        let rate = self.recv_history.back().copied().unwrap_or_default();
+        ui.label(format!("Received: {rate:.1} msg/s"));
+        sparkline(ui, &self.recv_history, history_len);
+
+        ui.separator();
+        ui.collapsing("Event log", |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in &self.log {
+                        ui.monospace(entry);
+                    }
+                });
+        });
+    }
+}
+
+fn push_bounded<T>(buf: &mut VecDeque<T>, value: T, len: usize) {
+    buf.push_back(value);
+    while buf.len() > len {
+        buf.pop_front();
+    }
+}
+
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn sparkline(ui: &mut egui::Ui, values: &VecDeque<f64>, len: usize) {
+    let size = egui::vec2(ui.available_width(), 40.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(rect, 0.0, ui.visuals().widgets.noninteractive.bg_stroke);
+
+    let max = values.iter().copied().fold(1.0, f64::max);
+    let step = rect.width() / len.saturating_sub(1).max(1) as f32;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let y = (value / max) as f32 * rect.height();
+            egui::pos2(rect.left() + i as f32 * step, rect.bottom() - y)
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, ui.visuals().text_color()),
+    ));
+}
+
+// systems
+
+fn toggle_overlay(keys: Res<Input<KeyCode>>, mut config: ResMut<DebugOverlayConfig>) {
+    if keys.just_pressed(config.toggle_key) {
+        config.visible = !config.visible;
+    }
+}
+
+fn overlay_visible(config: Res<DebugOverlayConfig>) -> bool {
+    config.visible
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_client<P, T>(
+    config: Res<DebugOverlayConfig>,
+    time: Res<Time>,
+    client: Res<T>,
+    mut overlay: ResMut<ClientOverlay<P, T>>,
+    mut connected: EventReader<LocalClientConnected>,
+    mut recv: EventReader<FromServer<P>>,
+    mut disconnected: EventReader<LocalClientDisconnected<P, T>>,
+    mut send: EventReader<ToServer<P>>,
+) where
+    P: TransportProtocol,
+    T: TransportClient<P> + Resource,
+    T::Error: Error,
+{
+    let overlay = &mut *overlay;
+    for _ in connected.read() {
+        overlay.history.log(&config, &time, "Connected");
+    }
+    for LocalClientDisconnected { cause } in disconnected.read() {
+        let entry = format!("Disconnected: {:#}", as_pretty(cause));
+        overlay.history.log(&config, &time, entry);
+    }
+    for _ in recv.read() {
+        overlay.history.on_recv();
+    }
+    for ToServer { msg } in send.read() {
+        let lane = overlay.lane_of.map_or(0, |lane_of| lane_of(msg));
+        overlay.history.on_sent(lane);
+    }
+
+    let rtt = overlay
+        .rtt
+        .and_then(|rtt| client.connection_info().map(|info| rtt(&info)));
+    overlay.history.tick(&config, time.delta(), rtt);
+}
+
+fn draw_client<P, T>(
+    mut egui: EguiContexts,
+    config: Res<DebugOverlayConfig>,
+    client: Res<T>,
+    overlay: Res<ClientOverlay<P, T>>,
+) where
+    P: TransportProtocol,
+    T: TransportClient<P> + Resource,
+{
+    egui::Window::new(format!("Client ({})", T::TRANSPORT_NAME)).show(egui.ctx_mut(), |ui| {
+        ui.label(if client.connected() {
+            "Connected"
+        } else {
+            "Not connected"
+        });
+        overlay.history.show(ui, config.history_len);
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_server<P, T>(
+    config: Res<DebugOverlayConfig>,
+    time: Res<Time>,
+    server: Res<T>,
+    mut overlay: ResMut<ServerOverlay<P, T>>,
+    mut connected: EventReader<RemoteClientConnected<P, T>>,
+    mut recv: EventReader<FromClient<P, T>>,
+    mut disconnected: EventReader<RemoteClientDisconnected<P, T>>,
+    mut send: EventReader<ToClient<P, T>>,
+) where
+    P: TransportProtocol,
+    T: TransportServer<P> + Resource,
+    T::Client: Debug,
+    T::Error: Error,
+{
+    let overlay = &mut *overlay;
+    for RemoteClientConnected { client } in connected.read() {
+        overlay
+            .history
+            .log(&config, &time, format!("{client:?} connected"));
+    }
+    for RemoteClientDisconnected { client, cause } in disconnected.read() {
+        let entry = format!("{client:?} disconnected: {:#}", as_pretty(cause));
+        overlay.history.log(&config, &time, entry);
+    }
+    for _ in recv.read() {
+        overlay.history.on_recv();
+    }
+    for ToClient { msg, .. } in send.read() {
+        let lane = overlay.lane_of.map_or(0, |lane_of| lane_of(msg));
+        overlay.history.on_sent(lane);
+    }
+
+    let rtt = overlay.rtt.and_then(|rtt| {
+        let rtts = server
+            .connected_clients()
+            .filter_map(|client| server.connection_info(client))
+            .map(|info| rtt(&info))
+            .collect::<Vec<_>>();
+        let count = u32::try_from(rtts.len()).ok().filter(|count| *count > 0)?;
+        Some(rtts.into_iter().sum::<Duration>() / count)
+    });
+    overlay.history.tick(&config, time.delta(), rtt);
+}
+
+fn draw_server<P, T>(
+    mut egui: EguiContexts,
+    config: Res<DebugOverlayConfig>,
+    server: Res<T>,
+    overlay: Res<ServerOverlay<P, T>>,
+) where
+    P: TransportProtocol,
+    T: TransportServer<P> + Resource,
+    T::Client: Debug,
+{
+    egui::Window::new(format!("Server ({})", T::TRANSPORT_NAME)).show(egui.ctx_mut(), |ui| {
+        let clients = server.connected_clients().collect::<Vec<_>>();
+        ui.collapsing(format!("Clients: {}", clients.len()), |ui| {
+            for client in clients {
+                let rtt = overlay.rtt.and_then(|rtt| {
+                    server
+                        .connection_info(client.clone())
+                        .map(|info| rtt(&info))
+                });
+                match rtt {
+                    Some(rtt) => {
+                        let millis = rtt.as_secs_f64() * 1000.0;
+                        ui.label(format!("{client:?}: {millis:.1} ms"))
+                    }
+                    None => ui.label(format!("{client:?}")),
+                };
+            }
+        });
+        overlay.history.show(ui, config.history_len);
+    });
+}
@@ -0,0 +1,109 @@
+use std::{convert::Infallible, ops::Deref};
+
+use crate::{ChannelKey, OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Outgoing message whose bytes are sent as-is on a lane chosen per message,
+/// for apps which bring their own serialization.
+///
+/// Apps which already have their own serialization or replication stack can
+/// use this as the outgoing message type of a protocol, so that the transport
+/// only manages the connection and its lanes. Its payload is passed through
+/// without being copied into another message type, and can never fail to
+/// serialize.
+///
+/// Since incoming messages are received without any info on what lane they
+/// were sent on (see [`OnChannel`]), the receiving side of a protocol uses
+/// [`RawPayload`] instead. If the receiver needs to know the lane, it has to
+/// be encoded into the payload by the app.
+///
+/// ```
+/// use aeronet::{ChannelKey, OnChannel, RawMessage, TryIntoBytes};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChannelKey)]
+/// enum Lane {
+///     #[channel_kind(Unreliable)]
+///     State,
+///     #[channel_kind(ReliableOrdered)]
+///     Events,
+/// }
+///
+/// let msg = RawMessage::new(Lane::Events, vec![1, 2, 3]);
+/// assert_eq!(Lane::Events, msg.channel());
+/// assert_eq!(&[1, 2, 3], msg.try_into_bytes().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawMessage<C> {
+    /// The lane which this message is sent on.
+    pub lane: C,
+    /// The bytes which are sent.
+    pub payload: Vec<u8>,
+}
+
+impl<C> RawMessage<C> {
+    /// Creates a message which sends `payload` on `lane`.
+    pub fn new(lane: C, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            lane,
+            payload: payload.into(),
+        }
+    }
+}
+
+impl<C> TryIntoBytes for RawMessage<C> {
+    type Output<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    type Error = Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        Ok(&self.payload)
+    }
+}
+
+impl<C: ChannelKey> OnChannel for RawMessage<C> {
+    type Channel = C;
+
+    fn channel(&self) -> Self::Channel {
+        self.lane.clone()
+    }
+}
+
+/// Incoming message whose bytes are received as-is, for apps which bring their
+/// own serialization.
+///
+/// This is the receiving counterpart of [`RawMessage`], and can never fail to
+/// deserialize.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RawPayload(pub Vec<u8>);
+
+impl RawPayload {
+    /// Takes the received bytes.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for RawPayload {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for RawPayload {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl TryFromBytes for RawPayload {
+    type Error = Infallible;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(buf.to_vec()))
+    }
+}
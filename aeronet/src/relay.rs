@@ -0,0 +1,477 @@
+//! Loop detection for messages forwarded between relays.
+//!
+//! A relay is an app which forwards the messages it receives from one peer to
+//! other peers, e.g. a proxy in front of several game servers, or a mesh of
+//! servers which broadcast chat messages to each other. If two relays are
+//! misconfigured to forward to each other, a single message bounces between
+//! them forever, and with more than two relays, every forwarded copy is
+//! forwarded again, causing a broadcast storm.
+//!
+//! To prevent this, messages sent between relays are wrapped in a
+//! [`Relayed`] envelope, which carries the [`RelayId`] of the relay which first
+//! sent the message, a sequence number unique to that relay, and the number of
+//! hops which the message has taken so far. Every relay keeps a [`RelayNode`],
+//! which stamps the envelope of messages that it originates, and checks the
+//! envelope of every message that it receives:
+//!
+//! ```ignore
+//! let mut node = RelayNode::new(RelayId(my_relay_id));
+//!
+//! // originating a message
+//! let msg = node.originate(ChatMessage::new("hello"));
+//! for peer in &peers {
+//!     server.send(peer, msg.clone())?;
+//! }
+//!
+//! // forwarding a message
+//! if let ServerEvent::Recv { client: from, msg } = event {
+//!     match node.forward(msg) {
+//!         Ok(msg) => {
+//!             for peer in peers.iter().filter(|peer| **peer != from) {
+//!                 server.send(peer, msg.clone())?;
+//!             }
+//!         }
+//!         Err(reason) => debug!("Dropped looped message: {reason}"),
+//!     }
+//! }
+//! ```
+//!
+//! A message is dropped if:
+//! * it was originated by this relay, and has come back to it
+//! * this relay has already seen the same message, i.e. it reached this relay
+//!   over two different paths
+//! * it has taken more than the maximum number of hops
+//!
+//! Note that the envelope does not prevent echoing a message back to the peer
+//! which it was received from, since this relay has not seen the message
+//! before - the peer will drop it, but it is still wasted bandwidth. Skip the
+//! sender when forwarding, as in the example above.
+//!
+//! # Wire format
+//!
+//! A [`Relayed`] message is encoded as a [`RELAY_HEADER_LEN`]-byte header,
+//! consisting of the origin as a big-endian `u64`, the sequence number as a
+//! big-endian `u64` and the hop count as a `u8`, followed by the message bytes.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header written by [`Relayed`].
+pub const RELAY_HEADER_LEN: usize = 17;
+
+/// Default value of [`RelayNode::max_hops`].
+pub const DEFAULT_MAX_HOPS: u8 = 8;
+
+/// Default value of [`RelayNode::history_len`].
+pub const DEFAULT_HISTORY_LEN: usize = 4096;
+
+/// Identifier of a relay, which must be unique among all relays which forward
+/// messages to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelayId(pub u64);
+
+impl fmt::Display for RelayId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "relay {}", self.0)
+    }
+}
+
+/// Envelope around a message which is forwarded between relays.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Relayed<T> {
+    /// Relay which originated this message.
+    pub origin: RelayId,
+    /// Sequence number of this message, unique among all messages originated
+    /// by [`Relayed::origin`].
+    pub seq: u64,
+    /// Number of times that this message has been forwarded.
+    pub hops: u8,
+    /// The message being relayed.
+    pub msg: T,
+}
+
+/// Error that occurs when deserializing a [`Relayed`] message.
+#[derive(Debug, thiserror::Error)]
+pub enum RelayedError<E> {
+    /// The message was shorter than the header.
+    #[error("message of {0} bytes is too short for a relay header")]
+    MissingHeader(usize),
+    /// The message bytes could not be deserialized.
+    #[error("failed to deserialize message")]
+    Deserialize(#[source] E),
+}
+
+impl<T> Deref for Relayed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.msg
+    }
+}
+
+impl<T> DerefMut for Relayed<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.msg
+    }
+}
+
+impl<T> TryIntoBytes for Relayed<T>
+where
+    T: TryIntoBytes,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = T::Error;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let bytes = self.msg.try_into_bytes()?;
+        let bytes = bytes.as_ref();
+        let mut buf = Vec::with_capacity(RELAY_HEADER_LEN + bytes.len());
+        buf.extend_from_slice(&self.origin.0.to_be_bytes());
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.push(self.hops);
+        buf.extend_from_slice(bytes);
+        Ok(buf)
+    }
+}
+
+impl<T> TryFromBytes for Relayed<T>
+where
+    T: TryFromBytes,
+{
+    type Error = RelayedError<T::Error>;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() < RELAY_HEADER_LEN {
+            return Err(RelayedError::MissingHeader(buf.len()));
+        }
+        let (header, bytes) = buf.split_at(RELAY_HEADER_LEN);
+        let mut origin = [0; 8];
+        origin.copy_from_slice(&header[..8]);
+        let mut seq = [0; 8];
+        seq.copy_from_slice(&header[8..16]);
+        let msg = T::try_from_bytes(bytes).map_err(RelayedError::Deserialize)?;
+        Ok(Self {
+            origin: RelayId(u64::from_be_bytes(origin)),
+            seq: u64::from_be_bytes(seq),
+            hops: header[16],
+            msg,
+        })
+    }
+}
+
+impl<T> OnChannel for Relayed<T>
+where
+    T: OnChannel,
+{
+    type Channel = T::Channel;
+
+    fn channel(&self) -> Self::Channel {
+        self.msg.channel()
+    }
+}
+
+/// Reason why a [`RelayNode`] dropped a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum LoopDetected {
+    /// The message was originated by this relay.
+    #[error("message originated by this relay")]
+    OwnMessage,
+    /// This relay has already received the same message.
+    #[error("message {seq} from {origin} already seen")]
+    Duplicate {
+        /// Origin of the message.
+        origin: RelayId,
+        /// Sequence number of the message.
+        seq: u64,
+    },
+    /// The message has been forwarded more than [`RelayNode::max_hops`] times.
+    #[error("message has taken {hops} hops, maximum is {max}")]
+    TooManyHops {
+        /// Number of hops that the message has taken.
+        hops: u8,
+        /// Maximum number of hops allowed.
+        max: u8,
+    },
+}
+
+/// State of a single relay, used to stamp the messages that it originates and
+/// detect looped messages that it receives.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone)]
+pub struct RelayNode {
+    id: RelayId,
+    next_seq: u64,
+    max_hops: u8,
+    history_len: usize,
+    seen: HashSet<(RelayId, u64)>,
+    seen_order: VecDeque<(RelayId, u64)>,
+    dropped: u64,
+}
+
+impl RelayNode {
+    /// Creates a node for the relay with the given ID.
+    #[must_use]
+    pub fn new(id: RelayId) -> Self {
+        Self {
+            id,
+            next_seq: 0,
+            max_hops: DEFAULT_MAX_HOPS,
+            history_len: DEFAULT_HISTORY_LEN,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Gets the ID of this relay.
+    #[must_use]
+    pub fn id(&self) -> RelayId {
+        self.id
+    }
+
+    /// Gets the maximum number of times that a message may be forwarded
+    /// before it is dropped.
+    ///
+    /// By default, this is [`DEFAULT_MAX_HOPS`].
+    #[must_use]
+    pub fn max_hops(&self) -> u8 {
+        self.max_hops
+    }
+
+    /// Sets the maximum number of times that a message may be forwarded before
+    /// it is dropped.
+    ///
+    /// See [`RelayNode::max_hops`].
+    pub fn set_max_hops(&mut self, max_hops: u8) {
+        self.max_hops = max_hops;
+    }
+
+    /// Gets how many of the most recently received messages are remembered to
+    /// detect duplicates.
+    ///
+    /// A duplicate which arrives after this many other messages is not
+    /// detected, but will still be dropped once it exceeds
+    /// [`RelayNode::max_hops`].
+    ///
+    /// By default, this is [`DEFAULT_HISTORY_LEN`].
+    #[must_use]
+    pub fn history_len(&self) -> usize {
+        self.history_len
+    }
+
+    /// Sets how many of the most recently received messages are remembered to
+    /// detect duplicates.
+    ///
+    /// See [`RelayNode::history_len`].
+    pub fn set_history_len(&mut self, history_len: usize) {
+        self.history_len = history_len;
+        self.trim_history();
+    }
+
+    /// Gets the number of messages dropped by [`RelayNode::accept`] and
+    /// [`RelayNode::forward`] so far.
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Wraps a message originated by this relay in an envelope, with the next
+    /// sequence number of this relay.
+    pub fn originate<T>(&mut self, msg: T) -> Relayed<T> {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Relayed {
+            origin: self.id,
+            seq,
+            hops: 0,
+            msg,
+        }
+    }
+
+    /// Checks if a received message should be handled by this relay, and
+    /// remembers it so that later copies of it are detected as duplicates.
+    ///
+    /// Use this when receiving a message which is not forwarded further.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message has looped, in which case it should be dropped.
+    pub fn accept<T>(&mut self, msg: &Relayed<T>) -> Result<(), LoopDetected> {
+        let result = self.check(msg);
+        if result.is_err() {
+            self.dropped += 1;
+        }
+        result
+    }
+
+    /// Checks if a received message should be forwarded, and increments its
+    /// hop count.
+    ///
+    /// This checks the message in the same way as [`RelayNode::accept`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message has looped, or forwarding it would take it over
+    /// the maximum number of hops, in which case it should be dropped.
+    pub fn forward<T>(&mut self, mut msg: Relayed<T>) -> Result<Relayed<T>, LoopDetected> {
+        self.accept(&msg)?;
+        if msg.hops >= self.max_hops {
+            self.dropped += 1;
+            return Err(LoopDetected::TooManyHops {
+                hops: msg.hops.saturating_add(1),
+                max: self.max_hops,
+            });
+        }
+        msg.hops += 1;
+        Ok(msg)
+    }
+
+    fn check<T>(&mut self, msg: &Relayed<T>) -> Result<(), LoopDetected> {
+        if msg.origin == self.id {
+            return Err(LoopDetected::OwnMessage);
+        }
+        if msg.hops > self.max_hops {
+            return Err(LoopDetected::TooManyHops {
+                hops: msg.hops,
+                max: self.max_hops,
+            });
+        }
+        let key = (msg.origin, msg.seq);
+        if !self.seen.insert(key) {
+            return Err(LoopDetected::Duplicate {
+                origin: msg.origin,
+                seq: msg.seq,
+            });
+        }
+        self.seen_order.push_back(key);
+        self.trim_history();
+        Ok(())
+    }
+
+    fn trim_history(&mut self) {
+        while self.seen_order.len() > self.history_len {
+            if let Some(key) = self.seen_order.pop_front() {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Msg(u8);
+
+    impl TryIntoBytes for Msg {
+        type Output<'a>
+            = [u8; 1]
+        where
+            Self: 'a;
+
+        type Error = Infallible;
+
+        fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+            Ok([self.0])
+        }
+    }
+
+    impl TryFromBytes for Msg {
+        type Error = Infallible;
+
+        fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Self(buf.first().copied().unwrap_or_default()))
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let msg = Relayed {
+            origin: RelayId(1),
+            seq: 2,
+            hops: 3,
+            msg: Msg(4),
+        };
+        let bytes = msg.try_into_bytes().unwrap();
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 3, 4],
+            bytes
+        );
+        assert_eq!(msg, Relayed::<Msg>::try_from_bytes(&bytes).unwrap());
+        assert!(matches!(
+            Relayed::<Msg>::try_from_bytes(&bytes[..5]),
+            Err(RelayedError::MissingHeader(5))
+        ));
+    }
+
+    #[test]
+    fn two_relay_loop() {
+        let mut a = RelayNode::new(RelayId(1));
+        let mut b = RelayNode::new(RelayId(2));
+
+        let msg = a.originate(Msg(0));
+        let msg = b.forward(msg).unwrap();
+        assert_eq!(1, msg.hops);
+        assert_eq!(Err(LoopDetected::OwnMessage), a.forward(msg).map(|_| ()));
+        assert_eq!(1, a.dropped());
+    }
+
+    #[test]
+    fn duplicate_over_two_paths() {
+        let mut a = RelayNode::new(RelayId(1));
+        let mut b = RelayNode::new(RelayId(2));
+        let mut c = RelayNode::new(RelayId(3));
+
+        let msg = a.originate(Msg(0));
+        let via_b = b.forward(msg.clone()).unwrap();
+        c.forward(msg).unwrap();
+        assert_eq!(
+            Err(LoopDetected::Duplicate {
+                origin: RelayId(1),
+                seq: 0
+            }),
+            c.forward(via_b).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn max_hops() {
+        let mut a = RelayNode::new(RelayId(1));
+        let mut b = RelayNode::new(RelayId(2));
+        b.set_max_hops(1);
+
+        let mut msg = a.originate(Msg(0));
+        msg.hops = 1;
+        assert_eq!(
+            Err(LoopDetected::TooManyHops { hops: 2, max: 1 }),
+            b.forward(msg).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut a = RelayNode::new(RelayId(1));
+        let mut b = RelayNode::new(RelayId(2));
+        b.set_history_len(1);
+
+        let first = a.originate(Msg(0));
+        b.accept(&first).unwrap();
+        b.accept(&a.originate(Msg(1))).unwrap();
+        b.accept(&first).unwrap();
+    }
+}
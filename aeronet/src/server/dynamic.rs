@@ -0,0 +1,298 @@
+use std::error::Error;
+
+use slotmap::{Key, KeyData};
+
+use crate::{
+    CustomEvent, DynConnectionInfo, DynError, ServerEvent, TransportProtocol, TransportServer,
+};
+
+/// A [`DynTransportServer`] behind a [`Box`], which itself implements
+/// [`TransportServer`].
+pub type BoxedTransportServer<P> = Box<dyn DynTransportServer<P>>;
+
+/// Object-safe version of [`TransportServer`].
+///
+/// This is automatically implemented for all [`TransportServer`]s which use a
+/// [`slotmap`] key as their client key, and whose errors implement [`Error`].
+/// This allows choosing a transport implementation at runtime, e.g. based on a
+/// launch flag, without making the rest of your app generic over the
+/// transport.
+///
+/// All type-specific information is erased:
+/// * client keys are converted to and from [`KeyData`]
+/// * errors are boxed as a [`DynError`]
+/// * connection info is boxed as a [`DynConnectionInfo`]
+/// * transport-specific events which do not map to a [`ServerEvent`] are
+///   discarded
+///
+/// A [`BoxedTransportServer`] implements [`TransportServer`], so it can be used
+/// anywhere that a concrete transport can.
+pub trait DynTransportServer<P>: Send + Sync
+where
+    P: TransportProtocol,
+{
+    /// See [`TransportServer::TRANSPORT_NAME`].
+    fn transport_name(&self) -> &'static str;
+
+    /// See [`TransportServer::connection_info`].
+    fn connection_info(&self, client: KeyData) -> Option<DynConnectionInfo>;
+
+    /// See [`TransportServer::connected_clients`].
+    fn connected_clients(&self) -> Vec<KeyData>;
+
+    /// See [`TransportServer::send`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    fn send(&mut self, client: KeyData, msg: P::S2C) -> Result<(), DynError>;
+
+    /// See [`TransportServer::recv`].
+    fn recv(&mut self) -> Vec<ServerEvent<P, BoxedTransportServer<P>>>;
+
+    /// See [`TransportServer::disconnect`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::disconnect`].
+    fn disconnect(&mut self, client: KeyData) -> Result<(), DynError>;
+
+    /// See [`TransportServer::push_event`].
+    fn push_event(&mut self, event: CustomEvent);
+}
+
+impl<P, T> DynTransportServer<P> for T
+where
+    P: TransportProtocol,
+    T: TransportServer<P> + Send + Sync,
+    T::Client: Key,
+    T::Error: Error,
+    T::ConnectionInfo: Send + Sync + 'static,
+{
+    fn transport_name(&self) -> &'static str {
+        T::TRANSPORT_NAME
+    }
+
+    fn connection_info(&self, client: KeyData) -> Option<DynConnectionInfo> {
+        TransportServer::connection_info(self, T::Client::from(client))
+            .map(|info| Box::new(info) as DynConnectionInfo)
+    }
+
+    fn connected_clients(&self) -> Vec<KeyData> {
+        TransportServer::connected_clients(self)
+            .map(|client| client.data())
+            .collect()
+    }
+
+    fn send(&mut self, client: KeyData, msg: P::S2C) -> Result<(), DynError> {
+        TransportServer::send(self, T::Client::from(client), msg).map_err(DynError::from)
+    }
+
+    fn recv(&mut self) -> Vec<ServerEvent<P, BoxedTransportServer<P>>> {
+        TransportServer::recv(self)
+            .filter_map(Into::into)
+            .map(|event| match event {
+                ServerEvent::Connected { client } => ServerEvent::Connected {
+                    client: client.data(),
+                },
+                ServerEvent::Recv { client, msg } => ServerEvent::Recv {
+                    client: client.data(),
+                    msg,
+                },
+                ServerEvent::Disconnected { client, cause } => ServerEvent::Disconnected {
+                    client: client.data(),
+                    cause: DynError::from(cause),
+                },
+                ServerEvent::Custom { event } => ServerEvent::Custom { event },
+            })
+            .collect()
+    }
+
+    fn disconnect(&mut self, client: KeyData) -> Result<(), DynError> {
+        TransportServer::disconnect(self, T::Client::from(client)).map_err(DynError::from)
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        TransportServer::push_event(self, event);
+    }
+}
+
+impl<P> TransportServer<P> for BoxedTransportServer<P>
+where
+    P: TransportProtocol,
+{
+    /// The name of the underlying transport is only known at runtime, and can
+    /// be accessed using [`DynTransportServer::transport_name`].
+    const TRANSPORT_NAME: &'static str = "dyn";
+
+    type Client = KeyData;
+
+    type Error = DynError;
+
+    type ConnectionInfo = DynConnectionInfo;
+
+    type Event = ServerEvent<P, Self>;
+
+    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+        DynTransportServer::connection_info(&**self, client)
+    }
+
+    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+        DynTransportServer::connected_clients(&**self).into_iter()
+    }
+
+    fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error> {
+        DynTransportServer::send(&mut **self, client, msg.into())
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        DynTransportServer::recv(&mut **self).into_iter()
+    }
+
+    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+        DynTransportServer::disconnect(&mut **self, client.into())
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        DynTransportServer::push_event(&mut **self, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, mem};
+
+    use slotmap::{new_key_type, SlotMap};
+
+    use super::*;
+
+    new_key_type! {
+        struct ClientKey;
+    }
+
+    struct Protocol;
+
+    impl TransportProtocol for Protocol {
+        type C2S = u32;
+        type S2C = u32;
+    }
+
+    enum MockEvent {
+        Generic(ServerEvent<Protocol, MockServer>),
+        Internal,
+    }
+
+    impl From<MockEvent> for Option<ServerEvent<Protocol, MockServer>> {
+        fn from(value: MockEvent) -> Self {
+            match value {
+                MockEvent::Generic(event) => Some(event),
+                MockEvent::Internal => None,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct MockServer {
+        clients: SlotMap<ClientKey, Vec<u32>>,
+        events: Vec<MockEvent>,
+    }
+
+    impl TransportServer<Protocol> for MockServer {
+        const TRANSPORT_NAME: &'static str = "mock";
+
+        type Client = ClientKey;
+
+        type Error = io::Error;
+
+        type ConnectionInfo = usize;
+
+        type Event = MockEvent;
+
+        fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+            self.clients.get(client).map(Vec::len)
+        }
+
+        fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+            self.clients.keys()
+        }
+
+        fn send(&mut self, client: Self::Client, msg: impl Into<u32>) -> Result<(), Self::Error> {
+            let sent = self
+                .clients
+                .get_mut(client)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            sent.push(msg.into());
+            Ok(())
+        }
+
+        fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+            mem::take(&mut self.events).into_iter()
+        }
+
+        fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+            let client = client.into();
+            self.clients
+                .remove(client)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            self.events
+                .push(MockEvent::Generic(ServerEvent::Disconnected {
+                    client,
+                    cause: io::Error::from(io::ErrorKind::ConnectionAborted),
+                }));
+            Ok(())
+        }
+
+        fn push_event(&mut self, event: CustomEvent) {
+            self.events
+                .push(MockEvent::Generic(ServerEvent::Custom { event }));
+        }
+    }
+
+    #[test]
+    fn boxed_server() {
+        let mut inner = MockServer::default();
+        let key = inner.clients.insert(Vec::new());
+        inner
+            .events
+            .push(MockEvent::Generic(ServerEvent::Connected { client: key }));
+        inner.events.push(MockEvent::Internal);
+        let mut server: BoxedTransportServer<Protocol> = Box::new(inner);
+        let client = key.data();
+
+        assert_eq!("mock", DynTransportServer::transport_name(&*server));
+        assert_eq!(
+            vec![client],
+            TransportServer::connected_clients(&server).collect::<Vec<_>>()
+        );
+
+        TransportServer::send(&mut server, client, 5u32).unwrap();
+        let info = TransportServer::connection_info(&server, client).unwrap();
+        assert_eq!(Some(&1), info.downcast_ref::<usize>());
+
+        TransportServer::push_event(&mut server, CustomEvent::new("custom"));
+        let events = TransportServer::recv(&mut server).collect::<Vec<_>>();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ServerEvent::Connected { client: connected },
+                ServerEvent::Custom { event },
+            ] if *connected == client && event.downcast_ref::<&str>() == Some(&"custom")
+        ));
+
+        TransportServer::disconnect(&mut server, client).unwrap();
+        assert!(TransportServer::send(&mut server, client, 6u32).is_err());
+        let events = TransportServer::recv(&mut server).collect::<Vec<_>>();
+        let [ServerEvent::Disconnected {
+            client: disconnected,
+            cause,
+        }] = events.as_slice()
+        else {
+            panic!("expected a single disconnect event");
+        };
+        assert_eq!(client, *disconnected);
+        assert_eq!(
+            Some(io::ErrorKind::ConnectionAborted),
+            cause.downcast_ref::<io::Error>().map(io::Error::kind)
+        );
+    }
+}
@@ -1,11 +1,23 @@
 #[cfg(feature = "bevy")]
 mod plugin;
+#[cfg(feature = "bevy")]
+mod room;
 
 #[cfg(feature = "bevy")]
-pub use plugin::*;
+pub use {plugin::*, room::*};
+
+mod dynamic;
+
+pub use dynamic::*;
+
+use std::{any::Any, sync::Arc};
 
 use crate::TransportProtocol;
 
+/// Result of [`TransportServer::send_to_many`], pairing each client that a
+/// message was sent to with the result of sending it to that client.
+pub type SendToManyResult<C, E> = Vec<(C, Result<(), E>)>;
+
 /// Allows listening for client connections, and transporting messages to/from
 /// the clients connected to this server.
 ///
@@ -14,6 +26,10 @@ pub trait TransportServer<P>
 where
     P: TransportProtocol,
 {
+    /// Human-readable name of this transport implementation, e.g. for use in
+    /// logs and diagnostics.
+    const TRANSPORT_NAME: &'static str;
+
     /// Key type that this server uses to uniquely identify clients.
     type Client: Send + Sync + Clone + 'static;
 
@@ -57,6 +73,15 @@ where
     /// connected, but not recognised as connected yet.
     fn connected_clients(&self) -> impl Iterator<Item = Self::Client>;
 
+    /// Gets the number of clients connected to this server.
+    ///
+    /// This is equal to the number of clients returned by
+    /// [`TransportServer::connected_clients`], but implementations may provide
+    /// a more efficient way of computing it.
+    fn connected_count(&self) -> usize {
+        self.connected_clients().count()
+    }
+
     /// Attempts to send a message to the given client.
     ///
     /// # Errors
@@ -75,6 +100,38 @@ where
     /// [`ServerEvent::Disconnected`].
     fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error>;
 
+    /// Attempts to send the same message to each of the given clients.
+    ///
+    /// Returns the result of sending to each client, in the order that the
+    /// clients were given, so that a failure to send to one client (e.g. a
+    /// party member who just disconnected) can be handled without affecting
+    /// the others. See [`TransportServer::send`] for when an error is returned.
+    ///
+    /// The default implementation is a per-client fallback: it calls
+    /// [`TransportServer::send`] with a clone of the message for each client,
+    /// so a transport which serializes messages serializes it once per client.
+    /// Such transports should override this to serialize the message once
+    /// using [`TryIntoBytes`], and queue the same bytes for every client.
+    ///
+    /// [`TryIntoBytes`]: crate::TryIntoBytes
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        let msg = msg.into();
+        clients
+            .into_iter()
+            .map(|client| {
+                let result = self.send(client.clone(), msg.clone());
+                (client, result)
+            })
+            .collect()
+    }
+
     /// Polls events and receives messages from this transport.
     ///
     /// This will consume messages and events from connected clients. Events
@@ -111,6 +168,58 @@ where
     /// server knows that this client is already disconnected), this returns an
     /// error.
     fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error>;
+
+    /// Injects a user-defined event into this server's event stream.
+    ///
+    /// The event will be returned as a [`ServerEvent::Custom`] on the next call
+    /// to [`TransportServer::recv`], in the same event stream as events raised
+    /// by the transport itself. This allows other subsystems of your app (e.g.
+    /// a matchmaking task) to feed events into the same event handling logic.
+    ///
+    /// The default implementation discards the event, for transports which do
+    /// not support custom events.
+    fn push_event(&mut self, event: CustomEvent) {
+        let _ = event;
+    }
+}
+
+/// User-defined event injected into a server's event stream using
+/// [`TransportServer::push_event`].
+///
+/// The event may be of any type, so that protocols do not have to declare the
+/// type of their custom events up front. Use [`CustomEvent::downcast_ref`] to
+/// get the event back as its original type.
+#[derive(Debug, Clone)]
+pub struct CustomEvent(Arc<dyn Any + Send + Sync>);
+
+impl CustomEvent {
+    /// Wraps an event so that it can be passed to
+    /// [`TransportServer::push_event`].
+    #[must_use]
+    pub fn new<E>(event: E) -> Self
+    where
+        E: Any + Send + Sync,
+    {
+        Self(Arc::new(event))
+    }
+
+    /// Gets if the event is of type `E`.
+    #[must_use]
+    pub fn is<E>(&self) -> bool
+    where
+        E: Any,
+    {
+        self.0.is::<E>()
+    }
+
+    /// Gets a reference to the event, if it is of type `E`.
+    #[must_use]
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: Any,
+    {
+        self.0.downcast_ref()
+    }
 }
 
 /// An event which is raised by a [`TransportServer`].
@@ -145,4 +254,9 @@ where
         /// The reason why the client lost connection.
         cause: T::Error,
     },
+    /// A user-defined event was injected using [`TransportServer::push_event`].
+    Custom {
+        /// The event.
+        event: CustomEvent,
+    },
 }
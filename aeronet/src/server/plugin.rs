@@ -1,9 +1,12 @@
-use std::marker::PhantomData;
+use std::{fmt::Display, marker::PhantomData};
 
 use bevy::prelude::*;
 use derivative::Derivative;
 
-use crate::{ServerEvent, TransportProtocol, TransportServer};
+use crate::{
+    CustomEvent, NetworkErrorPolicy, ServerEvent, ServerNetworkError, ServerOp, TransportProtocol,
+    TransportServer,
+};
 
 /// Provides systems to send commands to, and receive events from, a
 /// [`TransportServer`].
@@ -18,26 +21,32 @@ use crate::{ServerEvent, TransportProtocol, TransportServer};
 /// * [`RemoteClientConnected`]
 /// * [`FromClient`]
 /// * [`RemoteClientDisconnected`]
+/// * [`ServerCustomEvent`]
 ///
 /// ...and consumes the events:
 /// * [`ToClient`]
 /// * [`DisconnectRemoteClient`]
 ///
-/// Note that errors during operation will be silently ignored, e.g. if you
-/// attempt to send a message to an unconnected client.
+/// Errors during operation, e.g. if you attempt to send a message to an
+/// unconnected client, are handled according to the [`NetworkErrorPolicy`]
+/// resource. By default, a [`ServerNetworkError`] is sent.
 pub fn transport_server_plugin<P, T>(app: &mut App)
 where
     P: TransportProtocol,
     P::S2C: Clone,
     T: TransportServer<P> + Resource,
+    T::Error: Display,
 {
     app.configure_sets(PreUpdate, TransportServerSet::Recv)
         .configure_sets(PostUpdate, TransportServerSet::Send)
         .add_event::<RemoteClientConnected<P, T>>()
         .add_event::<FromClient<P, T>>()
         .add_event::<RemoteClientDisconnected<P, T>>()
+        .add_event::<ServerCustomEvent>()
         .add_event::<ToClient<P, T>>()
         .add_event::<DisconnectRemoteClient<P, T>>()
+        .add_event::<ServerNetworkError<P, T>>()
+        .init_resource::<NetworkErrorPolicy>()
         .add_systems(PreUpdate, recv::<P, T>.in_set(TransportServerSet::Recv))
         .add_systems(PostUpdate, send::<P, T>.in_set(TransportServerSet::Send));
 }
@@ -53,6 +62,7 @@ where
     P: TransportProtocol,
     P::S2C: Clone,
     T: TransportServer<P> + Resource,
+    T::Error: Display,
 {
     #[derivative(Debug = "ignore")]
     _phantom_p: PhantomData<P>,
@@ -65,6 +75,7 @@ where
     P: TransportProtocol,
     P::S2C: Clone,
     T: TransportServer<P> + Resource,
+    T::Error: Display,
 {
     fn build(&self, app: &mut App) {
         transport_server_plugin::<P, T>(app);
@@ -131,6 +142,18 @@ where
     pub cause: T::Error,
 }
 
+/// A user-defined event was injected into the server's event stream.
+///
+/// Custom events are not tied to a protocol, so the events of all servers are
+/// sent as this same event.
+///
+/// See [`ServerEvent::Custom`].
+#[derive(Debug, Clone, Event)]
+pub struct ServerCustomEvent {
+    /// The event.
+    pub event: CustomEvent,
+}
+
 /// Sends a message along the server to a client.
 ///
 /// See [`TransportServer::send`].
@@ -166,10 +189,12 @@ fn recv<P, T>(
     mut connected: EventWriter<RemoteClientConnected<P, T>>,
     mut recv: EventWriter<FromClient<P, T>>,
     mut disconnected: EventWriter<RemoteClientDisconnected<P, T>>,
+    mut custom: EventWriter<ServerCustomEvent>,
 ) where
     P: TransportProtocol,
     P::S2C: Clone,
     T: TransportServer<P> + Resource,
+    T::Error: Display,
 {
     for event in server.recv() {
         match event.into() {
@@ -181,24 +206,39 @@ fn recv<P, T>(
             Some(ServerEvent::Disconnected { client, cause }) => {
                 disconnected.send(RemoteClientDisconnected { client, cause });
             }
+            Some(ServerEvent::Custom { event }) => custom.send(ServerCustomEvent { event }),
         }
     }
 }
 
+#[allow(clippy::needless_pass_by_value)] // system params are passed by value
 fn send<P, T>(
     mut server: ResMut<T>,
     mut send: EventReader<ToClient<P, T>>,
     mut disconnect: EventReader<DisconnectRemoteClient<P, T>>,
+    policy: Res<NetworkErrorPolicy>,
+    mut errors: EventWriter<ServerNetworkError<P, T>>,
 ) where
     P: TransportProtocol,
     P::S2C: Clone,
     T: TransportServer<P> + Resource,
+    T::Error: Display,
 {
     for ToClient { client, msg } in send.read() {
-        let _ = server.send(client.clone(), msg.clone());
+        if let Err(error) = server.send(client.clone(), msg.clone()) {
+            let op = ServerOp::Send {
+                client: client.clone(),
+            };
+            policy.handle(&mut errors, T::TRANSPORT_NAME, op, error);
+        }
     }
 
     for DisconnectRemoteClient { client } in disconnect.read() {
-        let _ = server.disconnect(client.clone());
+        if let Err(error) = server.disconnect(client.clone()) {
+            let op = ServerOp::Disconnect {
+                client: client.clone(),
+            };
+            policy.handle(&mut errors, T::TRANSPORT_NAME, op, error);
+        }
     }
 }
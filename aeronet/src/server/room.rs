@@ -0,0 +1,483 @@
+use std::marker::PhantomData;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use derivative::Derivative;
+
+use crate::{
+    RemoteClientDisconnected, ToClient, TransportProtocol, TransportServer, TransportServerSet,
+};
+
+/// Provides rooms, which group clients connected to a [`TransportServer`] so
+/// that messages can be broadcast to all clients in a group at once.
+///
+/// To use a struct version of this plugin, see [`RoomPlugin`].
+///
+/// A room is an entity with the [`Room`] component. Each client in a room is
+/// represented by a child entity of the room with the [`RoomMember`]
+/// component. Use [`RoomCommands`] to add clients to and remove clients from
+/// rooms, and to broadcast messages to a room.
+///
+/// When a client disconnects from the server, it is automatically removed from
+/// all rooms that it was a member of.
+///
+/// This plugin requires the [`TransportServerPlugin`] for the same `P` and `T`
+/// to be added.
+///
+/// This plugin emits the events:
+/// * [`ClientJoinedRoom`]
+/// * [`ClientLeftRoom`]
+///
+/// [`TransportServerPlugin`]: crate::TransportServerPlugin
+pub fn room_plugin<P, T>(app: &mut App)
+where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    app.add_event::<ClientJoinedRoom<P, T>>()
+        .add_event::<ClientLeftRoom<P, T>>()
+        .add_systems(
+            PreUpdate,
+            remove_disconnected::<P, T>.after(TransportServerSet::Recv),
+        );
+}
+
+/// Provides rooms, which group clients connected to a [`TransportServer`].
+///
+/// See [`room_plugin`].
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+pub struct RoomPlugin<P, T>
+where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    #[derivative(Debug = "ignore")]
+    _phantom_p: PhantomData<P>,
+    #[derivative(Debug = "ignore")]
+    _phantom_t: PhantomData<T>,
+}
+
+impl<P, T> Plugin for RoomPlugin<P, T>
+where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    fn build(&self, app: &mut App) {
+        room_plugin::<P, T>(app);
+    }
+}
+
+/// Marks an entity as a room, which clients can be members of.
+///
+/// See [`room_plugin`].
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct Room;
+
+/// A client which is a member of the room that is the parent of this entity.
+///
+/// See [`room_plugin`].
+#[derive(Debug, Clone, Component)]
+pub struct RoomMember<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    /// The key of the client.
+    pub client: T::Client,
+}
+
+/// A client has been added to a room.
+#[derive(Debug, Clone, Event)]
+pub struct ClientJoinedRoom<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    /// The room entity.
+    pub room: Entity,
+    /// The key of the client.
+    pub client: T::Client,
+}
+
+/// A client has been removed from a room, either manually or because it
+/// disconnected.
+#[derive(Debug, Clone, Event)]
+pub struct ClientLeftRoom<P, T>
+where
+    P: TransportProtocol,
+    T: TransportServer<P>,
+{
+    /// The room entity.
+    pub room: Entity,
+    /// The key of the client.
+    pub client: T::Client,
+}
+
+/// System parameter for managing the members of rooms, and broadcasting
+/// messages to rooms.
+///
+/// See [`room_plugin`].
+#[derive(SystemParam)]
+pub struct RoomCommands<'w, 's, P, T>
+where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    commands: Commands<'w, 's>,
+    rooms: Query<'w, 's, Option<&'static Children>, With<Room>>,
+    members: Query<'w, 's, (Entity, &'static Parent, &'static RoomMember<P, T>)>,
+    to_client: EventWriter<'w, ToClient<P, T>>,
+    joined: EventWriter<'w, ClientJoinedRoom<P, T>>,
+    left: EventWriter<'w, ClientLeftRoom<P, T>>,
+}
+
+impl<P, T> RoomCommands<'_, '_, P, T>
+where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    /// Gets the keys of all clients which are members of the given room.
+    ///
+    /// If the entity is not a room, this returns an empty iterator.
+    ///
+    /// Changes made using this [`RoomCommands`] are not visible here until
+    /// commands are applied.
+    pub fn members(&self, room: Entity) -> impl Iterator<Item = T::Client> + '_ {
+        self.rooms
+            .get(room)
+            .ok()
+            .flatten()
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter_map(|child| self.members.get(*child).ok())
+            .map(|(_, _, member)| member.client.clone())
+    }
+
+    /// Gets if the given client is a member of the given room.
+    #[must_use]
+    pub fn contains(&self, room: Entity, client: &T::Client) -> bool {
+        self.member_entity(room, client).is_some()
+    }
+
+    /// Adds a client to a room, raising a [`ClientJoinedRoom`].
+    ///
+    /// If the client is already a member of this room, or the entity is not a
+    /// room, this does nothing.
+    pub fn join(&mut self, room: Entity, client: T::Client) {
+        if self.rooms.get(room).is_err() || self.contains(room, &client) {
+            return;
+        }
+
+        self.commands
+            .spawn(RoomMember::<P, T> {
+                client: client.clone(),
+            })
+            .set_parent(room);
+        self.joined.send(ClientJoinedRoom { room, client });
+    }
+
+    /// Removes a client from a room, raising a [`ClientLeftRoom`].
+    ///
+    /// If the client is not a member of this room, this does nothing.
+    pub fn leave(&mut self, room: Entity, client: T::Client) {
+        if let Some(member) = self.member_entity(room, &client) {
+            self.commands.entity(member).despawn_recursive();
+            self.left.send(ClientLeftRoom { room, client });
+        }
+    }
+
+    /// Removes a client from all rooms that it is a member of, raising a
+    /// [`ClientLeftRoom`] for each room.
+    pub fn leave_all(&mut self, client: &T::Client) {
+        remove_client(&mut self.commands, &self.members, &mut self.left, client);
+    }
+
+    /// Sends a message to all members of a room.
+    ///
+    /// This sends a [`ToClient`] event for each member of the room, so errors
+    /// are handled in the same way as with [`ToClient`].
+    pub fn broadcast(&mut self, room: Entity, msg: impl Into<P::S2C>) {
+        let msg = msg.into();
+        let clients = self.members(room).collect::<Vec<_>>();
+        for client in clients {
+            self.to_client.send(ToClient {
+                client,
+                msg: msg.clone(),
+            });
+        }
+    }
+
+    fn member_entity(&self, room: Entity, client: &T::Client) -> Option<Entity> {
+        self.rooms
+            .get(room)
+            .ok()
+            .flatten()?
+            .iter()
+            .find(|child| {
+                self.members
+                    .get(**child)
+                    .is_ok_and(|(_, _, member)| member.client == *client)
+            })
+            .copied()
+    }
+}
+
+// systems
+
+#[allow(clippy::needless_pass_by_value)] // system params are passed by value
+fn remove_disconnected<P, T>(
+    mut commands: Commands,
+    members: Query<(Entity, &Parent, &RoomMember<P, T>)>,
+    mut disconnected: EventReader<RemoteClientDisconnected<P, T>>,
+    mut left: EventWriter<ClientLeftRoom<P, T>>,
+) where
+    P: TransportProtocol,
+    P::S2C: Clone,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    for RemoteClientDisconnected { client, .. } in disconnected.read() {
+        remove_client(&mut commands, &members, &mut left, client);
+    }
+}
+
+fn remove_client<P, T>(
+    commands: &mut Commands,
+    members: &Query<(Entity, &Parent, &RoomMember<P, T>)>,
+    left: &mut EventWriter<ClientLeftRoom<P, T>>,
+    client: &T::Client,
+) where
+    P: TransportProtocol,
+    T: TransportServer<P> + Resource,
+    T::Client: PartialEq,
+{
+    for (member_entity, room, member) in members {
+        if member.client == *client {
+            commands.entity(member_entity).despawn_recursive();
+            left.send(ClientLeftRoom {
+                room: room.get(),
+                client: client.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, mem};
+
+    use bevy::ecs::system::SystemState;
+    use slotmap::{new_key_type, SlotMap};
+
+    use super::*;
+    use crate::{ServerEvent, TransportServerPlugin};
+
+    new_key_type! {
+        struct ClientKey;
+    }
+
+    struct Protocol;
+
+    impl TransportProtocol for Protocol {
+        type C2S = u32;
+        type S2C = u32;
+    }
+
+    #[derive(Default, Resource)]
+    struct MockServer {
+        clients: SlotMap<ClientKey, Vec<u32>>,
+        events: Vec<ServerEvent<Protocol, Self>>,
+    }
+
+    impl TransportServer<Protocol> for MockServer {
+        const TRANSPORT_NAME: &'static str = "mock";
+
+        type Client = ClientKey;
+
+        type Error = io::Error;
+
+        type ConnectionInfo = ();
+
+        type Event = ServerEvent<Protocol, Self>;
+
+        fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+            self.clients.get(client).map(|_| ())
+        }
+
+        fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+            self.clients.keys()
+        }
+
+        fn send(&mut self, client: Self::Client, msg: impl Into<u32>) -> Result<(), Self::Error> {
+            let sent = self
+                .clients
+                .get_mut(client)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            sent.push(msg.into());
+            Ok(())
+        }
+
+        fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+            mem::take(&mut self.events).into_iter()
+        }
+
+        fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+            let client = client.into();
+            self.clients
+                .remove(client)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            self.events.push(ServerEvent::Disconnected {
+                client,
+                cause: io::Error::from(io::ErrorKind::ConnectionAborted),
+            });
+            Ok(())
+        }
+    }
+
+    type Rooms<'w, 's> = RoomCommands<'w, 's, Protocol, MockServer>;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<MockServer>().add_plugins((
+            TransportServerPlugin::<Protocol, MockServer>::default(),
+            RoomPlugin::<Protocol, MockServer>::default(),
+        ));
+        app
+    }
+
+    fn connect(app: &mut App) -> ClientKey {
+        app.world
+            .resource_mut::<MockServer>()
+            .clients
+            .insert(Vec::new())
+    }
+
+    /// Runs `f` with the [`RoomCommands`] of `app`, then applies its commands.
+    fn with_rooms<R>(app: &mut App, f: impl FnOnce(&mut Rooms) -> R) -> R {
+        let mut state = SystemState::<Rooms>::new(&mut app.world);
+        let result = f(&mut state.get_mut(&mut app.world));
+        state.apply(&mut app.world);
+        result
+    }
+
+    fn members(app: &mut App, room: Entity) -> Vec<ClientKey> {
+        with_rooms(app, |rooms| rooms.members(room).collect())
+    }
+
+    fn joined(app: &mut App) -> Vec<(Entity, ClientKey)> {
+        app.world
+            .resource_mut::<Events<ClientJoinedRoom<Protocol, MockServer>>>()
+            .drain()
+            .map(|event| (event.room, event.client))
+            .collect()
+    }
+
+    fn left(app: &mut App) -> Vec<(Entity, ClientKey)> {
+        app.world
+            .resource_mut::<Events<ClientLeftRoom<Protocol, MockServer>>>()
+            .drain()
+            .map(|event| (event.room, event.client))
+            .collect()
+    }
+
+    #[test]
+    fn join_and_leave() {
+        let mut app = app();
+        let room = app.world.spawn(Room).id();
+        let not_room = app.world.spawn_empty().id();
+        let (a, b) = (connect(&mut app), connect(&mut app));
+
+        with_rooms(&mut app, |rooms| {
+            rooms.join(room, a);
+            rooms.join(room, b);
+            rooms.join(not_room, a);
+        });
+        assert_eq!(vec![a, b], members(&mut app, room));
+        assert_eq!(vec![(room, a), (room, b)], joined(&mut app));
+
+        // joining twice does nothing
+        with_rooms(&mut app, |rooms| rooms.join(room, a));
+        assert_eq!(vec![a, b], members(&mut app, room));
+        assert_eq!(Vec::<(Entity, ClientKey)>::new(), joined(&mut app));
+
+        with_rooms(&mut app, |rooms| {
+            rooms.leave(room, a);
+            rooms.leave(not_room, b);
+        });
+        assert_eq!(vec![b], members(&mut app, room));
+        assert!(with_rooms(&mut app, |rooms| !rooms.contains(room, &a)));
+        assert_eq!(vec![(room, a)], left(&mut app));
+    }
+
+    #[test]
+    fn leave_all() {
+        let mut app = app();
+        let (room1, room2) = (app.world.spawn(Room).id(), app.world.spawn(Room).id());
+        let (a, b) = (connect(&mut app), connect(&mut app));
+        with_rooms(&mut app, |rooms| {
+            rooms.join(room1, a);
+            rooms.join(room2, a);
+            rooms.join(room2, b);
+        });
+
+        with_rooms(&mut app, |rooms| rooms.leave_all(&a));
+        assert_eq!(Vec::<ClientKey>::new(), members(&mut app, room1));
+        assert_eq!(vec![b], members(&mut app, room2));
+        let mut left = left(&mut app);
+        left.sort();
+        let mut expected = vec![(room1, a), (room2, a)];
+        expected.sort();
+        assert_eq!(expected, left);
+    }
+
+    #[test]
+    fn broadcast_to_members_only() {
+        let mut app = app();
+        let (room1, room2) = (app.world.spawn(Room).id(), app.world.spawn(Room).id());
+        let (a, b, c) = (connect(&mut app), connect(&mut app), connect(&mut app));
+        with_rooms(&mut app, |rooms| {
+            rooms.join(room1, a);
+            rooms.join(room1, b);
+            rooms.join(room2, c);
+        });
+
+        with_rooms(&mut app, |rooms| rooms.broadcast(room1, 7u32));
+        app.update();
+
+        let server = app.world.resource::<MockServer>();
+        assert_eq!(vec![7], server.clients[a]);
+        assert_eq!(vec![7], server.clients[b]);
+        assert_eq!(Vec::<u32>::new(), server.clients[c]);
+    }
+
+    #[test]
+    fn disconnected_client_leaves_rooms() {
+        let mut app = app();
+        let room = app.world.spawn(Room).id();
+        let (a, b) = (connect(&mut app), connect(&mut app));
+        with_rooms(&mut app, |rooms| {
+            rooms.join(room, a);
+            rooms.join(room, b);
+        });
+        left(&mut app);
+
+        app.world
+            .resource_mut::<MockServer>()
+            .disconnect(a)
+            .unwrap();
+        app.update();
+
+        assert_eq!(vec![b], members(&mut app, room));
+        assert_eq!(vec![(room, a)], left(&mut app));
+    }
+}
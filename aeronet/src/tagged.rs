@@ -0,0 +1,174 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header written by [`Tagged`].
+pub const TAGGED_HEADER_LEN: usize = 4;
+
+/// A message type whose variants are identified by a hash of their name
+/// instead of their position, and whose fields can be serialized separately
+/// from the variant.
+///
+/// This should be derived - see [`aeronet_derive::NameTagged`].
+pub trait NameTagged: Sized {
+    /// Name tags of every variant of this type.
+    const NAME_TAGS: &'static [u32];
+
+    /// Name tag of the variant of this value.
+    fn name_tag(&self) -> u32;
+
+    /// Serializes the fields of this value's variant, without the variant
+    /// itself.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a field could not be serialized.
+    fn serialize_fields(&self) -> Result<Vec<u8>, bincode::Error>;
+
+    /// Deserializes the fields of the variant with the given name tag, and
+    /// creates a value of that variant.
+    ///
+    /// Returns [`None`] if this type has no variant with this tag.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a field could not be deserialized.
+    fn deserialize_fields(tag: u32, buf: &[u8]) -> Result<Option<Self>, bincode::Error>;
+}
+
+/// Computes the name tag of a variant, which is the 32-bit FNV-1a hash of its
+/// name.
+///
+/// This is the same hash as the one computed by the
+/// [`aeronet_derive::NameTagged`] derive.
+#[must_use]
+pub const fn name_tag(name: &str) -> u32 {
+    let bytes = name.as_bytes();
+    let mut hash = 0x811c_9dc5_u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
+/// Envelope around a message which is serialized using [`bincode`], but
+/// identifies the variant of the message by a stable hash of its name rather
+/// than by its index.
+///
+/// By default, [`bincode`] writes the index of an enum variant in front of its
+/// fields, so adding a variant in the middle of an enum, or reordering its
+/// variants, silently changes the meaning of messages sent between peers built
+/// from different revisions of the type. Instead, this writes the
+/// [`NameTagged::name_tag`] of the message as a big-endian `u32`, followed by
+/// the [`bincode`] encoding of the variant's fields.
+///
+/// Variants can therefore be added and reordered freely, and a variant can be
+/// renamed while keeping its tag using `#[wire_name(..)]`. Changing the fields
+/// of a variant is still a breaking change. Receiving a variant which this
+/// revision does not know about fails with [`TaggedError::UnknownVariant`];
+/// see [`Versioned`] for how to skip these messages instead of disconnecting
+/// the peer.
+///
+/// [`Versioned`]: crate::Versioned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Tagged<T>(pub T);
+
+/// Error that occurs when deserializing a [`Tagged`] message.
+#[derive(Debug, thiserror::Error)]
+pub enum TaggedError {
+    /// The message was shorter than the header.
+    #[error("message of {0} bytes is too short for a name tag")]
+    MissingHeader(usize),
+    /// The message is a variant which this revision does not know about.
+    #[error("unknown variant name tag {0:#010x}")]
+    UnknownVariant(u32),
+    /// The fields of the variant could not be deserialized.
+    #[error("failed to deserialize fields")]
+    Deserialize(#[source] bincode::Error),
+}
+
+impl<T> From<T> for Tagged<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Tagged<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Tagged<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> TryIntoBytes for Tagged<T>
+where
+    T: NameTagged,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = bincode::Error;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let fields = self.0.serialize_fields()?;
+        let mut buf = Vec::with_capacity(TAGGED_HEADER_LEN + fields.len());
+        buf.extend_from_slice(&self.0.name_tag().to_be_bytes());
+        buf.extend_from_slice(&fields);
+        Ok(buf)
+    }
+}
+
+impl<T> TryFromBytes for Tagged<T>
+where
+    T: NameTagged,
+{
+    type Error = TaggedError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() < TAGGED_HEADER_LEN {
+            return Err(TaggedError::MissingHeader(buf.len()));
+        }
+        let (header, fields) = buf.split_at(TAGGED_HEADER_LEN);
+        let tag = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        match T::deserialize_fields(tag, fields) {
+            Ok(Some(msg)) => Ok(Self(msg)),
+            Ok(None) => Err(TaggedError::UnknownVariant(tag)),
+            Err(err) => Err(TaggedError::Deserialize(err)),
+        }
+    }
+}
+
+impl<T> OnChannel for Tagged<T>
+where
+    T: OnChannel,
+{
+    type Channel = T::Channel;
+
+    fn channel(&self) -> Self::Channel {
+        self.0.channel()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a() {
+        assert_eq!(0x811c_9dc5, name_tag(""));
+        assert_eq!(0xe40c_292c, name_tag("a"));
+        assert_eq!(0xbf9c_f968, name_tag("foobar"));
+    }
+}
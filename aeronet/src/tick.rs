@@ -0,0 +1,404 @@
+//! Tick-aligned batching of messages.
+//!
+//! Lockstep and rollback netcode work in fixed simulation ticks, and needs to
+//! know which tick the sender was on when it sent a group of messages. Sending
+//! every message individually loses this information, and messages sent during
+//! the same tick may be spread out over several packets.
+//!
+//! Instead, messages can be queued up in a [`TickBatcher`] over the course of a
+//! tick. At the end of the tick, [`TickBatcher::flush_at_tick`] stamps all
+//! messages queued since the last flush with the tick number and packs them
+//! into one [`TickBatch`] per lane, which is then sent as a single message. The
+//! transport's [`TransportProtocol`] uses [`TickBatch`] as its `C2S` and/or
+//! `S2C` type.
+//!
+//! ```ignore
+//! struct AppProtocol;
+//!
+//! impl TransportProtocol for AppProtocol {
+//!     type C2S = TickBatch<AppChannel>;
+//!     type S2C = TickBatch<AppChannel>;
+//! }
+//!
+//! // sending
+//! let mut batcher = TickBatcher::new();
+//! batcher.push(&Input::Jump)?;
+//! batcher.push(&Input::Move(dir))?;
+//! for batch in batcher.flush_at_tick(tick) {
+//!     client.send(batch)?;
+//! }
+//!
+//! // receiving
+//! for event in server.recv() {
+//!     if let ServerEvent::Recv { client, msg } = event {
+//!         let inputs = msg.messages::<Input>().collect::<Result<Vec<_>, _>>()?;
+//!         simulation.apply(client, msg.tick(), inputs);
+//!     }
+//! }
+//! ```
+//!
+//! A server keeps one [`TickBatcher`] per client. Each [`ServerEvent::Recv`] or
+//! [`ClientEvent::Recv`] carries exactly one batch, so all messages from a
+//! sender's tick arrive together - unless they were sent on different lanes,
+//! in which case there is one batch per lane, all with the same tick.
+//!
+//! # Wire format
+//!
+//! A [`TickBatch`] is encoded as a [`HEADER_LEN`]-byte header, consisting of
+//! the tick as a big-endian `u64` and the [`ChannelKey::index`] of the batch's
+//! lane as a big-endian `u16`. This is followed by the messages of the batch,
+//! each prefixed with its length as a big-endian `u32`.
+//!
+//! [`TransportProtocol`]: crate::TransportProtocol
+//! [`ServerEvent::Recv`]: crate::ServerEvent::Recv
+//! [`ClientEvent::Recv`]: crate::ClientEvent::Recv
+
+use std::{convert::Infallible, marker::PhantomData};
+
+use crate::{ChannelKey, OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header of an encoded [`TickBatch`].
+pub const HEADER_LEN: usize = 10;
+
+const LEN_PREFIX: usize = 4;
+
+/// All messages on a single lane which were queued during a single tick.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickBatch<C> {
+    lane: C,
+    len: usize,
+    /// The encoded batch, including the header.
+    bytes: Vec<u8>,
+}
+
+impl<C> TickBatch<C>
+where
+    C: ChannelKey,
+{
+    /// Creates an empty batch stamped with `tick`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index of `lane` does not fit in a `u16`.
+    fn new(tick: u64, lane: C) -> Self {
+        let lane_index =
+            u16::try_from(lane.index()).expect("channel key should have at most 65536 variants");
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(&tick.to_be_bytes());
+        bytes.extend_from_slice(&lane_index.to_be_bytes());
+        Self {
+            lane,
+            len: 0,
+            bytes,
+        }
+    }
+
+    /// Gets the tick that the sender stamped this batch with.
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        let mut tick = [0; 8];
+        tick.copy_from_slice(&self.bytes[..8]);
+        u64::from_be_bytes(tick)
+    }
+
+    /// Gets the lane that this batch is sent on.
+    #[must_use]
+    pub fn lane(&self) -> &C {
+        &self.lane
+    }
+
+    /// Gets the number of messages in this batch.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets if this batch contains no messages.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the serialized messages of this batch, in the order that
+    /// they were pushed in.
+    pub fn payloads(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let mut rest = &self.bytes[HEADER_LEN..];
+        std::iter::from_fn(move || {
+            let (len, after) = split_prefix(rest)?;
+            let (payload, after) = after.split_at(len);
+            rest = after;
+            Some(payload)
+        })
+    }
+
+    /// Iterates over the deserialized messages of this batch, in the order that
+    /// they were pushed in.
+    pub fn messages<'a, M>(&'a self) -> impl Iterator<Item = Result<M, M::Error>> + 'a
+    where
+        M: TryFromBytes + 'a,
+    {
+        self.payloads().map(M::try_from_bytes)
+    }
+}
+
+/// Splits a length prefix off of `buf`, returning the length if there are at
+/// least that many bytes after it.
+fn split_prefix(buf: &[u8]) -> Option<(usize, &[u8])> {
+    let prefix = buf.get(..LEN_PREFIX)?;
+    let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+    let len = usize::try_from(len).ok()?;
+    let rest = &buf[LEN_PREFIX..];
+    (rest.len() >= len).then_some((len, rest))
+}
+
+impl<C> TryIntoBytes for TickBatch<C> {
+    type Output<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    type Error = Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        Ok(&self.bytes)
+    }
+}
+
+impl<C> TryFromBytes for TickBatch<C>
+where
+    C: ChannelKey,
+{
+    type Error = TickDecodeError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let Some(header) = buf.get(..HEADER_LEN) else {
+            return Err(TickDecodeError::TooShort(buf.len()));
+        };
+        let lane_index = usize::from(u16::from_be_bytes([header[8], header[9]]));
+        let lane = C::ALL
+            .get(lane_index)
+            .ok_or(TickDecodeError::InvalidLane(lane_index))?
+            .clone();
+
+        let mut len = 0;
+        let mut rest = &buf[HEADER_LEN..];
+        while !rest.is_empty() {
+            let (msg_len, after) = split_prefix(rest).ok_or(TickDecodeError::Truncated(len))?;
+            rest = &after[msg_len..];
+            len += 1;
+        }
+
+        Ok(Self {
+            lane,
+            len,
+            bytes: buf.to_vec(),
+        })
+    }
+}
+
+impl<C> OnChannel for TickBatch<C>
+where
+    C: ChannelKey,
+{
+    type Channel = C;
+
+    fn channel(&self) -> Self::Channel {
+        self.lane.clone()
+    }
+}
+
+/// Error that occurs when decoding a [`TickBatch`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TickDecodeError {
+    /// The batch was too short to contain a header.
+    #[error("batch of {0} bytes is too short to contain a header")]
+    TooShort(usize),
+    /// The header referred to a lane which does not exist.
+    #[error("no lane with index {0}")]
+    InvalidLane(usize),
+    /// The message at the given index was cut off.
+    #[error("message {0} is truncated")]
+    Truncated(usize),
+}
+
+/// Queues messages over the course of a tick, and packs them into
+/// [`TickBatch`]es when the tick ends.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone)]
+pub struct TickBatcher<C> {
+    /// Messages queued since the last flush, indexed by lane index.
+    pending: Vec<Vec<u8>>,
+    counts: Vec<usize>,
+    _phantom: PhantomData<C>,
+}
+
+impl<C> Default for TickBatcher<C>
+where
+    C: ChannelKey,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> TickBatcher<C>
+where
+    C: ChannelKey,
+{
+    /// Creates a batcher with no messages queued.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            pending: vec![Vec::new(); C::ALL.len()],
+            counts: vec![0; C::ALL.len()],
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Serializes a message and queues it on the lane given by its
+    /// [`OnChannel`] impl, until the next [`TickBatcher::flush_at_tick`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message could not be serialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the serialized message is 4 GiB or larger, as its length
+    /// would not fit in the length prefix.
+    pub fn push<M>(&mut self, msg: &M) -> Result<(), M::Error>
+    where
+        M: TryIntoBytes + OnChannel<Channel = C>,
+    {
+        let payload = msg.try_into_bytes()?;
+        let payload = payload.as_ref();
+        let len = u32::try_from(payload.len()).expect("message should be smaller than 4 GiB");
+        let index = msg.channel().index();
+        self.pending[index].extend_from_slice(&len.to_be_bytes());
+        self.pending[index].extend_from_slice(payload);
+        self.counts[index] += 1;
+        Ok(())
+    }
+
+    /// Gets the number of messages queued since the last flush.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Gets if no messages have been queued since the last flush.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    /// Stamps all messages queued since the last flush with `tick`, and packs
+    /// them into one batch per lane which has messages queued on it.
+    ///
+    /// Batches are returned in order of their lane's [`ChannelKey::index`],
+    /// so the output is deterministic for the same sequence of pushes. If no
+    /// messages were queued, no batches are returned.
+    ///
+    /// All queued messages are removed from this batcher, even if the
+    /// returned batches are not sent.
+    #[must_use]
+    pub fn flush_at_tick(&mut self, tick: u64) -> Vec<TickBatch<C>> {
+        C::ALL
+            .iter()
+            .zip(self.pending.iter_mut().zip(self.counts.iter_mut()))
+            .filter(|(_, (_, count))| **count > 0)
+            .map(move |(lane, (pending, count))| {
+                let mut batch = TickBatch::new(tick, lane.clone());
+                batch.bytes.append(pending);
+                batch.len = std::mem::take(count);
+                batch
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChannelKind;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Lane {
+        A,
+        B,
+    }
+
+    unsafe impl ChannelKey for Lane {
+        const ALL: &'static [Self] = &[Self::A, Self::B];
+
+        fn index(&self) -> usize {
+            match self {
+                Self::A => 0,
+                Self::B => 1,
+            }
+        }
+
+        fn kind(&self) -> ChannelKind {
+            ChannelKind::ReliableOrdered
+        }
+    }
+
+    struct Msg(Lane, &'static [u8]);
+
+    impl TryIntoBytes for Msg {
+        type Output<'a>
+            = &'a [u8]
+        where
+            Self: 'a;
+
+        type Error = Infallible;
+
+        fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+            Ok(self.1)
+        }
+    }
+
+    impl OnChannel for Msg {
+        type Channel = Lane;
+
+        fn channel(&self) -> Self::Channel {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut batcher = TickBatcher::<Lane>::new();
+        batcher.push(&Msg(Lane::B, b"one")).unwrap();
+        batcher.push(&Msg(Lane::A, b"")).unwrap();
+        batcher.push(&Msg(Lane::B, b"two")).unwrap();
+        let flushed = batcher.flush_at_tick(42);
+        assert!(batcher.is_empty());
+        assert_eq!(2, flushed.len());
+
+        let bytes = flushed[1].try_into_bytes().unwrap().to_vec();
+        let decoded = TickBatch::<Lane>::try_from_bytes(&bytes).unwrap();
+        assert_eq!(42, decoded.tick());
+        assert_eq!(&Lane::B, decoded.lane());
+        assert_eq!(
+            vec![b"one".as_slice(), b"two".as_slice()],
+            decoded.payloads().collect::<Vec<_>>()
+        );
+        assert_eq!(1, flushed[0].len());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut bytes = TickBatch::new(1, Lane::A).bytes;
+        bytes.extend_from_slice(&5u32.to_be_bytes());
+        bytes.extend_from_slice(b"abc");
+        assert_eq!(
+            Err(TickDecodeError::Truncated(0)),
+            TickBatch::<Lane>::try_from_bytes(&bytes)
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{any::Any, error::Error, net::SocketAddr, time::Duration};
 
-use crate::Message;
+pub use aeronet_proto::Features;
+
+use crate::{Extensions, Message};
 
 /// Defines the types of messages sent across a transport channel.
 ///
@@ -48,3 +50,84 @@ pub trait RemoteAddr {
     /// connected to.
     fn remote_addr(&self) -> SocketAddr;
 }
+
+/// Quality of the connection from this side to the remote side, as observed
+/// by the remote side and reported back to this side.
+///
+/// An endpoint can only measure the quality of the data it receives, so without
+/// a report from the other side, it has no way to know e.g. how many of the
+/// messages it sent were lost on the way.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct QualityReport {
+    /// Fraction of unreliable messages sent by this side which did not arrive
+    /// at the remote side, in the range `0.0..=1.0`.
+    pub loss: f32,
+    /// Variation in the round-trip time of the connection, as measured by the
+    /// remote side.
+    pub jitter: Duration,
+}
+
+/// Allows access to the quality of a connection as reported by the remote side.
+pub trait RemoteQuality {
+    /// Gets the last [`QualityReport`] received from the remote side.
+    ///
+    /// This is [`None`] if the remote side has not sent a report yet, or does
+    /// not send reports at all.
+    fn remote_quality(&self) -> Option<QualityReport>;
+}
+
+/// Allows access to the optional protocol [`Features`] which both sides of a
+/// connection support.
+///
+/// Check this before using a feature on a connection, so that a peer which
+/// does not support it still gets a working, if degraded, connection.
+pub trait NegotiatedFeatures {
+    /// Gets the features negotiated with the remote side.
+    ///
+    /// This is [`Features::NONE`] until the negotiation has completed, or if
+    /// the remote side does not advertise any features.
+    fn negotiated_features(&self) -> Features;
+}
+
+/// Allows access to transport-specific data about a connection, such as the
+/// stable ID of a QUIC connection, by its type.
+///
+/// Generic code can use this to read data which only some transports provide,
+/// without downcasting the whole transport, and handle the data being absent
+/// on other transports:
+///
+/// ```ignore
+/// fn log_connection<T>(client: &T)
+/// where
+///     T: TransportClient<AppProtocol>,
+///     T::ConnectionInfo: ConnectionExtensions,
+/// {
+///     if let Some(info) = client.connection_info() {
+///         let id = info.extensions().get::<StableId>();
+///         info!("Connected to server (stable ID: {id:?})");
+///     }
+/// }
+/// ```
+///
+/// See the docs of each transport for which types it provides.
+pub trait ConnectionExtensions {
+    /// Gets the transport-specific data of this connection.
+    fn extensions(&self) -> &Extensions;
+}
+
+/// Type-erased error returned by a [`DynTransportServer`] or
+/// [`DynTransportClient`].
+///
+/// [`DynTransportServer`]: crate::DynTransportServer
+/// [`DynTransportClient`]: crate::DynTransportClient
+pub type DynError = Box<dyn Error + Send + Sync>;
+
+/// Type-erased connection info returned by a [`DynTransportServer`] or
+/// [`DynTransportClient`].
+///
+/// This can be downcast to the concrete connection info type of the underlying
+/// transport.
+///
+/// [`DynTransportServer`]: crate::DynTransportServer
+/// [`DynTransportClient`]: crate::DynTransportClient
+pub type DynConnectionInfo = Box<dyn Any + Send + Sync>;
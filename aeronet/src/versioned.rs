@@ -0,0 +1,213 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header written by [`Versioned`].
+pub const VERSIONED_HEADER_LEN: usize = 4;
+
+/// A message type whose variants are identified by stable tags, which do not
+/// change as variants are added, removed or reordered.
+///
+/// This should be derived - see [`aeronet_derive::VariantTags`].
+pub trait VariantTags {
+    /// Version of the schema of this type, which should be bumped every time a
+    /// variant is added or removed.
+    const VERSION: u16;
+
+    /// Tags of every variant known by this version of the type.
+    const TAGS: &'static [u16];
+
+    /// Tag of the variant of this value.
+    fn variant_tag(&self) -> u16;
+}
+
+/// Envelope around a message which lets peers one schema version apart
+/// interoperate during a staged rollout.
+///
+/// A header containing the [`VariantTags::VERSION`] of the sender and the
+/// [`VariantTags::variant_tag`] of the message is written in front of the
+/// message bytes, as two big-endian `u16`s. When receiving a message with a
+/// variant which this version does not know about, e.g. because the peer is
+/// running a newer version which added it, deserialization fails with
+/// [`VersionedError::UnknownVariant`] without looking at the message bytes.
+///
+/// To skip these messages instead of disconnecting the peer, set the
+/// transport's deserialization error policy to
+/// [`OnMessageError::EmitEventOnly`], which raises an event with the error and
+/// keeps the connection open, or to [`OnMessageError::DropMessage`].
+///
+/// This only covers added variants. Whether fields can be added to an existing
+/// variant depends on the format of `T`: e.g. `bincode` ignores trailing bytes,
+/// so a field appended to the end of a variant is ignored by older receivers,
+/// but a newer receiver can not read messages without it.
+///
+/// [`OnMessageError::EmitEventOnly`]: crate::OnMessageError::EmitEventOnly
+/// [`OnMessageError::DropMessage`]: crate::OnMessageError::DropMessage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Versioned<T>(pub T);
+
+/// Error that occurs when deserializing a [`Versioned`] message.
+#[derive(Debug, thiserror::Error)]
+pub enum VersionedError<E> {
+    /// The message was shorter than the header.
+    #[error("message of {0} bytes is too short for a version header")]
+    MissingHeader(usize),
+    /// The message is a variant which this version does not know about.
+    #[error("unknown variant tag {tag} from version {version}")]
+    UnknownVariant {
+        /// Version of the sender.
+        version: u16,
+        /// Tag of the variant.
+        tag: u16,
+    },
+    /// The message bytes could not be deserialized.
+    #[error("failed to deserialize message")]
+    Deserialize(#[source] E),
+}
+
+impl<T> From<T> for Versioned<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Versioned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Versioned<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> TryIntoBytes for Versioned<T>
+where
+    T: TryIntoBytes + VariantTags,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = T::Error;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let bytes = self.0.try_into_bytes()?;
+        let bytes = bytes.as_ref();
+        let mut buf = Vec::with_capacity(VERSIONED_HEADER_LEN + bytes.len());
+        buf.extend_from_slice(&T::VERSION.to_be_bytes());
+        buf.extend_from_slice(&self.0.variant_tag().to_be_bytes());
+        buf.extend_from_slice(bytes);
+        Ok(buf)
+    }
+}
+
+impl<T> TryFromBytes for Versioned<T>
+where
+    T: TryFromBytes + VariantTags,
+{
+    type Error = VersionedError<T::Error>;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() < VERSIONED_HEADER_LEN {
+            return Err(VersionedError::MissingHeader(buf.len()));
+        }
+        let (header, bytes) = buf.split_at(VERSIONED_HEADER_LEN);
+        let version = u16::from_be_bytes([header[0], header[1]]);
+        let tag = u16::from_be_bytes([header[2], header[3]]);
+        if !T::TAGS.contains(&tag) {
+            return Err(VersionedError::UnknownVariant { version, tag });
+        }
+        T::try_from_bytes(bytes)
+            .map(Self)
+            .map_err(VersionedError::Deserialize)
+    }
+}
+
+impl<T> OnChannel for Versioned<T>
+where
+    T: OnChannel,
+{
+    type Channel = T::Channel;
+
+    fn channel(&self) -> Self::Channel {
+        self.0.channel()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Msg {
+        Ping,
+        Chat(u8),
+    }
+
+    impl VariantTags for Msg {
+        const VERSION: u16 = 2;
+
+        const TAGS: &'static [u16] = &[0, 1];
+
+        fn variant_tag(&self) -> u16 {
+            match self {
+                Self::Ping => 0,
+                Self::Chat(_) => 1,
+            }
+        }
+    }
+
+    impl TryIntoBytes for Msg {
+        type Output<'a>
+            = Vec<u8>
+        where
+            Self: 'a;
+
+        type Error = Infallible;
+
+        fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+            Ok(match self {
+                Self::Ping => vec![0],
+                Self::Chat(c) => vec![1, *c],
+            })
+        }
+    }
+
+    impl TryFromBytes for Msg {
+        type Error = Infallible;
+
+        fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+            Ok(match buf {
+                [1, c] => Self::Chat(*c),
+                _ => Self::Ping,
+            })
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = Versioned(Msg::Chat(7)).try_into_bytes().unwrap();
+        assert_eq!(vec![0, 2, 0, 1, 1, 7], bytes);
+        let msg = Versioned::<Msg>::try_from_bytes(&bytes).unwrap();
+        assert_eq!(Msg::Chat(7), msg.0);
+    }
+
+    #[test]
+    fn unknown_variant() {
+        let result = Versioned::<Msg>::try_from_bytes(&[0, 3, 0, 5, 1, 2, 3]);
+        assert!(matches!(
+            result,
+            Err(VersionedError::UnknownVariant { version: 3, tag: 5 })
+        ));
+        let result = Versioned::<Msg>::try_from_bytes(&[0, 3, 0]);
+        assert!(matches!(result, Err(VersionedError::MissingHeader(3))));
+    }
+}
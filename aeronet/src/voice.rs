@@ -0,0 +1,570 @@
+//! Framing for voice chat sent over an unreliable lane.
+//!
+//! Voice codecs such as Opus produce one small frame of encoded audio every
+//! few milliseconds, which must be played back at the same fixed rate on the
+//! receiving side. Sending these frames as regular messages on a reliable lane
+//! causes audible stalls whenever a packet is lost, since every following
+//! frame is held back until the lost one is resent. On an unreliable lane, lost
+//! frames are skipped, but frames arrive with a varying delay (jitter), and
+//! may arrive out of order.
+//!
+//! This module lets voice ride the same connection as gameplay messages, on an
+//! [`Unreliable`] lane of the app's [`ChannelKey`]:
+//! * a [`VoiceSender`] paces encoded frames out at a fixed interval, and wraps
+//!   each one in a [`VoicePacket`] with a sequence number and timestamp
+//! * with [`VoiceConfig::fec`] enabled, each packet also carries a copy of the
+//!   previous frame, so that a single lost packet can be recovered from the
+//!   next one
+//! * a [`JitterBuffer`] on the receiving side holds back a few frames to absorb
+//!   jitter, and hands them out in order at the playback rate, telling the
+//!   decoder when a frame was lost so that it can conceal the gap
+//!
+//! ```ignore
+//! // sending, every frame
+//! while let Some(frame) = mic.next_opus_frame() {
+//!     sender.push(frame);
+//! }
+//! while let Some(packet) = sender.poll(Instant::now()) {
+//!     client.send(packet)?;
+//! }
+//!
+//! // receiving
+//! if let ClientEvent::Recv { msg } = event {
+//!     jitter_buffer.insert(msg);
+//! }
+//! // in the audio callback, once per frame duration
+//! match jitter_buffer.pop() {
+//!     Some(VoicePlayout::Frame(frame)) => decoder.decode(&frame),
+//!     Some(VoicePlayout::Lost) => decoder.conceal(),
+//!     None => output_silence(),
+//! }
+//! ```
+//!
+//! # Wire format
+//!
+//! A [`VoicePacket`] is encoded as a [`VOICE_HEADER_LEN`]-byte header,
+//! consisting of the sequence number as a big-endian `u32`, the timestamp as a
+//! big-endian `u32`, the [`ChannelKey::index`] of the packet's lane as a
+//! big-endian `u16`, and a flags byte. This is followed by the frame, prefixed
+//! with its length as a big-endian `u16`, and, if the lowest bit of the flags
+//! is set, the previous frame in the same form.
+//!
+//! [`Unreliable`]: crate::ChannelKind::Unreliable
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{ChannelKey, OnChannel, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header of an encoded [`VoicePacket`].
+pub const VOICE_HEADER_LEN: usize = 11;
+
+/// Maximum length in bytes of a single voice frame.
+pub const MAX_FRAME_LEN: usize = u16::MAX as usize;
+
+const FLAG_FEC: u8 = 0b1;
+
+/// Settings shared by a [`VoiceSender`] and [`JitterBuffer`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VoiceConfig {
+    /// Duration of audio in a single frame.
+    ///
+    /// By default this is 20 ms, the default frame size of Opus.
+    pub frame_duration: Duration,
+    /// Number of samples per channel in a single frame, which the timestamp is
+    /// advanced by for every frame.
+    ///
+    /// By default this is 960, which is 20 ms at 48 kHz.
+    pub samples_per_frame: u32,
+    /// Whether each packet also carries the previous frame, so that a single
+    /// lost packet can be recovered.
+    ///
+    /// This roughly doubles the bandwidth used. By default this is `true`.
+    pub fec: bool,
+    /// Number of frames that the [`JitterBuffer`] holds back before it starts
+    /// playing.
+    ///
+    /// Larger values absorb more jitter, at the cost of latency. By default
+    /// this is 3.
+    pub jitter_frames: usize,
+    /// Maximum number of frames that the [`JitterBuffer`] holds, after which
+    /// the oldest frames are dropped to catch up.
+    ///
+    /// By default this is 16.
+    pub max_buffered_frames: usize,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            frame_duration: Duration::from_millis(20),
+            samples_per_frame: 960,
+            fec: true,
+            jitter_frames: 3,
+            max_buffered_frames: 16,
+        }
+    }
+}
+
+/// Single frame of encoded voice, sent on an unreliable lane.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VoicePacket<C> {
+    /// Lane that this packet is sent on.
+    pub lane: C,
+    /// Sequence number of this frame, incremented by one for every frame.
+    pub seq: u32,
+    /// Timestamp of the first sample of this frame, in samples.
+    pub timestamp: u32,
+    /// The encoded frame.
+    pub frame: Vec<u8>,
+    /// The previous encoded frame, if [`VoiceConfig::fec`] is enabled.
+    pub fec: Option<Vec<u8>>,
+}
+
+/// Error that occurs when decoding a [`VoicePacket`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VoiceDecodeError {
+    /// The packet was too short to contain a header.
+    #[error("packet of {0} bytes is too short to contain a header")]
+    TooShort(usize),
+    /// The header referred to a lane which does not exist.
+    #[error("no lane with index {0}")]
+    InvalidLane(usize),
+    /// A frame was cut off.
+    #[error("frame is truncated")]
+    Truncated,
+}
+
+/// Error that occurs when encoding a [`VoicePacket`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("frame is {0} bytes long, longer than {MAX_FRAME_LEN} bytes")]
+pub struct FrameTooLong(pub usize);
+
+fn put_frame(buf: &mut Vec<u8>, frame: &[u8]) -> Result<(), FrameTooLong> {
+    let len = u16::try_from(frame.len()).map_err(|_| FrameTooLong(frame.len()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(frame);
+    Ok(())
+}
+
+fn take_frame<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], VoiceDecodeError> {
+    let prefix = buf.get(..2).ok_or(VoiceDecodeError::Truncated)?;
+    let len = usize::from(u16::from_be_bytes([prefix[0], prefix[1]]));
+    let frame = buf.get(2..2 + len).ok_or(VoiceDecodeError::Truncated)?;
+    *buf = &buf[2 + len..];
+    Ok(frame)
+}
+
+impl<C> TryIntoBytes for VoicePacket<C>
+where
+    C: ChannelKey,
+{
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = FrameTooLong;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let lane_index = u16::try_from(self.lane.index())
+            .expect("channel key should have at most 65536 variants");
+        let fec_len = self.fec.as_ref().map_or(0, |fec| 2 + fec.len());
+        let mut buf = Vec::with_capacity(VOICE_HEADER_LEN + 2 + self.frame.len() + fec_len);
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(&lane_index.to_be_bytes());
+        buf.push(if self.fec.is_some() { FLAG_FEC } else { 0 });
+        put_frame(&mut buf, &self.frame)?;
+        if let Some(fec) = &self.fec {
+            put_frame(&mut buf, fec)?;
+        }
+        Ok(buf)
+    }
+}
+
+impl<C> TryFromBytes for VoicePacket<C>
+where
+    C: ChannelKey,
+{
+    type Error = VoiceDecodeError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let Some(header) = buf.get(..VOICE_HEADER_LEN) else {
+            return Err(VoiceDecodeError::TooShort(buf.len()));
+        };
+        let seq = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let timestamp = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let lane_index = usize::from(u16::from_be_bytes([header[8], header[9]]));
+        let lane = C::ALL
+            .get(lane_index)
+            .ok_or(VoiceDecodeError::InvalidLane(lane_index))?
+            .clone();
+        let flags = header[10];
+
+        let mut rest = &buf[VOICE_HEADER_LEN..];
+        let frame = take_frame(&mut rest)?.to_vec();
+        let fec = if flags & FLAG_FEC == 0 {
+            None
+        } else {
+            Some(take_frame(&mut rest)?.to_vec())
+        };
+        Ok(Self {
+            lane,
+            seq,
+            timestamp,
+            frame,
+            fec,
+        })
+    }
+}
+
+impl<C> OnChannel for VoicePacket<C>
+where
+    C: ChannelKey,
+{
+    type Channel = C;
+
+    fn channel(&self) -> Self::Channel {
+        self.lane.clone()
+    }
+}
+
+/// Paces encoded voice frames out at a fixed interval, wrapping each one in a
+/// [`VoicePacket`].
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone)]
+pub struct VoiceSender<C> {
+    lane: C,
+    config: VoiceConfig,
+    queue: VecDeque<Vec<u8>>,
+    prev_frame: Option<Vec<u8>>,
+    next_seq: u32,
+    next_timestamp: u32,
+    next_send: Option<Instant>,
+}
+
+impl<C> VoiceSender<C>
+where
+    C: ChannelKey,
+{
+    /// Creates a sender which sends packets on the given lane.
+    ///
+    /// The lane should be [unreliable](crate::ChannelKind::Unreliable).
+    #[must_use]
+    pub fn new(lane: C, config: VoiceConfig) -> Self {
+        Self {
+            lane,
+            config,
+            queue: VecDeque::new(),
+            prev_frame: None,
+            next_seq: 0,
+            next_timestamp: 0,
+            next_send: None,
+        }
+    }
+
+    /// Gets the config of this sender.
+    #[must_use]
+    pub fn config(&self) -> &VoiceConfig {
+        &self.config
+    }
+
+    /// Gets the number of frames waiting to be sent.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Queues an encoded frame to be sent.
+    pub fn push(&mut self, frame: impl Into<Vec<u8>>) {
+        self.queue.push_back(frame.into());
+    }
+
+    /// Gets the next packet to send, if a frame is queued and one frame
+    /// duration has passed since the last packet was sent.
+    ///
+    /// Call this in a loop until it returns [`None`], so that the sender can
+    /// catch up if it is polled less often than once per frame duration.
+    /// When no frames are queued, e.g. because the speaker stopped talking,
+    /// pacing restarts with the next frame pushed.
+    pub fn poll(&mut self, now: Instant) -> Option<VoicePacket<C>> {
+        if let Some(next_send) = self.next_send {
+            if now < next_send {
+                return None;
+            }
+        }
+
+        let Some(frame) = self.queue.pop_front() else {
+            self.next_send = None;
+            return None;
+        };
+        let send_at = self.next_send.unwrap_or(now);
+        self.next_send = Some(send_at + self.config.frame_duration);
+
+        let fec = if self.config.fec {
+            self.prev_frame.replace(frame.clone())
+        } else {
+            None
+        };
+        let packet = VoicePacket {
+            lane: self.lane.clone(),
+            seq: self.next_seq,
+            timestamp: self.next_timestamp,
+            frame,
+            fec,
+        };
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.next_timestamp = self
+            .next_timestamp
+            .wrapping_add(self.config.samples_per_frame);
+        Some(packet)
+    }
+}
+
+/// Next frame for the decoder to play, returned by [`JitterBuffer::pop`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VoicePlayout {
+    /// The frame was received, and should be decoded.
+    Frame(Vec<u8>),
+    /// The frame was lost, and the decoder should conceal the gap, e.g. using
+    /// packet loss concealment.
+    Lost,
+}
+
+/// Statistics of a [`JitterBuffer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct JitterStats {
+    /// Number of frames played.
+    pub played: u64,
+    /// Number of frames which were lost.
+    pub lost: u64,
+    /// Number of lost frames which were recovered from the next packet's
+    /// copy of them.
+    pub recovered: u64,
+    /// Number of frames which arrived after their turn to be played, and were
+    /// dropped.
+    pub late: u64,
+    /// Number of frames dropped because the buffer was full.
+    pub overflowed: u64,
+}
+
+/// Holds back received voice frames to absorb jitter, and hands them out in
+/// order at the playback rate.
+///
+/// The buffer starts playing once [`VoiceConfig::jitter_frames`] frames are
+/// buffered. After that, [`JitterBuffer::pop`] should be called once per
+/// [`VoiceConfig::frame_duration`], usually from the audio callback. If the
+/// buffer runs empty, e.g. because the speaker stopped talking, it waits until
+/// it is filled up again before playing.
+///
+/// Sequence numbers are assumed to not wrap around, which takes over two years
+/// of continuous speech at the default frame duration.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone)]
+pub struct JitterBuffer {
+    config: VoiceConfig,
+    frames: BTreeMap<u32, Vec<u8>>,
+    next_seq: Option<u32>,
+    playing: bool,
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    /// Creates an empty buffer.
+    #[must_use]
+    pub fn new(config: VoiceConfig) -> Self {
+        Self {
+            config,
+            frames: BTreeMap::new(),
+            next_seq: None,
+            playing: false,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Gets the config of this buffer.
+    #[must_use]
+    pub fn config(&self) -> &VoiceConfig {
+        &self.config
+    }
+
+    /// Gets the number of frames currently buffered.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Gets if no frames are currently buffered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Gets if the buffer has filled up and is handing out frames.
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Gets the statistics of this buffer.
+    #[must_use]
+    pub fn stats(&self) -> JitterStats {
+        self.stats
+    }
+
+    /// Adds a received packet to the buffer.
+    ///
+    /// If the packet carries a copy of the previous frame, and that frame has
+    /// not been received, it is recovered from this packet.
+    pub fn insert<C>(&mut self, packet: VoicePacket<C>) {
+        if let (Some(fec), Some(prev_seq)) = (packet.fec, packet.seq.checked_sub(1)) {
+            if !self.is_late(prev_seq) && !self.frames.contains_key(&prev_seq) {
+                self.stats.recovered += 1;
+                self.frames.insert(prev_seq, fec);
+            }
+        }
+
+        if self.is_late(packet.seq) {
+            self.stats.late += 1;
+        } else {
+            self.frames.insert(packet.seq, packet.frame);
+        }
+
+        while self.frames.len() > self.config.max_buffered_frames {
+            if let Some((seq, _)) = self.frames.pop_first() {
+                self.stats.overflowed += 1;
+                self.next_seq = Some(seq.saturating_add(1));
+            }
+        }
+    }
+
+    /// Takes the next frame to play.
+    ///
+    /// Returns [`None`] if the buffer is not playing yet, in which case the
+    /// app should output silence.
+    pub fn pop(&mut self) -> Option<VoicePlayout> {
+        if !self.playing {
+            if self.frames.len() < self.config.jitter_frames.max(1) {
+                return None;
+            }
+            self.playing = true;
+            // the first frame may arrive after a later one, but once playing
+            // starts, earlier frames are late
+            self.next_seq = self.frames.keys().next().copied();
+        }
+
+        let Some(&first_seq) = self.frames.keys().next() else {
+            self.playing = false;
+            return None;
+        };
+        let seq = self.next_seq.unwrap_or(first_seq);
+        self.next_seq = Some(seq.saturating_add(1));
+        if let Some(frame) = self.frames.remove(&seq) {
+            self.stats.played += 1;
+            Some(VoicePlayout::Frame(frame))
+        } else {
+            self.stats.lost += 1;
+            Some(VoicePlayout::Lost)
+        }
+    }
+
+    fn is_late(&self, seq: u32) -> bool {
+        self.next_seq.is_some_and(|next_seq| seq < next_seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChannelKind;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Lane {
+        Voice,
+    }
+
+    unsafe impl ChannelKey for Lane {
+        const ALL: &'static [Self] = &[Self::Voice];
+
+        fn index(&self) -> usize {
+            0
+        }
+
+        fn kind(&self) -> ChannelKind {
+            ChannelKind::Unreliable
+        }
+    }
+
+    fn packets(n: usize) -> Vec<VoicePacket<Lane>> {
+        let mut sender = VoiceSender::new(Lane::Voice, VoiceConfig::default());
+        for i in 0..n {
+            sender.push(vec![u8::try_from(i).unwrap()]);
+        }
+        let start = Instant::now();
+        (0..n)
+            .map(|i| {
+                let now = start + sender.config().frame_duration * u32::try_from(i).unwrap();
+                let packet = sender.poll(now).unwrap();
+                assert!(sender.poll(now).is_none());
+                packet
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let packet = packets(2).pop().unwrap();
+        assert_eq!(Some(vec![0]), packet.fec);
+        assert_eq!(960, packet.timestamp);
+        let bytes = packet.try_into_bytes().unwrap();
+        assert_eq!(Ok(packet), VoicePacket::<Lane>::try_from_bytes(&bytes));
+        assert_eq!(
+            Err(VoiceDecodeError::Truncated),
+            VoicePacket::<Lane>::try_from_bytes(&bytes[..bytes.len() - 1])
+        );
+    }
+
+    #[test]
+    fn reorder_and_recover() {
+        let mut packets = packets(5);
+        let mut buffer = JitterBuffer::new(VoiceConfig::default());
+        packets.remove(1);
+        packets.swap(0, 1);
+        for packet in packets {
+            buffer.insert(packet);
+        }
+
+        for i in 0..5 {
+            assert_eq!(Some(VoicePlayout::Frame(vec![i])), buffer.pop());
+        }
+        assert_eq!(None, buffer.pop());
+        assert_eq!(1, buffer.stats().recovered);
+    }
+
+    #[test]
+    fn lost_and_late() {
+        let mut packets = packets(5);
+        let mut buffer = JitterBuffer::new(VoiceConfig::default());
+        let lost = packets.remove(1);
+        for packet in &mut packets {
+            packet.fec = None;
+        }
+        for packet in packets {
+            buffer.insert(packet);
+        }
+
+        assert_eq!(Some(VoicePlayout::Frame(vec![0])), buffer.pop());
+        assert_eq!(Some(VoicePlayout::Lost), buffer.pop());
+        buffer.insert(lost);
+        assert_eq!(1, buffer.stats().late);
+        assert_eq!(Some(VoicePlayout::Frame(vec![2])), buffer.pop());
+    }
+}
@@ -0,0 +1,92 @@
+//! Tests the `NameTagged` derive macro and the `Tagged` wire mode.
+
+#![cfg(feature = "bincode")]
+
+use aeronet::{name_tag, NameTagged, Tagged, TaggedError, TryFromBytes, TryIntoBytes};
+
+#[derive(Debug, Clone, PartialEq, NameTagged)]
+struct AppMessage1 {
+    id: u32,
+    name: String,
+}
+
+mod v1 {
+    use aeronet::NameTagged;
+
+    #[derive(Debug, Clone, PartialEq, NameTagged)]
+    pub enum AppMessage {
+        Move(f32),
+        Chat { msg: String },
+        Shoot,
+    }
+}
+
+mod v2 {
+    use aeronet::NameTagged;
+
+    #[derive(Debug, Clone, PartialEq, NameTagged)]
+    pub enum AppMessage {
+        Shoot,
+        Emote(u8),
+        Move(f32),
+        #[wire_name("Chat")]
+        Say {
+            msg: String,
+        },
+    }
+}
+
+#[test]
+fn derive_on_struct() {
+    assert_eq!(&[name_tag("AppMessage1")], AppMessage1::NAME_TAGS);
+    let message = AppMessage1 {
+        id: 3,
+        name: "a".into(),
+    };
+    assert_eq!(name_tag("AppMessage1"), message.name_tag());
+
+    let bytes = Tagged(message.clone()).try_into_bytes().unwrap();
+    assert_eq!(
+        message,
+        Tagged::<AppMessage1>::try_from_bytes(&bytes).unwrap().0
+    );
+}
+
+#[test]
+fn derive_on_enum() {
+    assert_eq!(
+        &[name_tag("Move"), name_tag("Chat"), name_tag("Shoot")],
+        v1::AppMessage::NAME_TAGS
+    );
+    assert_eq!(name_tag("Move"), v1::AppMessage::Move(1.0).name_tag());
+    assert_eq!(
+        name_tag("Chat"),
+        v2::AppMessage::Say { msg: "a".into() }.name_tag()
+    );
+}
+
+#[test]
+fn variants_reordered() {
+    let bytes = Tagged(v1::AppMessage::Move(1.5)).try_into_bytes().unwrap();
+    let message = Tagged::<v2::AppMessage>::try_from_bytes(&bytes).unwrap();
+    assert_eq!(v2::AppMessage::Move(1.5), message.0);
+
+    let bytes = Tagged(v1::AppMessage::Chat { msg: "hi".into() })
+        .try_into_bytes()
+        .unwrap();
+    let message = Tagged::<v2::AppMessage>::try_from_bytes(&bytes).unwrap();
+    assert_eq!(v2::AppMessage::Say { msg: "hi".into() }, message.0);
+
+    let bytes = Tagged(v2::AppMessage::Shoot).try_into_bytes().unwrap();
+    let message = Tagged::<v1::AppMessage>::try_from_bytes(&bytes).unwrap();
+    assert_eq!(v1::AppMessage::Shoot, message.0);
+}
+
+#[test]
+fn unknown_variant() {
+    let bytes = Tagged(v2::AppMessage::Emote(1)).try_into_bytes().unwrap();
+    let result = Tagged::<v1::AppMessage>::try_from_bytes(&bytes);
+    assert!(matches!(result, Err(TaggedError::UnknownVariant(tag)) if tag == name_tag("Emote")));
+    let result = Tagged::<v1::AppMessage>::try_from_bytes(&[0, 1]);
+    assert!(matches!(result, Err(TaggedError::MissingHeader(2))));
+}
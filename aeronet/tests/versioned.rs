@@ -0,0 +1,36 @@
+//! Tests the `VariantTags` derive macro.
+
+use aeronet::VariantTags;
+
+#[derive(Debug, Clone, VariantTags)]
+#[message_version(3)]
+struct AppMessage1;
+
+#[allow(dead_code)] // the fields are only there to test the derive
+#[derive(Debug, Clone, VariantTags)]
+#[message_version(2)]
+enum AppMessage2 {
+    Move(f32),
+    #[variant_tag(4)]
+    Emote(u8),
+    Chat {
+        msg: String,
+    },
+}
+
+#[test]
+fn derive_on_struct() {
+    assert_eq!(3, AppMessage1::VERSION);
+    assert_eq!(&[0], AppMessage1::TAGS);
+    assert_eq!(0, AppMessage1.variant_tag());
+}
+
+#[test]
+fn derive_on_enum() {
+    assert_eq!(2, AppMessage2::VERSION);
+    assert_eq!(&[0, 4, 2], AppMessage2::TAGS);
+    assert_eq!(0, AppMessage2::Move(1.0).variant_tag());
+    assert_eq!(4, AppMessage2::Emote(1).variant_tag());
+    let message = AppMessage2::Chat { msg: "a".into() };
+    assert_eq!(2, message.variant_tag());
+}
@@ -0,0 +1,71 @@
+//! Messages/sec of each transport, echoing messages between a server and its
+//! clients.
+
+// `criterion_group!` generates an undocumented function
+#![allow(missing_docs)]
+
+use std::time::{Duration, Instant};
+
+use aeronet::{TransportClient, TransportServer};
+use aeronet_bench::{BenchProtocol, Echo, Recorder};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const CLIENTS: [usize; 2] = [1, 8];
+const BATCH: usize = 16;
+const PAYLOAD_LEN: usize = 64;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn run<S, C>(echo: &mut Echo<S, C>, iters: u64) -> Duration
+where
+    S: TransportServer<BenchProtocol>,
+    S::Error: std::error::Error,
+    C: TransportClient<BenchProtocol>,
+    C::Error: std::error::Error,
+{
+    let mut recorder = Recorder::new();
+    let start = Instant::now();
+    for _ in 0..iters {
+        echo.round(BATCH, &mut recorder, TIMEOUT)
+            .expect("failed to echo messages");
+    }
+    start.elapsed()
+}
+
+fn channel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("channel");
+    for clients in CLIENTS {
+        let mut echo = aeronet_bench::channel(clients, PAYLOAD_LEN);
+        echo.connect(TIMEOUT).expect("failed to connect clients");
+
+        group.throughput(Throughput::Elements((clients * BATCH) as u64));
+        group.bench_function(BenchmarkId::from_parameter(clients), |b| {
+            b.iter_custom(|iters| run(&mut echo, iters));
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "webtransport")]
+fn webtransport(c: &mut Criterion) {
+    use aeronet_bench::webtransport;
+
+    let rt = webtransport::runtime().expect("failed to create runtime");
+    let mut group = c.benchmark_group("webtransport");
+    for clients in CLIENTS {
+        let mut echo = webtransport::webtransport(&rt, clients, PAYLOAD_LEN, TIMEOUT)
+            .expect("failed to open server");
+        echo.connect(TIMEOUT).expect("failed to connect clients");
+
+        group.throughput(Throughput::Elements((clients * BATCH) as u64));
+        group.bench_function(BenchmarkId::from_parameter(clients), |b| {
+            b.iter_custom(|iters| run(&mut echo, iters));
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "webtransport"))]
+fn webtransport(_: &mut Criterion) {}
+
+criterion_group!(benches, channel, webtransport);
+criterion_main!(benches);
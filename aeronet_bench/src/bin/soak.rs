@@ -0,0 +1,113 @@
+//! Runs the echo loop of a transport for a period of time, printing a report
+//! every second.
+//!
+//! See the crate README for the flags.
+
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use aeronet::{TransportClient, TransportServer};
+use aeronet_bench::{BenchProtocol, CountingAlloc, Echo, Recorder};
+use anyhow::{bail, Context, Result};
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Args {
+    transport: String,
+    clients: usize,
+    duration: Duration,
+    payload: usize,
+    batch: usize,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut args = Self {
+            transport: "channel".to_owned(),
+            clients: 4,
+            duration: Duration::from_secs(10),
+            payload: 64,
+            batch: 16,
+        };
+
+        let mut iter = env::args().skip(1);
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .with_context(|| format!("missing value for `{flag}`"))?;
+            match flag.as_str() {
+                "--transport" => args.transport = value,
+                "--clients" => args.clients = number(&flag, &value)?,
+                "--duration" => args.duration = Duration::from_secs(number(&flag, &value)? as u64),
+                "--payload" => args.payload = number(&flag, &value)?,
+                "--batch" => args.batch = number(&flag, &value)?,
+                _ => bail!("unknown flag `{flag}`"),
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn number(flag: &str, value: &str) -> Result<usize> {
+    value
+        .parse()
+        .with_context(|| format!("invalid value for `{flag}`: `{value}`"))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+    println!(
+        "Soaking {} with {} clients for {:?}, {} byte payloads in batches of {}",
+        args.transport, args.clients, args.duration, args.payload, args.batch
+    );
+
+    match args.transport.as_str() {
+        "channel" => soak(
+            &mut aeronet_bench::channel(args.clients, args.payload),
+            &args,
+        ),
+        #[cfg(feature = "webtransport")]
+        "webtransport" => {
+            use aeronet_bench::webtransport;
+
+            let rt = webtransport::runtime()?;
+            let mut echo = webtransport::webtransport(&rt, args.clients, args.payload, TIMEOUT)?;
+            soak(&mut echo, &args)
+        }
+        transport => bail!("unknown transport `{transport}`"),
+    }
+}
+
+fn soak<S, C>(echo: &mut Echo<S, C>, args: &Args) -> Result<()>
+where
+    S: TransportServer<BenchProtocol>,
+    S::Error: std::error::Error,
+    C: TransportClient<BenchProtocol>,
+    C::Error: std::error::Error,
+{
+    echo.connect(TIMEOUT).context("failed to connect clients")?;
+
+    let start = Instant::now();
+    let mut next_report = start + REPORT_INTERVAL;
+    let mut total = Recorder::new();
+    let mut interval = Recorder::new();
+    while start.elapsed() < args.duration {
+        echo.round(args.batch, &mut interval, TIMEOUT)?;
+        if Instant::now() >= next_report {
+            next_report += REPORT_INTERVAL;
+            total.merge(&interval);
+            println!("{}", interval.finish());
+        }
+    }
+    total.merge(&interval);
+    println!("---");
+    println!("{}", total.finish());
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+use aeronet::{ClientEvent, ServerEvent, TransportClient, TransportServer};
+use anyhow::{bail, Result};
+
+use crate::{BenchMessage, BenchProtocol, Recorder};
+
+/// Server and clients of a single transport, where the clients send messages
+/// which the server echoes back.
+///
+/// This only uses the generic [`TransportServer`] and [`TransportClient`] API,
+/// so every transport is measured doing the same work. Both sides are polled
+/// from the current thread in a busy loop; transports with an async backend
+/// must have it running elsewhere.
+#[derive(Debug)]
+pub struct Echo<S, C> {
+    /// The server which echoes messages back.
+    pub server: S,
+    /// The clients which send messages and measure their round trip time.
+    pub clients: Vec<C>,
+    started_at: Instant,
+    payload: Vec<u8>,
+    next_seq: u64,
+}
+
+impl<S, C> Echo<S, C>
+where
+    S: TransportServer<BenchProtocol>,
+    S::Error: Error,
+    C: TransportClient<BenchProtocol>,
+    C::Error: Error,
+{
+    /// Creates an echo setup for a server and its clients, which sends
+    /// messages with a payload of `payload_len` bytes.
+    ///
+    /// The clients must already be connecting to the server.
+    #[must_use]
+    pub fn new(server: S, clients: Vec<C>, payload_len: usize) -> Self {
+        Self {
+            server,
+            clients,
+            started_at: Instant::now(),
+            payload: vec![0xa5; payload_len],
+            next_seq: 0,
+        }
+    }
+
+    /// Polls the server and clients until all clients are connected.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a client disconnects, or if not all clients are connected
+    /// after `timeout`.
+    pub fn connect(&mut self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.poll_server()?;
+            self.poll_clients(&mut Recorder::new())?;
+            if self.server.connected_count() == self.clients.len()
+                && self.clients.iter().all(TransportClient::connected)
+            {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "{} of {} clients connected after {timeout:?}",
+                    self.server.connected_count(),
+                    self.clients.len()
+                );
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// Sends `batch` messages from every client, and polls the server and
+    /// clients until all of them have been echoed back, recording their round
+    /// trip times into `recorder`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a message could not be sent, if a client disconnects, or if
+    /// not all messages are echoed back after `timeout`.
+    pub fn round(
+        &mut self,
+        batch: usize,
+        recorder: &mut Recorder,
+        timeout: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        for client in &mut self.clients {
+            for _ in 0..batch {
+                let msg = BenchMessage {
+                    seq: self.next_seq,
+                    sent_at: self.started_at.elapsed(),
+                    payload: self.payload.clone(),
+                };
+                self.next_seq += 1;
+                client.send(msg)?;
+            }
+        }
+
+        let target = recorder.msgs() + batch * self.clients.len();
+        while recorder.msgs() < target {
+            self.poll_server()?;
+            self.poll_clients(recorder)?;
+            if Instant::now() > deadline {
+                bail!(
+                    "{} of {} messages echoed after {timeout:?}",
+                    batch * self.clients.len() - (target - recorder.msgs()),
+                    batch * self.clients.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_server(&mut self) -> Result<()> {
+        for event in self.server.recv() {
+            match event.into() {
+                Some(ServerEvent::Recv { client, msg }) => self.server.send(client, msg)?,
+                Some(ServerEvent::Disconnected { cause, .. }) => {
+                    return Err(anyhow::Error::new(cause).context("client disconnected"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_clients(&mut self, recorder: &mut Recorder) -> Result<()> {
+        for client in &mut self.clients {
+            for event in client.recv() {
+                match event.into() {
+                    Some(ClientEvent::Recv { msg }) => {
+                        recorder.record(self.started_at.elapsed().saturating_sub(msg.sent_at));
+                    }
+                    Some(ClientEvent::Disconnected { cause }) => {
+                        return Err(anyhow::Error::new(cause).context("disconnected from server"));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+#![doc = include_str!("../README.md")]
+
+mod allocs;
+mod echo;
+mod protocol;
+mod report;
+#[cfg(feature = "webtransport")]
+pub mod webtransport;
+
+pub use {allocs::*, echo::*, protocol::*, report::*};
+
+use aeronet_channel::{ChannelClient, ChannelServer};
+
+/// [`Echo`] over the in-memory channel transport.
+pub type ChannelEcho = Echo<ChannelServer<BenchProtocol>, ChannelClient<BenchProtocol>>;
+
+/// Creates an [`Echo`] over the in-memory channel transport, with `clients`
+/// clients connected to the server.
+#[must_use]
+pub fn channel(clients: usize, payload_len: usize) -> ChannelEcho {
+    let mut server = ChannelServer::new();
+    let clients = (0..clients)
+        .map(|_| ChannelClient::connected(&mut server).0)
+        .collect();
+    Echo::new(server, clients, payload_len)
+}
@@ -0,0 +1,85 @@
+use std::{convert::Infallible, time::Duration};
+
+use aeronet::{ChannelKey, OnChannel, TransportProtocol, TryFromBytes, TryIntoBytes};
+
+/// Length in bytes of the header of a serialized [`BenchMessage`].
+pub const BENCH_HEADER_LEN: usize = 16;
+
+/// Channel which benchmark messages are sent on.
+///
+/// This is reliable, so that every message sent is echoed back and can be
+/// counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChannelKey)]
+#[channel_kind(ReliableOrdered)]
+pub struct BenchLane;
+
+/// Message sent by a client and echoed back by the server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, OnChannel)]
+#[channel_type(BenchLane)]
+#[on_channel(BenchLane)]
+pub struct BenchMessage {
+    /// Sequence number of the message, unique per run.
+    pub seq: u64,
+    /// When the message was first sent, relative to the start of the run.
+    pub sent_at: Duration,
+    /// Padding which makes up the size of the message.
+    pub payload: Vec<u8>,
+}
+
+/// Error that occurs when deserializing a [`BenchMessage`].
+#[derive(Debug, thiserror::Error)]
+#[error("message of {0} bytes is too short for a header")]
+pub struct MissingHeader(pub usize);
+
+impl TryIntoBytes for BenchMessage {
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        // the round trip of a single message never takes 500 years
+        #[allow(clippy::cast_possible_truncation)]
+        let sent_at = self.sent_at.as_nanos() as u64;
+        let mut buf = Vec::with_capacity(BENCH_HEADER_LEN + self.payload.len());
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&sent_at.to_be_bytes());
+        buf.extend_from_slice(&self.payload);
+        Ok(buf)
+    }
+}
+
+impl TryFromBytes for BenchMessage {
+    type Error = MissingHeader;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        if buf.len() < BENCH_HEADER_LEN {
+            return Err(MissingHeader(buf.len()));
+        }
+        let (seq, rest) = buf.split_at(8);
+        let (sent_at, payload) = rest.split_at(8);
+        Ok(Self {
+            seq: u64::from_be_bytes(seq.try_into().expect("slice is 8 bytes long")),
+            sent_at: Duration::from_nanos(u64::from_be_bytes(
+                sent_at.try_into().expect("slice is 8 bytes long"),
+            )),
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+/// Protocol used by all benchmarks.
+#[derive(Debug)]
+pub struct BenchProtocol;
+
+impl TransportProtocol for BenchProtocol {
+    type C2S = BenchMessage;
+    type S2C = BenchMessage;
+}
+
+#[cfg(feature = "webtransport")]
+impl aeronet_wt_native::WebTransportProtocol for BenchProtocol {
+    type Channel = BenchLane;
+}
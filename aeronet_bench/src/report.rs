@@ -0,0 +1,134 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use crate::AllocStats;
+
+/// Collects the round trip times of messages, and summarizes them into a
+/// [`Report`].
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    started_at: Instant,
+    allocs_at_start: AllocStats,
+    rtts: Vec<Duration>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    /// Creates a recorder which starts measuring now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            allocs_at_start: AllocStats::now(),
+            rtts: Vec::new(),
+        }
+    }
+
+    /// Records the round trip time of a single message.
+    pub fn record(&mut self, rtt: Duration) {
+        self.rtts.push(rtt);
+    }
+
+    /// Adds the round trip times recorded by `other` to this recorder.
+    ///
+    /// This is useful for keeping a report of a whole run, while also
+    /// reporting on shorter periods of it.
+    pub fn merge(&mut self, other: &Self) {
+        self.rtts.extend_from_slice(&other.rtts);
+    }
+
+    /// Number of messages recorded since the recorder was started.
+    #[must_use]
+    pub fn msgs(&self) -> usize {
+        self.rtts.len()
+    }
+
+    /// Summarizes the messages recorded since the recorder was started, and
+    /// restarts it.
+    pub fn finish(&mut self) -> Report {
+        let elapsed = self.started_at.elapsed();
+        let allocs = AllocStats::now().since(self.allocs_at_start);
+        let mut rtts = std::mem::take(&mut self.rtts);
+        rtts.sort_unstable();
+        *self = Self {
+            rtts: Vec::with_capacity(rtts.len()),
+            ..Self::new()
+        };
+
+        Report {
+            msgs: rtts.len(),
+            elapsed,
+            p50: percentile(&rtts, 50),
+            p99: percentile(&rtts, 99),
+            max: rtts.last().copied().unwrap_or_default(),
+            allocs,
+        }
+    }
+}
+
+// nearest-rank percentile of a sorted slice
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Summary of the messages echoed over a period of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Report {
+    /// Number of messages which completed a round trip.
+    pub msgs: usize,
+    /// Length of the period.
+    pub elapsed: Duration,
+    /// Median round trip time.
+    pub p50: Duration,
+    /// 99th percentile round trip time.
+    pub p99: Duration,
+    /// Longest round trip time.
+    pub max: Duration,
+    /// Heap allocations made by the whole process during the period.
+    ///
+    /// See [`CountingAlloc`].
+    ///
+    /// [`CountingAlloc`]: crate::CountingAlloc
+    pub allocs: AllocStats,
+}
+
+impl Report {
+    /// Number of messages which completed a round trip per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn msgs_per_sec(&self) -> f64 {
+        self.msgs as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Average number of heap allocations made per message.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn allocs_per_msg(&self) -> f64 {
+        self.allocs.count as f64 / self.msgs.max(1) as f64
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>10.0} msgs/s | rtt p50 {:>9.1?} p99 {:>9.1?} max {:>9.1?} | {:>6.1} allocs/msg",
+            self.msgs_per_sec(),
+            self.p50,
+            self.p99,
+            self.max,
+            self.allocs_per_msg(),
+        )
+    }
+}
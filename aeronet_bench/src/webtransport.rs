@@ -0,0 +1,90 @@
+//! Benchmarks of the native WebTransport transport over loopback.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use aeronet::TransportServer;
+use aeronet_wt_native::{Identity, WebTransportClient, WebTransportServer};
+use anyhow::{bail, Context, Result};
+use tokio::runtime::Runtime;
+use wtransport::{ClientConfig, ServerConfig};
+
+use crate::{BenchProtocol, Echo};
+
+/// [`Echo`] over the native WebTransport transport.
+pub type WebTransportEcho =
+    Echo<WebTransportServer<BenchProtocol>, WebTransportClient<BenchProtocol>>;
+
+/// Creates an [`Echo`] over the native WebTransport transport, with a server
+/// listening on a random loopback port and `clients` clients connecting to it.
+///
+/// The backends of the server and clients run on `rt`.
+///
+/// # Errors
+///
+/// Errors if the certificate could not be generated, or if the server could
+/// not be opened within `timeout`.
+pub fn webtransport(
+    rt: &Runtime,
+    clients: usize,
+    payload_len: usize,
+    timeout: Duration,
+) -> Result<WebTransportEcho> {
+    let cert = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_owned()])
+        .context("failed to generate certificate")?;
+    let identity = Identity::from_der(
+        vec![cert
+            .serialize_der()
+            .context("failed to serialize certificate")?],
+        cert.serialize_private_key_der(),
+    );
+
+    let config = ServerConfig::builder()
+        .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        .with_certificate(identity.into())
+        .build();
+    let (mut server, backend) = WebTransportServer::opening(config);
+    rt.spawn(backend);
+
+    let deadline = Instant::now() + timeout;
+    let port = loop {
+        // drives the server from opening to open
+        server.recv().for_each(drop);
+        if let Ok(Ok(addr)) = server.local_addr() {
+            break addr.port();
+        }
+        if Instant::now() > deadline {
+            bail!("server not opened after {timeout:?}");
+        }
+        std::thread::yield_now();
+    };
+
+    let clients = (0..clients)
+        .map(|_| {
+            let config = ClientConfig::builder()
+                .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+                .with_no_cert_validation()
+                .build();
+            let (client, backend) =
+                WebTransportClient::connecting(config, format!("https://127.0.0.1:{port}"));
+            rt.spawn(backend);
+            client
+        })
+        .collect();
+
+    Ok(Echo::new(server, clients, payload_len))
+}
+
+/// Creates a multi-threaded runtime for running WebTransport backends on.
+///
+/// # Errors
+///
+/// Errors if the runtime could not be created.
+pub fn runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("failed to create async runtime")
+}
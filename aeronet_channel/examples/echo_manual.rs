@@ -121,6 +121,7 @@ fn update_client<const N: usize>(mut client: ResMut<Client<N>>, mut state: ResMu
                 "Disconnected: {:#}",
                 aeronet::error::as_pretty(&cause)
             )),
+            ServerEvent::Custom { .. } => {}
         }
     }
 }
@@ -1,5 +1,7 @@
+use std::{collections::VecDeque, num::NonZeroUsize};
+
 use aeronet::{ServerEvent, TransportClient, TransportProtocol};
-use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use crossbeam_channel::{Receiver, Sender, TryRecvError, TrySendError};
 use derivative::Derivative;
 
 use crate::{server, ChannelError, ChannelServer, ClientKey};
@@ -45,11 +47,16 @@ where
 
     /// Creates and connects a new client to an existing server.
     ///
-    /// This will raise a [`ClientEvent::Connected`].
+    /// This will raise a [`ClientEvent::Connected`] on the next call to
+    /// [`TransportClient::recv`], and a [`ServerEvent::Connected`] on the
+    /// server's next call to [`TransportServer::recv`]. Like with networked
+    /// transports, each side only considers the connection open, and can only
+    /// send messages over it, once it has raised its event.
     ///
     /// To remove this client from this server in the future, pass the key
     /// returned from this function into [`TransportServer::disconnect`].
     ///
+    /// [`TransportServer::recv`]: aeronet::TransportServer::recv
     /// [`TransportServer::disconnect`]: aeronet::TransportServer::disconnect
     pub fn connected(server: &mut ChannelServer<P>) -> (Self, ClientKey) {
         let (server, key) = ConnectedClient::new(server);
@@ -86,6 +93,8 @@ impl<P> TransportClient<P> for ChannelClient<P>
 where
     P: TransportProtocol,
 {
+    const TRANSPORT_NAME: &'static str = "channel";
+
     type Error = ChannelError;
 
     type ConnectionInfo = ();
@@ -93,9 +102,9 @@ where
     type Event = ClientEvent<P>;
 
     fn connection_info(&self) -> Option<Self::ConnectionInfo> {
-        match self.state {
-            State::Disconnected => None,
-            State::Connected(_) => Some(()),
+        match &self.state {
+            State::Connected(client) if client.sent_connect_event => Some(()),
+            State::Disconnected | State::Connected(_) => None,
         }
     }
 
@@ -143,7 +152,6 @@ where
     send_c2s: Sender<P::C2S>,
     #[derivative(Debug = "ignore")]
     recv_s2c: Receiver<P::S2C>,
-    #[derivative(Debug = "ignore")]
     sent_connect_event: bool,
 }
 
@@ -152,10 +160,15 @@ where
     P: TransportProtocol,
 {
     fn new(server: &mut ChannelServer<P>) -> (Self, ClientKey) {
-        let (send_c2s, recv_c2s) = crossbeam_channel::unbounded::<P::C2S>();
-        let (send_s2c, recv_s2c) = crossbeam_channel::unbounded::<P::S2C>();
-
-        let remote_state = server::ClientState { send_s2c, recv_c2s };
+        let (send_c2s, recv_c2s) = channel::<P::C2S>(server.capacity);
+        let (send_s2c, recv_s2c) = channel::<P::S2C>(server.capacity);
+
+        let remote_state = server::ClientState {
+            send_s2c,
+            recv_c2s,
+            outbox: VecDeque::new(),
+            connected: false,
+        };
         let key = server.clients.insert(remote_state);
         server
             .event_buf
@@ -172,10 +185,14 @@ where
     }
 
     fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), ChannelError> {
+        if !self.sent_connect_event {
+            return Err(ChannelError::Disconnected);
+        }
         let msg = msg.into();
-        self.send_c2s
-            .send(msg)
-            .map_err(|_| ChannelError::Disconnected)
+        self.send_c2s.try_send(msg).map_err(|err| match err {
+            TrySendError::Full(_) => ChannelError::Full,
+            TrySendError::Disconnected(_) => ChannelError::Disconnected,
+        })
     }
 
     fn recv(&mut self) -> (Vec<ClientEvent<P>>, Result<(), ChannelError>) {
@@ -199,3 +216,10 @@ where
         (events, Ok(()))
     }
 }
+
+fn channel<T>(capacity: Option<NonZeroUsize>) -> (Sender<T>, Receiver<T>) {
+    match capacity {
+        Some(capacity) => crossbeam_channel::bounded(capacity.get()),
+        None => crossbeam_channel::unbounded(),
+    }
+}
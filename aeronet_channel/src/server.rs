@@ -1,7 +1,7 @@
-use std::mem;
+use std::{collections::VecDeque, mem, num::NonZeroUsize};
 
-use aeronet::{TransportProtocol, TransportServer};
-use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use aeronet::{CustomEvent, SendToManyResult, TransportProtocol, TransportServer};
+use crossbeam_channel::{Receiver, Sender, TryRecvError, TrySendError};
 use derivative::Derivative;
 use slotmap::SlotMap;
 
@@ -23,6 +23,8 @@ where
     pub(super) clients: SlotMap<ClientKey, ClientState<P>>,
     #[derivative(Debug = "ignore")]
     pub(super) event_buf: Vec<ServerEvent<P>>,
+    deterministic: Option<Rng>,
+    pub(super) capacity: Option<NonZeroUsize>,
 }
 
 #[derive(Debug)]
@@ -32,6 +34,11 @@ where
 {
     pub(super) send_s2c: Sender<P::S2C>,
     pub(super) recv_c2s: Receiver<P::C2S>,
+    /// Messages sent to this client which are not visible to it yet, in
+    /// deterministic mode.
+    pub(super) outbox: VecDeque<P::S2C>,
+    /// Whether the [`ServerEvent::Connected`] of this client has been raised.
+    pub(super) connected: bool,
 }
 
 impl<P> ChannelServer<P>
@@ -49,14 +56,163 @@ where
         Self {
             clients: SlotMap::default(),
             event_buf: Vec::default(),
+            deterministic: None,
+            capacity: None,
+        }
+    }
+
+    /// Creates a new server with no clients connected, in deterministic mode.
+    ///
+    /// In this mode, messages sent between the server and its clients only
+    /// become visible to the other side when [`ChannelServer::pump`] is
+    /// called. Messages received from different clients in the same pump are
+    /// interleaved in an order determined by `seed`, while messages from the
+    /// same client are always received in the order they were sent.
+    ///
+    /// Given the same seed and the same sequence of operations, this server
+    /// will always produce the same sequence of events, which makes it suitable
+    /// for e.g. lockstep simulation tests.
+    #[must_use]
+    pub fn deterministic(seed: u64) -> Self {
+        Self {
+            clients: SlotMap::default(),
+            event_buf: Vec::default(),
+            deterministic: Some(Rng(seed)),
+            capacity: None,
+        }
+    }
+
+    /// Gets if this server is in deterministic mode.
+    ///
+    /// See [`ChannelServer::deterministic`].
+    #[must_use]
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic.is_some()
+    }
+
+    /// Gets the capacity of the channels between this server and each of its
+    /// clients, or [`None`] if the channels are unbounded.
+    ///
+    /// See [`ChannelServer::set_capacity`].
+    #[must_use]
+    pub fn capacity(&self) -> Option<NonZeroUsize> {
+        self.capacity
+    }
+
+    /// Sets the capacity of the channels between this server and each of its
+    /// clients, or [`None`] to make them unbounded, which is the default.
+    ///
+    /// Each direction of a connection gets its own channel with this capacity.
+    /// When a channel is full, because the receiving side has not called
+    /// `recv` since the messages were sent, sending fails with
+    /// [`ChannelError::Full`] instead of queueing the message. Use this in
+    /// tests to exercise how your app handles backpressure.
+    ///
+    /// This only applies to clients which connect after this is called.
+    pub fn set_capacity(&mut self, capacity: Option<NonZeroUsize>) {
+        self.capacity = capacity;
+    }
+
+    /// Delivers all messages sent since the last pump, if this server is in
+    /// deterministic mode.
+    ///
+    /// This makes all messages sent by the server visible to the clients, and
+    /// all messages sent by clients visible to the server on its next call to
+    /// [`TransportServer::recv`]. Clients which have disconnected are detected
+    /// here as well.
+    ///
+    /// If the channel to a client is full, the messages which do not fit stay
+    /// buffered until a later pump.
+    ///
+    /// If this server is not in deterministic mode, this does nothing.
+    ///
+    /// See [`ChannelServer::deterministic`].
+    pub fn pump(&mut self) {
+        let Some(rng) = &mut self.deterministic else {
+            return;
+        };
+
+        let mut inbound = Vec::new();
+        let mut disconnected = Vec::new();
+        for (client, state) in &mut self.clients {
+            while let Some(msg) = state.outbox.pop_front() {
+                match state.send_s2c.try_send(msg) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(msg)) => {
+                        state.outbox.push_front(msg);
+                        break;
+                    }
+                    // detected below
+                    Err(TrySendError::Disconnected(_)) => break,
+                }
+            }
+
+            let mut msgs = VecDeque::new();
+            loop {
+                match state.recv_c2s.try_recv() {
+                    Ok(msg) => msgs.push_back(msg),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        disconnected.push(client);
+                        break;
+                    }
+                }
+            }
+            if !msgs.is_empty() {
+                inbound.push((client, msgs));
+            }
+        }
+
+        while !inbound.is_empty() {
+            let index = rng.next_below(inbound.len());
+            let (client, msgs) = &mut inbound[index];
+            let client = *client;
+            if let Some(msg) = msgs.pop_front() {
+                self.event_buf.push(ServerEvent::Recv { client, msg });
+            }
+            if msgs.is_empty() {
+                inbound.remove(index);
+            }
+        }
+
+        for client in disconnected {
+            self.clients.remove(client);
+            self.event_buf.push(ServerEvent::Disconnected {
+                client,
+                cause: ChannelError::Disconnected,
+            });
         }
     }
 }
 
+/// Seeded pseudo-random number generator used in deterministic mode, using the
+/// `SplitMix64` algorithm.
+///
+/// This is not suitable for anything other than picking a reproducible order.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // the result is always below `n`
+    fn next_below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
 impl<P> TransportServer<P> for ChannelServer<P>
 where
     P: TransportProtocol,
 {
+    const TRANSPORT_NAME: &'static str = "channel";
+
     type Client = ClientKey;
 
     type Error = ChannelError;
@@ -66,26 +222,77 @@ where
     type Event = ServerEvent<P>;
 
     fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
-        self.clients.get(client).map(|_| ())
+        self.clients
+            .get(client)
+            .filter(|state| state.connected)
+            .map(|_| ())
     }
 
     fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
-        self.clients.keys()
+        self.clients
+            .iter()
+            .filter(|(_, state)| state.connected)
+            .map(|(client, _)| client)
     }
 
     fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error> {
         let msg = msg.into();
-        let Some(client) = self.clients.get(client) else {
+        let Some(state) = self.clients.get_mut(client) else {
             return Err(ChannelError::NoClient(client));
         };
-        client
-            .send_s2c
-            .send(msg)
-            .map_err(|_| ChannelError::Disconnected)
+        if !state.connected {
+            return Err(ChannelError::NotConnected(client));
+        }
+        if self.deterministic.is_some() {
+            if let Some(capacity) = self.capacity {
+                if state.outbox.len() + state.send_s2c.len() >= capacity.get() {
+                    return Err(ChannelError::Full);
+                }
+            }
+            state.outbox.push_back(msg);
+            return Ok(());
+        }
+        state.send_s2c.try_send(msg).map_err(|err| match err {
+            TrySendError::Full(_) => ChannelError::Full,
+            TrySendError::Disconnected(_) => ChannelError::Disconnected,
+        })
+    }
+
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        // messages are passed to clients as-is, so there is nothing to
+        // serialize; every client but the last gets a clone, and the last one
+        // gets the message itself
+        let msg = msg.into();
+        let mut results = Vec::new();
+        let mut clients = clients.into_iter().peekable();
+        while let Some(client) = clients.next() {
+            if clients.peek().is_none() {
+                results.push((client, self.send(client, msg)));
+                break;
+            }
+            results.push((client, self.send(client, msg.clone())));
+        }
+        results
     }
 
     fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
         let mut events = mem::take(&mut self.event_buf);
+        // the `Connected` events of all new clients are raised now, so that
+        // they come before any other event of those clients
+        for (_, state) in &mut self.clients {
+            state.connected = true;
+        }
+        if self.deterministic.is_some() {
+            // messages are only received in `pump`
+            return events.into_iter();
+        }
 
         let mut to_remove = Vec::new();
         for (client, state) in &self.clients {
@@ -99,6 +306,7 @@ where
                             cause: ChannelError::Disconnected,
                         });
                         to_remove.push(client);
+                        break;
                     }
                 }
             }
@@ -124,4 +332,135 @@ where
             None => Err(ChannelError::NoClient(client)),
         }
     }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        self.event_buf.push(ServerEvent::Custom { event });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aeronet::{ClientEvent, TransportClient};
+
+    use super::*;
+    use crate::ChannelClient;
+
+    struct Protocol;
+
+    impl TransportProtocol for Protocol {
+        type C2S = u32;
+        type S2C = u32;
+    }
+
+    /// Connects a few clients to a deterministic server, sends some messages
+    /// from each, and returns the order that the server received them in, as
+    /// `(client index, message)`.
+    fn delivery_order(seed: u64) -> Vec<(usize, u32)> {
+        let mut server = ChannelServer::<Protocol>::deterministic(seed);
+        let mut clients = (0..4)
+            .map(|_| ChannelClient::connected(&mut server))
+            .collect::<Vec<_>>();
+        assert_eq!(4, server.recv().count());
+
+        for (index, (client, _)) in clients.iter_mut().enumerate() {
+            assert_eq!(1, client.recv().count());
+            for i in 0..8 {
+                client
+                    .send(u32::try_from(index).unwrap() * 100 + i)
+                    .unwrap();
+            }
+        }
+        // nothing is delivered until the server is pumped
+        assert_eq!(0, server.recv().count());
+
+        server.pump();
+        server
+            .recv()
+            .map(|event| match event {
+                ServerEvent::Recv { client, msg } => {
+                    let index = clients.iter().position(|(_, key)| *key == client).unwrap();
+                    (index, msg)
+                }
+                _ => panic!("expected only messages"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_order() {
+        let order = delivery_order(42);
+        assert_eq!(32, order.len());
+        assert_eq!(order, delivery_order(42));
+
+        // messages from the same client are still received in order
+        for index in 0..4 {
+            let msgs = order
+                .iter()
+                .filter(|(from, _)| *from == index)
+                .map(|(_, msg)| *msg)
+                .collect::<Vec<_>>();
+            let expected = (0..8)
+                .map(|i| u32::try_from(index).unwrap() * 100 + i)
+                .collect::<Vec<_>>();
+            assert_eq!(expected, msgs);
+        }
+    }
+
+    #[test]
+    fn seed_changes_order() {
+        let order = delivery_order(0);
+        assert!((1..16).any(|seed| delivery_order(seed) != order));
+    }
+
+    #[test]
+    fn send_to_many_reports_each_client() {
+        let mut server = ChannelServer::<Protocol>::new();
+        let mut clients = (0..2)
+            .map(|_| ChannelClient::connected(&mut server))
+            .collect::<Vec<_>>();
+        assert_eq!(2, server.recv().count());
+        // not connected until the server's next `recv`
+        let (_, pending) = ChannelClient::connected(&mut server);
+
+        let keys = clients.iter().map(|(_, key)| *key).collect::<Vec<_>>();
+        let results = server.send_to_many(keys.iter().copied().chain([pending]), 5u32);
+        assert_eq!(3, results.len());
+        assert!(results[..2].iter().all(|(_, result)| result.is_ok()));
+        assert!(matches!(
+            results[2],
+            (key, Err(ChannelError::NotConnected(_))) if key == pending
+        ));
+
+        for (client, _) in &mut clients {
+            let msgs = client
+                .recv()
+                .filter_map(|event| match event {
+                    ClientEvent::Recv { msg } => Some(msg),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(vec![5], msgs);
+        }
+    }
+
+    #[test]
+    fn server_messages_wait_for_pump() {
+        let mut server = ChannelServer::<Protocol>::deterministic(0);
+        let (mut client, key) = ChannelClient::connected(&mut server);
+        assert_eq!(1, server.recv().count());
+        assert_eq!(1, client.recv().count());
+
+        server.send(key, 1u32).unwrap();
+        assert_eq!(0, client.recv().count());
+
+        server.pump();
+        let msgs = client
+            .recv()
+            .filter_map(|event| match event {
+                ClientEvent::Recv { msg } => Some(msg),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1], msgs);
+    }
 }
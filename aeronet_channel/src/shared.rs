@@ -15,10 +15,27 @@ pub enum ChannelError {
     /// A client with the given key does not exist.
     #[error("no client with key {0:?}")]
     NoClient(ClientKey),
-    /// The other side disconnected from this side, due to the other side being
-    /// dropped and closing the MPSC channels.
+    /// This side is not connected, or the other side disconnected from this
+    /// side, due to the other side being dropped and closing the MPSC channels.
     #[error("disconnected")]
     Disconnected,
+    /// Attempted to send a message to a client which is not connected yet,
+    /// because its [`ServerEvent::Connected`] has not been raised.
+    ///
+    /// [`ServerEvent::Connected`]: aeronet::ServerEvent::Connected
+    #[error("client {0:?} has not connected yet")]
+    NotConnected(ClientKey),
+    /// Attempted to send a message while the bounded channel to the other side
+    /// is full, because the other side has not received the messages sent
+    /// before it.
+    ///
+    /// The message is not sent, but the connection stays open.
+    ///
+    /// See [`ChannelServer::set_capacity`].
+    ///
+    /// [`ChannelServer::set_capacity`]: crate::ChannelServer::set_capacity
+    #[error("channel is full")]
+    Full,
     /// The client was forcefully disconnected by the server.
     #[error("force disconnect")]
     ForceDisconnect,
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+/// Settings of a [`ChatServer`].
+///
+/// [`ChatServer`]: crate::ChatServer
+#[derive(Debug, Clone)]
+pub struct ChatConfig {
+    /// Maximum number of characters in the text of a message or whisper.
+    ///
+    /// By default this is 256.
+    pub max_message_len: usize,
+    /// Maximum number of clients which a single client can mute.
+    ///
+    /// By default this is 128.
+    pub max_mutes: usize,
+    /// Number of messages which a client can send in a burst before being
+    /// rate limited.
+    ///
+    /// By default this is 5.
+    pub flood_burst: u32,
+    /// Time after which a client can send one more message, up to
+    /// [`ChatConfig::flood_burst`] messages.
+    ///
+    /// If this is zero, clients are never rate limited. By default this is 1
+    /// second.
+    pub flood_refill: Duration,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            max_message_len: 256,
+            max_mutes: 128,
+            flood_burst: 5,
+            flood_refill: Duration::from_secs(1),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#![doc = include_str!("../README.md")]
+
+mod config;
+mod msg;
+mod server;
+
+pub use {config::*, msg::*, server::*};
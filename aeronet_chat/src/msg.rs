@@ -0,0 +1,328 @@
+use aeronet::{TryFromBytes, TryIntoBytes};
+
+/// Maximum length in bytes of a single string field of a chat message.
+pub const MAX_FIELD_LEN: usize = u16::MAX as usize;
+
+/// Chat message sent from a client to the server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChatC2S {
+    /// Join a channel, to receive the messages said in it.
+    Join {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// Leave a channel.
+    Leave {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// Say something in a channel which this client has joined.
+    Say {
+        /// Name of the channel.
+        channel: String,
+        /// Text of the message.
+        text: String,
+    },
+    /// Send a private message to another client.
+    Whisper {
+        /// Name of the recipient.
+        to: String,
+        /// Text of the message.
+        text: String,
+    },
+    /// Stop receiving messages and whispers from another client.
+    Mute {
+        /// Name of the client to mute.
+        name: String,
+    },
+    /// Receive messages and whispers from a muted client again.
+    Unmute {
+        /// Name of the client to unmute.
+        name: String,
+    },
+}
+
+/// Chat message sent from the server to a client.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChatS2C {
+    /// This client joined a channel.
+    Joined {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// This client left a channel, either because it asked to or because the
+    /// channel was removed.
+    Left {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// Someone said something in a channel which this client has joined.
+    Message {
+        /// Name of the channel.
+        channel: String,
+        /// Name of the sender.
+        from: String,
+        /// Text of the message.
+        text: String,
+    },
+    /// Another client sent a private message to this client.
+    Whisper {
+        /// Name of the sender.
+        from: String,
+        /// Text of the message.
+        text: String,
+    },
+    /// The server sent an announcement.
+    Announcement {
+        /// Text of the announcement.
+        text: String,
+    },
+    /// A message sent by this client was rejected.
+    Rejected(ChatRejection),
+}
+
+/// Reason why the server rejected a [`ChatC2S`] message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum ChatRejection {
+    /// The client sent too many messages in a short time.
+    #[error("sending messages too quickly")]
+    RateLimited,
+    /// The text of the message was too long.
+    #[error("message is longer than {max} characters")]
+    TooLong {
+        /// Maximum number of characters in a message.
+        max: u32,
+    },
+    /// The channel does not exist.
+    #[error("no channel named `{channel}`")]
+    UnknownChannel {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// The client tried to say something in a channel it has not joined.
+    #[error("not in channel `{channel}`")]
+    NotInChannel {
+        /// Name of the channel.
+        channel: String,
+    },
+    /// There is no client with the given name.
+    #[error("no client named `{name}`")]
+    UnknownClient {
+        /// Name of the client.
+        name: String,
+    },
+    /// The client's mute list is full.
+    #[error("cannot mute more than {max} clients")]
+    TooManyMutes {
+        /// Maximum number of clients which can be muted.
+        max: u32,
+    },
+}
+
+/// Error that occurs when encoding or decoding a chat message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChatWireError {
+    /// A field was longer than [`MAX_FIELD_LEN`] bytes.
+    #[error("field is {0} bytes long, longer than {MAX_FIELD_LEN} bytes")]
+    FieldTooLong(usize),
+    /// The message was cut off.
+    #[error("message is truncated")]
+    Truncated,
+    /// The message had an unknown tag.
+    #[error("invalid tag {0}")]
+    InvalidTag(u8),
+    /// A field was not valid UTF-8.
+    #[error("field is not valid UTF-8")]
+    InvalidUtf8,
+}
+
+// encoding
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn new(tag: u8) -> Self {
+        Self(vec![tag])
+    }
+
+    fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn str(mut self, value: &str) -> Result<Self, ChatWireError> {
+        let len =
+            u16::try_from(value.len()).map_err(|_| ChatWireError::FieldTooLong(value.len()))?;
+        self.0.extend_from_slice(&len.to_be_bytes());
+        self.0.extend_from_slice(value.as_bytes());
+        Ok(self)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], ChatWireError> {
+        if self.0.len() < len {
+            return Err(ChatWireError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChatWireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ChatWireError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn str(&mut self) -> Result<String, ChatWireError> {
+        let len = self.take(2)?;
+        let len = usize::from(u16::from_be_bytes([len[0], len[1]]));
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| ChatWireError::InvalidUtf8)
+    }
+}
+
+impl TryIntoBytes for ChatC2S {
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = ChatWireError;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let writer = match self {
+            Self::Join { channel } => Writer::new(0).str(channel)?,
+            Self::Leave { channel } => Writer::new(1).str(channel)?,
+            Self::Say { channel, text } => Writer::new(2).str(channel)?.str(text)?,
+            Self::Whisper { to, text } => Writer::new(3).str(to)?.str(text)?,
+            Self::Mute { name } => Writer::new(4).str(name)?,
+            Self::Unmute { name } => Writer::new(5).str(name)?,
+        };
+        Ok(writer.0)
+    }
+}
+
+impl TryFromBytes for ChatC2S {
+    type Error = ChatWireError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let mut r = Reader(buf);
+        Ok(match r.u8()? {
+            0 => Self::Join { channel: r.str()? },
+            1 => Self::Leave { channel: r.str()? },
+            2 => Self::Say {
+                channel: r.str()?,
+                text: r.str()?,
+            },
+            3 => Self::Whisper {
+                to: r.str()?,
+                text: r.str()?,
+            },
+            4 => Self::Mute { name: r.str()? },
+            5 => Self::Unmute { name: r.str()? },
+            tag => return Err(ChatWireError::InvalidTag(tag)),
+        })
+    }
+}
+
+impl TryIntoBytes for ChatS2C {
+    type Output<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+
+    type Error = ChatWireError;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let writer = match self {
+            Self::Joined { channel } => Writer::new(0).str(channel)?,
+            Self::Left { channel } => Writer::new(1).str(channel)?,
+            Self::Message {
+                channel,
+                from,
+                text,
+            } => Writer::new(2).str(channel)?.str(from)?.str(text)?,
+            Self::Whisper { from, text } => Writer::new(3).str(from)?.str(text)?,
+            Self::Announcement { text } => Writer::new(4).str(text)?,
+            Self::Rejected(reason) => {
+                let writer = Writer::new(5);
+                match reason {
+                    ChatRejection::RateLimited => writer.u8(0),
+                    ChatRejection::TooLong { max } => writer.u8(1).u32(*max),
+                    ChatRejection::UnknownChannel { channel } => writer.u8(2).str(channel)?,
+                    ChatRejection::NotInChannel { channel } => writer.u8(3).str(channel)?,
+                    ChatRejection::UnknownClient { name } => writer.u8(4).str(name)?,
+                    ChatRejection::TooManyMutes { max } => writer.u8(5).u32(*max),
+                }
+            }
+        };
+        Ok(writer.0)
+    }
+}
+
+impl TryFromBytes for ChatS2C {
+    type Error = ChatWireError;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let mut r = Reader(buf);
+        Ok(match r.u8()? {
+            0 => Self::Joined { channel: r.str()? },
+            1 => Self::Left { channel: r.str()? },
+            2 => Self::Message {
+                channel: r.str()?,
+                from: r.str()?,
+                text: r.str()?,
+            },
+            3 => Self::Whisper {
+                from: r.str()?,
+                text: r.str()?,
+            },
+            4 => Self::Announcement { text: r.str()? },
+            5 => Self::Rejected(match r.u8()? {
+                0 => ChatRejection::RateLimited,
+                1 => ChatRejection::TooLong { max: r.u32()? },
+                2 => ChatRejection::UnknownChannel { channel: r.str()? },
+                3 => ChatRejection::NotInChannel { channel: r.str()? },
+                4 => ChatRejection::UnknownClient { name: r.str()? },
+                5 => ChatRejection::TooManyMutes { max: r.u32()? },
+                tag => return Err(ChatWireError::InvalidTag(tag)),
+            }),
+            tag => return Err(ChatWireError::InvalidTag(tag)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let c2s = ChatC2S::Say {
+            channel: "global".into(),
+            text: "hello".into(),
+        };
+        let buf = c2s.try_into_bytes().unwrap();
+        assert_eq!(Ok(c2s), ChatC2S::try_from_bytes(&buf));
+        assert_eq!(
+            Err(ChatWireError::Truncated),
+            ChatC2S::try_from_bytes(&buf[..buf.len() - 1])
+        );
+
+        let s2c = ChatS2C::Rejected(ChatRejection::TooLong { max: 256 });
+        let buf = s2c.try_into_bytes().unwrap();
+        assert_eq!(Ok(s2c), ChatS2C::try_from_bytes(&buf));
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Instant,
+};
+
+use crate::{ChatC2S, ChatConfig, ChatRejection, ChatS2C};
+
+/// A chat message which the server should send to a client.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delivery<K> {
+    /// Key of the client to send the message to.
+    pub to: K,
+    /// The message to send.
+    pub msg: ChatS2C,
+}
+
+/// Error that occurs when adding a client to a [`ChatServer`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChatError {
+    /// The client is already connected to the chat.
+    #[error("client already connected")]
+    AlreadyConnected,
+    /// Another client is already using this name.
+    #[error("name `{0}` is already taken")]
+    NameTaken(String),
+}
+
+/// Server-side state of the chat, independent of any transport.
+///
+/// Clients are identified by a key `K`, which is usually the client key of the
+/// transport server. The app adds each client with a display name using
+/// [`ChatServer::connect`] after it connects, and passes every [`ChatC2S`]
+/// received from it to [`ChatServer::handle`]. This returns the messages
+/// which should be sent out as a result, which the app sends using its
+/// transport.
+///
+/// Channels are created by the app, and clients can only join existing
+/// channels.
+#[derive(Debug, Clone)]
+pub struct ChatServer<K> {
+    config: ChatConfig,
+    channels: HashMap<String, HashSet<K>>,
+    members: HashMap<K, Member>,
+    names: HashMap<String, K>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    name: String,
+    channels: HashSet<String>,
+    muted: HashSet<String>,
+    flood: Flood,
+}
+
+/// Token bucket of a single client's messages.
+#[derive(Debug, Clone, Default)]
+struct Flood {
+    allowance: u32,
+    last_refill: Option<Instant>,
+}
+
+impl Flood {
+    fn try_take(&mut self, config: &ChatConfig, now: Instant) -> bool {
+        if config.flood_refill.is_zero() {
+            return true;
+        }
+
+        match self.last_refill {
+            None => {
+                self.allowance = config.flood_burst;
+                self.last_refill = Some(now);
+            }
+            Some(last) => {
+                let elapsed = now.saturating_duration_since(last);
+                let refilled = elapsed.as_nanos() / config.flood_refill.as_nanos();
+                let refilled = u32::try_from(refilled).unwrap_or(u32::MAX);
+                if refilled > 0 {
+                    self.allowance = self
+                        .allowance
+                        .saturating_add(refilled)
+                        .min(config.flood_burst);
+                    self.last_refill = Some(
+                        last.checked_add(config.flood_refill.saturating_mul(refilled))
+                            .unwrap_or(now),
+                    );
+                }
+            }
+        }
+
+        if self.allowance == 0 {
+            false
+        } else {
+            self.allowance -= 1;
+            true
+        }
+    }
+}
+
+impl<K> ChatServer<K>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Creates a chat with no channels or clients.
+    #[must_use]
+    pub fn new(config: ChatConfig) -> Self {
+        Self {
+            config,
+            channels: HashMap::new(),
+            members: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Gets the config of this chat.
+    #[must_use]
+    pub fn config(&self) -> &ChatConfig {
+        &self.config
+    }
+
+    /// Creates a channel which clients can join.
+    ///
+    /// Returns `false` if the channel already existed.
+    pub fn create_channel(&mut self, channel: impl Into<String>) -> bool {
+        let channel = channel.into();
+        if self.channels.contains_key(&channel) {
+            return false;
+        }
+        self.channels.insert(channel, HashSet::new());
+        true
+    }
+
+    /// Removes a channel, making all of its members leave it.
+    pub fn remove_channel(&mut self, channel: &str) -> Vec<Delivery<K>> {
+        let Some(members) = self.channels.remove(channel) else {
+            return Vec::new();
+        };
+        members
+            .into_iter()
+            .map(|client| {
+                if let Some(member) = self.members.get_mut(&client) {
+                    member.channels.remove(channel);
+                }
+                Delivery {
+                    to: client,
+                    msg: ChatS2C::Left {
+                        channel: channel.to_owned(),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Iterates over the names of all channels.
+    pub fn channels(&self) -> impl Iterator<Item = &str> + '_ {
+        self.channels.keys().map(String::as_str)
+    }
+
+    /// Iterates over the clients which have joined a channel.
+    pub fn channel_members(&self, channel: &str) -> impl Iterator<Item = &K> + '_ {
+        self.channels.get(channel).into_iter().flatten()
+    }
+
+    /// Adds a client to the chat with the given display name.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the client is already connected, or the name is taken.
+    pub fn connect(&mut self, client: K, name: impl Into<String>) -> Result<(), ChatError> {
+        let name = name.into();
+        if self.members.contains_key(&client) {
+            return Err(ChatError::AlreadyConnected);
+        }
+        if self.names.contains_key(&name) {
+            return Err(ChatError::NameTaken(name));
+        }
+        self.names.insert(name.clone(), client.clone());
+        self.members.insert(
+            client,
+            Member {
+                name,
+                channels: HashSet::new(),
+                muted: HashSet::new(),
+                flood: Flood::default(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes a client from the chat and all of its channels.
+    ///
+    /// Other clients which muted this client keep it in their mute list, so
+    /// that it is still muted if it reconnects with the same name.
+    pub fn disconnect(&mut self, client: &K) {
+        let Some(member) = self.members.remove(client) else {
+            return;
+        };
+        self.names.remove(&member.name);
+        for channel in &member.channels {
+            if let Some(members) = self.channels.get_mut(channel) {
+                members.remove(client);
+            }
+        }
+    }
+
+    /// Gets the display name of a client.
+    #[must_use]
+    pub fn name(&self, client: &K) -> Option<&str> {
+        self.members.get(client).map(|member| member.name.as_str())
+    }
+
+    /// Handles a message sent by a client, returning the messages to send out
+    /// as a result.
+    ///
+    /// Messages from clients which are not connected are ignored. If the
+    /// message is invalid, a [`ChatS2C::Rejected`] is sent back to the client.
+    /// Messages and whispers are not delivered to clients which have muted the
+    /// sender.
+    pub fn handle(&mut self, client: &K, msg: ChatC2S, now: Instant) -> Vec<Delivery<K>> {
+        let reply = |msg| {
+            vec![Delivery {
+                to: client.clone(),
+                msg,
+            }]
+        };
+        let reject = |reason| reply(ChatS2C::Rejected(reason));
+
+        let Some(member) = self.members.get_mut(client) else {
+            return Vec::new();
+        };
+        match msg {
+            ChatC2S::Join { channel } => {
+                let Some(members) = self.channels.get_mut(&channel) else {
+                    return reject(ChatRejection::UnknownChannel { channel });
+                };
+                members.insert(client.clone());
+                member.channels.insert(channel.clone());
+                reply(ChatS2C::Joined { channel })
+            }
+            ChatC2S::Leave { channel } => {
+                if !member.channels.remove(&channel) {
+                    return reject(ChatRejection::NotInChannel { channel });
+                }
+                if let Some(members) = self.channels.get_mut(&channel) {
+                    members.remove(client);
+                }
+                reply(ChatS2C::Left { channel })
+            }
+            ChatC2S::Say { channel, text } => {
+                if !member.channels.contains(&channel) {
+                    return reject(ChatRejection::NotInChannel { channel });
+                }
+                if let Err(reason) = check_text(&self.config, member, &text, now) {
+                    return reject(reason);
+                }
+                let from = member.name.clone();
+                self.channel_members(&channel)
+                    .filter(|to| !self.is_muted_by(to, &from))
+                    .map(|to| Delivery {
+                        to: to.clone(),
+                        msg: ChatS2C::Message {
+                            channel: channel.clone(),
+                            from: from.clone(),
+                            text: text.clone(),
+                        },
+                    })
+                    .collect()
+            }
+            ChatC2S::Whisper { to, text } => {
+                if let Err(reason) = check_text(&self.config, member, &text, now) {
+                    return reject(reason);
+                }
+                let from = member.name.clone();
+                let Some(target) = self.names.get(&to) else {
+                    return reject(ChatRejection::UnknownClient { name: to });
+                };
+                if self.is_muted_by(target, &from) {
+                    return Vec::new();
+                }
+                vec![Delivery {
+                    to: target.clone(),
+                    msg: ChatS2C::Whisper { from, text },
+                }]
+            }
+            ChatC2S::Mute { name } => {
+                if !self.names.contains_key(&name) {
+                    return reject(ChatRejection::UnknownClient { name });
+                }
+                if member.muted.len() >= self.config.max_mutes && !member.muted.contains(&name) {
+                    return reject(ChatRejection::TooManyMutes {
+                        max: u32::try_from(self.config.max_mutes).unwrap_or(u32::MAX),
+                    });
+                }
+                member.muted.insert(name);
+                Vec::new()
+            }
+            ChatC2S::Unmute { name } => {
+                member.muted.remove(&name);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Sends an announcement to every connected client.
+    #[must_use]
+    pub fn announce(&self, text: &str) -> Vec<Delivery<K>> {
+        self.members
+            .keys()
+            .map(|to| Delivery {
+                to: to.clone(),
+                msg: ChatS2C::Announcement {
+                    text: text.to_owned(),
+                },
+            })
+            .collect()
+    }
+
+    /// Sends an announcement to every client in a channel.
+    #[must_use]
+    pub fn announce_in(&self, channel: &str, text: &str) -> Vec<Delivery<K>> {
+        self.channel_members(channel)
+            .map(|to| Delivery {
+                to: to.clone(),
+                msg: ChatS2C::Announcement {
+                    text: text.to_owned(),
+                },
+            })
+            .collect()
+    }
+
+    fn is_muted_by(&self, client: &K, name: &str) -> bool {
+        self.members
+            .get(client)
+            .is_some_and(|member| member.muted.contains(name))
+    }
+}
+
+/// Checks that a client may send a message with the given text, consuming
+/// one message of its flood allowance.
+fn check_text(
+    config: &ChatConfig,
+    member: &mut Member,
+    text: &str,
+    now: Instant,
+) -> Result<(), ChatRejection> {
+    if text.chars().count() > config.max_message_len {
+        return Err(ChatRejection::TooLong {
+            max: u32::try_from(config.max_message_len).unwrap_or(u32::MAX),
+        });
+    }
+    if !member.flood.try_take(config, now) {
+        return Err(ChatRejection::RateLimited);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn chat() -> ChatServer<u32> {
+        let mut chat = ChatServer::new(ChatConfig::default());
+        chat.create_channel("global");
+        chat.connect(1, "alice").unwrap();
+        chat.connect(2, "bob").unwrap();
+        for client in [1, 2] {
+            chat.handle(
+                &client,
+                ChatC2S::Join {
+                    channel: "global".into(),
+                },
+                Instant::now(),
+            );
+        }
+        chat
+    }
+
+    fn say(text: &str) -> ChatC2S {
+        ChatC2S::Say {
+            channel: "global".into(),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn say_and_mute() {
+        let mut chat = chat();
+        let now = Instant::now();
+        let mut to = chat
+            .handle(&1, say("hi"), now)
+            .into_iter()
+            .map(|delivery| delivery.to)
+            .collect::<Vec<_>>();
+        to.sort_unstable();
+        assert_eq!(vec![1, 2], to);
+
+        chat.handle(
+            &2,
+            ChatC2S::Mute {
+                name: "alice".into(),
+            },
+            now,
+        );
+        let to = chat
+            .handle(&1, say("hi"), now)
+            .into_iter()
+            .map(|delivery| delivery.to)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1], to);
+    }
+
+    #[test]
+    fn flood_control() {
+        let mut chat = chat();
+        let now = Instant::now();
+        for _ in 0..chat.config().flood_burst {
+            assert_eq!(2, chat.handle(&1, say("spam"), now).len());
+        }
+        assert_eq!(
+            vec![Delivery {
+                to: 1,
+                msg: ChatS2C::Rejected(ChatRejection::RateLimited),
+            }],
+            chat.handle(&1, say("spam"), now)
+        );
+        let later = now + Duration::from_secs(1);
+        assert_eq!(2, chat.handle(&1, say("spam"), later).len());
+    }
+
+    #[test]
+    fn whisper_unknown_client() {
+        let mut chat = chat();
+        let msg = ChatC2S::Whisper {
+            to: "carol".into(),
+            text: "hi".into(),
+        };
+        assert_eq!(
+            vec![Delivery {
+                to: 1,
+                msg: ChatS2C::Rejected(ChatRejection::UnknownClient {
+                    name: "carol".into()
+                }),
+            }],
+            chat.handle(&1, msg, Instant::now())
+        );
+    }
+}
@@ -4,7 +4,9 @@ use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
 
 mod channel_key;
+mod name_tagged;
 mod on_channel;
+mod variant_tags;
 
 /// Defines a type of key used to represent the different app-specific channels
 /// that can be used to send messages.
@@ -106,6 +108,99 @@ pub fn on_channel(input: TokenStream) -> TokenStream {
         .into()
 }
 
+/// Assigns stable tags to the variants of a message type, for use with
+/// `Versioned`.
+///
+/// # Attributes
+///
+/// * `#[message_version(n)]` sets the version of the schema of this type, which
+///   should be bumped every time a variant is added or removed. Defaults to 0.
+/// * `#[variant_tag(n)]` sets the tag of a variant. Defaults to the index of
+///   the variant. Tags must be unique, and must not change once the type has
+///   been released.
+///
+/// # Usage
+///
+/// ## Struct
+///
+/// The type has a single variant with tag 0.
+///
+/// ```ignore
+/// #[derive(VariantTags)]
+/// #[message_version(1)]
+/// struct AppMessage(pub String);
+/// ```
+///
+/// ## Enum
+///
+/// ```ignore
+/// #[derive(VariantTags)]
+/// #[message_version(2)]
+/// enum AppMessage {
+///     #[variant_tag(0)]
+///     Move(f32),
+///     // added in version 2, so older clients skip it
+///     #[variant_tag(2)]
+///     Emote(u8),
+///     #[variant_tag(1)]
+///     Chat { msg: String },
+/// }
+/// ```
+#[proc_macro_derive(VariantTags, attributes(message_version, variant_tag))]
+pub fn variant_tags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    variant_tags::derive(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Identifies the variants of a message type by a hash of their name, for use
+/// with `Tagged`.
+///
+/// Requires the `bincode` feature of `aeronet`, and that all fields implement
+/// `serde::Serialize` and `serde::de::DeserializeOwned`.
+///
+/// # Attributes
+///
+/// * `#[wire_name("name")]` sets the name which is hashed to get the tag of a
+///   variant. Defaults to the name of the variant, or of the type for a struct.
+///   Use this to keep the tag of a variant when renaming it.
+///
+/// # Usage
+///
+/// ## Struct
+///
+/// The type has a single variant, whose tag is the hash of the type's name.
+///
+/// ```ignore
+/// #[derive(NameTagged)]
+/// struct AppMessage(pub String);
+/// ```
+///
+/// ## Enum
+///
+/// ```ignore
+/// #[derive(NameTagged)]
+/// enum AppMessage {
+///     Move(f32),
+///     // added in the middle, without changing the tag of `Chat`
+///     Emote(u8),
+///     // renamed from `Chat`
+///     #[wire_name("Chat")]
+///     Say { msg: String },
+/// }
+/// ```
+#[proc_macro_derive(NameTagged, attributes(wire_name))]
+pub fn name_tagged(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    name_tagged::derive(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
 const CHANNEL_KIND: &str = "channel_kind";
 const CHANNEL_TYPE: &str = "channel_type";
 const ON_CHANNEL: &str = "on_channel";
+const MESSAGE_VERSION: &str = "message_version";
+const VARIANT_TAG: &str = "variant_tag";
+const WIRE_NAME: &str = "wire_name";
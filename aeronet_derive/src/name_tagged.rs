@@ -0,0 +1,206 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DataEnum, DeriveInput, Error, Fields, Ident, LitStr, Result};
+
+use crate::WIRE_NAME;
+
+// serde implements `Serialize` and `Deserialize` for tuples of up to this many
+// elements
+const MAX_FIELDS: usize = 16;
+
+pub(super) fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    match &input.data {
+        Data::Struct(data) => on_struct(input, &data.fields),
+        Data::Enum(data) => on_enum(input, data),
+        Data::Union(_) => Err(Error::new_spanned(
+            input,
+            "union as NameTagged is not supported",
+        )),
+    }
+}
+
+fn on_struct(input: &DeriveInput, fields: &Fields) -> Result<TokenStream> {
+    let name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let tag = name_tag(&wire_name(&input.attrs, name)?);
+    let (destruct, ser, de) = fields_codec(input, fields)?;
+
+    Ok(quote! {
+        impl #impl_generics ::aeronet::NameTagged for #name #type_generics #where_clause {
+            const NAME_TAGS: &'static [u32] = &[#tag];
+
+            fn name_tag(&self) -> u32 {
+                #tag
+            }
+
+            fn serialize_fields(
+                &self,
+            ) -> ::std::result::Result<
+                ::std::vec::Vec<u8>,
+                ::aeronet::__private::bincode::Error,
+            > {
+                let Self #destruct = self;
+                #ser
+            }
+
+            fn deserialize_fields(
+                tag: u32,
+                buf: &[u8],
+            ) -> ::std::result::Result<
+                ::std::option::Option<Self>,
+                ::aeronet::__private::bincode::Error,
+            > {
+                if tag != #tag {
+                    return ::std::result::Result::Ok(::std::option::Option::None);
+                }
+                #de
+                ::std::result::Result::Ok(::std::option::Option::Some(Self #destruct))
+            }
+        }
+    })
+}
+
+fn on_enum(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
+    let name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let mut tags = Vec::<u32>::new();
+    let mut names = Vec::<String>::new();
+    for variant in &data.variants {
+        let wire_name = wire_name(&variant.attrs, &variant.ident)?;
+        let tag = name_tag(&wire_name);
+        if let Some(index) = tags.iter().position(|other| *other == tag) {
+            return Err(Error::new_spanned(
+                variant,
+                format!(
+                    "name tag of `{wire_name}` collides with `{}`, use #[{WIRE_NAME}(..)] to \
+                     rename one of them",
+                    names[index]
+                ),
+            ));
+        }
+        tags.push(tag);
+        names.push(wire_name);
+    }
+
+    let mut tag_arms = Vec::new();
+    let mut ser_arms = Vec::new();
+    let mut de_arms = Vec::new();
+    for (variant, tag) in data.variants.iter().zip(tags.iter()) {
+        let ident = &variant.ident;
+        let (destruct, ser, de) = fields_codec(variant, &variant.fields)?;
+        tag_arms.push(quote! { Self::#ident { .. } => #tag });
+        ser_arms.push(quote! { Self::#ident #destruct => { #ser } });
+        de_arms.push(quote! { #tag => { #de Self::#ident #destruct } });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::aeronet::NameTagged for #name #type_generics #where_clause {
+            const NAME_TAGS: &'static [u32] = &[#(#tags),*];
+
+            fn name_tag(&self) -> u32 {
+                match *self {
+                    #(#tag_arms),*
+                }
+            }
+
+            fn serialize_fields(
+                &self,
+            ) -> ::std::result::Result<
+                ::std::vec::Vec<u8>,
+                ::aeronet::__private::bincode::Error,
+            > {
+                match self {
+                    #(#ser_arms)*
+                }
+            }
+
+            fn deserialize_fields(
+                tag: u32,
+                buf: &[u8],
+            ) -> ::std::result::Result<
+                ::std::option::Option<Self>,
+                ::aeronet::__private::bincode::Error,
+            > {
+                ::std::result::Result::Ok(::std::option::Option::Some(match tag {
+                    #(#de_arms)*
+                    _ => return ::std::result::Result::Ok(::std::option::Option::None),
+                }))
+            }
+        }
+    })
+}
+
+/// Generates, for a set of fields:
+/// * the pattern which binds or constructs them, e.g. `{ a, b }` or `(f0, f1)`
+/// * the expression which serializes the bound fields as a tuple
+/// * the statement which deserializes the fields from `buf` and binds them
+fn fields_codec(
+    spanned: &impl quote::ToTokens,
+    fields: &Fields,
+) -> Result<(TokenStream, TokenStream, TokenStream)> {
+    if fields.len() > MAX_FIELDS {
+        return Err(Error::new_spanned(
+            spanned,
+            format!("NameTagged supports at most {MAX_FIELDS} fields"),
+        ));
+    }
+
+    let idents = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("__field{index}"),
+        })
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    let destruct = match fields {
+        Fields::Unit => quote! {},
+        Fields::Named(_) => quote! { { #(#idents),* } },
+        Fields::Unnamed(_) => quote! { ( #(#idents),* ) },
+    };
+    let ser = quote! {
+        ::aeronet::__private::bincode::serialize(&( #(#idents,)* ))
+    };
+    let de = quote! {
+        let ( #(#idents,)* ): ( #(#types,)* ) =
+            ::aeronet::__private::bincode::deserialize(buf)?;
+    };
+    Ok((destruct, ser, de))
+}
+
+// attributes
+
+fn wire_name(attrs: &[Attribute], ident: &Ident) -> Result<String> {
+    let mut value = None;
+    for attr in attrs {
+        if !attr.path().is_ident(WIRE_NAME) {
+            continue;
+        }
+
+        if value.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                format!("duplicate #[{WIRE_NAME}] attribute"),
+            ));
+        }
+
+        let lit = attr
+            .parse_args::<LitStr>()
+            .map_err(|_| Error::new_spanned(attr, format!("expected #[{WIRE_NAME}(\"name\")]")))?;
+        value = Some(lit.value());
+    }
+    Ok(value.unwrap_or_else(|| ident.to_string()))
+}
+
+// must match `aeronet::name_tag`
+fn name_tag(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
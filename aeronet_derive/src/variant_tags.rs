@@ -0,0 +1,120 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, Data, DataEnum, DeriveInput, Error, Fields, LitInt, Result};
+
+use crate::{MESSAGE_VERSION, VARIANT_TAG};
+
+pub(super) fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    match &input.data {
+        Data::Struct(_) => on_struct(input),
+        Data::Enum(data) => on_enum(input, data),
+        Data::Union(_) => Err(Error::new_spanned(
+            input,
+            "union as VariantTags is not supported",
+        )),
+    }
+}
+
+fn on_struct(input: &DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let version = parse_message_version(&input.attrs)?;
+
+    Ok(quote! {
+        impl #impl_generics ::aeronet::VariantTags for #name #type_generics #where_clause {
+            const VERSION: u16 = #version;
+
+            const TAGS: &'static [u16] = &[0];
+
+            fn variant_tag(&self) -> u16 {
+                0
+            }
+        }
+    })
+}
+
+fn on_enum(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
+    let name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let version = parse_message_version(&input.attrs)?;
+    let mut tags = Vec::<u16>::new();
+    for (index, variant) in data.variants.iter().enumerate() {
+        let tag = match parse_variant_tag(&variant.attrs)? {
+            Some(tag) => tag,
+            None => u16::try_from(index)
+                .map_err(|_| Error::new_spanned(variant, "too many variants"))?,
+        };
+        if tags.contains(&tag) {
+            return Err(Error::new_spanned(
+                variant,
+                format!("duplicate variant tag {tag}"),
+            ));
+        }
+        tags.push(tag);
+    }
+
+    let match_body = data
+        .variants
+        .iter()
+        .zip(tags.iter())
+        .map(|(variant, tag)| {
+            let pattern = &variant.ident;
+            let destruct = match variant.fields {
+                Fields::Unit => quote! {},
+                Fields::Named(_) => quote! { { .. } },
+                Fields::Unnamed(_) => quote! { (..) },
+            };
+            quote! { Self::#pattern #destruct => #tag }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(quote! {
+        impl #impl_generics ::aeronet::VariantTags for #name #type_generics #where_clause {
+            const VERSION: u16 = #version;
+
+            const TAGS: &'static [u16] = &[#(#tags),*];
+
+            fn variant_tag(&self) -> u16 {
+                match *self {
+                    #(#match_body),*
+                }
+            }
+        }
+    })
+}
+
+// attributes
+
+fn parse_message_version(attrs: &[Attribute]) -> Result<u16> {
+    Ok(parse_u16(attrs, MESSAGE_VERSION)?.unwrap_or(0))
+}
+
+fn parse_variant_tag(attrs: &[Attribute]) -> Result<Option<u16>> {
+    parse_u16(attrs, VARIANT_TAG)
+}
+
+fn parse_u16(attrs: &[Attribute], ident: &str) -> Result<Option<u16>> {
+    let mut value = None;
+    for attr in attrs {
+        if !attr.path().is_ident(ident) {
+            continue;
+        }
+
+        if value.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                format!("duplicate #[{ident}] attribute"),
+            ));
+        }
+
+        let lit = attr
+            .parse_args::<LitInt>()
+            .map_err(|_| Error::new_spanned(attr, format!("expected #[{ident}(n)]")))?;
+        value = Some(lit.base10_parse::<u16>()?);
+    }
+    Ok(value)
+}
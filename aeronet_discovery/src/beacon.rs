@@ -0,0 +1,128 @@
+/// Magic bytes at the start of every encoded beacon.
+const MAGIC: [u8; 4] = *b"AEDB";
+
+/// Version of the beacon wire format.
+const VERSION: u8 = 1;
+
+/// Length of the fixed-size part of an encoded beacon.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+
+/// Maximum length in bytes of a beacon's name and connect address each.
+pub const MAX_FIELD_LEN: usize = u8::MAX as usize;
+
+/// Presence information which a server advertises on the local network.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Beacon {
+    /// Display name of the server.
+    pub name: String,
+    /// Number of players currently on the server.
+    pub players: u32,
+    /// Maximum number of players that the server accepts.
+    pub max_players: u32,
+    /// Address that clients should connect to, in whichever form the
+    /// transport expects, e.g. a URL or a socket address.
+    pub connect_addr: String,
+}
+
+/// Error that occurs when encoding or decoding a [`Beacon`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BeaconError {
+    /// A field was longer than [`MAX_FIELD_LEN`] bytes.
+    #[error("field is {0} bytes long, longer than {MAX_FIELD_LEN} bytes")]
+    FieldTooLong(usize),
+    /// The packet was not a beacon of a supported version.
+    #[error("not a beacon")]
+    NotBeacon,
+    /// The packet was cut off.
+    #[error("beacon is truncated")]
+    Truncated,
+    /// A field was not valid UTF-8.
+    #[error("field is not valid UTF-8")]
+    InvalidUtf8,
+}
+
+impl Beacon {
+    /// Encodes this beacon, tagged with the given app ID.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the name or connect address is too long.
+    pub fn encode(&self, app_id: u32) -> Result<Vec<u8>, BeaconError> {
+        let mut buf =
+            Vec::with_capacity(HEADER_LEN + 2 + self.name.len() + self.connect_addr.len());
+        buf.extend_from_slice(&MAGIC);
+        buf.push(VERSION);
+        buf.extend_from_slice(&app_id.to_be_bytes());
+        buf.extend_from_slice(&self.players.to_be_bytes());
+        buf.extend_from_slice(&self.max_players.to_be_bytes());
+        for field in [&self.name, &self.connect_addr] {
+            let len =
+                u8::try_from(field.len()).map_err(|_| BeaconError::FieldTooLong(field.len()))?;
+            buf.push(len);
+            buf.extend_from_slice(field.as_bytes());
+        }
+        Ok(buf)
+    }
+
+    /// Decodes a beacon, returning it along with the app ID that it was tagged
+    /// with.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the packet is not a valid beacon.
+    pub fn decode(buf: &[u8]) -> Result<(u32, Self), BeaconError> {
+        let header = buf.get(..HEADER_LEN).ok_or(BeaconError::NotBeacon)?;
+        if header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+            return Err(BeaconError::NotBeacon);
+        }
+        let read_u32 = |at: usize| {
+            u32::from_be_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+        };
+        let app_id = read_u32(5);
+        let players = read_u32(9);
+        let max_players = read_u32(13);
+
+        let mut rest = &buf[HEADER_LEN..];
+        let mut read_field = || {
+            let (&len, after) = rest.split_first().ok_or(BeaconError::Truncated)?;
+            let field = after
+                .get(..usize::from(len))
+                .ok_or(BeaconError::Truncated)?;
+            rest = &after[usize::from(len)..];
+            String::from_utf8(field.to_vec()).map_err(|_| BeaconError::InvalidUtf8)
+        };
+        let name = read_field()?;
+        let connect_addr = read_field()?;
+
+        Ok((
+            app_id,
+            Self {
+                name,
+                players,
+                max_players,
+                connect_addr,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let beacon = Beacon {
+            name: "my server".into(),
+            players: 3,
+            max_players: 8,
+            connect_addr: "https://192.168.1.20:25565".into(),
+        };
+        let buf = beacon.encode(1234).unwrap();
+        assert_eq!(Ok((1234, beacon)), Beacon::decode(&buf));
+        assert_eq!(
+            Err(BeaconError::Truncated),
+            Beacon::decode(&buf[..buf.len() - 1])
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    io,
+    net::{SocketAddr, SocketAddrV4, UdpSocket},
+    time::Instant,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::debug;
+
+use crate::{Beacon, BeaconError, DiscoveryConfig};
+
+/// Periodically sends a server's [`Beacon`] to the local network.
+///
+/// See the [crate-level docs](crate).
+#[derive(Debug)]
+pub struct BeaconBroadcaster {
+    config: DiscoveryConfig,
+    socket: UdpSocket,
+    /// The encoded beacon, if one is being broadcast.
+    beacon: Option<Vec<u8>>,
+    last_sent: Option<Instant>,
+}
+
+impl BeaconBroadcaster {
+    /// Creates a broadcaster which is not broadcasting any beacon yet.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the socket could not be created.
+    pub fn new(config: DiscoveryConfig) -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_ttl_v4(config.ttl)?;
+        socket.set_multicast_if_v4(&config.interface)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.bind(&SocketAddr::V4(SocketAddrV4::new(config.interface, 0)).into())?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            config,
+            socket: socket.into(),
+            beacon: None,
+            last_sent: None,
+        })
+    }
+
+    /// Gets the config that this broadcaster was created with.
+    #[must_use]
+    pub fn config(&self) -> &DiscoveryConfig {
+        &self.config
+    }
+
+    /// Starts broadcasting the given beacon, replacing the previous one.
+    ///
+    /// The beacon is sent on the next [`BeaconBroadcaster::poll`], so that
+    /// changes such as the player count show up in browsers quickly.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the beacon could not be encoded.
+    pub fn set_beacon(&mut self, beacon: &Beacon) -> Result<(), BeaconError> {
+        self.beacon = Some(beacon.encode(self.config.app_id)?);
+        self.last_sent = None;
+        Ok(())
+    }
+
+    /// Stops broadcasting any beacon.
+    ///
+    /// Browsers will consider this server lost once the expiry time passes.
+    pub fn clear_beacon(&mut self) {
+        self.beacon = None;
+    }
+
+    /// Sends the beacon if the beacon interval has elapsed since it was last
+    /// sent.
+    ///
+    /// This should be called regularly, e.g. every frame.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the beacon could not be sent.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let Some(beacon) = &self.beacon else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if self
+            .last_sent
+            .is_some_and(|last| now.duration_since(last) < self.config.interval)
+        {
+            return Ok(());
+        }
+
+        match self.socket.send_to(beacon, self.config.group) {
+            Ok(_) => {
+                self.last_sent = Some(now);
+                Ok(())
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                debug!("Beacon send would block, retrying on next poll");
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    time::Instant,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::debug;
+
+use crate::{Beacon, DiscoveryConfig};
+
+/// Size of the buffer that beacons are received into, which is larger than
+/// any valid beacon.
+const RECV_BUF_LEN: usize = 1024;
+
+/// A server found on the local network by a [`ServerBrowser`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredServer {
+    /// Address that the server's beacons are sent from.
+    ///
+    /// This identifies the server, but is not necessarily the address to
+    /// connect to - use the beacon's [`Beacon::connect_addr`] for that.
+    pub source: SocketAddr,
+    /// The most recent beacon received from the server.
+    pub beacon: Beacon,
+    /// When the most recent beacon was received.
+    pub last_seen: Instant,
+}
+
+/// Event raised by a [`ServerBrowser`].
+#[derive(Debug, Clone)]
+pub enum DiscoveryEvent {
+    /// A beacon was received from a server which was not known before.
+    Discovered {
+        /// The server which was discovered.
+        server: DiscoveredServer,
+    },
+    /// A known server sent a beacon with different contents than before, e.g.
+    /// because its player count changed.
+    Updated {
+        /// The server with its new beacon.
+        server: DiscoveredServer,
+    },
+    /// No beacon was received from a known server within the expiry time, and
+    /// it has been removed from the browser.
+    Lost {
+        /// The server which was lost.
+        server: DiscoveredServer,
+    },
+}
+
+/// Listens for [`Beacon`]s on the local network, and keeps a list of the
+/// servers which sent them.
+///
+/// See the [crate-level docs](crate).
+#[derive(Debug)]
+pub struct ServerBrowser {
+    config: DiscoveryConfig,
+    socket: UdpSocket,
+    servers: HashMap<SocketAddr, DiscoveredServer>,
+}
+
+impl ServerBrowser {
+    /// Creates a browser and starts listening for beacons.
+    ///
+    /// Multiple browsers can run on the same machine at once.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the socket could not be created, or could not join the
+    /// multicast group.
+    pub fn new(config: DiscoveryConfig) -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        let bind_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.group.port());
+        socket.bind(&SocketAddr::V4(bind_addr).into())?;
+        socket.join_multicast_v4(config.group.ip(), &config.interface)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            config,
+            socket: socket.into(),
+            servers: HashMap::new(),
+        })
+    }
+
+    /// Gets the config that this browser was created with.
+    #[must_use]
+    pub fn config(&self) -> &DiscoveryConfig {
+        &self.config
+    }
+
+    /// Gets all servers which are currently known.
+    pub fn servers(&self) -> impl Iterator<Item = &DiscoveredServer> + '_ {
+        self.servers.values()
+    }
+
+    /// Receives all pending beacons, and removes servers which have expired.
+    ///
+    /// This should be called regularly, e.g. every frame. Packets which are
+    /// not valid beacons, or are beacons of a different app, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the socket could not be read from.
+    pub fn poll(&mut self) -> io::Result<Vec<DiscoveryEvent>> {
+        let mut events = Vec::new();
+        let mut buf = [0; RECV_BUF_LEN];
+        loop {
+            let (len, source) = match self.socket.recv_from(&mut buf) {
+                Ok(t) => t,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            };
+            let now = Instant::now();
+
+            let beacon = match Beacon::decode(&buf[..len]) {
+                Ok((app_id, beacon)) if app_id == self.config.app_id => beacon,
+                Ok(_) => continue,
+                Err(err) => {
+                    debug!("Ignoring invalid beacon from {source}: {err:#}");
+                    continue;
+                }
+            };
+
+            if let Some(server) = self.servers.get_mut(&source) {
+                server.last_seen = now;
+                if server.beacon != beacon {
+                    server.beacon = beacon;
+                    events.push(DiscoveryEvent::Updated {
+                        server: server.clone(),
+                    });
+                }
+            } else {
+                let server = DiscoveredServer {
+                    source,
+                    beacon,
+                    last_seen: now,
+                };
+                self.servers.insert(source, server.clone());
+                events.push(DiscoveryEvent::Discovered { server });
+            }
+        }
+
+        let now = Instant::now();
+        let expiry = self.config.expiry;
+        self.servers.retain(|_, server| {
+            if now.duration_since(server.last_seen) < expiry {
+                true
+            } else {
+                events.push(DiscoveryEvent::Lost {
+                    server: server.clone(),
+                });
+                false
+            }
+        });
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    time::Duration,
+};
+
+/// Default multicast group and port that beacons are sent to.
+pub const DEFAULT_GROUP: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 77, 41), 47_941);
+
+/// Settings shared by a [`BeaconBroadcaster`] and [`ServerBrowser`].
+///
+/// Both sides must use the same group and app ID to see each other.
+///
+/// [`BeaconBroadcaster`]: crate::BeaconBroadcaster
+/// [`ServerBrowser`]: crate::ServerBrowser
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Identifier of the app, so that different apps on the same network do
+    /// not discover each other's servers.
+    pub app_id: u32,
+    /// Multicast group and port that beacons are sent to.
+    ///
+    /// By default this is [`DEFAULT_GROUP`].
+    pub group: SocketAddrV4,
+    /// Address of the local interface to send and receive beacons on.
+    ///
+    /// By default this is [`Ipv4Addr::UNSPECIFIED`], which lets the OS choose.
+    pub interface: Ipv4Addr,
+    /// Multicast TTL of sent beacons.
+    ///
+    /// By default this is 1, which keeps beacons on the local network segment.
+    pub ttl: u32,
+    /// Time between two beacons sent by a broadcaster.
+    ///
+    /// By default this is 1 second.
+    pub interval: Duration,
+    /// Time after the last beacon from a server after which a browser
+    /// considers the server lost.
+    ///
+    /// This should be a few times larger than [`DiscoveryConfig::interval`], so
+    /// that a couple of dropped beacons do not make a server disappear. By
+    /// default this is 5 seconds.
+    pub expiry: Duration,
+}
+
+impl DiscoveryConfig {
+    /// Creates a config with the default settings for the given app.
+    #[must_use]
+    pub fn new(app_id: u32) -> Self {
+        Self {
+            app_id,
+            group: DEFAULT_GROUP,
+            interface: Ipv4Addr::UNSPECIFIED,
+            ttl: 1,
+            interval: Duration::from_secs(1),
+            expiry: Duration::from_secs(5),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+
+mod beacon;
+mod broadcaster;
+mod browser;
+mod config;
+
+pub use {beacon::*, broadcaster::*, browser::*, config::*};
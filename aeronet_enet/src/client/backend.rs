@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use enet::{Address, ChannelLimit, Enet, EventKind};
+use tracing::debug;
+
+use crate::{
+    transport::{bandwidth, SERVICE_TIMEOUT},
+    BackendError, EnetClientConfig, EnetInfo, Outgoing,
+};
+
+/// Interval at which connection info is sent to the frontend.
+const INFO_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected { info: EnetInfo },
+    Info { info: EnetInfo },
+    Recv { bytes: Vec<u8> },
+    Disconnected { data: u32 },
+    Error { cause: BackendError },
+}
+
+pub(super) fn start(
+    config: &EnetClientConfig,
+    channel_count: usize,
+    recv_c2s: &Receiver<Outgoing>,
+    send_update: &Sender<Update>,
+) {
+    let enet = match Enet::new() {
+        Ok(enet) => enet,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Initialize(err),
+            });
+            return;
+        }
+    };
+
+    let mut host = match enet.create_host::<()>(
+        None,
+        1,
+        ChannelLimit::Limited(channel_count),
+        bandwidth(config.incoming_bandwidth),
+        bandwidth(config.outgoing_bandwidth),
+    ) {
+        Ok(host) => host,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::CreateHost(err),
+            });
+            return;
+        }
+    };
+
+    let addr = Address::new(*config.addr.ip(), config.addr.port());
+    let peer_id = match host.connect(&addr, channel_count, config.connect_data) {
+        Ok((_, peer_id)) => peer_id,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Connect(err),
+            });
+            return;
+        }
+    };
+
+    debug!("Starting client loop");
+    let mut connected = false;
+    let mut last_info = Instant::now();
+    loop {
+        loop {
+            match recv_c2s.try_recv() {
+                Ok(msg) => {
+                    let Some(peer) = host.peer_mut(peer_id) else {
+                        continue;
+                    };
+                    let channel_id = msg.channel_id;
+                    if let Ok(packet) = msg.into_packet() {
+                        let _ = peer.send_packet(packet, channel_id);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("Frontend closed");
+                    if let Some(peer) = host.peer_mut(peer_id) {
+                        peer.disconnect_now(0);
+                    }
+                    host.flush();
+                    return;
+                }
+            }
+        }
+
+        match host.service(SERVICE_TIMEOUT) {
+            Ok(Some(event)) => {
+                let info = EnetInfo::from_peer(event.peer());
+                let update = match event.kind() {
+                    EventKind::Connect => {
+                        connected = true;
+                        Update::Connected { info }
+                    }
+                    EventKind::Disconnect { data } => Update::Disconnected { data: *data },
+                    EventKind::Receive { packet, .. } => Update::Recv {
+                        bytes: packet.data().to_vec(),
+                    },
+                };
+                let disconnected = matches!(update, Update::Disconnected { .. });
+                let _ = send_update.send(update);
+                if disconnected {
+                    return;
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                let _ = send_update.send(Update::Error {
+                    cause: BackendError::Service(err),
+                });
+                return;
+            }
+        }
+
+        if connected && last_info.elapsed() >= INFO_INTERVAL {
+            last_info = Instant::now();
+            if let Some(peer) = host.peer(peer_id) {
+                let info = EnetInfo::from_peer(peer);
+                let _ = send_update.send(Update::Info { info });
+            }
+        }
+    }
+}
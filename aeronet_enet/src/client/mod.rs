@@ -0,0 +1,159 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, marker::PhantomData, net::SocketAddrV4};
+
+use aeronet::{
+    OnChannel, OnMessageError, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes,
+};
+use crossbeam_channel::{Receiver, Sender};
+use derivative::Derivative;
+
+use crate::{EnetInfo, EnetProtocol, Outgoing};
+
+use self::backend::Update;
+
+type EnetError<P> = crate::EnetError<<P as TransportProtocol>::C2S, <P as TransportProtocol>::S2C>;
+
+/// Configuration for connecting an [`EnetClient`] to a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnetClientConfig {
+    /// Address of the server to connect to.
+    pub addr: SocketAddrV4,
+    /// User data sent to the server along with the connection request.
+    ///
+    /// Existing ENet servers may use this to e.g. check the version of the
+    /// client.
+    pub connect_data: u32,
+    /// Incoming bandwidth of the client in bytes per second, or [`None`] for
+    /// unlimited bandwidth.
+    pub incoming_bandwidth: Option<u32>,
+    /// Outgoing bandwidth of the client in bytes per second, or [`None`] for
+    /// unlimited bandwidth.
+    pub outgoing_bandwidth: Option<u32>,
+}
+
+impl EnetClientConfig {
+    /// Creates a config which connects to the given address, with no connect
+    /// data and unlimited bandwidth.
+    #[must_use]
+    pub fn new(addr: SocketAddrV4) -> Self {
+        Self {
+            addr,
+            connect_data: 0,
+            incoming_bandwidth: None,
+            outgoing_bandwidth: None,
+        }
+    }
+}
+
+/// Implementation of [`TransportClient`] using ENet.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug, Default(bound = ""))]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct EnetClient<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    state: State,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<P>,
+}
+
+/// Event raised by an [`EnetClient`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ClientEvent<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    /// This client has fully connected to a server.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Connected`].
+    Connected,
+    /// The connected server sent a message to the client.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Recv`].
+    Recv {
+        /// The message received.
+        msg: P::S2C,
+    },
+    /// A message received from the server failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`EnetClient::set_on_deserialize_error`].
+    MessageError {
+        /// The error which occurred.
+        cause: EnetError<P>,
+    },
+    /// The client lost connection from its previous server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Disconnected`].
+    Disconnected {
+        /// The reason why the client lost connection.
+        cause: EnetError<P>,
+    },
+}
+
+impl<P, T> From<ClientEvent<P>> for Option<aeronet::ClientEvent<P, T>>
+where
+    P: EnetProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+    T: TransportClient<P, Error = EnetError<P>>,
+{
+    fn from(value: ClientEvent<P>) -> Self {
+        match value {
+            ClientEvent::Connected => Some(aeronet::ClientEvent::Connected),
+            ClientEvent::Recv { msg } => Some(aeronet::ClientEvent::Recv { msg }),
+            ClientEvent::Disconnected { cause } => {
+                Some(aeronet::ClientEvent::Disconnected { cause })
+            }
+            ClientEvent::MessageError { .. } => None,
+        }
+    }
+}
+
+/// The current state of an [`EnetClient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    /// Not connected and is not attempting to connect to a server.
+    Disconnected,
+    /// Currently attempting to connect to a server.
+    Connecting,
+    /// Fully connected to a server and ready to transmit messages.
+    Connected,
+}
+
+// client states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Disconnected,
+    Connecting(Backend),
+    Connected(Backend, EnetInfo),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Backend {
+    #[derivative(Debug = "ignore")]
+    send_c2s: Sender<Outgoing>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
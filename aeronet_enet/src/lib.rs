@@ -0,0 +1,9 @@
+#![doc = include_str!("../README.md")]
+
+mod client;
+mod server;
+mod transport;
+
+pub use enet;
+
+pub use {client::*, server::*, transport::*};
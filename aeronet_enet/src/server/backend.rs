@@ -0,0 +1,250 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use enet::{Address, ChannelLimit, Enet, EventKind, Host, Peer, PeerID};
+use slotmap::{SecondaryMap, SlotMap};
+use tracing::debug;
+
+use crate::{
+    transport::{bandwidth, SERVICE_TIMEOUT},
+    BackendError, ClientKey, EnetInfo, EnetServerConfig, Outgoing,
+};
+
+/// Interval at which connection info is sent to the frontend.
+const INFO_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Request from the frontend to the backend.
+#[derive(Debug)]
+pub(super) enum Request {
+    Send { client: ClientKey, msg: Outgoing },
+    Disconnect { client: ClientKey },
+}
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected { client: ClientKey, info: EnetInfo },
+    Info { client: ClientKey, info: EnetInfo },
+    Recv { client: ClientKey, bytes: Vec<u8> },
+    Disconnected { client: ClientKey, data: u32 },
+    Closed { cause: BackendError },
+}
+
+pub(super) fn start(
+    config: &EnetServerConfig,
+    channel_count: usize,
+    send_open: &Sender<Result<(), BackendError>>,
+    recv_req: &Receiver<Request>,
+    send_update: &Sender<Update>,
+) {
+    let enet = match Enet::new() {
+        Ok(enet) => enet,
+        Err(err) => {
+            let _ = send_open.send(Err(BackendError::Initialize(err)));
+            return;
+        }
+    };
+
+    let addr = Address::new(*config.addr.ip(), config.addr.port());
+    let mut host = match enet.create_host::<ClientKey>(
+        Some(&addr),
+        config.max_clients,
+        ChannelLimit::Limited(channel_count),
+        bandwidth(config.incoming_bandwidth),
+        bandwidth(config.outgoing_bandwidth),
+    ) {
+        Ok(host) => host,
+        Err(err) => {
+            let _ = send_open.send(Err(BackendError::CreateHost(err)));
+            return;
+        }
+    };
+
+    if send_open.send(Ok(())).is_err() {
+        debug!("Frontend closed");
+        return;
+    }
+
+    debug!("Starting server loop");
+    let mut peers = SlotMap::<ClientKey, PeerID>::default();
+    let mut outgoing = SendQueues::default();
+    let mut last_info = Instant::now();
+    loop {
+        loop {
+            match recv_req.try_recv() {
+                Ok(Request::Send { client, msg }) => {
+                    if peers.contains_key(client) {
+                        outgoing.push(client, msg);
+                    }
+                }
+                Ok(Request::Disconnect { client }) => {
+                    let pending = outgoing.remove(client);
+                    if let Some(peer) = peers.remove(client).and_then(|id| host.peer_mut(id)) {
+                        // packets sent before the disconnect are still
+                        // delivered, as ENet flushes them before disconnecting
+                        for msg in pending {
+                            send_packet(peer, msg);
+                        }
+                        peer.set_data(None);
+                        peer.disconnect(0);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("Frontend closed");
+                    for (_, id) in peers.drain() {
+                        if let Some(peer) = host.peer_mut(id) {
+                            peer.disconnect_now(0);
+                        }
+                    }
+                    host.flush();
+                    return;
+                }
+            }
+        }
+
+        outgoing.flush(&mut host, &peers, config.send_quantum, config.send_budget);
+
+        match host.service(SERVICE_TIMEOUT) {
+            Ok(Some(event)) => {
+                let peer_id = event.peer_id();
+                let client = event.peer().data().copied();
+                let info = EnetInfo::from_peer(event.peer());
+                let received = match event.kind() {
+                    EventKind::Connect => Received::Connect,
+                    EventKind::Disconnect { data } => Received::Disconnect(*data),
+                    EventKind::Receive { packet, .. } => Received::Recv(packet.data().to_vec()),
+                };
+                drop(event);
+
+                match (received, client) {
+                    (Received::Connect, _) => {
+                        let client = peers.insert(peer_id);
+                        if let Some(peer) = host.peer_mut(peer_id) {
+                            peer.set_data(Some(client));
+                        }
+                        let _ = send_update.send(Update::Connected { client, info });
+                    }
+                    (Received::Disconnect(data), Some(client)) => {
+                        // peers disconnected by the frontend have already been
+                        // removed
+                        let _ = outgoing.remove(client);
+                        if peers.remove(client).is_some() {
+                            let _ = send_update.send(Update::Disconnected { client, data });
+                        }
+                    }
+                    (Received::Recv(bytes), Some(client)) => {
+                        let _ = send_update.send(Update::Recv { client, bytes });
+                    }
+                    (Received::Disconnect(_) | Received::Recv(_), None) => {}
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                let _ = send_update.send(Update::Closed {
+                    cause: BackendError::Service(err),
+                });
+                return;
+            }
+        }
+
+        if last_info.elapsed() >= INFO_INTERVAL {
+            last_info = Instant::now();
+            for (client, id) in &peers {
+                if let Some(peer) = host.peer(*id) {
+                    let info = EnetInfo::from_peer(peer);
+                    let _ = send_update.send(Update::Info { client, info });
+                }
+            }
+        }
+    }
+}
+
+/// Owned copy of the data of an [`enet::Event`], so that the host can be
+/// borrowed again after receiving it.
+enum Received {
+    Connect,
+    Disconnect(u32),
+    Recv(Vec<u8>),
+}
+
+/// Packets waiting to be queued to ENet, which are handed over to each client
+/// in round-robin order.
+///
+/// Without this, a single client with a large backlog of packets would have
+/// its entire backlog queued before any other client's packets, adding latency
+/// to every other client sharing this backend.
+#[derive(Default)]
+struct SendQueues {
+    queues: SecondaryMap<ClientKey, VecDeque<Outgoing>>,
+    /// Clients with a non-empty queue, in the order that they will be served.
+    ///
+    /// This may contain clients which have since been removed, which are
+    /// skipped when they are reached.
+    order: VecDeque<ClientKey>,
+}
+
+impl SendQueues {
+    fn push(&mut self, client: ClientKey, msg: Outgoing) {
+        let Some(queue) = self.queues.entry(client) else {
+            return;
+        };
+        let queue = queue.or_default();
+        if queue.is_empty() {
+            self.order.push_back(client);
+        }
+        queue.push_back(msg);
+    }
+
+    fn remove(&mut self, client: ClientKey) -> VecDeque<Outgoing> {
+        self.queues.remove(client).unwrap_or_default()
+    }
+
+    fn flush(
+        &mut self,
+        host: &mut Host<ClientKey>,
+        peers: &SlotMap<ClientKey, PeerID>,
+        quantum: usize,
+        budget: usize,
+    ) {
+        let quantum = quantum.max(1);
+        let mut sent = 0;
+        while sent < budget {
+            let Some(client) = self.order.pop_front() else {
+                break;
+            };
+            let Some(queue) = self.queues.get_mut(client) else {
+                continue;
+            };
+            let Some(peer) = peers.get(client).and_then(|id| host.peer_mut(*id)) else {
+                self.queues.remove(client);
+                continue;
+            };
+
+            let count = quantum.min(budget - sent).min(queue.len());
+            for msg in queue.drain(..count) {
+                send_packet(peer, msg);
+            }
+            sent += count;
+
+            if queue.is_empty() {
+                self.queues.remove(client);
+            } else {
+                self.order.push_back(client);
+            }
+        }
+    }
+}
+
+fn send_packet(peer: &mut Peer<ClientKey>, msg: Outgoing) {
+    // errors are only returned if the packet could not be allocated or the
+    // peer is not connected - in both cases, the peer will be disconnected soon
+    // anyway
+    let channel_id = msg.channel_id;
+    if let Ok(packet) = msg.into_packet() {
+        let _ = peer.send_packet(packet, channel_id);
+    }
+}
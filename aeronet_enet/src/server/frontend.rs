@@ -0,0 +1,416 @@
+use std::{mem, thread};
+
+use aeronet::{
+    CustomEvent, OnChannel, OnMessageError, SendToManyResult, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+use crossbeam_channel::TryRecvError;
+use slotmap::SecondaryMap;
+use tracing::debug;
+
+use crate::{
+    transport::{self, Outgoing},
+    ClientKey, EnetInfo, EnetProtocol, EnetServer, EnetServerConfig,
+};
+
+use super::{
+    backend::{self, Request, Update},
+    EnetError, OpenServer, OpeningServer, ServerEvent, State,
+};
+
+impl<P> EnetServer<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Creates a new server which is not open for connections, and is not
+    /// starting to open.
+    ///
+    /// If you want to create a server and start listening for connections
+    /// immediately after creation, use [`EnetServer::opening`] instead.
+    #[must_use]
+    pub fn closed() -> Self {
+        Self {
+            state: State::Closed,
+            event_buf: Vec::new(),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+        }
+    }
+
+    /// Creates and starts opening a server.
+    ///
+    /// This spawns a new thread for the server's backend, which runs until the
+    /// server is closed or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the protocol's channels can not be mapped to ENet channels.
+    pub fn opening(config: EnetServerConfig) -> Result<Self, EnetError<P>> {
+        let server = OpeningServer::new::<P>(config)?;
+        Ok(Self {
+            state: State::Opening(server),
+            event_buf: Vec::new(),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+        })
+    }
+
+    /// Attempts to open this server for connections.
+    ///
+    /// See [`EnetServer::opening`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already opening or is opened, or if the
+    /// protocol's channels can not be mapped to ENet channels.
+    pub fn open(&mut self, config: EnetServerConfig) -> Result<(), EnetError<P>> {
+        match self.state {
+            State::Closed => {
+                self.state = State::Opening(OpeningServer::new::<P>(config)?);
+                Ok(())
+            }
+            State::Opening(_) | State::Open(_) => Err(EnetError::<P>::BackendOpen),
+        }
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// a client.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// a client.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from a client fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from a client fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ServerEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+
+    /// Closes this server, disconnecting all clients and stopping the backend
+    /// thread.
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already closed.
+    pub fn close(&mut self) -> Result<(), EnetError<P>> {
+        match self.state {
+            State::Closed => Err(EnetError::<P>::BackendClosed),
+            State::Opening(_) | State::Open(_) => {
+                self.state = State::Closed;
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a message to a client without taking ownership of it.
+    ///
+    /// The message is serialized directly from the reference, so the same
+    /// message can be sent to many clients without cloning it for each one.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    pub fn send_ref(&mut self, client: ClientKey, msg: &P::S2C) -> Result<(), EnetError<P>> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(EnetError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P>(client, msg),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    /// Applies the serialize error policy to the result of sending a message.
+    fn apply_serialize_policy(
+        &mut self,
+        client: ClientKey,
+        result: Result<(), EnetError<P>>,
+    ) -> Result<(), EnetError<P>> {
+        match result {
+            Err(err @ EnetError::<P>::Serialize(_)) => match self.on_serialize_error {
+                OnMessageError::DisconnectClient => {
+                    let _ = self.disconnect(client);
+                    Err(err)
+                }
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message to {client:?}: {err:#}");
+                    Ok(())
+                }
+                OnMessageError::EmitEventOnly => Err(err),
+            },
+            result => result,
+        }
+    }
+}
+
+impl<P> TransportServer<P> for EnetServer<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    const TRANSPORT_NAME: &'static str = "enet";
+
+    type Client = ClientKey;
+
+    type Error = EnetError<P>;
+
+    type ConnectionInfo = EnetInfo;
+
+    type Event = ServerEvent<P>;
+
+    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server.clients.get(client).cloned(),
+        }
+    }
+
+    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+        let clients = match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.clients.keys()),
+        };
+        clients.into_iter().flatten()
+    }
+
+    fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(EnetError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P>(client, &msg.into()),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        let msg = msg.into();
+        if let State::Open(server) = &self.state {
+            if let Ok(serialized) = transport::serialize::<P, _, _>(&msg) {
+                return clients
+                    .into_iter()
+                    .map(|client| (client, server.queue::<P>(client, serialized.clone())))
+                    .collect();
+            }
+        }
+
+        // the serialize error policy is applied separately for each client
+        clients
+            .into_iter()
+            .map(|client| (client, self.send_ref(client, &msg)))
+            .collect()
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let on_deserialize_error = self.on_deserialize_error;
+        let mut events = mem::take(&mut self.event_buf);
+        self.state = match mem::take(&mut self.state) {
+            State::Closed => State::Closed,
+            State::Opening(server) => match server.recv_open.try_recv() {
+                Ok(Ok(())) => {
+                    events.push(ServerEvent::Opened);
+                    State::Open(OpenServer {
+                        clients: SecondaryMap::new(),
+                        send_req: server.send_req,
+                        recv_update: server.recv_update,
+                    })
+                }
+                Ok(Err(cause)) => {
+                    events.push(ServerEvent::Closed {
+                        cause: cause.into(),
+                    });
+                    State::Closed
+                }
+                Err(TryRecvError::Empty) => State::Opening(server),
+                Err(TryRecvError::Disconnected) => {
+                    events.push(ServerEvent::Closed {
+                        cause: EnetError::<P>::BackendClosed,
+                    });
+                    State::Closed
+                }
+            },
+            State::Open(mut server) => match server.recv::<P>(&mut events, on_deserialize_error) {
+                Ok(()) => State::Open(server),
+                Err(cause) => {
+                    events.push(ServerEvent::Closed { cause });
+                    State::Closed
+                }
+            },
+        };
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+        let client = client.into();
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(EnetError::<P>::BackendClosed),
+            State::Open(server) => {
+                server.disconnect::<P>(client)?;
+                self.event_buf.push(ServerEvent::Disconnected {
+                    client,
+                    cause: EnetError::<P>::ForceDisconnect,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        self.event_buf.push(ServerEvent::Custom { event });
+    }
+}
+
+impl OpeningServer {
+    fn new<P>(config: EnetServerConfig) -> Result<Self, EnetError<P>>
+    where
+        P: EnetProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        let channel_count = transport::channel_count::<P, _, _>()?;
+        let (send_open, recv_open) = crossbeam_channel::bounded(1);
+        let (send_req, recv_req) = crossbeam_channel::unbounded();
+        let (send_update, recv_update) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            backend::start(&config, channel_count, &send_open, &recv_req, &send_update);
+            debug!("Server backend stopped");
+        });
+        Ok(Self {
+            recv_open,
+            send_req,
+            recv_update,
+        })
+    }
+}
+
+impl OpenServer {
+    fn send<P>(&self, client: ClientKey, msg: &P::S2C) -> Result<(), EnetError<P>>
+    where
+        P: EnetProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(EnetError::<P>::NoClient(client));
+        }
+
+        let msg = transport::serialize::<P, _, _>(msg)?;
+        self.queue::<P>(client, msg)
+    }
+
+    fn queue<P>(&self, client: ClientKey, msg: Outgoing) -> Result<(), EnetError<P>>
+    where
+        P: EnetProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(EnetError::<P>::NoClient(client));
+        }
+
+        self.send_req
+            .send(Request::Send { client, msg })
+            .map_err(|_| EnetError::<P>::BackendClosed)
+    }
+
+    fn disconnect<P>(&mut self, client: ClientKey) -> Result<(), EnetError<P>>
+    where
+        P: EnetProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        if self.clients.remove(client).is_none() {
+            return Err(EnetError::<P>::NoClient(client));
+        }
+
+        let _ = self.send_req.send(Request::Disconnect { client });
+        Ok(())
+    }
+
+    fn recv<P>(
+        &mut self,
+        events: &mut Vec<ServerEvent<P>>,
+        on_deserialize_error: OnMessageError,
+    ) -> Result<(), EnetError<P>>
+    where
+        P: EnetProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        loop {
+            match self.recv_update.try_recv() {
+                Ok(Update::Connected { client, info }) => {
+                    self.clients.insert(client, info);
+                    events.push(ServerEvent::Connected { client });
+                }
+                Ok(Update::Info { client, info }) => {
+                    if let Some(client) = self.clients.get_mut(client) {
+                        *client = info;
+                    }
+                }
+                Ok(Update::Recv { client, bytes }) => {
+                    if !self.clients.contains_key(client) {
+                        continue;
+                    }
+
+                    let cause = match P::C2S::try_from_bytes(&bytes) {
+                        Ok(msg) => {
+                            events.push(ServerEvent::Recv { client, msg });
+                            continue;
+                        }
+                        Err(err) => EnetError::<P>::Deserialize(err),
+                    };
+                    match on_deserialize_error {
+                        OnMessageError::DisconnectClient => {
+                            self.clients.remove(client);
+                            let _ = self.send_req.send(Request::Disconnect { client });
+                            events.push(ServerEvent::Disconnected { client, cause });
+                        }
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message from {client:?}: {cause:#}");
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ServerEvent::MessageError { client, cause });
+                        }
+                    }
+                }
+                Ok(Update::Disconnected { client, data }) => {
+                    if self.clients.remove(client).is_some() {
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: EnetError::<P>::Disconnected(data),
+                        });
+                    }
+                }
+                Ok(Update::Closed { cause }) => return Err(cause.into()),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(EnetError::<P>::BackendClosed),
+            }
+        }
+    }
+}
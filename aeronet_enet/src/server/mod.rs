@@ -0,0 +1,213 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, net::SocketAddrV4};
+
+use aeronet::{
+    CustomEvent, OnChannel, OnMessageError, TransportProtocol, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+use crossbeam_channel::{Receiver, Sender};
+use derivative::Derivative;
+use slotmap::SecondaryMap;
+
+use crate::{BackendError, ClientKey, EnetInfo, EnetProtocol};
+
+use self::backend::{Request, Update};
+
+type EnetError<P> = crate::EnetError<<P as TransportProtocol>::S2C, <P as TransportProtocol>::C2S>;
+
+/// Configuration for opening an [`EnetServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnetServerConfig {
+    /// Address to listen for connections on.
+    pub addr: SocketAddrV4,
+    /// Maximum number of clients which can be connected at once.
+    pub max_clients: usize,
+    /// Incoming bandwidth of the server in bytes per second, or [`None`] for
+    /// unlimited bandwidth.
+    pub incoming_bandwidth: Option<u32>,
+    /// Outgoing bandwidth of the server in bytes per second, or [`None`] for
+    /// unlimited bandwidth.
+    pub outgoing_bandwidth: Option<u32>,
+    /// Maximum number of packets queued to a single client before the backend
+    /// moves on to the next client with pending packets.
+    ///
+    /// Outgoing packets are queued per client, and clients are served in
+    /// round-robin order, so that a client receiving many messages does not
+    /// delay the messages sent to every other client.
+    pub send_quantum: usize,
+    /// Maximum number of packets queued to ENet in total between two services
+    /// of the host.
+    ///
+    /// Packets over this budget stay queued in the backend until the next
+    /// service, so that received packets are still processed while a large
+    /// amount of data is being sent.
+    pub send_budget: usize,
+}
+
+impl EnetServerConfig {
+    /// Default value of [`EnetServerConfig::max_clients`].
+    pub const DEFAULT_MAX_CLIENTS: usize = 32;
+
+    /// Default value of [`EnetServerConfig::send_quantum`].
+    pub const DEFAULT_SEND_QUANTUM: usize = 1;
+
+    /// Default value of [`EnetServerConfig::send_budget`].
+    pub const DEFAULT_SEND_BUDGET: usize = 1024;
+
+    /// Creates a config which listens on the given address, allowing
+    /// [`EnetServerConfig::DEFAULT_MAX_CLIENTS`] clients with unlimited
+    /// bandwidth.
+    #[must_use]
+    pub fn new(addr: SocketAddrV4) -> Self {
+        Self {
+            addr,
+            max_clients: Self::DEFAULT_MAX_CLIENTS,
+            incoming_bandwidth: None,
+            outgoing_bandwidth: None,
+            send_quantum: Self::DEFAULT_SEND_QUANTUM,
+            send_budget: Self::DEFAULT_SEND_BUDGET,
+        }
+    }
+}
+
+/// Implementation of [`TransportServer`] using ENet.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct EnetServer<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    state: State,
+    #[derivative(Debug = "ignore")]
+    event_buf: Vec<ServerEvent<P>>,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+}
+
+/// Event raised by an [`EnetServer`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ServerEvent<P>
+where
+    P: EnetProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// The server backend has been set up and is ready to accept connections.
+    Opened,
+    /// A client has connected to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Connected`].
+    Connected {
+        /// The key of the client.
+        client: ClientKey,
+    },
+    /// A client sent a message to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Recv`].
+    Recv {
+        /// The key of the client which sent the message.
+        client: ClientKey,
+        /// The message.
+        msg: P::C2S,
+    },
+    /// A message received from a client failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`EnetServer::set_on_deserialize_error`].
+    MessageError {
+        /// The key of the client.
+        client: ClientKey,
+        /// The error which occurred.
+        cause: EnetError<P>,
+    },
+    /// A client has lost connection from this server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Disconnected`].
+    Disconnected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The reason why the client lost connection.
+        cause: EnetError<P>,
+    },
+    /// The server backend has been shut down, all client connections have been
+    /// dropped, and the backend must be re-opened.
+    Closed {
+        /// The reason why the backend was closed.
+        cause: EnetError<P>,
+    },
+    /// A user-defined event was injected using
+    /// [`TransportServer::push_event`].
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Custom`].
+    Custom {
+        /// The event.
+        event: CustomEvent,
+    },
+}
+
+impl<P, T> From<ServerEvent<P>> for Option<aeronet::ServerEvent<P, T>>
+where
+    P: EnetProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    T: TransportServer<P, Client = ClientKey, Error = EnetError<P>>,
+{
+    fn from(value: ServerEvent<P>) -> Self {
+        match value {
+            ServerEvent::Connected { client } => Some(aeronet::ServerEvent::Connected { client }),
+            ServerEvent::Recv { client, msg } => Some(aeronet::ServerEvent::Recv { client, msg }),
+            ServerEvent::Disconnected { client, cause } => {
+                Some(aeronet::ServerEvent::Disconnected { client, cause })
+            }
+            ServerEvent::Custom { event } => Some(aeronet::ServerEvent::Custom { event }),
+            ServerEvent::Opened | ServerEvent::MessageError { .. } | ServerEvent::Closed { .. } => {
+                None
+            }
+        }
+    }
+}
+
+// server states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Closed,
+    Opening(OpeningServer),
+    Open(OpenServer),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpeningServer {
+    #[derivative(Debug = "ignore")]
+    recv_open: Receiver<Result<(), BackendError>>,
+    #[derivative(Debug = "ignore")]
+    send_req: Sender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpenServer {
+    clients: SecondaryMap<ClientKey, EnetInfo>,
+    #[derivative(Debug = "ignore")]
+    send_req: Sender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
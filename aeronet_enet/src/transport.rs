@@ -0,0 +1,206 @@
+use std::{
+    fmt::Debug,
+    net::{SocketAddr, SocketAddrV4},
+    time::Duration,
+};
+
+use aeronet::{
+    ChannelKey, ChannelKind, Message, OnChannel, RemoteAddr, Rtt, TransportProtocol, TryFromBytes,
+    TryIntoBytes,
+};
+
+slotmap::new_key_type! {
+    /// Key type used to uniquely identify a client connected to an
+    /// [`EnetServer`].
+    ///
+    /// [`EnetServer`]: crate::EnetServer
+    pub struct ClientKey;
+}
+
+/// Extension of [`TransportProtocol`] for ENet implementations.
+pub trait EnetProtocol: TransportProtocol {
+    /// The type of [`ChannelKey`] used to specify along what channel a message
+    /// is sent.
+    ///
+    /// Each variant is sent on the ENet channel with the same ID as its
+    /// [`ChannelKey::index`], so there may be at most [`MAX_CHANNELS`]
+    /// variants.
+    type Channel: ChannelKey;
+}
+
+/// Maximum number of channels that an ENet connection supports.
+pub const MAX_CHANNELS: usize = 255;
+
+/// Statistics on the network state of an ENet peer managed by an endpoint.
+///
+/// This serves as a snapshot of network stats, not a live updating value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnetInfo {
+    /// The mean round-trip time of the connection as defined by [`Rtt`].
+    pub rtt: Duration,
+    /// The remote address of this connection as defined by [`RemoteAddr`].
+    pub remote_addr: SocketAddrV4,
+}
+
+impl EnetInfo {
+    pub(crate) fn from_peer<T>(peer: &enet::Peer<T>) -> Self {
+        let addr = peer.address();
+        Self {
+            rtt: peer.mean_rtt(),
+            remote_addr: SocketAddrV4::new(*addr.ip(), addr.port()),
+        }
+    }
+}
+
+impl Rtt for EnetInfo {
+    fn rtt(&self) -> Duration {
+        self.rtt
+    }
+}
+
+impl RemoteAddr for EnetInfo {
+    fn remote_addr(&self) -> SocketAddr {
+        SocketAddr::V4(self.remote_addr)
+    }
+}
+
+/// Error that occurs when processing an ENet transport implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum EnetError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    /// The backend that handles connections is shut down or not ready for
+    /// this operation.
+    #[error("backend closed")]
+    BackendClosed,
+    /// Attempted to open the backend while it was already open.
+    #[error("backend already open")]
+    BackendOpen,
+    /// The protocol's channel key has more variants than ENet supports.
+    ///
+    /// See [`MAX_CHANNELS`].
+    #[error("too many channels: {0} > {MAX_CHANNELS}")]
+    TooManyChannels(usize),
+    /// Failed to initialize the ENet library.
+    #[error("failed to initialize ENet")]
+    Initialize(#[source] enet::InitializationError),
+    /// Failed to create the ENet host.
+    #[error("failed to create host")]
+    CreateHost(#[source] enet::Error),
+    /// Failed to start connecting to the server.
+    #[error("failed to connect")]
+    Connect(#[source] enet::Error),
+    /// Failed to poll the ENet host for events.
+    #[error("failed to service host")]
+    Service(#[source] enet::Error),
+    /// Attempted to perform an operation on a client which does not exist.
+    #[error("no client with key {0:?}")]
+    NoClient(ClientKey),
+    /// The other side closed the connection, or the connection timed out.
+    ///
+    /// Contains the user data sent along with the disconnect by the other
+    /// side.
+    #[error("disconnected with data {0}")]
+    Disconnected(u32),
+    /// The client was forcefully disconnected by the server.
+    #[error("force disconnect")]
+    ForceDisconnect,
+    /// Failed to serialize data using [`TryIntoBytes::try_into_bytes`].
+    #[error("failed to serialize data")]
+    Serialize(#[source] S::Error),
+    /// Failed to deserialize data using [`TryFromBytes::try_from_bytes`].
+    #[error("failed to deserialize data")]
+    Deserialize(#[source] R::Error),
+}
+
+/// Error that occurs in a backend, which is independent of the message types.
+#[derive(Debug)]
+pub(crate) enum BackendError {
+    Initialize(enet::InitializationError),
+    CreateHost(enet::Error),
+    Connect(enet::Error),
+    Service(enet::Error),
+}
+
+impl<S, R> From<BackendError> for EnetError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    fn from(value: BackendError) -> Self {
+        match value {
+            BackendError::Initialize(err) => Self::Initialize(err),
+            BackendError::CreateHost(err) => Self::CreateHost(err),
+            BackendError::Connect(err) => Self::Connect(err),
+            BackendError::Service(err) => Self::Service(err),
+        }
+    }
+}
+
+/// Time that a backend blocks waiting for network events before checking for
+/// requests from the frontend again.
+pub(crate) const SERVICE_TIMEOUT: Duration = Duration::from_millis(1);
+
+pub(crate) fn bandwidth(limit: Option<u32>) -> enet::BandwidthLimit {
+    limit.map_or(
+        enet::BandwidthLimit::Unlimited,
+        enet::BandwidthLimit::Limited,
+    )
+}
+
+/// A message which has been serialized by the frontend, waiting to be sent by
+/// the backend.
+#[derive(Debug, Clone)]
+pub(crate) struct Outgoing {
+    pub channel_id: u8,
+    pub kind: ChannelKind,
+    pub bytes: Vec<u8>,
+}
+
+impl Outgoing {
+    pub fn into_packet(self) -> Result<enet::Packet, enet::Error> {
+        let mode = match self.kind {
+            ChannelKind::Unreliable => enet::PacketMode::UnreliableUnsequenced,
+            ChannelKind::ReliableUnordered | ChannelKind::ReliableOrdered => {
+                enet::PacketMode::ReliableSequenced
+            }
+        };
+        enet::Packet::new(self.bytes, mode)
+    }
+}
+
+/// Checks that the protocol's channels can all be mapped to ENet channels,
+/// returning the number of channels.
+pub(crate) fn channel_count<P, S, R>() -> Result<usize, EnetError<S, R>>
+where
+    P: EnetProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let count = P::Channel::ALL.len();
+    if count > MAX_CHANNELS {
+        Err(EnetError::TooManyChannels(count))
+    } else {
+        Ok(count)
+    }
+}
+
+/// Serializes a message so that it can be passed to the backend.
+pub(crate) fn serialize<P, S, R>(msg: &S) -> Result<Outgoing, EnetError<S, R>>
+where
+    P: EnetProtocol,
+    S: Message + TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Message + TryFromBytes,
+{
+    let channel = msg.channel();
+    let serialized = msg.try_into_bytes().map_err(EnetError::Serialize)?;
+    Ok(Outgoing {
+        // the channel count is checked when the backend is started
+        #[allow(clippy::cast_possible_truncation)]
+        channel_id: channel.index() as u8,
+        kind: channel.kind(),
+        bytes: serialized.as_ref().to_vec(),
+    })
+}
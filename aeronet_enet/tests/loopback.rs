@@ -0,0 +1,205 @@
+//! Tests a client and server connected over the loopback interface.
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddrV4},
+    thread,
+    time::{Duration, Instant},
+};
+
+use aeronet::{
+    ChannelKey, OnChannel, TransportClient, TransportProtocol, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+use aeronet_enet::{
+    ClientEvent, ClientKey, EnetClient, EnetClientConfig, EnetError, EnetProtocol, EnetServer,
+    EnetServerConfig, ServerEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChannelKey)]
+enum Lane {
+    #[channel_kind(Unreliable)]
+    Unreliable,
+    #[channel_kind(ReliableUnordered)]
+    ReliableUnordered,
+    #[channel_kind(ReliableOrdered)]
+    ReliableOrdered,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Msg(Lane, Vec<u8>);
+
+impl TryIntoBytes for Msg {
+    type Output<'a> = Vec<u8>;
+
+    type Error = io::Error;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        let mut bytes = vec![u8::try_from(self.0.index()).unwrap()];
+        bytes.extend_from_slice(&self.1);
+        Ok(bytes)
+    }
+}
+
+impl TryFromBytes for Msg {
+    type Error = io::Error;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        let (&lane, body) = buf
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty message"))?;
+        let lane = Lane::ALL
+            .get(usize::from(lane))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid lane"))?;
+        Ok(Self(*lane, body.to_vec()))
+    }
+}
+
+impl OnChannel for Msg {
+    type Channel = Lane;
+
+    fn channel(&self) -> Self::Channel {
+        self.0
+    }
+}
+
+struct AppProtocol;
+
+impl TransportProtocol for AppProtocol {
+    type C2S = Msg;
+    type S2C = Msg;
+}
+
+impl EnetProtocol for AppProtocol {
+    type Channel = Lane;
+}
+
+type Server = EnetServer<AppProtocol>;
+type Client = EnetClient<AppProtocol>;
+
+/// Calls `f` until it returns a value, panicking if it takes too long.
+fn poll<T>(mut f: impl FnMut() -> Option<T>) -> T {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Some(value) = f() {
+            return value;
+        }
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Opens a server on `port` and connects a client to it.
+///
+/// ENet does not expose the port that a host is bound to, so each test uses
+/// its own port, to be able to run in parallel.
+fn connect(port: u16) -> (Server, Client, ClientKey) {
+    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, port);
+    let mut server = Server::opening(EnetServerConfig::new(addr)).unwrap();
+    poll(|| {
+        server
+            .recv()
+            .any(|event| matches!(event, ServerEvent::Opened))
+            .then_some(())
+    });
+
+    let mut client = Client::connecting(EnetClientConfig::new(addr)).unwrap();
+    // either side may see the connection first
+    let mut client_connected = false;
+    let mut key = None;
+    let key = poll(|| {
+        client_connected |= client
+            .recv()
+            .any(|event| matches!(event, ClientEvent::Connected));
+        key = key.or_else(|| {
+            server.recv().find_map(|event| match event {
+                ServerEvent::Connected { client } => Some(client),
+                _ => None,
+            })
+        });
+        key.filter(|_| client_connected)
+    });
+    (server, client, key)
+}
+
+#[test]
+fn send_recv_on_each_lane() {
+    let (mut server, mut client, key) = connect(27310);
+    assert_eq!(vec![key], server.connected_clients().collect::<Vec<_>>());
+
+    for &lane in Lane::ALL {
+        let msg = Msg(lane, b"ping".to_vec());
+        client.send(msg.clone()).unwrap();
+        let (from, received) = poll(|| {
+            server.recv().find_map(|event| match event {
+                ServerEvent::Recv { client, msg } => Some((client, msg)),
+                _ => None,
+            })
+        });
+        assert_eq!(key, from);
+        assert_eq!(msg, received);
+
+        let msg = Msg(lane, b"pong".to_vec());
+        server.send(key, msg.clone()).unwrap();
+        let received = poll(|| {
+            client.recv().find_map(|event| match event {
+                ClientEvent::Recv { msg } => Some(msg),
+                _ => None,
+            })
+        });
+        assert_eq!(msg, received);
+    }
+}
+
+#[test]
+fn reliable_ordered_keeps_order() {
+    let (mut server, mut client, _) = connect(27311);
+    // ENet only orders messages within a channel
+    let sent = (0..32u8)
+        .map(|i| Msg(Lane::ReliableOrdered, vec![i]))
+        .collect::<Vec<_>>();
+    for msg in &sent {
+        client.send(msg.clone()).unwrap();
+    }
+
+    let mut received = Vec::new();
+    poll(|| {
+        received.extend(server.recv().filter_map(|event| match event {
+            ServerEvent::Recv { msg, .. } => Some(msg),
+            _ => None,
+        }));
+        (received.len() >= sent.len()).then_some(())
+    });
+    assert_eq!(sent, received);
+}
+
+#[test]
+fn client_disconnect() {
+    let (mut server, mut client, key) = connect(27312);
+    client.disconnect().unwrap();
+
+    let (from, cause) = poll(|| {
+        server.recv().find_map(|event| match event {
+            ServerEvent::Disconnected { client, cause } => Some((client, cause)),
+            _ => None,
+        })
+    });
+    assert_eq!(key, from);
+    assert!(matches!(cause, EnetError::Disconnected(0)), "{cause:?}");
+    assert_eq!(0, server.connected_clients().count());
+}
+
+#[test]
+fn server_disconnect() {
+    let (mut server, mut client, key) = connect(27313);
+    server.disconnect(key).unwrap();
+    assert_eq!(0, server.connected_clients().count());
+
+    let cause = poll(|| {
+        client.recv().find_map(|event| match event {
+            ClientEvent::Disconnected { cause } => Some(cause),
+            _ => None,
+        })
+    });
+    assert!(matches!(cause, EnetError::Disconnected(0)), "{cause:?}");
+}
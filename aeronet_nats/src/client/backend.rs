@@ -0,0 +1,110 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use tracing::debug;
+
+use crate::{transport::SERVICE_TIMEOUT, BackendError, NatsClientConfig, NatsInfo};
+
+/// Interval at which connection info is sent to the frontend.
+const INFO_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected { info: NatsInfo },
+    Info { info: NatsInfo },
+    Recv { bytes: Vec<u8> },
+    Error { cause: BackendError },
+}
+
+pub(super) fn start(
+    config: &NatsClientConfig,
+    recv_c2s: &Receiver<Vec<u8>>,
+    send_update: &Sender<Update>,
+) {
+    let mut options = nats::Options::new();
+    if let Some(name) = &config.connection_name {
+        options = options.with_name(name);
+    }
+    let conn = match options.connect(&config.url) {
+        Ok(conn) => conn,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Connect(err),
+            });
+            return;
+        }
+    };
+
+    let sub = match conn.subscribe(&config.subscribe_subject) {
+        Ok(sub) => sub,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Subscribe(err),
+            });
+            return;
+        }
+    };
+
+    let mut info = NatsInfo {
+        rtt: conn.rtt().unwrap_or_default(),
+        msgs_sent: 0,
+        msgs_recv: 0,
+    };
+    let _ = send_update.send(Update::Connected { info: info.clone() });
+
+    debug!("Starting client loop");
+    let mut last_info = Instant::now();
+    loop {
+        loop {
+            match recv_c2s.try_recv() {
+                Ok(bytes) => {
+                    if let Err(err) = conn.publish(&config.publish_subject, bytes) {
+                        let _ = send_update.send(Update::Error {
+                            cause: BackendError::Publish(err),
+                        });
+                        return;
+                    }
+                    info.msgs_sent += 1;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("Frontend closed");
+                    let _ = conn.flush();
+                    conn.close();
+                    return;
+                }
+            }
+        }
+
+        match sub.next_timeout(SERVICE_TIMEOUT) {
+            Ok(msg) => {
+                info.msgs_recv += 1;
+                let _ = send_update.send(Update::Recv { bytes: msg.data });
+                // drain any other messages which arrived in the meantime
+                while let Some(msg) = sub.try_next() {
+                    info.msgs_recv += 1;
+                    let _ = send_update.send(Update::Recv { bytes: msg.data });
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => {}
+            Err(err) => {
+                let _ = send_update.send(Update::Error {
+                    cause: BackendError::Recv(err),
+                });
+                return;
+            }
+        }
+
+        if last_info.elapsed() >= INFO_INTERVAL {
+            last_info = Instant::now();
+            if let Ok(rtt) = conn.rtt() {
+                info.rtt = rtt;
+            }
+            let _ = send_update.send(Update::Info { info: info.clone() });
+        }
+    }
+}
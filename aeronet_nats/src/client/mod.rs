@@ -0,0 +1,162 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, marker::PhantomData};
+
+use aeronet::{OnMessageError, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes};
+use crossbeam_channel::{Receiver, Sender};
+use derivative::Derivative;
+
+use crate::NatsInfo;
+
+use self::backend::Update;
+
+type NatsError<P> = crate::NatsError<<P as TransportProtocol>::C2S, <P as TransportProtocol>::S2C>;
+
+/// Configuration for connecting a [`NatsClient`] to a NATS server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsClientConfig {
+    /// URL of the NATS server to connect to, e.g. `nats://localhost:4222`.
+    ///
+    /// Multiple servers may be given as a comma-separated list.
+    pub url: String,
+    /// Subject that messages sent by this client are published to.
+    pub publish_subject: String,
+    /// Subject that this client subscribes to, and receives messages from.
+    pub subscribe_subject: String,
+    /// Name of this connection, which is shown in the NATS server's monitoring
+    /// tools.
+    pub connection_name: Option<String>,
+}
+
+impl NatsClientConfig {
+    /// Creates a config which connects to the given server, and sends and
+    /// receives messages on the given subjects.
+    #[must_use]
+    pub fn new(
+        url: impl Into<String>,
+        publish_subject: impl Into<String>,
+        subscribe_subject: impl Into<String>,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            publish_subject: publish_subject.into(),
+            subscribe_subject: subscribe_subject.into(),
+            connection_name: None,
+        }
+    }
+}
+
+/// Implementation of [`TransportClient`] bridging to backend services through
+/// a NATS server.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug, Default(bound = ""))]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct NatsClient<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    state: State,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<P>,
+}
+
+/// Event raised by a [`NatsClient`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ClientEvent<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    /// This client has connected to the NATS server, and has subscribed to its
+    /// subject.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Connected`].
+    Connected,
+    /// A message was published to the subject that this client is subscribed
+    /// to.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Recv`].
+    Recv {
+        /// The message received.
+        msg: P::S2C,
+    },
+    /// A message received from the subscription failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`NatsClient::set_on_deserialize_error`].
+    MessageError {
+        /// The error which occurred.
+        cause: NatsError<P>,
+    },
+    /// The client lost connection from the NATS server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Disconnected`].
+    Disconnected {
+        /// The reason why the client lost connection.
+        cause: NatsError<P>,
+    },
+}
+
+impl<P, T> From<ClientEvent<P>> for Option<aeronet::ClientEvent<P, T>>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+    T: TransportClient<P, Error = NatsError<P>>,
+{
+    fn from(value: ClientEvent<P>) -> Self {
+        match value {
+            ClientEvent::Connected => Some(aeronet::ClientEvent::Connected),
+            ClientEvent::Recv { msg } => Some(aeronet::ClientEvent::Recv { msg }),
+            ClientEvent::Disconnected { cause } => {
+                Some(aeronet::ClientEvent::Disconnected { cause })
+            }
+            ClientEvent::MessageError { .. } => None,
+        }
+    }
+}
+
+/// The current state of a [`NatsClient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    /// Not connected and is not attempting to connect to a NATS server.
+    Disconnected,
+    /// Currently attempting to connect to a NATS server.
+    Connecting,
+    /// Connected to a NATS server and subscribed, ready to transmit messages.
+    Connected,
+}
+
+// client states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Disconnected,
+    Connecting(Backend),
+    Connected(Backend, NatsInfo),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Backend {
+    #[derivative(Debug = "ignore")]
+    send_c2s: Sender<Vec<u8>>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
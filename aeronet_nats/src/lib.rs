@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+
+mod client;
+mod transport;
+
+pub use nats;
+
+pub use {client::*, transport::*};
@@ -0,0 +1,88 @@
+use std::{io, time::Duration};
+
+use aeronet::{Message, Rtt, TryFromBytes, TryIntoBytes};
+
+/// Statistics on the state of a NATS connection managed by a client.
+///
+/// This serves as a snapshot of network stats, not a live updating value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsInfo {
+    /// The round-trip time to the NATS server as defined by [`Rtt`].
+    ///
+    /// Note that this is the round-trip time to the broker, not to the service
+    /// on the other end of the bridge.
+    pub rtt: Duration,
+    /// Number of messages published by this client.
+    pub msgs_sent: usize,
+    /// Number of messages received by this client.
+    pub msgs_recv: usize,
+}
+
+impl Rtt for NatsInfo {
+    fn rtt(&self) -> Duration {
+        self.rtt
+    }
+}
+
+/// Error that occurs when processing a NATS transport implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum NatsError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    /// The backend that handles connections is shut down or not ready for
+    /// this operation.
+    #[error("backend closed")]
+    BackendClosed,
+    /// Attempted to open the backend while it was already open.
+    #[error("backend already open")]
+    BackendOpen,
+    /// Failed to connect to the NATS server.
+    #[error("failed to connect")]
+    Connect(#[source] io::Error),
+    /// Failed to subscribe to the subject that messages are received on.
+    #[error("failed to subscribe")]
+    Subscribe(#[source] io::Error),
+    /// Failed to publish a message to the subject that messages are sent on.
+    #[error("failed to publish")]
+    Publish(#[source] io::Error),
+    /// Failed to receive a message from the subscription, e.g. because the
+    /// connection to the NATS server was lost.
+    #[error("failed to receive")]
+    Recv(#[source] io::Error),
+    /// Failed to serialize data using [`TryIntoBytes::try_into_bytes`].
+    #[error("failed to serialize data")]
+    Serialize(#[source] S::Error),
+    /// Failed to deserialize data using [`TryFromBytes::try_from_bytes`].
+    #[error("failed to deserialize data")]
+    Deserialize(#[source] R::Error),
+}
+
+/// Error that occurs in a backend, which is independent of the message types.
+#[derive(Debug)]
+pub(crate) enum BackendError {
+    Connect(io::Error),
+    Subscribe(io::Error),
+    Publish(io::Error),
+    Recv(io::Error),
+}
+
+impl<S, R> From<BackendError> for NatsError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    fn from(value: BackendError) -> Self {
+        match value {
+            BackendError::Connect(err) => Self::Connect(err),
+            BackendError::Subscribe(err) => Self::Subscribe(err),
+            BackendError::Publish(err) => Self::Publish(err),
+            BackendError::Recv(err) => Self::Recv(err),
+        }
+    }
+}
+
+/// Time that a backend blocks waiting for messages from the subscription
+/// before checking for requests from the frontend again.
+pub(crate) const SERVICE_TIMEOUT: Duration = Duration::from_millis(1);
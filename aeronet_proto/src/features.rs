@@ -0,0 +1,202 @@
+use core::{
+    fmt,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
+
+/// Length in bytes of an encoded features frame.
+pub const FEATURES_FRAME_LEN: usize = 8;
+
+/// Bytes which every features frame starts with.
+///
+/// This lets a peer tell a features stream apart from other unidirectional
+/// streams, such as the quality report stream, by its first bytes.
+pub const FEATURES_MAGIC: [u8; 4] = *b"AEFT";
+
+/// Set of optional protocol features supported by a peer, or negotiated
+/// between two peers.
+///
+/// After a connection is established, each peer advertises the features that
+/// it supports, and the features which both peers support are the
+/// [negotiated](Features::negotiate) features of the connection. A peer only
+/// uses a feature if it was negotiated, so peers built from different
+/// revisions of an app can connect to each other, and lose only the features
+/// which one of them does not know about.
+///
+/// Encoded as [`FEATURES_MAGIC`], followed by the bits of the set as a
+/// big-endian `u32`. Bits which this revision does not know about are kept, so
+/// that they are negotiated correctly against a newer peer.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Features(u32);
+
+impl Features {
+    /// No features.
+    pub const NONE: Self = Self(0);
+
+    /// Messages may be compressed.
+    pub const COMPRESSION: Self = Self(1 << 0);
+
+    /// Messages larger than a single datagram may be split into fragments and
+    /// reassembled by the receiver.
+    pub const FRAGMENTATION: Self = Self(1 << 1);
+
+    // bit 2 is reserved for tracked acknowledgements, which are not
+    // implemented yet, so must not be advertised
+
+    /// The peers exchange timestamps to synchronize their clocks.
+    pub const CLOCK_SYNC: Self = Self(1 << 3);
+
+    /// The peers measure the round-trip time using [`Ping`]s sent on their
+    /// features streams, which bypass the message lanes.
+    ///
+    /// [`Ping`]: crate::Ping
+    pub const PING: Self = Self(1 << 4);
+
+    /// The peers may move the messages of a channel onto another lane while
+    /// the connection is open, using [`LaneMigration`] frames.
+    ///
+    /// [`LaneMigration`]: crate::LaneMigration
+    pub const LANE_MIGRATION: Self = Self(1 << 5);
+
+    /// All features known by this revision, along with their names.
+    pub const KNOWN: [(Self, &'static str); 5] = [
+        (Self::COMPRESSION, "compression"),
+        (Self::FRAGMENTATION, "fragmentation"),
+        (Self::CLOCK_SYNC, "clock_sync"),
+        (Self::PING, "ping"),
+        (Self::LANE_MIGRATION, "lane_migration"),
+    ];
+
+    /// Creates a set from its raw bits, keeping any unknown bits.
+    #[must_use]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Gets the raw bits of this set.
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Gets if this set has no features.
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Gets if this set has all features of `other`.
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Gets the features in either this set or `other`.
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Gets the features in both this set and `other`.
+    #[must_use]
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Gets the features of a connection, where this set is supported by the
+    /// local peer and `remote` is advertised by the remote peer.
+    ///
+    /// This is the [intersection](Features::intersection) of both sets, so
+    /// both peers negotiate the same features.
+    #[must_use]
+    pub const fn negotiate(self, remote: Self) -> Self {
+        self.intersection(remote)
+    }
+
+    /// Encodes this set as a features frame.
+    #[must_use]
+    pub fn to_frame(self) -> [u8; FEATURES_FRAME_LEN] {
+        let mut buf = [0; FEATURES_FRAME_LEN];
+        buf[..4].copy_from_slice(&FEATURES_MAGIC);
+        buf[4..].copy_from_slice(&self.0.to_be_bytes());
+        buf
+    }
+
+    /// Decodes a features frame.
+    ///
+    /// Returns [`None`] if the frame does not start with [`FEATURES_MAGIC`].
+    #[must_use]
+    pub fn from_frame(buf: &[u8; FEATURES_FRAME_LEN]) -> Option<Self> {
+        if buf[..4] != FEATURES_MAGIC {
+            return None;
+        }
+        let mut bits = [0; 4];
+        bits.copy_from_slice(&buf[4..]);
+        Some(Self(u32::from_be_bytes(bits)))
+    }
+}
+
+impl BitOr for Features {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for Features {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl BitAnd for Features {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl BitAndAssign for Features {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = self.intersection(rhs);
+    }
+}
+
+impl fmt::Debug for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        let mut unknown = self.0;
+        for (feature, name) in Self::KNOWN {
+            if self.contains(feature) {
+                set.entry(&format_args!("{name}"));
+                unknown &= !feature.0;
+            }
+        }
+        if unknown != 0 {
+            set.entry(&format_args!("{unknown:#x}"));
+        }
+        set.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let local = Features::COMPRESSION | Features::CLOCK_SYNC;
+        let remote = Features::COMPRESSION | Features::PING | Features::from_bits(1 << 31);
+        assert_eq!(Features::COMPRESSION, local.negotiate(remote));
+        assert_eq!(local.negotiate(remote), remote.negotiate(local));
+        assert!(local.negotiate(Features::NONE).is_empty());
+    }
+
+    #[test]
+    fn encode_decode_frame() {
+        let features = Features::FRAGMENTATION | Features::from_bits(1 << 20);
+        assert_eq!(Some(features), Features::from_frame(&features.to_frame()));
+        assert_eq!(None, Features::from_frame(&[0; FEATURES_FRAME_LEN]));
+    }
+}
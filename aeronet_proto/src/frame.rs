@@ -0,0 +1,306 @@
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Length in bytes of the header of a stream frame.
+pub const FRAME_HEADER_LEN: usize = 4;
+
+/// Default maximum length in bytes of a message which a [`FrameDecoder`]
+/// accepts.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Creates the header of a stream frame for a message of length `len`.
+///
+/// The header is the length of the message as a big-endian `u32`.
+///
+/// Returns [`None`] if the message is too large to be framed.
+#[must_use]
+pub fn frame_header(len: usize) -> Option<[u8; FRAME_HEADER_LEN]> {
+    u32::try_from(len).ok().map(u32::to_be_bytes)
+}
+
+/// Encodes a message as a stream frame.
+///
+/// Returns [`None`] if the message is too large to be framed.
+#[cfg(feature = "alloc")]
+#[must_use]
+pub fn encode_frame(payload: &[u8]) -> Option<Vec<u8>> {
+    let header = frame_header(payload.len())?;
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(payload);
+    Some(frame)
+}
+
+/// Parses the stream frame at the start of `buf`.
+///
+/// Returns the message in the frame, and the bytes after the frame, or
+/// [`None`] if `buf` does not contain a complete frame yet.
+///
+/// This is the boundary at which untrusted bytes read from a stream are
+/// interpreted, and never panics on any input.
+#[must_use]
+pub fn parse_frame(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let header = buf.get(..FRAME_HEADER_LEN)?;
+    let len = u32::from_be_bytes(header.try_into().ok()?);
+    let end = usize::try_from(len).ok()?.checked_add(FRAME_HEADER_LEN)?;
+    let payload = buf.get(FRAME_HEADER_LEN..end)?;
+    Some((payload, &buf[end..]))
+}
+
+/// Error returned by [`FrameDecoder::next_frame`] when the header of a frame
+/// gives a length longer than the decoder's maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLong {
+    /// Length of the message given by the frame header.
+    pub len: usize,
+    /// Maximum length of a message accepted by the decoder.
+    pub max_len: usize,
+}
+
+impl fmt::Display for FrameTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame of {} bytes is longer than the maximum of {} bytes",
+            self.len, self.max_len
+        )
+    }
+}
+
+/// Splits a byte stream into the messages sent in it as stream frames.
+///
+/// Bytes read from a stream are pushed into this decoder using
+/// [`FrameDecoder::push`], and complete messages are taken out using
+/// [`FrameDecoder::next_frame`].
+///
+/// A frame header can claim a length of up to 4 GiB, so the decoder rejects
+/// frames longer than a maximum length instead of buffering them, which is
+/// [`DEFAULT_MAX_FRAME_LEN`] unless set using [`FrameDecoder::with_max_len`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    /// Index in `buf` of the first byte which has not been taken out as part
+    /// of a message yet.
+    ///
+    /// Bytes before this are only removed from `buf` once they make up at
+    /// least half of it, so that taking out each message does not need to
+    /// move all of the bytes after it.
+    start: usize,
+    /// Number of bytes of a discarded frame which have not been read from the
+    /// stream yet.
+    skip: usize,
+    max_len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::with_max_len(DEFAULT_MAX_FRAME_LEN)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FrameDecoder {
+    /// Creates a decoder with no buffered bytes, which accepts messages of up
+    /// to [`DEFAULT_MAX_FRAME_LEN`] bytes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a decoder with no buffered bytes, which accepts messages of up
+    /// to `max_len` bytes.
+    #[must_use]
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            start: 0,
+            skip: 0,
+            max_len,
+        }
+    }
+
+    /// Gets the maximum length in bytes of a message accepted by this
+    /// decoder.
+    #[must_use]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Adds bytes read from the stream to this decoder.
+    pub fn push(&mut self, bytes: &[u8]) {
+        let skipped = self.skip.min(bytes.len());
+        self.skip -= skipped;
+        if self.start > 0 && self.start >= self.buffered() {
+            self.buf.drain(..self.start);
+            self.start = 0;
+        }
+        self.buf.extend_from_slice(&bytes[skipped..]);
+    }
+
+    /// Gets the number of bytes buffered which have not been returned as part
+    /// of a message yet.
+    #[must_use]
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    /// Gets the total length of the frame at the front of the buffer,
+    /// including its header, if its header has been received.
+    #[must_use]
+    pub fn pending_len(&self) -> Option<usize> {
+        let header = self.buf.get(self.start..self.start + FRAME_HEADER_LEN)?;
+        let len = u32::from_be_bytes(header.try_into().ok()?);
+        usize::try_from(len).ok()?.checked_add(FRAME_HEADER_LEN)
+    }
+
+    /// Discards the frame at the front of the buffer, which must not have
+    /// been fully received yet.
+    ///
+    /// The bytes of this frame which are pushed afterwards are skipped, so
+    /// that the frames after it are decoded as normal.
+    pub fn discard_pending(&mut self) {
+        if let Some(len) = self.pending_len() {
+            self.skip = len.saturating_sub(self.buffered());
+        }
+        self.buf.clear();
+        self.start = 0;
+    }
+
+    /// Takes the next complete message out of this decoder, if one has been
+    /// fully received.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the header of the next frame gives a length longer than
+    /// [`FrameDecoder::max_len`]. The frame is kept in the buffer, so the
+    /// same error is returned until it is removed using
+    /// [`FrameDecoder::discard_pending`].
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameTooLong> {
+        if let Some(len) = self.pending_len() {
+            let len = len - FRAME_HEADER_LEN;
+            if len > self.max_len {
+                return Err(FrameTooLong {
+                    len,
+                    max_len: self.max_len,
+                });
+            }
+        }
+
+        let Some((payload, rest)) = parse_frame(&self.buf[self.start..]) else {
+            return Ok(None);
+        };
+        let frame = payload.to_vec();
+        self.start = self.buf.len() - rest.len();
+        if self.start == self.buf.len() {
+            self.buf.clear();
+            self.start = 0;
+        }
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_frame_rest() {
+        assert_eq!(None, parse_frame(&[0, 0]));
+        assert_eq!(None, parse_frame(&[0, 0, 0, 2, 1]));
+        assert_eq!(
+            Some((&[1, 2][..], &[9][..])),
+            parse_frame(&[0, 0, 0, 2, 1, 2, 9])
+        );
+        assert_eq!(None, parse_frame(&[0xff, 0xff, 0xff, 0xff, 1]));
+    }
+
+    #[cfg(feature = "alloc")]
+    mod decoder {
+        use alloc::{vec, vec::Vec};
+
+        use super::*;
+
+        #[test]
+        fn encode_decode_frames() {
+            let mut bytes = encode_frame(b"hello").unwrap();
+            bytes.extend(encode_frame(b"").unwrap());
+            bytes.extend(encode_frame(b"world").unwrap());
+
+            let mut decoder = FrameDecoder::new();
+            let mut frames = Vec::new();
+            // push one byte at a time to test partial reads
+            for byte in bytes {
+                decoder.push(&[byte]);
+                while let Some(frame) = decoder.next_frame().unwrap() {
+                    frames.push(frame);
+                }
+            }
+
+            assert_eq!(vec![b"hello".to_vec(), vec![], b"world".to_vec()], frames);
+            assert_eq!(0, decoder.buffered());
+        }
+
+        #[test]
+        fn discard_pending_frame() {
+            let large = encode_frame(&[7; 16]).unwrap();
+            let mut decoder = FrameDecoder::new();
+            decoder.push(&large[..6]);
+            assert_eq!(Ok(None), decoder.next_frame());
+            assert_eq!(Some(20), decoder.pending_len());
+
+            decoder.discard_pending();
+            assert_eq!(0, decoder.buffered());
+            let mut rest = large[6..].to_vec();
+            rest.extend(encode_frame(b"after").unwrap());
+            decoder.push(&rest);
+            assert_eq!(Ok(Some(b"after".to_vec())), decoder.next_frame());
+            assert_eq!(0, decoder.buffered());
+        }
+
+        #[test]
+        fn incomplete_frame() {
+            let mut decoder = FrameDecoder::new();
+            decoder.push(&[0, 0, 0, 3, 1, 2]);
+            assert_eq!(Ok(None), decoder.next_frame());
+            decoder.push(&[3]);
+            assert_eq!(Ok(Some(vec![1, 2, 3])), decoder.next_frame());
+        }
+
+        #[test]
+        fn reject_long_frame() {
+            let mut decoder = FrameDecoder::with_max_len(4);
+            decoder.push(&encode_frame(b"1234").unwrap());
+            decoder.push(&[0, 0, 0, 5, 1]);
+            assert_eq!(Ok(Some(b"1234".to_vec())), decoder.next_frame());
+            assert_eq!(
+                Err(FrameTooLong { len: 5, max_len: 4 }),
+                decoder.next_frame()
+            );
+
+            decoder.discard_pending();
+            decoder.push(&[2, 3, 4, 5]);
+            decoder.push(&encode_frame(b"ok").unwrap());
+            assert_eq!(Ok(Some(b"ok".to_vec())), decoder.next_frame());
+        }
+
+        #[test]
+        fn many_frames_in_one_push() {
+            let mut bytes = Vec::new();
+            for i in 0..100u8 {
+                bytes.extend(encode_frame(&[i; 3]).unwrap());
+            }
+
+            let mut decoder = FrameDecoder::new();
+            decoder.push(&bytes);
+            for i in 0..100u8 {
+                assert_eq!(Ok(Some(vec![i; 3])), decoder.next_frame());
+                assert_eq!(usize::from(99 - i) * 7, decoder.buffered());
+            }
+            assert_eq!(Ok(None), decoder.next_frame());
+        }
+    }
+}
@@ -0,0 +1,132 @@
+/// Represents what kind of method is used to transport data along a connection.
+///
+/// A connection may support different methods for transporting messages, where
+/// each different method is called a channel. A channel provides guarantees on:
+/// * **reliablity** - ensuring that the message reaches the other side without
+///   being lost
+/// * **ordering** - ensuring that messages are received in the same order that
+///   they are sent
+///
+/// Although it is not a part of the guarantees laid out by the channel kinds,
+/// **head-of-line blocking** is also an important factor to consider when
+/// choosing which kind of channel to use. A channel kind with head-of-line
+/// blocking may block when it is awaiting a message sent earlier, in order to
+/// maintain ordering; others may not.
+///
+/// The transport implementation is guaranteed to provide these channel kinds,
+/// either by using a feature of the underlying transport mechanism (i.e. QUIC
+/// streams on WebTransport) or via a custom layer implemented on top of the
+/// transport mechanism.
+///
+/// Note that channel kinds provide a *minimum* guarantee of reliability and
+/// ordering - a transport may provide some guarantees even if using a less
+/// reliable channel kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// No guarantees given on **reliability** or **ordering**.
+    ///
+    /// This is useful for messages which should be sent in a fire-and-forget
+    /// manner: that is, you don't expect to get a response for this message,
+    /// and it is OK if a few messages are lost in transit.
+    ///
+    /// This channel kind typically has the best performance, as it does not
+    /// require any sort of handshaking to ensure that messages have arrived
+    /// from one side to the other.
+    ///
+    /// An example of a message using this channel kind is a player positional
+    /// update, sent to the server whenever a client moves in a game world.
+    /// Since the game client will constantly be sending positional update
+    /// messages at a high rate, it is OK if a few are lost in transit, as the
+    /// server will hopefully catch the next messages.
+    Unreliable,
+    /// Messages are sent **reliably** but the **ordering** is not guaranteed.
+    ///
+    /// This is useful for important one-off events where you need a guarantee
+    /// that the message will be delivered, but the order in which it is
+    /// delivered is not important.
+    ///
+    /// This channel kind is typically slower to send and receive than an
+    /// unreliable message, but is still faster than an ordered channel because
+    /// the implementation may be able to avoid head-of-line blocking.
+    ///
+    /// An example of a message using this channel kind is sending level data
+    /// from a server to a client. It is not important what order the different
+    /// parts of the level are received in, but it is important that they are
+    /// all received.
+    ReliableUnordered,
+    /// Messages are sent **reliablity** and **ordered**.
+    ///
+    /// This is useful for important one-off events where you need a guarantee
+    /// that the message will be delivered, and the order in which it's
+    /// delivered is important.
+    ///
+    /// This channel kind offers the most guarantees, but is typically slower to
+    /// send and receive than other channel kinds. Most notably, implementations
+    /// may suffer from head-of-line blocking.
+    ///
+    /// Implementations may suffer from head-of-line blocking if a reliable
+    /// channel is used, where messages cannot be received because they are
+    /// being held up by a message sent earlier. To avoid this, you may use
+    /// multiple different instances of this kind of channel, all of which hold
+    /// their own message queues.
+    ///
+    /// An example of a message using this channel kind is sending chat messages
+    /// from the server to the client. Since the server aggregates chat messages
+    /// from different sources (system, other players, etc.) in a specific
+    /// order, it must then tell its clients about the chat messages in that
+    /// specific order as well.
+    ReliableOrdered,
+}
+
+impl ChannelKind {
+    /// Gets whether lanes of this kind are sent on their own stream, rather
+    /// than as datagrams.
+    #[must_use]
+    pub const fn uses_stream(self) -> bool {
+        match self {
+            Self::Unreliable => false,
+            Self::ReliableUnordered | Self::ReliableOrdered => true,
+        }
+    }
+}
+
+/// Gets the position of a lane's stream in the order that streams are opened.
+///
+/// `kinds` is the kind of every lane of the protocol, in the order of
+/// `ChannelKey::ALL`, and `lane` is the index of the lane in it. Every lane
+/// which [uses a stream](ChannelKind::uses_stream) gets a stream, in the order
+/// that the lanes are listed.
+///
+/// Returns [`None`] if the lane is sent as datagrams, or if `lane` is out of
+/// bounds.
+#[must_use]
+pub fn stream_index(kinds: &[ChannelKind], lane: usize) -> Option<usize> {
+    let kind = kinds.get(lane)?;
+    if !kind.uses_stream() {
+        return None;
+    }
+    Some(
+        kinds[..lane]
+            .iter()
+            .filter(|kind| kind.uses_stream())
+            .count(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_indices() {
+        let kinds = [
+            ChannelKind::ReliableOrdered,
+            ChannelKind::Unreliable,
+            ChannelKind::ReliableUnordered,
+        ];
+        assert_eq!(Some(0), stream_index(&kinds, 0));
+        assert_eq!(None, stream_index(&kinds, 1));
+        assert_eq!(Some(1), stream_index(&kinds, 2));
+        assert_eq!(None, stream_index(&kinds, 3));
+    }
+}
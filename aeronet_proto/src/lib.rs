@@ -0,0 +1,26 @@
+#![doc = include_str!("../README.md")]
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod features;
+mod frame;
+mod lane;
+mod migration;
+mod ping;
+mod quality;
+
+pub use {features::*, frame::*, lane::*, migration::*, ping::*, quality::*};
+
+/// Version of the wire format implemented by this crate.
+///
+/// This is increased every time the format changes in a way which peers
+/// implementing an older version do not understand:
+/// * `1` - messages on streams are written back-to-back, with no framing
+/// * `2` - messages on streams are framed with a length prefix
+/// * `3` - the server may open a quality report stream
+/// * `4` - the client may negotiate optional features on a features stream
+/// * `5` - pings are sent on the features streams instead of as datagrams
+/// * `6` - peers may migrate channels between lanes on a lane migration stream
+pub const WIRE_VERSION: u32 = 6;
@@ -0,0 +1,114 @@
+/// Length in bytes of an encoded [`LaneMigration`].
+pub const LANE_MIGRATION_LEN: usize = 13;
+
+/// Bytes which every [`LaneMigration`] frame starts with.
+///
+/// This lets a peer tell the lane migration stream apart from other
+/// unidirectional streams, such as the quality report stream, by its first
+/// bytes.
+pub const LANE_MIGRATION_MAGIC: [u8; 4] = *b"AELM";
+
+/// Frame used to move the messages of a channel onto the lane of another
+/// channel while the connection is open.
+///
+/// A peer proposes a migration with [`LaneMigration::Propose`], and keeps
+/// sending the channel's messages on its current lane until the peer answers.
+/// The peer either switches its own messages of that channel to the new lane
+/// and answers with [`LaneMigration::Accept`], or leaves them where they are
+/// and answers with [`LaneMigration::Reject`]. The proposing peer only
+/// switches once it receives the acceptance, so both peers agree on the lane
+/// of the channel once the exchange is complete.
+///
+/// Channels and lanes are both identified by their index in the protocol's
+/// `ChannelKey::ALL`. Migrating a channel back onto its own lane undoes a
+/// migration.
+///
+/// Migrations are only proposed if both peers negotiated
+/// [`Features::LANE_MIGRATION`](crate::Features::LANE_MIGRATION).
+///
+/// Encoded as [`LANE_MIGRATION_MAGIC`], followed by `0` for a proposal, `1`
+/// for an acceptance or `2` for a rejection, followed by the sequence number
+/// as a big-endian `u32`, followed by the channel and lane indices as
+/// big-endian `u16`s. The indices are zero in acceptances and rejections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaneMigration {
+    /// Asks the peer to move the messages of `channel` onto `lane`.
+    Propose {
+        /// Sequence number which the answer carries.
+        seq: u32,
+        /// Index of the channel whose messages are moved.
+        channel: u16,
+        /// Index of the lane which the messages are moved onto.
+        lane: u16,
+    },
+    /// Accepts the proposal with the given sequence number.
+    Accept(u32),
+    /// Rejects the proposal with the given sequence number.
+    Reject(u32),
+}
+
+impl LaneMigration {
+    /// Encodes this frame.
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; LANE_MIGRATION_LEN] {
+        let (kind, seq, channel, lane) = match self {
+            Self::Propose { seq, channel, lane } => (0, seq, channel, lane),
+            Self::Accept(seq) => (1, seq, 0, 0),
+            Self::Reject(seq) => (2, seq, 0, 0),
+        };
+        let mut buf = [0; LANE_MIGRATION_LEN];
+        buf[..4].copy_from_slice(&LANE_MIGRATION_MAGIC);
+        buf[4] = kind;
+        buf[5..9].copy_from_slice(&seq.to_be_bytes());
+        buf[9..11].copy_from_slice(&channel.to_be_bytes());
+        buf[11..].copy_from_slice(&lane.to_be_bytes());
+        buf
+    }
+
+    /// Decodes a frame.
+    ///
+    /// Returns [`None`] if the frame does not start with
+    /// [`LANE_MIGRATION_MAGIC`], or is not a known kind of frame.
+    #[must_use]
+    pub fn from_bytes(buf: &[u8; LANE_MIGRATION_LEN]) -> Option<Self> {
+        if buf[..4] != LANE_MIGRATION_MAGIC {
+            return None;
+        }
+        let seq = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]);
+        match buf[4] {
+            0 => Some(Self::Propose {
+                seq,
+                channel: u16::from_be_bytes([buf[9], buf[10]]),
+                lane: u16::from_be_bytes([buf[11], buf[12]]),
+            }),
+            1 => Some(Self::Accept(seq)),
+            2 => Some(Self::Reject(seq)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_migration() {
+        for frame in [
+            LaneMigration::Propose {
+                seq: 3,
+                channel: 1,
+                lane: u16::MAX,
+            },
+            LaneMigration::Accept(3),
+            LaneMigration::Reject(u32::MAX),
+        ] {
+            assert_eq!(Some(frame), LaneMigration::from_bytes(&frame.to_bytes()));
+        }
+
+        let mut unknown = LaneMigration::Accept(3).to_bytes();
+        unknown[4] = 3;
+        assert_eq!(None, LaneMigration::from_bytes(&unknown));
+        assert_eq!(None, LaneMigration::from_bytes(&[0; LANE_MIGRATION_LEN]));
+    }
+}
@@ -0,0 +1,83 @@
+/// Length in bytes of an encoded [`Ping`].
+pub const PING_LEN: usize = 9;
+
+/// Bytes which every [`Ping`] starts with.
+///
+/// This lets a peer detect a features stream which does not carry pings.
+pub const PING_MAGIC: [u8; 4] = *b"AEPG";
+
+/// Frame used to measure the round-trip time of a connection, independently
+/// of the messages sent on it.
+///
+/// Each peer writes its pings on its own features stream, after the features
+/// frame. Pings are not queued behind messages or passed through
+/// serialization, so the measured round-trip time reflects the latency of the
+/// network even when the message lanes are congested. A peer answers every
+/// [`Ping::Request`] it receives with a [`Ping::Reply`] carrying the same
+/// sequence number, and measures the time between sending a request and
+/// receiving its reply.
+///
+/// Pings are only sent if both peers negotiated
+/// [`Features::PING`](crate::Features::PING).
+///
+/// Encoded as [`PING_MAGIC`], followed by `0` for a request or `1` for a reply,
+/// followed by the sequence number as a big-endian `u32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ping {
+    /// Asks the peer to reply with the same sequence number.
+    Request(u32),
+    /// Answers a request with the given sequence number.
+    Reply(u32),
+}
+
+impl Ping {
+    /// Encodes this ping as a frame.
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; PING_LEN] {
+        let (kind, seq) = match self {
+            Self::Request(seq) => (0, seq),
+            Self::Reply(seq) => (1, seq),
+        };
+        let mut buf = [0; PING_LEN];
+        buf[..4].copy_from_slice(&PING_MAGIC);
+        buf[4] = kind;
+        buf[5..].copy_from_slice(&seq.to_be_bytes());
+        buf
+    }
+
+    /// Decodes a frame as a ping.
+    ///
+    /// Returns [`None`] if the frame is not exactly [`PING_LEN`] bytes long,
+    /// does not start with [`PING_MAGIC`], or is not a known kind of ping.
+    #[must_use]
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PING_LEN || buf[..4] != PING_MAGIC {
+            return None;
+        }
+        let mut seq = [0; 4];
+        seq.copy_from_slice(&buf[5..]);
+        let seq = u32::from_be_bytes(seq);
+        match buf[4] {
+            0 => Some(Self::Request(seq)),
+            1 => Some(Self::Reply(seq)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_ping() {
+        for ping in [Ping::Request(7), Ping::Reply(u32::MAX)] {
+            assert_eq!(Some(ping), Ping::from_bytes(&ping.to_bytes()));
+        }
+
+        let mut unknown = Ping::Request(7).to_bytes();
+        unknown[4] = 2;
+        assert_eq!(None, Ping::from_bytes(&unknown));
+        assert_eq!(None, Ping::from_bytes(&Ping::Request(7).to_bytes()[1..]));
+    }
+}
@@ -0,0 +1,60 @@
+use core::time::Duration;
+
+/// Length in bytes of an encoded [`QualitySample`].
+pub const QUALITY_SAMPLE_LEN: usize = 12;
+
+/// Measurements of the client-to-server direction of a connection, sent by the
+/// server on its quality report stream.
+///
+/// Encoded as the number of datagrams received as a big-endian `u64`, followed
+/// by the jitter in microseconds as a big-endian `u32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QualitySample {
+    /// Total number of datagrams that the server has received from the client
+    /// since the connection was established.
+    pub recv_datagrams: u64,
+    /// Variation in the round-trip time of the connection, as measured by the
+    /// server.
+    pub jitter: Duration,
+}
+
+impl QualitySample {
+    /// Encodes this sample.
+    ///
+    /// Jitter is saturated to [`u32::MAX`] microseconds.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; QUALITY_SAMPLE_LEN] {
+        let jitter = u32::try_from(self.jitter.as_micros()).unwrap_or(u32::MAX);
+        let mut buf = [0; QUALITY_SAMPLE_LEN];
+        buf[..8].copy_from_slice(&self.recv_datagrams.to_be_bytes());
+        buf[8..].copy_from_slice(&jitter.to_be_bytes());
+        buf
+    }
+
+    /// Decodes a sample.
+    #[must_use]
+    pub fn from_bytes(buf: &[u8; QUALITY_SAMPLE_LEN]) -> Self {
+        let mut recv_datagrams = [0; 8];
+        recv_datagrams.copy_from_slice(&buf[..8]);
+        let mut jitter = [0; 4];
+        jitter.copy_from_slice(&buf[8..]);
+        Self {
+            recv_datagrams: u64::from_be_bytes(recv_datagrams),
+            jitter: Duration::from_micros(u64::from(u32::from_be_bytes(jitter))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_quality_sample() {
+        let sample = QualitySample {
+            recv_datagrams: 1234,
+            jitter: Duration::from_micros(5678),
+        };
+        assert_eq!(sample, QualitySample::from_bytes(&sample.to_bytes()));
+    }
+}
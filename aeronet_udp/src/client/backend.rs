@@ -0,0 +1,280 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use tracing::debug;
+
+use crate::{
+    packet::Packet,
+    transport::{self, flush, INFO_INTERVAL, PING_INTERVAL, SERVICE_TIMEOUT},
+    BackendError, Outgoing, Reliability, UdpClientConfig, UdpInfo, MAX_DATAGRAM_LEN,
+};
+
+/// Interval at which connect requests are resent until the server answers.
+const CONNECT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected { info: UdpInfo },
+    Info { info: UdpInfo },
+    Recv { bytes: Vec<u8> },
+    Error { cause: BackendError },
+}
+
+pub(super) fn start<R: Reliability>(
+    config: &UdpClientConfig,
+    recv_c2s: &Receiver<Outgoing>,
+    send_update: &Sender<Update>,
+) {
+    let socket = match bind(config.addr) {
+        Ok(socket) => socket,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Bind(err),
+            });
+            return;
+        }
+    };
+
+    let mut buf = vec![0; MAX_DATAGRAM_LEN];
+    match handshake(config, &socket, recv_c2s, &mut buf) {
+        Ok(true) => {}
+        Ok(false) => {
+            debug!("Frontend closed");
+            return;
+        }
+        Err(cause) => {
+            let _ = send_update.send(Update::Error { cause });
+            return;
+        }
+    }
+
+    debug!("Starting client loop");
+    let mut conn = Connection::<R>::new(config, socket);
+    let _ = send_update.send(Update::Connected {
+        info: conn.info.clone(),
+    });
+    let mut last_info = Instant::now();
+    loop {
+        loop {
+            match recv_c2s.try_recv() {
+                Ok(msg) => {
+                    if let Err(cause) = conn.send(&msg) {
+                        let _ = conn
+                            .socket
+                            .send_to(&Packet::Disconnect.encode(), config.addr);
+                        let _ = send_update.send(Update::Error { cause });
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("Frontend closed");
+                    let _ = conn
+                        .socket
+                        .send_to(&Packet::Disconnect.encode(), config.addr);
+                    return;
+                }
+            }
+        }
+
+        let result = loop {
+            match recv_from_server(&conn.socket, config.addr, &mut buf) {
+                Ok(Some(datagram)) => {
+                    if let Err(cause) = conn.recv(datagram, send_update) {
+                        break Err(cause);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(BackendError::Socket(err)),
+            }
+        };
+        if let Err(cause) = result.and_then(|()| conn.update(config)) {
+            let _ = send_update.send(Update::Error { cause });
+            return;
+        }
+
+        if last_info.elapsed() >= INFO_INTERVAL {
+            last_info = Instant::now();
+            let _ = send_update.send(Update::Info {
+                info: conn.info.clone(),
+            });
+        }
+    }
+}
+
+/// State of the connection to the server once the handshake is complete.
+struct Connection<R> {
+    socket: UdpSocket,
+    start: Instant,
+    reliability: R,
+    info: UdpInfo,
+    out: Vec<Vec<u8>>,
+    recv: Vec<Vec<u8>>,
+    last_recv: Instant,
+    last_ping: Instant,
+}
+
+impl<R: Reliability> Connection<R> {
+    fn new(config: &UdpClientConfig, socket: UdpSocket) -> Self {
+        let now = Instant::now();
+        Self {
+            socket,
+            start: now,
+            reliability: R::default(),
+            info: UdpInfo {
+                rtt: Duration::ZERO,
+                remote_addr: config.addr,
+            },
+            out: Vec::new(),
+            recv: Vec::new(),
+            last_recv: now,
+            last_ping: now,
+        }
+    }
+
+    fn send(&mut self, msg: &Outgoing) -> Result<(), BackendError> {
+        let result = self.reliability.send(
+            msg.channel_id,
+            msg.kind,
+            &msg.bytes,
+            Instant::now(),
+            &mut self.out,
+        );
+        flush(&self.socket, self.info.remote_addr, &mut self.out);
+        result.map_err(BackendError::Reliability)
+    }
+
+    fn recv(&mut self, datagram: &[u8], send_update: &Sender<Update>) -> Result<(), BackendError> {
+        let Some(packet) = Packet::decode(datagram) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        self.last_recv = now;
+
+        match packet {
+            Packet::Data(payload) => {
+                self.reliability
+                    .recv(payload, now, &mut self.recv, &mut self.out);
+                for bytes in self.recv.drain(..) {
+                    let _ = send_update.send(Update::Recv { bytes });
+                }
+                flush(&self.socket, self.info.remote_addr, &mut self.out);
+            }
+            Packet::Ping { time } => {
+                let pong = Packet::Pong { time }.encode();
+                let _ = self.socket.send_to(&pong, self.info.remote_addr);
+            }
+            Packet::Pong { time } => {
+                self.info.rtt = transport::ping_rtt(self.start, time);
+            }
+            Packet::Disconnect => return Err(BackendError::Disconnected),
+            // the server resends these if it receives a connect request which
+            // was resent before our first one was answered
+            Packet::Connect { .. } | Packet::Accept { .. } | Packet::Reject { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, config: &UdpClientConfig) -> Result<(), BackendError> {
+        let now = Instant::now();
+        if now.duration_since(self.last_recv) >= config.timeout {
+            return Err(BackendError::TimedOut);
+        }
+
+        self.reliability
+            .update(now, &mut self.out)
+            .map_err(BackendError::Reliability)?;
+        flush(&self.socket, config.addr, &mut self.out);
+
+        if now.duration_since(self.last_ping) >= PING_INTERVAL {
+            self.last_ping = now;
+            let time = transport::ping_time(self.start);
+            let _ = self
+                .socket
+                .send_to(&Packet::Ping { time }.encode(), config.addr);
+        }
+        Ok(())
+    }
+}
+
+fn bind(server_addr: SocketAddr) -> io::Result<UdpSocket> {
+    let local_addr = match server_addr {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local_addr)?;
+    socket.set_read_timeout(Some(SERVICE_TIMEOUT))?;
+    Ok(socket)
+}
+
+/// Sends connect requests until the server accepts or rejects one.
+///
+/// Returns `false` if the frontend was closed while connecting.
+fn handshake(
+    config: &UdpClientConfig,
+    socket: &UdpSocket,
+    recv_c2s: &Receiver<Outgoing>,
+    buf: &mut [u8],
+) -> Result<bool, BackendError> {
+    // only used to tell the answers to this connection apart from the answers
+    // to a previous connection from the same address
+    let nonce = RandomState::new().build_hasher().finish();
+    let connect = Packet::Connect {
+        protocol_id: config.protocol_id,
+        nonce,
+    }
+    .encode();
+
+    let start = Instant::now();
+    let mut last_connect = None::<Instant>;
+    loop {
+        if matches!(recv_c2s.try_recv(), Err(TryRecvError::Disconnected)) {
+            return Ok(false);
+        }
+        if start.elapsed() >= config.timeout {
+            return Err(BackendError::TimedOut);
+        }
+        if last_connect.map_or(true, |at| at.elapsed() >= CONNECT_INTERVAL) {
+            last_connect = Some(Instant::now());
+            let _ = socket.send_to(&connect, config.addr);
+        }
+
+        let Some(datagram) =
+            recv_from_server(socket, config.addr, buf).map_err(BackendError::Socket)?
+        else {
+            continue;
+        };
+        match Packet::decode(datagram) {
+            Some(Packet::Accept { nonce: accepted }) if accepted == nonce => return Ok(true),
+            Some(Packet::Reject { nonce: rejected }) if rejected == nonce => {
+                return Err(BackendError::Rejected)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Receives a single datagram sent by the server, blocking for at most
+/// [`SERVICE_TIMEOUT`].
+///
+/// Returns [`None`] if no datagram was received, or if it was sent by another
+/// address.
+fn recv_from_server<'a>(
+    socket: &UdpSocket,
+    server_addr: SocketAddr,
+    buf: &'a mut [u8],
+) -> io::Result<Option<&'a [u8]>> {
+    match socket.recv_from(buf) {
+        Ok((len, from)) if from == server_addr => Ok(Some(&buf[..len])),
+        Ok(_) => Ok(None),
+        Err(err) if transport::is_transient(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,291 @@
+use std::{marker::PhantomData, mem, thread};
+
+use aeronet::{OnChannel, OnMessageError, TransportClient, TryFromBytes, TryIntoBytes};
+use crossbeam_channel::TryRecvError;
+use tracing::debug;
+
+use crate::{transport, Reliability, UdpClient, UdpClientConfig, UdpInfo, UdpProtocol};
+
+use super::{
+    backend::{self, Update},
+    Backend, ClientEvent, ClientState, State, UdpError,
+};
+
+impl<P, R> UdpClient<P, R>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+    R: Reliability,
+{
+    /// Creates a new client which is not connecting to any server.
+    ///
+    /// If you want to create a client and connect to a server immediately after
+    /// creation, use [`UdpClient::connecting`] instead.
+    #[must_use]
+    pub fn disconnected() -> Self {
+        Self {
+            state: State::Disconnected,
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates and starts connecting a client to a server.
+    ///
+    /// This spawns a new thread for the client's backend, which runs until the
+    /// client is disconnected or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the protocol has more than [`MAX_CHANNELS`] channels.
+    ///
+    /// [`MAX_CHANNELS`]: crate::MAX_CHANNELS
+    pub fn connecting(config: UdpClientConfig) -> Result<Self, UdpError<P>> {
+        Ok(Self {
+            state: State::Connecting(Backend::start::<P, R>(config)?),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Attempts to start connecting this client to a server.
+    ///
+    /// See [`UdpClient::connecting`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this client is already connecting or is connected to a
+    /// server, or if the protocol has more than [`MAX_CHANNELS`] channels.
+    ///
+    /// [`MAX_CHANNELS`]: crate::MAX_CHANNELS
+    pub fn connect(&mut self, config: UdpClientConfig) -> Result<(), UdpError<P>> {
+        match self.state {
+            State::Disconnected => {
+                self.state = State::Connecting(Backend::start::<P, R>(config)?);
+                Ok(())
+            }
+            State::Connecting(_) | State::Connected(..) => Err(UdpError::<P>::BackendOpen),
+        }
+    }
+
+    /// Gets the current state of the client.
+    #[must_use]
+    pub fn state(&self) -> ClientState {
+        match self.state {
+            State::Disconnected => ClientState::Disconnected,
+            State::Connecting(_) => ClientState::Connecting,
+            State::Connected(..) => ClientState::Connected,
+        }
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// the server.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// the server.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from the server fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from the server fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ClientEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+}
+
+impl<P, R> TransportClient<P> for UdpClient<P, R>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+    R: Reliability,
+{
+    const TRANSPORT_NAME: &'static str = "udp";
+
+    type Error = UdpError<P>;
+
+    type ConnectionInfo = UdpInfo;
+
+    type Event = ClientEvent<P>;
+
+    fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => None,
+            State::Connected(_, info) => Some(info.clone()),
+        }
+    }
+
+    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
+        let backend = match &self.state {
+            State::Disconnected | State::Connecting(_) => return Err(UdpError::<P>::BackendClosed),
+            State::Connected(backend, _) => backend,
+        };
+        let msg = match transport::serialize::<P, R, _, _>(&msg.into()) {
+            Ok(msg) => msg,
+            Err(err) => {
+                return match self.on_serialize_error {
+                    OnMessageError::DisconnectClient => {
+                        self.state = State::Disconnected;
+                        Err(err)
+                    }
+                    OnMessageError::DropMessage => {
+                        debug!("Dropped message to server: {err:#}");
+                        Ok(())
+                    }
+                    OnMessageError::EmitEventOnly => Err(err),
+                };
+            }
+        };
+        backend
+            .send_c2s
+            .send(msg)
+            .map_err(|_| UdpError::<P>::BackendClosed)
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let on_deserialize_error = self.on_deserialize_error;
+        let mut events = Vec::new();
+        self.state = match mem::take(&mut self.state) {
+            State::Disconnected => State::Disconnected,
+            State::Connecting(backend) => match backend.recv_update.try_recv() {
+                Ok(Update::Connected { info }) => {
+                    events.push(ClientEvent::Connected);
+                    // messages may have been received in the same poll
+                    recv_connected(backend, info, on_deserialize_error, &mut events)
+                }
+                Ok(update) => {
+                    events.push(ClientEvent::Disconnected {
+                        cause: disconnect_cause::<P>(update),
+                    });
+                    State::Disconnected
+                }
+                Err(TryRecvError::Empty) => State::Connecting(backend),
+                Err(TryRecvError::Disconnected) => {
+                    events.push(ClientEvent::Disconnected {
+                        cause: UdpError::<P>::BackendClosed,
+                    });
+                    State::Disconnected
+                }
+            },
+            State::Connected(backend, info) => {
+                recv_connected(backend, info, on_deserialize_error, &mut events)
+            }
+        };
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        match self.state {
+            State::Disconnected => Err(UdpError::<P>::BackendClosed),
+            State::Connecting(_) | State::Connected(..) => {
+                self.state = State::Disconnected;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn recv_connected<P>(
+    backend: Backend,
+    mut info: UdpInfo,
+    on_deserialize_error: OnMessageError,
+    events: &mut Vec<ClientEvent<P>>,
+) -> State
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    loop {
+        let cause = match backend.recv_update.try_recv() {
+            Ok(Update::Info { info: new_info }) => {
+                info = new_info;
+                continue;
+            }
+            Ok(Update::Recv { bytes }) => match P::S2C::try_from_bytes(&bytes) {
+                Ok(msg) => {
+                    events.push(ClientEvent::Recv { msg });
+                    continue;
+                }
+                Err(err) => {
+                    let cause = UdpError::<P>::Deserialize(err);
+                    match on_deserialize_error {
+                        OnMessageError::DisconnectClient => cause,
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message from server: {cause:#}");
+                            continue;
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ClientEvent::MessageError { cause });
+                            continue;
+                        }
+                    }
+                }
+            },
+            Ok(update) => disconnect_cause::<P>(update),
+            Err(TryRecvError::Empty) => return State::Connected(backend, info),
+            Err(TryRecvError::Disconnected) => UdpError::<P>::BackendClosed,
+        };
+        events.push(ClientEvent::Disconnected { cause });
+        return State::Disconnected;
+    }
+}
+
+fn disconnect_cause<P>(update: Update) -> UdpError<P>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    match update {
+        Update::Error { cause } => cause.into(),
+        // the backend only sends these while connected, which is handled by
+        // the caller
+        Update::Connected { .. } | Update::Info { .. } | Update::Recv { .. } => {
+            UdpError::<P>::BackendClosed
+        }
+    }
+}
+
+impl Backend {
+    fn start<P, R>(config: UdpClientConfig) -> Result<Self, UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+        P::S2C: TryFromBytes,
+        R: Reliability,
+    {
+        transport::channel_count::<P, _, _>()?;
+        let (send_c2s, recv_c2s) = crossbeam_channel::unbounded();
+        let (send_update, recv_update) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            backend::start::<R>(&config, &recv_c2s, &send_update);
+            debug!("Client backend stopped");
+        });
+        Ok(Self {
+            send_c2s,
+            recv_update,
+        })
+    }
+}
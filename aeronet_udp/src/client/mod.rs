@@ -0,0 +1,162 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, marker::PhantomData, net::SocketAddr, time::Duration};
+
+use aeronet::{
+    OnChannel, OnMessageError, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes,
+};
+use crossbeam_channel::{Receiver, Sender};
+use derivative::Derivative;
+
+use crate::{Outgoing, Reliability, UdpInfo, UdpProtocol, Unreliable};
+
+use self::backend::Update;
+
+type UdpError<P> = crate::UdpError<<P as TransportProtocol>::C2S, <P as TransportProtocol>::S2C>;
+
+/// Configuration for connecting a [`UdpClient`] to a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpClientConfig {
+    /// Address of the server to connect to.
+    pub addr: SocketAddr,
+    /// Identifier of the app's protocol, which must match the
+    /// [`UdpServerConfig::protocol_id`] of the server.
+    ///
+    /// [`UdpServerConfig::protocol_id`]: crate::UdpServerConfig::protocol_id
+    pub protocol_id: u64,
+    /// Time after which the client gives up if no datagrams have been received
+    /// from the server, both while connecting and once connected.
+    pub timeout: Duration,
+}
+
+impl UdpClientConfig {
+    /// Default value of [`UdpClientConfig::timeout`].
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates a config which connects to the given address with a protocol ID
+    /// of `0`.
+    #[must_use]
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            protocol_id: 0,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// Implementation of [`TransportClient`] using raw UDP datagrams.
+///
+/// Messages are delivered according to the [`Reliability`] `R`, which must be
+/// the same as the one that the server uses.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct UdpClient<P, R = Unreliable>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+    R: Reliability,
+{
+    state: State,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> (P, R)>,
+}
+
+/// Event raised by an [`UdpClient`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ClientEvent<P>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    /// This client has fully connected to a server.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Connected`].
+    Connected,
+    /// The connected server sent a message to the client.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Recv`].
+    Recv {
+        /// The message received.
+        msg: P::S2C,
+    },
+    /// A message received from the server failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`UdpClient::set_on_deserialize_error`].
+    MessageError {
+        /// The error which occurred.
+        cause: UdpError<P>,
+    },
+    /// The client lost connection from its previous server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Disconnected`].
+    Disconnected {
+        /// The reason why the client lost connection.
+        cause: UdpError<P>,
+    },
+}
+
+impl<P, T> From<ClientEvent<P>> for Option<aeronet::ClientEvent<P, T>>
+where
+    P: UdpProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+    T: TransportClient<P, Error = UdpError<P>>,
+{
+    fn from(value: ClientEvent<P>) -> Self {
+        match value {
+            ClientEvent::Connected => Some(aeronet::ClientEvent::Connected),
+            ClientEvent::Recv { msg } => Some(aeronet::ClientEvent::Recv { msg }),
+            ClientEvent::Disconnected { cause } => {
+                Some(aeronet::ClientEvent::Disconnected { cause })
+            }
+            ClientEvent::MessageError { .. } => None,
+        }
+    }
+}
+
+/// The current state of an [`UdpClient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    /// Not connected and is not attempting to connect to a server.
+    Disconnected,
+    /// Currently attempting to connect to a server.
+    Connecting,
+    /// Fully connected to a server and ready to transmit messages.
+    Connected,
+}
+
+// client states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Disconnected,
+    Connecting(Backend),
+    Connected(Backend, UdpInfo),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Backend {
+    #[derivative(Debug = "ignore")]
+    send_c2s: Sender<Outgoing>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
@@ -0,0 +1,9 @@
+#![doc = include_str!("../README.md")]
+
+mod client;
+mod packet;
+mod reliability;
+mod server;
+mod transport;
+
+pub use {client::*, reliability::*, server::*, transport::*};
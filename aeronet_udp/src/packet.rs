@@ -0,0 +1,142 @@
+/// Length in bytes of the tag which every datagram starts with.
+pub(crate) const TAG_LEN: usize = 1;
+
+const CONNECT: u8 = 0;
+const ACCEPT: u8 = 1;
+const REJECT: u8 = 2;
+const DATA: u8 = 3;
+const PING: u8 = 4;
+const PONG: u8 = 5;
+const DISCONNECT: u8 = 6;
+
+/// A single datagram sent between a client and a server.
+///
+/// Encoded as a tag byte identifying the kind of packet, followed by its
+/// fields as big-endian integers. The payload of a [`Packet::Data`] takes up
+/// the rest of the datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Packet<'a> {
+    /// Sent by a client to start connecting, and resent until it is answered.
+    Connect { protocol_id: u64, nonce: u64 },
+    /// Sent by the server when it accepts the connect request with `nonce`.
+    Accept { nonce: u64 },
+    /// Sent by the server when it is full.
+    Reject { nonce: u64 },
+    /// Payload framed by the connection's reliability.
+    Data(&'a [u8]),
+    /// Keep-alive which the other side answers with a [`Packet::Pong`]
+    /// carrying the same time.
+    Ping { time: u64 },
+    /// Answer to a [`Packet::Ping`].
+    Pong { time: u64 },
+    /// Sent by either side when it closes the connection.
+    Disconnect,
+}
+
+impl<'a> Packet<'a> {
+    /// Encodes this packet into a datagram.
+    pub fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(TAG_LEN + 16);
+        match self {
+            Self::Connect { protocol_id, nonce } => {
+                buf.push(CONNECT);
+                buf.extend_from_slice(&protocol_id.to_be_bytes());
+                buf.extend_from_slice(&nonce.to_be_bytes());
+            }
+            Self::Accept { nonce } => {
+                buf.push(ACCEPT);
+                buf.extend_from_slice(&nonce.to_be_bytes());
+            }
+            Self::Reject { nonce } => {
+                buf.push(REJECT);
+                buf.extend_from_slice(&nonce.to_be_bytes());
+            }
+            Self::Data(payload) => {
+                buf.reserve(payload.len());
+                buf.push(DATA);
+                buf.extend_from_slice(payload);
+            }
+            Self::Ping { time } => {
+                buf.push(PING);
+                buf.extend_from_slice(&time.to_be_bytes());
+            }
+            Self::Pong { time } => {
+                buf.push(PONG);
+                buf.extend_from_slice(&time.to_be_bytes());
+            }
+            Self::Disconnect => buf.push(DISCONNECT),
+        }
+        buf
+    }
+
+    /// Decodes a datagram.
+    ///
+    /// Returns [`None`] if the datagram is not a known kind of packet, or is
+    /// too short for its kind.
+    pub fn decode(buf: &'a [u8]) -> Option<Self> {
+        let (&tag, rest) = buf.split_first()?;
+        match tag {
+            CONNECT => Some(Self::Connect {
+                protocol_id: read_u64(rest, 0)?,
+                nonce: read_u64(rest, 8)?,
+            }),
+            ACCEPT => Some(Self::Accept {
+                nonce: read_u64(rest, 0)?,
+            }),
+            REJECT => Some(Self::Reject {
+                nonce: read_u64(rest, 0)?,
+            }),
+            DATA => Some(Self::Data(rest)),
+            PING => Some(Self::Ping {
+                time: read_u64(rest, 0)?,
+            }),
+            PONG => Some(Self::Pong {
+                time: read_u64(rest, 0)?,
+            }),
+            DISCONNECT => Some(Self::Disconnect),
+            _ => None,
+        }
+    }
+}
+
+fn read_u64(buf: &[u8], at: usize) -> Option<u64> {
+    let bytes = buf.get(at..at + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_packet() {
+        for packet in [
+            Packet::Connect {
+                protocol_id: 7,
+                nonce: u64::MAX,
+            },
+            Packet::Accept { nonce: 3 },
+            Packet::Reject { nonce: 3 },
+            Packet::Data(&[1, 2, 3]),
+            Packet::Data(&[]),
+            Packet::Ping { time: 1234 },
+            Packet::Pong { time: 1234 },
+            Packet::Disconnect,
+        ] {
+            assert_eq!(Some(packet), Packet::decode(&packet.encode()));
+        }
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(None, Packet::decode(&[]));
+        assert_eq!(None, Packet::decode(&[DISCONNECT + 1]));
+        assert_eq!(None, Packet::decode(&[ACCEPT, 0, 0, 0]));
+        let connect = Packet::Connect {
+            protocol_id: 1,
+            nonce: 2,
+        }
+        .encode();
+        assert_eq!(None, Packet::decode(&connect[..connect.len() - 1]));
+    }
+}
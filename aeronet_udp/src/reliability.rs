@@ -0,0 +1,445 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use aeronet::ChannelKind;
+
+/// Strategy for delivering messages on top of the unreliable datagrams of a
+/// UDP connection.
+///
+/// Each connection has its own instance, created using [`Default`] when the
+/// connection is established. The backend passes every message sent on the
+/// connection to [`Reliability::send`], and the payload of every data
+/// datagram received to [`Reliability::recv`], and calls
+/// [`Reliability::update`] regularly so that lost payloads can be resent.
+///
+/// Both sides of a connection must use the same strategy, as the strategy
+/// defines the format of the payloads.
+///
+/// If [`Reliability::send`] or [`Reliability::update`] return an error, the
+/// connection can no longer deliver messages as promised, and is closed.
+///
+/// This crate provides [`Unreliable`], which sends every message exactly once,
+/// and [`Reliable`], which acknowledges and resends messages on reliable
+/// channels. Apps with other needs, such as forward error correction or
+/// delta-compressed snapshots, can implement their own.
+pub trait Reliability: Default + Send + 'static {
+    /// Maximum number of bytes that this adds to the front of a message.
+    ///
+    /// This is subtracted from the maximum length of a message, so that every
+    /// payload fits in a single datagram.
+    const HEADER_LEN: usize;
+
+    /// Frames a message sent on the channel with the given index and kind,
+    /// pushing the payloads to send to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message can not be delivered as promised by the kind of
+    /// the channel.
+    fn send(
+        &mut self,
+        channel: u8,
+        kind: ChannelKind,
+        msg: &[u8],
+        now: Instant,
+        out: &mut Vec<Vec<u8>>,
+    ) -> Result<(), ReliabilityError>;
+
+    /// Handles a payload received from the other side, pushing the messages
+    /// which are ready to be delivered to `recv`, and any payloads to send in
+    /// response, such as acknowledgements, to `out`.
+    fn recv(
+        &mut self,
+        payload: &[u8],
+        now: Instant,
+        recv: &mut Vec<Vec<u8>>,
+        out: &mut Vec<Vec<u8>>,
+    );
+
+    /// Pushes any payloads which should be sent again to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a message which was sent earlier can no longer be delivered.
+    fn update(&mut self, now: Instant, out: &mut Vec<Vec<u8>>) -> Result<(), ReliabilityError>;
+}
+
+/// Error that occurs when a [`Reliability`] can no longer deliver messages on
+/// a connection.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReliabilityError {
+    /// A message on a reliable channel was sent [`Reliable::MAX_SENDS`] times
+    /// without being acknowledged.
+    #[error("message {seq} on channel {channel} was not acknowledged")]
+    Unacknowledged {
+        /// Index of the channel that the message was sent on.
+        channel: u8,
+        /// Sequence number of the message.
+        seq: u32,
+    },
+    /// Every sequence number of a reliable channel has been used up.
+    #[error("no sequence numbers left on channel {0}")]
+    SequenceExhausted(u8),
+}
+
+/// [`Reliability`] which sends every message exactly once, with no framing.
+///
+/// Messages may be lost, duplicated or arrive out of order, whatever the kind
+/// of the channel that they are sent on. This suits apps which send the full
+/// state every tick, and apps which handle reliability at a higher level.
+#[derive(Debug, Clone, Default)]
+pub struct Unreliable;
+
+impl Reliability for Unreliable {
+    const HEADER_LEN: usize = 0;
+
+    fn send(
+        &mut self,
+        _channel: u8,
+        _kind: ChannelKind,
+        msg: &[u8],
+        _now: Instant,
+        out: &mut Vec<Vec<u8>>,
+    ) -> Result<(), ReliabilityError> {
+        out.push(msg.to_vec());
+        Ok(())
+    }
+
+    fn recv(
+        &mut self,
+        payload: &[u8],
+        _now: Instant,
+        recv: &mut Vec<Vec<u8>>,
+        _out: &mut Vec<Vec<u8>>,
+    ) {
+        recv.push(payload.to_vec());
+    }
+
+    fn update(&mut self, _now: Instant, _out: &mut Vec<Vec<u8>>) -> Result<(), ReliabilityError> {
+        Ok(())
+    }
+}
+
+const UNRELIABLE: u8 = 0;
+const RELIABLE_UNORDERED: u8 = 1;
+const RELIABLE_ORDERED: u8 = 2;
+const ACK: u8 = 3;
+
+/// [`Reliability`] which honors the [`ChannelKind`] of each channel by
+/// acknowledging and resending messages on reliable channels.
+///
+/// Messages on reliable channels are numbered per channel, and are resent
+/// every [`Reliable::RESEND_INTERVAL`] until the other side acknowledges
+/// them. Duplicates are dropped, and messages on
+/// [`ChannelKind::ReliableOrdered`] channels are held back until every
+/// earlier message on the channel has been delivered. Messages on
+/// [`ChannelKind::Unreliable`] channels are sent once, like [`Unreliable`].
+///
+/// A message which is still not acknowledged after being sent
+/// [`Reliable::MAX_SENDS`] times closes the connection with
+/// [`ReliabilityError::Unacknowledged`], so that messages are not kept around
+/// forever for a peer which stopped responding.
+///
+/// Only messages less than [`Reliable::RECV_WINDOW`] ahead of the first
+/// missing message on a channel are accepted. Messages further ahead are
+/// dropped without being acknowledged, and are delivered once the sender
+/// sends them again after the gap has been filled.
+///
+/// Every payload starts with a byte identifying the kind of payload. Messages
+/// on reliable channels and acknowledgements follow this with the channel
+/// index and the sequence number of the message as a big-endian `u32`.
+/// Sequence numbers do not wrap around, so a channel can carry at most
+/// [`u32::MAX`] reliable messages over the lifetime of a connection, after
+/// which sending fails with [`ReliabilityError::SequenceExhausted`].
+#[derive(Debug, Default)]
+pub struct Reliable {
+    next_seq: HashMap<u8, u32>,
+    unacked: BTreeMap<(u8, u32), Unacked>,
+    lanes: HashMap<u8, RecvLane>,
+}
+
+#[derive(Debug)]
+struct Unacked {
+    payload: Vec<u8>,
+    last_sent: Instant,
+    sends: u32,
+}
+
+/// Messages received on a single reliable channel.
+#[derive(Debug, Default)]
+struct RecvLane {
+    /// Sequence number of the first message which has not been received.
+    ///
+    /// Every message before this has been received and delivered.
+    next: u32,
+    /// Messages received after a missing message, keyed by sequence number.
+    ///
+    /// Messages which have already been delivered are kept as [`None`], so
+    /// that duplicates can be detected.
+    ahead: BTreeMap<u32, Option<Vec<u8>>>,
+}
+
+impl Reliable {
+    /// Interval at which unacknowledged messages are resent.
+    pub const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Number of times that a message is sent without being acknowledged
+    /// before the connection is closed.
+    pub const MAX_SENDS: u32 = 50;
+
+    /// Number of messages after the first missing message on a channel which
+    /// are accepted.
+    pub const RECV_WINDOW: u32 = 1024;
+
+    /// Gets the number of sent messages which have not been acknowledged yet.
+    #[must_use]
+    pub fn unacked_len(&self) -> usize {
+        self.unacked.len()
+    }
+}
+
+fn header(kind: u8, channel: u8, seq: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(Reliable::HEADER_LEN);
+    buf.push(kind);
+    buf.push(channel);
+    buf.extend_from_slice(&seq.to_be_bytes());
+    buf
+}
+
+impl Reliability for Reliable {
+    const HEADER_LEN: usize = 6;
+
+    fn send(
+        &mut self,
+        channel: u8,
+        kind: ChannelKind,
+        msg: &[u8],
+        now: Instant,
+        out: &mut Vec<Vec<u8>>,
+    ) -> Result<(), ReliabilityError> {
+        let tag = match kind {
+            ChannelKind::Unreliable => {
+                let mut payload = Vec::with_capacity(1 + msg.len());
+                payload.push(UNRELIABLE);
+                payload.extend_from_slice(msg);
+                out.push(payload);
+                return Ok(());
+            }
+            ChannelKind::ReliableUnordered => RELIABLE_UNORDERED,
+            ChannelKind::ReliableOrdered => RELIABLE_ORDERED,
+        };
+
+        let next_seq = self.next_seq.entry(channel).or_default();
+        let seq = *next_seq;
+        *next_seq = seq
+            .checked_add(1)
+            .ok_or(ReliabilityError::SequenceExhausted(channel))?;
+
+        let mut payload = header(tag, channel, seq);
+        payload.extend_from_slice(msg);
+        out.push(payload.clone());
+        self.unacked.insert(
+            (channel, seq),
+            Unacked {
+                payload,
+                last_sent: now,
+                sends: 1,
+            },
+        );
+        Ok(())
+    }
+
+    fn recv(
+        &mut self,
+        payload: &[u8],
+        _now: Instant,
+        recv: &mut Vec<Vec<u8>>,
+        out: &mut Vec<Vec<u8>>,
+    ) {
+        let Some((&tag, rest)) = payload.split_first() else {
+            return;
+        };
+        if tag == UNRELIABLE {
+            recv.push(rest.to_vec());
+            return;
+        }
+
+        let Some((&channel, rest)) = rest.split_first() else {
+            return;
+        };
+        let (Some(seq), Some(msg)) = (rest.get(..4), rest.get(4..)) else {
+            return;
+        };
+        let seq = u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]);
+
+        let ordered = match tag {
+            ACK => {
+                self.unacked.remove(&(channel, seq));
+                return;
+            }
+            RELIABLE_UNORDERED => false,
+            RELIABLE_ORDERED => true,
+            _ => return,
+        };
+
+        let lane = self.lanes.entry(channel).or_default();
+        if seq >= lane.next && seq - lane.next >= Self::RECV_WINDOW {
+            // not acknowledged, so the sender keeps resending it until the
+            // window catches up
+            return;
+        }
+
+        // acknowledge duplicates as well, since the ack of the original may
+        // have been lost
+        out.push(header(ACK, channel, seq));
+        if seq < lane.next || lane.ahead.contains_key(&seq) {
+            return;
+        }
+
+        if ordered {
+            lane.ahead.insert(seq, Some(msg.to_vec()));
+        } else {
+            recv.push(msg.to_vec());
+            lane.ahead.insert(seq, None);
+        }
+        while let Some(msg) = lane.ahead.remove(&lane.next) {
+            recv.extend(msg);
+            lane.next = lane.next.saturating_add(1);
+        }
+    }
+
+    fn update(&mut self, now: Instant, out: &mut Vec<Vec<u8>>) -> Result<(), ReliabilityError> {
+        for (&(channel, seq), unacked) in &mut self.unacked {
+            if now.duration_since(unacked.last_sent) < Self::RESEND_INTERVAL {
+                continue;
+            }
+            if unacked.sends >= Self::MAX_SENDS {
+                return Err(ReliabilityError::Unacknowledged { channel, seq });
+            }
+            unacked.last_sent = now;
+            unacked.sends += 1;
+            out.push(unacked.payload.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(r: &mut Reliable, kind: ChannelKind, msg: &[u8], now: Instant) -> Vec<u8> {
+        let mut out = Vec::new();
+        r.send(0, kind, msg, now, &mut out).unwrap();
+        assert_eq!(1, out.len());
+        out.remove(0)
+    }
+
+    fn recv(r: &mut Reliable, payload: &[u8], now: Instant) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let (mut msgs, mut out) = (Vec::new(), Vec::new());
+        r.recv(payload, now, &mut msgs, &mut out);
+        (msgs, out)
+    }
+
+    #[test]
+    fn ordered_holds_back_until_gap_filled() {
+        let now = Instant::now();
+        let (mut tx, mut rx) = (Reliable::default(), Reliable::default());
+        let a = send(&mut tx, ChannelKind::ReliableOrdered, b"a", now);
+        let b = send(&mut tx, ChannelKind::ReliableOrdered, b"b", now);
+
+        let (msgs, acks) = recv(&mut rx, &b, now);
+        assert!(msgs.is_empty());
+        assert_eq!(1, acks.len());
+        let (msgs, _) = recv(&mut rx, &a, now);
+        assert_eq!(vec![b"a".to_vec(), b"b".to_vec()], msgs);
+
+        // duplicates are acked again, but not delivered
+        let (msgs, acks) = recv(&mut rx, &a, now);
+        assert!(msgs.is_empty());
+        assert_eq!(1, acks.len());
+    }
+
+    #[test]
+    fn unordered_delivers_immediately() {
+        let now = Instant::now();
+        let (mut tx, mut rx) = (Reliable::default(), Reliable::default());
+        let _ = send(&mut tx, ChannelKind::ReliableUnordered, b"a", now);
+        let b = send(&mut tx, ChannelKind::ReliableUnordered, b"b", now);
+
+        assert_eq!(vec![b"b".to_vec()], recv(&mut rx, &b, now).0);
+        assert!(recv(&mut rx, &b, now).0.is_empty());
+    }
+
+    #[test]
+    fn resends_until_acked() {
+        let now = Instant::now();
+        let (mut tx, mut rx) = (Reliable::default(), Reliable::default());
+        let a = send(&mut tx, ChannelKind::ReliableOrdered, b"a", now);
+        let _ = send(&mut tx, ChannelKind::Unreliable, b"b", now);
+        assert_eq!(1, tx.unacked_len());
+
+        let mut out = Vec::new();
+        tx.update(now, &mut out).unwrap();
+        assert!(out.is_empty());
+        tx.update(now + Reliable::RESEND_INTERVAL, &mut out)
+            .unwrap();
+        assert_eq!(vec![a.clone()], out);
+
+        let (_, acks) = recv(&mut rx, &a, now);
+        let (msgs, out) = recv(&mut tx, &acks[0], now);
+        assert!(msgs.is_empty());
+        assert!(out.is_empty());
+        assert_eq!(0, tx.unacked_len());
+    }
+
+    #[test]
+    fn gives_up_when_never_acked() {
+        let mut now = Instant::now();
+        let mut tx = Reliable::default();
+        let _ = send(&mut tx, ChannelKind::ReliableOrdered, b"a", now);
+
+        let mut out = Vec::new();
+        for _ in 1..Reliable::MAX_SENDS {
+            now += Reliable::RESEND_INTERVAL;
+            tx.update(now, &mut out).unwrap();
+        }
+        assert_eq!(Reliable::MAX_SENDS as usize - 1, out.len());
+
+        now += Reliable::RESEND_INTERVAL;
+        assert_eq!(
+            Err(ReliabilityError::Unacknowledged { channel: 0, seq: 0 }),
+            tx.update(now, &mut out)
+        );
+    }
+
+    #[test]
+    fn drops_outside_window() {
+        let now = Instant::now();
+        let mut rx = Reliable::default();
+        let far = header(RELIABLE_UNORDERED, 0, Reliable::RECV_WINDOW);
+        let (msgs, acks) = recv(&mut rx, &far, now);
+        assert!(msgs.is_empty());
+        assert!(acks.is_empty());
+
+        let last = header(RELIABLE_UNORDERED, 0, Reliable::RECV_WINDOW - 1);
+        let (msgs, acks) = recv(&mut rx, &last, now);
+        assert_eq!(1, msgs.len());
+        assert_eq!(1, acks.len());
+    }
+
+    #[test]
+    fn errors_when_sequence_exhausted() {
+        let now = Instant::now();
+        let mut tx = Reliable::default();
+        tx.next_seq.insert(0, u32::MAX);
+        let mut out = Vec::new();
+        assert_eq!(
+            Err(ReliabilityError::SequenceExhausted(0)),
+            tx.send(0, ChannelKind::ReliableOrdered, b"a", now, &mut out)
+        );
+        assert!(out.is_empty());
+    }
+}
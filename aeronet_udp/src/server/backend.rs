@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use slotmap::SlotMap;
+use tracing::debug;
+
+use crate::{
+    packet::Packet,
+    transport::{self, flush, INFO_INTERVAL, PING_INTERVAL, SERVICE_TIMEOUT},
+    BackendError, ClientKey, Outgoing, Reliability, UdpInfo, UdpServerConfig, MAX_DATAGRAM_LEN,
+};
+
+/// Request from the frontend to the backend.
+#[derive(Debug)]
+pub(super) enum Request {
+    Send { client: ClientKey, msg: Outgoing },
+    Disconnect { client: ClientKey },
+}
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected {
+        client: ClientKey,
+        info: UdpInfo,
+    },
+    Info {
+        client: ClientKey,
+        info: UdpInfo,
+    },
+    Recv {
+        client: ClientKey,
+        bytes: Vec<u8>,
+    },
+    Disconnected {
+        client: ClientKey,
+        cause: BackendError,
+    },
+    Closed {
+        cause: BackendError,
+    },
+}
+
+struct Peer<R> {
+    addr: SocketAddr,
+    nonce: u64,
+    reliability: R,
+    rtt: Duration,
+    last_recv: Instant,
+    last_ping: Instant,
+}
+
+impl<R> Peer<R> {
+    fn info(&self) -> UdpInfo {
+        UdpInfo {
+            rtt: self.rtt,
+            remote_addr: self.addr,
+        }
+    }
+}
+
+pub(super) fn start<R: Reliability>(
+    config: &UdpServerConfig,
+    send_open: &Sender<Result<SocketAddr, BackendError>>,
+    recv_req: &Receiver<Request>,
+    send_update: &Sender<Update>,
+) {
+    let socket = match bind(config) {
+        Ok(socket) => socket,
+        Err(err) => {
+            let _ = send_open.send(Err(BackendError::Bind(err)));
+            return;
+        }
+    };
+
+    let local_addr = socket.local_addr().unwrap_or(config.addr);
+    if send_open.send(Ok(local_addr)).is_err() {
+        debug!("Frontend closed");
+        return;
+    }
+
+    debug!("Starting server loop on {local_addr}");
+    let mut server = Server::<R> {
+        socket,
+        start: Instant::now(),
+        peers: SlotMap::default(),
+        addrs: HashMap::new(),
+        out: Vec::new(),
+        recv: Vec::new(),
+    };
+    let mut last_info = Instant::now();
+    let mut buf = vec![0; MAX_DATAGRAM_LEN];
+    loop {
+        loop {
+            match recv_req.try_recv() {
+                Ok(Request::Send { client, msg }) => server.send(client, &msg, send_update),
+                Ok(Request::Disconnect { client }) => {
+                    if let Some(peer) = server.peers.remove(client) {
+                        server.addrs.remove(&peer.addr);
+                        let _ = server
+                            .socket
+                            .send_to(&Packet::Disconnect.encode(), peer.addr);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("Frontend closed");
+                    let disconnect = Packet::Disconnect.encode();
+                    for (_, peer) in server.peers.drain() {
+                        let _ = server.socket.send_to(&disconnect, peer.addr);
+                    }
+                    return;
+                }
+            }
+        }
+
+        // block for at most `SERVICE_TIMEOUT` on the first receive, then keep
+        // receiving until there are no datagrams left
+        loop {
+            match server.socket.recv_from(&mut buf) {
+                Ok((len, from)) => server.recv(config, &buf[..len], from, send_update),
+                Err(err) if transport::is_transient(&err) => break,
+                Err(err) => {
+                    let _ = send_update.send(Update::Closed {
+                        cause: BackendError::Socket(err),
+                    });
+                    return;
+                }
+            }
+        }
+
+        server.update(config, send_update);
+
+        if last_info.elapsed() >= INFO_INTERVAL {
+            last_info = Instant::now();
+            for (client, peer) in &server.peers {
+                let info = peer.info();
+                let _ = send_update.send(Update::Info { client, info });
+            }
+        }
+    }
+}
+
+fn bind(config: &UdpServerConfig) -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(config.addr)?;
+    socket.set_read_timeout(Some(SERVICE_TIMEOUT))?;
+    Ok(socket)
+}
+
+struct Server<R> {
+    socket: UdpSocket,
+    start: Instant,
+    peers: SlotMap<ClientKey, Peer<R>>,
+    addrs: HashMap<SocketAddr, ClientKey>,
+    out: Vec<Vec<u8>>,
+    recv: Vec<Vec<u8>>,
+}
+
+impl<R: Reliability> Server<R> {
+    fn send(&mut self, client: ClientKey, msg: &Outgoing, send_update: &Sender<Update>) {
+        let Some(peer) = self.peers.get_mut(client) else {
+            return;
+        };
+        let result = peer.reliability.send(
+            msg.channel_id,
+            msg.kind,
+            &msg.bytes,
+            Instant::now(),
+            &mut self.out,
+        );
+        flush(&self.socket, peer.addr, &mut self.out);
+        if let Err(err) = result {
+            self.kick(client, BackendError::Reliability(err), send_update);
+        }
+    }
+
+    fn recv(
+        &mut self,
+        config: &UdpServerConfig,
+        datagram: &[u8],
+        from: SocketAddr,
+        send_update: &Sender<Update>,
+    ) {
+        let Some(packet) = Packet::decode(datagram) else {
+            return;
+        };
+        let now = Instant::now();
+
+        if let Packet::Connect { protocol_id, nonce } = packet {
+            if protocol_id != config.protocol_id {
+                return;
+            }
+
+            if let Some(&client) = self.addrs.get(&from) {
+                if self
+                    .peers
+                    .get(client)
+                    .is_some_and(|peer| peer.nonce != nonce)
+                {
+                    // the client restarted without disconnecting, and happened
+                    // to bind the same address
+                    self.remove(client, BackendError::Disconnected, send_update);
+                }
+            }
+
+            let reply = match self.addrs.get(&from).and_then(|&key| self.peers.get(key)) {
+                // the client did not receive our accept, and is still trying
+                Some(_) => Packet::Accept { nonce },
+                None if self.peers.len() >= config.max_clients => Packet::Reject { nonce },
+                None => {
+                    let client = self.peers.insert(Peer {
+                        addr: from,
+                        nonce,
+                        reliability: R::default(),
+                        rtt: Duration::ZERO,
+                        last_recv: now,
+                        last_ping: now,
+                    });
+                    self.addrs.insert(from, client);
+                    let info = self.peers[client].info();
+                    let _ = send_update.send(Update::Connected { client, info });
+                    Packet::Accept { nonce }
+                }
+            };
+            let _ = self.socket.send_to(&reply.encode(), from);
+            return;
+        }
+
+        let Some(&client) = self.addrs.get(&from) else {
+            return;
+        };
+        let Some(peer) = self.peers.get_mut(client) else {
+            return;
+        };
+        peer.last_recv = now;
+
+        match packet {
+            Packet::Data(payload) => {
+                peer.reliability
+                    .recv(payload, now, &mut self.recv, &mut self.out);
+                for bytes in self.recv.drain(..) {
+                    let _ = send_update.send(Update::Recv { client, bytes });
+                }
+                flush(&self.socket, peer.addr, &mut self.out);
+            }
+            Packet::Ping { time } => {
+                let _ = self.socket.send_to(&Packet::Pong { time }.encode(), from);
+            }
+            Packet::Pong { time } => {
+                peer.rtt = transport::ping_rtt(self.start, time);
+            }
+            Packet::Disconnect => self.remove(client, BackendError::Disconnected, send_update),
+            // clients never send these
+            Packet::Connect { .. } | Packet::Accept { .. } | Packet::Reject { .. } => {}
+        }
+    }
+
+    fn update(&mut self, config: &UdpServerConfig, send_update: &Sender<Update>) {
+        let now = Instant::now();
+        let mut timed_out = Vec::new();
+        let mut failed = Vec::new();
+        for (client, peer) in &mut self.peers {
+            if now.duration_since(peer.last_recv) >= config.timeout {
+                timed_out.push(client);
+                continue;
+            }
+
+            if let Err(err) = peer.reliability.update(now, &mut self.out) {
+                failed.push((client, err));
+                continue;
+            }
+            flush(&self.socket, peer.addr, &mut self.out);
+
+            if now.duration_since(peer.last_ping) >= PING_INTERVAL {
+                peer.last_ping = now;
+                let time = transport::ping_time(self.start);
+                let _ = self
+                    .socket
+                    .send_to(&Packet::Ping { time }.encode(), peer.addr);
+            }
+        }
+
+        for client in timed_out {
+            self.remove(client, BackendError::TimedOut, send_update);
+        }
+        for (client, err) in failed {
+            self.kick(client, BackendError::Reliability(err), send_update);
+        }
+    }
+
+    /// Removes a client which is still reachable, telling it that it has been
+    /// disconnected.
+    fn kick(&mut self, client: ClientKey, cause: BackendError, send_update: &Sender<Update>) {
+        if let Some(peer) = self.peers.get(client) {
+            let _ = self.socket.send_to(&Packet::Disconnect.encode(), peer.addr);
+        }
+        self.remove(client, cause, send_update);
+    }
+
+    fn remove(&mut self, client: ClientKey, cause: BackendError, send_update: &Sender<Update>) {
+        if let Some(peer) = self.peers.remove(client) {
+            self.addrs.remove(&peer.addr);
+            let _ = send_update.send(Update::Disconnected { client, cause });
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::{marker::PhantomData, mem, net::SocketAddr, thread};
+
+use aeronet::{
+    CustomEvent, OnChannel, OnMessageError, SendToManyResult, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+use crossbeam_channel::TryRecvError;
+use slotmap::SecondaryMap;
+use tracing::debug;
+
+use crate::{
+    transport::{self, Outgoing},
+    ClientKey, Reliability, UdpInfo, UdpProtocol, UdpServer, UdpServerConfig,
+};
+
+use super::{
+    backend::{self, Request, Update},
+    OpenServer, OpeningServer, ServerEvent, State, UdpError,
+};
+
+impl<P, R> UdpServer<P, R>
+where
+    P: UdpProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Reliability,
+{
+    /// Creates a new server which is not open for connections, and is not
+    /// starting to open.
+    ///
+    /// If you want to create a server and start listening for connections
+    /// immediately after creation, use [`UdpServer::opening`] instead.
+    #[must_use]
+    pub fn closed() -> Self {
+        Self {
+            state: State::Closed,
+            event_buf: Vec::new(),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates and starts opening a server.
+    ///
+    /// This spawns a new thread for the server's backend, which runs until the
+    /// server is closed or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the protocol has more than [`MAX_CHANNELS`] channels.
+    ///
+    /// [`MAX_CHANNELS`]: crate::MAX_CHANNELS
+    pub fn opening(config: UdpServerConfig) -> Result<Self, UdpError<P>> {
+        let server = OpeningServer::new::<P, R>(config)?;
+        Ok(Self {
+            state: State::Opening(server),
+            event_buf: Vec::new(),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Attempts to open this server for connections.
+    ///
+    /// See [`UdpServer::opening`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already opening or is opened, or if the
+    /// protocol has more than [`MAX_CHANNELS`] channels.
+    ///
+    /// [`MAX_CHANNELS`]: crate::MAX_CHANNELS
+    pub fn open(&mut self, config: UdpServerConfig) -> Result<(), UdpError<P>> {
+        match self.state {
+            State::Closed => {
+                self.state = State::Opening(OpeningServer::new::<P, R>(config)?);
+                Ok(())
+            }
+            State::Opening(_) | State::Open(_) => Err(UdpError::<P>::BackendOpen),
+        }
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// a client.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// a client.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from a client fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from a client fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ServerEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+
+    /// Gets the local address that the server's socket is bound to, if it is
+    /// open.
+    ///
+    /// This is useful when binding to port `0`, to find the port that was
+    /// assigned by the OS.
+    #[must_use]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.local_addr),
+        }
+    }
+
+    /// Closes this server, disconnecting all clients and stopping the backend
+    /// thread.
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already closed.
+    pub fn close(&mut self) -> Result<(), UdpError<P>> {
+        match self.state {
+            State::Closed => Err(UdpError::<P>::BackendClosed),
+            State::Opening(_) | State::Open(_) => {
+                self.state = State::Closed;
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a message to a client without taking ownership of it.
+    ///
+    /// The message is serialized directly from the reference, so the same
+    /// message can be sent to many clients without cloning it for each one.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    pub fn send_ref(&mut self, client: ClientKey, msg: &P::S2C) -> Result<(), UdpError<P>> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(UdpError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P, R>(client, msg),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    /// Applies the serialize error policy to the result of sending a message.
+    fn apply_serialize_policy(
+        &mut self,
+        client: ClientKey,
+        result: Result<(), UdpError<P>>,
+    ) -> Result<(), UdpError<P>> {
+        match result {
+            Err(err @ UdpError::<P>::Serialize(_)) => match self.on_serialize_error {
+                OnMessageError::DisconnectClient => {
+                    let _ = self.disconnect(client);
+                    Err(err)
+                }
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message to {client:?}: {err:#}");
+                    Ok(())
+                }
+                OnMessageError::EmitEventOnly => Err(err),
+            },
+            result => result,
+        }
+    }
+}
+
+impl<P, R> TransportServer<P> for UdpServer<P, R>
+where
+    P: UdpProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Reliability,
+{
+    const TRANSPORT_NAME: &'static str = "udp";
+
+    type Client = ClientKey;
+
+    type Error = UdpError<P>;
+
+    type ConnectionInfo = UdpInfo;
+
+    type Event = ServerEvent<P>;
+
+    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server.clients.get(client).cloned(),
+        }
+    }
+
+    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+        let clients = match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.clients.keys()),
+        };
+        clients.into_iter().flatten()
+    }
+
+    fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(UdpError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P, R>(client, &msg.into()),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        let msg = msg.into();
+        if let State::Open(server) = &self.state {
+            if let Ok(serialized) = transport::serialize::<P, R, _, P::C2S>(&msg) {
+                return clients
+                    .into_iter()
+                    .map(|client| (client, server.queue::<P>(client, serialized.clone())))
+                    .collect();
+            }
+        }
+
+        // the serialize error policy is applied separately for each client
+        clients
+            .into_iter()
+            .map(|client| (client, self.send_ref(client, &msg)))
+            .collect()
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let on_deserialize_error = self.on_deserialize_error;
+        let mut events = mem::take(&mut self.event_buf);
+        self.state = match mem::take(&mut self.state) {
+            State::Closed => State::Closed,
+            State::Opening(server) => match server.recv_open.try_recv() {
+                Ok(Ok(local_addr)) => {
+                    events.push(ServerEvent::Opened);
+                    State::Open(OpenServer {
+                        local_addr,
+                        clients: SecondaryMap::new(),
+                        send_req: server.send_req,
+                        recv_update: server.recv_update,
+                    })
+                }
+                Ok(Err(cause)) => {
+                    events.push(ServerEvent::Closed {
+                        cause: cause.into(),
+                    });
+                    State::Closed
+                }
+                Err(TryRecvError::Empty) => State::Opening(server),
+                Err(TryRecvError::Disconnected) => {
+                    events.push(ServerEvent::Closed {
+                        cause: UdpError::<P>::BackendClosed,
+                    });
+                    State::Closed
+                }
+            },
+            State::Open(mut server) => match server.recv::<P>(&mut events, on_deserialize_error) {
+                Ok(()) => State::Open(server),
+                Err(cause) => {
+                    events.push(ServerEvent::Closed { cause });
+                    State::Closed
+                }
+            },
+        };
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+        let client = client.into();
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(UdpError::<P>::BackendClosed),
+            State::Open(server) => {
+                server.disconnect::<P>(client)?;
+                self.event_buf.push(ServerEvent::Disconnected {
+                    client,
+                    cause: UdpError::<P>::ForceDisconnect,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        self.event_buf.push(ServerEvent::Custom { event });
+    }
+}
+
+impl OpeningServer {
+    fn new<P, R>(config: UdpServerConfig) -> Result<Self, UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+        R: Reliability,
+    {
+        transport::channel_count::<P, _, _>()?;
+        let (send_open, recv_open) = crossbeam_channel::bounded(1);
+        let (send_req, recv_req) = crossbeam_channel::unbounded();
+        let (send_update, recv_update) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            backend::start::<R>(&config, &send_open, &recv_req, &send_update);
+            debug!("Server backend stopped");
+        });
+        Ok(Self {
+            recv_open,
+            send_req,
+            recv_update,
+        })
+    }
+}
+
+impl OpenServer {
+    fn send<P, R>(&self, client: ClientKey, msg: &P::S2C) -> Result<(), UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+        R: Reliability,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(UdpError::<P>::NoClient(client));
+        }
+
+        let msg = transport::serialize::<P, R, _, _>(msg)?;
+        self.queue::<P>(client, msg)
+    }
+
+    fn queue<P>(&self, client: ClientKey, msg: Outgoing) -> Result<(), UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(UdpError::<P>::NoClient(client));
+        }
+
+        self.send_req
+            .send(Request::Send { client, msg })
+            .map_err(|_| UdpError::<P>::BackendClosed)
+    }
+
+    fn disconnect<P>(&mut self, client: ClientKey) -> Result<(), UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        if self.clients.remove(client).is_none() {
+            return Err(UdpError::<P>::NoClient(client));
+        }
+
+        let _ = self.send_req.send(Request::Disconnect { client });
+        Ok(())
+    }
+
+    fn recv<P>(
+        &mut self,
+        events: &mut Vec<ServerEvent<P>>,
+        on_deserialize_error: OnMessageError,
+    ) -> Result<(), UdpError<P>>
+    where
+        P: UdpProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        loop {
+            match self.recv_update.try_recv() {
+                Ok(Update::Connected { client, info }) => {
+                    self.clients.insert(client, info);
+                    events.push(ServerEvent::Connected { client });
+                }
+                Ok(Update::Info { client, info }) => {
+                    if let Some(client) = self.clients.get_mut(client) {
+                        *client = info;
+                    }
+                }
+                Ok(Update::Recv { client, bytes }) => {
+                    if !self.clients.contains_key(client) {
+                        continue;
+                    }
+
+                    let cause = match P::C2S::try_from_bytes(&bytes) {
+                        Ok(msg) => {
+                            events.push(ServerEvent::Recv { client, msg });
+                            continue;
+                        }
+                        Err(err) => UdpError::<P>::Deserialize(err),
+                    };
+                    match on_deserialize_error {
+                        OnMessageError::DisconnectClient => {
+                            self.clients.remove(client);
+                            let _ = self.send_req.send(Request::Disconnect { client });
+                            events.push(ServerEvent::Disconnected { client, cause });
+                        }
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message from {client:?}: {cause:#}");
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ServerEvent::MessageError { client, cause });
+                        }
+                    }
+                }
+                Ok(Update::Disconnected { client, cause }) => {
+                    if self.clients.remove(client).is_some() {
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: cause.into(),
+                        });
+                    }
+                }
+                Ok(Update::Closed { cause }) => return Err(cause.into()),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(UdpError::<P>::BackendClosed),
+            }
+        }
+    }
+}
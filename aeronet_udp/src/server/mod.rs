@@ -0,0 +1,206 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, marker::PhantomData, net::SocketAddr, time::Duration};
+
+use aeronet::{
+    CustomEvent, OnChannel, OnMessageError, TransportProtocol, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+use crossbeam_channel::{Receiver, Sender};
+use derivative::Derivative;
+use slotmap::SecondaryMap;
+
+use crate::{BackendError, ClientKey, Reliability, UdpInfo, UdpProtocol, Unreliable};
+
+use self::backend::{Request, Update};
+
+type UdpError<P> = crate::UdpError<<P as TransportProtocol>::S2C, <P as TransportProtocol>::C2S>;
+
+/// Configuration for opening a [`UdpServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpServerConfig {
+    /// Address to bind the socket to.
+    pub addr: SocketAddr,
+    /// Identifier of the app's protocol, which clients must send when
+    /// connecting.
+    ///
+    /// Connect requests with a different identifier are ignored, so that
+    /// clients of an incompatible version, or stray datagrams from other apps,
+    /// do not take up a client slot.
+    pub protocol_id: u64,
+    /// Maximum number of clients which can be connected at once.
+    ///
+    /// Clients which connect while the server is full are rejected.
+    pub max_clients: usize,
+    /// Time after which a client is disconnected if no datagrams have been
+    /// received from it.
+    pub timeout: Duration,
+}
+
+impl UdpServerConfig {
+    /// Default value of [`UdpServerConfig::max_clients`].
+    pub const DEFAULT_MAX_CLIENTS: usize = 32;
+
+    /// Default value of [`UdpServerConfig::timeout`].
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates a config which listens on the given address with a protocol ID
+    /// of `0`, allowing [`UdpServerConfig::DEFAULT_MAX_CLIENTS`] clients.
+    #[must_use]
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            protocol_id: 0,
+            max_clients: Self::DEFAULT_MAX_CLIENTS,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// Implementation of [`TransportServer`] using raw UDP datagrams.
+///
+/// Messages are delivered according to the [`Reliability`] `R`, which must be
+/// the same as the one that clients use.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct UdpServer<P, R = Unreliable>
+where
+    P: UdpProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Reliability,
+{
+    state: State,
+    #[derivative(Debug = "ignore")]
+    event_buf: Vec<ServerEvent<P>>,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> R>,
+}
+
+/// Event raised by a [`UdpServer`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ServerEvent<P>
+where
+    P: UdpProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// The server backend has been set up and is ready to accept connections.
+    Opened,
+    /// A client has connected to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Connected`].
+    Connected {
+        /// The key of the client.
+        client: ClientKey,
+    },
+    /// A client sent a message to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Recv`].
+    Recv {
+        /// The key of the client which sent the message.
+        client: ClientKey,
+        /// The message.
+        msg: P::C2S,
+    },
+    /// A message received from a client failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`UdpServer::set_on_deserialize_error`].
+    MessageError {
+        /// The key of the client.
+        client: ClientKey,
+        /// The error which occurred.
+        cause: UdpError<P>,
+    },
+    /// A client has lost connection from this server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Disconnected`].
+    Disconnected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The reason why the client lost connection.
+        cause: UdpError<P>,
+    },
+    /// The server backend has been shut down, all client connections have been
+    /// dropped, and the backend must be re-opened.
+    Closed {
+        /// The reason why the backend was closed.
+        cause: UdpError<P>,
+    },
+    /// A user-defined event was injected using
+    /// [`TransportServer::push_event`].
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Custom`].
+    Custom {
+        /// The event.
+        event: CustomEvent,
+    },
+}
+
+impl<P, T> From<ServerEvent<P>> for Option<aeronet::ServerEvent<P, T>>
+where
+    P: UdpProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    T: TransportServer<P, Client = ClientKey, Error = UdpError<P>>,
+{
+    fn from(value: ServerEvent<P>) -> Self {
+        match value {
+            ServerEvent::Connected { client } => Some(aeronet::ServerEvent::Connected { client }),
+            ServerEvent::Recv { client, msg } => Some(aeronet::ServerEvent::Recv { client, msg }),
+            ServerEvent::Disconnected { client, cause } => {
+                Some(aeronet::ServerEvent::Disconnected { client, cause })
+            }
+            ServerEvent::Custom { event } => Some(aeronet::ServerEvent::Custom { event }),
+            ServerEvent::Opened | ServerEvent::MessageError { .. } | ServerEvent::Closed { .. } => {
+                None
+            }
+        }
+    }
+}
+
+// server states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Closed,
+    Opening(OpeningServer),
+    Open(OpenServer),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpeningServer {
+    #[derivative(Debug = "ignore")]
+    recv_open: Receiver<Result<SocketAddr, BackendError>>,
+    #[derivative(Debug = "ignore")]
+    send_req: Sender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpenServer {
+    local_addr: SocketAddr,
+    clients: SecondaryMap<ClientKey, UdpInfo>,
+    #[derivative(Debug = "ignore")]
+    send_req: Sender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: Receiver<Update>,
+}
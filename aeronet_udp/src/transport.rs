@@ -0,0 +1,262 @@
+use std::{
+    fmt::Debug,
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use aeronet::{
+    ChannelKey, ChannelKind, Message, OnChannel, RemoteAddr, Rtt, TransportProtocol, TryFromBytes,
+    TryIntoBytes,
+};
+
+use crate::{packet::Packet, Reliability, ReliabilityError};
+
+slotmap::new_key_type! {
+    /// Key type used to uniquely identify a client connected to a
+    /// [`UdpServer`].
+    ///
+    /// [`UdpServer`]: crate::UdpServer
+    pub struct ClientKey;
+}
+
+/// Extension of [`TransportProtocol`] for raw UDP implementations.
+pub trait UdpProtocol: TransportProtocol {
+    /// The type of [`ChannelKey`] used to specify along what channel a message
+    /// is sent.
+    ///
+    /// Each variant is identified on the wire by its [`ChannelKey::index`], so
+    /// there may be at most [`MAX_CHANNELS`] variants.
+    type Channel: ChannelKey;
+}
+
+/// Maximum number of channels that a UDP connection supports.
+pub const MAX_CHANNELS: usize = 255;
+
+/// Maximum length in bytes of a single datagram sent by an endpoint.
+///
+/// This is kept below the minimum MTU of most paths on the internet, so that
+/// datagrams are not fragmented by IP. Each message is sent in a single
+/// datagram, so the largest message which can be sent is this minus the
+/// packet tag and the [`Reliability::HEADER_LEN`] of the connection.
+pub const MAX_DATAGRAM_LEN: usize = 1200;
+
+/// Statistics on the network state of a UDP connection managed by an
+/// endpoint.
+///
+/// This serves as a snapshot of network stats, not a live updating value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpInfo {
+    /// The round-trip time of the connection as defined by [`Rtt`], measured
+    /// by the last keep-alive ping.
+    pub rtt: Duration,
+    /// The remote address of this connection as defined by [`RemoteAddr`].
+    pub remote_addr: SocketAddr,
+}
+
+impl Rtt for UdpInfo {
+    fn rtt(&self) -> Duration {
+        self.rtt
+    }
+}
+
+impl RemoteAddr for UdpInfo {
+    fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+}
+
+/// Error that occurs when processing a raw UDP transport implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum UdpError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    /// The backend that handles connections is shut down or not ready for
+    /// this operation.
+    #[error("backend closed")]
+    BackendClosed,
+    /// Attempted to open the backend while it was already open.
+    #[error("backend already open")]
+    BackendOpen,
+    /// The protocol's channel key has more variants than can be identified on
+    /// the wire.
+    ///
+    /// See [`MAX_CHANNELS`].
+    #[error("too many channels: {0} > {MAX_CHANNELS}")]
+    TooManyChannels(usize),
+    /// Failed to bind the UDP socket.
+    #[error("failed to bind socket")]
+    Bind(#[source] io::Error),
+    /// Failed to send or receive on the UDP socket.
+    #[error("socket error")]
+    Socket(#[source] io::Error),
+    /// Attempted to perform an operation on a client which does not exist.
+    #[error("no client with key {0:?}")]
+    NoClient(ClientKey),
+    /// The server refused the connection, because it is full.
+    #[error("rejected by server")]
+    Rejected,
+    /// No datagrams were received from the other side within the configured
+    /// timeout.
+    #[error("timed out")]
+    TimedOut,
+    /// The other side closed the connection.
+    #[error("disconnected")]
+    Disconnected,
+    /// The client was forcefully disconnected by the server.
+    #[error("force disconnect")]
+    ForceDisconnect,
+    /// The [`Reliability`] of the connection can no longer deliver messages.
+    #[error("reliability failed")]
+    Reliability(#[source] ReliabilityError),
+    /// Attempted to send a message which does not fit in a single datagram.
+    ///
+    /// See [`MAX_DATAGRAM_LEN`].
+    #[error("message too large: {len} > {max} bytes")]
+    MessageTooLarge {
+        /// Length of the serialized message.
+        len: usize,
+        /// Maximum length of a message on this connection.
+        max: usize,
+    },
+    /// Failed to serialize data using [`TryIntoBytes::try_into_bytes`].
+    #[error("failed to serialize data")]
+    Serialize(#[source] S::Error),
+    /// Failed to deserialize data using [`TryFromBytes::try_from_bytes`].
+    #[error("failed to deserialize data")]
+    Deserialize(#[source] R::Error),
+}
+
+/// Error that occurs in a backend, which is independent of the message types.
+#[derive(Debug)]
+pub(crate) enum BackendError {
+    Bind(io::Error),
+    Socket(io::Error),
+    Rejected,
+    TimedOut,
+    Disconnected,
+    Reliability(ReliabilityError),
+}
+
+impl<S, R> From<BackendError> for UdpError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    fn from(value: BackendError) -> Self {
+        match value {
+            BackendError::Bind(err) => Self::Bind(err),
+            BackendError::Socket(err) => Self::Socket(err),
+            BackendError::Rejected => Self::Rejected,
+            BackendError::TimedOut => Self::TimedOut,
+            BackendError::Disconnected => Self::Disconnected,
+            BackendError::Reliability(err) => Self::Reliability(err),
+        }
+    }
+}
+
+/// Time that a backend blocks waiting for a datagram before checking for
+/// requests from the frontend again.
+pub(crate) const SERVICE_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Interval at which connection info is sent to the frontend.
+pub(crate) const INFO_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval at which pings are sent to measure the round-trip time, which
+/// also keep the connection alive while no messages are sent.
+pub(crate) const PING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Returns if an error returned by a socket is caused by a single datagram,
+/// rather than the socket itself failing.
+///
+/// Some platforms report ICMP errors of previously sent datagrams on the next
+/// receive, which should not close the socket.
+pub(crate) fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Gets the time since `start` as used in pings, in microseconds.
+pub(crate) fn ping_time(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Gets the round-trip time of a ping which was sent at `time`.
+pub(crate) fn ping_rtt(start: Instant, time: u64) -> Duration {
+    Duration::from_micros(ping_time(start).saturating_sub(time))
+}
+
+/// A message which has been serialized by the frontend, waiting to be sent by
+/// the backend.
+#[derive(Debug, Clone)]
+pub(crate) struct Outgoing {
+    pub channel_id: u8,
+    pub kind: ChannelKind,
+    pub bytes: Vec<u8>,
+}
+
+/// Checks that the protocol's channels can all be identified on the wire,
+/// returning the number of channels.
+pub(crate) fn channel_count<P, S, R>() -> Result<usize, UdpError<S, R>>
+where
+    P: UdpProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let count = P::Channel::ALL.len();
+    if count > MAX_CHANNELS {
+        Err(UdpError::TooManyChannels(count))
+    } else {
+        Ok(count)
+    }
+}
+
+/// Gets the maximum length of a serialized message sent using `R`.
+pub(crate) fn max_message_len<R: Reliability>() -> usize {
+    MAX_DATAGRAM_LEN - crate::packet::TAG_LEN - R::HEADER_LEN
+}
+
+/// Serializes a message so that it can be passed to the backend.
+pub(crate) fn serialize<P, L, S, R>(msg: &S) -> Result<Outgoing, UdpError<S, R>>
+where
+    P: UdpProtocol,
+    L: Reliability,
+    S: Message + TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Message + TryFromBytes,
+{
+    let channel = msg.channel();
+    let serialized = msg.try_into_bytes().map_err(UdpError::Serialize)?;
+    let bytes = serialized.as_ref();
+    let max = max_message_len::<L>();
+    if bytes.len() > max {
+        return Err(UdpError::MessageTooLarge {
+            len: bytes.len(),
+            max,
+        });
+    }
+    Ok(Outgoing {
+        // the channel count is checked when the backend is started
+        #[allow(clippy::cast_possible_truncation)]
+        channel_id: channel.index() as u8,
+        kind: channel.kind(),
+        bytes: bytes.to_vec(),
+    })
+}
+
+/// Sends every payload in `out` as a data packet.
+///
+/// Errors are ignored, since a datagram which could not be sent is no
+/// different from one lost in transit.
+pub(crate) fn flush(socket: &UdpSocket, addr: SocketAddr, out: &mut Vec<Vec<u8>>) {
+    for payload in out.drain(..) {
+        let _ = socket.send_to(&Packet::Data(&payload).encode(), addr);
+    }
+}
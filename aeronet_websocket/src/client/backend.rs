@@ -0,0 +1,95 @@
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{
+    tungstenite::{
+        client::IntoClientRequest,
+        error::{Error, UrlError},
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::debug;
+
+use crate::{
+    shared::{self, LinkEvent},
+    BackendError, WebSocketClientConfig, WebSocketInfo,
+};
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected { info: WebSocketInfo },
+    Info { info: WebSocketInfo },
+    Recv { bytes: Vec<u8> },
+    Error { cause: BackendError },
+}
+
+pub(super) async fn start(
+    config: WebSocketClientConfig,
+    recv_c2s: mpsc::UnboundedReceiver<Vec<u8>>,
+    send_update: mpsc::UnboundedSender<Update>,
+) {
+    let (stream, info) = match connect(&config).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            let _ = send_update.send(Update::Error {
+                cause: BackendError::Connect(err),
+            });
+            return;
+        }
+    };
+
+    debug!("Connected to {}", info.remote_addr);
+    let _ = send_update.send(Update::Connected { info: info.clone() });
+    let result = shared::handle_connection(stream, info, recv_c2s, |event| {
+        let update = match event {
+            LinkEvent::Info(info) => Update::Info { info },
+            LinkEvent::Recv(bytes) => Update::Recv { bytes },
+        };
+        let _ = send_update.send(update);
+    })
+    .await;
+    match result {
+        Ok(()) => debug!("Frontend closed"),
+        Err(cause) => {
+            let _ = send_update.send(Update::Error { cause });
+        }
+    }
+}
+
+type Stream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Opens the TCP connection ourselves rather than through
+/// [`tokio_tungstenite::connect_async`], so that the remote address is known
+/// even if the stream is wrapped in TLS.
+async fn connect(config: &WebSocketClientConfig) -> Result<(Stream, WebSocketInfo), Error> {
+    let request = config.url.as_str().into_client_request()?;
+    let uri = request.uri();
+    let host = uri.host().ok_or(Error::Url(UrlError::NoHostName))?;
+    let port = uri
+        .port_u16()
+        .or_else(|| match uri.scheme_str() {
+            Some("wss") => Some(443),
+            Some("ws") => Some(80),
+            _ => None,
+        })
+        .ok_or(Error::Url(UrlError::UnsupportedUrlScheme))?;
+
+    #[cfg(not(feature = "tls"))]
+    if uri.scheme_str() == Some("wss") {
+        return Err(Error::Url(UrlError::TlsFeatureNotEnabled));
+    }
+
+    let socket = TcpStream::connect(format!("{host}:{port}")).await?;
+    let info = WebSocketInfo::new(socket.peer_addr()?);
+    let ws_config = Some(shared::ws_config(config.max_message_size));
+    #[cfg(feature = "tls")]
+    let (stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, socket, ws_config, None).await?;
+    #[cfg(not(feature = "tls"))]
+    let (stream, _) = tokio_tungstenite::client_async_with_config(
+        request,
+        MaybeTlsStream::Plain(socket),
+        ws_config,
+    )
+    .await?;
+    Ok((stream, info))
+}
@@ -0,0 +1,288 @@
+use std::{future::Future, marker::PhantomData, mem};
+
+use aeronet::{OnMessageError, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tracing::debug;
+
+use crate::{transport, WebSocketClient, WebSocketClientConfig, WebSocketInfo};
+
+use super::{
+    backend::{self, Update},
+    Backend, ClientEvent, ClientState, State, WebSocketError,
+};
+
+impl<P> WebSocketClient<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    /// Creates a new client which is not connecting to any server.
+    ///
+    /// If you want to create a client and connect to a server immediately after
+    /// creation, use [`WebSocketClient::connecting`] instead.
+    #[must_use]
+    pub fn disconnected() -> Self {
+        Self {
+            state: State::Disconnected,
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates and starts connecting a client to a server.
+    ///
+    /// This returns the client frontend, and the backend future, which must be
+    /// spawned on a tokio runtime. The backend runs until the client is
+    /// disconnected or dropped.
+    pub fn connecting(config: WebSocketClientConfig) -> (Self, impl Future<Output = ()> + Send) {
+        let (backend, future) = Backend::start(config);
+        (
+            Self {
+                state: State::Connecting(backend),
+                on_serialize_error: OnMessageError::EmitEventOnly,
+                on_deserialize_error: OnMessageError::DisconnectClient,
+                _phantom: PhantomData,
+            },
+            future,
+        )
+    }
+
+    /// Attempts to start connecting this client to a server.
+    ///
+    /// See [`WebSocketClient::connecting`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this client is already connecting or is connected to a
+    /// server.
+    pub fn connect(
+        &mut self,
+        config: WebSocketClientConfig,
+    ) -> Result<impl Future<Output = ()> + Send, WebSocketError<P>> {
+        match self.state {
+            State::Disconnected => {
+                let (backend, future) = Backend::start(config);
+                self.state = State::Connecting(backend);
+                Ok(future)
+            }
+            State::Connecting(_) | State::Connected(..) => Err(WebSocketError::<P>::BackendOpen),
+        }
+    }
+
+    /// Gets the current state of the client.
+    #[must_use]
+    pub fn state(&self) -> ClientState {
+        match self.state {
+            State::Disconnected => ClientState::Disconnected,
+            State::Connecting(_) => ClientState::Connecting,
+            State::Connected(..) => ClientState::Connected,
+        }
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// the server.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// the server.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from the server fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from the server fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ClientEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+}
+
+impl<P> TransportClient<P> for WebSocketClient<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    const TRANSPORT_NAME: &'static str = "websocket";
+
+    type Error = WebSocketError<P>;
+
+    type ConnectionInfo = WebSocketInfo;
+
+    type Event = ClientEvent<P>;
+
+    fn connection_info(&self) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => None,
+            State::Connected(_, info) => Some(info.clone()),
+        }
+    }
+
+    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
+        let backend = match &self.state {
+            State::Disconnected | State::Connecting(_) => {
+                return Err(WebSocketError::<P>::BackendClosed)
+            }
+            State::Connected(backend, _) => backend,
+        };
+        let bytes = match transport::serialize(&msg.into()) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                return match self.on_serialize_error {
+                    OnMessageError::DisconnectClient => {
+                        self.state = State::Disconnected;
+                        Err(err)
+                    }
+                    OnMessageError::DropMessage => {
+                        debug!("Dropped message to server: {err:#}");
+                        Ok(())
+                    }
+                    OnMessageError::EmitEventOnly => Err(err),
+                };
+            }
+        };
+        backend
+            .send_c2s
+            .send(bytes)
+            .map_err(|_| WebSocketError::<P>::BackendClosed)
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let on_deserialize_error = self.on_deserialize_error;
+        let mut events = Vec::new();
+        self.state = match mem::take(&mut self.state) {
+            State::Disconnected => State::Disconnected,
+            State::Connecting(mut backend) => match backend.recv_update.try_recv() {
+                Ok(Update::Connected { info }) => {
+                    events.push(ClientEvent::Connected);
+                    // messages may have been received in the same poll
+                    recv_connected(backend, info, on_deserialize_error, &mut events)
+                }
+                Ok(update) => {
+                    events.push(ClientEvent::Disconnected {
+                        cause: disconnect_cause::<P>(update),
+                    });
+                    State::Disconnected
+                }
+                Err(TryRecvError::Empty) => State::Connecting(backend),
+                Err(TryRecvError::Disconnected) => {
+                    events.push(ClientEvent::Disconnected {
+                        cause: WebSocketError::<P>::BackendClosed,
+                    });
+                    State::Disconnected
+                }
+            },
+            State::Connected(backend, info) => {
+                recv_connected(backend, info, on_deserialize_error, &mut events)
+            }
+        };
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self) -> Result<(), Self::Error> {
+        match self.state {
+            State::Disconnected => Err(WebSocketError::<P>::BackendClosed),
+            State::Connecting(_) | State::Connected(..) => {
+                self.state = State::Disconnected;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn recv_connected<P>(
+    mut backend: Backend,
+    mut info: WebSocketInfo,
+    on_deserialize_error: OnMessageError,
+    events: &mut Vec<ClientEvent<P>>,
+) -> State
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    loop {
+        let cause = match backend.recv_update.try_recv() {
+            Ok(Update::Info { info: new_info }) => {
+                info = new_info;
+                continue;
+            }
+            Ok(Update::Recv { bytes }) => match P::S2C::try_from_bytes(&bytes) {
+                Ok(msg) => {
+                    events.push(ClientEvent::Recv { msg });
+                    continue;
+                }
+                Err(err) => {
+                    let cause = WebSocketError::<P>::Deserialize(err);
+                    match on_deserialize_error {
+                        OnMessageError::DisconnectClient => cause,
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message from server: {cause:#}");
+                            continue;
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ClientEvent::MessageError { cause });
+                            continue;
+                        }
+                    }
+                }
+            },
+            Ok(update) => disconnect_cause::<P>(update),
+            Err(TryRecvError::Empty) => return State::Connected(backend, info),
+            Err(TryRecvError::Disconnected) => WebSocketError::<P>::BackendClosed,
+        };
+        events.push(ClientEvent::Disconnected { cause });
+        return State::Disconnected;
+    }
+}
+
+fn disconnect_cause<P>(update: Update) -> WebSocketError<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    match update {
+        Update::Error { cause } => cause.into(),
+        // the backend only sends these while connected, which is handled by
+        // the caller
+        Update::Connected { .. } | Update::Info { .. } | Update::Recv { .. } => {
+            WebSocketError::<P>::BackendClosed
+        }
+    }
+}
+
+impl Backend {
+    fn start(config: WebSocketClientConfig) -> (Self, impl Future<Output = ()> + Send) {
+        let (send_c2s, recv_c2s) = mpsc::unbounded_channel();
+        let (send_update, recv_update) = mpsc::unbounded_channel();
+        let backend = async move {
+            backend::start(config, recv_c2s, send_update).await;
+            debug!("Client backend stopped");
+        };
+        (
+            Self {
+                send_c2s,
+                recv_update,
+            },
+            backend,
+        )
+    }
+}
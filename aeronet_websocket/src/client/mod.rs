@@ -0,0 +1,155 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, marker::PhantomData};
+
+use aeronet::{OnMessageError, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+use tokio::sync::mpsc;
+
+use crate::WebSocketInfo;
+
+use self::backend::Update;
+
+type WebSocketError<P> =
+    crate::WebSocketError<<P as TransportProtocol>::C2S, <P as TransportProtocol>::S2C>;
+
+/// Configuration for connecting a [`WebSocketClient`] to a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketClientConfig {
+    /// URL of the server to connect to, e.g. `ws://127.0.0.1:25565`.
+    ///
+    /// `wss://` URLs require the `tls` feature.
+    pub url: String,
+    /// Maximum size of a message received from the server in bytes, or
+    /// [`None`] for no limit.
+    ///
+    /// The client disconnects if the server sends a larger message.
+    pub max_message_size: Option<usize>,
+}
+
+impl WebSocketClientConfig {
+    /// Default value of [`WebSocketClientConfig::max_message_size`].
+    pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+    /// Creates a config which connects to the given URL, with a maximum
+    /// message size of [`WebSocketClientConfig::DEFAULT_MAX_MESSAGE_SIZE`].
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            max_message_size: Some(Self::DEFAULT_MAX_MESSAGE_SIZE),
+        }
+    }
+}
+
+/// Implementation of [`TransportClient`] using WebSocket.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug, Default(bound = ""))]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct WebSocketClient<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    state: State,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<P>,
+}
+
+/// Event raised by a [`WebSocketClient`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ClientEvent<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+{
+    /// This client has fully connected to a server.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Connected`].
+    Connected,
+    /// The connected server sent a message to the client.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Recv`].
+    Recv {
+        /// The message received.
+        msg: P::S2C,
+    },
+    /// A message received from the server failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`WebSocketClient::set_on_deserialize_error`].
+    MessageError {
+        /// The error which occurred.
+        cause: WebSocketError<P>,
+    },
+    /// The client lost connection from its previous server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ClientEvent::Disconnected`].
+    Disconnected {
+        /// The reason why the client lost connection.
+        cause: WebSocketError<P>,
+    },
+}
+
+impl<P, T> From<ClientEvent<P>> for Option<aeronet::ClientEvent<P, T>>
+where
+    P: TransportProtocol,
+    P::C2S: TryIntoBytes,
+    P::S2C: TryFromBytes,
+    T: TransportClient<P, Error = WebSocketError<P>>,
+{
+    fn from(value: ClientEvent<P>) -> Self {
+        match value {
+            ClientEvent::Connected => Some(aeronet::ClientEvent::Connected),
+            ClientEvent::Recv { msg } => Some(aeronet::ClientEvent::Recv { msg }),
+            ClientEvent::Disconnected { cause } => {
+                Some(aeronet::ClientEvent::Disconnected { cause })
+            }
+            ClientEvent::MessageError { .. } => None,
+        }
+    }
+}
+
+/// The current state of a [`WebSocketClient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    /// Not connected and is not attempting to connect to a server.
+    Disconnected,
+    /// Currently attempting to connect to a server.
+    Connecting,
+    /// Fully connected to a server and ready to transmit messages.
+    Connected,
+}
+
+// client states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Disconnected,
+    Connecting(Backend),
+    Connected(Backend, WebSocketInfo),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Backend {
+    #[derivative(Debug = "ignore")]
+    send_c2s: mpsc::UnboundedSender<Vec<u8>>,
+    #[derivative(Debug = "ignore")]
+    recv_update: mpsc::UnboundedReceiver<Update>,
+}
@@ -0,0 +1,10 @@
+#![doc = include_str!("../README.md")]
+
+mod client;
+mod server;
+mod shared;
+mod transport;
+
+pub use tokio_tungstenite;
+
+pub use {client::*, server::*, transport::*};
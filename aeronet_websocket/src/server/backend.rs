@@ -0,0 +1,165 @@
+use std::{net::SocketAddr, time::Duration};
+
+use slotmap::SlotMap;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    time,
+};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tracing::debug;
+
+use crate::{
+    shared::{self, LinkEvent},
+    BackendError, ClientKey, WebSocketInfo, WebSocketServerConfig,
+};
+
+/// Time that a client has to complete the WebSocket handshake after opening a
+/// TCP connection.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request from the frontend to the backend.
+#[derive(Debug)]
+pub(super) enum Request {
+    Send { client: ClientKey, bytes: Vec<u8> },
+    Disconnect { client: ClientKey },
+}
+
+/// Update from the backend to the frontend.
+#[derive(Debug)]
+pub(super) enum Update {
+    Connected {
+        client: ClientKey,
+        info: WebSocketInfo,
+    },
+    Info {
+        client: ClientKey,
+        info: WebSocketInfo,
+    },
+    Recv {
+        client: ClientKey,
+        bytes: Vec<u8>,
+    },
+    Disconnected {
+        client: ClientKey,
+        cause: BackendError,
+    },
+}
+
+pub(super) async fn start(
+    config: WebSocketServerConfig,
+    send_open: oneshot::Sender<Result<SocketAddr, BackendError>>,
+    mut recv_req: mpsc::UnboundedReceiver<Request>,
+    send_update: mpsc::UnboundedSender<Update>,
+) {
+    let listener = match TcpListener::bind(config.addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            let _ = send_open.send(Err(BackendError::Bind(err)));
+            return;
+        }
+    };
+    let local_addr = match listener.local_addr() {
+        Ok(local_addr) => local_addr,
+        Err(err) => {
+            let _ = send_open.send(Err(BackendError::Bind(err)));
+            return;
+        }
+    };
+
+    if send_open.send(Ok(local_addr)).is_err() {
+        debug!("Frontend closed");
+        return;
+    }
+
+    debug!("Starting server loop on {local_addr}");
+    let ws_config = shared::ws_config(config.max_message_size);
+    let mut clients = SlotMap::<ClientKey, mpsc::UnboundedSender<Vec<u8>>>::default();
+    let (send_done, mut recv_done) = mpsc::unbounded_channel();
+    loop {
+        tokio::select! {
+            req = recv_req.recv() => match req {
+                Some(Request::Send { client, bytes }) => {
+                    if let Some(send_s) = clients.get(client) {
+                        let _ = send_s.send(bytes);
+                    }
+                }
+                Some(Request::Disconnect { client }) => {
+                    // dropping the sender makes the client's task close the
+                    // connection, after sending what was already queued
+                    clients.remove(client);
+                }
+                None => {
+                    // dropping the senders closes every connection
+                    debug!("Frontend closed");
+                    return;
+                }
+            },
+            accepted = listener.accept() => match accepted {
+                Ok((stream, remote_addr)) => {
+                    let (send_s, recv_s) = mpsc::unbounded_channel();
+                    let client = clients.insert(send_s);
+                    tokio::spawn(handle_client(
+                        client,
+                        stream,
+                        remote_addr,
+                        ws_config,
+                        recv_s,
+                        send_update.clone(),
+                        send_done.clone(),
+                    ));
+                }
+                Err(err) => debug!("Failed to accept connection: {err:#}"),
+            },
+            Some(client) = recv_done.recv() => {
+                clients.remove(client);
+            }
+        }
+    }
+}
+
+async fn handle_client(
+    client: ClientKey,
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    ws_config: WebSocketConfig,
+    recv_s: mpsc::UnboundedReceiver<Vec<u8>>,
+    send_update: mpsc::UnboundedSender<Update>,
+    send_done: mpsc::UnboundedSender<ClientKey>,
+) {
+    let handshake = tokio_tungstenite::accept_async_with_config(stream, Some(ws_config));
+    let stream = match time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(err)) => {
+            debug!("Failed to accept connection from {remote_addr}: {err:#}");
+            let _ = send_done.send(client);
+            return;
+        }
+        Err(_) => {
+            debug!("Connection from {remote_addr} timed out during handshake");
+            let _ = send_done.send(client);
+            return;
+        }
+    };
+
+    debug!("Accepted {client:?} from {remote_addr}");
+    let info = WebSocketInfo::new(remote_addr);
+    let _ = send_update.send(Update::Connected {
+        client,
+        info: info.clone(),
+    });
+    let result = shared::handle_connection(stream, info, recv_s, |event| {
+        let update = match event {
+            LinkEvent::Info(info) => Update::Info { client, info },
+            LinkEvent::Recv(bytes) => Update::Recv { client, bytes },
+        };
+        let _ = send_update.send(update);
+    })
+    .await;
+    // clients closed by the frontend have already been removed there
+    if let Err(cause) = result {
+        debug!("{client:?} disconnected");
+        let _ = send_update.send(Update::Disconnected { client, cause });
+    }
+    let _ = send_done.send(client);
+}
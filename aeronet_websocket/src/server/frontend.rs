@@ -0,0 +1,425 @@
+use std::{future::Future, mem, net::SocketAddr};
+
+use aeronet::{
+    CustomEvent, OnMessageError, SendToManyResult, TransportProtocol, TransportServer,
+    TryFromBytes, TryIntoBytes,
+};
+use slotmap::SecondaryMap;
+use tokio::sync::{
+    mpsc::{self, error::TryRecvError},
+    oneshot,
+};
+use tracing::debug;
+
+use crate::{transport, ClientKey, WebSocketInfo, WebSocketServer, WebSocketServerConfig};
+
+use super::{
+    backend::{self, Request, Update},
+    OpenServer, OpeningServer, ServerEvent, State, WebSocketError,
+};
+
+impl<P> WebSocketServer<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes,
+{
+    /// Creates a new server which is not open for connections, and is not
+    /// starting to open.
+    ///
+    /// If you want to create a server and start listening for connections
+    /// immediately after creation, use [`WebSocketServer::opening`] instead.
+    #[must_use]
+    pub fn closed() -> Self {
+        Self {
+            state: State::Closed,
+            event_buf: Vec::new(),
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+        }
+    }
+
+    /// Creates and starts opening a server.
+    ///
+    /// This returns the server frontend, and the backend future, which must be
+    /// spawned on a tokio runtime. The backend spawns a task for each client
+    /// connection, and runs until the server is closed or dropped.
+    pub fn opening(config: WebSocketServerConfig) -> (Self, impl Future<Output = ()> + Send) {
+        let (server, backend) = OpeningServer::new(config);
+        (
+            Self {
+                state: State::Opening(server),
+                event_buf: Vec::new(),
+                on_serialize_error: OnMessageError::EmitEventOnly,
+                on_deserialize_error: OnMessageError::DisconnectClient,
+            },
+            backend,
+        )
+    }
+
+    /// Attempts to open this server for connections.
+    ///
+    /// See [`WebSocketServer::opening`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already opening or is opened.
+    pub fn open(
+        &mut self,
+        config: WebSocketServerConfig,
+    ) -> Result<impl Future<Output = ()> + Send, WebSocketError<P>> {
+        match self.state {
+            State::Closed => {
+                let (server, backend) = OpeningServer::new(config);
+                self.state = State::Opening(server);
+                Ok(backend)
+            }
+            State::Opening(_) | State::Open(_) => Err(WebSocketError::<P>::BackendOpen),
+        }
+    }
+
+    /// Gets the local address which this server is listening on, if it is
+    /// open.
+    #[must_use]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.local_addr),
+        }
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// a client.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// a client.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from a client fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from a client fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ServerEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+
+    /// Closes this server, disconnecting all clients and stopping the backend.
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already closed.
+    pub fn close(&mut self) -> Result<(), WebSocketError<P>> {
+        match self.state {
+            State::Closed => Err(WebSocketError::<P>::BackendClosed),
+            State::Opening(_) | State::Open(_) => {
+                self.state = State::Closed;
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a message to a client without taking ownership of it.
+    ///
+    /// The message is serialized directly from the reference, so the same
+    /// message can be sent to many clients without cloning it for each one.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    pub fn send_ref(&mut self, client: ClientKey, msg: &P::S2C) -> Result<(), WebSocketError<P>> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(WebSocketError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P>(client, msg),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    /// Applies the serialize error policy to the result of sending a message.
+    fn apply_serialize_policy(
+        &mut self,
+        client: ClientKey,
+        result: Result<(), WebSocketError<P>>,
+    ) -> Result<(), WebSocketError<P>> {
+        match result {
+            Err(err @ WebSocketError::<P>::Serialize(_)) => match self.on_serialize_error {
+                OnMessageError::DisconnectClient => {
+                    let _ = self.disconnect(client);
+                    Err(err)
+                }
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message to {client:?}: {err:#}");
+                    Ok(())
+                }
+                OnMessageError::EmitEventOnly => Err(err),
+            },
+            result => result,
+        }
+    }
+}
+
+impl<P> TransportServer<P> for WebSocketServer<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes,
+{
+    const TRANSPORT_NAME: &'static str = "websocket";
+
+    type Client = ClientKey;
+
+    type Error = WebSocketError<P>;
+
+    type ConnectionInfo = WebSocketInfo;
+
+    type Event = ServerEvent<P>;
+
+    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server.clients.get(client).cloned(),
+        }
+    }
+
+    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+        let clients = match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.clients.keys()),
+        };
+        clients.into_iter().flatten()
+    }
+
+    fn send(&mut self, client: Self::Client, msg: impl Into<P::S2C>) -> Result<(), Self::Error> {
+        let result = match &self.state {
+            State::Closed | State::Opening(_) => Err(WebSocketError::<P>::BackendClosed),
+            State::Open(server) => server.send::<P>(client, &msg.into()),
+        };
+        self.apply_serialize_policy(client, result)
+    }
+
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        let msg = msg.into();
+        if let State::Open(server) = &self.state {
+            if let Ok(bytes) = transport::serialize::<_, P::C2S>(&msg) {
+                return clients
+                    .into_iter()
+                    .map(|client| (client, server.queue::<P>(client, bytes.clone())))
+                    .collect();
+            }
+        }
+
+        // the serialize error policy is applied separately for each client
+        clients
+            .into_iter()
+            .map(|client| (client, self.send_ref(client, &msg)))
+            .collect()
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let on_deserialize_error = self.on_deserialize_error;
+        let mut events = mem::take(&mut self.event_buf);
+        self.state = match mem::take(&mut self.state) {
+            State::Closed => State::Closed,
+            State::Opening(mut server) => match server.recv_open.try_recv() {
+                Ok(Ok(local_addr)) => {
+                    events.push(ServerEvent::Opened { local_addr });
+                    State::Open(OpenServer {
+                        local_addr,
+                        clients: SecondaryMap::new(),
+                        send_req: server.send_req,
+                        recv_update: server.recv_update,
+                    })
+                }
+                Ok(Err(cause)) => {
+                    events.push(ServerEvent::Closed {
+                        cause: cause.into(),
+                    });
+                    State::Closed
+                }
+                Err(oneshot::error::TryRecvError::Empty) => State::Opening(server),
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    events.push(ServerEvent::Closed {
+                        cause: WebSocketError::<P>::BackendClosed,
+                    });
+                    State::Closed
+                }
+            },
+            State::Open(mut server) => match server.recv::<P>(&mut events, on_deserialize_error) {
+                Ok(()) => State::Open(server),
+                Err(cause) => {
+                    events.push(ServerEvent::Closed { cause });
+                    State::Closed
+                }
+            },
+        };
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+        let client = client.into();
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebSocketError::<P>::BackendClosed),
+            State::Open(server) => {
+                server.disconnect::<P>(client)?;
+                self.event_buf.push(ServerEvent::Disconnected {
+                    client,
+                    cause: WebSocketError::<P>::ForceDisconnect,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        self.event_buf.push(ServerEvent::Custom { event });
+    }
+}
+
+impl OpeningServer {
+    fn new(config: WebSocketServerConfig) -> (Self, impl Future<Output = ()> + Send) {
+        let (send_open, recv_open) = oneshot::channel();
+        let (send_req, recv_req) = mpsc::unbounded_channel();
+        let (send_update, recv_update) = mpsc::unbounded_channel();
+        let backend = async move {
+            backend::start(config, send_open, recv_req, send_update).await;
+            debug!("Server backend stopped");
+        };
+        (
+            Self {
+                recv_open,
+                send_req,
+                recv_update,
+            },
+            backend,
+        )
+    }
+}
+
+impl OpenServer {
+    fn send<P>(&self, client: ClientKey, msg: &P::S2C) -> Result<(), WebSocketError<P>>
+    where
+        P: TransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(WebSocketError::<P>::NoClient(client));
+        }
+
+        let bytes = transport::serialize(msg)?;
+        self.queue::<P>(client, bytes)
+    }
+
+    fn queue<P>(&self, client: ClientKey, bytes: Vec<u8>) -> Result<(), WebSocketError<P>>
+    where
+        P: TransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes,
+    {
+        if !self.clients.contains_key(client) {
+            return Err(WebSocketError::<P>::NoClient(client));
+        }
+
+        self.send_req
+            .send(Request::Send { client, bytes })
+            .map_err(|_| WebSocketError::<P>::BackendClosed)
+    }
+
+    fn disconnect<P>(&mut self, client: ClientKey) -> Result<(), WebSocketError<P>>
+    where
+        P: TransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes,
+    {
+        if self.clients.remove(client).is_none() {
+            return Err(WebSocketError::<P>::NoClient(client));
+        }
+
+        let _ = self.send_req.send(Request::Disconnect { client });
+        Ok(())
+    }
+
+    fn recv<P>(
+        &mut self,
+        events: &mut Vec<ServerEvent<P>>,
+        on_deserialize_error: OnMessageError,
+    ) -> Result<(), WebSocketError<P>>
+    where
+        P: TransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes,
+    {
+        loop {
+            match self.recv_update.try_recv() {
+                Ok(Update::Connected { client, info }) => {
+                    self.clients.insert(client, info);
+                    events.push(ServerEvent::Connected { client });
+                }
+                Ok(Update::Info { client, info }) => {
+                    if let Some(client) = self.clients.get_mut(client) {
+                        *client = info;
+                    }
+                }
+                Ok(Update::Recv { client, bytes }) => {
+                    if !self.clients.contains_key(client) {
+                        continue;
+                    }
+
+                    let cause = match P::C2S::try_from_bytes(&bytes) {
+                        Ok(msg) => {
+                            events.push(ServerEvent::Recv { client, msg });
+                            continue;
+                        }
+                        Err(err) => WebSocketError::<P>::Deserialize(err),
+                    };
+                    match on_deserialize_error {
+                        OnMessageError::DisconnectClient => {
+                            self.clients.remove(client);
+                            let _ = self.send_req.send(Request::Disconnect { client });
+                            events.push(ServerEvent::Disconnected { client, cause });
+                        }
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message from {client:?}: {cause:#}");
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ServerEvent::MessageError { client, cause });
+                        }
+                    }
+                }
+                Ok(Update::Disconnected { client, cause }) => {
+                    if self.clients.remove(client).is_some() {
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: cause.into(),
+                        });
+                    }
+                }
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(WebSocketError::<P>::BackendClosed),
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+mod backend;
+mod frontend;
+
+use std::{fmt::Debug, net::SocketAddr};
+
+use aeronet::{
+    CustomEvent, OnMessageError, TransportProtocol, TransportServer, TryFromBytes, TryIntoBytes,
+};
+use derivative::Derivative;
+use slotmap::SecondaryMap;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{BackendError, ClientKey, WebSocketInfo};
+
+use self::backend::{Request, Update};
+
+type WebSocketError<P> =
+    crate::WebSocketError<<P as TransportProtocol>::S2C, <P as TransportProtocol>::C2S>;
+
+/// Configuration for opening a [`WebSocketServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketServerConfig {
+    /// Address to listen for connections on.
+    pub addr: SocketAddr,
+    /// Maximum size of a message received from a client in bytes, or [`None`]
+    /// for no limit.
+    ///
+    /// Clients which send a larger message are disconnected.
+    pub max_message_size: Option<usize>,
+}
+
+impl WebSocketServerConfig {
+    /// Default value of [`WebSocketServerConfig::max_message_size`].
+    pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+    /// Creates a config which listens on the given address, with a maximum
+    /// message size of [`WebSocketServerConfig::DEFAULT_MAX_MESSAGE_SIZE`].
+    #[must_use]
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            max_message_size: Some(Self::DEFAULT_MAX_MESSAGE_SIZE),
+        }
+    }
+}
+
+/// Implementation of [`TransportServer`] using WebSocket.
+///
+/// See the [crate-level docs](crate).
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
+pub struct WebSocketServer<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes,
+{
+    state: State,
+    #[derivative(Debug = "ignore")]
+    event_buf: Vec<ServerEvent<P>>,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+}
+
+/// Event raised by a [`WebSocketServer`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug"))]
+pub enum ServerEvent<P>
+where
+    P: TransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes,
+{
+    /// The server backend has been set up and is ready to accept connections.
+    Opened {
+        /// The local address which the server is listening on.
+        local_addr: SocketAddr,
+    },
+    /// A client has connected to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Connected`].
+    Connected {
+        /// The key of the client.
+        client: ClientKey,
+    },
+    /// A client sent a message to the server.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Recv`].
+    Recv {
+        /// The key of the client which sent the message.
+        client: ClientKey,
+        /// The message.
+        msg: P::C2S,
+    },
+    /// A message received from a client failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`WebSocketServer::set_on_deserialize_error`].
+    MessageError {
+        /// The key of the client.
+        client: ClientKey,
+        /// The error which occurred.
+        cause: WebSocketError<P>,
+    },
+    /// A client has lost connection from this server, which could not be
+    /// recovered from.
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Disconnected`].
+    Disconnected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The reason why the client lost connection.
+        cause: WebSocketError<P>,
+    },
+    /// The server backend has been shut down, all client connections have been
+    /// dropped, and the backend must be re-opened.
+    Closed {
+        /// The reason why the backend was closed.
+        cause: WebSocketError<P>,
+    },
+    /// A user-defined event was injected using
+    /// [`TransportServer::push_event`].
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Custom`].
+    Custom {
+        /// The event.
+        event: CustomEvent,
+    },
+}
+
+impl<P, T> From<ServerEvent<P>> for Option<aeronet::ServerEvent<P, T>>
+where
+    P: TransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes,
+    T: TransportServer<P, Client = ClientKey, Error = WebSocketError<P>>,
+{
+    fn from(value: ServerEvent<P>) -> Self {
+        match value {
+            ServerEvent::Connected { client } => Some(aeronet::ServerEvent::Connected { client }),
+            ServerEvent::Recv { client, msg } => Some(aeronet::ServerEvent::Recv { client, msg }),
+            ServerEvent::Disconnected { client, cause } => {
+                Some(aeronet::ServerEvent::Disconnected { client, cause })
+            }
+            ServerEvent::Custom { event } => Some(aeronet::ServerEvent::Custom { event }),
+            ServerEvent::Opened { .. }
+            | ServerEvent::MessageError { .. }
+            | ServerEvent::Closed { .. } => None,
+        }
+    }
+}
+
+// server states
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Closed,
+    Opening(OpeningServer),
+    Open(OpenServer),
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpeningServer {
+    #[derivative(Debug = "ignore")]
+    recv_open: oneshot::Receiver<Result<SocketAddr, BackendError>>,
+    #[derivative(Debug = "ignore")]
+    send_req: mpsc::UnboundedSender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: mpsc::UnboundedReceiver<Update>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct OpenServer {
+    local_addr: SocketAddr,
+    clients: SecondaryMap<ClientKey, WebSocketInfo>,
+    #[derivative(Debug = "ignore")]
+    send_req: mpsc::UnboundedSender<Request>,
+    #[derivative(Debug = "ignore")]
+    recv_update: mpsc::UnboundedReceiver<Update>,
+}
@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+    time::{self, MissedTickBehavior},
+};
+use tokio_tungstenite::{
+    tungstenite::{protocol::WebSocketConfig, Message},
+    WebSocketStream,
+};
+
+use crate::{transport::BackendError, WebSocketInfo};
+
+/// Interval at which pings are sent to measure the round-trip time.
+const PING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time that the peer has to answer our close frame after we close the
+/// connection.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Event raised by a connection while it is open.
+#[derive(Debug)]
+pub(crate) enum LinkEvent {
+    Info(WebSocketInfo),
+    Recv(Vec<u8>),
+}
+
+pub(crate) fn ws_config(max_message_size: Option<usize>) -> WebSocketConfig {
+    WebSocketConfig {
+        max_message_size,
+        ..Default::default()
+    }
+}
+
+/// Sends and receives messages on an open connection until it is closed.
+///
+/// Returns [`Ok`] if the connection was closed by this side, because
+/// `recv_s` was closed, or the reason why the connection was lost otherwise.
+pub(crate) async fn handle_connection<S>(
+    mut stream: WebSocketStream<S>,
+    mut info: WebSocketInfo,
+    mut recv_s: mpsc::UnboundedReceiver<Vec<u8>>,
+    mut on_event: impl FnMut(LinkEvent),
+) -> Result<(), BackendError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let start = Instant::now();
+    let mut ping_interval = time::interval(PING_INTERVAL);
+    ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            bytes = recv_s.recv() => {
+                let Some(bytes) = bytes else {
+                    // the close handshake is best-effort, as this side is
+                    // done with the connection either way - but the peer's
+                    // close frame is still waited for, since dropping the
+                    // socket with unread data in it resets the connection
+                    // before the peer can read our close frame
+                    let _ = stream.close(None).await;
+                    let _ = time::timeout(CLOSE_TIMEOUT, async {
+                        while let Some(Ok(_)) = stream.next().await {}
+                    })
+                    .await;
+                    return Ok(());
+                };
+                stream
+                    .send(Message::Binary(bytes))
+                    .await
+                    .map_err(BackendError::Connection)?;
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Binary(bytes))) => on_event(LinkEvent::Recv(bytes)),
+                Some(Ok(Message::Pong(payload))) => {
+                    // pongs which do not answer one of our pings are ignored
+                    let Ok(sent) = <[u8; 8]>::try_from(payload.as_slice()) else {
+                        continue;
+                    };
+                    let sent = Duration::from_nanos(u64::from_be_bytes(sent));
+                    if let Some(rtt) = start.elapsed().checked_sub(sent) {
+                        info.rtt = rtt;
+                        on_event(LinkEvent::Info(info.clone()));
+                    }
+                }
+                // pings are answered by tungstenite, and text messages are not
+                // part of the protocol
+                Some(Ok(Message::Ping(_) | Message::Text(_) | Message::Frame(_))) => {}
+                Some(Ok(Message::Close(_))) | None => return Err(BackendError::Disconnected),
+                Some(Err(err)) => return Err(BackendError::Connection(err)),
+            },
+            _ = ping_interval.tick() => {
+                let sent = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+                stream
+                    .send(Message::Ping(sent.to_be_bytes().to_vec()))
+                    .await
+                    .map_err(BackendError::Connection)?;
+            }
+        }
+    }
+}
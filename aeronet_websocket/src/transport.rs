@@ -0,0 +1,121 @@
+use std::{io, net::SocketAddr, time::Duration};
+
+use aeronet::{Message, RemoteAddr, Rtt, TryFromBytes, TryIntoBytes};
+use tokio_tungstenite::tungstenite;
+
+slotmap::new_key_type! {
+    /// Key type used to uniquely identify a client connected to a
+    /// [`WebSocketServer`].
+    ///
+    /// [`WebSocketServer`]: crate::WebSocketServer
+    pub struct ClientKey;
+}
+
+/// Statistics on the network state of a WebSocket connection.
+///
+/// This serves as a snapshot of network stats, not a live updating value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketInfo {
+    /// The round-trip time of the connection as defined by [`Rtt`].
+    ///
+    /// This is measured using WebSocket pings, and is zero until the first
+    /// ping has been answered.
+    pub rtt: Duration,
+    /// The remote address of this connection as defined by [`RemoteAddr`].
+    pub remote_addr: SocketAddr,
+}
+
+impl WebSocketInfo {
+    pub(crate) fn new(remote_addr: SocketAddr) -> Self {
+        Self {
+            rtt: Duration::ZERO,
+            remote_addr,
+        }
+    }
+}
+
+impl Rtt for WebSocketInfo {
+    fn rtt(&self) -> Duration {
+        self.rtt
+    }
+}
+
+impl RemoteAddr for WebSocketInfo {
+    fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+}
+
+/// Error that occurs when processing a WebSocket transport implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum WebSocketError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    /// The backend that handles connections is shut down or not ready for
+    /// this operation.
+    #[error("backend closed")]
+    BackendClosed,
+    /// Attempted to open the backend while it was already open.
+    #[error("backend already open")]
+    BackendOpen,
+    /// Failed to bind the server's listener to its address.
+    #[error("failed to bind listener")]
+    Bind(#[source] io::Error),
+    /// Failed to connect to the server.
+    #[error("failed to connect")]
+    Connect(#[source] Box<tungstenite::Error>),
+    /// An established connection failed to send or receive data.
+    #[error("connection error")]
+    Connection(#[source] Box<tungstenite::Error>),
+    /// Attempted to perform an operation on a client which does not exist.
+    #[error("no client with key {0:?}")]
+    NoClient(ClientKey),
+    /// The other side closed the connection.
+    #[error("disconnected")]
+    Disconnected,
+    /// The client was forcefully disconnected by the server.
+    #[error("force disconnect")]
+    ForceDisconnect,
+    /// Failed to serialize data using [`TryIntoBytes::try_into_bytes`].
+    #[error("failed to serialize data")]
+    Serialize(#[source] S::Error),
+    /// Failed to deserialize data using [`TryFromBytes::try_from_bytes`].
+    #[error("failed to deserialize data")]
+    Deserialize(#[source] R::Error),
+}
+
+/// Error that occurs in a backend, which is independent of the message types.
+#[derive(Debug)]
+pub(crate) enum BackendError {
+    Bind(io::Error),
+    Connect(tungstenite::Error),
+    Connection(tungstenite::Error),
+    Disconnected,
+}
+
+impl<S, R> From<BackendError> for WebSocketError<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    fn from(value: BackendError) -> Self {
+        match value {
+            BackendError::Bind(err) => Self::Bind(err),
+            BackendError::Connect(err) => Self::Connect(Box::new(err)),
+            BackendError::Connection(err) => Self::Connection(Box::new(err)),
+            BackendError::Disconnected => Self::Disconnected,
+        }
+    }
+}
+
+/// Serializes a message so that it can be passed to the backend.
+pub(crate) fn serialize<S, R>(msg: &S) -> Result<Vec<u8>, WebSocketError<S, R>>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let serialized = msg.try_into_bytes().map_err(WebSocketError::Serialize)?;
+    Ok(serialized.as_ref().to_vec())
+}
@@ -1,15 +1,22 @@
 //!
 
-use std::{convert::Infallible, string::FromUtf8Error, time::Duration};
+use std::{
+    convert::Infallible,
+    fs,
+    net::{Ipv6Addr, SocketAddr},
+    string::FromUtf8Error,
+    time::Duration,
+};
 
 use aeronet::{
     AsyncRuntime, ChannelKey, OnChannel, TransportProtocol, TransportServer, TryFromBytes,
     TryIntoBytes,
 };
-use aeronet_wt_native::{ServerEvent, WebTransportProtocol, WebTransportServer};
+use aeronet_wt_native::{
+    Identity, ServerEvent, WebTransportProtocol, WebTransportServer, WebTransportServerConfig,
+};
 use anyhow::Result;
 use bevy::{app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*};
-use wtransport::{tls::Certificate, ServerConfig};
 
 // protocol
 
@@ -97,17 +104,14 @@ fn setup(mut commands: Commands, rt: Res<AsyncRuntime>) {
 }
 
 fn create(rt: &AsyncRuntime) -> Result<Server> {
-    let cert = tokio::runtime::Runtime::new()
-        .unwrap()
-        .block_on(Certificate::load(
-            "./aeronet_wt_native/examples/cert.pem",
-            "./aeronet_wt_native/examples/key.pem",
-        ))?;
-
-    let config = ServerConfig::builder()
-        .with_bind_default(25565)
-        .with_certificate(cert)
-        .keep_alive_interval(Some(Duration::from_secs(5)))
+    let cert = Identity::from_pem(
+        &fs::read("./aeronet_wt_native/examples/cert.pem")?,
+        &fs::read("./aeronet_wt_native/examples/key.pem")?,
+    )?;
+
+    let config = WebTransportServerConfig::builder()
+        .bind_addr(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 25565)))
+        .identity(cert)
         .build();
 
     let (server, backend) = WebTransportServer::opening(config);
@@ -121,12 +125,14 @@ fn update_server(mut server: ResMut<Server>) {
         match event {
             ServerEvent::Opened => info!("Opened server for connections"),
             ServerEvent::Incoming { client } => info!("{client:?} incoming"),
+            ServerEvent::Requested { client, .. } => info!("{client:?} requested a session"),
             ServerEvent::Accepted {
                 client,
                 authority,
                 path,
                 ..
             } => info!("{client:?} accepted from {authority}{path}"),
+            ServerEvent::PendingConnected { client } => info!("{client:?} pending admission"),
             ServerEvent::Connected { client } => {
                 let remote_addr = server.connection_info(client).unwrap().remote_addr;
                 info!("{client:?} connected from {remote_addr}");
@@ -142,10 +148,29 @@ fn update_server(mut server: ResMut<Server>) {
                     ),
                 }
             }
+            ServerEvent::RecvProgress { .. }
+            | ServerEvent::LaneStats { .. }
+            | ServerEvent::StreamClosed { .. }
+            | ServerEvent::LaneMigrated { .. }
+            | ServerEvent::LaneMigrationRejected { .. }
+            | ServerEvent::ChecksumMismatch { .. }
+            | ServerEvent::HandoverIssued { .. }
+            | ServerEvent::HandoverAccepted { .. }
+            | ServerEvent::HandoverRejected { .. }
+            | ServerEvent::Custom { .. } => {}
+            ServerEvent::MessageError { client, cause } => warn!(
+                "Invalid message from {client:?}: {:#}",
+                aeronet::error::as_pretty(&cause)
+            ),
+            ServerEvent::LimitWarning { client, usage } => warn!(
+                "{client:?} reached {:?} limit: {} >= {}",
+                usage.kind, usage.usage, usage.limit
+            ),
             ServerEvent::Disconnected { client, cause } => info!(
                 "{client:?} disconnected: {:#}",
                 aeronet::error::as_pretty(&cause)
             ),
+            ServerEvent::Drained => info!("Server drained"),
             ServerEvent::Closed { cause } => {
                 info!("Server closed: {:#}", aeronet::error::as_pretty(&cause))
             }
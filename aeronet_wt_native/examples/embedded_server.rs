@@ -0,0 +1,190 @@
+//! Echo server embedded in an `axum` app, without Bevy.
+//!
+//! The server runs on the same tokio runtime as the HTTP service, which
+//! exposes:
+//! * `GET /health` - `200 OK` while the server is open, otherwise `503 Service
+//!   Unavailable`
+//! * `GET /metrics` - server metrics in the Prometheus text format
+//!
+//! Press Ctrl+C to drain the server and shut down.
+
+use std::{
+    convert::Infallible,
+    fs,
+    string::FromUtf8Error,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use aeronet::{
+    ChannelKey, OnChannel, TransportProtocol, TransportServer, TryFromBytes, TryIntoBytes,
+};
+use aeronet_wt_native::{
+    Identity, ServerEvent, ServerHandle, WebTransportProtocol, WebTransportServer,
+};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use futures::StreamExt;
+use tokio::{net::TcpListener, runtime::Handle, signal};
+use wtransport::ServerConfig;
+
+// protocol
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChannelKey)]
+#[channel_kind(Unreliable)]
+struct AppChannel;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, OnChannel)]
+#[channel_type(AppChannel)]
+#[on_channel(AppChannel)]
+struct AppMessage(String);
+
+impl<T> From<T> for AppMessage
+where
+    T: Into<String>,
+{
+    fn from(value: T) -> Self {
+        Self(value.into())
+    }
+}
+
+impl TryIntoBytes for AppMessage {
+    type Output<'a> = &'a [u8];
+
+    type Error = Infallible;
+
+    fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+        Ok(self.0.as_bytes())
+    }
+}
+
+impl TryFromBytes for AppMessage {
+    type Error = FromUtf8Error;
+
+    fn try_from_bytes(buf: &[u8]) -> Result<Self, Self::Error> {
+        String::from_utf8(buf.to_owned()).map(AppMessage)
+    }
+}
+
+struct AppProtocol;
+
+impl TransportProtocol for AppProtocol {
+    type C2S = AppMessage;
+    type S2C = AppMessage;
+}
+
+impl WebTransportProtocol for AppProtocol {
+    type Channel = AppChannel;
+}
+
+// metrics
+
+/// Server state shared with the HTTP handlers.
+#[derive(Debug, Default)]
+struct Metrics {
+    open: AtomicBool,
+    clients: AtomicUsize,
+    msgs_recv: AtomicU64,
+}
+
+async fn health(State(metrics): State<Arc<Metrics>>) -> StatusCode {
+    if metrics.open.load(Ordering::Relaxed) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+async fn metrics(State(metrics): State<Arc<Metrics>>) -> String {
+    format!(
+        "aeronet_server_open {}\naeronet_server_clients {}\naeronet_server_msgs_recv_total {}\n",
+        u8::from(metrics.open.load(Ordering::Relaxed)),
+        metrics.clients.load(Ordering::Relaxed),
+        metrics.msgs_recv.load(Ordering::Relaxed),
+    )
+}
+
+// logic
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cert = Identity::from_pem(
+        &fs::read("./aeronet_wt_native/examples/cert.pem")?,
+        &fs::read("./aeronet_wt_native/examples/key.pem")?,
+    )?;
+
+    let config = ServerConfig::builder()
+        .with_bind_default(25565)
+        .with_certificate(cert.into())
+        .keep_alive_interval(Some(Duration::from_secs(5)))
+        .build();
+
+    let mut server =
+        ServerHandle::<AppProtocol>::opening(&Handle::current(), config, Duration::from_millis(5));
+
+    let metrics = Arc::new(Metrics::default());
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(self::metrics))
+        .with_state(metrics.clone());
+    let listener = TcpListener::bind("0.0.0.0:8080").await?;
+    let http = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        let event = tokio::select! {
+            event = server.next() => event,
+            _ = &mut ctrl_c => break,
+        };
+        let Some(event) = event else {
+            break;
+        };
+        on_event(&mut server, &metrics, event);
+    }
+
+    println!("Shutting down");
+    metrics.open.store(false, Ordering::Relaxed);
+    server.shutdown(Duration::from_secs(5)).await?;
+    http.abort();
+    Ok(())
+}
+
+fn on_event(
+    server: &mut WebTransportServer<AppProtocol>,
+    metrics: &Metrics,
+    event: ServerEvent<AppProtocol>,
+) {
+    match event {
+        ServerEvent::Opened => {
+            println!("Opened server for connections");
+            metrics.open.store(true, Ordering::Relaxed);
+        }
+        ServerEvent::Connected { client } => println!("{client:?} connected"),
+        ServerEvent::Recv { client, msg } => {
+            metrics.msgs_recv.fetch_add(1, Ordering::Relaxed);
+            let msg = format!("You sent: {}", msg.0);
+            if let Err(err) = server.send(client, msg) {
+                println!(
+                    "Failed to send message to {client:?}: {:#}",
+                    aeronet::error::as_pretty(&err)
+                );
+            }
+        }
+        ServerEvent::Disconnected { client, cause } => println!(
+            "{client:?} disconnected: {:#}",
+            aeronet::error::as_pretty(&cause)
+        ),
+        ServerEvent::Closed { cause } => {
+            println!("Server closed: {:#}", aeronet::error::as_pretty(&cause));
+            metrics.open.store(false, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+    metrics
+        .clients
+        .store(server.connected_clients().count(), Ordering::Relaxed);
+}
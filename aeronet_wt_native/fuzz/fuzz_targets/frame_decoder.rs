@@ -0,0 +1,43 @@
+//! Pushes arbitrary bytes into a `FrameDecoder` in arbitrary chunks, as they
+//! could be read from a stream, and checks that the frames taken out are the
+//! same as when parsing all of the bytes at once.
+
+#![no_main]
+
+use aeronet_wt_native::wire::{self, FrameDecoder};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (Vec<u8>, Vec<u8>)| {
+    let (chunk_lens, data) = input;
+
+    let mut expected = Vec::new();
+    let mut rest = data.as_slice();
+    while let Some((payload, next)) = wire::parse_frame(rest) {
+        expected.push(payload.to_vec());
+        rest = next;
+    }
+
+    // every frame which fits in the input is accepted
+    let mut decoder = FrameDecoder::with_max_len(usize::MAX);
+    let mut frames = Vec::new();
+    let mut chunk_lens = chunk_lens
+        .iter()
+        .map(|&len| usize::from(len).max(1))
+        .cycle();
+    let mut remaining = data.as_slice();
+    while !remaining.is_empty() {
+        let len = chunk_lens
+            .next()
+            .unwrap_or(remaining.len())
+            .min(remaining.len());
+        let (chunk, next) = remaining.split_at(len);
+        decoder.push(chunk);
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            frames.push(frame);
+        }
+        remaining = next;
+    }
+
+    assert_eq!(expected, frames);
+    assert_eq!(rest.len(), decoder.buffered());
+});
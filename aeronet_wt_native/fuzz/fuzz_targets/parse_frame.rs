@@ -0,0 +1,19 @@
+//! Parses arbitrary bytes as a sequence of stream frames.
+
+#![no_main]
+
+use aeronet_wt_native::wire::{self, FRAME_HEADER_LEN};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = data;
+    while let Some((payload, rest)) = wire::parse_frame(buf) {
+        let frame_len = buf.len() - rest.len();
+        assert_eq!(FRAME_HEADER_LEN + payload.len(), frame_len);
+        assert_eq!(
+            Some(&buf[..frame_len]),
+            wire::encode_frame(payload).as_deref()
+        );
+        buf = rest;
+    }
+});
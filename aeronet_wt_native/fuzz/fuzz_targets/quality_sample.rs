@@ -0,0 +1,11 @@
+//! Decodes arbitrary bytes as a quality sample read from the report stream.
+
+#![no_main]
+
+use aeronet_wt_native::wire::{QualitySample, QUALITY_SAMPLE_LEN};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|buf: [u8; QUALITY_SAMPLE_LEN]| {
+    let sample = QualitySample::from_bytes(&buf);
+    assert_eq!(buf, sample.to_bytes());
+});
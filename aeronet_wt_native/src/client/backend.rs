@@ -1,44 +1,109 @@
-use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
-use tokio::sync::{mpsc, oneshot};
+use std::{sync::Arc, time::Duration};
+
+use aeronet::{Features, OnChannel, TryFromBytes, TryIntoBytes};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
 use tracing::debug;
 use wtransport::{endpoint::endpoint_side, ClientConfig, Connection, Endpoint};
 
 use crate::{
-    shared::{self, ChannelsState},
-    EndpointInfo, WebTransportProtocol,
+    security::LaneCipher,
+    shared::{
+        self, ChannelsState, FeatureLink, LaneEvent, QualityLink, QualityReceiver, SharedCounters,
+    },
+    ClientEvent, EndpointInfo, RecvBufferCaps, WebTransportProtocol,
 };
 
 use super::{ConnectedClient, ConnectedClientResult, WebTransportError};
 
 pub(super) async fn start<P>(
+    generation: u64,
     config: ClientConfig,
-    url: String,
+    urls: Vec<String>,
+    attempt_timeout: Option<Duration>,
+    cipher: Option<Arc<LaneCipher>>,
+    features: Features,
     send_connected: oneshot::Sender<ConnectedClientResult<P>>,
+    send_attempt: mpsc::UnboundedSender<ClientEvent<P>>,
 ) where
     P: WebTransportProtocol,
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
-    let (endpoint, conn, channels) = match connect::<P>(config, url).await {
-        Ok(t) => t,
+    debug!("Creating endpoint");
+    let endpoint = match Endpoint::client(config) {
+        Ok(endpoint) => endpoint,
         Err(err) => {
-            debug!("Failed to connect");
-            let _ = send_connected.send(Err(err));
+            let _ = send_connected.send(Err(WebTransportError::Endpoint(err)));
             return;
         }
     };
 
+    let counters = shared::counters::<P::Channel>(&RecvBufferCaps::default());
+    let (send_lane_event, recv_lane_events) = mpsc::unbounded_channel();
+    let mut urls = urls.into_iter().peekable();
+    let (conn, channels) = loop {
+        let Some(url) = urls.next() else {
+            let _ = send_connected.send(Err(WebTransportError::NoUrls));
+            return;
+        };
+
+        let _ = send_attempt.send(ClientEvent::ConnectAttempt {
+            url: url.clone(),
+            generation,
+        });
+        let attempt = connect::<P>(&endpoint, &url, &counters, &send_lane_event, cipher.clone());
+        let result = match attempt_timeout {
+            Some(timeout) => time::timeout(timeout, attempt)
+                .await
+                .unwrap_or(Err(WebTransportError::ConnectTimeout(timeout))),
+            None => attempt.await,
+        };
+
+        match result {
+            Ok(t) => break t,
+            // the cause of the last attempt is reported as the disconnect cause
+            Err(cause) if urls.peek().is_some() => {
+                debug!("Failed to connect to {url}, trying next URL");
+                let _ = send_attempt.send(ClientEvent::ConnectAttemptFailed {
+                    url,
+                    cause,
+                    generation,
+                });
+            }
+            Err(cause) => {
+                debug!("Failed to connect");
+                let _ = send_connected.send(Err(cause));
+                return;
+            }
+        }
+    };
+
     let (send_c2s, recv_c2s) = mpsc::unbounded_channel();
     let (send_s2c, recv_s2c) = mpsc::unbounded_channel();
+    let replace_c2s = shared::replace_queue();
     let (send_info, recv_info) = mpsc::unbounded_channel();
+    let (send_quality, recv_quality) = mpsc::unbounded_channel();
+    let (send_migrate, recv_migrate) = mpsc::unbounded_channel();
     let (send_err, recv_err) = oneshot::channel();
     let connected = ConnectedClient::<P> {
+        generation,
         local_addr: endpoint.local_addr(),
         info: EndpointInfo::from_connection(&conn),
         recv_info,
         recv_s2c,
+        recv_lane_events,
         send_c2s,
+        replace_c2s: replace_c2s.clone(),
         recv_err,
+        counters: counters.clone(),
+        last_lane_stats: None,
+        last_recv: None,
+        recv_quality,
+        quality: QualityReceiver::default(),
+        send_migrate,
     };
     if send_connected.send(Ok(connected)).is_err() {
         debug!("Frontend closed");
@@ -47,7 +112,16 @@ pub(super) async fn start<P>(
 
     debug!("Starting connection loop");
     if let Err(err) = shared::handle_connection::<P, P::C2S, P::S2C>(
-        conn, channels, send_info, send_s2c, recv_c2s,
+        conn,
+        channels,
+        send_info,
+        send_s2c,
+        recv_c2s,
+        replace_c2s,
+        counters,
+        QualityLink::Recv(send_quality),
+        FeatureLink::Offer(features),
+        recv_migrate,
     )
     .await
     {
@@ -59,32 +133,31 @@ pub(super) async fn start<P>(
 }
 
 async fn connect<P>(
-    config: ClientConfig,
-    url: String,
-) -> Result<
-    (
-        Endpoint<endpoint_side::Client>,
-        Connection,
-        ChannelsState<P, P::C2S, P::S2C>,
-    ),
-    WebTransportError<P>,
->
+    endpoint: &Endpoint<endpoint_side::Client>,
+    url: &str,
+    counters: &SharedCounters,
+    send_lane_event: &mpsc::UnboundedSender<LaneEvent<P::Channel>>,
+    cipher: Option<Arc<LaneCipher>>,
+) -> Result<(Connection, ChannelsState<P, P::C2S, P::S2C>), WebTransportError<P>>
 where
     P: WebTransportProtocol,
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
-    debug!("Creating endpoint for {url}");
-    let endpoint = Endpoint::client(config).map_err(WebTransportError::Endpoint)?;
-
-    debug!("Connecting");
+    debug!("Connecting to {url}");
     let conn = endpoint
-        .connect(url.clone())
+        .connect(url)
         .await
         .map_err(WebTransportError::Connect)?;
 
     debug!("Establishing channels");
-    let channels = shared::establish_channels::<P, P::C2S, P::S2C, false>(&conn).await?;
+    let channels = shared::establish_channels::<P, P::C2S, P::S2C, false>(
+        &conn,
+        counters,
+        send_lane_event,
+        cipher,
+    )
+    .await?;
 
-    Ok((endpoint, conn, channels))
+    Ok((conn, channels))
 }
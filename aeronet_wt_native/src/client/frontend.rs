@@ -1,10 +1,27 @@
-use std::{future::Future, task::Poll};
+use std::{
+    future::Future,
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant},
+};
 
-use aeronet::{OnChannel, TransportClient, TryFromBytes, TryIntoBytes};
-use tokio::sync::oneshot;
+use aeronet::{
+    ChannelKey, ChannelKind, Clock, Features, OnChannel, OnMessageError, SystemClock,
+    TransportClient, TryFromBytes, TryIntoBytes,
+};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
 use wtransport::ClientConfig;
 
-use crate::{ClientEvent, ClientState, EndpointInfo, WebTransportClient, WebTransportProtocol};
+use crate::{
+    security::LaneCipher,
+    shared::{self, CodecHook, Incoming, LaneEvent, MigrateLane},
+    ClientEvent, ClientState, EndpointInfo, LaneSecurityConfig, WebTransportClient,
+    WebTransportProtocol,
+};
+
+#[cfg(feature = "codec-timing")]
+use crate::CodecTimings;
 
 use super::{
     backend, ConnectedClient, ConnectedClientResult, ConnectingClient, State, WebTransportError,
@@ -27,6 +44,17 @@ where
     pub fn disconnected() -> Self {
         Self {
             state: State::Disconnected,
+            generation: 0,
+            clock: Arc::new(SystemClock),
+            lane_stats_interval: None,
+            recv_progress_min_len: None,
+            resume_threshold: None,
+            drop_stale_on_resume: false,
+            on_serialize_error: OnMessageError::EmitEventOnly,
+            on_deserialize_error: OnMessageError::DisconnectClient,
+            lane_security: None,
+            features: Features::NONE,
+            codec: CodecHook::default(),
         }
     }
 
@@ -43,10 +71,22 @@ where
         config: ClientConfig,
         url: impl Into<String>,
     ) -> (Self, impl Future<Output = ()> + Send) {
-        let (client, backend) = ConnectingClient::new(config, url);
+        let (client, backend) =
+            ConnectingClient::new(1, config, vec![url.into()], None, None, Features::NONE);
         (
             Self {
                 state: State::Connecting(client),
+                generation: 1,
+                clock: Arc::new(SystemClock),
+                lane_stats_interval: None,
+                recv_progress_min_len: None,
+                resume_threshold: None,
+                drop_stale_on_resume: false,
+                on_serialize_error: OnMessageError::EmitEventOnly,
+                on_deserialize_error: OnMessageError::DisconnectClient,
+                lane_security: None,
+                features: Features::NONE,
+                codec: CodecHook::default(),
             },
             backend,
         )
@@ -66,7 +106,67 @@ where
     ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
         match self.state {
             State::Disconnected => {
-                let (client, backend) = ConnectingClient::new(config, url);
+                let cipher = self.cipher();
+                let generation = self.next_generation();
+                let (client, backend) = ConnectingClient::new(
+                    generation,
+                    config,
+                    vec![url.into()],
+                    None,
+                    cipher,
+                    self.features,
+                );
+                self.state = State::Connecting(client);
+                Ok(backend)
+            }
+            State::Connecting(_) | State::Connected(_) => Err(WebTransportError::BackendOpen),
+        }
+    }
+
+    /// Attempts to start connecting this client to the first server out of a
+    /// list of URLs which accepts the connection.
+    ///
+    /// The URLs are tried one after another in the given order, e.g. a nearby
+    /// regional server followed by a global fallback. If `attempt_timeout` is
+    /// set, an attempt which has not connected within that time fails with
+    /// [`WebTransportError::ConnectTimeout`], and the next URL is tried.
+    ///
+    /// A [`ClientEvent::ConnectAttempt`] is raised when each attempt starts,
+    /// and a [`ClientEvent::ConnectAttemptFailed`] when an attempt fails and
+    /// the client fails over to the next URL. If the attempt on the last URL
+    /// fails, a [`ClientEvent::Disconnected`] is raised with its cause.
+    ///
+    /// See [`WebTransportClient::connect`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this client is already connecting or is connected to a
+    /// server, or if `urls` is empty.
+    ///
+    /// [`WebTransportError::ConnectTimeout`]: crate::WebTransportError::ConnectTimeout
+    pub fn connect_failover(
+        &mut self,
+        config: ClientConfig,
+        urls: impl IntoIterator<Item = impl Into<String>>,
+        attempt_timeout: Option<Duration>,
+    ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
+        match self.state {
+            State::Disconnected => {
+                let urls = urls.into_iter().map(Into::into).collect::<Vec<_>>();
+                if urls.is_empty() {
+                    return Err(WebTransportError::NoUrls);
+                }
+
+                let cipher = self.cipher();
+                let generation = self.next_generation();
+                let (client, backend) = ConnectingClient::new(
+                    generation,
+                    config,
+                    urls,
+                    attempt_timeout,
+                    cipher,
+                    self.features,
+                );
                 self.state = State::Connecting(client);
                 Ok(backend)
             }
@@ -74,6 +174,95 @@ where
         }
     }
 
+    /// Gets the lane security configuration used when connecting.
+    #[must_use]
+    pub fn lane_security(&self) -> Option<&LaneSecurityConfig<P::Channel>> {
+        self.lane_security.as_ref()
+    }
+
+    /// Sets the lane security configuration used when connecting.
+    ///
+    /// This takes effect the next time this client connects using
+    /// [`WebTransportClient::connect`] or
+    /// [`WebTransportClient::connect_failover`], and must match the
+    /// configuration of the server. By default, all lanes are plain.
+    ///
+    /// See [`LaneSecurityConfig`].
+    pub fn set_lane_security(&mut self, security: Option<LaneSecurityConfig<P::Channel>>) {
+        self.lane_security = security;
+    }
+
+    /// Gets the optional protocol features that this client advertises when
+    /// connecting.
+    #[must_use]
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    /// Sets the optional protocol features that this client advertises when
+    /// connecting.
+    ///
+    /// This takes effect the next time this client connects using
+    /// [`WebTransportClient::connect`] or
+    /// [`WebTransportClient::connect_failover`]. Once connected, the features
+    /// that both this client and the server support are available in
+    /// [`EndpointInfo::features`]. By default, no features are advertised.
+    ///
+    /// See [`Features`].
+    pub fn set_features(&mut self, features: Features) {
+        self.features = features;
+    }
+
+    /// Gets the timings of how long messages take to serialize and
+    /// deserialize on this client, if they are being recorded.
+    ///
+    /// See [`CodecTimings`].
+    #[cfg(feature = "codec-timing")]
+    #[must_use]
+    pub fn codec_timings(&self) -> Option<&Arc<CodecTimings<P::C2S, P::S2C>>> {
+        self.codec.timings.as_ref()
+    }
+
+    /// Starts or stops recording how long messages take to serialize and
+    /// deserialize on this client.
+    ///
+    /// See [`CodecTimings`].
+    #[cfg(feature = "codec-timing")]
+    pub fn set_codec_timings(&mut self, timings: Option<Arc<CodecTimings<P::C2S, P::S2C>>>) {
+        self.codec.timings = timings;
+    }
+
+    /// Gets the generation of the current or last connection attempt of this
+    /// client.
+    ///
+    /// Every call to [`WebTransportClient::connect`] or
+    /// [`WebTransportClient::connect_failover`] starts a new generation, and
+    /// the events which start or end a connection carry the generation that
+    /// they belong to. Compare it to this value to tell apart events of a
+    /// previous connection which user code has not handled yet, e.g. a
+    /// [`ClientEvent::Disconnected`] buffered by the app which arrives after
+    /// the client has already reconnected. A client which has never connected
+    /// has generation 0.
+    ///
+    /// The client itself never raises events of a previous generation, since
+    /// the channels to the backend of a connection are dropped as soon as it
+    /// is disconnected, even if that backend task is still shutting down.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn next_generation(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    fn cipher(&self) -> Option<Arc<LaneCipher>> {
+        self.lane_security
+            .as_ref()
+            .map(|security| Arc::new(security.cipher(false)))
+    }
+
     /// Gets the current state of the client.
     #[must_use]
     pub fn state(&self) -> ClientState {
@@ -83,6 +272,321 @@ where
             State::Connected(_) => ClientState::Connected,
         }
     }
+
+    /// Gets the interval at which [`ClientEvent::LaneStats`] events are raised.
+    ///
+    /// If this is [`None`], no lane stats events are raised.
+    #[must_use]
+    pub fn lane_stats_interval(&self) -> Option<Duration> {
+        self.lane_stats_interval
+    }
+
+    /// Sets the interval at which [`ClientEvent::LaneStats`] events are raised
+    /// for each lane of the connection.
+    ///
+    /// Pass [`None`] to stop raising these events. By default, no lane stats
+    /// events are raised.
+    pub fn set_lane_stats_interval(&mut self, interval: Option<Duration>) {
+        self.lane_stats_interval = interval;
+    }
+
+    /// Gets the minimum length in bytes of a message for which
+    /// [`ClientEvent::RecvProgress`] events are raised while it is received.
+    ///
+    /// If this is [`None`], no progress events are raised.
+    #[must_use]
+    pub fn recv_progress_min_len(&self) -> Option<usize> {
+        self.recv_progress_min_len
+    }
+
+    /// Sets the minimum length in bytes of a message for which
+    /// [`ClientEvent::RecvProgress`] events are raised while it is received.
+    ///
+    /// Pass [`None`] to stop raising these events. By default, no progress
+    /// events are raised.
+    pub fn set_recv_progress_min_len(&mut self, min_len: Option<usize>) {
+        self.recv_progress_min_len = min_len;
+    }
+
+    /// Gets the clock that this client reads the current time from.
+    #[must_use]
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Sets the clock that this client reads the current time from.
+    ///
+    /// This drives the [`ClientEvent::LaneStats`] interval and the
+    /// [resume threshold](WebTransportClient::set_resume_threshold).
+    ///
+    /// See [`WebTransportServer::set_clock`](crate::WebTransportServer::set_clock).
+    pub fn set_clock(&mut self, clock: impl Clock) {
+        self.clock = Arc::new(clock);
+    }
+
+    /// Gets the minimum time between two polls of this client after which a
+    /// [`ClientEvent::Resumed`] is raised.
+    ///
+    /// If this is [`None`], no resumed events are raised.
+    #[must_use]
+    pub fn resume_threshold(&self) -> Option<Duration> {
+        self.resume_threshold
+    }
+
+    /// Sets the minimum time between two polls of this client after which a
+    /// [`ClientEvent::Resumed`] is raised.
+    ///
+    /// On platforms where an app can be suspended, such as a browser tab in
+    /// the background or a mobile app, the client may not be polled for a long
+    /// time. Use this event to detect when the app is running again, e.g. to
+    /// request a full state update from the server.
+    ///
+    /// Pass [`None`] to stop raising these events. By default, no resumed
+    /// events are raised.
+    pub fn set_resume_threshold(&mut self, threshold: Option<Duration>) {
+        self.resume_threshold = threshold;
+    }
+
+    /// Gets whether messages received on unreliable lanes are discarded when
+    /// this client resumes.
+    #[must_use]
+    pub fn drop_stale_on_resume(&self) -> bool {
+        self.drop_stale_on_resume
+    }
+
+    /// Sets whether messages received on unreliable lanes are discarded when
+    /// this client resumes after not being polled for longer than the
+    /// [resume threshold](WebTransportClient::set_resume_threshold).
+    ///
+    /// Unreliable lanes are typically used for frequently sent, short-lived
+    /// data such as state snapshots. After a long gap, delivering every
+    /// snapshot received during the gap at once is wasteful, since only the
+    /// newest one is relevant. Messages on reliable lanes are always
+    /// delivered.
+    ///
+    /// By default, this is `false`.
+    pub fn set_drop_stale_on_resume(&mut self, drop_stale: bool) {
+        self.drop_stale_on_resume = drop_stale;
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// the server.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// the server.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from the server fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from the server fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ClientEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.on_deserialize_error = policy;
+    }
+
+    /// Sends a message to the server, dropping it if the backend has not sent
+    /// it within `ttl`.
+    ///
+    /// See [`WebTransportServer::send_with_ttl`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportClient::send`].
+    ///
+    /// [`WebTransportServer::send_with_ttl`]: crate::WebTransportServer::send_with_ttl
+    pub fn send_with_ttl(
+        &mut self,
+        msg: impl Into<P::C2S>,
+        ttl: Duration,
+    ) -> Result<(), WebTransportError<P>> {
+        let result = match &mut self.state {
+            State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
+            State::Connected(client) => client.send(msg, Some(ttl), &self.codec),
+        };
+        self.apply_serialize_policy(result)
+    }
+
+    /// Applies the serialize error policy to the result of sending a message.
+    fn apply_serialize_policy(
+        &mut self,
+        result: Result<(), WebTransportError<P>>,
+    ) -> Result<(), WebTransportError<P>> {
+        match result {
+            Err(err) if shared::is_serialize_error(&err) => match self.on_serialize_error {
+                OnMessageError::DisconnectClient => {
+                    self.state = State::Disconnected;
+                    Err(err)
+                }
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message to server: {err:#}");
+                    Ok(())
+                }
+                OnMessageError::EmitEventOnly => Err(err),
+            },
+            result => result,
+        }
+    }
+
+    /// Sends a message to the server, replacing any message previously sent in
+    /// the same slot which has not been sent yet.
+    ///
+    /// See [`WebTransportServer::send_replaceable`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportClient::send`].
+    ///
+    /// [`WebTransportServer::send_replaceable`]: crate::WebTransportServer::send_replaceable
+    pub fn send_replaceable(
+        &mut self,
+        slot: u64,
+        msg: impl Into<P::C2S>,
+    ) -> Result<(), WebTransportError<P>> {
+        let result = match &self.state {
+            State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
+            State::Connected(client) => client.send_replaceable(slot, msg, &self.codec),
+        };
+        self.apply_serialize_policy(result)
+    }
+
+    /// Gets the number of messages sent on a lane which are waiting in the
+    /// send queue.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    #[must_use]
+    pub fn queued_msgs(&self, lane: P::Channel) -> Option<usize> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => None,
+            State::Connected(client) => Some(client.counters.lane_queued(lane.index())),
+        }
+    }
+
+    /// Gets the total size in bytes of the serialized messages sent on a lane
+    /// which are waiting in the send queue.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    #[must_use]
+    pub fn queued_bytes(&self, lane: P::Channel) -> Option<usize> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => None,
+            State::Connected(client) => Some(client.counters.lane_queued_bytes(lane.index())),
+        }
+    }
+
+    /// Discards all messages sent on a lane which have not been sent by the
+    /// backend yet.
+    ///
+    /// This can be used to reset cleanly, e.g. to throw away all stale input
+    /// when the game is paused. Messages sent after this call are not
+    /// affected. The discarded messages are counted in
+    /// [`LaneStats::dropped`], and keep counting towards
+    /// [`WebTransportClient::queued_msgs`] until the backend reaches them in
+    /// its queue.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the client is not connected.
+    ///
+    /// [`LaneStats::dropped`]: crate::LaneStats::dropped
+    pub fn clear_lane(&mut self, lane: P::Channel) -> Result<(), WebTransportError<P>> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
+            State::Connected(client) => {
+                let index = lane.index();
+                client.counters.lanes[index].clear();
+                client.replace_c2s.clear_lane(&client.counters.lanes, index);
+                Ok(())
+            }
+        }
+    }
+
+    /// Asks the server to send the messages of `channel` on the lane of `lane`
+    /// instead, in both directions, without reconnecting.
+    ///
+    /// This can be used to e.g. move chat onto an unreliable lane while
+    /// bandwidth is scarce, and back onto its own lane once it recovers, by
+    /// migrating it onto itself. The messages of `channel` stay on their
+    /// current lane until the server has agreed, after which
+    /// [`ClientEvent::LaneMigrated`] is raised. If the server does not agree,
+    /// [`ClientEvent::LaneMigrationRejected`] is raised instead.
+    ///
+    /// Messages are assigned to a lane when the backend sends them, so there
+    /// is no ordering between messages sent before and after a migration, and
+    /// messages moved onto an unreliable lane lose their delivery guarantee.
+    /// [`LaneStats`] keep counting the messages under their own channel.
+    ///
+    /// See [`wire`](crate::wire) for how the migration is agreed on.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the client is not connected, or if the server has not
+    /// negotiated [`Features::LANE_MIGRATION`], e.g. because features have not
+    /// been exchanged yet.
+    ///
+    /// [`LaneStats`]: crate::LaneStats
+    pub fn migrate_lane(
+        &mut self,
+        channel: P::Channel,
+        lane: P::Channel,
+    ) -> Result<(), WebTransportError<P>> {
+        match &self.state {
+            State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
+            State::Connected(client) => {
+                let features = client.counters.features();
+                if !features.contains(Features::LANE_MIGRATION) {
+                    return Err(WebTransportError::LaneMigrationNotNegotiated);
+                }
+                client
+                    .send_migrate
+                    .send(MigrateLane {
+                        channel: channel.index(),
+                        lane: lane.index(),
+                    })
+                    .map_err(|_| WebTransportError::BackendClosed)
+            }
+        }
+    }
+
+    /// Starts a new statistics epoch, and returns its [`EpochStats::epoch`].
+    ///
+    /// This resets the totals in [`EndpointInfo::stats`], and the values of
+    /// [`ClientEvent::LaneStats`] which are measured over a period. The lane
+    /// stats interval also restarts from the next poll.
+    ///
+    /// See [`WebTransportServer::reset_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the client is not connected.
+    ///
+    /// [`EpochStats::epoch`]: crate::EpochStats::epoch
+    /// [`WebTransportServer::reset_stats`]: crate::WebTransportServer::reset_stats
+    pub fn reset_stats(&mut self) -> Result<u64, WebTransportError<P>> {
+        match &mut self.state {
+            State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
+            State::Connected(client) => {
+                client.last_lane_stats = None;
+                Ok(client.counters.reset_epoch())
+            }
+        }
+    }
 }
 
 impl<P> TransportClient<P> for WebTransportClient<P>
@@ -91,6 +595,8 @@ where
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
+    const TRANSPORT_NAME: &'static str = "webtransport";
+
     type Error = WebTransportError<P>;
 
     type ConnectionInfo = EndpointInfo;
@@ -105,34 +611,61 @@ where
     }
 
     fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), Self::Error> {
-        match &mut self.state {
+        let result = match &mut self.state {
             State::Disconnected | State::Connecting(_) => Err(WebTransportError::BackendClosed),
-            State::Connected(client) => client.send(msg),
-        }
+            State::Connected(client) => client.send(msg, None, &self.codec),
+        };
+        self.apply_serialize_policy(result)
     }
 
     fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let now = self.clock.now();
+        let lane_stats_interval = self.lane_stats_interval;
+        let recv_progress_min_len = self.recv_progress_min_len;
+        let resume = ResumeConfig {
+            threshold: self.resume_threshold,
+            drop_stale: self.drop_stale_on_resume,
+        };
+        let on_deserialize_error = self.on_deserialize_error;
         match &mut self.state {
             State::Disconnected => vec![].into_iter(),
-            State::Connecting(client) => match client.poll() {
-                Poll::Pending => vec![].into_iter(),
-                Poll::Ready(Ok(client)) => {
-                    self.state = State::Connected(client);
-                    vec![ClientEvent::Connected].into_iter()
+            State::Connecting(client) => {
+                let generation = client.generation;
+                let mut events = Vec::new();
+                while let Ok(event) = client.recv_attempts.try_recv() {
+                    events.push(event);
                 }
-                Poll::Ready(Err(cause)) => {
-                    self.state = State::Disconnected;
-                    vec![ClientEvent::Disconnected { cause }].into_iter()
+                match client.poll() {
+                    Poll::Pending => {}
+                    Poll::Ready(Ok(client)) => {
+                        self.state = State::Connected(client);
+                        events.push(ClientEvent::Connected { generation });
+                    }
+                    Poll::Ready(Err(cause)) => {
+                        self.state = State::Disconnected;
+                        events.push(ClientEvent::Disconnected { cause, generation });
+                    }
                 }
-            },
-            State::Connected(server) => match server.recv() {
-                (events, Ok(())) => events.into_iter(),
-                (mut events, Err(cause)) => {
-                    self.state = State::Disconnected;
-                    events.push(ClientEvent::Disconnected { cause });
-                    events.into_iter()
+                events.into_iter()
+            }
+            State::Connected(server) => {
+                let generation = server.generation;
+                match server.recv(
+                    now,
+                    lane_stats_interval,
+                    recv_progress_min_len,
+                    resume,
+                    on_deserialize_error,
+                    &self.codec,
+                ) {
+                    (events, Ok(())) => events.into_iter(),
+                    (mut events, Err(cause)) => {
+                        self.state = State::Disconnected;
+                        events.push(ClientEvent::Disconnected { cause, generation });
+                        events.into_iter()
+                    }
                 }
-            },
+            }
         }
     }
 
@@ -147,6 +680,12 @@ where
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct ResumeConfig {
+    threshold: Option<Duration>,
+    drop_stale: bool,
+}
+
 impl<P> ConnectingClient<P>
 where
     P: WebTransportProtocol,
@@ -154,14 +693,31 @@ where
     P::S2C: TryFromBytes,
 {
     fn new(
+        generation: u64,
         config: ClientConfig,
-        url: impl Into<String>,
+        urls: Vec<String>,
+        attempt_timeout: Option<Duration>,
+        cipher: Option<Arc<LaneCipher>>,
+        features: Features,
     ) -> (Self, impl Future<Output = ()> + Send) {
         let (send_connected, recv_connected) = oneshot::channel();
-        let url = url.into();
+        let (send_attempt, recv_attempts) = mpsc::unbounded_channel();
         (
-            Self { recv_connected },
-            backend::start::<P>(config, url, send_connected),
+            Self {
+                generation,
+                recv_connected,
+                recv_attempts,
+            },
+            backend::start::<P>(
+                generation,
+                config,
+                urls,
+                attempt_timeout,
+                cipher,
+                features,
+                send_connected,
+                send_attempt,
+            ),
         )
     }
 
@@ -183,25 +739,146 @@ where
     P::S2C: TryFromBytes,
 {
     fn connection_info(&self) -> EndpointInfo {
-        self.info.clone()
+        EndpointInfo {
+            features: self.counters.features(),
+            stats: self.counters.epoch_stats(),
+            ..self.info.clone()
+        }
+    }
+
+    fn send(
+        &mut self,
+        msg: impl Into<P::C2S>,
+        ttl: Option<Duration>,
+        codec: &CodecHook<P::C2S, P::S2C>,
+    ) -> Result<(), WebTransportError<P>> {
+        let mut msg = codec.serialize(&msg.into())?;
+        if let Some(ttl) = ttl {
+            msg.expire_after(ttl);
+        }
+        if shared::queue(&self.send_c2s, &self.counters.lanes, msg) {
+            Ok(())
+        } else {
+            Err(WebTransportError::BackendClosed)
+        }
     }
 
-    fn send(&mut self, msg: impl Into<P::C2S>) -> Result<(), WebTransportError<P>> {
+    fn send_replaceable(
+        &self,
+        slot: u64,
+        msg: impl Into<P::C2S>,
+        codec: &CodecHook<P::C2S, P::S2C>,
+    ) -> Result<(), WebTransportError<P>> {
+        if self.send_c2s.is_closed() {
+            return Err(WebTransportError::BackendClosed);
+        }
+
         let msg = msg.into();
-        self.send_c2s
-            .send(msg)
-            .map_err(|_| WebTransportError::BackendClosed)
+        let ordered = msg.channel().kind() == ChannelKind::ReliableOrdered;
+        let msg = codec.serialize(&msg)?;
+        if ordered {
+            // the replace queue could overtake earlier messages on this lane
+            return if shared::queue(&self.send_c2s, &self.counters.lanes, msg) {
+                Ok(())
+            } else {
+                Err(WebTransportError::BackendClosed)
+            };
+        }
+        self.replace_c2s.push(&self.counters.lanes, slot, msg);
+        Ok(())
     }
 
-    fn recv(&mut self) -> (Vec<ClientEvent<P>>, Result<(), WebTransportError<P>>) {
+    fn recv(
+        &mut self,
+        now: Instant,
+        lane_stats_interval: Option<Duration>,
+        recv_progress_min_len: Option<usize>,
+        resume: ResumeConfig,
+        on_deserialize_error: OnMessageError,
+        codec: &CodecHook<P::C2S, P::S2C>,
+    ) -> (Vec<ClientEvent<P>>, Result<(), WebTransportError<P>>) {
         let mut events = Vec::new();
 
         while let Ok(info) = self.recv_info.try_recv() {
             self.info = info;
         }
+        while let Ok(sample) = self.recv_quality.try_recv() {
+            self.quality.on_sample(&self.counters, sample);
+        }
+        self.info.quality = self.quality.report;
+
+        let gap = self
+            .last_recv
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        let resumed = resume.threshold.is_some_and(|threshold| gap >= threshold);
+
+        let mut dropped = 0;
+        while let Ok(recv) = self.recv_s2c.try_recv() {
+            codec.on_recv(&recv);
+            let Incoming {
+                msg, lane, size, ..
+            } = recv;
+            self.counters.on_recv_taken(size);
+            if resumed && resume.drop_stale && lane.is_none() {
+                dropped += 1;
+                continue;
+            }
+            let err = match msg {
+                Ok(msg) => {
+                    events.push(ClientEvent::Recv { msg });
+                    continue;
+                }
+                Err(err) => err,
+            };
+
+            let cause = shared::deserialize_error(lane, err);
+            match on_deserialize_error {
+                OnMessageError::DisconnectClient => return (events, Err(cause)),
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message from server: {cause:#}");
+                }
+                OnMessageError::EmitEventOnly => events.push(ClientEvent::MessageError { cause }),
+            }
+        }
+        while let Ok(event) = self.recv_lane_events.try_recv() {
+            events.push(match event {
+                LaneEvent::Closed(channel) => ClientEvent::StreamClosed { channel },
+                LaneEvent::ChecksumMismatch(mismatch) => ClientEvent::ChecksumMismatch { mismatch },
+                LaneEvent::Rejected(channel) => ClientEvent::MessageRejected { channel },
+                LaneEvent::Migrated { channel, lane } => {
+                    ClientEvent::LaneMigrated { channel, lane }
+                }
+                LaneEvent::MigrationRejected { channel, lane } => {
+                    ClientEvent::LaneMigrationRejected { channel, lane }
+                }
+            });
+        }
+        events.extend(
+            shared::take_recv_progress(&self.counters, recv_progress_min_len)
+                .into_iter()
+                .map(|(from, received, total)| ClientEvent::RecvProgress {
+                    from,
+                    received,
+                    total,
+                }),
+        );
+
+        if resumed {
+            events.insert(0, ClientEvent::Resumed { gap, dropped });
+        }
 
-        while let Ok(msg) = self.recv_s2c.try_recv() {
-            events.push(ClientEvent::Recv { msg });
+        if let Some(stats) = shared::take_lane_stats(
+            &self.counters,
+            lane_stats_interval,
+            &mut self.last_lane_stats,
+            now,
+        ) {
+            events.extend(
+                stats
+                    .into_iter()
+                    .map(|stats| ClientEvent::LaneStats { stats }),
+            );
         }
 
         match self.recv_err.try_recv() {
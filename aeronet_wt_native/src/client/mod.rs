@@ -1,13 +1,37 @@
 mod backend;
 mod frontend;
+mod probe;
+#[cfg(feature = "async")]
+mod stream;
 
-use std::{fmt::Debug, io, net::SocketAddr};
+pub use probe::*;
 
-use aeronet::{OnChannel, TransportClient, TransportProtocol, TryFromBytes, TryIntoBytes};
+#[cfg(feature = "async")]
+pub use stream::*;
+
+use std::{
+    fmt::Debug,
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use aeronet::{
+    Clock, Features, OnChannel, OnMessageError, SystemClock, TransportClient, TransportProtocol,
+    TryFromBytes, TryIntoBytes,
+};
 use derivative::Derivative;
 use tokio::sync::{mpsc, oneshot};
 
-use crate::{EndpointInfo, WebTransportProtocol};
+use crate::{
+    shared::{
+        CodecHook, Incoming, LaneEvent, MigrateLane, Outgoing, QualityReceiver, SharedCounters,
+        SharedReplaceQueue,
+    },
+    wire::QualitySample,
+    ChecksumMismatch, EndpointInfo, LaneSecurityConfig, LaneStats, WebTransportProtocol,
+};
 
 type WebTransportError<P> =
     crate::WebTransportError<P, <P as TransportProtocol>::C2S, <P as TransportProtocol>::S2C>;
@@ -25,9 +49,31 @@ where
     P::S2C: TryFromBytes,
 {
     state: State<P>,
+    generation: u64,
+    #[derivative(Default(value = "Arc::new(SystemClock)"))]
+    clock: Arc<dyn Clock>,
+    lane_stats_interval: Option<Duration>,
+    recv_progress_min_len: Option<usize>,
+    resume_threshold: Option<Duration>,
+    drop_stale_on_resume: bool,
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    on_serialize_error: OnMessageError,
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    on_deserialize_error: OnMessageError,
+    lane_security: Option<LaneSecurityConfig<P::Channel>>,
+    features: Features,
+    #[derivative(Debug = "ignore")]
+    codec: CodecHook<P::C2S, P::S2C>,
 }
 
 /// Event raised by a [`WebTransportClient`].
+///
+/// Events which start or end a connection carry the [generation] of the
+/// connection attempt that they belong to. All other events are only raised
+/// between the [`ClientEvent::Connected`] and [`ClientEvent::Disconnected`] of
+/// the current generation.
+///
+/// [generation]: WebTransportClient::generation
 #[derive(Derivative)]
 #[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug, P::Channel: Debug"))]
 pub enum ClientEvent<P>
@@ -36,10 +82,38 @@ where
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
+    /// This client started an attempt to connect to a server URL.
+    ///
+    /// If the client connects successfully, the URL of the last attempt is the
+    /// one that it is connected to.
+    ///
+    /// See [`WebTransportClient::connect_failover`].
+    ConnectAttempt {
+        /// The URL that the client is connecting to.
+        url: String,
+        /// The generation of the connection attempt.
+        generation: u64,
+    },
+    /// An attempt to connect to a server URL failed, and the client moved on to
+    /// the next URL.
+    ///
+    /// This is not raised for the last URL, since if that fails, the cause is
+    /// reported in [`ClientEvent::Disconnected`] instead.
+    ConnectAttemptFailed {
+        /// The URL that the client failed to connect to.
+        url: String,
+        /// The reason why the attempt failed.
+        cause: WebTransportError<P>,
+        /// The generation of the connection attempt.
+        generation: u64,
+    },
     /// This client has fully connected to a server.
     ///
     /// This is equivalent to [`aeronet::ClientEvent::Connected`].
-    Connected,
+    Connected {
+        /// The generation of the connection.
+        generation: u64,
+    },
     /// The connected server sent a message to the client.
     ///
     /// This is equivalent to [`aeronet::ClientEvent::Recv`].
@@ -47,6 +121,107 @@ where
         /// The message received.
         msg: P::S2C,
     },
+    /// Part of a large message from the server has been received on a stream
+    /// lane, but the message is not complete yet.
+    ///
+    /// This can be used to show a loading screen while a large payload, such
+    /// as the initial world state, is downloaded. This is only raised if a
+    /// minimum length has been set using
+    /// [`WebTransportClient::set_recv_progress_min_len`].
+    ///
+    /// See [`ServerEvent::RecvProgress`](crate::ServerEvent::RecvProgress).
+    RecvProgress {
+        /// The lane which the message is arriving on.
+        from: P::Channel,
+        /// Number of bytes of the message received so far.
+        received: usize,
+        /// Length of the full message in bytes.
+        total: usize,
+    },
+    /// The app did not poll this client for a long time, e.g. because the app
+    /// was suspended or moved to the background, and has now resumed.
+    ///
+    /// This is raised before any [`ClientEvent::Recv`] events for the messages
+    /// received during the gap, and is only raised if a resume threshold has
+    /// been set using [`WebTransportClient::set_resume_threshold`].
+    Resumed {
+        /// Time since this client was last polled.
+        gap: Duration,
+        /// Number of messages received on unreliable lanes during the gap
+        /// which were discarded as stale.
+        ///
+        /// See [`WebTransportClient::set_drop_stale_on_resume`].
+        dropped: usize,
+    },
+    /// Periodic statistics on a lane of the connection.
+    ///
+    /// This is only raised if a lane stats interval has been set using
+    /// [`WebTransportClient::set_lane_stats_interval`].
+    LaneStats {
+        /// The stats of the lane.
+        stats: LaneStats<P::Channel>,
+    },
+    /// The server finished sending on one of the stream lanes.
+    ///
+    /// See [`ServerEvent::StreamClosed`](crate::ServerEvent::StreamClosed).
+    StreamClosed {
+        /// The lane which was closed.
+        channel: P::Channel,
+    },
+    /// The client and the server agreed to send the messages of a channel on
+    /// another lane.
+    ///
+    /// This is raised both when the client requested the migration using
+    /// [`WebTransportClient::migrate_lane`], and when the server did.
+    ///
+    /// See [`ServerEvent::LaneMigrated`](crate::ServerEvent::LaneMigrated).
+    LaneMigrated {
+        /// The channel whose messages were moved.
+        channel: P::Channel,
+        /// The lane which the messages are now sent on.
+        lane: P::Channel,
+    },
+    /// A lane migration requested using [`WebTransportClient::migrate_lane`]
+    /// was not agreed to, and the messages of the channel stay on their
+    /// current lane.
+    ///
+    /// See
+    /// [`ServerEvent::LaneMigrationRejected`](crate::ServerEvent::LaneMigrationRejected).
+    LaneMigrationRejected {
+        /// The channel which was not migrated.
+        channel: P::Channel,
+        /// The lane which the migration requested.
+        lane: P::Channel,
+    },
+    /// A message received from the server was dropped because its checksum
+    /// did not match its contents.
+    ///
+    /// This is only raised with the `checksum` feature enabled.
+    ChecksumMismatch {
+        /// Details of the mismatched message.
+        mismatch: ChecksumMismatch<P::Channel>,
+    },
+    /// A message received from the server was dropped because it failed lane
+    /// security verification.
+    ///
+    /// This is only raised if lane security is configured using
+    /// [`WebTransportClient::set_lane_security`].
+    MessageRejected {
+        /// The lane that the message was received on, or [`None`] if it was
+        /// received as a datagram.
+        channel: Option<P::Channel>,
+    },
+    /// A message received from the server failed to deserialize, and was
+    /// dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`WebTransportClient::set_on_deserialize_error`].
+    MessageError {
+        /// The error which occurred.
+        cause: WebTransportError<P>,
+    },
     /// The client lost connection from its previous server, which could not be
     /// recovered from.
     ///
@@ -54,6 +229,8 @@ where
     Disconnected {
         /// The reason why the client lost connection.
         cause: WebTransportError<P>,
+        /// The generation of the connection which was lost.
+        generation: u64,
     },
 }
 
@@ -66,11 +243,22 @@ where
 {
     fn from(value: ClientEvent<P>) -> Self {
         match value {
-            ClientEvent::Connected => Some(aeronet::ClientEvent::Connected),
+            ClientEvent::Connected { .. } => Some(aeronet::ClientEvent::Connected),
             ClientEvent::Recv { msg } => Some(aeronet::ClientEvent::Recv { msg }),
-            ClientEvent::Disconnected { cause } => {
+            ClientEvent::Disconnected { cause, .. } => {
                 Some(aeronet::ClientEvent::Disconnected { cause })
             }
+            ClientEvent::ConnectAttempt { .. }
+            | ClientEvent::ConnectAttemptFailed { .. }
+            | ClientEvent::Resumed { .. }
+            | ClientEvent::RecvProgress { .. }
+            | ClientEvent::LaneStats { .. }
+            | ClientEvent::StreamClosed { .. }
+            | ClientEvent::LaneMigrated { .. }
+            | ClientEvent::LaneMigrationRejected { .. }
+            | ClientEvent::ChecksumMismatch { .. }
+            | ClientEvent::MessageRejected { .. }
+            | ClientEvent::MessageError { .. } => None,
         }
     }
 }
@@ -109,8 +297,11 @@ where
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
+    generation: u64,
     #[derivative(Debug = "ignore")]
     recv_connected: oneshot::Receiver<ConnectedClientResult<P>>,
+    #[derivative(Debug = "ignore")]
+    recv_attempts: mpsc::UnboundedReceiver<ClientEvent<P>>,
 }
 
 #[derive(Derivative)]
@@ -121,16 +312,30 @@ where
     P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
     P::S2C: TryFromBytes,
 {
+    generation: u64,
     local_addr: Result<SocketAddr, io::Error>,
     info: EndpointInfo,
     #[derivative(Debug = "ignore")]
     recv_info: mpsc::UnboundedReceiver<EndpointInfo>,
     #[derivative(Debug = "ignore")]
-    recv_s2c: mpsc::UnboundedReceiver<P::S2C>,
+    recv_s2c: mpsc::UnboundedReceiver<Incoming<P::S2C>>,
     #[derivative(Debug = "ignore")]
-    send_c2s: mpsc::UnboundedSender<P::C2S>,
+    recv_lane_events: mpsc::UnboundedReceiver<LaneEvent<P::Channel>>,
+    #[derivative(Debug = "ignore")]
+    send_c2s: mpsc::UnboundedSender<Outgoing>,
+    #[derivative(Debug = "ignore")]
+    replace_c2s: SharedReplaceQueue,
     #[derivative(Debug = "ignore")]
     recv_err: oneshot::Receiver<WebTransportError<P>>,
+    #[derivative(Debug = "ignore")]
+    counters: SharedCounters,
+    last_lane_stats: Option<Instant>,
+    last_recv: Option<Instant>,
+    #[derivative(Debug = "ignore")]
+    recv_quality: mpsc::UnboundedReceiver<QualitySample>,
+    quality: QualityReceiver,
+    #[derivative(Debug = "ignore")]
+    send_migrate: mpsc::UnboundedSender<MigrateLane>,
 }
 
 type ConnectedClientResult<P> = Result<ConnectedClient<P>, WebTransportError<P>>;
@@ -0,0 +1,85 @@
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+use tracing::debug;
+use wtransport::{error::ConnectingError, ClientConfig, Endpoint};
+
+use crate::{WebTransportClient, WebTransportProtocol};
+
+/// Result of [`WebTransportClient::probe`].
+pub type ProbeResult = Result<Probe, ProbeError>;
+
+/// Measurements of a server which was successfully probed.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    /// Round-trip time to the server, as estimated by the connection at the end
+    /// of the handshake.
+    pub rtt: Duration,
+    /// Total time taken from starting the handshake until the session was
+    /// established.
+    ///
+    /// This includes the QUIC and TLS handshakes, and the WebTransport session
+    /// request, so it is usually a few times larger than [`Probe::rtt`].
+    pub handshake: Duration,
+    /// Address of the server.
+    pub remote_addr: SocketAddr,
+}
+
+/// Error that occurs when probing a server.
+#[derive(Debug, thiserror::Error)]
+pub enum ProbeError {
+    /// Failed to create the [`wtransport::Endpoint`].
+    #[error("failed to create endpoint")]
+    Endpoint(#[source] io::Error),
+    /// The server could not be reached, rejected the session, or presented a
+    /// certificate which was not valid under the [`ClientConfig`].
+    #[error("failed to connect")]
+    Connect(#[source] ConnectingError),
+}
+
+impl<P> WebTransportClient<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    /// Checks if a server is reachable, and measures its round-trip time,
+    /// without fully connecting to it.
+    ///
+    /// This performs the handshake with the server, verifying its certificate
+    /// using `config`, then closes the session immediately without opening any
+    /// channels. This is useful for e.g. filling in the ping column of a server
+    /// browser, where connecting to every server would be too expensive.
+    ///
+    /// The server still sees the session being requested, so it will raise an
+    /// incoming client which disconnects shortly after being accepted.
+    ///
+    /// The returned future must be run on an async runtime, like the backend
+    /// future of [`WebTransportClient::connecting`].
+    pub fn probe(
+        config: ClientConfig,
+        url: impl Into<String>,
+    ) -> impl Future<Output = ProbeResult> + Send {
+        let url = url.into();
+        async move {
+            debug!("Probing {url}");
+            let endpoint = Endpoint::client(config).map_err(ProbeError::Endpoint)?;
+
+            let start = Instant::now();
+            let conn = endpoint.connect(url).await.map_err(ProbeError::Connect)?;
+            let probe = Probe {
+                rtt: conn.rtt(),
+                handshake: start.elapsed(),
+                remote_addr: conn.remote_address(),
+            };
+            debug!("Probe finished in {:?}", probe.handshake);
+            // dropping the connection closes the session
+            Ok(probe)
+        }
+    }
+}
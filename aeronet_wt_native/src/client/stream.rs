@@ -0,0 +1,131 @@
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use aeronet::{OnChannel, TransportClient, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+use futures::Stream;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::{ClientEvent, WebTransportClient, WebTransportProtocol};
+
+use super::State;
+
+/// Adapter which exposes the events of a [`WebTransportClient`] as an async
+/// [`Stream`].
+///
+/// The stream ends once the client is disconnected and all of its events have
+/// been returned.
+///
+/// See [`ServerEventStream`](crate::ServerEventStream).
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    client: WebTransportClient<P>,
+    #[derivative(Debug = "ignore")]
+    buf: VecDeque<ClientEvent<P>>,
+    #[derivative(Debug = "ignore")]
+    interval: Interval,
+}
+
+impl<P> ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    /// Wraps a client, polling it every `poll_interval` while there are no
+    /// events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_interval` is zero, or if this is not called from inside
+    /// a tokio runtime with the time driver enabled.
+    #[must_use]
+    pub fn new(client: WebTransportClient<P>, poll_interval: Duration) -> Self {
+        let mut interval = time::interval(poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            client,
+            buf: VecDeque::new(),
+            interval,
+        }
+    }
+
+    /// Unwraps the wrapped client, dropping all events which have been polled
+    /// but not returned from the stream yet.
+    #[must_use]
+    pub fn into_inner(self) -> WebTransportClient<P> {
+        self.client
+    }
+}
+
+impl<P> Deref for ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    type Target = WebTransportClient<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<P> DerefMut for ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+// no fields are structurally pinned
+impl<P> Unpin for ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+}
+
+impl<P> Stream for ClientEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    P::S2C: TryFromBytes,
+{
+    type Item = ClientEvent<P>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.buf.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+
+            this.buf.extend(this.client.recv());
+            if !this.buf.is_empty() {
+                continue;
+            }
+
+            if matches!(this.client.state, State::Disconnected) {
+                return Poll::Ready(None);
+            }
+            ready!(this.interval.poll_tick(cx));
+        }
+    }
+}
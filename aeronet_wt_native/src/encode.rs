@@ -0,0 +1,281 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    io,
+    num::NonZeroUsize,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+use derivative::Derivative;
+use tokio::runtime::Handle;
+
+use crate::shared::Outgoing;
+
+/// Pool of workers which serialize outgoing messages, so that encoding large
+/// messages does not block the thread which sends them.
+///
+/// Normally, a message is serialized, including any compression done by its
+/// [`TryIntoBytes`] implementation, on the thread which sends it, before it is
+/// passed to the backend. When a pool is set on a [`WebTransportServer`],
+/// messages are instead passed to one of the workers, which serializes the
+/// message and passes it on to the backend itself. This suits apps which send
+/// huge messages, such as full world snapshots, which would otherwise stall
+/// the game loop.
+///
+/// Messages sent to the same client are still sent in the order that they
+/// were passed to the server, even if a later message finishes serializing
+/// first.
+///
+/// This is cheap to clone, and clones share the same workers, so one pool can
+/// be used by every shard of a server.
+///
+/// [`TryIntoBytes`]: aeronet::TryIntoBytes
+/// [`WebTransportServer`]: crate::WebTransportServer
+#[derive(Clone)]
+pub struct EncodePool {
+    workers: Arc<Workers>,
+}
+
+enum Workers {
+    Threads(mpsc::Sender<Job>),
+    Tokio(Handle),
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+impl EncodePool {
+    /// Creates a pool of `count` dedicated threads.
+    ///
+    /// The threads are stopped once every clone of this pool is dropped, and
+    /// they have finished the messages that were already passed to them.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a thread could not be spawned.
+    pub fn threads(count: NonZeroUsize) -> io::Result<Self> {
+        let (send_job, recv_job) = mpsc::channel::<Job>();
+        let recv_job = Arc::new(Mutex::new(recv_job));
+        for index in 0..count.get() {
+            let recv_job = recv_job.clone();
+            thread::Builder::new()
+                .name(format!("aeronet-encode-{index}"))
+                .spawn(move || loop {
+                    // the lock is only held while waiting for a job, so that
+                    // the other workers can take jobs while this one runs
+                    let job = recv_job
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })?;
+        }
+        Ok(Self {
+            workers: Arc::new(Workers::Threads(send_job)),
+        })
+    }
+
+    /// Creates a pool which runs on the blocking thread pool of a Tokio
+    /// runtime.
+    ///
+    /// Use this if the server backend already runs on a Tokio runtime, so
+    /// that no extra threads are kept around while nothing is being sent.
+    #[must_use]
+    pub fn tokio(handle: Handle) -> Self {
+        Self {
+            workers: Arc::new(Workers::Tokio(handle)),
+        }
+    }
+
+    /// Runs `job` on one of the workers.
+    pub(crate) fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        match &*self.workers {
+            Workers::Threads(send_job) => {
+                // the workers only stop once the sender is dropped, so this
+                // only fails if every worker has panicked - the job is then
+                // run here instead, so that the position it reserved in an
+                // `EncodeOrder` is still filled in, and later messages to the
+                // same client are not held back forever
+                if let Err(mpsc::SendError(job)) = send_job.send(Box::new(job)) {
+                    job();
+                }
+            }
+            Workers::Tokio(handle) => {
+                drop(handle.spawn_blocking(job));
+            }
+        }
+    }
+}
+
+impl Debug for EncodePool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match &*self.workers {
+            Workers::Threads(_) => "threads",
+            Workers::Tokio(_) => "tokio",
+        };
+        f.debug_struct("EncodePool")
+            .field("workers", &kind)
+            .finish()
+    }
+}
+
+/// Keeps the messages sent to a single peer in the order that they were sent,
+/// while some of them are still being serialized by an [`EncodePool`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = "T: Debug"), Default(bound = ""))]
+pub(crate) struct EncodeOrder<T> {
+    slots: Mutex<Slots<T>>,
+}
+
+pub(crate) type SharedEncodeOrder = Arc<EncodeOrder<Outgoing>>;
+
+#[derive(Derivative)]
+#[derivative(Debug(bound = "T: Debug"), Default(bound = ""))]
+struct Slots<T> {
+    /// Sequence number of the message at the front of `pending`.
+    front: u64,
+    /// Messages which are waiting to be passed on, either because they are
+    /// still being serialized, or because an earlier message is.
+    pending: VecDeque<Slot<T>>,
+}
+
+#[derive(Debug)]
+enum Slot<T> {
+    Encoding,
+    Encoded(T),
+    /// The message failed to serialize, and is skipped. The error is reported
+    /// by whoever serialized it, not by the order.
+    Failed,
+}
+
+impl<T> Slots<T> {
+    /// Passes every message which is no longer waiting on an earlier message
+    /// to `send`, returning `false` if `send` failed for any of them.
+    fn flush(&mut self, mut send: impl FnMut(T) -> bool) -> bool {
+        let mut all_sent = true;
+        while self
+            .pending
+            .front()
+            .is_some_and(|slot| !matches!(slot, Slot::Encoding))
+        {
+            self.front += 1;
+            if let Some(Slot::Encoded(msg)) = self.pending.pop_front() {
+                all_sent &= send(msg);
+            }
+        }
+        all_sent
+    }
+}
+
+impl<T> EncodeOrder<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Slots<T>> {
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Reserves the position of a message which is about to be serialized,
+    /// returning its sequence number.
+    pub fn reserve(&self) -> u64 {
+        let mut slots = self.lock();
+        slots.pending.push_back(Slot::Encoding);
+        slots.front + slots.pending.len() as u64 - 1
+    }
+
+    /// Fills in the position reserved by [`EncodeOrder::reserve`] with the
+    /// serialized message, or [`None`] if it failed to serialize.
+    ///
+    /// Every message which is no longer waiting on an earlier message is
+    /// passed to `send`, in order. This includes messages held back by
+    /// [`EncodeOrder::push`], so this returns `false` if `send` failed for any
+    /// of them, and the caller must report that the messages were dropped.
+    ///
+    /// A message which failed to serialize is skipped, and the caller must
+    /// report its serialize error itself.
+    pub fn complete(&self, seq: u64, msg: Option<T>, send: impl FnMut(T) -> bool) -> bool {
+        let mut slots = self.lock();
+        let index =
+            usize::try_from(seq - slots.front).expect("pending messages should fit in memory");
+        slots.pending[index] = msg.map_or(Slot::Failed, Slot::Encoded);
+        slots.flush(send)
+    }
+
+    /// Passes an already serialized message to `send`, or holds it back if an
+    /// earlier message is still being serialized.
+    ///
+    /// Returns the result of `send`, or `true` if the message was held back.
+    /// If `send` fails once a held back message is passed on, this is reported
+    /// by the [`EncodeOrder::complete`] call which passed it on instead.
+    pub fn push(&self, msg: T, send: impl FnOnce(T) -> bool) -> bool {
+        // the lock is held while sending, so that a message which completes
+        // on a worker at the same time can not overtake this one
+        let mut slots = self.lock();
+        if slots.pending.is_empty() {
+            send(msg)
+        } else {
+            slots.pending.push_back(Slot::Encoded(msg));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_order_of_completions() {
+        let order = EncodeOrder::default();
+        let mut sent = Vec::new();
+        let first = order.reserve();
+        let second = order.reserve();
+        assert!(order.push(2, |_| unreachable!()));
+
+        let mut send = |msg| {
+            sent.push(msg);
+            true
+        };
+        assert!(order.complete(second, Some(1), &mut send));
+        assert!(order.complete(first, Some(0), &mut send));
+        assert_eq!(vec![0, 1, 2], sent);
+
+        // nothing is pending, so this is sent right away
+        assert!(order.push(3, |msg| {
+            sent.push(msg);
+            true
+        }));
+        assert_eq!(vec![0, 1, 2, 3], sent);
+    }
+
+    #[test]
+    fn skips_failed() {
+        let order = EncodeOrder::default();
+        let mut sent = Vec::new();
+        let first = order.reserve();
+        let second = order.reserve();
+        let mut send = |msg| {
+            sent.push(msg);
+            true
+        };
+        assert!(order.complete(second, Some(1), &mut send));
+        assert!(order.complete(first, None, &mut send));
+        assert_eq!(vec![1], sent);
+        assert_eq!(2, order.reserve());
+    }
+
+    #[test]
+    fn reports_held_back_send_failure() {
+        let order = EncodeOrder::default();
+        let first = order.reserve();
+        assert!(order.push(1, |_| unreachable!()));
+
+        // the message held back by `push` is passed on here, and fails
+        let mut sent = Vec::new();
+        assert!(!order.complete(first, Some(0), |msg| {
+            sent.push(msg);
+            msg == 0
+        }));
+        assert_eq!(vec![0, 1], sent);
+    }
+}
@@ -1,10 +1,20 @@
 #![doc = include_str!("../README.md")]
 
 mod client;
+mod encode;
+mod raw;
+mod security;
 mod server;
 mod shared;
+mod socket;
+#[cfg(feature = "codec-timing")]
+mod timing;
 mod transport;
+pub mod wire;
 
 pub use wtransport;
 
-pub use {client::*, server::*, transport::*};
+pub use {client::*, encode::EncodePool, raw::*, security::*, server::*, socket::*, transport::*};
+
+#[cfg(feature = "codec-timing")]
+pub use timing::*;
@@ -0,0 +1,70 @@
+use std::marker::PhantomData;
+
+use aeronet::{ChannelKey, RawMessage, RawPayload, TransportProtocol};
+use derivative::Derivative;
+
+use crate::{WebTransportClient, WebTransportProtocol, WebTransportServer};
+
+/// Server which sends and receives raw bytes on the lanes of `C`, for apps
+/// which bring their own serialization.
+///
+/// The server manages connections and lanes as usual, but passes the payloads
+/// of messages through as-is: a [`RawMessage`] is sent on its lane without
+/// being serialized, and every message received is a [`RawPayload`] of the
+/// bytes that the client sent. Serialization can never fail, so the
+/// [`OnMessageError`] policies of the server have no effect.
+///
+/// Options which change the bytes on the wire, such as lane security or the
+/// `checksum` feature, still apply, and must match on both sides.
+///
+/// ```ignore
+/// let (mut server, backend) = RawServer::<Lane>::opening(config);
+/// for event in server.recv() {
+///     if let ServerEvent::Recv { client, msg } = event {
+///         server.send(client, RawMessage::new(Lane::Events, msg.into_inner()))?;
+///     }
+/// }
+/// ```
+///
+/// [`OnMessageError`]: aeronet::OnMessageError
+pub type RawServer<C> = WebTransportServer<RawServerProtocol<C>>;
+
+/// Client which sends and receives raw bytes on the lanes of `C`, for apps
+/// which bring their own serialization.
+///
+/// See [`RawServer`].
+pub type RawClient<C> = WebTransportClient<RawClientProtocol<C>>;
+
+/// Protocol of a [`RawServer`], which receives [`RawPayload`]s and sends
+/// [`RawMessage`]s on the lanes of `C`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct RawServerProtocol<C> {
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<C: ChannelKey> TransportProtocol for RawServerProtocol<C> {
+    type C2S = RawPayload;
+    type S2C = RawMessage<C>;
+}
+
+impl<C: ChannelKey> WebTransportProtocol for RawServerProtocol<C> {
+    type Channel = C;
+}
+
+/// Protocol of a [`RawClient`], which sends [`RawMessage`]s on the lanes of `C`
+/// and receives [`RawPayload`]s.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct RawClientProtocol<C> {
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<C: ChannelKey> TransportProtocol for RawClientProtocol<C> {
+    type C2S = RawMessage<C>;
+    type S2C = RawPayload;
+}
+
+impl<C: ChannelKey> WebTransportProtocol for RawClientProtocol<C> {
+    type Channel = C;
+}
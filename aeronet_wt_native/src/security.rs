@@ -0,0 +1,273 @@
+use std::{borrow::Cow, marker::PhantomData, sync::Arc};
+
+use aeronet::{ChannelKey, ChannelKind};
+use derivative::Derivative;
+use ring::{
+    aead, hkdf, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// Minimum length in bytes of the secret of a [`LaneSecurityConfig`].
+pub const MIN_SECRET_LEN: usize = 16;
+
+const SALT: &[u8] = b"aeronet_wt_native lane security";
+
+const C2S: &[u8] = b"c2s";
+
+const S2C: &[u8] = b"s2c";
+
+const HMAC_TAG_LEN: usize = 32;
+
+/// How messages on a lane are protected, on top of the TLS encryption of the
+/// connection itself.
+///
+/// TLS only protects messages up to the endpoint which terminates the QUIC
+/// connection. If a relay or proxy between the client and the server
+/// terminates TLS, it can read and modify every message passing through it.
+/// Lane security protects messages end-to-end using a secret which is shared
+/// by the client and the server, but not by the relay.
+///
+/// See [`LaneSecurityConfig`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LaneSecurity {
+    /// Messages are sent as they are.
+    #[default]
+    Plain,
+    /// An HMAC-SHA256 tag is appended to every message, so that messages which
+    /// were modified or forged are rejected. The contents of messages can
+    /// still be read by a relay.
+    ///
+    /// This adds 32 bytes to every message.
+    Signed,
+    /// Every message is encrypted and authenticated using ChaCha20-Poly1305,
+    /// with a random nonce.
+    ///
+    /// This adds 28 bytes to every message.
+    Encrypted,
+}
+
+/// Configuration of the [`LaneSecurity`] of every lane of a protocol.
+///
+/// The client and the server must use the same secret and the same policy for
+/// every lane. Messages which fail verification are dropped, and raise a
+/// `MessageRejected` event on the receiving side. Separate keys are derived
+/// from the secret for each lane and each direction, so a message can not be
+/// moved to another lane or reflected back to its sender.
+///
+/// Since datagrams do not carry the lane that they were sent on, all
+/// unreliable lanes must use the same policy.
+///
+/// Note that this does not protect against a relay dropping, delaying or
+/// replaying messages.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub struct LaneSecurityConfig<C> {
+    #[derivative(Debug = "ignore")]
+    secret: Arc<[u8]>,
+    policies: Box<[LaneSecurity]>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> C>,
+}
+
+/// Error that occurs when creating a [`LaneSecurityConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LaneSecurityError {
+    /// The secret is shorter than [`MIN_SECRET_LEN`].
+    #[error("secret is {0} bytes long, shorter than {MIN_SECRET_LEN} bytes")]
+    SecretTooShort(usize),
+    /// Not all unreliable lanes use the same policy.
+    #[error("unreliable lanes must all use the same policy")]
+    MixedDatagramPolicies,
+}
+
+impl<C: ChannelKey> LaneSecurityConfig<C> {
+    /// Creates a configuration from a shared secret, and a function giving the
+    /// policy of each lane.
+    ///
+    /// The secret should be at least 32 bytes of random data.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the secret is too short, or if not all unreliable lanes use
+    /// the same policy.
+    pub fn new(
+        secret: &[u8],
+        policy: impl Fn(&C) -> LaneSecurity,
+    ) -> Result<Self, LaneSecurityError> {
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(LaneSecurityError::SecretTooShort(secret.len()));
+        }
+
+        let policies = C::ALL.iter().map(policy).collect::<Box<[_]>>();
+        let mut datagram_policies = C::ALL
+            .iter()
+            .zip(policies.iter())
+            .filter(|(lane, _)| lane.kind() == ChannelKind::Unreliable)
+            .map(|(_, policy)| *policy);
+        if let Some(first) = datagram_policies.next() {
+            if datagram_policies.any(|policy| policy != first) {
+                return Err(LaneSecurityError::MixedDatagramPolicies);
+            }
+        }
+
+        Ok(Self {
+            secret: Arc::from(secret),
+            policies,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Gets the policy of a lane.
+    #[must_use]
+    pub fn policy(&self, lane: &C) -> LaneSecurity {
+        self.policies[lane.index()]
+    }
+
+    /// Derives the keys used by one side of a connection.
+    pub(crate) fn cipher(&self, server: bool) -> LaneCipher {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, SALT).extract(&self.secret);
+        let (seal, open) = if server { (S2C, C2S) } else { (C2S, S2C) };
+        let datagram = C::ALL
+            .iter()
+            .find(|lane| lane.kind() == ChannelKind::Unreliable)
+            .map_or(LaneSecurity::Plain, |lane| self.policy(lane));
+        LaneCipher {
+            policies: self.policies.clone(),
+            unreliable: C::ALL
+                .iter()
+                .map(|lane| lane.kind() == ChannelKind::Unreliable)
+                .collect(),
+            datagram,
+            seal: derive_keys(&prk, seal, C::ALL.len()),
+            open: derive_keys(&prk, open, C::ALL.len()),
+            rng: SystemRandom::new(),
+        }
+    }
+}
+
+/// Keys used to protect the messages of a single lane in one direction.
+struct LaneKeys {
+    hmac: hmac::Key,
+    aead: aead::LessSafeKey,
+}
+
+/// Derives the keys for every stream lane, followed by the keys for datagrams.
+fn derive_keys(prk: &hkdf::Prk, direction: &[u8], lanes: usize) -> Box<[LaneKeys]> {
+    (0..=lanes)
+        .map(|slot| {
+            let slot = u32::try_from(slot).unwrap_or(u32::MAX).to_be_bytes();
+            let hmac_info: [&[u8]; 3] = [direction, &slot, b"hmac"];
+            let aead_info: [&[u8]; 3] = [direction, &slot, b"aead"];
+            let hmac = prk
+                .expand(&hmac_info, hmac::HMAC_SHA256)
+                .expect("HMAC key length should be valid for HKDF");
+            let aead = prk
+                .expand(&aead_info, &aead::CHACHA20_POLY1305)
+                .expect("AEAD key length should be valid for HKDF");
+            LaneKeys {
+                hmac: hmac::Key::from(hmac),
+                aead: aead::LessSafeKey::new(aead::UnboundKey::from(aead)),
+            }
+        })
+        .collect()
+}
+
+/// Seals outgoing and opens incoming messages on one side of a connection.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(crate) struct LaneCipher {
+    policies: Box<[LaneSecurity]>,
+    unreliable: Box<[bool]>,
+    datagram: LaneSecurity,
+    #[derivative(Debug = "ignore")]
+    seal: Box<[LaneKeys]>,
+    #[derivative(Debug = "ignore")]
+    open: Box<[LaneKeys]>,
+    #[derivative(Debug = "ignore")]
+    rng: SystemRandom,
+}
+
+impl LaneCipher {
+    /// Gets if the lanes with the given indices protect their messages in the
+    /// same way, so that the messages of one can be sent on the other.
+    pub fn same_policy(&self, a: usize, b: usize) -> bool {
+        self.policies[a] == self.policies[b]
+    }
+
+    /// Protects a serialized message sent on the lane with the given index.
+    ///
+    /// Returns [`None`] if the message could not be sealed.
+    pub fn seal(&self, lane: usize, mut bytes: Vec<u8>) -> Option<Vec<u8>> {
+        let slot = if self.unreliable[lane] {
+            self.policies.len()
+        } else {
+            lane
+        };
+        let keys = &self.seal[slot];
+        match self.policies[lane] {
+            LaneSecurity::Plain => Some(bytes),
+            LaneSecurity::Signed => {
+                let tag = hmac::sign(&keys.hmac, &bytes);
+                bytes.extend_from_slice(tag.as_ref());
+                Some(bytes)
+            }
+            LaneSecurity::Encrypted => {
+                let mut nonce = [0; aead::NONCE_LEN];
+                self.rng.fill(&mut nonce).ok()?;
+                let tag = keys
+                    .aead
+                    .seal_in_place_separate_tag(
+                        aead::Nonce::assume_unique_for_key(nonce),
+                        aead::Aad::empty(),
+                        &mut bytes,
+                    )
+                    .ok()?;
+                let mut sealed =
+                    Vec::with_capacity(aead::NONCE_LEN + bytes.len() + tag.as_ref().len());
+                sealed.extend_from_slice(&nonce);
+                sealed.extend_from_slice(&bytes);
+                sealed.extend_from_slice(tag.as_ref());
+                Some(sealed)
+            }
+        }
+    }
+
+    /// Verifies a received message and strips its protection.
+    ///
+    /// `lane` is the index of the reliable lane that the message was received
+    /// on, or [`None`] if it was received as a datagram.
+    ///
+    /// Returns [`None`] if the message was not sealed by the peer with the same
+    /// secret and policy.
+    pub fn open<'a>(&self, lane: Option<usize>, bytes: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        let (slot, policy) = match lane {
+            Some(lane) => (lane, self.policies[lane]),
+            None => (self.policies.len(), self.datagram),
+        };
+        let keys = &self.open[slot];
+        match policy {
+            LaneSecurity::Plain => Some(Cow::Borrowed(bytes)),
+            LaneSecurity::Signed => {
+                let split = bytes.len().checked_sub(HMAC_TAG_LEN)?;
+                let (payload, tag) = bytes.split_at(split);
+                hmac::verify(&keys.hmac, payload, tag).ok()?;
+                Some(Cow::Borrowed(payload))
+            }
+            LaneSecurity::Encrypted => {
+                if bytes.len() < aead::NONCE_LEN + aead::MAX_TAG_LEN {
+                    return None;
+                }
+                let (nonce, sealed) = bytes.split_at(aead::NONCE_LEN);
+                let nonce = aead::Nonce::try_assume_unique_for_key(nonce).ok()?;
+                let mut buf = sealed.to_vec();
+                let len = keys
+                    .aead
+                    .open_in_place(nonce, aead::Aad::empty(), &mut buf)
+                    .ok()?
+                    .len();
+                buf.truncate(len);
+                Some(Cow::Owned(buf))
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+
+use crate::{ClientKey, WebTransportProtocol};
+
+use super::{ServerEvent, WebTransportError};
+
+/// Receiver of the connection funnel events of a [`WebTransportServer`], used
+/// for analyzing at which stage clients fail to join.
+///
+/// Every client goes through the [`FunnelStage`]s in order, and stops at the
+/// first stage it fails to reach. A [`FunnelEvent::Reached`] is recorded every
+/// time a client reaches a stage, and a [`FunnelEvent::Disconnected`] when it
+/// disconnects, along with the last stage that it reached. Counting the
+/// clients which reached each stage gives the funnel, and the durations show
+/// which stages are slow.
+///
+/// This is implemented for closures, so a sink can forward the events to an
+/// analytics backend:
+///
+/// ```ignore
+/// server.set_analytics_sink(move |event| {
+///     analytics_tx.send(event).ok();
+/// });
+/// ```
+///
+/// The sink is called from [`TransportServer::recv`], so it should not block.
+///
+/// Enable this using [`WebTransportServer::set_analytics_sink`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`TransportServer::recv`]: aeronet::TransportServer::recv
+/// [`WebTransportServer::set_analytics_sink`]: crate::WebTransportServer::set_analytics_sink
+pub trait AnalyticsSink: Send + Sync {
+    /// Records a single event.
+    fn record(&mut self, event: FunnelEvent);
+}
+
+impl<F> AnalyticsSink for F
+where
+    F: FnMut(FunnelEvent) + Send + Sync,
+{
+    fn record(&mut self, event: FunnelEvent) {
+        self(event);
+    }
+}
+
+/// Stage of the connection process that a client has reached.
+///
+/// See [`AnalyticsSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FunnelStage {
+    /// The client requested to open a session.
+    ///
+    /// See [`ServerEvent::Incoming`].
+    Requested,
+    /// The server accepted the client's session request.
+    ///
+    /// See [`ServerEvent::Accepted`].
+    Accepted,
+    /// The client finished its handshake and is connected.
+    ///
+    /// See [`ServerEvent::Connected`].
+    Connected,
+    /// The server received the first message from the client.
+    ///
+    /// See [`ServerEvent::Recv`].
+    FirstMessage,
+}
+
+/// Event recorded by an [`AnalyticsSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunnelEvent {
+    /// A client reached a new stage.
+    Reached {
+        /// The key of the client.
+        client: ClientKey,
+        /// The stage reached.
+        stage: FunnelStage,
+        /// Time since the client reached [`FunnelStage::Requested`].
+        since_requested: Duration,
+        /// Time since the client reached its previous stage.
+        since_previous: Duration,
+    },
+    /// A client disconnected.
+    Disconnected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The last stage that the client reached.
+        reached: FunnelStage,
+        /// Time since the client reached [`FunnelStage::Requested`].
+        since_requested: Duration,
+        /// Time since the client reached its last stage.
+        since_previous: Duration,
+        /// The cause of the disconnect, followed by each of its sources.
+        causes: Vec<String>,
+    },
+}
+
+/// Tracks the stage of every client, and records funnel events into a sink.
+pub(super) struct Funnel {
+    sink: Box<dyn AnalyticsSink>,
+    clients: HashMap<ClientKey, Progress>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    stage: FunnelStage,
+    requested_at: Instant,
+    previous_at: Instant,
+}
+
+impl Funnel {
+    pub(super) fn new(sink: Box<dyn AnalyticsSink>) -> Self {
+        Self {
+            sink,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Forgets all clients, e.g. because the server was closed.
+    pub(super) fn clear(&mut self) {
+        self.clients.clear();
+    }
+
+    pub(super) fn observe<P>(
+        &mut self,
+        event: &ServerEvent<P>,
+        now: Instant,
+        error_chain: fn(&WebTransportError<P>) -> Vec<String>,
+    ) where
+        P: WebTransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        match event {
+            ServerEvent::Incoming { client } => {
+                self.clients.insert(
+                    *client,
+                    Progress {
+                        stage: FunnelStage::Requested,
+                        requested_at: now,
+                        previous_at: now,
+                    },
+                );
+                self.sink.record(FunnelEvent::Reached {
+                    client: *client,
+                    stage: FunnelStage::Requested,
+                    since_requested: Duration::ZERO,
+                    since_previous: Duration::ZERO,
+                });
+            }
+            ServerEvent::Accepted { client, .. } => {
+                self.advance(*client, FunnelStage::Accepted, now);
+            }
+            ServerEvent::Connected { client } => {
+                self.advance(*client, FunnelStage::Connected, now);
+            }
+            ServerEvent::Recv { client, .. } => {
+                self.advance(*client, FunnelStage::FirstMessage, now);
+            }
+            ServerEvent::Disconnected { client, cause } => {
+                let Some(progress) = self.clients.remove(client) else {
+                    return;
+                };
+                self.sink.record(FunnelEvent::Disconnected {
+                    client: *client,
+                    reached: progress.stage,
+                    since_requested: now.saturating_duration_since(progress.requested_at),
+                    since_previous: now.saturating_duration_since(progress.previous_at),
+                    causes: error_chain(cause),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn advance(&mut self, client: ClientKey, stage: FunnelStage, now: Instant) {
+        let Some(progress) = self.clients.get_mut(&client) else {
+            return;
+        };
+        if stage <= progress.stage {
+            return;
+        }
+        self.sink.record(FunnelEvent::Reached {
+            client,
+            stage,
+            since_requested: now.saturating_duration_since(progress.requested_at),
+            since_previous: now.saturating_duration_since(progress.previous_at),
+        });
+        progress.stage = stage;
+        progress.previous_at = now;
+    }
+}
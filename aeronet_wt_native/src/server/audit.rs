@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+use serde::Serialize;
+use slotmap::Key;
+use tracing::warn;
+
+use crate::{ClientKey, EndpointInfo, WebTransportProtocol, WebTransportServer};
+
+use super::{disconnect_log, ErrorChainFn, ServerEvent, WebTransportError};
+
+/// Append-only record of client connections to a [`WebTransportServer`],
+/// written as JSON lines, for retaining connection records for moderation
+/// and appeals.
+///
+/// A line is written when:
+/// * a client establishes its connection (`"event": "connect"`), with its
+///   address and the authority, path, origin and user agent of its session
+///   request
+/// * the app sets the identity of a client using
+///   [`WebTransportServer::set_identity`] (`"event": "identify"`)
+/// * a client which has been accepted disconnects (`"event": "disconnect"`),
+///   with its address, identity and the full error chain of the cause
+///
+/// Each line is a JSON object, shown formatted here:
+///
+/// ```json
+/// {
+///   "at_ms": 1700000093000,
+///   "client": 4294967297,
+///   "event": "disconnect",
+///   "addr": "203.0.113.7:51234",
+///   "identity": "account-42",
+///   "causes": ["kicked: griefing"]
+/// }
+/// ```
+///
+/// `at_ms` is the number of milliseconds since the Unix epoch, and `client`
+/// is the FFI representation of the [`ClientKey`], which is only unique for
+/// the lifetime of a server. Message contents are never logged.
+///
+/// Once the log file would grow beyond a maximum size, it is rotated: the
+/// current file is renamed by appending `.1` to its name, previously rotated
+/// files are shifted up by one, and files beyond the maximum count are
+/// deleted.
+///
+/// Lines are written from [`TransportServer::recv`] and flushed once per
+/// call, so the file system should be fast enough to not stall the server.
+/// Errors while writing are logged using [`tracing`] and do not affect the
+/// server.
+///
+/// Enable this using [`WebTransportServer::enable_audit_log`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`WebTransportServer::set_identity`]: crate::WebTransportServer::set_identity
+/// [`TransportServer::recv`]: aeronet::TransportServer::recv
+/// [`WebTransportServer::enable_audit_log`]: crate::WebTransportServer::enable_audit_log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: BufWriter<File>,
+    file_bytes: u64,
+    clients: HashMap<ClientKey, AuditedClient>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AuditedClient {
+    connected: bool,
+    addr: Option<SocketAddr>,
+    identity: Option<String>,
+    request: Option<Request>,
+}
+
+#[derive(Debug, Clone)]
+struct Request {
+    authority: String,
+    path: String,
+    origin: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    at_ms: u64,
+    client: u64,
+    #[serde(flatten)]
+    entry: Entry<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Entry<'a> {
+    Connect {
+        addr: Option<SocketAddr>,
+        authority: Option<&'a str>,
+        path: Option<&'a str>,
+        origin: Option<&'a str>,
+        user_agent: Option<&'a str>,
+    },
+    Identify {
+        addr: Option<SocketAddr>,
+        identity: &'a str,
+    },
+    Disconnect {
+        addr: Option<SocketAddr>,
+        identity: Option<&'a str>,
+        causes: &'a [String],
+    },
+}
+
+impl AuditLog {
+    /// Opens the log file at `path` for appending, creating it if it does not
+    /// exist.
+    ///
+    /// * `max_file_bytes` is the size above which the file is rotated
+    /// * `max_files` is the maximum number of rotated files kept, in addition
+    ///   to the current one; if this is 0, the file is truncated instead of
+    ///   rotated
+    ///
+    /// # Errors
+    ///
+    /// Errors if the file could not be opened.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        max_file_bytes: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        let file_bytes = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_file_bytes,
+            max_files,
+            file: BufWriter::new(file),
+            file_bytes,
+            clients: HashMap::new(),
+        })
+    }
+
+    /// Gets the path of the current log file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the size above which the log file is rotated.
+    #[must_use]
+    pub fn max_file_bytes(&self) -> u64 {
+        self.max_file_bytes
+    }
+
+    /// Gets the maximum number of rotated files kept.
+    #[must_use]
+    pub fn max_files(&self) -> usize {
+        self.max_files
+    }
+
+    /// Gets the path of the rotated file with the given index, where 1 is the
+    /// most recently rotated file.
+    #[must_use]
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        PathBuf::from(path)
+    }
+
+    /// Writes any buffered lines to the log file.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the lines could not be written.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    pub(super) fn observe<P>(
+        &mut self,
+        event: &ServerEvent<P>,
+        info: impl FnOnce(ClientKey) -> Option<EndpointInfo>,
+        error_chain: ErrorChainFn<P>,
+    ) where
+        P: WebTransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        match event {
+            ServerEvent::Accepted {
+                client,
+                authority,
+                path,
+                origin,
+                user_agent,
+            } => {
+                self.clients.entry(*client).or_default().request = Some(Request {
+                    authority: authority.clone(),
+                    path: path.clone(),
+                    origin: origin.clone(),
+                    user_agent: user_agent.clone(),
+                });
+            }
+            ServerEvent::PendingConnected { client } | ServerEvent::Connected { client } => {
+                let audited = self.clients.entry(*client).or_default();
+                if audited.connected {
+                    // already logged when the client was pending admission
+                    return;
+                }
+                audited.connected = true;
+                audited.addr = info(*client).map(|info| info.remote_addr);
+                let audited = audited.clone();
+                let request = audited.request.as_ref();
+                self.write(
+                    *client,
+                    Entry::Connect {
+                        addr: audited.addr,
+                        authority: request.map(|request| request.authority.as_str()),
+                        path: request.map(|request| request.path.as_str()),
+                        origin: request.and_then(|request| request.origin.as_deref()),
+                        user_agent: request.and_then(|request| request.user_agent.as_deref()),
+                    },
+                );
+            }
+            ServerEvent::Disconnected { client, cause } => {
+                let Some(audited) = self.clients.remove(client) else {
+                    // the client was never accepted, so there is nothing
+                    // identifying about it to log
+                    return;
+                };
+                let addr = audited
+                    .addr
+                    .or_else(|| info(*client).map(|info| info.remote_addr));
+                self.write(
+                    *client,
+                    Entry::Disconnect {
+                        addr,
+                        identity: audited.identity.as_deref(),
+                        causes: &error_chain(cause),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn identify(&mut self, client: ClientKey, identity: &str) {
+        let audited = self.clients.entry(client).or_default();
+        audited.identity = Some(identity.to_owned());
+        let addr = audited.addr;
+        self.write(client, Entry::Identify { addr, identity });
+    }
+
+    /// Forgets all clients, e.g. because the server was closed.
+    pub(super) fn clear(&mut self) {
+        self.clients.clear();
+    }
+
+    pub(super) fn flush_or_warn(&mut self) {
+        if let Err(err) = self.flush() {
+            warn!("Failed to flush audit log: {err:#}");
+        }
+    }
+
+    fn write(&mut self, client: ClientKey, entry: Entry<'_>) {
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+            });
+        let line = Line {
+            at_ms,
+            client: client.data().as_ffi(),
+            entry,
+        };
+        if let Err(err) = self.write_line(&line) {
+            warn!("Failed to write audit log: {err:#}");
+        }
+    }
+
+    fn write_line(&mut self, line: &Line<'_>) -> io::Result<()> {
+        let mut buf = serde_json::to_vec(line)?;
+        buf.push(b'\n');
+        let len = buf.len() as u64;
+        if self.file_bytes > 0 && self.file_bytes + len > self.max_file_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&buf)?;
+        self.file_bytes += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = BufWriter::new(File::create(&self.path)?);
+        } else {
+            for index in (1..self.max_files).rev() {
+                if let Err(err) = fs::rename(self.rotated_path(index), self.rotated_path(index + 1))
+                {
+                    if err.kind() != io::ErrorKind::NotFound {
+                        return Err(err);
+                    }
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = BufWriter::new(open_append(&self.path)?);
+        }
+        self.file_bytes = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl<P> WebTransportServer<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Starts recording client connections into the given [`AuditLog`].
+    ///
+    /// Only clients which are accepted after this is called are recorded.
+    /// This replaces any previously enabled log.
+    pub fn enable_audit_log(&mut self, log: AuditLog)
+    where
+        WebTransportError<P>: Error,
+    {
+        let error_chain: ErrorChainFn<P> = disconnect_log::error_chain;
+        self.audit = Some((log, error_chain));
+    }
+
+    /// Stops recording client connections, returning the log if one was
+    /// enabled.
+    pub fn disable_audit_log(&mut self) -> Option<AuditLog> {
+        self.audit.take().map(|(mut log, _)| {
+            log.flush_or_warn();
+            log
+        })
+    }
+
+    /// Gets the audit log of client connections, if one is enabled.
+    ///
+    /// See [`WebTransportServer::enable_audit_log`].
+    #[must_use]
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref().map(|(log, _)| log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use slotmap::KeyData;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aeronet-audit-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let path = temp_path("lines");
+        let mut log = AuditLog::open(&path, u64::MAX, 0).unwrap();
+        let client = ClientKey::from(KeyData::from_ffi(1));
+        log.identify(client, "account-42");
+        log.flush().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!("identify", line["event"]);
+        assert_eq!(client.data().as_ffi(), line["client"]);
+        assert_eq!("account-42", line["identity"]);
+        assert!(line["addr"].is_null());
+    }
+
+    #[test]
+    fn rotates_files() {
+        let path = temp_path("rotate");
+        let mut log = AuditLog::open(&path, 1, 2).unwrap();
+        let client = ClientKey::from(KeyData::from_ffi(1));
+        for identity in ["a", "b", "c", "d"] {
+            log.identify(client, identity);
+        }
+        log.flush().unwrap();
+
+        let identity = |path: &Path| {
+            let line: serde_json::Value =
+                serde_json::from_str(fs::read_to_string(path).unwrap().trim_end()).unwrap();
+            line["identity"].as_str().unwrap().to_owned()
+        };
+        assert_eq!("d", identity(&path));
+        assert_eq!("c", identity(&log.rotated_path(1)));
+        assert_eq!("b", identity(&log.rotated_path(2)));
+        assert!(!log.rotated_path(3).exists());
+    }
+}
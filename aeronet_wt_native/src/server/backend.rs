@@ -1,46 +1,87 @@
-use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+use std::{collections::hash_map::RandomState, future, hash::BuildHasher, io, sync::Arc};
+
+use aeronet::{Features, OnChannel, TryFromBytes, TryIntoBytes};
 use slotmap::SlotMap;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot, Semaphore,
+};
 use tracing::debug;
-use wtransport::{endpoint::IncomingSession, Endpoint, ServerConfig};
+use wtransport::{
+    endpoint::{IncomingSession, SessionRequest},
+    Endpoint, ServerConfig,
+};
 
-use crate::{shared, EndpointInfo, WebTransportProtocol};
+use crate::{
+    encode::SharedEncodeOrder,
+    security::LaneCipher,
+    shared::{self, FeatureLink, QualityLink, QualitySender},
+    EndpointInfo, RecvBufferCaps, SessionResponse, WebTransportProtocol,
+};
 
 use super::{
-    AcceptedClient, AcceptedClientResult, ConnectedClient, IncomingClient, OpenServer,
-    OpenServerResult, WebTransportError,
+    limits::LimitsState, AcceptedClient, AcceptedClientResult, ConnectedClient, IncomingClient,
+    OpenServer, OpenServerResult, SessionRouter, WebTransportError,
 };
 
+/// Runs the endpoint of a server, which is shared by one frontend per shard.
+///
+/// Each incoming session is assigned to one of the shards which are still
+/// open. The endpoint is closed once all of them are closed.
 pub(super) async fn start<P: WebTransportProtocol>(
     config: ServerConfig,
-    send_open: oneshot::Sender<OpenServerResult<P>>,
+    router: Option<SessionRouter>,
+    cipher: Option<Arc<LaneCipher>>,
+    recv_buffer_caps: RecvBufferCaps<P::Channel>,
+    features: Features,
+    fast_start: Option<usize>,
+    incoming_capacity: Option<usize>,
+    send_open: Vec<oneshot::Sender<OpenServerResult<P>>>,
 ) where
     P::C2S: TryFromBytes,
     P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
 {
-    let endpoint = match Endpoint::server(config).map_err(WebTransportError::Endpoint) {
+    let endpoint = match Endpoint::server(config) {
         Ok(endpoint) => endpoint,
         Err(err) => {
-            let _ = send_open.send(Err(err));
+            for send_open in send_open {
+                let err = io::Error::new(err.kind(), err.to_string());
+                let _ = send_open.send(Err(WebTransportError::Endpoint(err)));
+            }
             return;
         }
     };
     debug!("Created endpoint");
 
-    let (send_client, recv_client) = mpsc::unbounded_channel();
+    let incoming_capacity = incoming_capacity
+        .unwrap_or(Semaphore::MAX_PERMITS)
+        .clamp(1, Semaphore::MAX_PERMITS);
     let (send_closed, mut recv_closed) = mpsc::channel(1);
-    let open = OpenServer {
-        local_addr: endpoint.local_addr(),
-        clients: SlotMap::default(),
-        recv_client,
-        send_closed,
-    };
-    if send_open.send(Ok(open)).is_err() {
-        debug!("Frontend closed");
+    let mut shards = Vec::with_capacity(send_open.len());
+    for send_open in send_open {
+        let (send_client, recv_client) = mpsc::channel(incoming_capacity);
+        let open = OpenServer {
+            local_addr: endpoint.local_addr(),
+            clients: SlotMap::default(),
+            idle_since: None,
+            recv_client,
+            drain: None,
+            send_closed: send_closed.clone(),
+        };
+        if send_open.send(Ok(open)).is_err() {
+            debug!("Frontend closed");
+            continue;
+        }
+        shards.push(send_client);
+    }
+    // the endpoint is closed once every frontend has dropped its sender
+    drop(send_closed);
+    if shards.is_empty() {
         return;
     }
 
-    loop {
+    let hasher = RandomState::new();
+    for session_index in 0_u64.. {
         debug!("Listening for incoming sessions");
         let session = tokio::select! {
             session = endpoint.accept() => session,
@@ -48,54 +89,220 @@ pub(super) async fn start<P: WebTransportProtocol>(
         };
         debug!("Incoming session");
 
-        let (send_accepted, recv_accepted) = oneshot::channel();
-        let client_state = IncomingClient { recv_accepted };
-        if send_client.send(client_state).is_err() {
+        let Some(send_client) = pick_shard(&shards, &hasher, session_index) else {
             debug!("Frontend closed");
             return;
         };
 
-        tokio::spawn(handle_session::<P>(session, send_accepted));
+        if let Some(router) = &router {
+            tokio::spawn(route_session::<P>(
+                session,
+                router.clone(),
+                cipher.clone(),
+                recv_buffer_caps.clone(),
+                features,
+                fast_start,
+                send_client.clone(),
+            ));
+            continue;
+        }
+
+        let (send_accepted, recv_accepted) = oneshot::channel();
+        let client_state = IncomingClient { recv_accepted };
+        match send_client.try_send(client_state) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!("Incoming queue full, dropping session");
+                continue;
+            }
+            Err(TrySendError::Closed(_)) => {
+                debug!("Frontend closed, dropping session");
+                continue;
+            }
+        }
+
+        tokio::spawn(handle_session::<P>(
+            session,
+            cipher.clone(),
+            recv_buffer_caps.clone(),
+            features,
+            fast_start,
+            send_accepted,
+        ));
+    }
+}
+
+/// Picks the shard which handles a new session by hashing the index of the
+/// session, so that sessions are spread evenly across shards. If the frontend
+/// of that shard is closed, the next open shard is used instead.
+///
+/// Returns [`None`] if all frontends are closed.
+fn pick_shard<'a, T>(
+    shards: &'a [mpsc::Sender<T>],
+    hasher: &RandomState,
+    session_index: u64,
+) -> Option<&'a mpsc::Sender<T>> {
+    // the hash is uniform, so truncating it keeps it uniform
+    #[allow(clippy::cast_possible_truncation)]
+    let start = hasher.hash_one(session_index) as usize;
+    (0..shards.len())
+        .map(|offset| &shards[start.wrapping_add(offset) % shards.len()])
+        .find(|send_client| !send_client.is_closed())
+}
+
+async fn route_session<P: WebTransportProtocol>(
+    session: IncomingSession,
+    router: SessionRouter,
+    cipher: Option<Arc<LaneCipher>>,
+    recv_buffer_caps: RecvBufferCaps<P::Channel>,
+    features: Features,
+    fast_start: Option<usize>,
+    send_client: mpsc::Sender<IncomingClient<P>>,
+) where
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    let request = match session.await {
+        Ok(request) => request,
+        Err(err) => {
+            debug!("Failed to receive session request: {err:#}");
+            return;
+        }
+    };
+
+    if !router.claims(request.path()) {
+        debug!("Forwarding unclaimed session on {}", request.path());
+        router.forward(request);
+        return;
+    }
+
+    let (send_accepted, recv_accepted) = oneshot::channel();
+    let client_state = IncomingClient { recv_accepted };
+    match send_client.try_send(client_state) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            debug!(
+                "Incoming queue full, dropping session on {}",
+                request.path()
+            );
+            return;
+        }
+        Err(TrySendError::Closed(_)) => {
+            debug!("Frontend closed");
+            return;
+        }
     }
+
+    handle_request::<P>(
+        request,
+        cipher,
+        recv_buffer_caps,
+        features,
+        fast_start,
+        send_accepted,
+    )
+    .await;
 }
 
 async fn handle_session<P: WebTransportProtocol>(
     session: IncomingSession,
+    cipher: Option<Arc<LaneCipher>>,
+    recv_buffer_caps: RecvBufferCaps<P::Channel>,
+    features: Features,
+    fast_start: Option<usize>,
     send_accepted: oneshot::Sender<AcceptedClientResult<P>>,
 ) where
     P::C2S: TryFromBytes,
     P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
 {
-    let session = match session.await.map_err(WebTransportError::IncomingSession) {
-        Ok(session) => session,
+    match session.await.map_err(WebTransportError::IncomingSession) {
+        Ok(session) => {
+            handle_request::<P>(
+                session,
+                cipher,
+                recv_buffer_caps,
+                features,
+                fast_start,
+                send_accepted,
+            )
+            .await;
+        }
         Err(err) => {
             let _ = send_accepted.send(Err(err));
-            return;
         }
-    };
+    }
+}
 
+async fn handle_request<P: WebTransportProtocol>(
+    session: SessionRequest,
+    cipher: Option<Arc<LaneCipher>>,
+    recv_buffer_caps: RecvBufferCaps<P::Channel>,
+    features: Features,
+    fast_start: Option<usize>,
+    send_accepted: oneshot::Sender<AcceptedClientResult<P>>,
+) where
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
     let authority = session.authority();
     let path = session.path();
     debug!("Session accepted on {authority}{path}");
 
-    let (send_connected, recv_connected) = oneshot::channel();
+    let (send_response, recv_response) = oneshot::channel();
+    let (mut send_connected, recv_connected) = oneshot::channel();
+    // the frontend can send on the fast-start lane as soon as the session is
+    // accepted, so the send queue is created up front
+    let counters = shared::counters::<P::Channel>(&recv_buffer_caps);
+    let (send_s2c, mut recv_s2c) = mpsc::unbounded_channel();
+    let (send_early, recv_early) = mpsc::unbounded_channel();
+    let encode_order = SharedEncodeOrder::default();
     let accepted = AcceptedClient {
         authority: authority.to_owned(),
         path: path.to_owned(),
         origin: session.origin().map(ToOwned::to_owned),
         user_agent: session.user_agent().map(ToOwned::to_owned),
+        handshake_since: None,
+        send_response: Some(send_response),
+        responded: None,
         recv_connected,
+        fast_start,
+        send_s2c: send_s2c.clone(),
+        recv_early,
+        counters: counters.clone(),
+        encode_order: encode_order.clone(),
     };
     if send_accepted.send(Ok(accepted)).is_err() {
         debug!("Frontend closed");
         return;
     }
 
-    let conn = match session
-        .accept()
-        .await
-        .map_err(WebTransportError::AcceptSession)
-    {
+    match recv_response.await {
+        Ok(SessionResponse::Accept) => {}
+        Ok(SessionResponse::Forbidden) => {
+            debug!("Session rejected as forbidden");
+            session.forbidden().await;
+            return;
+        }
+        Ok(SessionResponse::NotFound) => {
+            debug!("Session rejected as not found");
+            session.not_found().await;
+            return;
+        }
+        Err(_) => {
+            debug!("Frontend closed");
+            return;
+        }
+    }
+
+    // the frontend drops its receiver if the handshake times out
+    let conn = tokio::select! {
+        result = session.accept() => result.map_err(WebTransportError::AcceptSession),
+        () = send_connected.closed() => {
+            debug!("Frontend gave up on handshake");
+            return;
+        }
+    };
+    let conn = match conn {
         Ok(conn) => conn,
         Err(err) => {
             let _ = send_connected.send(Err(err));
@@ -104,7 +311,36 @@ async fn handle_session<P: WebTransportProtocol>(
     };
 
     debug!("Establishing channels");
-    let channels_state = match shared::establish_channels::<P, P::S2C, P::C2S, true>(&conn).await {
+    let (send_lane_event, recv_lane_events) = mpsc::unbounded_channel();
+    let fast_start_cipher = cipher.clone();
+    let pump_fast_start = async {
+        if fast_start.is_none() {
+            return future::pending().await;
+        }
+        shared::pump_fast_start::<P, P::S2C, P::C2S>(
+            &conn,
+            &counters,
+            fast_start_cipher.as_deref(),
+            &mut recv_s2c,
+            &send_early,
+            &send_lane_event,
+        )
+        .await
+    };
+    let channels_state = tokio::select! {
+        result = shared::establish_channels::<P, P::S2C, P::C2S, true>(
+            &conn,
+            &counters,
+            &send_lane_event,
+            cipher,
+        ) => result,
+        err = pump_fast_start => Err(err),
+        () = send_connected.closed() => {
+            debug!("Frontend gave up on handshake");
+            return;
+        }
+    };
+    let channels_state = match channels_state {
         Ok(state) => state,
         Err(err) => {
             let _ = send_connected.send(Err(err));
@@ -112,16 +348,34 @@ async fn handle_session<P: WebTransportProtocol>(
         }
     };
 
+    // everything received on the fast-start lane has been passed to the
+    // frontend by now
+    drop(send_early);
+
     let (send_c2s, recv_c2s) = mpsc::unbounded_channel();
-    let (send_s2c, recv_s2c) = mpsc::unbounded_channel();
+    let replace_s2c = shared::replace_queue();
     let (send_info, recv_info) = mpsc::unbounded_channel();
+    let (send_quality, recv_quality) = mpsc::unbounded_channel();
+    let (send_migrate, recv_migrate) = mpsc::unbounded_channel();
     let (send_err, recv_err) = oneshot::channel();
     let connected = ConnectedClient {
         info: EndpointInfo::from_connection(&conn),
         recv_info,
         recv_c2s,
+        early: Vec::new(),
+        recv_lane_events,
         send_s2c,
+        replace_s2c: replace_s2c.clone(),
+        encode_order,
         recv_err,
+        counters: counters.clone(),
+        last_lane_stats: None,
+        send_quality,
+        quality: QualitySender::default(),
+        send_migrate,
+        limits: LimitsState::new(),
+        identity: None,
+        skipped_broadcasts: 0,
     };
     if send_connected.send(Ok(connected)).is_err() {
         debug!("Frontend closed");
@@ -135,6 +389,11 @@ async fn handle_session<P: WebTransportProtocol>(
         send_info,
         send_c2s,
         recv_s2c,
+        replace_s2c,
+        counters,
+        QualityLink::Send(recv_quality),
+        FeatureLink::Answer(features),
+        recv_migrate,
     )
     .await
     {
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, SystemTime},
+};
+
+use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+
+use crate::{ClientKey, WebTransportProtocol, WebTransportServer};
+
+use super::{ClientState, State, WebTransportError};
+
+/// What a [`Ban`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    /// Clients connecting from this IP address.
+    Addr(IpAddr),
+    /// Clients which the app identified using
+    /// [`WebTransportServer::set_identity`], e.g. by the ID of their account.
+    ///
+    /// [`WebTransportServer::set_identity`]: crate::WebTransportServer::set_identity
+    Identity(String),
+}
+
+/// Entry in a [`BanList`], which stops clients matching its target from
+/// connecting until it expires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    /// What this ban applies to.
+    pub target: BanTarget,
+    /// Why the client was banned.
+    pub reason: String,
+    /// When this ban stops applying.
+    pub expires_at: SystemTime,
+}
+
+/// Receiver of the changes made to a [`BanList`], used for persisting bans,
+/// e.g. to a database which is shared between servers or survives restarts.
+///
+/// The store is called from the same thread as the server, so it should not
+/// block. Bans which are read back from the store should be added using
+/// [`BanList::load`], which does not call the store again.
+pub trait BanStore: Send + Sync {
+    /// Records that `ban` was added, replacing any ban with the same target.
+    fn insert(&mut self, ban: &Ban);
+
+    /// Records that the ban on `target` was removed, or has expired.
+    fn remove(&mut self, target: &BanTarget);
+}
+
+/// Deny list of a [`WebTransportServer`].
+///
+/// A client whose address is banned is disconnected with
+/// [`WebTransportError::Banned`] as soon as its connection is established,
+/// before a [`ServerEvent::Connected`] or [`ServerEvent::PendingConnected`]
+/// is raised for it. A client whose identity is banned is disconnected when
+/// the app sets its identity using [`WebTransportServer::set_identity`].
+///
+/// Expired bans are ignored, but are only removed from the list by
+/// [`BanList::purge_expired`].
+///
+/// See [`WebTransportServer::kick`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`WebTransportError::Banned`]: crate::WebTransportError::Banned
+/// [`ServerEvent::Connected`]: crate::ServerEvent::Connected
+/// [`ServerEvent::PendingConnected`]: crate::ServerEvent::PendingConnected
+/// [`WebTransportServer::set_identity`]: crate::WebTransportServer::set_identity
+/// [`WebTransportServer::kick`]: crate::WebTransportServer::kick
+#[derive(Derivative, Default)]
+#[derivative(Debug)]
+pub struct BanList {
+    bans: HashMap<BanTarget, Ban>,
+    #[derivative(Debug = "ignore")]
+    store: Option<Box<dyn BanStore>>,
+}
+
+impl BanList {
+    /// Creates an empty list with no store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the store which changes to this list are recorded in.
+    ///
+    /// Bans which are already in the list are not passed to the new store.
+    pub fn set_store(&mut self, store: Option<Box<dyn BanStore>>) {
+        self.store = store;
+    }
+
+    /// Adds bans which were read from a [`BanStore`], without recording them
+    /// in the store again.
+    pub fn load(&mut self, bans: impl IntoIterator<Item = Ban>) {
+        self.bans
+            .extend(bans.into_iter().map(|ban| (ban.target.clone(), ban)));
+    }
+
+    /// Adds a ban, replacing any ban with the same target.
+    pub fn insert(&mut self, ban: Ban) {
+        if let Some(store) = &mut self.store {
+            store.insert(&ban);
+        }
+        self.bans.insert(ban.target.clone(), ban);
+    }
+
+    /// Removes the ban on `target`, returning it if there was one.
+    pub fn remove(&mut self, target: &BanTarget) -> Option<Ban> {
+        let ban = self.bans.remove(target)?;
+        if let Some(store) = &mut self.store {
+            store.remove(target);
+        }
+        Some(ban)
+    }
+
+    /// Gets the ban on `target`, if there is one which has not expired at
+    /// `now`.
+    #[must_use]
+    pub fn get(&self, target: &BanTarget, now: SystemTime) -> Option<&Ban> {
+        self.bans.get(target).filter(|ban| ban.expires_at > now)
+    }
+
+    /// Gets all bans in this list, including expired ones.
+    pub fn iter(&self) -> impl Iterator<Item = &Ban> {
+        self.bans.values()
+    }
+
+    /// Gets the number of bans in this list, including expired ones.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.bans.len()
+    }
+
+    /// Gets if this list has no bans.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bans.is_empty()
+    }
+
+    /// Removes the bans which have expired at `now`.
+    pub fn purge_expired(&mut self, now: SystemTime) {
+        let store = &mut self.store;
+        self.bans.retain(|target, ban| {
+            if ban.expires_at > now {
+                return true;
+            }
+            if let Some(store) = store.as_mut() {
+                store.remove(target);
+            }
+            false
+        });
+    }
+}
+
+impl<P> WebTransportServer<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Gets the deny list of this server.
+    #[must_use]
+    pub fn ban_list(&self) -> &BanList {
+        &self.bans
+    }
+
+    /// Gets mutable access to the deny list of this server, e.g. to load
+    /// persisted bans or lift a ban.
+    pub fn ban_list_mut(&mut self) -> &mut BanList {
+        &mut self.bans
+    }
+
+    /// Gets the identity set for a client using
+    /// [`WebTransportServer::set_identity`].
+    #[must_use]
+    pub fn identity(&self, client: ClientKey) -> Option<&str> {
+        let State::Open(server) = &self.state else {
+            return None;
+        };
+        match server.clients.get(client)? {
+            ClientState::Pending { connected, .. } | ClientState::Connected(connected) => {
+                connected.identity.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Sets the identity of a connected client, once the app has
+    /// authenticated it, e.g. to the ID of its account.
+    ///
+    /// If this identity is banned, the client is disconnected, and a
+    /// [`ServerEvent::Disconnected`] is raised for it with
+    /// [`WebTransportError::Banned`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not connected.
+    ///
+    /// [`ServerEvent::Disconnected`]: crate::ServerEvent::Disconnected
+    /// [`WebTransportError::Banned`]: crate::WebTransportError::Banned
+    pub fn set_identity(
+        &mut self,
+        client: ClientKey,
+        identity: impl Into<String>,
+    ) -> Result<(), WebTransportError<P>> {
+        let State::Open(server) = &mut self.state else {
+            return Err(WebTransportError::BackendClosed);
+        };
+        let state = server
+            .clients
+            .get_mut(client)
+            .ok_or(WebTransportError::NoClient(client))?;
+        let (ClientState::Pending { connected, .. } | ClientState::Connected(connected)) = state
+        else {
+            return Err(WebTransportError::NotConnected(client));
+        };
+
+        let identity = identity.into();
+        let target = BanTarget::Identity(identity.clone());
+        let banned = self
+            .bans
+            .get(&target, SystemTime::now())
+            .map(|ban| ban.reason.clone());
+        #[cfg(feature = "audit")]
+        if let Some((log, _)) = &mut self.audit {
+            log.identify(client, &identity);
+        }
+        connected.identity = Some(identity);
+        if let Some(reason) = banned {
+            *state = ClientState::Kicked {
+                reason,
+                banned: true,
+            };
+        }
+        Ok(())
+    }
+
+    /// Disconnects a client, and optionally bans it from reconnecting for a
+    /// duration.
+    ///
+    /// A [`ServerEvent::Disconnected`] is raised for the client with
+    /// [`WebTransportError::Kicked`] and the given reason.
+    ///
+    /// If `ban` is set, the client's IP address and its identity, if one was
+    /// set using [`WebTransportServer::set_identity`], are added to the
+    /// [`BanList`] of this server until the ban expires. Existing bans which
+    /// expire later are kept. Clients which have not established their
+    /// connection yet have no known address, so only their identity can be
+    /// banned. Other clients which are already connected from the same
+    /// address are not disconnected.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if there is no client with this
+    /// key.
+    ///
+    /// [`ServerEvent::Disconnected`]: crate::ServerEvent::Disconnected
+    /// [`WebTransportError::Kicked`]: crate::WebTransportError::Kicked
+    pub fn kick(
+        &mut self,
+        client: ClientKey,
+        reason: impl Into<String>,
+        ban: Option<Duration>,
+    ) -> Result<(), WebTransportError<P>> {
+        let State::Open(server) = &mut self.state else {
+            return Err(WebTransportError::BackendClosed);
+        };
+        let state = server
+            .clients
+            .get_mut(client)
+            .ok_or(WebTransportError::NoClient(client))?;
+        let reason = reason.into();
+
+        if let Some(duration) = ban {
+            let (addr, identity) = match state {
+                ClientState::Pending { connected, .. } | ClientState::Connected(connected) => (
+                    Some(connected.info.remote_addr.ip()),
+                    connected.identity.clone(),
+                ),
+                _ => (None, None),
+            };
+            let now = SystemTime::now();
+            let expires_at = now + duration;
+            let targets = addr
+                .map(BanTarget::Addr)
+                .into_iter()
+                .chain(identity.map(BanTarget::Identity));
+            for target in targets {
+                let outlasts = self
+                    .bans
+                    .get(&target, now)
+                    .is_some_and(|ban| ban.expires_at >= expires_at);
+                if !outlasts {
+                    self.bans.insert(Ban {
+                        target,
+                        reason: reason.clone(),
+                        expires_at,
+                    });
+                }
+            }
+        }
+
+        *state = ClientState::Kicked {
+            reason,
+            banned: false,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorded {
+        inserted: Vec<BanTarget>,
+        removed: Vec<BanTarget>,
+    }
+
+    struct Store(Arc<Mutex<Recorded>>);
+
+    impl BanStore for Store {
+        fn insert(&mut self, ban: &Ban) {
+            self.0.lock().unwrap().inserted.push(ban.target.clone());
+        }
+
+        fn remove(&mut self, target: &BanTarget) {
+            self.0.lock().unwrap().removed.push(target.clone());
+        }
+    }
+
+    fn ban(target: BanTarget, expires_at: SystemTime) -> Ban {
+        Ban {
+            target,
+            reason: "griefing".into(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn expired_bans_are_ignored() {
+        let now = SystemTime::now();
+        let addr = BanTarget::Addr(Ipv4Addr::LOCALHOST.into());
+        let mut bans = BanList::new();
+        bans.insert(ban(addr.clone(), now + Duration::from_secs(60)));
+
+        assert!(bans.get(&addr, now).is_some());
+        assert!(bans.get(&addr, now + Duration::from_secs(60)).is_none());
+        assert!(bans
+            .get(&BanTarget::Identity("player".into()), now)
+            .is_none());
+    }
+
+    #[test]
+    fn store_records_changes() {
+        let now = SystemTime::now();
+        let addr = BanTarget::Addr(Ipv4Addr::LOCALHOST.into());
+        let identity = BanTarget::Identity("player".into());
+        let recorded = Arc::new(Mutex::new(Recorded::default()));
+        let mut bans = BanList::new();
+        bans.set_store(Some(Box::new(Store(recorded.clone()))));
+
+        bans.load([ban(addr.clone(), now + Duration::from_secs(60))]);
+        bans.insert(ban(identity.clone(), now + Duration::from_secs(1)));
+        assert_eq!(2, bans.len());
+
+        bans.purge_expired(now + Duration::from_secs(30));
+        assert_eq!(1, bans.len());
+        assert!(bans.remove(&addr).is_some());
+        assert!(bans.is_empty());
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(vec![identity.clone()], recorded.inserted);
+        assert_eq!(vec![identity, addr], recorded.removed);
+    }
+}
@@ -0,0 +1,597 @@
+use std::{fmt::Debug, net::SocketAddr, time::Duration};
+
+use aeronet::{ChannelKey, ChannelKind, Features, OnMessageError};
+use derivative::Derivative;
+use wtransport::ServerConfig;
+
+use crate::{
+    ClientArena, ConnectionLimits, Identity, IncomingQueue, LaneSecurityConfig, LimitKind,
+    MemoryCap, RecvBufferCaps,
+};
+
+use super::DEFAULT_HANDSHAKE_TIMEOUT;
+
+/// Default value of [`ServerTimeouts::idle`].
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default value of [`ServerTimeouts::keep_alive`].
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Configuration used to open a [`WebTransportServer`], whose lanes are the
+/// channels of `C`.
+///
+/// Use [`WebTransportServerConfig::builder`] to configure the server, which
+/// is checked for invalid combinations of settings when the server is opened:
+///
+/// ```ignore
+/// let config = WebTransportServerConfig::builder()
+///     .bind_addr(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 25565)))
+///     .identity(Identity::from_pem(&cert, &key)?)
+///     .timeouts(ServerTimeouts {
+///         idle: Some(Duration::from_secs(10)),
+///         ..Default::default()
+///     })
+///     .build();
+/// let (server, backend) = WebTransportServer::<AppProtocol>::opening(config);
+/// ```
+///
+/// Settings which are not exposed by the builder can be set by building a
+/// [`ServerConfig`] directly, and converting it with
+/// [`WebTransportServerConfig::from_raw`]. A raw config is used as-is, and
+/// leaves the settings of the server unchanged.
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct WebTransportServerConfig<C> {
+    source: ConfigSource<C>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+enum ConfigSource<C> {
+    Raw(#[derivative(Debug = "ignore")] ServerConfig),
+    Built(WebTransportServerConfigBuilder<C>),
+}
+
+/// Builder for a [`WebTransportServerConfig`].
+///
+/// Only [`WebTransportServerConfigBuilder::bind_addr`] and
+/// [`WebTransportServerConfigBuilder::identity`] are required. Settings which
+/// are not set keep their current value on the server.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Default(bound = ""))]
+pub struct WebTransportServerConfigBuilder<C> {
+    bind_addr: Option<SocketAddr>,
+    identity: Option<Identity>,
+    settings: Option<ServerSettings<C>>,
+    lanes: Option<LaneConfig<C>>,
+    limits: Option<ConnectionLimits>,
+    timeouts: Option<ServerTimeouts>,
+}
+
+/// Settings of a [`WebTransportServer`] which are not tied to its endpoint.
+///
+/// The server keeps its current settings in this struct, which can be read
+/// using [`WebTransportServer::settings`]. Each setting can be changed using
+/// the matching setter on the server, or all of them at once when the server
+/// opens using [`WebTransportServerConfigBuilder::settings`], in which case
+/// they are checked along with the rest of the config.
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`WebTransportServer::settings`]: crate::WebTransportServer::settings
+#[derive(Derivative)]
+#[derivative(
+    Debug(bound = "C: Debug"),
+    Clone(bound = "C: Clone"),
+    Default(bound = "")
+)]
+pub struct ServerSettings<C> {
+    /// See [`WebTransportServer::set_lane_stats_interval`].
+    ///
+    /// [`WebTransportServer::set_lane_stats_interval`]: crate::WebTransportServer::set_lane_stats_interval
+    pub lane_stats_interval: Option<Duration>,
+    /// See [`WebTransportServer::set_recv_progress_min_len`].
+    ///
+    /// [`WebTransportServer::set_recv_progress_min_len`]: crate::WebTransportServer::set_recv_progress_min_len
+    pub recv_progress_min_len: Option<usize>,
+    /// See [`WebTransportServer::set_quality_report_interval`].
+    ///
+    /// [`WebTransportServer::set_quality_report_interval`]: crate::WebTransportServer::set_quality_report_interval
+    pub quality_report_interval: Option<Duration>,
+    /// See [`WebTransportServer::set_handshake_timeout`].
+    ///
+    /// Defaults to [`DEFAULT_HANDSHAKE_TIMEOUT`].
+    ///
+    /// [`WebTransportServer::set_handshake_timeout`]: crate::WebTransportServer::set_handshake_timeout
+    #[derivative(Default(value = "Some(DEFAULT_HANDSHAKE_TIMEOUT)"))]
+    pub handshake_timeout: Option<Duration>,
+    /// See [`WebTransportServer::set_limits`].
+    ///
+    /// [`WebTransportServer::set_limits`]: crate::WebTransportServer::set_limits
+    pub limits: ConnectionLimits,
+    /// See [`WebTransportServer::set_memory_cap`].
+    ///
+    /// [`WebTransportServer::set_memory_cap`]: crate::WebTransportServer::set_memory_cap
+    pub memory_cap: Option<MemoryCap>,
+    /// See [`WebTransportServer::set_arena`].
+    ///
+    /// [`WebTransportServer::set_arena`]: crate::WebTransportServer::set_arena
+    pub arena: ClientArena,
+    /// See [`WebTransportServer::set_incoming_queue`].
+    ///
+    /// [`WebTransportServer::set_incoming_queue`]: crate::WebTransportServer::set_incoming_queue
+    pub incoming_queue: IncomingQueue,
+    /// See [`WebTransportServer::set_on_serialize_error`].
+    ///
+    /// Defaults to [`OnMessageError::EmitEventOnly`].
+    ///
+    /// [`WebTransportServer::set_on_serialize_error`]: crate::WebTransportServer::set_on_serialize_error
+    #[derivative(Default(value = "OnMessageError::EmitEventOnly"))]
+    pub on_serialize_error: OnMessageError,
+    /// See [`WebTransportServer::set_on_deserialize_error`].
+    ///
+    /// Defaults to [`OnMessageError::DisconnectClient`].
+    ///
+    /// [`WebTransportServer::set_on_deserialize_error`]: crate::WebTransportServer::set_on_deserialize_error
+    #[derivative(Default(value = "OnMessageError::DisconnectClient"))]
+    pub on_deserialize_error: OnMessageError,
+    /// See [`WebTransportServer::set_manual_accept`].
+    ///
+    /// [`WebTransportServer::set_manual_accept`]: crate::WebTransportServer::set_manual_accept
+    pub manual_accept: bool,
+    /// See [`WebTransportServer::set_manual_admit`].
+    ///
+    /// [`WebTransportServer::set_manual_admit`]: crate::WebTransportServer::set_manual_admit
+    pub manual_admit: bool,
+    /// See [`WebTransportServer::set_lane_security`].
+    ///
+    /// [`WebTransportServer::set_lane_security`]: crate::WebTransportServer::set_lane_security
+    pub lane_security: Option<LaneSecurityConfig<C>>,
+    /// See [`WebTransportServer::set_recv_buffer_caps`].
+    ///
+    /// [`WebTransportServer::set_recv_buffer_caps`]: crate::WebTransportServer::set_recv_buffer_caps
+    pub recv_buffer_caps: RecvBufferCaps<C>,
+    /// See [`WebTransportServer::set_features`].
+    ///
+    /// [`WebTransportServer::set_features`]: crate::WebTransportServer::set_features
+    pub features: Features,
+    /// See [`WebTransportServer::set_fast_start_lane`].
+    ///
+    /// [`WebTransportServer::set_fast_start_lane`]: crate::WebTransportServer::set_fast_start_lane
+    pub fast_start_lane: Option<C>,
+}
+
+/// Settings of the lanes of a server.
+///
+/// See [`WebTransportServerConfigBuilder::lanes`].
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""), Default(bound = ""))]
+pub struct LaneConfig<C> {
+    /// See [`WebTransportServer::set_lane_security`].
+    ///
+    /// [`WebTransportServer::set_lane_security`]: crate::WebTransportServer::set_lane_security
+    pub security: Option<LaneSecurityConfig<C>>,
+    /// See [`WebTransportServer::set_recv_buffer_caps`].
+    ///
+    /// [`WebTransportServer::set_recv_buffer_caps`]: crate::WebTransportServer::set_recv_buffer_caps
+    pub recv_buffer_caps: RecvBufferCaps<C>,
+}
+
+/// Timeouts of the connections of a server.
+///
+/// See [`WebTransportServerConfigBuilder::timeouts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServerTimeouts {
+    /// Time after which a connection which has not received anything from its
+    /// peer is closed, or [`None`] to never close idle connections.
+    ///
+    /// Defaults to [`DEFAULT_IDLE_TIMEOUT`].
+    pub idle: Option<Duration>,
+    /// Interval at which keep-alive packets are sent on connections which are
+    /// otherwise idle, or [`None`] to never send them.
+    ///
+    /// This must be shorter than the idle timeout, otherwise connections which
+    /// only send keep-alive packets would still time out.
+    ///
+    /// Defaults to [`DEFAULT_KEEP_ALIVE_INTERVAL`].
+    pub keep_alive: Option<Duration>,
+    /// See [`WebTransportServer::set_handshake_timeout`].
+    ///
+    /// Defaults to [`DEFAULT_HANDSHAKE_TIMEOUT`].
+    ///
+    /// [`WebTransportServer::set_handshake_timeout`]: crate::WebTransportServer::set_handshake_timeout
+    pub handshake: Option<Duration>,
+}
+
+impl Default for ServerTimeouts {
+    fn default() -> Self {
+        Self {
+            idle: Some(DEFAULT_IDLE_TIMEOUT),
+            keep_alive: Some(DEFAULT_KEEP_ALIVE_INTERVAL),
+            handshake: Some(DEFAULT_HANDSHAKE_TIMEOUT),
+        }
+    }
+}
+
+/// Error that occurs when opening a server with an invalid
+/// [`WebTransportServerConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ServerConfigError {
+    /// The protocol does not have any lanes to send messages on.
+    #[error("protocol has no lanes")]
+    NoLanes,
+    /// No address to listen on was configured.
+    #[error("no bind address configured")]
+    NoBindAddr,
+    /// No identity to present to clients was configured.
+    #[error("no identity configured")]
+    NoIdentity,
+    /// The idle timeout is not longer than the keep-alive interval.
+    #[error("idle timeout of {idle:?} is not longer than keep-alive interval of {keep_alive:?}")]
+    IdleTimeoutBelowKeepAlive {
+        /// The configured idle timeout.
+        idle: Duration,
+        /// The configured keep-alive interval.
+        keep_alive: Duration,
+    },
+    /// The idle timeout is too long to be sent to clients.
+    #[error("idle timeout of {0:?} is too long")]
+    IdleTimeoutTooLong(Duration),
+    /// The keep-alive interval is zero.
+    #[error("keep-alive interval is zero")]
+    ZeroKeepAlive,
+    /// The handshake timeout is zero, so no client could ever connect.
+    #[error("handshake timeout is zero")]
+    ZeroHandshakeTimeout,
+    /// The soft limit of a resource is above its hard limit, so a warning
+    /// would never be raised before the client is disconnected.
+    #[error("soft {0:?} limit is above its hard limit")]
+    SoftLimitAboveHard(LimitKind),
+    /// The fast-start lane is not [`ChannelKind::Unreliable`].
+    ///
+    /// See [`WebTransportServer::set_fast_start_lane`].
+    ///
+    /// [`WebTransportServer::set_fast_start_lane`]: crate::WebTransportServer::set_fast_start_lane
+    #[error("fast-start lane is reliable")]
+    FastStartLaneReliable,
+}
+
+/// Settings of a [`WebTransportServerConfig`] which are applied to the
+/// frontend of the server when it is opened.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "C: Clone"))]
+pub(super) struct FrontendSettings<C> {
+    pub settings: Option<ServerSettings<C>>,
+    pub lanes: Option<LaneConfig<C>>,
+    pub limits: Option<ConnectionLimits>,
+    pub handshake_timeout: Option<Option<Duration>>,
+}
+
+impl<C> WebTransportServerConfig<C> {
+    /// Creates a builder with no settings.
+    #[must_use]
+    pub fn builder() -> WebTransportServerConfigBuilder<C> {
+        WebTransportServerConfigBuilder::default()
+    }
+
+    /// Creates a config which uses `config` as-is.
+    ///
+    /// This is an escape hatch for settings which are not exposed by
+    /// [`WebTransportServerConfig::builder`]. Apart from the protocol having
+    /// lanes, nothing is checked before the server is opened.
+    #[must_use]
+    pub fn from_raw(config: ServerConfig) -> Self {
+        Self {
+            source: ConfigSource::Raw(config),
+        }
+    }
+}
+
+impl<C: ChannelKey> WebTransportServerConfig<C> {
+    /// Checks this config, and splits it into the config of the endpoint and
+    /// the settings applied to the frontend.
+    pub(super) fn resolve(self) -> Result<(ServerConfig, FrontendSettings<C>), ServerConfigError> {
+        if C::ALL.is_empty() {
+            return Err(ServerConfigError::NoLanes);
+        }
+        match self.source {
+            ConfigSource::Raw(config) => Ok((
+                config,
+                FrontendSettings {
+                    settings: None,
+                    lanes: None,
+                    limits: None,
+                    handshake_timeout: None,
+                },
+            )),
+            ConfigSource::Built(builder) => builder.resolve(),
+        }
+    }
+}
+
+impl<C> From<ServerConfig> for WebTransportServerConfig<C> {
+    fn from(value: ServerConfig) -> Self {
+        Self::from_raw(value)
+    }
+}
+
+impl<C> From<WebTransportServerConfigBuilder<C>> for WebTransportServerConfig<C> {
+    fn from(value: WebTransportServerConfigBuilder<C>) -> Self {
+        value.build()
+    }
+}
+
+impl<C> WebTransportServerConfigBuilder<C> {
+    /// Sets the address that the server listens on.
+    #[must_use]
+    pub fn bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.bind_addr = Some(addr);
+        self
+    }
+
+    /// Sets the identity that the server presents to its clients.
+    #[must_use]
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Sets all settings of the server which are not tied to its endpoint.
+    ///
+    /// These replace the current settings of the server when it opens. The
+    /// lanes, limits and timeouts set on this builder take precedence over the
+    /// matching fields of `settings`.
+    #[must_use]
+    pub fn settings(mut self, settings: ServerSettings<C>) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Sets the lane security and receive buffer caps of the server.
+    #[must_use]
+    pub fn lanes(mut self, lanes: LaneConfig<C>) -> Self {
+        self.lanes = Some(lanes);
+        self
+    }
+
+    /// Sets the limits on the resources used by each client.
+    ///
+    /// See [`WebTransportServer::set_limits`].
+    ///
+    /// [`WebTransportServer::set_limits`]: crate::WebTransportServer::set_limits
+    #[must_use]
+    pub fn limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Sets the timeouts of the connections of the server.
+    ///
+    /// If this is not set, [`ServerTimeouts::default`] is used for the
+    /// endpoint, and the handshake timeout of the server is unchanged.
+    #[must_use]
+    pub fn timeouts(mut self, timeouts: ServerTimeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// Finishes building the config.
+    ///
+    /// The config is only checked when the server is opened.
+    #[must_use]
+    pub fn build(self) -> WebTransportServerConfig<C> {
+        WebTransportServerConfig {
+            source: ConfigSource::Built(self),
+        }
+    }
+}
+
+impl<C: ChannelKey> WebTransportServerConfigBuilder<C> {
+    fn validate(&self) -> Result<(), ServerConfigError> {
+        if self.bind_addr.is_none() {
+            return Err(ServerConfigError::NoBindAddr);
+        }
+        if self.identity.is_none() {
+            return Err(ServerConfigError::NoIdentity);
+        }
+
+        let timeouts = self.timeouts.unwrap_or_default();
+        if timeouts.keep_alive == Some(Duration::ZERO) {
+            return Err(ServerConfigError::ZeroKeepAlive);
+        }
+        if timeouts.handshake == Some(Duration::ZERO) {
+            return Err(ServerConfigError::ZeroHandshakeTimeout);
+        }
+        if let (Some(idle), Some(keep_alive)) = (timeouts.idle, timeouts.keep_alive) {
+            if idle <= keep_alive {
+                return Err(ServerConfigError::IdleTimeoutBelowKeepAlive { idle, keep_alive });
+            }
+        }
+
+        if let Some(settings) = &self.settings {
+            if self.timeouts.is_none() && settings.handshake_timeout == Some(Duration::ZERO) {
+                return Err(ServerConfigError::ZeroHandshakeTimeout);
+            }
+            if self.limits.is_none() {
+                validate_limits(&settings.limits)?;
+            }
+            if let Some(lane) = &settings.fast_start_lane {
+                if lane.kind() != ChannelKind::Unreliable {
+                    return Err(ServerConfigError::FastStartLaneReliable);
+                }
+            }
+        }
+        if let Some(limits) = &self.limits {
+            validate_limits(limits)?;
+        }
+        Ok(())
+    }
+
+    fn resolve(self) -> Result<(ServerConfig, FrontendSettings<C>), ServerConfigError> {
+        self.validate()?;
+        let bind_addr = self.bind_addr.expect("should have been validated");
+        let identity = self.identity.expect("should have been validated");
+        let timeouts = self.timeouts.unwrap_or_default();
+        let config = ServerConfig::builder()
+            .with_bind_address(bind_addr)
+            .with_certificate(identity.into())
+            .keep_alive_interval(timeouts.keep_alive)
+            .max_idle_timeout(timeouts.idle)
+            .map_err(|_| ServerConfigError::IdleTimeoutTooLong(timeouts.idle.unwrap_or_default()))?
+            .build();
+        Ok((
+            config,
+            FrontendSettings {
+                settings: self.settings,
+                lanes: self.lanes,
+                limits: self.limits,
+                handshake_timeout: self.timeouts.map(|timeouts| timeouts.handshake),
+            },
+        ))
+    }
+}
+
+fn validate_limits(limits: &ConnectionLimits) -> Result<(), ServerConfigError> {
+    for (kind, limit) in [
+        (LimitKind::RecvMsgSize, limits.recv_msg_size),
+        (LimitKind::RecvRate, limits.recv_rate),
+        (LimitKind::SendQueue, limits.send_queue),
+        (LimitKind::EarlyMsgs, limits.early_msgs),
+        (LimitKind::EarlyBytes, limits.early_bytes),
+    ] {
+        if let (Some(soft), Some(hard)) = (limit.soft, limit.hard) {
+            if soft > hard {
+                return Err(ServerConfigError::SoftLimitAboveHard(kind));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Limit;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChannelKey)]
+    enum Lane {
+        #[channel_kind(Unreliable)]
+        State,
+        #[channel_kind(ReliableOrdered)]
+        Events,
+    }
+
+    fn builder() -> WebTransportServerConfigBuilder<Lane> {
+        WebTransportServerConfigBuilder::default()
+            .bind_addr(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .identity(Identity::from_der(vec![vec![0]], vec![0]))
+    }
+
+    #[test]
+    fn validate_required() {
+        assert_eq!(Ok(()), builder().validate());
+        assert_eq!(
+            Err(ServerConfigError::NoBindAddr),
+            WebTransportServerConfigBuilder::<Lane>::default().validate()
+        );
+        assert_eq!(
+            Err(ServerConfigError::NoIdentity),
+            WebTransportServerConfigBuilder::<Lane>::default()
+                .bind_addr(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .validate()
+        );
+    }
+
+    #[test]
+    fn validate_timeouts() {
+        let with_timeouts = |idle, keep_alive| {
+            builder()
+                .timeouts(ServerTimeouts {
+                    idle,
+                    keep_alive,
+                    ..Default::default()
+                })
+                .validate()
+        };
+        let secs = |secs| Some(Duration::from_secs(secs));
+
+        assert_eq!(Ok(()), with_timeouts(secs(10), secs(5)));
+        assert_eq!(Ok(()), with_timeouts(None, secs(5)));
+        assert_eq!(Ok(()), with_timeouts(secs(10), None));
+        assert_eq!(
+            Err(ServerConfigError::IdleTimeoutBelowKeepAlive {
+                idle: Duration::from_secs(5),
+                keep_alive: Duration::from_secs(5),
+            }),
+            with_timeouts(secs(5), secs(5))
+        );
+        assert_eq!(
+            Err(ServerConfigError::ZeroKeepAlive),
+            with_timeouts(secs(10), secs(0))
+        );
+    }
+
+    #[test]
+    fn validate_limits() {
+        let limits = ConnectionLimits {
+            send_queue: Limit {
+                soft: Some(100),
+                hard: Some(10),
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(ServerConfigError::SoftLimitAboveHard(LimitKind::SendQueue)),
+            builder().limits(limits.clone()).validate()
+        );
+        assert_eq!(
+            Err(ServerConfigError::SoftLimitAboveHard(LimitKind::SendQueue)),
+            builder()
+                .settings(ServerSettings {
+                    limits: limits.clone(),
+                    ..Default::default()
+                })
+                .validate()
+        );
+        assert_eq!(
+            Ok(()),
+            builder()
+                .settings(ServerSettings {
+                    limits,
+                    ..Default::default()
+                })
+                .limits(ConnectionLimits::default())
+                .validate()
+        );
+    }
+
+    #[test]
+    fn validate_settings() {
+        let with_settings = |settings| builder().settings(settings).validate();
+
+        assert_eq!(Ok(()), with_settings(ServerSettings::default()));
+        assert_eq!(
+            Ok(()),
+            with_settings(ServerSettings {
+                fast_start_lane: Some(Lane::State),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            Err(ServerConfigError::FastStartLaneReliable),
+            with_settings(ServerSettings {
+                fast_start_lane: Some(Lane::Events),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            Err(ServerConfigError::ZeroHandshakeTimeout),
+            with_settings(ServerSettings {
+                handshake_timeout: Some(Duration::ZERO),
+                ..Default::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    time::SystemTime,
+};
+
+use aeronet::{ChannelKey, OnChannel, TryFromBytes, TryIntoBytes};
+
+use crate::{ClientKey, EndpointInfo, WebTransportProtocol};
+
+use super::{ServerEvent, WebTransportError};
+
+/// Record of every client disconnect from a [`WebTransportServer`], used for
+/// finding out why a client was disconnected after the fact.
+///
+/// For every disconnected client, this stores a [`DisconnectRecord`]
+/// containing:
+/// * when the client disconnected
+/// * the full error chain of the cause of the disconnect
+/// * the last known connection info of the client
+/// * the last few events raised for the client before it disconnected
+///
+/// Only the most recent records are kept, up to a maximum set when the log is
+/// created.
+///
+/// Enable this using [`WebTransportServer::enable_disconnect_log`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`WebTransportServer::enable_disconnect_log`]: crate::WebTransportServer::enable_disconnect_log
+#[derive(Debug, Clone)]
+pub struct DisconnectLog {
+    max_records: usize,
+    max_events: usize,
+    records: VecDeque<DisconnectRecord>,
+    history: HashMap<ClientKey, VecDeque<LoggedEvent>>,
+}
+
+/// Info on a single client disconnect, stored in a [`DisconnectLog`].
+#[derive(Debug, Clone)]
+pub struct DisconnectRecord {
+    /// The key of the client.
+    pub client: ClientKey,
+    /// When the server found out about the disconnect.
+    pub at: SystemTime,
+    /// The cause of the disconnect, followed by each of its
+    /// [`Error::source`]s.
+    pub causes: Vec<String>,
+    /// The last known connection info of the client, if it was connected.
+    pub info: Option<EndpointInfo>,
+    /// The last events raised for this client before it disconnected, oldest
+    /// first.
+    pub recent_events: Vec<LoggedEvent>,
+}
+
+/// An event raised for a client, stored in a [`DisconnectRecord`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedEvent {
+    /// When the event was raised.
+    pub at: SystemTime,
+    /// What kind of event was raised.
+    pub kind: LoggedEventKind,
+}
+
+/// Kind of a [`LoggedEvent`].
+///
+/// Message contents are not stored in the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoggedEventKind {
+    /// See [`ServerEvent::Incoming`].
+    Incoming,
+    /// See [`ServerEvent::Requested`].
+    Requested {
+        /// See [`ServerEvent::Requested::authority`].
+        authority: String,
+        /// See [`ServerEvent::Requested::path`].
+        path: String,
+    },
+    /// See [`ServerEvent::Accepted`].
+    Accepted {
+        /// See [`ServerEvent::Accepted::authority`].
+        authority: String,
+        /// See [`ServerEvent::Accepted::path`].
+        path: String,
+    },
+    /// See [`ServerEvent::PendingConnected`].
+    PendingConnected,
+    /// See [`ServerEvent::Connected`].
+    Connected,
+    /// See [`ServerEvent::Recv`].
+    Recv,
+    /// See [`ServerEvent::ChecksumMismatch`].
+    ChecksumMismatch,
+    /// See [`ServerEvent::MessageRejected`].
+    MessageRejected,
+    /// See [`ServerEvent::MessageError`].
+    MessageError,
+    /// See [`ServerEvent::HandoverIssued`].
+    HandoverIssued,
+    /// See [`ServerEvent::HandoverAccepted`].
+    HandoverAccepted,
+    /// See [`ServerEvent::HandoverRejected`].
+    HandoverRejected,
+    /// See [`ServerEvent::StreamClosed`].
+    StreamClosed {
+        /// [`ChannelKey::index`] of [`ServerEvent::StreamClosed::channel`].
+        ///
+        /// [`ChannelKey::index`]: aeronet::ChannelKey::index
+        lane: usize,
+    },
+    /// See [`ServerEvent::LaneMigrated`].
+    LaneMigrated {
+        /// [`ChannelKey::index`] of [`ServerEvent::LaneMigrated::channel`].
+        ///
+        /// [`ChannelKey::index`]: aeronet::ChannelKey::index
+        channel: usize,
+        /// [`ChannelKey::index`] of [`ServerEvent::LaneMigrated::lane`].
+        ///
+        /// [`ChannelKey::index`]: aeronet::ChannelKey::index
+        lane: usize,
+    },
+}
+
+impl DisconnectLog {
+    /// Creates a new empty log.
+    ///
+    /// * `max_records` is the maximum number of [`DisconnectRecord`]s stored
+    ///   before the oldest ones are discarded
+    /// * `max_events` is the maximum number of recent events stored for each
+    ///   client
+    #[must_use]
+    pub fn new(max_records: usize, max_events: usize) -> Self {
+        Self {
+            max_records,
+            max_events,
+            records: VecDeque::new(),
+            history: HashMap::new(),
+        }
+    }
+
+    /// Gets the maximum number of records stored in this log.
+    #[must_use]
+    pub fn max_records(&self) -> usize {
+        self.max_records
+    }
+
+    /// Gets the maximum number of recent events stored for each client.
+    #[must_use]
+    pub fn max_events(&self) -> usize {
+        self.max_events
+    }
+
+    /// Gets all records stored in this log, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &DisconnectRecord> {
+        self.records.iter()
+    }
+
+    /// Gets the most recent record for the given client.
+    #[must_use]
+    pub fn get(&self, client: ClientKey) -> Option<&DisconnectRecord> {
+        self.records
+            .iter()
+            .rev()
+            .find(|record| record.client == client)
+    }
+
+    /// Removes all records from this log.
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Forgets the recent events of every client, e.g. because the server was
+    /// closed.
+    ///
+    /// The records of clients which already disconnected are kept.
+    pub(super) fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    pub(super) fn observe<P>(
+        &mut self,
+        event: &ServerEvent<P>,
+        info: impl FnOnce(ClientKey) -> Option<EndpointInfo>,
+        error_chain: fn(&WebTransportError<P>) -> Vec<String>,
+    ) where
+        P: WebTransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        let (client, kind) = match event {
+            ServerEvent::Incoming { client } => (*client, LoggedEventKind::Incoming),
+            ServerEvent::Requested {
+                client,
+                authority,
+                path,
+                ..
+            } => (
+                *client,
+                LoggedEventKind::Requested {
+                    authority: authority.clone(),
+                    path: path.clone(),
+                },
+            ),
+            ServerEvent::Accepted {
+                client,
+                authority,
+                path,
+                ..
+            } => (
+                *client,
+                LoggedEventKind::Accepted {
+                    authority: authority.clone(),
+                    path: path.clone(),
+                },
+            ),
+            ServerEvent::PendingConnected { client } => {
+                (*client, LoggedEventKind::PendingConnected)
+            }
+            ServerEvent::Connected { client } => (*client, LoggedEventKind::Connected),
+            ServerEvent::Recv { client, .. } => (*client, LoggedEventKind::Recv),
+            ServerEvent::ChecksumMismatch { client, .. } => {
+                (*client, LoggedEventKind::ChecksumMismatch)
+            }
+            ServerEvent::MessageRejected { client, .. } => {
+                (*client, LoggedEventKind::MessageRejected)
+            }
+            ServerEvent::MessageError { client, .. } => (*client, LoggedEventKind::MessageError),
+            ServerEvent::HandoverIssued { client, .. } => {
+                (*client, LoggedEventKind::HandoverIssued)
+            }
+            ServerEvent::HandoverAccepted { client, .. } => {
+                (*client, LoggedEventKind::HandoverAccepted)
+            }
+            ServerEvent::HandoverRejected { client, .. } => {
+                (*client, LoggedEventKind::HandoverRejected)
+            }
+            ServerEvent::StreamClosed { client, channel } => (
+                *client,
+                LoggedEventKind::StreamClosed {
+                    lane: channel.index(),
+                },
+            ),
+            ServerEvent::LaneMigrated {
+                client,
+                channel,
+                lane,
+            } => (
+                *client,
+                LoggedEventKind::LaneMigrated {
+                    channel: channel.index(),
+                    lane: lane.index(),
+                },
+            ),
+            ServerEvent::Disconnected { client, cause } => {
+                let recent_events = self
+                    .history
+                    .remove(client)
+                    .map(Vec::from)
+                    .unwrap_or_default();
+                self.push_record(DisconnectRecord {
+                    client: *client,
+                    at: SystemTime::now(),
+                    causes: error_chain(cause),
+                    info: info(*client),
+                    recent_events,
+                });
+                return;
+            }
+            ServerEvent::Opened
+            | ServerEvent::RecvProgress { .. }
+            | ServerEvent::LaneStats { .. }
+            | ServerEvent::LaneMigrationRejected { .. }
+            | ServerEvent::LimitWarning { .. }
+            | ServerEvent::Drained
+            | ServerEvent::Closed { .. }
+            | ServerEvent::Custom { .. } => return,
+        };
+
+        if self.max_events == 0 {
+            return;
+        }
+        let history = self.history.entry(client).or_default();
+        if history.len() >= self.max_events {
+            history.pop_front();
+        }
+        history.push_back(LoggedEvent {
+            at: SystemTime::now(),
+            kind,
+        });
+    }
+
+    fn push_record(&mut self, record: DisconnectRecord) {
+        if self.max_records == 0 {
+            return;
+        }
+        if self.records.len() >= self.max_records {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+/// Formats an error followed by each of its sources.
+pub(super) fn error_chain<E: Error>(err: &E) -> Vec<String> {
+    let mut causes = vec![err.to_string()];
+    let mut cur = err.source();
+    while let Some(source) = cur {
+        causes.push(source.to_string());
+        cur = source.source();
+    }
+    causes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use aeronet::TransportProtocol;
+    use slotmap::KeyData;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChannelKey)]
+    #[channel_kind(Unreliable)]
+    struct Channel;
+
+    #[derive(Debug, Clone, OnChannel)]
+    #[channel_type(Channel)]
+    #[on_channel(Channel)]
+    struct Msg;
+
+    impl TryIntoBytes for Msg {
+        type Output<'a> = [u8; 0];
+
+        type Error = Infallible;
+
+        fn try_into_bytes(&self) -> Result<Self::Output<'_>, Self::Error> {
+            Ok([])
+        }
+    }
+
+    impl TryFromBytes for Msg {
+        type Error = Infallible;
+
+        fn try_from_bytes(_: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+    }
+
+    struct Protocol;
+
+    impl TransportProtocol for Protocol {
+        type C2S = Msg;
+        type S2C = Msg;
+    }
+
+    impl WebTransportProtocol for Protocol {
+        type Channel = Channel;
+    }
+
+    fn observe(log: &mut DisconnectLog, event: &ServerEvent<Protocol>) {
+        log.observe(event, |_| None, error_chain);
+    }
+
+    #[test]
+    fn clear_history_keeps_records() {
+        let mut log = DisconnectLog::new(4, 4);
+        let first = ClientKey::from(KeyData::from_ffi(1));
+        let second = ClientKey::from(KeyData::from_ffi(2));
+        observe(&mut log, &ServerEvent::Connected { client: first });
+        observe(
+            &mut log,
+            &ServerEvent::Disconnected {
+                client: first,
+                cause: WebTransportError::<Protocol>::ForceDisconnect,
+            },
+        );
+        observe(&mut log, &ServerEvent::Connected { client: second });
+
+        // the server closed, so `second` will never get a disconnect event,
+        // and its key may be reused by a client of the next session
+        log.clear_history();
+        observe(
+            &mut log,
+            &ServerEvent::Disconnected {
+                client: second,
+                cause: WebTransportError::<Protocol>::ForceDisconnect,
+            },
+        );
+
+        let kinds = |client| {
+            log.get(client)
+                .unwrap()
+                .recent_events
+                .iter()
+                .map(|event| event.kind.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![LoggedEventKind::Connected], kinds(first));
+        assert_eq!(Vec::<LoggedEventKind>::new(), kinds(second));
+    }
+}
@@ -0,0 +1,174 @@
+use std::cmp::Reverse;
+
+use crate::{ClientKey, EndpointInfo};
+
+/// Decides which clients a [`WebTransportServer`] disconnects first when it is
+/// overloaded.
+///
+/// When the server goes over one of its resource caps, it builds the list of
+/// clients which can be evicted, and asks the policy to pick the clients to
+/// disconnect, in the order that they should be disconnected. The server then
+/// disconnects them in that order until it is no longer overloaded, and raises
+/// a [`ServerEvent::Disconnected`] for each of them with
+/// [`WebTransportError::Evicted`]. Clients which the policy does not return are
+/// kept, even if the server stays overloaded.
+///
+/// This is implemented for closures, so a policy can use app-specific
+/// knowledge, e.g. to drop idle spectators before players:
+///
+/// ```ignore
+/// server.set_eviction_policy(move |_: &Overload, candidates: &[EvictionCandidate]| {
+///     let spectators = spectators.lock().unwrap();
+///     let mut candidates = candidates
+///         .iter()
+///         .filter(|candidate| spectators.contains(&candidate.client))
+///         .collect::<Vec<_>>();
+///     candidates.sort_by_key(|candidate| candidate.info.stats.msgs_recv);
+///     candidates.into_iter().map(|candidate| candidate.client).collect()
+/// });
+/// ```
+///
+/// The policy is called from [`TransportServer::recv`], so it should not
+/// block. If no policy is set, [`LeastActiveFirst`] is used.
+///
+/// See [`WebTransportServer::set_eviction_policy`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`ServerEvent::Disconnected`]: crate::ServerEvent::Disconnected
+/// [`WebTransportError::Evicted`]: crate::WebTransportError::Evicted
+/// [`TransportServer::recv`]: aeronet::TransportServer::recv
+/// [`WebTransportServer::set_eviction_policy`]: crate::WebTransportServer::set_eviction_policy
+pub trait EvictionPolicy: Send + Sync {
+    /// Picks the clients to evict from `candidates` because of `overload`, in
+    /// the order that they should be evicted.
+    fn select(&mut self, overload: &Overload, candidates: &[EvictionCandidate]) -> Vec<ClientKey>;
+}
+
+impl<F> EvictionPolicy for F
+where
+    F: FnMut(&Overload, &[EvictionCandidate]) -> Vec<ClientKey> + Send + Sync,
+{
+    fn select(&mut self, overload: &Overload, candidates: &[EvictionCandidate]) -> Vec<ClientKey> {
+        self(overload, candidates)
+    }
+}
+
+/// Resource cap of a [`WebTransportServer`] which was exceeded, causing clients
+/// to be evicted.
+///
+/// See [`EvictionPolicy`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overload {
+    /// The total memory usage of the server exceeded its [`MemoryCap`], and
+    /// [`MemoryCap::shed`] is set.
+    ///
+    /// This is only used if an eviction policy is set using
+    /// [`WebTransportServer::set_eviction_policy`]. Otherwise, the clients
+    /// using the most memory are disconnected with
+    /// [`WebTransportError::MemoryCapExceeded`].
+    ///
+    /// [`MemoryCap`]: crate::MemoryCap
+    /// [`MemoryCap::shed`]: crate::MemoryCap::shed
+    /// [`WebTransportServer::set_eviction_policy`]: crate::WebTransportServer::set_eviction_policy
+    /// [`WebTransportError::MemoryCapExceeded`]: crate::WebTransportError::MemoryCapExceeded
+    Memory {
+        /// Total memory usage in bytes.
+        usage: usize,
+        /// The cap on the memory usage in bytes.
+        limit: usize,
+    },
+    /// The number of connected clients exceeded the server's maximum.
+    ///
+    /// See [`WebTransportServer::set_max_clients`].
+    ///
+    /// [`WebTransportServer::set_max_clients`]: crate::WebTransportServer::set_max_clients
+    Clients {
+        /// Number of connected clients.
+        count: usize,
+        /// The maximum number of connected clients.
+        limit: usize,
+    },
+    /// The app requested clients to be evicted, e.g. because the server is
+    /// over its CPU budget.
+    ///
+    /// See [`WebTransportServer::evict`].
+    ///
+    /// [`WebTransportServer::evict`]: crate::WebTransportServer::evict
+    Requested {
+        /// Number of clients to evict.
+        count: usize,
+    },
+}
+
+/// Client which may be evicted by an [`EvictionPolicy`].
+///
+/// Only clients which have established a connection, including ones pending
+/// admission, are candidates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvictionCandidate {
+    /// The key of the client.
+    pub client: ClientKey,
+    /// Whether the client has been admitted.
+    ///
+    /// See [`WebTransportServer::set_manual_admit`].
+    ///
+    /// [`WebTransportServer::set_manual_admit`]: crate::WebTransportServer::set_manual_admit
+    pub admitted: bool,
+    /// Total size in bytes of the buffers held for this client.
+    ///
+    /// See [`MemoryUsage::total`].
+    ///
+    /// [`MemoryUsage::total`]: crate::MemoryUsage::total
+    pub memory_usage: usize,
+    /// The latest connection stats of the client.
+    pub info: EndpointInfo,
+}
+
+/// [`EvictionPolicy`] which evicts clients pending admission first, then the
+/// clients which received the fewest messages in the current stats epoch,
+/// using memory usage to break ties.
+///
+/// This is the policy used if none is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LeastActiveFirst;
+
+impl EvictionPolicy for LeastActiveFirst {
+    fn select(&mut self, _: &Overload, candidates: &[EvictionCandidate]) -> Vec<ClientKey> {
+        let mut candidates = candidates.iter().collect::<Vec<_>>();
+        candidates.sort_by_key(|candidate| {
+            (
+                candidate.admitted,
+                candidate.info.stats.msgs_recv,
+                Reverse(candidate.memory_usage),
+            )
+        });
+        candidates
+            .into_iter()
+            .map(|candidate| candidate.client)
+            .collect()
+    }
+}
+
+/// Eviction settings of a server.
+#[derive(Default)]
+pub(super) struct Eviction {
+    pub policy: Option<Box<dyn EvictionPolicy>>,
+    pub max_clients: Option<usize>,
+    /// Number of evictions requested since the last poll.
+    pub requested: usize,
+}
+
+impl Eviction {
+    pub fn select(
+        &mut self,
+        overload: &Overload,
+        candidates: &[EvictionCandidate],
+    ) -> Vec<ClientKey> {
+        match &mut self.policy {
+            Some(policy) => policy.select(overload, candidates),
+            None => LeastActiveFirst.select(overload, candidates),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use aeronet::TransportProtocol;
+
+use crate::ClientKey;
+
+/// Decision of a message filter on what to do with a message.
+///
+/// See [`WebTransportServer::add_recv_filter`].
+///
+/// [`WebTransportServer::add_recv_filter`]: crate::WebTransportServer::add_recv_filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Verdict {
+    /// Let the message through, including any changes made to it by the
+    /// filter.
+    Pass,
+    /// Discard the message. No further filters are run on it.
+    Drop,
+}
+
+/// Filter run on every message received from a client.
+pub type RecvFilter<P> =
+    Box<dyn FnMut(ClientKey, &mut <P as TransportProtocol>::C2S) -> Verdict + Send + Sync>;
+
+/// Filter run on every message sent to a client.
+pub type SendFilter<P> =
+    Box<dyn FnMut(ClientKey, &mut <P as TransportProtocol>::S2C) -> Verdict + Send + Sync>;
+
+/// Runs `filters` on `msg` in order, stopping at the first filter which drops
+/// the message.
+pub(super) fn run<T>(
+    filters: &mut [Box<dyn FnMut(ClientKey, &mut T) -> Verdict + Send + Sync>],
+    client: ClientKey,
+    msg: &mut T,
+) -> Verdict {
+    for filter in filters {
+        if filter(client, msg) == Verdict::Drop {
+            return Verdict::Drop;
+        }
+    }
+    Verdict::Pass
+}
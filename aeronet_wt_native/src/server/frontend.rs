@@ -1,15 +1,51 @@
-use std::{future::Future, io, net::SocketAddr, task::Poll};
+use std::{
+    cmp::Reverse,
+    error::Error,
+    future::Future,
+    io, iter, mem,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant, SystemTime},
+};
 
-use aeronet::{OnChannel, TransportServer, TryFromBytes, TryIntoBytes};
+use aeronet::{
+    ChannelKey, ChannelKind, Clock, CustomEvent, Features, OnChannel, OnMessageError,
+    SendToManyResult, SystemClock, TransportServer, TryFromBytes, TryIntoBytes,
+};
+use futures::future::{self, Either};
+use slotmap::SlotMap;
 use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
 use wtransport::ServerConfig;
 
-use crate::{ClientKey, EndpointInfo, ServerEvent, WebTransportProtocol, WebTransportServer};
+use crate::{
+    encode::SharedEncodeOrder,
+    security::LaneCipher,
+    shared::{
+        self, CodecHook, Counters, Incoming, LaneEvent, MigrateLane, Outgoing, SharedCounters,
+    },
+    AnalyticsSink, ArenaShrink, BanList, BanTarget, ClientArena, ClientKey, ConnectionLimits,
+    EncodePool, EndpointInfo, IncomingQueue, LaneSecurityConfig, MemoryCap, MemoryUsage,
+    RecvBufferCaps, ServerEvent, ServerSettings, SessionResponse, WebTransportProtocol,
+    WebTransportServer, WebTransportServerConfig,
+};
 
 use super::{
-    backend, ClientState, OpenServer, OpenServerResult, OpeningServer, State, WebTransportError,
+    analytics, backend, config::FrontendSettings, disconnect_log, eviction::Eviction, filter,
+    handover::Handover, limits::LimitsState, AcceptedClient, Broadcast, ClientState,
+    ConnectedClient, DisconnectLog, Drain, EncodeErrors, ErrorChainFn, EvictionCandidate,
+    EvictionPolicy, OpenServer, OpenServerResult, OpeningServer, Overload, RecvFilter, SendFilter,
+    SessionRouter, State, Verdict, WebTransportError,
 };
 
+#[cfg(feature = "audit")]
+use super::AuditLog;
+
+#[cfg(feature = "codec-timing")]
+use crate::CodecTimings;
+
 impl<P> WebTransportServer<P>
 where
     P: WebTransportProtocol,
@@ -28,6 +64,21 @@ where
     pub fn closed() -> Self {
         Self {
             state: State::Closed,
+            clock: Arc::new(SystemClock),
+            settings: ServerSettings::default(),
+            event_buf: Vec::new(),
+            disconnect_log: None,
+            analytics: None,
+            codec: CodecHook::default(),
+            #[cfg(feature = "audit")]
+            audit: None,
+            eviction: Eviction::default(),
+            handover: Handover::default(),
+            bans: BanList::default(),
+            recv_filters: Vec::new(),
+            send_filters: Vec::new(),
+            encode_pool: None,
+            encode_errors: EncodeErrors::default(),
         }
     }
 
@@ -38,30 +89,99 @@ where
     ///   * use this throughout your app to interface with the server
     /// * a [`Future`] for the server's backend task
     ///   * run this on an async runtime as soon as possible
-    pub fn opening(config: ServerConfig) -> (Self, impl Future<Output = ()> + Send) {
-        let (server, backend) = OpeningServer::new(config);
-        (
-            Self {
-                state: State::Opening(server),
-            },
-            backend,
-        )
+    ///
+    /// `config` is either a [`WebTransportServerConfig`], or a raw
+    /// [`ServerConfig`]. If it is invalid, the server raises a
+    /// [`ServerEvent::Closed`] with the error the first time it is polled.
+    pub fn opening(
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+    ) -> (Self, impl Future<Output = ()> + Send) {
+        Self::opening_with(config.into(), None)
+    }
+
+    /// Creates and starts opening a server which only handles sessions claimed
+    /// by the given [`SessionRouter`].
+    ///
+    /// See [`WebTransportServer::opening`].
+    pub fn opening_routed(
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+        router: SessionRouter,
+    ) -> (Self, impl Future<Output = ()> + Send) {
+        Self::opening_with(config.into(), Some(router))
+    }
+
+    fn opening_with(
+        config: WebTransportServerConfig<P::Channel>,
+        router: Option<SessionRouter>,
+    ) -> (Self, impl Future<Output = ()> + Send) {
+        let mut server = Self::closed();
+        let backend = match server.open_with(config, router) {
+            Ok(backend) => Either::Left(backend),
+            Err(cause) => {
+                server.state = State::Opening(OpeningServer::failed(cause));
+                Either::Right(future::ready(()))
+            }
+        };
+        (server, backend)
     }
 
     /// Attempts to open this server for connections.
     ///
+    /// The settings set in `config` replace the current settings of this
+    /// server.
+    ///
     /// See [`WebTransportServer::opening`].
     ///
     /// # Errors
     ///
-    /// Errors if this server is already opening or is opened.
+    /// Errors if this server is already opening or is opened, or if `config`
+    /// is invalid.
     pub fn open(
         &mut self,
-        config: ServerConfig,
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+    ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
+        self.open_with(config.into(), None)
+    }
+
+    /// Attempts to open this server for connections, only handling sessions
+    /// claimed by the given [`SessionRouter`].
+    ///
+    /// See [`WebTransportServer::opening_routed`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if this server is already opening or is opened, or if `config`
+    /// is invalid.
+    pub fn open_routed(
+        &mut self,
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+        router: SessionRouter,
+    ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
+        self.open_with(config.into(), Some(router))
+    }
+
+    fn open_with(
+        &mut self,
+        config: WebTransportServerConfig<P::Channel>,
+        router: Option<SessionRouter>,
     ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
         match self.state {
             State::Closed => {
-                let (server, backend) = OpeningServer::new(config);
+                let (config, settings) =
+                    config.resolve().map_err(WebTransportError::ServerConfig)?;
+                self.apply_settings(settings);
+                let (server, backend) = OpeningServer::new(
+                    config,
+                    router,
+                    self.cipher(),
+                    self.settings.recv_buffer_caps.clone(),
+                    self.settings.features,
+                    self.settings
+                        .fast_start_lane
+                        .as_ref()
+                        .map(ChannelKey::index),
+                    self.settings.incoming_queue,
+                );
                 self.state = State::Opening(server);
                 Ok(backend)
             }
@@ -69,6 +189,282 @@ where
         }
     }
 
+    fn apply_settings(&mut self, settings: FrontendSettings<P::Channel>) {
+        if let Some(base) = settings.settings {
+            self.settings = base;
+        }
+        if let Some(lanes) = settings.lanes {
+            self.settings.lane_security = lanes.security;
+            self.settings.recv_buffer_caps = lanes.recv_buffer_caps;
+        }
+        if let Some(limits) = settings.limits {
+            self.settings.limits = limits;
+        }
+        if let Some(timeout) = settings.handshake_timeout {
+            self.settings.handshake_timeout = timeout;
+        }
+    }
+
+    /// Creates and starts opening a server which is split into `shards`
+    /// frontends, all sharing the same listening endpoint.
+    ///
+    /// Each incoming session is assigned to one of the frontends by hashing,
+    /// and is handled by that frontend for its entire lifetime. Since each
+    /// frontend is a separate [`WebTransportServer`], they can be polled
+    /// independently, e.g. by different Bevy systems or on different threads,
+    /// which lets servers with thousands of connections process their events
+    /// in parallel.
+    ///
+    /// [`ClientKey`]s are only unique within a single frontend, so the same key
+    /// may refer to different clients on different frontends. If a frontend is
+    /// closed, sessions are assigned to the remaining ones, and the endpoint is
+    /// closed once all of them are closed.
+    ///
+    /// This returns:
+    /// * the server frontends, one per shard
+    /// * a [`Future`] for the backend task shared by all of them
+    ///   * run this on an async runtime as soon as possible
+    ///
+    /// If `config` is invalid, every frontend raises a [`ServerEvent::Closed`]
+    /// with the error the first time it is polled.
+    pub fn opening_sharded(
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+        shards: NonZeroUsize,
+    ) -> (Vec<Self>, impl Future<Output = ()> + Send) {
+        let mut servers = iter::repeat_with(Self::closed)
+            .take(shards.get())
+            .collect::<Vec<_>>();
+        let backend = match config.into().resolve() {
+            Ok((config, settings)) => {
+                Either::Left(Self::open_sharded_with(&mut servers, config, &settings))
+            }
+            Err(err) => {
+                for server in &mut servers {
+                    let cause = WebTransportError::ServerConfig(err.clone());
+                    server.state = State::Opening(OpeningServer::failed(cause));
+                }
+                Either::Right(future::ready(()))
+            }
+        };
+        (servers, backend)
+    }
+
+    /// Attempts to open a set of closed servers for connections, as shards
+    /// sharing the same listening endpoint.
+    ///
+    /// The settings set in `config` replace the current settings of every
+    /// shard. Otherwise, the lane security, receive
+    /// buffer caps, protocol features, fast-start lane and incoming queue of
+    /// the first server are used for the endpoint, so these should be
+    /// configured the same way on every shard.
+    ///
+    /// See [`WebTransportServer::opening_sharded`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if `shards` is empty, if any of the servers is already opening
+    /// or is opened, or if `config` is invalid.
+    pub fn open_sharded(
+        shards: &mut [Self],
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+    ) -> Result<impl Future<Output = ()> + Send, WebTransportError<P>> {
+        if shards.is_empty() {
+            return Err(WebTransportError::NoShards);
+        }
+        if shards
+            .iter()
+            .any(|shard| !matches!(shard.state, State::Closed))
+        {
+            return Err(WebTransportError::BackendOpen);
+        }
+
+        let (config, settings) = config
+            .into()
+            .resolve()
+            .map_err(WebTransportError::ServerConfig)?;
+        Ok(Self::open_sharded_with(shards, config, &settings))
+    }
+
+    fn open_sharded_with(
+        shards: &mut [Self],
+        config: ServerConfig,
+        settings: &FrontendSettings<P::Channel>,
+    ) -> impl Future<Output = ()> + Send {
+        for shard in shards.iter_mut() {
+            shard.apply_settings(settings.clone());
+        }
+        let first = &shards[0];
+        let (servers, backend) = OpeningServer::new_sharded(
+            config,
+            None,
+            first.cipher(),
+            first.settings.recv_buffer_caps.clone(),
+            first.settings.features,
+            first
+                .settings
+                .fast_start_lane
+                .as_ref()
+                .map(ChannelKey::index),
+            first.settings.incoming_queue,
+            NonZeroUsize::new(shards.len()).expect("should not be empty"),
+        );
+        for (shard, server) in shards.iter_mut().zip(servers) {
+            shard.state = State::Opening(server);
+        }
+        backend
+    }
+
+    /// Gets the current settings of this server.
+    ///
+    /// Each setting can be changed using its setter on this server, or when
+    /// the server opens using [`WebTransportServerConfigBuilder::settings`].
+    ///
+    /// [`WebTransportServerConfigBuilder::settings`]: crate::WebTransportServerConfigBuilder::settings
+    #[must_use]
+    pub fn settings(&self) -> &ServerSettings<P::Channel> {
+        &self.settings
+    }
+
+    /// Gets the lane security configuration used for new sessions.
+    #[must_use]
+    pub fn lane_security(&self) -> Option<&LaneSecurityConfig<P::Channel>> {
+        self.settings.lane_security.as_ref()
+    }
+
+    /// Sets the lane security configuration used for new sessions.
+    ///
+    /// This takes effect the next time this server opens using
+    /// [`WebTransportServer::open`] or [`WebTransportServer::open_routed`],
+    /// and must match the configuration of the clients. By default, all lanes
+    /// are plain.
+    ///
+    /// See [`LaneSecurityConfig`].
+    pub fn set_lane_security(&mut self, security: Option<LaneSecurityConfig<P::Channel>>) {
+        self.settings.lane_security = security;
+    }
+
+    /// Gets the caps on the data buffered on each lane of new sessions.
+    #[must_use]
+    pub fn recv_buffer_caps(&self) -> &RecvBufferCaps<P::Channel> {
+        &self.settings.recv_buffer_caps
+    }
+
+    /// Sets the caps on the data buffered on each lane of new sessions.
+    ///
+    /// This takes effect the next time this server opens using
+    /// [`WebTransportServer::open`] or [`WebTransportServer::open_routed`].
+    /// By default, no lane has a cap.
+    ///
+    /// See [`RecvBufferCap`].
+    ///
+    /// [`RecvBufferCap`]: crate::RecvBufferCap
+    pub fn set_recv_buffer_caps(&mut self, caps: RecvBufferCaps<P::Channel>) {
+        self.settings.recv_buffer_caps = caps;
+    }
+
+    /// Gets the optional protocol features that this server supports on new
+    /// sessions.
+    #[must_use]
+    pub fn features(&self) -> Features {
+        self.settings.features
+    }
+
+    /// Sets the optional protocol features that this server supports on new
+    /// sessions.
+    ///
+    /// This takes effect the next time this server opens using
+    /// [`WebTransportServer::open`] or [`WebTransportServer::open_routed`].
+    /// Once a client is connected, the features that both it and this server
+    /// support are available in [`EndpointInfo::features`]. Clients which do
+    /// not advertise any features get none. By default, no features are
+    /// supported.
+    ///
+    /// See [`Features`].
+    pub fn set_features(&mut self, features: Features) {
+        self.settings.features = features;
+    }
+
+    /// Gets the lane which can be used before new sessions are connected.
+    #[must_use]
+    pub fn fast_start_lane(&self) -> Option<&P::Channel> {
+        self.settings.fast_start_lane.as_ref()
+    }
+
+    /// Sets the lane which can be used before new sessions are connected.
+    ///
+    /// Normally, messages can only be sent to and received from a client once
+    /// it is connected, which takes at least another round trip after its
+    /// session is accepted while the streams of its lanes are opened. Messages
+    /// on the fast-start lane are instead sent and received as datagrams as
+    /// soon as [`ServerEvent::Accepted`] is raised for the client, so that
+    /// latency-critical data such as the initial input of a player can flow
+    /// during the handshake. Messages on other lanes are still rejected with
+    /// [`WebTransportError::NotConnected`] until the client is connected.
+    ///
+    /// Messages received on the fast-start lane before the client is connected
+    /// are raised as [`ServerEvent::Recv`] before [`ServerEvent::Connected`],
+    /// so the app must not assume that a client is connected when it receives
+    /// a message from it.
+    ///
+    /// This takes effect the next time this server opens using
+    /// [`WebTransportServer::open`] or [`WebTransportServer::open_routed`].
+    /// By default, there is no fast-start lane.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `lane` is not [`ChannelKind::Unreliable`], since reliable
+    /// lanes are sent over streams which are not open yet.
+    ///
+    /// [`ChannelKind::Unreliable`]: aeronet::ChannelKind::Unreliable
+    pub fn set_fast_start_lane(
+        &mut self,
+        lane: Option<P::Channel>,
+    ) -> Result<(), WebTransportError<P>> {
+        match lane {
+            Some(lane) if lane.kind() != ChannelKind::Unreliable => {
+                Err(WebTransportError::FastStartLaneReliable(lane))
+            }
+            lane => {
+                self.settings.fast_start_lane = lane;
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets the pool which messages sent by this server are serialized on, if
+    /// any.
+    #[must_use]
+    pub fn encode_pool(&self) -> Option<&EncodePool> {
+        self.encode_pool.as_ref()
+    }
+
+    /// Sets the pool which messages sent by this server are serialized on.
+    ///
+    /// While a pool is set, messages passed to [`TransportServer::send`],
+    /// [`WebTransportServer::send_with_ttl`] and
+    /// [`TransportServer::send_to_many`] are serialized on the pool instead of
+    /// the calling thread, and a message passed to `send_to_many` is only
+    /// serialized once for all clients. Since the message is serialized after
+    /// the send returns, serialize errors are raised as
+    /// [`ServerEvent::EncodeError`] instead of being returned.
+    ///
+    /// Other ways of sending, such as [`WebTransportServer::send_ref`], still
+    /// serialize on the calling thread, but are still sent after any earlier
+    /// messages which are being serialized on the pool. This takes effect
+    /// immediately. By default, there is no pool.
+    ///
+    /// See [`EncodePool`].
+    pub fn set_encode_pool(&mut self, pool: Option<EncodePool>) {
+        self.encode_pool = pool;
+    }
+
+    fn cipher(&self) -> Option<Arc<LaneCipher>> {
+        self.settings
+            .lane_security
+            .as_ref()
+            .map(|security| Arc::new(security.cipher(true)))
+    }
+
     /// Gets the local socket address of this server if it is open.
     ///
     /// # Errors
@@ -80,144 +476,1768 @@ where
             State::Open(server) => Ok(server.local_addr()),
         }
     }
-}
 
-impl<P> TransportServer<P> for WebTransportServer<P>
-where
-    P: WebTransportProtocol,
-    P::C2S: TryFromBytes,
-    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
-{
-    type Client = ClientKey;
+    /// Gets the interval at which [`ServerEvent::LaneStats`] events are raised
+    /// for each connected client.
+    ///
+    /// If this is [`None`], no lane stats events are raised.
+    #[must_use]
+    pub fn lane_stats_interval(&self) -> Option<Duration> {
+        self.settings.lane_stats_interval
+    }
 
-    type Error = WebTransportError<P>;
+    /// Sets the interval at which [`ServerEvent::LaneStats`] events are raised
+    /// for each lane of each connected client.
+    ///
+    /// Pass [`None`] to stop raising these events. By default, no lane stats
+    /// events are raised.
+    pub fn set_lane_stats_interval(&mut self, interval: Option<Duration>) {
+        self.settings.lane_stats_interval = interval;
+    }
 
-    type ConnectionInfo = EndpointInfo;
+    /// Gets the minimum length in bytes of a message for which
+    /// [`ServerEvent::RecvProgress`] events are raised while it is received.
+    ///
+    /// If this is [`None`], no progress events are raised.
+    #[must_use]
+    pub fn recv_progress_min_len(&self) -> Option<usize> {
+        self.settings.recv_progress_min_len
+    }
 
-    type Event = ServerEvent<P>;
+    /// Sets the minimum length in bytes of a message for which
+    /// [`ServerEvent::RecvProgress`] events are raised while it is received.
+    ///
+    /// Pass [`None`] to stop raising these events. By default, no progress
+    /// events are raised.
+    pub fn set_recv_progress_min_len(&mut self, min_len: Option<usize>) {
+        self.settings.recv_progress_min_len = min_len;
+    }
 
-    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+    /// Gets the interval at which each connected client is sent a report on
+    /// the quality of its connection to this server.
+    ///
+    /// If this is [`None`], no reports are sent.
+    #[must_use]
+    pub fn quality_report_interval(&self) -> Option<Duration> {
+        self.settings.quality_report_interval
+    }
+
+    /// Sets the interval at which each connected client is sent a report on
+    /// the quality of its connection to this server.
+    ///
+    /// A client can only measure the data it receives, so it has no other way
+    /// of knowing how many of the messages it sends are lost on the way to the
+    /// server. Reports are sent on a dedicated stream, which is opened when
+    /// the first report is sent, and are exposed by the client through its
+    /// [`RemoteQuality`] connection info.
+    ///
+    /// Pass [`None`] to stop sending reports. By default, no reports are sent.
+    ///
+    /// [`RemoteQuality`]: aeronet::RemoteQuality
+    pub fn set_quality_report_interval(&mut self, interval: Option<Duration>) {
+        self.settings.quality_report_interval = interval;
+    }
+
+    /// Gets how long a client may take to finish opening its channels after
+    /// its session was accepted.
+    ///
+    /// If this is [`None`], clients may take any amount of time.
+    #[must_use]
+    pub fn handshake_timeout(&self) -> Option<Duration> {
+        self.settings.handshake_timeout
+    }
+
+    /// Sets how long a client may take to finish opening its channels after
+    /// its session was accepted.
+    ///
+    /// A client which has been accepted, but has not connected within this
+    /// time, is disconnected and a [`ServerEvent::Disconnected`] is raised
+    /// for it with [`WebTransportError::HandshakeTimeout`]. Otherwise, a client
+    /// which never opens its channels occupies a slot in the client arena
+    /// forever. The time spent waiting for [`WebTransportServer::respond`] is
+    /// not counted.
+    ///
+    /// Pass [`None`] to never time out. By default, this is
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`].
+    ///
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`]: crate::DEFAULT_HANDSHAKE_TIMEOUT
+    /// [`WebTransportError::HandshakeTimeout`]: crate::WebTransportError::HandshakeTimeout
+    pub fn set_handshake_timeout(&mut self, timeout: Option<Duration>) {
+        self.settings.handshake_timeout = timeout;
+    }
+
+    /// Gets the clock that this server reads the current time from.
+    #[must_use]
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Sets the clock that this server reads the current time from.
+    ///
+    /// This drives the time-based behaviour of the frontend, such as the
+    /// [`ServerEvent::LaneStats`] interval, the [`LimitKind::RecvRate`] window
+    /// and the [drain](WebTransportServer::drain) deadline. By default, this is
+    /// the [`SystemClock`]; tests can use a [`MockClock`] to trigger this
+    /// behaviour deterministically.
+    ///
+    /// Timings measured by the backend, such as the latencies in
+    /// [`LaneStats`], always use the real time.
+    ///
+    /// [`LimitKind::RecvRate`]: crate::LimitKind::RecvRate
+    /// [`MockClock`]: aeronet::MockClock
+    /// [`LaneStats`]: crate::LaneStats
+    pub fn set_clock(&mut self, clock: impl Clock) {
+        self.clock = Arc::new(clock);
+    }
+
+    /// Gets what happens when a message fails to serialize while sending it to
+    /// a client.
+    #[must_use]
+    pub fn on_serialize_error(&self) -> OnMessageError {
+        self.settings.on_serialize_error
+    }
+
+    /// Sets what happens when a message fails to serialize while sending it to
+    /// a client.
+    ///
+    /// By default, this is [`OnMessageError::EmitEventOnly`], so the error is
+    /// returned from the send function and the client stays connected.
+    pub fn set_on_serialize_error(&mut self, policy: OnMessageError) {
+        self.settings.on_serialize_error = policy;
+    }
+
+    /// Gets what happens when a message received from a client fails to
+    /// deserialize.
+    #[must_use]
+    pub fn on_deserialize_error(&self) -> OnMessageError {
+        self.settings.on_deserialize_error
+    }
+
+    /// Sets what happens when a message received from a client fails to
+    /// deserialize.
+    ///
+    /// With [`OnMessageError::EmitEventOnly`], a [`ServerEvent::MessageError`]
+    /// is raised. By default, this is [`OnMessageError::DisconnectClient`].
+    pub fn set_on_deserialize_error(&mut self, policy: OnMessageError) {
+        self.settings.on_deserialize_error = policy;
+    }
+
+    /// Gets the limits on the resources used by each connected client.
+    #[must_use]
+    pub fn limits(&self) -> &ConnectionLimits {
+        &self.settings.limits
+    }
+
+    /// Sets the limits on the resources used by each connected client.
+    ///
+    /// When a client reaches a soft limit, a [`ServerEvent::LimitWarning`] is
+    /// raised. When a client exceeds a hard limit, it is disconnected with
+    /// [`WebTransportError::LimitExceeded`].
+    ///
+    /// [`WebTransportError::LimitExceeded`]: crate::WebTransportError::LimitExceeded
+    pub fn set_limits(&mut self, limits: ConnectionLimits) {
+        self.settings.limits = limits;
+    }
+
+    /// Gets the number of messages sent to a client which are waiting in the
+    /// send queue, across all lanes.
+    ///
+    /// This reflects the backend's actual queue depth, and can be used for
+    /// adaptive send logic, e.g. skipping a snapshot for this client if it is
+    /// still sending the previous one.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    #[must_use]
+    pub fn queued_msgs(&self, client: ClientKey) -> Option<usize> {
         match &self.state {
             State::Closed | State::Opening(_) => None,
-            State::Open(server) => server.connection_info(client),
+            State::Open(server) => server.connected_counters(client).map(Counters::queued),
         }
     }
 
-    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
-        let clients = match &self.state {
+    /// Gets the total size in bytes of the serialized messages sent to a client
+    /// which are waiting in the send queue, across all lanes.
+    ///
+    /// See [`WebTransportServer::queued_msgs`].
+    #[must_use]
+    pub fn queued_bytes(&self, client: ClientKey) -> Option<usize> {
+        match &self.state {
             State::Closed | State::Opening(_) => None,
-            State::Open(server) => Some(server.clients()),
-        };
-        clients.into_iter().flatten()
+            State::Open(server) => server
+                .connected_counters(client)
+                .map(Counters::queued_bytes),
+        }
     }
 
-    fn send(
-        &mut self,
-        client: Self::Client,
-        msg: impl Into<P::S2C>,
-    ) -> Result<(), WebTransportError<P>> {
+    /// Starts a new statistics epoch for a client, and returns its
+    /// [`EpochStats::epoch`].
+    ///
+    /// This resets the totals in [`EndpointInfo::stats`], and the values of
+    /// the client's [`ServerEvent::LaneStats`] which are measured over a
+    /// period, so that e.g. the traffic of a single match can be measured by
+    /// resetting the stats when it starts. The lane stats interval also
+    /// restarts from the next poll.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not connected or
+    /// pending admission.
+    ///
+    /// [`EpochStats::epoch`]: crate::EpochStats::epoch
+    pub fn reset_stats(&mut self, client: ClientKey) -> Result<u64, WebTransportError<P>> {
         match &mut self.state {
             State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
-            State::Open(server) => server.send(client, msg),
+            State::Open(server) => server.reset_stats(client),
         }
     }
 
-    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
-        match &mut self.state {
-            State::Closed => vec![].into_iter(),
-            State::Opening(server) => match server.poll() {
-                Poll::Pending => vec![].into_iter(),
-                Poll::Ready(Ok(server)) => {
-                    self.state = State::Open(server);
-                    vec![ServerEvent::Opened].into_iter()
-                }
-                Poll::Ready(Err(cause)) => {
-                    self.state = State::Closed;
-                    vec![ServerEvent::Closed { cause }].into_iter()
-                }
-            },
-            State::Open(server) => match server.recv() {
-                (events, Ok(())) => events.into_iter(),
-                (mut events, Err(cause)) => {
-                    self.state = State::Closed;
-                    events.push(ServerEvent::Closed { cause });
-                    events.into_iter()
-                }
-            },
+    /// Asks a client to send the messages of `channel` on the lane of `lane`
+    /// instead, in both directions, without reconnecting.
+    ///
+    /// This can be used to e.g. move chat onto an unreliable lane while
+    /// bandwidth is scarce, and back onto its own lane once it recovers, by
+    /// migrating it onto itself. The messages of `channel` stay on their
+    /// current lane until the client has agreed, after which
+    /// [`ServerEvent::LaneMigrated`] is raised. If the client does not agree,
+    /// [`ServerEvent::LaneMigrationRejected`] is raised instead.
+    ///
+    /// Messages are assigned to a lane when the backend sends them, so there
+    /// is no ordering between messages sent before and after a migration, and
+    /// messages moved onto an unreliable lane lose their delivery guarantee.
+    /// [`ServerEvent::LaneStats`] keep counting the messages under their own
+    /// channel.
+    ///
+    /// See [`wire`](crate::wire) for how the migration is agreed on.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, if the client is not connected or
+    /// pending admission, or if the client has not negotiated
+    /// [`Features::LANE_MIGRATION`], e.g. because features have not been
+    /// exchanged yet.
+    pub fn migrate_lane(
+        &mut self,
+        client: ClientKey,
+        channel: P::Channel,
+        lane: P::Channel,
+    ) -> Result<(), WebTransportError<P>> {
+        match &self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.migrate_lane(client, channel, lane),
         }
     }
 
-    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
-        match &mut self.state {
+    /// Stops reading the streams and datagrams of a client.
+    ///
+    /// Messages which the client sends while paused are not read from the
+    /// connection, so they do not queue up in the transport, and QUIC flow
+    /// control stops the client from sending more once its stream windows are
+    /// full. This is useful while a client is pending admission, or while its
+    /// inputs must be ignored, e.g. during a cutscene. Datagrams are not flow
+    /// controlled, so unreliable messages sent while paused are dropped once
+    /// the connection's datagram buffer is full.
+    ///
+    /// Messages which were already read before pausing are still raised as
+    /// events. Sending to the client is not affected.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not connected or
+    /// pending admission.
+    pub fn pause_recv(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        self.set_recv_paused(client, true)
+    }
+
+    /// Resumes reading the streams and datagrams of a client paused using
+    /// [`WebTransportServer::pause_recv`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not connected or
+    /// pending admission.
+    pub fn resume_recv(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        self.set_recv_paused(client, false)
+    }
+
+    fn set_recv_paused(
+        &mut self,
+        client: ClientKey,
+        paused: bool,
+    ) -> Result<(), WebTransportError<P>> {
+        match &self.state {
             State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
-            State::Open(server) => server.disconnect(client),
+            State::Open(server) => {
+                server.sendable(client)?.counters.set_recv_paused(paused);
+                Ok(())
+            }
         }
     }
-}
 
-impl<P> OpeningServer<P>
-where
+    /// Gets whether reading from a client is paused.
+    ///
+    /// Returns [`None`] if the client is not connected or pending admission.
+    ///
+    /// See [`WebTransportServer::pause_recv`].
+    #[must_use]
+    pub fn is_recv_paused(&self, client: ClientKey) -> Option<bool> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server
+                .sendable(client)
+                .ok()
+                .map(|client| client.counters.recv_paused()),
+        }
+    }
+
+    /// Gets the approximate memory held by the transport for a client.
+    ///
+    /// Returns [`None`] if the client is not connected or pending admission.
+    #[must_use]
+    pub fn memory_usage(&self, client: ClientKey) -> Option<MemoryUsage> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server
+                .sendable(client)
+                .ok()
+                .map(|client| client.counters.memory_usage()),
+        }
+    }
+
+    /// Gets the approximate memory in bytes held by the transport for all
+    /// clients.
+    ///
+    /// See [`WebTransportServer::memory_usage`].
+    #[must_use]
+    pub fn total_memory_usage(&self) -> usize {
+        match &self.state {
+            State::Closed | State::Opening(_) => 0,
+            State::Open(server) => server.total_memory_usage(),
+        }
+    }
+
+    /// Gets the cap on the total memory held for all clients.
+    ///
+    /// See [`WebTransportServer::set_memory_cap`].
+    #[must_use]
+    pub fn memory_cap(&self) -> Option<MemoryCap> {
+        self.settings.memory_cap
+    }
+
+    /// Sets the cap on the total memory held for all clients.
+    ///
+    /// While the [total memory usage] is above the cap, all new session
+    /// requests are rejected with `403 Forbidden`, and a
+    /// [`ServerEvent::Disconnected`] is raised for them with
+    /// [`WebTransportError::MemoryCapExceeded`]. If [`MemoryCap::shed`] is
+    /// set, connected clients are also disconnected with the same error,
+    /// starting with the most expensive one, until the usage is back under the
+    /// cap. This is checked every time the server is polled.
+    ///
+    /// Pass [`None`] to remove the cap. By default, there is no cap.
+    ///
+    /// [total memory usage]: WebTransportServer::total_memory_usage
+    /// [`WebTransportError::MemoryCapExceeded`]: crate::WebTransportError::MemoryCapExceeded
+    pub fn set_memory_cap(&mut self, cap: Option<MemoryCap>) {
+        self.settings.memory_cap = cap;
+    }
+
+    /// Gets the maximum number of connected clients.
+    ///
+    /// See [`WebTransportServer::set_max_clients`].
+    #[must_use]
+    pub fn max_clients(&self) -> Option<usize> {
+        self.eviction.max_clients
+    }
+
+    /// Sets the maximum number of connected clients, including clients pending
+    /// admission.
+    ///
+    /// When a new client connects while the server is at the maximum, clients
+    /// are evicted using the [eviction policy] until the server is back at the
+    /// maximum, and a [`ServerEvent::Disconnected`] is raised for them with
+    /// [`WebTransportError::Evicted`]. Depending on the policy, this may be
+    /// the new client itself. This is checked every time the server is polled.
+    ///
+    /// Pass [`None`] to remove the maximum. By default, there is no maximum.
+    ///
+    /// [eviction policy]: WebTransportServer::set_eviction_policy
+    /// [`WebTransportError::Evicted`]: crate::WebTransportError::Evicted
+    pub fn set_max_clients(&mut self, max: Option<usize>) {
+        self.eviction.max_clients = max;
+    }
+
+    /// Sets the policy which picks the clients to evict when the server is
+    /// overloaded.
+    ///
+    /// This is consulted when the server goes over its
+    /// [maximum number of clients], when evictions are requested using
+    /// [`WebTransportServer::evict`], and when it goes over its
+    /// [memory cap] if [`MemoryCap::shed`] is set. This replaces any
+    /// previously set policy.
+    ///
+    /// See [`EvictionPolicy`].
+    ///
+    /// [maximum number of clients]: WebTransportServer::set_max_clients
+    /// [memory cap]: WebTransportServer::set_memory_cap
+    pub fn set_eviction_policy(&mut self, policy: impl EvictionPolicy + 'static) {
+        self.eviction.policy = Some(Box::new(policy));
+    }
+
+    /// Removes the eviction policy, going back to the default behavior.
+    ///
+    /// See [`WebTransportServer::set_eviction_policy`].
+    pub fn clear_eviction_policy(&mut self) {
+        self.eviction.policy = None;
+    }
+
+    /// Requests `count` clients to be evicted on the next poll, picked by the
+    /// [eviction policy].
+    ///
+    /// Use this to shed load when the app detects an overload which the
+    /// server does not track itself, e.g. when a tick takes longer than its
+    /// CPU budget. The policy is called with [`Overload::Requested`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open.
+    ///
+    /// [eviction policy]: WebTransportServer::set_eviction_policy
+    pub fn evict(&mut self, count: usize) -> Result<(), WebTransportError<P>> {
+        match self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(_) => {
+                self.eviction.requested += count;
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets how this server allocates and releases the storage for its
+    /// clients.
+    #[must_use]
+    pub fn arena(&self) -> ClientArena {
+        self.settings.arena
+    }
+
+    /// Sets how this server allocates and releases the storage for its
+    /// clients.
+    ///
+    /// [`ClientArena::initial_capacity`] is applied the next time the server
+    /// opens, and [`ClientArena::shrink`] is applied immediately. By default,
+    /// no slots are allocated up front and the arena never shrinks.
+    pub fn set_arena(&mut self, arena: ClientArena) {
+        self.settings.arena = arena;
+    }
+
+    /// Reserves client slots for at least `additional` more clients than are
+    /// currently connected.
+    ///
+    /// Use this ahead of an expected burst of connections, e.g. when a match
+    /// is about to start, so that the arena is not reallocated while the
+    /// clients are connecting.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open.
+    pub fn reserve_clients(&mut self, additional: usize) -> Result<(), WebTransportError<P>> {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                server.clients.reserve(additional);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets how this server queues and takes in new sessions.
+    #[must_use]
+    pub fn incoming_queue(&self) -> IncomingQueue {
+        self.settings.incoming_queue
+    }
+
+    /// Sets how this server queues and takes in new sessions.
+    ///
+    /// [`IncomingQueue::capacity`] is applied the next time the server opens,
+    /// and [`IncomingQueue::per_poll`] is applied immediately.
+    pub fn set_incoming_queue(&mut self, queue: IncomingQueue) {
+        self.settings.incoming_queue = queue;
+    }
+
+    /// Gets the number of clients that this server can hold without
+    /// reallocating its arena.
+    ///
+    /// Returns [`None`] if the server is not open.
+    #[must_use]
+    pub fn client_capacity(&self) -> Option<usize> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.clients.capacity()),
+        }
+    }
+
+    /// Starts draining this server ahead of a restart or shutdown.
+    ///
+    /// While draining, all new session requests are rejected with
+    /// `403 Forbidden`, and a [`ServerEvent::Disconnected`] is raised for them
+    /// with [`WebTransportError::Draining`]. Clients which have been accepted
+    /// but have not finished connecting yet are disconnected.
+    ///
+    /// If a `notice` is given, it is sent to every client which is connected or
+    /// pending admission, so that e.g. the client can tell the player that the
+    /// server is restarting, and when to reconnect. The notice is passed
+    /// through the send filters for each client, and is only serialized once
+    /// if there are no filters.
+    ///
+    /// Once all clients have left, or `timeout` has passed and the remaining
+    /// clients have been disconnected with [`WebTransportError::Draining`],
+    /// a [`ServerEvent::Drained`] is raised.
+    ///
+    /// Calling this while already draining sends the new notice, and replaces
+    /// the previous deadline.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the notice could not be
+    /// serialized.
+    ///
+    /// [`WebTransportError::Draining`]: crate::WebTransportError::Draining
+    pub fn drain(
+        &mut self,
+        timeout: Duration,
+        notice: Option<P::S2C>,
+    ) -> Result<(), WebTransportError<P>>
+    where
+        P::S2C: Clone,
+    {
+        let now = self.clock.now();
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                let notices = match notice {
+                    Some(notice) => {
+                        server.drain_notices(&notice, &mut self.send_filters, &self.codec)?
+                    }
+                    None => Vec::new(),
+                };
+                server.drain(timeout, notices, now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts draining this server without sending a notice.
+    ///
+    /// Unlike [`WebTransportServer::drain`], this does not need to clone a
+    /// notice for the send filters.
+    #[cfg(feature = "async")]
+    pub(super) fn drain_silently(&mut self, timeout: Duration) -> Result<(), WebTransportError<P>> {
+        let now = self.clock.now();
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                server.drain(timeout, Vec::new(), now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets if this server is draining.
+    ///
+    /// See [`WebTransportServer::drain`].
+    #[must_use]
+    pub fn is_draining(&self) -> bool {
+        match &self.state {
+            State::Closed | State::Opening(_) => false,
+            State::Open(server) => server.drain.is_some(),
+        }
+    }
+
+    /// Sends a message to all connected clients, skipping clients whose send
+    /// queue is congested.
+    ///
+    /// A client is congested if the total size of the messages waiting in its
+    /// send queue (see [`WebTransportServer::queued_bytes`]) is above
+    /// `max_queued_bytes`. This is intended for unreliable broadcasts such as
+    /// state snapshots: instead of queueing ever more stale data for a slow
+    /// client, which wastes server memory and delays the data that the client
+    /// actually needs, the message is not sent to it at all. The number of
+    /// broadcasts which skipped each client can be read using
+    /// [`WebTransportServer::skipped_broadcasts`].
+    ///
+    /// The message is passed through the send filters for each client which is
+    /// not congested, and is only serialized once if there are no filters.
+    /// Clients for which a filter dropped the message are neither counted as
+    /// sent nor as skipped.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the message could not be
+    /// serialized.
+    pub fn broadcast_uncongested(
+        &mut self,
+        msg: impl Into<P::S2C>,
+        max_queued_bytes: usize,
+    ) -> Result<Broadcast, WebTransportError<P>>
+    where
+        P::S2C: Clone,
+    {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.broadcast_uncongested(
+                msg.into(),
+                max_queued_bytes,
+                &mut self.send_filters,
+                &self.codec,
+            ),
+        }
+    }
+
+    /// Gets the number of broadcasts sent using
+    /// [`WebTransportServer::broadcast_uncongested`] which skipped a client
+    /// since it connected, because it was congested.
+    ///
+    /// Returns [`None`] if the client is not connected.
+    #[must_use]
+    pub fn skipped_broadcasts(&self, client: ClientKey) -> Option<usize> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => match server.clients.get(client) {
+                Some(ClientState::Connected(client)) => Some(client.skipped_broadcasts),
+                _ => None,
+            },
+        }
+    }
+
+    /// Gets if incoming sessions must be manually accepted or rejected.
+    ///
+    /// See [`WebTransportServer::set_manual_accept`].
+    #[must_use]
+    pub fn manual_accept(&self) -> bool {
+        self.settings.manual_accept
+    }
+
+    /// Sets if incoming sessions must be manually accepted or rejected.
+    ///
+    /// By default, all sessions are accepted automatically. If this is enabled,
+    /// a [`ServerEvent::Requested`] is raised for each session request instead,
+    /// and the client will not connect until the request is responded to using
+    /// [`WebTransportServer::respond`].
+    ///
+    /// This only affects session requests received after this is set.
+    pub fn set_manual_accept(&mut self, manual_accept: bool) {
+        self.settings.manual_accept = manual_accept;
+    }
+
+    /// Gets if connected clients must be manually admitted.
+    ///
+    /// See [`WebTransportServer::set_manual_admit`].
+    #[must_use]
+    pub fn manual_admit(&self) -> bool {
+        self.settings.manual_admit
+    }
+
+    /// Sets if connected clients must be manually admitted.
+    ///
+    /// By default, a [`ServerEvent::Connected`] is raised as soon as a client's
+    /// connection is established. If this is enabled, a
+    /// [`ServerEvent::PendingConnected`] is raised instead, and the client is
+    /// only considered connected once it is admitted using
+    /// [`WebTransportServer::admit`].
+    ///
+    /// This gives a hook for e.g. validating a login token before any gameplay
+    /// messages are processed. Messages received from a pending client are
+    /// held back until it is admitted, and are then raised as
+    /// [`ServerEvent::Recv`] events after its [`ServerEvent::Connected`].
+    /// Messages can already be sent to a pending client, e.g. to tell it why
+    /// it is about to be refused.
+    ///
+    /// The messages held back for each pending client are bounded by
+    /// [`ConnectionLimits::early_msgs`] and
+    /// [`ConnectionLimits::early_bytes`].
+    ///
+    /// This only affects clients which connect after this is set.
+    pub fn set_manual_admit(&mut self, manual_admit: bool) {
+        self.settings.manual_admit = manual_admit;
+    }
+
+    /// Admits a client raised as a [`ServerEvent::PendingConnected`], raising a
+    /// [`ServerEvent::Connected`] for it.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not pending
+    /// admission.
+    pub fn admit(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.admit(client),
+        }
+    }
+
+    /// Refuses a client raised as a [`ServerEvent::PendingConnected`],
+    /// disconnecting it.
+    ///
+    /// A [`ServerEvent::Disconnected`] is raised for the client with
+    /// [`WebTransportError::ForceDisconnect`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not pending
+    /// admission.
+    ///
+    /// [`WebTransportError::ForceDisconnect`]: crate::WebTransportError::ForceDisconnect
+    pub fn refuse(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.refuse(client),
+        }
+    }
+
+    /// Responds to a client's session request raised as a
+    /// [`ServerEvent::Requested`].
+    ///
+    /// If the session is accepted, a [`ServerEvent::Accepted`] is raised, and
+    /// the client continues connecting as normal. If it is rejected, the
+    /// response's HTTP status is sent to the client, and a
+    /// [`ServerEvent::Disconnected`] is raised with
+    /// [`WebTransportError::SessionRejected`].
+    ///
+    /// A session can only be rejected with `403` or `404`, and without any
+    /// response headers - see [`SessionResponse`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the server is not open, or if the client is not waiting for a
+    /// response to its session request.
+    ///
+    /// [`WebTransportError::SessionRejected`]: crate::WebTransportError::SessionRejected
+    pub fn respond(
+        &mut self,
+        client: ClientKey,
+        response: SessionResponse,
+    ) -> Result<(), WebTransportError<P>> {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.respond(client, response),
+        }
+    }
+
+    /// Sends a message to a client, replacing any message previously sent to
+    /// this client in the same slot which has not been sent yet.
+    ///
+    /// This is useful for messages which are superseded by newer versions of
+    /// themselves, such as state snapshots: if the connection is congested and
+    /// messages are queued up, a stale snapshot will be dropped instead of
+    /// consuming bandwidth. A replaced message takes the position of the
+    /// message it replaced in the queue of replaceable messages, and counts as
+    /// dropped in the [`LaneStats`] of its lane.
+    ///
+    /// Slots are scoped to the client. Once the backend has started sending a
+    /// message, it can no longer be replaced.
+    ///
+    /// Replaceable messages are sent from a separate queue, so they may be
+    /// sent before or after messages passed to [`TransportServer::send`]
+    /// around the same time. Since this would break the ordering of a
+    /// [`ChannelKind::ReliableOrdered`] lane, messages on these lanes are
+    /// never replaced, and are sent like with [`TransportServer::send`]
+    /// instead.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    ///
+    /// [`LaneStats`]: crate::LaneStats
+    pub fn send_replaceable(
+        &mut self,
+        client: ClientKey,
+        slot: u64,
+        msg: impl Into<P::S2C>,
+    ) -> Result<(), WebTransportError<P>> {
+        let mut msg = msg.into();
+        if filter::run(&mut self.send_filters, client, &mut msg) == Verdict::Drop {
+            return Ok(());
+        }
+
+        let policy = self.settings.on_serialize_error;
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                let result = server.send_replaceable(client, slot, msg, &self.codec);
+                server.apply_serialize_policy(client, policy, result)
+            }
+        }
+    }
+
+    /// Sends a message to a client, dropping it if the backend has not sent it
+    /// within `ttl`.
+    ///
+    /// This is useful for frequent, short-lived messages such as position
+    /// updates, where sending an outdated message under congestion is worse
+    /// than not sending it at all. Expired messages are counted in
+    /// [`LaneStats::expired`].
+    ///
+    /// The TTL only applies to messages on unreliable lanes, and is ignored
+    /// for reliable ones, which are always sent.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    ///
+    /// [`LaneStats::expired`]: crate::LaneStats::expired
+    pub fn send_with_ttl(
+        &mut self,
+        client: ClientKey,
+        msg: impl Into<P::S2C>,
+        ttl: Duration,
+    ) -> Result<(), WebTransportError<P>> {
+        self.send_with(client, msg.into(), Some(ttl))
+    }
+
+    /// Sends a message to a client without taking ownership of it.
+    ///
+    /// The message is serialized directly from the reference, so the same
+    /// message can be sent to many clients without cloning it for each one.
+    /// Since send filters may mutate the message, it is only cloned if any
+    /// send filters are registered, so that they can be run on the copy.
+    ///
+    /// # Errors
+    ///
+    /// See [`TransportServer::send`].
+    pub fn send_ref(&mut self, client: ClientKey, msg: &P::S2C) -> Result<(), WebTransportError<P>>
+    where
+        P::S2C: Clone,
+    {
+        let filtered;
+        let msg = if self.send_filters.is_empty() {
+            msg
+        } else {
+            let mut msg = msg.clone();
+            if filter::run(&mut self.send_filters, client, &mut msg) == Verdict::Drop {
+                return Ok(());
+            }
+            filtered = msg;
+            &filtered
+        };
+
+        let policy = self.settings.on_serialize_error;
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                let result = server.send(client, msg, None, &self.codec);
+                server.apply_serialize_policy(client, policy, result)
+            }
+        }
+    }
+
+    fn send_with(
+        &mut self,
+        client: ClientKey,
+        mut msg: P::S2C,
+        ttl: Option<Duration>,
+    ) -> Result<(), WebTransportError<P>> {
+        if filter::run(&mut self.send_filters, client, &mut msg) == Verdict::Drop {
+            return Ok(());
+        }
+
+        let policy = self.settings.on_serialize_error;
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => {
+                if let Some(pool) = &self.encode_pool {
+                    let send_err = &self.encode_errors.send;
+                    return server.send_offloaded(client, msg, ttl, &self.codec, pool, send_err);
+                }
+                let result = server.send(client, &msg, ttl, &self.codec);
+                server.apply_serialize_policy(client, policy, result)
+            }
+        }
+    }
+
+    /// Registers a filter which is run on every message received from a
+    /// client, before it is raised as a [`ServerEvent::Recv`].
+    ///
+    /// Filters can mutate the message, or return [`Verdict::Drop`] to discard
+    /// it entirely. This allows logic such as anti-cheat checks or chat
+    /// moderation to be applied centrally, rather than in every place that
+    /// handles received messages.
+    ///
+    /// Filters are run in the order that they were added, and are not run on
+    /// a message once a previous filter has dropped it.
+    pub fn add_recv_filter(
+        &mut self,
+        filter: impl FnMut(ClientKey, &mut P::C2S) -> Verdict + Send + Sync + 'static,
+    ) {
+        let filter: RecvFilter<P> = Box::new(filter);
+        self.recv_filters.push(filter);
+    }
+
+    /// Registers a filter which is run on every message sent to a client,
+    /// before it is queued for sending.
+    ///
+    /// A message dropped by a filter is discarded without being sent, but the
+    /// send operation still succeeds.
+    ///
+    /// See [`WebTransportServer::add_recv_filter`].
+    pub fn add_send_filter(
+        &mut self,
+        filter: impl FnMut(ClientKey, &mut P::S2C) -> Verdict + Send + Sync + 'static,
+    ) {
+        let filter: SendFilter<P> = Box::new(filter);
+        self.send_filters.push(filter);
+    }
+
+    /// Removes all filters registered using
+    /// [`WebTransportServer::add_recv_filter`] and
+    /// [`WebTransportServer::add_send_filter`].
+    pub fn clear_filters(&mut self) {
+        self.recv_filters.clear();
+        self.send_filters.clear();
+    }
+
+    /// Starts recording every client disconnect into the given
+    /// [`DisconnectLog`].
+    ///
+    /// This replaces any previously enabled log.
+    pub fn enable_disconnect_log(&mut self, log: DisconnectLog)
+    where
+        WebTransportError<P>: Error,
+    {
+        let error_chain: ErrorChainFn<P> = disconnect_log::error_chain;
+        self.disconnect_log = Some((log, error_chain));
+    }
+
+    /// Stops recording client disconnects, returning the log if one was
+    /// enabled.
+    pub fn disable_disconnect_log(&mut self) -> Option<DisconnectLog> {
+        self.disconnect_log.take().map(|(log, _)| log)
+    }
+
+    /// Gets the log of client disconnects, if one is enabled.
+    ///
+    /// See [`WebTransportServer::enable_disconnect_log`].
+    #[must_use]
+    pub fn disconnect_log(&self) -> Option<&DisconnectLog> {
+        self.disconnect_log.as_ref().map(|(log, _)| log)
+    }
+
+    /// Gets mutable access to the log of client disconnects, if one is
+    /// enabled.
+    ///
+    /// See [`WebTransportServer::enable_disconnect_log`].
+    pub fn disconnect_log_mut(&mut self) -> Option<&mut DisconnectLog> {
+        self.disconnect_log.as_mut().map(|(log, _)| log)
+    }
+
+    /// Starts recording the connection funnel of every client into the given
+    /// [`AnalyticsSink`].
+    ///
+    /// Only clients which connect after this is called are recorded. This
+    /// replaces any previously set sink.
+    pub fn set_analytics_sink(&mut self, sink: impl AnalyticsSink + 'static)
+    where
+        WebTransportError<P>: Error,
+    {
+        let error_chain: ErrorChainFn<P> = disconnect_log::error_chain;
+        self.analytics = Some((analytics::Funnel::new(Box::new(sink)), error_chain));
+    }
+
+    /// Stops recording the connection funnel of clients.
+    ///
+    /// See [`WebTransportServer::set_analytics_sink`].
+    pub fn clear_analytics_sink(&mut self) {
+        self.analytics = None;
+    }
+
+    /// Gets the timings of how long messages take to serialize and
+    /// deserialize on this server, if they are being recorded.
+    ///
+    /// See [`CodecTimings`].
+    #[cfg(feature = "codec-timing")]
+    #[must_use]
+    pub fn codec_timings(&self) -> Option<&Arc<CodecTimings<P::S2C, P::C2S>>> {
+        self.codec.timings.as_ref()
+    }
+
+    /// Starts or stops recording how long messages take to serialize and
+    /// deserialize on this server.
+    ///
+    /// See [`CodecTimings`].
+    #[cfg(feature = "codec-timing")]
+    pub fn set_codec_timings(&mut self, timings: Option<Arc<CodecTimings<P::S2C, P::C2S>>>) {
+        self.codec.timings = timings;
+    }
+}
+
+impl<P> TransportServer<P> for WebTransportServer<P>
+where
     P: WebTransportProtocol,
     P::C2S: TryFromBytes,
     P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
 {
-    fn new(config: ServerConfig) -> (Self, impl Future<Output = ()> + Send) {
+    const TRANSPORT_NAME: &'static str = "webtransport";
+
+    type Client = ClientKey;
+
+    type Error = WebTransportError<P>;
+
+    type ConnectionInfo = EndpointInfo;
+
+    type Event = ServerEvent<P>;
+
+    fn connection_info(&self, client: Self::Client) -> Option<Self::ConnectionInfo> {
+        match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => server.connection_info(client),
+        }
+    }
+
+    fn connected_clients(&self) -> impl Iterator<Item = Self::Client> {
+        let clients = match &self.state {
+            State::Closed | State::Opening(_) => None,
+            State::Open(server) => Some(server.clients()),
+        };
+        clients.into_iter().flatten()
+    }
+
+    fn send(
+        &mut self,
+        client: Self::Client,
+        msg: impl Into<P::S2C>,
+    ) -> Result<(), WebTransportError<P>> {
+        self.send_with(client, msg.into(), None)
+    }
+
+    fn send_to_many(
+        &mut self,
+        clients: impl IntoIterator<Item = Self::Client>,
+        msg: impl Into<P::S2C>,
+    ) -> SendToManyResult<Self::Client, Self::Error>
+    where
+        P::S2C: Clone,
+    {
+        let msg = msg.into();
+        // filters may change the message differently for each client, and the
+        // serialize error policy is applied separately for each client, so
+        // only the plain case can share one serialized message
+        if let (State::Open(server), true) = (&self.state, self.send_filters.is_empty()) {
+            if let Some(pool) = &self.encode_pool {
+                let send_err = &self.encode_errors.send;
+                return server.send_to_many_offloaded(clients, msg, &self.codec, pool, send_err);
+            }
+            if let Ok(serialized) = self.codec.serialize::<P>(&msg) {
+                return clients
+                    .into_iter()
+                    .map(|client| (client, server.queue(client, serialized.clone())))
+                    .collect();
+            }
+        }
+
+        clients
+            .into_iter()
+            .map(|client| (client, self.send_with(client, msg.clone(), None)))
+            .collect()
+    }
+
+    fn recv<'a>(&mut self) -> impl Iterator<Item = Self::Event> + 'a {
+        let config = RecvConfig {
+            now: self.clock.now(),
+            lane_stats_interval: self.settings.lane_stats_interval,
+            recv_progress_min_len: self.settings.recv_progress_min_len,
+            quality_report_interval: self.settings.quality_report_interval,
+            handshake_timeout: self.settings.handshake_timeout,
+            manual_accept: self.settings.manual_accept,
+            manual_admit: self.settings.manual_admit,
+            limits: &self.settings.limits,
+            bans: &self.bans,
+            memory_cap: self.settings.memory_cap,
+            arena: self.settings.arena,
+            incoming_per_poll: self.settings.incoming_queue.per_poll,
+            on_deserialize_error: self.settings.on_deserialize_error,
+            over_memory_cap: None,
+            draining: false,
+        };
+        let mut events = mem::take(&mut self.event_buf);
+        match &mut self.state {
+            State::Closed => {}
+            State::Opening(server) => match server.poll() {
+                Poll::Pending => {}
+                Poll::Ready(Ok(mut server)) => {
+                    server.clients.reserve(self.settings.arena.initial_capacity);
+                    self.state = State::Open(server);
+                    events.push(ServerEvent::Opened);
+                }
+                Poll::Ready(Err(cause)) => {
+                    self.state = State::Closed;
+                    events.push(ServerEvent::Closed { cause });
+                }
+            },
+            State::Open(server) => {
+                while let Ok((client, cause)) = self.encode_errors.recv.try_recv() {
+                    if matches!(cause, WebTransportError::NotConnected(_)) {
+                        // the message was dropped since the backend of the
+                        // client closed, which is not a serialize error
+                        events.push(ServerEvent::EncodeError { client, cause });
+                        continue;
+                    }
+                    match self.settings.on_serialize_error {
+                        OnMessageError::DisconnectClient => {
+                            let _ = server.disconnect(client);
+                            events.push(ServerEvent::EncodeError { client, cause });
+                        }
+                        OnMessageError::DropMessage => {
+                            debug!("Dropped message to {client:?}: {cause:#}");
+                        }
+                        OnMessageError::EmitEventOnly => {
+                            events.push(ServerEvent::EncodeError { client, cause });
+                        }
+                    }
+                }
+
+                match server.recv(
+                    &config,
+                    &self.codec,
+                    &mut self.eviction,
+                    &mut self.disconnect_log,
+                    &mut self.analytics,
+                    #[cfg(feature = "audit")]
+                    &mut self.audit,
+                ) {
+                    (new_events, Ok(())) => {
+                        events.extend(filter_recv(&mut self.recv_filters, new_events));
+                    }
+                    (new_events, Err(cause)) => {
+                        self.state = State::Closed;
+                        if let Some((funnel, _)) = &mut self.analytics {
+                            funnel.clear();
+                        }
+                        if let Some((log, _)) = &mut self.disconnect_log {
+                            log.clear_history();
+                        }
+                        #[cfg(feature = "audit")]
+                        if let Some((log, _)) = &mut self.audit {
+                            log.clear();
+                        }
+                        events.extend(filter_recv(&mut self.recv_filters, new_events));
+                        events.push(ServerEvent::Closed { cause });
+                    }
+                }
+            }
+        }
+        events.into_iter()
+    }
+
+    fn disconnect(&mut self, client: impl Into<Self::Client>) -> Result<(), Self::Error> {
+        match &mut self.state {
+            State::Closed | State::Opening(_) => Err(WebTransportError::BackendClosed),
+            State::Open(server) => server.disconnect(client),
+        }
+    }
+
+    fn push_event(&mut self, event: CustomEvent) {
+        self.event_buf.push(ServerEvent::Custom { event });
+    }
+}
+
+/// Queue through which messages are passed to the backend of a client.
+struct SendQueue<'a> {
+    send_s2c: &'a mpsc::UnboundedSender<Outgoing>,
+    counters: &'a SharedCounters,
+    order: &'a SharedEncodeOrder,
+}
+
+impl<'a> SendQueue<'a> {
+    fn of<P>(connected: &'a ConnectedClient<P>) -> Self
+    where
+        P: WebTransportProtocol,
+        P::C2S: TryFromBytes,
+        P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+    {
+        Self {
+            send_s2c: &connected.send_s2c,
+            counters: &connected.counters,
+            order: &connected.encode_order,
+        }
+    }
+
+    /// Passes a serialized message to the backend, after any earlier messages
+    /// which are still being serialized on an encode pool.
+    ///
+    /// Returns `false` if the backend is closed.
+    fn push(&self, msg: Outgoing) -> bool {
+        self.order.push(msg, |msg| {
+            shared::queue(self.send_s2c, &self.counters.lanes, msg)
+        })
+    }
+
+    /// Reserves the position of a message which is about to be serialized on
+    /// an encode pool.
+    fn reserve(&self) -> Reserved {
+        Reserved {
+            seq: self.order.reserve(),
+            send_s2c: self.send_s2c.clone(),
+            counters: self.counters.clone(),
+            order: self.order.clone(),
+        }
+    }
+}
+
+/// Position reserved in the queue of a client for a message which is being
+/// serialized on an encode pool.
+struct Reserved {
+    seq: u64,
+    send_s2c: mpsc::UnboundedSender<Outgoing>,
+    counters: SharedCounters,
+    order: SharedEncodeOrder,
+}
+
+impl Reserved {
+    /// Fills in the reserved position with the serialized message, or [`None`]
+    /// if it failed to serialize.
+    ///
+    /// Returns `false` if the backend closed before this message, or a message
+    /// held back behind it, could be passed to it.
+    #[must_use]
+    fn complete(self, msg: Option<Outgoing>) -> bool {
+        let Self {
+            seq,
+            send_s2c,
+            counters,
+            order,
+        } = self;
+        order.complete(seq, msg, |msg| {
+            shared::queue(&send_s2c, &counters.lanes, msg)
+        })
+    }
+}
+
+/// Settings of a [`WebTransportServer`] used when receiving events from its
+/// clients.
+#[derive(Debug, Clone, Copy)]
+struct RecvConfig<'a> {
+    /// Time at the start of this poll.
+    now: Instant,
+    lane_stats_interval: Option<Duration>,
+    recv_progress_min_len: Option<usize>,
+    quality_report_interval: Option<Duration>,
+    handshake_timeout: Option<Duration>,
+    manual_accept: bool,
+    manual_admit: bool,
+    limits: &'a ConnectionLimits,
+    bans: &'a BanList,
+    memory_cap: Option<MemoryCap>,
+    arena: ClientArena,
+    incoming_per_poll: Option<usize>,
+    on_deserialize_error: OnMessageError,
+    /// Total memory usage at the start of this poll, if it is above the cap.
+    over_memory_cap: Option<usize>,
+    draining: bool,
+}
+
+fn filter_recv<'a, P>(
+    filters: &'a mut [RecvFilter<P>],
+    events: Vec<ServerEvent<P>>,
+) -> impl Iterator<Item = ServerEvent<P>> + 'a
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    events.into_iter().filter_map(|event| match event {
+        ServerEvent::Recv { client, mut msg } => match filter::run(filters, client, &mut msg) {
+            Verdict::Pass => Some(ServerEvent::Recv { client, msg }),
+            Verdict::Drop => None,
+        },
+        event => Some(event),
+    })
+}
+
+impl<P> OpeningServer<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    fn new(
+        config: ServerConfig,
+        router: Option<SessionRouter>,
+        cipher: Option<Arc<LaneCipher>>,
+        recv_buffer_caps: RecvBufferCaps<P::Channel>,
+        features: Features,
+        fast_start: Option<usize>,
+        incoming_queue: IncomingQueue,
+    ) -> (Self, impl Future<Output = ()> + Send) {
+        let (mut servers, backend) = Self::new_sharded(
+            config,
+            router,
+            cipher,
+            recv_buffer_caps,
+            features,
+            fast_start,
+            incoming_queue,
+            NonZeroUsize::MIN,
+        );
+        let server = servers.pop().expect("should have created one shard");
+        (server, backend)
+    }
+
+    /// Creates a server which fails to open with `cause` the first time it is
+    /// polled.
+    fn failed(cause: WebTransportError<P>) -> Self {
         let (send_open, recv_open) = oneshot::channel();
-        (Self { recv_open }, backend::start::<P>(config, send_open))
+        let _ = send_open.send(Err(cause));
+        Self { recv_open }
+    }
+
+    fn new_sharded(
+        config: ServerConfig,
+        router: Option<SessionRouter>,
+        cipher: Option<Arc<LaneCipher>>,
+        recv_buffer_caps: RecvBufferCaps<P::Channel>,
+        features: Features,
+        fast_start: Option<usize>,
+        incoming_queue: IncomingQueue,
+        shards: NonZeroUsize,
+    ) -> (Vec<Self>, impl Future<Output = ()> + Send) {
+        let (send_open, servers) = (0..shards.get())
+            .map(|_| {
+                let (send_open, recv_open) = oneshot::channel();
+                (send_open, Self { recv_open })
+            })
+            .unzip();
+        (
+            servers,
+            backend::start::<P>(
+                config,
+                router,
+                cipher,
+                recv_buffer_caps,
+                features,
+                fast_start,
+                incoming_queue.capacity,
+                send_open,
+            ),
+        )
+    }
+
+    fn poll(&mut self) -> Poll<OpenServerResult<P>> {
+        match self.recv_open.try_recv() {
+            Ok(result) => Poll::Ready(result),
+            Err(oneshot::error::TryRecvError::Empty) => Poll::Pending,
+            Err(oneshot::error::TryRecvError::Closed) => {
+                Poll::Ready(Err(WebTransportError::BackendClosed))
+            }
+        }
+    }
+}
+
+impl<P> OpenServer<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    fn local_addr(&self) -> Result<SocketAddr, &io::Error> {
+        self.local_addr.as_ref().map(|addr| *addr)
+    }
+
+    fn clients(&self) -> impl Iterator<Item = ClientKey> + '_ {
+        self.clients
+            .iter()
+            .filter(|(_, state)| matches!(state, ClientState::Connected(_)))
+            .map(|(client, _)| client)
+    }
+
+    fn connection_info(&self, client: ClientKey) -> Option<EndpointInfo> {
+        self.clients.get(client).and_then(|client| match client {
+            ClientState::Pending { connected, .. } | ClientState::Connected(connected) => {
+                Some(EndpointInfo {
+                    features: connected.counters.features(),
+                    stats: connected.counters.epoch_stats(),
+                    ..connected.info.clone()
+                })
+            }
+            _ => None,
+        })
+    }
+
+    fn reset_stats(&mut self, client: ClientKey) -> Result<u64, WebTransportError<P>> {
+        match self.clients.get_mut(client) {
+            Some(ClientState::Pending { connected, .. } | ClientState::Connected(connected)) => {
+                connected.last_lane_stats = None;
+                Ok(connected.counters.reset_epoch())
+            }
+            Some(_) => Err(WebTransportError::NotConnected(client)),
+            None => Err(WebTransportError::NoClient(client)),
+        }
+    }
+
+    fn migrate_lane(
+        &self,
+        client: ClientKey,
+        channel: P::Channel,
+        lane: P::Channel,
+    ) -> Result<(), WebTransportError<P>> {
+        let state = self.sendable(client)?;
+        if !state.counters.features().contains(Features::LANE_MIGRATION) {
+            return Err(WebTransportError::LaneMigrationNotNegotiated);
+        }
+        state
+            .send_migrate
+            .send(MigrateLane {
+                channel: channel.index(),
+                lane: lane.index(),
+            })
+            .map_err(|_| WebTransportError::NotConnected(client))
+    }
+
+    /// Gets a client which messages can be sent to.
+    fn sendable(&self, client: ClientKey) -> Result<&ConnectedClient<P>, WebTransportError<P>> {
+        match self.clients.get(client) {
+            Some(ClientState::Pending { connected, .. } | ClientState::Connected(connected)) => {
+                Ok(connected)
+            }
+            Some(_) => Err(WebTransportError::NotConnected(client)),
+            None => Err(WebTransportError::NoClient(client)),
+        }
+    }
+
+    fn send(
+        &self,
+        client: ClientKey,
+        msg: &P::S2C,
+        ttl: Option<Duration>,
+        codec: &CodecHook<P::S2C, P::C2S>,
+    ) -> Result<(), WebTransportError<P>> {
+        self.send_queue(client, msg.channel().index())?;
+
+        let mut msg = codec.serialize(msg)?;
+        if let Some(ttl) = ttl {
+            msg.expire_after(ttl);
+        }
+        self.queue(client, msg)
+    }
+
+    /// Queues an already serialized message to be sent to a client.
+    fn queue(&self, client: ClientKey, msg: Outgoing) -> Result<(), WebTransportError<P>> {
+        let queue = self.send_queue(client, msg.lane())?;
+        if queue.push(msg) {
+            Ok(())
+        } else {
+            Err(WebTransportError::NotConnected(client))
+        }
+    }
+
+    /// Gets the queue through which a message on the given lane can be sent
+    /// to a client.
+    ///
+    /// Before the client is connected, only messages on the fast-start lane
+    /// can be sent, once its session has been accepted.
+    fn send_queue(
+        &self,
+        client: ClientKey,
+        lane: usize,
+    ) -> Result<SendQueue<'_>, WebTransportError<P>> {
+        match self.clients.get(client) {
+            Some(ClientState::Accepted(accepted))
+                if accepted.fast_start == Some(lane) && accepted.handshake_since.is_some() =>
+            {
+                Ok(SendQueue {
+                    send_s2c: &accepted.send_s2c,
+                    counters: &accepted.counters,
+                    order: &accepted.encode_order,
+                })
+            }
+            Some(ClientState::Pending { connected, .. } | ClientState::Connected(connected)) => {
+                Ok(SendQueue {
+                    send_s2c: &connected.send_s2c,
+                    counters: &connected.counters,
+                    order: &connected.encode_order,
+                })
+            }
+            Some(_) => Err(WebTransportError::NotConnected(client)),
+            None => Err(WebTransportError::NoClient(client)),
+        }
+    }
+
+    /// Sends a message to a client, serializing it on an encode pool.
+    ///
+    /// If the message fails to serialize, or the backend of the client closes
+    /// before it is passed on, the error is passed to `send_err`.
+    fn send_offloaded(
+        &self,
+        client: ClientKey,
+        msg: P::S2C,
+        ttl: Option<Duration>,
+        codec: &CodecHook<P::S2C, P::C2S>,
+        pool: &EncodePool,
+        send_err: &mpsc::UnboundedSender<(ClientKey, WebTransportError<P>)>,
+    ) -> Result<(), WebTransportError<P>> {
+        let reserved = self.send_queue(client, msg.channel().index())?.reserve();
+        let (codec, send_err) = (codec.clone(), send_err.clone());
+        pool.spawn(move || match codec.serialize::<P>(&msg) {
+            Ok(mut serialized) => {
+                if let Some(ttl) = ttl {
+                    serialized.expire_after(ttl);
+                }
+                if !reserved.complete(Some(serialized)) {
+                    let _ = send_err.send((client, WebTransportError::NotConnected(client)));
+                }
+            }
+            Err(err) => {
+                if !reserved.complete(None) {
+                    let _ = send_err.send((client, WebTransportError::NotConnected(client)));
+                }
+                let _ = send_err.send((client, err));
+            }
+        });
+        Ok(())
+    }
+
+    /// Sends a message to many clients, serializing it once on an encode pool.
+    ///
+    /// If the message fails to serialize, an error is passed to `send_err` for
+    /// each client, and likewise for each client whose backend closes before
+    /// the message is passed on.
+    fn send_to_many_offloaded(
+        &self,
+        clients: impl IntoIterator<Item = ClientKey>,
+        msg: P::S2C,
+        codec: &CodecHook<P::S2C, P::C2S>,
+        pool: &EncodePool,
+        send_err: &mpsc::UnboundedSender<(ClientKey, WebTransportError<P>)>,
+    ) -> Vec<(ClientKey, Result<(), WebTransportError<P>>)> {
+        let lane = msg.channel().index();
+        let mut reserved = Vec::new();
+        let results = clients
+            .into_iter()
+            .map(|client| {
+                let result = self.send_queue(client, lane).map(|queue| {
+                    reserved.push((client, queue.reserve()));
+                });
+                (client, result)
+            })
+            .collect();
+
+        if reserved.is_empty() {
+            return results;
+        }
+
+        let (codec, send_err) = (codec.clone(), send_err.clone());
+        pool.spawn(move || match codec.serialize::<P>(&msg) {
+            Ok(serialized) => {
+                for (client, reserved) in reserved {
+                    if !reserved.complete(Some(serialized.clone())) {
+                        let _ = send_err.send((client, WebTransportError::NotConnected(client)));
+                    }
+                }
+            }
+            Err(_) => {
+                for (client, reserved) in reserved {
+                    if !reserved.complete(None) {
+                        let _ = send_err.send((client, WebTransportError::NotConnected(client)));
+                    }
+                    // errors can not be cloned, so the message is serialized
+                    // again to get an error for each client
+                    if let Err(err) = codec.serialize::<P>(&msg) {
+                        let _ = send_err.send((client, err));
+                    }
+                }
+            }
+        });
+        results
+    }
+
+    fn total_memory_usage(&self) -> usize {
+        self.clients
+            .values()
+            .filter_map(|state| match state {
+                ClientState::Pending { connected, .. } | ClientState::Connected(connected) => {
+                    Some(connected.counters.memory_usage().total())
+                }
+                _ => None,
+            })
+            .sum()
+    }
+
+    fn connected_counters(&self, client: ClientKey) -> Option<&Counters> {
+        match self.clients.get(client) {
+            Some(ClientState::Connected(client)) => Some(&client.counters),
+            _ => None,
+        }
+    }
+
+    fn respond(
+        &mut self,
+        client: ClientKey,
+        response: SessionResponse,
+    ) -> Result<(), WebTransportError<P>> {
+        let Some(state) = self.clients.get_mut(client) else {
+            return Err(WebTransportError::NoClient(client));
+        };
+        let ClientState::Accepted(accepted) = state else {
+            return Err(WebTransportError::NotAwaitingResponse(client));
+        };
+        let Some(send_response) = accepted.send_response.take() else {
+            return Err(WebTransportError::NotAwaitingResponse(client));
+        };
+
+        // if the backend is gone, the client will be disconnected on the next
+        // recv anyway
+        let _ = send_response.send(response);
+        accepted.responded = Some(response);
+        Ok(())
+    }
+
+    /// Serializes a drain notice for every client which is connected or
+    /// pending admission.
+    fn drain_notices(
+        &self,
+        notice: &P::S2C,
+        filters: &mut [SendFilter<P>],
+        codec: &CodecHook<P::S2C, P::C2S>,
+    ) -> Result<Vec<(ClientKey, Outgoing)>, WebTransportError<P>>
+    where
+        P::S2C: Clone,
+    {
+        let clients = self
+            .clients
+            .iter()
+            .filter_map(|(client, state)| match state {
+                ClientState::Pending { .. } | ClientState::Connected(_) => Some(client),
+                _ => None,
+            });
+        serialize_for_each(clients, notice, filters, codec)
+    }
+
+    fn drain(&mut self, timeout: Duration, notices: Vec<(ClientKey, Outgoing)>, now: Instant) {
+        for state in self.clients.values_mut() {
+            if let ClientState::Accepted(_) = state {
+                *state = ClientState::Disconnected;
+            }
+        }
+        for (client, notice) in notices {
+            if let Some(
+                ClientState::Pending { connected, .. } | ClientState::Connected(connected),
+            ) = self.clients.get(client)
+            {
+                SendQueue::of(connected).push(notice);
+            }
+        }
+
+        self.drain = Some(Drain {
+            deadline: now + timeout,
+            drained: false,
+        });
     }
 
-    fn poll(&mut self) -> Poll<OpenServerResult<P>> {
-        match self.recv_open.try_recv() {
-            Ok(result) => Poll::Ready(result),
-            Err(oneshot::error::TryRecvError::Empty) => Poll::Pending,
-            Err(oneshot::error::TryRecvError::Closed) => {
-                Poll::Ready(Err(WebTransportError::BackendClosed))
+    fn broadcast_uncongested(
+        &mut self,
+        msg: P::S2C,
+        max_queued_bytes: usize,
+        filters: &mut [SendFilter<P>],
+        codec: &CodecHook<P::S2C, P::C2S>,
+    ) -> Result<Broadcast, WebTransportError<P>>
+    where
+        P::S2C: Clone,
+    {
+        let (mut congested, mut uncongested) = (Vec::new(), Vec::new());
+        for (client, state) in &self.clients {
+            let ClientState::Connected(connected) = state else {
+                continue;
+            };
+
+            if connected.counters.queued_bytes() > max_queued_bytes {
+                congested.push(client);
+            } else {
+                uncongested.push(client);
             }
         }
-    }
-}
+        let msgs = serialize_for_each(uncongested, &msg, filters, codec)?;
 
-impl<P> OpenServer<P>
-where
-    P: WebTransportProtocol,
-    P::C2S: TryFromBytes,
-    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
-{
-    fn local_addr(&self) -> Result<SocketAddr, &io::Error> {
-        self.local_addr.as_ref().map(|addr| *addr)
+        let mut result = Broadcast::default();
+        for client in congested {
+            if let Some(ClientState::Connected(connected)) = self.clients.get_mut(client) {
+                connected.skipped_broadcasts += 1;
+                result.skipped += 1;
+            }
+        }
+        for (client, msg) in msgs {
+            if let Some(ClientState::Connected(connected)) = self.clients.get(client) {
+                if SendQueue::of(connected).push(msg) {
+                    result.sent += 1;
+                }
+            }
+        }
+        Ok(result)
     }
 
-    fn clients(&self) -> impl Iterator<Item = ClientKey> + '_ {
-        self.clients.keys()
+    fn admit(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        match self.clients.get_mut(client) {
+            Some(ClientState::Pending { admitted, .. }) if !*admitted => {
+                *admitted = true;
+                Ok(())
+            }
+            Some(_) => Err(WebTransportError::NotPending(client)),
+            None => Err(WebTransportError::NoClient(client)),
+        }
     }
 
-    fn connection_info(&self, client: ClientKey) -> Option<EndpointInfo> {
-        self.clients.get(client).and_then(|client| match client {
-            ClientState::Connected(client) => Some(client.info.clone()),
-            _ => None,
-        })
+    fn refuse(&mut self, client: ClientKey) -> Result<(), WebTransportError<P>> {
+        match self.clients.get_mut(client) {
+            Some(
+                state @ ClientState::Pending {
+                    admitted: false, ..
+                },
+            ) => {
+                *state = ClientState::Disconnected;
+                Ok(())
+            }
+            Some(_) => Err(WebTransportError::NotPending(client)),
+            None => Err(WebTransportError::NoClient(client)),
+        }
     }
 
-    fn send(&self, client: ClientKey, msg: impl Into<P::S2C>) -> Result<(), WebTransportError<P>> {
-        let Some(state) = self.clients.get(client) else {
-            return Err(WebTransportError::NoClient(client));
-        };
-        let ClientState::Connected(state) = state else {
+    fn send_replaceable(
+        &self,
+        client: ClientKey,
+        slot: u64,
+        msg: impl Into<P::S2C>,
+        codec: &CodecHook<P::S2C, P::C2S>,
+    ) -> Result<(), WebTransportError<P>> {
+        let state = self.sendable(client)?;
+        if state.send_s2c.is_closed() {
             return Err(WebTransportError::NotConnected(client));
-        };
+        }
 
         let msg = msg.into();
-        state
-            .send_s2c
-            .send(msg)
-            .map_err(|_| WebTransportError::NotConnected(client))
+        let ordered = msg.channel().kind() == ChannelKind::ReliableOrdered;
+        let msg = codec.serialize(&msg)?;
+        if ordered {
+            // the replace queue could overtake earlier messages on this lane
+            return self.queue(client, msg);
+        }
+        state.replace_s2c.push(&state.counters.lanes, slot, msg);
+        Ok(())
     }
 
-    fn recv(&mut self) -> (Vec<ServerEvent<P>>, Result<(), WebTransportError<P>>) {
+    fn recv(
+        &mut self,
+        config: &RecvConfig,
+        codec: &CodecHook<P::S2C, P::C2S>,
+        eviction: &mut Eviction,
+        disconnect_log: &mut Option<(DisconnectLog, ErrorChainFn<P>)>,
+        analytics: &mut Option<(analytics::Funnel, ErrorChainFn<P>)>,
+        #[cfg(feature = "audit")] audit: &mut Option<(AuditLog, ErrorChainFn<P>)>,
+    ) -> (Vec<ServerEvent<P>>, Result<(), WebTransportError<P>>) {
         let mut events = Vec::new();
-        loop {
+        let mut taken = 0;
+        while config
+            .incoming_per_poll
+            .map_or(true, |per_poll| taken < per_poll)
+        {
             match self.recv_client.try_recv() {
                 Ok(client) => {
+                    taken += 1;
                     let client = self.clients.insert(ClientState::Incoming(client));
                     events.push(ServerEvent::Incoming { client });
                 }
@@ -228,17 +2248,234 @@ where
             }
         }
 
+        let config = &RecvConfig {
+            over_memory_cap: config.memory_cap.and_then(|cap| {
+                let usage = self.total_memory_usage();
+                (usage > cap.limit).then_some(usage)
+            }),
+            draining: self.drain.is_some(),
+            ..*config
+        };
+
         let mut to_remove = Vec::new();
         for (client, state) in &mut self.clients {
-            recv_client(client, state, &mut events, &mut to_remove);
+            recv_client(client, state, config, codec, &mut events, &mut to_remove);
+        }
+
+        if let Some(cap) = config.memory_cap.filter(|cap| cap.shed) {
+            if eviction.policy.is_some() {
+                let usage = self
+                    .eviction_candidates(&to_remove)
+                    .iter()
+                    .map(|candidate| candidate.memory_usage)
+                    .sum::<usize>();
+                if usage > cap.limit {
+                    let overload = Overload::Memory {
+                        usage,
+                        limit: cap.limit,
+                    };
+                    let mut total = usage;
+                    self.evict_clients(
+                        overload,
+                        eviction,
+                        &mut events,
+                        &mut to_remove,
+                        |evicted| {
+                            total = total.saturating_sub(evicted.memory_usage);
+                            total <= cap.limit
+                        },
+                    );
+                }
+            } else {
+                self.shed_clients(cap, &mut events, &mut to_remove);
+            }
+        }
+
+        if let Some(limit) = eviction.max_clients {
+            let mut count = self.eviction_candidates(&to_remove).len();
+            if count > limit {
+                let overload = Overload::Clients { count, limit };
+                self.evict_clients(overload, eviction, &mut events, &mut to_remove, |_| {
+                    count -= 1;
+                    count <= limit
+                });
+            }
+        }
+
+        let mut requested = mem::take(&mut eviction.requested);
+        if requested > 0 {
+            let overload = Overload::Requested { count: requested };
+            self.evict_clients(overload, eviction, &mut events, &mut to_remove, |_| {
+                requested -= 1;
+                requested == 0
+            });
         }
+
+        let drain_expired = self
+            .drain
+            .is_some_and(|drain| !drain.drained && config.now >= drain.deadline);
+        if drain_expired {
+            for client in self.clients.keys() {
+                if !to_remove.contains(&client) {
+                    events.push(ServerEvent::Disconnected {
+                        client,
+                        cause: WebTransportError::Draining,
+                    });
+                    to_remove.push(client);
+                }
+            }
+        }
+
+        if let Some((log, error_chain)) = disconnect_log {
+            for event in &events {
+                log.observe(event, |client| self.connection_info(client), *error_chain);
+            }
+        }
+
+        if let Some((funnel, error_chain)) = analytics {
+            for event in &events {
+                funnel.observe(event, config.now, *error_chain);
+            }
+        }
+
+        #[cfg(feature = "audit")]
+        if let Some((log, error_chain)) = audit {
+            for event in &events {
+                log.observe(event, |client| self.connection_info(client), *error_chain);
+            }
+            log.flush_or_warn();
+        }
+
         for client in to_remove {
             self.clients.remove(client);
         }
 
+        if let Some(drain) = &mut self.drain {
+            if !drain.drained && self.clients.is_empty() {
+                drain.drained = true;
+                events.push(ServerEvent::Drained);
+            }
+        }
+
+        self.compact_if_idle(config.arena, config.now);
+
         (events, Ok(()))
     }
 
+    fn compact_if_idle(&mut self, arena: ClientArena, now: Instant) {
+        if !self.clients.is_empty() {
+            self.idle_since = None;
+            return;
+        }
+
+        let idle_since = *self.idle_since.get_or_insert(now);
+        let ArenaShrink::OnIdle(after) = arena.shrink else {
+            return;
+        };
+        if now.saturating_duration_since(idle_since) >= after
+            && self.clients.capacity() > arena.initial_capacity
+        {
+            debug!(
+                "Compacting client arena from {} to {} slots",
+                self.clients.capacity(),
+                arena.initial_capacity
+            );
+            self.clients = SlotMap::with_capacity_and_key(arena.initial_capacity);
+        }
+    }
+
+    /// Disconnects the clients using the most memory until the total usage is
+    /// no longer above the cap.
+    fn shed_clients(
+        &self,
+        cap: MemoryCap,
+        events: &mut Vec<ServerEvent<P>>,
+        to_remove: &mut Vec<ClientKey>,
+    ) {
+        let mut usages = self
+            .clients
+            .iter()
+            .filter(|(client, _)| !to_remove.contains(client))
+            .filter_map(|(client, state)| match state {
+                ClientState::Pending { connected, .. } | ClientState::Connected(connected) => {
+                    Some((client, connected.counters.memory_usage().total()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut total = usages.iter().map(|(_, usage)| usage).sum::<usize>();
+        if total <= cap.limit {
+            return;
+        }
+
+        usages.sort_unstable_by_key(|(_, usage)| Reverse(*usage));
+        for (client, usage) in usages {
+            if total <= cap.limit {
+                break;
+            }
+            events.push(ServerEvent::Disconnected {
+                client,
+                cause: WebTransportError::MemoryCapExceeded(total),
+            });
+            to_remove.push(client);
+            total -= usage;
+        }
+    }
+
+    /// Gets the clients which can be evicted, which are the clients with an
+    /// established connection that are not being removed already.
+    fn eviction_candidates(&self, to_remove: &[ClientKey]) -> Vec<EvictionCandidate> {
+        self.clients
+            .iter()
+            .filter(|(client, _)| !to_remove.contains(client))
+            .filter_map(|(client, state)| {
+                let (connected, admitted) = match state {
+                    ClientState::Pending { connected, .. } => (connected, false),
+                    ClientState::Connected(connected) => (connected, true),
+                    _ => return None,
+                };
+                Some(EvictionCandidate {
+                    client,
+                    admitted,
+                    memory_usage: connected.counters.memory_usage().total(),
+                    info: connected.info.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Evicts the clients picked by the eviction policy, in order, until
+    /// `relieved` returns `true` for the last evicted client.
+    fn evict_clients(
+        &self,
+        overload: Overload,
+        eviction: &mut Eviction,
+        events: &mut Vec<ServerEvent<P>>,
+        to_remove: &mut Vec<ClientKey>,
+        mut relieved: impl FnMut(&EvictionCandidate) -> bool,
+    ) {
+        let candidates = self.eviction_candidates(to_remove);
+        for client in eviction.select(&overload, &candidates) {
+            let Some(candidate) = candidates
+                .iter()
+                .find(|candidate| candidate.client == client)
+            else {
+                continue;
+            };
+            if to_remove.contains(&client) {
+                continue;
+            }
+            events.push(ServerEvent::Disconnected {
+                client,
+                cause: WebTransportError::Evicted(overload),
+            });
+            to_remove.push(client);
+            if relieved(candidate) {
+                break;
+            }
+        }
+    }
+
     fn disconnect(&mut self, client: impl Into<ClientKey>) -> Result<(), WebTransportError<P>> {
         let client = client.into();
         match self.clients.get_mut(client) {
@@ -249,11 +2486,73 @@ where
             None => Err(WebTransportError::NoClient(client)),
         }
     }
+
+    /// Applies the serialize error policy to the result of sending a message
+    /// to a client.
+    fn apply_serialize_policy(
+        &mut self,
+        client: ClientKey,
+        policy: OnMessageError,
+        result: Result<(), WebTransportError<P>>,
+    ) -> Result<(), WebTransportError<P>> {
+        match result {
+            Err(err) if shared::is_serialize_error(&err) => match policy {
+                OnMessageError::DisconnectClient => {
+                    let _ = self.disconnect(client);
+                    Err(err)
+                }
+                OnMessageError::DropMessage => {
+                    debug!("Dropped message to {client:?}: {err:#}");
+                    Ok(())
+                }
+                OnMessageError::EmitEventOnly => Err(err),
+            },
+            result => result,
+        }
+    }
+}
+
+/// Serializes a message sent to many clients, running the send filters on it
+/// for each client.
+///
+/// Without filters, the message is serialized once and shared by all clients.
+/// Otherwise, each client gets its own copy of the message for the filters to
+/// change, and clients for which a filter drops the message are left out. If
+/// any copy fails to serialize, the error is returned and nothing is sent.
+fn serialize_for_each<P>(
+    clients: impl IntoIterator<Item = ClientKey>,
+    msg: &P::S2C,
+    filters: &mut [SendFilter<P>],
+    codec: &CodecHook<P::S2C, P::C2S>,
+) -> Result<Vec<(ClientKey, Outgoing)>, WebTransportError<P>>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel> + Clone,
+{
+    if filters.is_empty() {
+        let msg = codec.serialize::<P>(msg)?;
+        return Ok(clients
+            .into_iter()
+            .map(|client| (client, msg.clone()))
+            .collect());
+    }
+
+    let mut msgs = Vec::new();
+    for client in clients {
+        let mut msg = msg.clone();
+        if filter::run(filters, client, &mut msg) == Verdict::Pass {
+            msgs.push((client, codec.serialize::<P>(&msg)?));
+        }
+    }
+    Ok(msgs)
 }
 
 fn recv_client<P>(
     client: ClientKey,
     state: &mut ClientState<P>,
+    config: &RecvConfig,
+    codec: &CodecHook<P::S2C, P::C2S>,
     events: &mut Vec<ServerEvent<P>>,
     to_remove: &mut Vec<ClientKey>,
 ) where
@@ -263,14 +2562,38 @@ fn recv_client<P>(
 {
     match state {
         ClientState::Incoming(incoming) => match incoming.recv_accepted.try_recv() {
-            Ok(Ok(accepted)) => {
-                events.push(ServerEvent::Accepted {
-                    client,
-                    authority: accepted.authority.clone(),
-                    path: accepted.path.clone(),
-                    origin: accepted.origin.clone(),
-                    user_agent: accepted.user_agent.clone(),
-                });
+            Ok(Ok(mut accepted)) => {
+                let rejected = if config.draining {
+                    Some(WebTransportError::Draining)
+                } else {
+                    config
+                        .over_memory_cap
+                        .map(WebTransportError::MemoryCapExceeded)
+                };
+                if let Some(cause) = rejected {
+                    if let Some(send_response) = accepted.send_response.take() {
+                        let _ = send_response.send(SessionResponse::Forbidden);
+                    }
+                    events.push(ServerEvent::Disconnected { client, cause });
+                    to_remove.push(client);
+                    return;
+                }
+
+                if config.manual_accept {
+                    events.push(ServerEvent::Requested {
+                        client,
+                        authority: accepted.authority.clone(),
+                        path: accepted.path.clone(),
+                        origin: accepted.origin.clone(),
+                        user_agent: accepted.user_agent.clone(),
+                    });
+                } else {
+                    if let Some(send_response) = accepted.send_response.take() {
+                        let _ = send_response.send(SessionResponse::Accept);
+                    }
+                    accepted.handshake_since = Some(config.now);
+                    events.push(accepted_event(client, &accepted));
+                }
                 *state = ClientState::Accepted(accepted);
             }
             Ok(Err(cause)) => {
@@ -286,47 +2609,233 @@ fn recv_client<P>(
                 to_remove.push(client);
             }
         },
-        ClientState::Accepted(accepted) => match accepted.recv_connected.try_recv() {
-            Ok(Ok(connected)) => {
-                events.push(ServerEvent::Connected { client });
+        ClientState::Accepted(accepted) => {
+            match accepted.responded.take() {
+                Some(SessionResponse::Accept) => {
+                    accepted.handshake_since = Some(config.now);
+                    events.push(accepted_event(client, accepted));
+                }
+                Some(response) => {
+                    events.push(ServerEvent::Disconnected {
+                        client,
+                        cause: WebTransportError::SessionRejected(response),
+                    });
+                    to_remove.push(client);
+                    return;
+                }
+                None => {}
+            }
+
+            if accepted.fast_start.is_some() {
+                let recv_early = &mut accepted.recv_early;
+                let incoming = iter::from_fn(|| recv_early.try_recv().ok());
+                let counters = &accepted.counters;
+                if recv_msgs(client, counters, incoming, config, codec, events, to_remove).is_none()
+                {
+                    return;
+                }
+            }
+
+            match accepted.recv_connected.try_recv() {
+                Ok(Ok(mut connected)) => {
+                    // anything received on the fast-start lane since the
+                    // messages above were taken is delivered once connected
+                    connected
+                        .early
+                        .extend(iter::from_fn(|| accepted.recv_early.try_recv().ok()));
+
+                    let addr = BanTarget::Addr(connected.info.remote_addr.ip());
+                    if let Some(ban) = config.bans.get(&addr, SystemTime::now()) {
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: WebTransportError::Banned(ban.reason.clone()),
+                        });
+                        // kept until the client is removed, so that its
+                        // connection info is still available to the logs
+                        *state = ClientState::Connected(connected);
+                        to_remove.push(client);
+                        return;
+                    }
+
+                    if config.manual_admit {
+                        events.push(ServerEvent::PendingConnected { client });
+                        *state = ClientState::Pending {
+                            connected,
+                            admitted: false,
+                        };
+                    } else {
+                        events.push(ServerEvent::Connected { client });
+                        *state = ClientState::Connected(connected);
+                    }
+                }
+                Ok(Err(cause)) => {
+                    events.push(ServerEvent::Disconnected { client, cause });
+                    to_remove.push(client);
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    let timed_out = config.handshake_timeout.filter(|timeout| {
+                        accepted.handshake_since.is_some_and(|since| {
+                            config.now.saturating_duration_since(since) >= *timeout
+                        })
+                    });
+                    if let Some(timeout) = timed_out {
+                        // dropping the client's receiver stops the backend
+                        // from waiting on its channels
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: WebTransportError::HandshakeTimeout(timeout),
+                        });
+                        to_remove.push(client);
+                    }
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    events.push(ServerEvent::Disconnected {
+                        client,
+                        cause: WebTransportError::BackendClosed,
+                    });
+                    to_remove.push(client);
+                }
+            }
+        }
+        ClientState::Pending { admitted: true, .. } => {
+            if let ClientState::Pending { connected, .. } =
+                mem::replace(state, ClientState::Disconnected)
+            {
                 *state = ClientState::Connected(connected);
             }
-            Ok(Err(cause)) => {
-                events.push(ServerEvent::Disconnected { client, cause });
-                to_remove.push(client);
+            events.push(ServerEvent::Connected { client });
+            // deliver the messages which were held back while pending
+            recv_client(client, state, config, codec, events, to_remove);
+        }
+        ClientState::Pending {
+            connected,
+            admitted: false,
+        } => {
+            while let Ok(info) = connected.recv_info.try_recv() {
+                connected.info = info;
             }
-            Err(oneshot::error::TryRecvError::Empty) => {}
-            Err(oneshot::error::TryRecvError::Closed) => {
-                events.push(ServerEvent::Disconnected {
-                    client,
-                    cause: WebTransportError::BackendClosed,
-                });
-                to_remove.push(client);
+
+            while let Ok(incoming) = connected.recv_c2s.try_recv() {
+                match connected.limits.check_early(config.limits, incoming.size) {
+                    Ok(early) => {
+                        events.extend(
+                            early
+                                .warnings
+                                .into_iter()
+                                .map(|usage| ServerEvent::LimitWarning { client, usage }),
+                        );
+                        if early.hold {
+                            connected.early.push(incoming);
+                        } else {
+                            connected.counters.on_recv_taken(incoming.size);
+                            debug!("Dropped early message from {client:?}");
+                        }
+                    }
+                    Err(usage) => {
+                        events.push(ServerEvent::Disconnected {
+                            client,
+                            cause: WebTransportError::LimitExceeded(usage),
+                        });
+                        to_remove.push(client);
+                        return;
+                    }
+                }
             }
-        },
+            recv_err(client, connected, events, to_remove);
+        }
         ClientState::Connected(connected) => {
             while let Ok(info) = connected.recv_info.try_recv() {
+                connected.quality.on_rtt(info.rtt);
                 connected.info = info;
             }
 
-            while let Ok(msg) = connected.recv_c2s.try_recv() {
-                events.push(ServerEvent::Recv { client, msg });
+            // messages held back while pending are delivered first
+            let early = mem::take(&mut connected.early);
+            let recv_c2s = &mut connected.recv_c2s;
+            let incoming = early
+                .into_iter()
+                .chain(iter::from_fn(|| recv_c2s.try_recv().ok()));
+            let (counters, limits) = (&connected.counters, &mut connected.limits);
+            if recv_msgs(
+                client, counters, limits, incoming, config, codec, events, to_remove,
+            )
+            .is_none()
+            {
+                return;
             }
+            while let Ok(event) = connected.recv_lane_events.try_recv() {
+                events.push(match event {
+                    LaneEvent::Closed(channel) => ServerEvent::StreamClosed { client, channel },
+                    LaneEvent::ChecksumMismatch(mismatch) => {
+                        ServerEvent::ChecksumMismatch { client, mismatch }
+                    }
+                    LaneEvent::Rejected(channel) => {
+                        ServerEvent::MessageRejected { client, channel }
+                    }
+                    LaneEvent::Migrated { channel, lane } => ServerEvent::LaneMigrated {
+                        client,
+                        channel,
+                        lane,
+                    },
+                    LaneEvent::MigrationRejected { channel, lane } => {
+                        ServerEvent::LaneMigrationRejected {
+                            client,
+                            channel,
+                            lane,
+                        }
+                    }
+                });
+            }
+            events.extend(
+                shared::take_recv_progress(&connected.counters, config.recv_progress_min_len)
+                    .into_iter()
+                    .map(|(from, received, total)| ServerEvent::RecvProgress {
+                        client,
+                        from,
+                        received,
+                        total,
+                    }),
+            );
 
-            match connected.recv_err.try_recv() {
-                Ok(cause) => {
-                    events.push(ServerEvent::Disconnected { client, cause });
-                    to_remove.push(client);
+            match connected
+                .limits
+                .check_send(config.limits, connected.counters.queued())
+            {
+                Ok(warning) => {
+                    events.extend(warning.map(|usage| ServerEvent::LimitWarning { client, usage }))
                 }
-                Err(oneshot::error::TryRecvError::Empty) => {}
-                Err(oneshot::error::TryRecvError::Closed) => {
+                Err(usage) => {
                     events.push(ServerEvent::Disconnected {
                         client,
-                        cause: WebTransportError::BackendClosed,
+                        cause: WebTransportError::LimitExceeded(usage),
                     });
                     to_remove.push(client);
+                    return;
                 }
             }
+
+            if let Some(stats) = shared::take_lane_stats(
+                &connected.counters,
+                config.lane_stats_interval,
+                &mut connected.last_lane_stats,
+                config.now,
+            ) {
+                events.extend(
+                    stats
+                        .into_iter()
+                        .map(|stats| ServerEvent::LaneStats { client, stats }),
+                );
+            }
+
+            if let Some(sample) = connected.quality.take_sample(
+                &connected.counters,
+                config.quality_report_interval,
+                config.now,
+            ) {
+                let _ = connected.send_quality.send(sample);
+            }
+
+            recv_err(client, connected, events, to_remove);
         }
         ClientState::Disconnected => {
             events.push(ServerEvent::Disconnected {
@@ -335,5 +2844,127 @@ fn recv_client<P>(
             });
             to_remove.push(client);
         }
+        ClientState::Kicked { reason, banned } => {
+            let reason = mem::take(reason);
+            let cause = if *banned {
+                WebTransportError::Banned(reason)
+            } else {
+                WebTransportError::Kicked(reason)
+            };
+            events.push(ServerEvent::Disconnected { client, cause });
+            to_remove.push(client);
+        }
+    }
+}
+
+/// Raises events for messages received from a client, applying the
+/// deserialize error policy.
+///
+/// Each message is checked against the receive limits before it is raised, so
+/// a message over a hard limit is never delivered.
+///
+/// Returns [`None`] if the client was disconnected, because a message exceeded
+/// a limit or failed to deserialize.
+#[allow(clippy::too_many_arguments)]
+fn recv_msgs<P>(
+    client: ClientKey,
+    counters: &Counters,
+    limits: &mut LimitsState,
+    incoming: impl Iterator<Item = Incoming<P::C2S>>,
+    config: &RecvConfig,
+    codec: &CodecHook<P::S2C, P::C2S>,
+    events: &mut Vec<ServerEvent<P>>,
+    to_remove: &mut Vec<ClientKey>,
+) -> Option<()>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    for recv in incoming {
+        codec.on_recv(&recv);
+        let Incoming {
+            msg, lane, size, ..
+        } = recv;
+        counters.on_recv_taken(size);
+        match limits.check_recv(config.limits, size, config.now) {
+            Ok(warnings) => events.extend(
+                warnings
+                    .into_iter()
+                    .map(|usage| ServerEvent::LimitWarning { client, usage }),
+            ),
+            Err(usage) => {
+                events.push(ServerEvent::Disconnected {
+                    client,
+                    cause: WebTransportError::LimitExceeded(usage),
+                });
+                to_remove.push(client);
+                return None;
+            }
+        }
+        let err = match msg {
+            Ok(msg) => {
+                events.push(ServerEvent::Recv { client, msg });
+                continue;
+            }
+            Err(err) => err,
+        };
+
+        let cause = shared::deserialize_error(lane, err);
+        match config.on_deserialize_error {
+            OnMessageError::DisconnectClient => {
+                events.push(ServerEvent::Disconnected { client, cause });
+                to_remove.push(client);
+                return None;
+            }
+            OnMessageError::DropMessage => {
+                debug!("Dropped message from {client:?}: {cause:#}");
+            }
+            OnMessageError::EmitEventOnly => {
+                events.push(ServerEvent::MessageError { client, cause });
+            }
+        }
+    }
+    Some(())
+}
+
+fn recv_err<P>(
+    client: ClientKey,
+    connected: &mut ConnectedClient<P>,
+    events: &mut Vec<ServerEvent<P>>,
+    to_remove: &mut Vec<ClientKey>,
+) where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    match connected.recv_err.try_recv() {
+        Ok(cause) => {
+            events.push(ServerEvent::Disconnected { client, cause });
+            to_remove.push(client);
+        }
+        Err(oneshot::error::TryRecvError::Empty) => {}
+        Err(oneshot::error::TryRecvError::Closed) => {
+            events.push(ServerEvent::Disconnected {
+                client,
+                cause: WebTransportError::BackendClosed,
+            });
+            to_remove.push(client);
+        }
+    }
+}
+
+fn accepted_event<P>(client: ClientKey, accepted: &AcceptedClient<P>) -> ServerEvent<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    ServerEvent::Accepted {
+        client,
+        authority: accepted.authority.clone(),
+        path: accepted.path.clone(),
+        origin: accepted.origin.clone(),
+        user_agent: accepted.user_agent.clone(),
     }
 }
@@ -0,0 +1,203 @@
+use std::{
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use aeronet::{OnChannel, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+use futures::{Stream, StreamExt};
+use tokio::{
+    runtime::Handle,
+    task::{JoinError, JoinHandle},
+};
+
+use crate::{
+    ServerEvent, ServerEventStream, WebTransportProtocol, WebTransportServer,
+    WebTransportServerConfig,
+};
+
+use super::WebTransportError;
+
+/// Server whose backend runs on an existing tokio runtime, for embedding
+/// aeronet in an app which does not use Bevy.
+///
+/// The handle owns both the server frontend and the task running its backend.
+/// Events can be received in two ways, which should not be mixed:
+/// * from a loop owned by the app, by calling [`TransportServer::recv`] through
+///   [`DerefMut`], e.g. once per tick of a game loop
+/// * as an async [`Stream`], which polls the server every poll interval while
+///   there are no events (see [`ServerEventStream`])
+///
+/// ```ignore
+/// use futures::StreamExt;
+///
+/// let mut server = ServerHandle::opening(&Handle::current(), config, Duration::from_millis(5));
+/// while let Some(event) = server.next().await {
+///     if let ServerEvent::Recv { client, msg } = event {
+///         server.send(client, msg)?;
+///     }
+/// }
+/// server.shutdown(Duration::from_secs(5)).await?;
+/// ```
+///
+/// Dropping the handle closes the server and stops its backend, but does not
+/// wait for the backend to finish. Use [`ServerHandle::shutdown`] to
+/// disconnect clients gracefully and wait for the backend.
+///
+/// See the `embedded_server` example for a server embedded in an `axum` app,
+/// which also serves health and metrics endpoints.
+///
+/// [`TransportServer::recv`]: aeronet::TransportServer::recv
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ServerHandle<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    events: ServerEventStream<P>,
+    #[derivative(Debug = "ignore")]
+    backend: JoinHandle<()>,
+}
+
+impl<P> ServerHandle<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Creates and starts opening a server, spawning its backend on `runtime`.
+    ///
+    /// While there are no events, the event stream polls the server every
+    /// `poll_interval`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_interval` is zero, or if the time driver of `runtime` is
+    /// not enabled.
+    #[must_use]
+    pub fn opening(
+        runtime: &Handle,
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+        poll_interval: Duration,
+    ) -> Self {
+        let (server, backend) = WebTransportServer::opening(config);
+        Self::new(runtime, server, backend, poll_interval)
+    }
+
+    /// Opens a closed server which has already been configured, spawning its
+    /// backend on `runtime`.
+    ///
+    /// See [`ServerHandle::opening`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if `server` is already opening or is opened, or if `config` is
+    /// invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_interval` is zero, or if the time driver of `runtime` is
+    /// not enabled.
+    pub fn spawn(
+        runtime: &Handle,
+        mut server: WebTransportServer<P>,
+        config: impl Into<WebTransportServerConfig<P::Channel>>,
+        poll_interval: Duration,
+    ) -> Result<Self, WebTransportError<P>> {
+        let backend = server.open(config)?;
+        Ok(Self::new(runtime, server, backend, poll_interval))
+    }
+
+    fn new(
+        runtime: &Handle,
+        server: WebTransportServer<P>,
+        backend: impl Future<Output = ()> + Send + 'static,
+        poll_interval: Duration,
+    ) -> Self {
+        let backend = runtime.spawn(backend);
+        // the poll interval is registered with the time driver of the
+        // current runtime
+        let _guard = runtime.enter();
+        Self {
+            events: ServerEventStream::new(server, poll_interval),
+            backend,
+        }
+    }
+
+    /// Gets if the backend task has finished, e.g. because the endpoint could
+    /// not be created or the server was closed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.backend.is_finished()
+    }
+
+    /// Shuts down the server gracefully, and waits for its backend to finish.
+    ///
+    /// If the server is open, it is drained with no notice (see
+    /// [`WebTransportServer::drain`]), and its events are received until
+    /// either all clients have left or `timeout` has passed. These events are
+    /// discarded, so if they are needed, drain the server and receive its
+    /// events manually before calling this.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the backend task panicked or was cancelled.
+    pub async fn shutdown(self, timeout: Duration) -> Result<(), JoinError> {
+        let Self {
+            mut events,
+            backend,
+        } = self;
+        if events.drain_silently(timeout).is_ok() {
+            while let Some(event) = events.next().await {
+                if matches!(event, ServerEvent::Drained) {
+                    break;
+                }
+            }
+        }
+        // the backend stops once the frontend is dropped
+        drop(events);
+        backend.await
+    }
+}
+
+impl<P> Deref for ServerHandle<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    type Target = WebTransportServer<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+impl<P> DerefMut for ServerHandle<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.events
+    }
+}
+
+impl<P> Stream for ServerHandle<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    type Item = ServerEvent<P>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().events.poll_next_unpin(cx)
+    }
+}
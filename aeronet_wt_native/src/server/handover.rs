@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use aeronet::{OnChannel, TransportServer, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{ClientKey, ServerEvent, WebTransportProtocol, WebTransportServer};
+
+const VERSION: u8 = 1;
+
+const TAG_LEN: usize = 32;
+
+// version + resume key + issued at + expires at + payload length
+const HEADER_LEN: usize = 1 + RESUME_KEY_LEN + 8 + 8 + 4;
+
+/// Length in bytes of [`HandoverTicket::resume_key`].
+pub const RESUME_KEY_LEN: usize = 16;
+
+/// Secret used to sign and verify [`HandoverTicket`]s.
+///
+/// Every server which a client may be handed over between must use the same
+/// secret, and the secret must never be sent to clients.
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct HandoverKey {
+    #[derivative(Debug = "ignore")]
+    key: hmac::Key,
+}
+
+impl HandoverKey {
+    /// Creates a key from a shared secret.
+    ///
+    /// The secret should be at least 32 bytes of random data.
+    #[must_use]
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+}
+
+/// Signed proof that a client was connected to a server, allowing it to
+/// connect to another server without authenticating again.
+///
+/// A ticket is issued by the server that the client is leaving using
+/// [`WebTransportServer::issue_handover`], and is then sent to the client by
+/// the app (e.g. in a message telling it which server to move to). The client
+/// connects to the new server and presents the encoded ticket, for example in
+/// the query of the session request or in its first message, and the new
+/// server checks it using [`WebTransportServer::accept_handover`].
+///
+/// [`WebTransportServer::issue_handover`]: crate::WebTransportServer::issue_handover
+/// [`WebTransportServer::accept_handover`]: crate::WebTransportServer::accept_handover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandoverTicket {
+    /// Random key identifying this handover.
+    ///
+    /// Each ticket can only be accepted once per server, which is tracked
+    /// using this key.
+    pub resume_key: [u8; RESUME_KEY_LEN],
+    /// When this ticket was issued.
+    pub issued_at: SystemTime,
+    /// When this ticket stops being accepted.
+    pub expires_at: SystemTime,
+    /// App-defined data carried from the old server to the new one, such as
+    /// the ID of the player's account.
+    ///
+    /// This is signed, but not encrypted, so the client can read it.
+    pub payload: Vec<u8>,
+}
+
+/// Error that occurs when issuing or accepting a [`HandoverTicket`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HandoverError {
+    /// The server has no [`HandoverKey`] set.
+    #[error("no handover key set")]
+    NoKey,
+    /// Attempted to issue a ticket for a client which is not connected.
+    #[error("no client with key {0:?}")]
+    NoClient(ClientKey),
+    /// Failed to generate a random resume key.
+    #[error("failed to generate resume key")]
+    Random,
+    /// The ticket could not be decoded.
+    #[error("malformed ticket")]
+    Malformed,
+    /// The ticket was not signed with the same [`HandoverKey`] as this server,
+    /// or was modified after it was signed.
+    #[error("invalid signature")]
+    InvalidSignature,
+    /// The ticket has expired.
+    #[error("ticket expired")]
+    Expired,
+    /// The ticket has already been accepted by this server.
+    #[error("ticket already redeemed")]
+    AlreadyRedeemed,
+}
+
+impl HandoverTicket {
+    /// Encodes and signs this ticket.
+    #[must_use]
+    pub fn to_bytes(&self, key: &HandoverKey) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.payload.len() + TAG_LEN);
+        buf.push(VERSION);
+        buf.extend_from_slice(&self.resume_key);
+        buf.extend_from_slice(&unix_secs(self.issued_at).to_be_bytes());
+        buf.extend_from_slice(&unix_secs(self.expires_at).to_be_bytes());
+        // payloads are app-defined and small
+        #[allow(clippy::cast_possible_truncation)]
+        buf.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&self.payload);
+        let tag = hmac::sign(&key.key, &buf);
+        buf.extend_from_slice(tag.as_ref());
+        buf
+    }
+
+    /// Verifies the signature of an encoded ticket, and decodes it.
+    ///
+    /// This does not check if the ticket has expired.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the ticket is malformed or was not signed with `key`.
+    pub fn from_bytes(key: &HandoverKey, buf: &[u8]) -> Result<Self, HandoverError> {
+        if buf.len() < HEADER_LEN + TAG_LEN {
+            return Err(HandoverError::Malformed);
+        }
+        let (data, tag) = buf.split_at(buf.len() - TAG_LEN);
+        hmac::verify(&key.key, data, tag).map_err(|_| HandoverError::InvalidSignature)?;
+
+        let (header, payload) = data.split_at(HEADER_LEN);
+        if header[0] != VERSION {
+            return Err(HandoverError::Malformed);
+        }
+        let mut resume_key = [0; RESUME_KEY_LEN];
+        resume_key.copy_from_slice(&header[1..=RESUME_KEY_LEN]);
+        let rest = &header[1 + RESUME_KEY_LEN..];
+        let issued_at = read_u64(&rest[0..8]);
+        let expires_at = read_u64(&rest[8..16]);
+        let payload_len = u32::from_be_bytes([rest[16], rest[17], rest[18], rest[19]]) as usize;
+        if payload.len() != payload_len {
+            return Err(HandoverError::Malformed);
+        }
+
+        Ok(Self {
+            resume_key,
+            issued_at: UNIX_EPOCH + Duration::from_secs(issued_at),
+            expires_at: UNIX_EPOCH + Duration::from_secs(expires_at),
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn read_u64(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(buf);
+    u64::from_be_bytes(bytes)
+}
+
+impl<P> WebTransportServer<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Gets the key used to sign and verify handover tickets.
+    #[must_use]
+    pub fn handover_key(&self) -> Option<&HandoverKey> {
+        self.handover.key.as_ref()
+    }
+
+    /// Sets the key used to sign and verify handover tickets.
+    ///
+    /// Handovers are disabled if this is [`None`], which is the default.
+    pub fn set_handover_key(&mut self, key: Option<HandoverKey>) {
+        self.handover.key = key;
+    }
+
+    /// Issues a ticket allowing a connected client to move to another server
+    /// which uses the same [`HandoverKey`], without authenticating again.
+    ///
+    /// This returns the encoded ticket, which the app must send to the
+    /// client, and raises a [`ServerEvent::HandoverIssued`]. The client is not
+    /// disconnected from this server, so that it can keep playing until it
+    /// has connected to the new one.
+    ///
+    /// See [`HandoverTicket`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if no handover key is set, or if the client is not connected.
+    pub fn issue_handover(
+        &mut self,
+        client: ClientKey,
+        payload: impl Into<Vec<u8>>,
+        ttl: Duration,
+    ) -> Result<Vec<u8>, HandoverError> {
+        if self.connection_info(client).is_none() {
+            return Err(HandoverError::NoClient(client));
+        }
+
+        let (ticket, bytes) = self.handover.issue(payload.into(), ttl)?;
+        self.event_buf.push(ServerEvent::HandoverIssued {
+            client,
+            resume_key: ticket.resume_key,
+        });
+        Ok(bytes)
+    }
+
+    /// Checks a ticket presented by a client which is being handed over from
+    /// another server.
+    ///
+    /// This raises a [`ServerEvent::HandoverAccepted`] if the ticket is valid,
+    /// or a [`ServerEvent::HandoverRejected`] if not. The ticket can be
+    /// checked at any point after the client is [`ServerEvent::Incoming`],
+    /// e.g. when responding to a [`ServerEvent::Requested`] if the client
+    /// passes it in the session request.
+    ///
+    /// # Errors
+    ///
+    /// Errors if no handover key is set, or if the ticket is malformed, not
+    /// signed with the same key, expired, or has already been accepted.
+    pub fn accept_handover(
+        &mut self,
+        client: ClientKey,
+        ticket: &[u8],
+    ) -> Result<HandoverTicket, HandoverError> {
+        match self.handover.accept(ticket) {
+            Ok(ticket) => {
+                self.event_buf.push(ServerEvent::HandoverAccepted {
+                    client,
+                    ticket: ticket.clone(),
+                });
+                Ok(ticket)
+            }
+            Err(error) => {
+                self.event_buf.push(ServerEvent::HandoverRejected {
+                    client,
+                    error: error.clone(),
+                });
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Handover state of a [`WebTransportServer`].
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+pub(super) struct Handover {
+    pub key: Option<HandoverKey>,
+    /// Resume keys of accepted tickets, mapped to when they expire.
+    #[derivative(Debug = "ignore")]
+    redeemed: HashMap<[u8; RESUME_KEY_LEN], SystemTime>,
+    #[derivative(Debug = "ignore", Default(value = "SystemRandom::new()"))]
+    rng: SystemRandom,
+}
+
+impl Handover {
+    pub fn issue(
+        &self,
+        payload: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<(HandoverTicket, Vec<u8>), HandoverError> {
+        let key = self.key.as_ref().ok_or(HandoverError::NoKey)?;
+        let mut resume_key = [0; RESUME_KEY_LEN];
+        self.rng
+            .fill(&mut resume_key)
+            .map_err(|_| HandoverError::Random)?;
+        let issued_at = SystemTime::now();
+        let ticket = HandoverTicket {
+            resume_key,
+            issued_at,
+            expires_at: issued_at + ttl,
+            payload,
+        };
+        let bytes = ticket.to_bytes(key);
+        Ok((ticket, bytes))
+    }
+
+    pub fn accept(&mut self, buf: &[u8]) -> Result<HandoverTicket, HandoverError> {
+        let key = self.key.as_ref().ok_or(HandoverError::NoKey)?;
+        let ticket = HandoverTicket::from_bytes(key, buf)?;
+
+        let now = SystemTime::now();
+        self.redeemed.retain(|_, expires_at| *expires_at > now);
+        if ticket.expires_at <= now {
+            return Err(HandoverError::Expired);
+        }
+        if self.redeemed.contains_key(&ticket.resume_key) {
+            return Err(HandoverError::AlreadyRedeemed);
+        }
+        self.redeemed.insert(ticket.resume_key, ticket.expires_at);
+        Ok(ticket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut handover = Handover {
+            key: Some(HandoverKey::new(b"secret")),
+            ..Default::default()
+        };
+        let (ticket, bytes) = handover
+            .issue(b"player".to_vec(), Duration::from_secs(60))
+            .unwrap();
+
+        let accepted = handover.accept(&bytes).unwrap();
+        assert_eq!(ticket.resume_key, accepted.resume_key);
+        assert_eq!(ticket.payload, accepted.payload);
+        assert_eq!(Err(HandoverError::AlreadyRedeemed), handover.accept(&bytes));
+
+        let other = HandoverKey::new(b"other secret");
+        assert_eq!(
+            Err(HandoverError::InvalidSignature),
+            HandoverTicket::from_bytes(&other, &bytes)
+        );
+    }
+}
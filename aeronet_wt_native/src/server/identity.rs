@@ -0,0 +1,168 @@
+use std::io;
+
+use derivative::Derivative;
+use wtransport::tls::Certificate;
+
+/// TLS certificate chain and private key that a [`WebTransportServer`]
+/// presents to its clients.
+///
+/// This can be built from in-memory DER or PEM data, or from [`rustls`] types,
+/// so that the identity does not have to be stored on disk, e.g. when it is
+/// passed to a container as an environment variable or fetched from a secret
+/// store at runtime. Convert it into a [`Certificate`] to use it in a
+/// [`ServerConfig`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`ServerConfig`]: wtransport::ServerConfig
+#[derive(Derivative, Clone, PartialEq, Eq)]
+#[derivative(Debug)]
+pub struct Identity {
+    /// DER-encoded certificates, starting with the end-entity certificate.
+    pub certificate_chain: Vec<Vec<u8>>,
+    /// DER-encoded private key of the end-entity certificate, in PKCS #8,
+    /// PKCS #1 or SEC1 format.
+    #[derivative(Debug = "ignore")]
+    pub private_key: Vec<u8>,
+}
+
+/// Error that occurs when building an [`Identity`].
+#[derive(Debug, thiserror::Error)]
+pub enum IdentityError {
+    /// Failed to read PEM data.
+    #[error("failed to read PEM")]
+    ReadPem(#[source] io::Error),
+    /// The PEM data contained no certificates.
+    #[error("no certificates found")]
+    NoCertificates,
+    /// The PEM data contained no private key.
+    #[error("no private key found")]
+    NoPrivateKey,
+    /// An [`IdentityProvider`] failed to provide an identity.
+    #[error("identity provider failed")]
+    Provider(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Identity {
+    /// Creates an identity from a DER-encoded certificate chain and private
+    /// key.
+    #[must_use]
+    pub fn from_der(certificate_chain: Vec<Vec<u8>>, private_key: Vec<u8>) -> Self {
+        Self {
+            certificate_chain,
+            private_key,
+        }
+    }
+
+    /// Creates an identity from PEM-encoded certificates and a PEM-encoded
+    /// private key.
+    ///
+    /// `certificates` may contain multiple certificates, which are used as the
+    /// chain in the order that they appear. If `private_key` contains multiple
+    /// keys, the first one is used.
+    ///
+    /// # Errors
+    ///
+    /// Errors if either input is not valid PEM, or if no certificate or no
+    /// private key is found.
+    pub fn from_pem(certificates: &[u8], private_key: &[u8]) -> Result<Self, IdentityError> {
+        let certificate_chain =
+            rustls_pemfile::certs(&mut &*certificates).map_err(IdentityError::ReadPem)?;
+        if certificate_chain.is_empty() {
+            return Err(IdentityError::NoCertificates);
+        }
+
+        let private_key = rustls_pemfile::read_all(&mut &*private_key)
+            .map_err(IdentityError::ReadPem)?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key) => Some(key),
+                _ => None,
+            })
+            .ok_or(IdentityError::NoPrivateKey)?;
+
+        Ok(Self {
+            certificate_chain,
+            private_key,
+        })
+    }
+
+    /// Creates an identity from [`rustls`] certificates and private key.
+    #[must_use]
+    pub fn from_rustls(
+        certificate_chain: Vec<rustls::Certificate>,
+        private_key: rustls::PrivateKey,
+    ) -> Self {
+        Self {
+            certificate_chain: certificate_chain.into_iter().map(|cert| cert.0).collect(),
+            private_key: private_key.0,
+        }
+    }
+}
+
+impl From<Identity> for Certificate {
+    fn from(value: Identity) -> Self {
+        Certificate::new(value.certificate_chain, value.private_key)
+    }
+}
+
+/// Source of the [`Identity`] used by a server, such as an ACME client which
+/// renews the certificate before it expires.
+///
+/// The identity is read when building a [`ServerConfig`], so a server must be
+/// reopened with a new config to pick up a rotated identity. Use
+/// [`IdentityProvider::needs_rotation`] to find out when to do this.
+///
+/// [`ServerConfig`]: wtransport::ServerConfig
+pub trait IdentityProvider: Send + Sync {
+    /// Gets the current identity.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the identity is not available, e.g. if it has not been issued
+    /// yet.
+    fn identity(&self) -> Result<Identity, IdentityError>;
+
+    /// Gets if the identity has changed since `current` was provided, and the
+    /// server should be reopened with the new identity.
+    ///
+    /// By default, this compares `current` with [`IdentityProvider::identity`].
+    fn needs_rotation(&self, current: &Identity) -> bool {
+        self.identity().is_ok_and(|identity| identity != *current)
+    }
+}
+
+impl IdentityProvider for Identity {
+    fn identity(&self) -> Result<Identity, IdentityError> {
+        Ok(self.clone())
+    }
+
+    fn needs_rotation(&self, _: &Identity) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_pem() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let cert_pem = cert.serialize_pem().unwrap();
+        let key_pem = cert.serialize_private_key_pem();
+
+        let identity = Identity::from_pem(cert_pem.as_bytes(), key_pem.as_bytes()).unwrap();
+        assert_eq!(
+            vec![cert.serialize_der().unwrap()],
+            identity.certificate_chain
+        );
+        assert_eq!(cert.serialize_private_key_der(), identity.private_key);
+
+        assert!(matches!(
+            Identity::from_pem(key_pem.as_bytes(), key_pem.as_bytes()),
+            Err(IdentityError::NoCertificates)
+        ));
+    }
+}
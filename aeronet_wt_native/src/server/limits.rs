@@ -0,0 +1,323 @@
+use std::time::{Duration, Instant};
+
+use crate::{ConnectionLimits, Limit, LimitKind, LimitUsage};
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Tracks the usage of a single client against the server's
+/// [`ConnectionLimits`].
+#[derive(Debug)]
+pub(super) struct LimitsState {
+    rate_window_start: Option<Instant>,
+    recv_count: usize,
+    /// Whether a warning has been raised for the recv size, recv rate and
+    /// send queue limits, since the usage was last below the soft limit.
+    warned: [bool; 3],
+    early_msgs: usize,
+    early_bytes: usize,
+    early_warned: [bool; 2],
+}
+
+/// Result of [`LimitsState::check_early`].
+#[derive(Debug)]
+pub(super) struct EarlyCheck {
+    /// Whether the message should be held until the client is admitted,
+    /// rather than dropped.
+    pub hold: bool,
+    /// Soft limits which have just been reached.
+    pub warnings: Vec<LimitUsage>,
+}
+
+impl LimitsState {
+    pub fn new() -> Self {
+        Self {
+            rate_window_start: None,
+            recv_count: 0,
+            warned: [false; 3],
+            early_msgs: 0,
+            early_bytes: 0,
+            early_warned: [false; 2],
+        }
+    }
+
+    /// Checks a message of `size` bytes received from this client while it is
+    /// pending admission against the early data limits in `limits`.
+    ///
+    /// Every received message counts towards the usage, even if it ends up
+    /// being dropped. Returns if the message should be held, or the first hard
+    /// limit which has been exceeded.
+    pub fn check_early(
+        &mut self,
+        limits: &ConnectionLimits,
+        size: usize,
+    ) -> Result<EarlyCheck, LimitUsage> {
+        self.early_msgs += 1;
+        self.early_bytes += size;
+
+        let usages = [
+            (LimitKind::EarlyMsgs, self.early_msgs, limits.early_msgs),
+            (LimitKind::EarlyBytes, self.early_bytes, limits.early_bytes),
+        ];
+
+        let mut check = EarlyCheck {
+            hold: true,
+            warnings: Vec::new(),
+        };
+        for ((kind, usage, limit), warned) in usages.into_iter().zip(&mut self.early_warned) {
+            let Limit { soft, hard } = limit;
+            if let Some(hard) = hard {
+                if usage > hard {
+                    return Err(LimitUsage {
+                        kind,
+                        usage,
+                        limit: hard,
+                    });
+                }
+            }
+
+            if let Some(soft) = soft {
+                if usage > soft {
+                    check.hold = false;
+                }
+                if usage >= soft && !*warned {
+                    *warned = true;
+                    check.warnings.push(LimitUsage {
+                        kind,
+                        usage,
+                        limit: soft,
+                    });
+                }
+            }
+        }
+        Ok(check)
+    }
+
+    /// Checks a message of `size` bytes received from this client at `now`
+    /// against the receive limits in `limits`.
+    ///
+    /// This must be called before the message is delivered, so that a message
+    /// over a hard limit is never raised as an event. Returns the soft limits
+    /// which have just been reached, or the first hard limit which has been
+    /// exceeded.
+    pub fn check_recv(
+        &mut self,
+        limits: &ConnectionLimits,
+        size: usize,
+        now: Instant,
+    ) -> Result<Vec<LimitUsage>, LimitUsage> {
+        let window_start = self.rate_window_start.get_or_insert(now);
+        if now.duration_since(*window_start) >= RATE_WINDOW {
+            *window_start = now;
+            self.recv_count = 0;
+        }
+        self.recv_count += 1;
+
+        let [size_warned, rate_warned, _] = &mut self.warned;
+        let warnings = [
+            check(
+                LimitKind::RecvMsgSize,
+                size,
+                limits.recv_msg_size,
+                size_warned,
+            )?,
+            check(
+                LimitKind::RecvRate,
+                self.recv_count,
+                limits.recv_rate,
+                rate_warned,
+            )?,
+        ];
+        Ok(warnings.into_iter().flatten().collect())
+    }
+
+    /// Checks the `queued` messages waiting to be sent to this client against
+    /// the send queue limit in `limits`.
+    ///
+    /// Returns the soft limit if it has just been reached, or the hard limit if
+    /// it has been exceeded.
+    pub fn check_send(
+        &mut self,
+        limits: &ConnectionLimits,
+        queued: usize,
+    ) -> Result<Option<LimitUsage>, LimitUsage> {
+        let [_, _, queue_warned] = &mut self.warned;
+        check(
+            LimitKind::SendQueue,
+            queued,
+            limits.send_queue,
+            queue_warned,
+        )
+    }
+}
+
+/// Checks a single `usage` against `limit`.
+///
+/// Returns the soft limit if it has just been reached, in which case `warned`
+/// is set so that it is not returned again until the usage drops back below
+/// it, or the hard limit if it has been exceeded.
+fn check(
+    kind: LimitKind,
+    usage: usize,
+    limit: Limit,
+    warned: &mut bool,
+) -> Result<Option<LimitUsage>, LimitUsage> {
+    let Limit { soft, hard } = limit;
+    if let Some(hard) = hard {
+        if usage > hard {
+            return Err(LimitUsage {
+                kind,
+                usage,
+                limit: hard,
+            });
+        }
+    }
+
+    match soft {
+        Some(soft) if usage >= soft => {
+            if *warned {
+                Ok(None)
+            } else {
+                *warned = true;
+                Ok(Some(LimitUsage {
+                    kind,
+                    usage,
+                    limit: soft,
+                }))
+            }
+        }
+        _ => {
+            *warned = false;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(soft: usize, hard: usize) -> ConnectionLimits {
+        ConnectionLimits {
+            early_msgs: Limit {
+                soft: Some(soft),
+                hard: Some(hard),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn early_msgs_held_up_to_soft_limit() {
+        let limits = limits(2, 4);
+        let mut state = LimitsState::new();
+
+        let first = state.check_early(&limits, 8).unwrap();
+        assert!(first.hold);
+        assert!(first.warnings.is_empty());
+
+        let second = state.check_early(&limits, 8).unwrap();
+        assert!(second.hold);
+        assert_eq!(1, second.warnings.len());
+
+        let third = state.check_early(&limits, 8).unwrap();
+        assert!(!third.hold);
+        assert!(third.warnings.is_empty());
+    }
+
+    #[test]
+    fn early_msgs_over_hard_limit() {
+        let limits = limits(1, 2);
+        let mut state = LimitsState::new();
+
+        state.check_early(&limits, 8).unwrap();
+        state.check_early(&limits, 8).unwrap();
+        let usage = state.check_early(&limits, 8).unwrap_err();
+        assert_eq!(LimitKind::EarlyMsgs, usage.kind);
+        assert_eq!(3, usage.usage);
+        assert_eq!(2, usage.limit);
+    }
+
+    fn limit(soft: usize, hard: usize) -> Limit {
+        Limit {
+            soft: Some(soft),
+            hard: Some(hard),
+        }
+    }
+
+    #[test]
+    fn recv_size_warns_once_until_below_soft_limit() {
+        let limits = ConnectionLimits {
+            recv_msg_size: limit(10, 20),
+            ..Default::default()
+        };
+        let mut state = LimitsState::new();
+        let now = Instant::now();
+
+        let warnings = state.check_recv(&limits, 15, now).unwrap();
+        assert_eq!(1, warnings.len());
+        assert_eq!(LimitKind::RecvMsgSize, warnings[0].kind);
+        assert_eq!(15, warnings[0].usage);
+        assert_eq!(10, warnings[0].limit);
+
+        // still over the soft limit, so no new warning
+        assert!(state.check_recv(&limits, 12, now).unwrap().is_empty());
+        assert!(state.check_recv(&limits, 5, now).unwrap().is_empty());
+        assert_eq!(1, state.check_recv(&limits, 10, now).unwrap().len());
+    }
+
+    #[test]
+    fn recv_size_over_hard_limit() {
+        let limits = ConnectionLimits {
+            recv_msg_size: limit(10, 20),
+            ..Default::default()
+        };
+        let mut state = LimitsState::new();
+
+        let usage = state.check_recv(&limits, 21, Instant::now()).unwrap_err();
+        assert_eq!(LimitKind::RecvMsgSize, usage.kind);
+        assert_eq!(21, usage.usage);
+        assert_eq!(20, usage.limit);
+    }
+
+    #[test]
+    fn recv_rate_resets_each_window() {
+        let limits = ConnectionLimits {
+            recv_rate: limit(2, 3),
+            ..Default::default()
+        };
+        let mut state = LimitsState::new();
+        let now = Instant::now();
+
+        assert!(state.check_recv(&limits, 1, now).unwrap().is_empty());
+        assert_eq!(1, state.check_recv(&limits, 1, now).unwrap().len());
+        assert!(state.check_recv(&limits, 1, now).unwrap().is_empty());
+        let usage = state.check_recv(&limits, 1, now).unwrap_err();
+        assert_eq!(LimitKind::RecvRate, usage.kind);
+        assert_eq!(4, usage.usage);
+
+        let later = now + RATE_WINDOW;
+        assert!(state.check_recv(&limits, 1, later).unwrap().is_empty());
+        assert_eq!(1, state.check_recv(&limits, 1, later).unwrap().len());
+    }
+
+    #[test]
+    fn send_queue_thresholds() {
+        let limits = ConnectionLimits {
+            send_queue: limit(4, 8),
+            ..Default::default()
+        };
+        let mut state = LimitsState::new();
+
+        assert_eq!(None, state.check_send(&limits, 3).unwrap());
+        let usage = state.check_send(&limits, 4).unwrap().unwrap();
+        assert_eq!(LimitKind::SendQueue, usage.kind);
+        assert_eq!(None, state.check_send(&limits, 8).unwrap());
+        assert_eq!(None, state.check_send(&limits, 0).unwrap());
+        assert!(state.check_send(&limits, 5).unwrap().is_some());
+
+        let usage = state.check_send(&limits, 9).unwrap_err();
+        assert_eq!(LimitKind::SendQueue, usage.kind);
+        assert_eq!(9, usage.usage);
+        assert_eq!(8, usage.limit);
+    }
+}
@@ -1,24 +1,74 @@
+mod analytics;
+#[cfg(feature = "audit")]
+mod audit;
 mod backend;
+mod ban;
+mod config;
+mod disconnect_log;
+mod eviction;
+mod filter;
 mod frontend;
+#[cfg(feature = "async")]
+mod handle;
+mod handover;
+mod identity;
+mod limits;
+mod router;
+#[cfg(feature = "async")]
+mod stream;
 
-use aeronet::{OnChannel, TransportProtocol, TransportServer, TryFromBytes, TryIntoBytes};
+pub use {
+    analytics::*, ban::*, config::*, disconnect_log::*, eviction::*, filter::*, handover::*,
+    identity::*, router::*,
+};
 
-use std::{fmt::Debug, io, net::SocketAddr};
+#[cfg(feature = "async")]
+pub use {handle::*, stream::*};
+
+#[cfg(feature = "audit")]
+pub use audit::*;
+
+use aeronet::{
+    Clock, CustomEvent, OnChannel, SystemClock, TransportProtocol, TransportServer, TryFromBytes,
+    TryIntoBytes,
+};
+
+use std::{
+    fmt::Debug,
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use derivative::Derivative;
 use slotmap::SlotMap;
 use tokio::sync::{mpsc, oneshot};
 
-use crate::{ClientKey, EndpointInfo, WebTransportProtocol};
+use crate::{
+    encode::SharedEncodeOrder,
+    shared::{
+        CodecHook, Incoming, LaneEvent, MigrateLane, Outgoing, QualitySender, SharedCounters,
+        SharedReplaceQueue,
+    },
+    wire::QualitySample,
+    ChecksumMismatch, ClientKey, EncodePool, EndpointInfo, LaneStats, LimitUsage, SessionResponse,
+    WebTransportProtocol,
+};
+
+use self::limits::LimitsState;
 
 type WebTransportError<P> =
     crate::WebTransportError<P, <P as TransportProtocol>::S2C, <P as TransportProtocol>::C2S>;
 
+/// Default value of [`WebTransportServer::handshake_timeout`].
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Implementation of [`TransportServer`] using the WebTransport protocol.
 ///
 /// See the [crate-level docs](crate).
-#[derive(Debug, Derivative)]
-#[derivative(Default)]
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
 #[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
 pub struct WebTransportServer<P>
 where
@@ -27,6 +77,55 @@ where
     P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
 {
     state: State<P>,
+    #[derivative(Default(value = "Arc::new(SystemClock)"))]
+    clock: Arc<dyn Clock>,
+    settings: ServerSettings<P::Channel>,
+    #[derivative(Debug = "ignore")]
+    event_buf: Vec<ServerEvent<P>>,
+    disconnect_log: Option<(DisconnectLog, ErrorChainFn<P>)>,
+    #[derivative(Debug = "ignore")]
+    analytics: Option<(analytics::Funnel, ErrorChainFn<P>)>,
+    #[cfg(feature = "audit")]
+    audit: Option<(AuditLog, ErrorChainFn<P>)>,
+    #[derivative(Debug = "ignore")]
+    codec: CodecHook<P::S2C, P::C2S>,
+    #[derivative(Debug = "ignore")]
+    eviction: eviction::Eviction,
+    handover: handover::Handover,
+    bans: BanList,
+    #[derivative(Debug = "ignore")]
+    recv_filters: Vec<RecvFilter<P>>,
+    #[derivative(Debug = "ignore")]
+    send_filters: Vec<SendFilter<P>>,
+    encode_pool: Option<EncodePool>,
+    #[derivative(Debug = "ignore")]
+    encode_errors: EncodeErrors<P>,
+}
+
+type ErrorChainFn<P> = fn(&WebTransportError<P>) -> Vec<String>;
+
+/// Errors from serializing messages on an [`EncodePool`], which are passed
+/// back to the frontend to apply the serialize error policy.
+struct EncodeErrors<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    send: mpsc::UnboundedSender<(ClientKey, WebTransportError<P>)>,
+    recv: mpsc::UnboundedReceiver<(ClientKey, WebTransportError<P>)>,
+}
+
+impl<P> Default for EncodeErrors<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    fn default() -> Self {
+        let (send, recv) = mpsc::unbounded_channel();
+        Self { send, recv }
+    }
 }
 
 /// Event raised by a [`WebTransportServer`].
@@ -47,7 +146,29 @@ where
         /// The key of the client.
         client: ClientKey,
     },
+    /// A client has requested to open a session, and is waiting for the
+    /// server to accept or reject it.
+    ///
+    /// This is only raised if manual session acceptance is enabled using
+    /// [`WebTransportServer::set_manual_accept`]. Use
+    /// [`WebTransportServer::respond`] to respond to the request.
+    Requested {
+        /// The key of the client.
+        client: ClientKey,
+        /// See [`wtransport::endpoint::SessionRequest::authority`].
+        authority: String,
+        /// See [`wtransport::endpoint::SessionRequest::path`].
+        path: String,
+        /// See [`wtransport::endpoint::SessionRequest::origin`].
+        origin: Option<String>,
+        /// See [`wtransport::endpoint::SessionRequest::user_agent`].
+        user_agent: Option<String>,
+    },
     /// The server has accepted a client's request to connect.
+    ///
+    /// From this point on, messages can be sent and received on the
+    /// fast-start lane, if one is set using
+    /// [`WebTransportServer::set_fast_start_lane`].
     Accepted {
         /// The key of the client.
         client: ClientKey,
@@ -60,6 +181,17 @@ where
         /// See [`wtransport::endpoint::SessionRequest::user_agent`].
         user_agent: Option<String>,
     },
+    /// A client has fully established a connection to the server, and is
+    /// waiting to be admitted before it is considered connected.
+    ///
+    /// This is only raised if manual admission is enabled using
+    /// [`WebTransportServer::set_manual_admit`]. Use
+    /// [`WebTransportServer::admit`] or [`WebTransportServer::refuse`] to
+    /// decide if the client should be connected.
+    PendingConnected {
+        /// The key of the client.
+        client: ClientKey,
+    },
     /// A client has fully established a connection to the server (including
     /// opening streams) and the connection is ready for messages.
     ///
@@ -77,6 +209,181 @@ where
         /// The message.
         msg: P::C2S,
     },
+    /// Part of a large message from a connected client has been received on
+    /// a stream lane, but the message is not complete yet.
+    ///
+    /// This can be used to show progress for large payloads, such as an
+    /// upload of a level. Once the message is complete, it is raised as a
+    /// [`ServerEvent::Recv`] as usual. Progress is raised at most once per
+    /// lane every time the server is polled, and only for messages which take
+    /// more than a single read to receive, so small messages never raise it.
+    ///
+    /// This is only raised if a minimum length has been set using
+    /// [`WebTransportServer::set_recv_progress_min_len`], and never for
+    /// unreliable lanes, since datagrams are received whole.
+    RecvProgress {
+        /// The key of the client.
+        client: ClientKey,
+        /// The lane which the message is arriving on.
+        from: P::Channel,
+        /// Number of bytes of the message received so far.
+        received: usize,
+        /// Length of the full message in bytes.
+        total: usize,
+    },
+    /// Periodic statistics on a lane of a connected client.
+    ///
+    /// This is only raised if a lane stats interval has been set using
+    /// [`WebTransportServer::set_lane_stats_interval`].
+    LaneStats {
+        /// The key of the client.
+        client: ClientKey,
+        /// The stats of the lane.
+        stats: LaneStats<P::Channel>,
+    },
+    /// A connected client finished sending on one of its stream lanes.
+    ///
+    /// Some protocols close lanes which they no longer need, e.g. a lane for
+    /// downloading assets once loading has finished. The rest of the
+    /// connection is unaffected, and the server can still send to the client
+    /// on this lane, but will not receive any more messages on it.
+    ///
+    /// This is only raised for reliable lanes, since unreliable lanes are not
+    /// backed by a stream.
+    StreamClosed {
+        /// The key of the client.
+        client: ClientKey,
+        /// The lane which was closed.
+        channel: P::Channel,
+    },
+    /// The server and a connected client agreed to send the messages of a
+    /// channel on another lane.
+    ///
+    /// This is raised both when the server requested the migration using
+    /// [`WebTransportServer::migrate_lane`], and when the client did.
+    LaneMigrated {
+        /// The key of the client.
+        client: ClientKey,
+        /// The channel whose messages were moved.
+        channel: P::Channel,
+        /// The lane which the messages are now sent on.
+        lane: P::Channel,
+    },
+    /// A lane migration requested using [`WebTransportServer::migrate_lane`]
+    /// was not agreed to, and the messages of the channel stay on their
+    /// current lane.
+    ///
+    /// This happens if the client rejected the migration, if the lanes have
+    /// different lane security policies, or if the client requested a
+    /// migration of the same channel at the same time.
+    LaneMigrationRejected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The channel which was not migrated.
+        channel: P::Channel,
+        /// The lane which the migration requested.
+        lane: P::Channel,
+    },
+    /// A message received from a connected client was dropped because its
+    /// checksum did not match its contents.
+    ///
+    /// This is only raised with the `checksum` feature enabled.
+    ChecksumMismatch {
+        /// The key of the client.
+        client: ClientKey,
+        /// Details of the mismatched message.
+        mismatch: ChecksumMismatch<P::Channel>,
+    },
+    /// A message received from a connected client was dropped because it
+    /// failed lane security verification.
+    ///
+    /// This is only raised if lane security is configured using
+    /// [`WebTransportServer::set_lane_security`].
+    MessageRejected {
+        /// The key of the client.
+        client: ClientKey,
+        /// The lane that the message was received on, or [`None`] if it was
+        /// received as a datagram.
+        channel: Option<P::Channel>,
+    },
+    /// A message sent to a client using an [`EncodePool`] failed to serialize,
+    /// and was dropped.
+    ///
+    /// This is raised instead of the error being returned from the send, since
+    /// the message is serialized after the send returns. It is not raised if
+    /// the serialize error policy is [`OnMessageError::DropMessage`], and the
+    /// client is also disconnected if it is
+    /// [`OnMessageError::DisconnectClient`].
+    ///
+    /// This is also raised, with [`WebTransportError::NotConnected`] and
+    /// regardless of the policy, if the backend of the client closed before
+    /// the message could be passed to it. This includes messages which were
+    /// already serialized, but were held back behind a message which was
+    /// still being serialized on the pool.
+    ///
+    /// See [`WebTransportServer::set_encode_pool`].
+    ///
+    /// [`OnMessageError::DropMessage`]: aeronet::OnMessageError::DropMessage
+    /// [`OnMessageError::DisconnectClient`]: aeronet::OnMessageError::DisconnectClient
+    EncodeError {
+        /// The key of the client.
+        client: ClientKey,
+        /// The error which occurred.
+        cause: WebTransportError<P>,
+    },
+    /// A message received from a connected client failed to deserialize, and
+    /// was dropped.
+    ///
+    /// This is only raised if the deserialize error policy is
+    /// [`OnMessageError::EmitEventOnly`].
+    ///
+    /// See [`WebTransportServer::set_on_deserialize_error`].
+    ///
+    /// [`OnMessageError::EmitEventOnly`]: aeronet::OnMessageError::EmitEventOnly
+    MessageError {
+        /// The key of the client.
+        client: ClientKey,
+        /// The error which occurred.
+        cause: WebTransportError<P>,
+    },
+    /// A connected client has reached a soft limit set on the server.
+    ///
+    /// If the client exceeds the hard limit, it will be disconnected.
+    ///
+    /// See [`WebTransportServer::set_limits`].
+    LimitWarning {
+        /// The key of the client.
+        client: ClientKey,
+        /// The usage of the resource, and the soft limit which was reached.
+        usage: LimitUsage,
+    },
+    /// A handover ticket was issued for a connected client.
+    ///
+    /// See [`WebTransportServer::issue_handover`].
+    HandoverIssued {
+        /// The key of the client.
+        client: ClientKey,
+        /// [`HandoverTicket::resume_key`] of the issued ticket.
+        resume_key: [u8; RESUME_KEY_LEN],
+    },
+    /// A client presented a valid handover ticket from another server.
+    ///
+    /// See [`WebTransportServer::accept_handover`].
+    HandoverAccepted {
+        /// The key of the client.
+        client: ClientKey,
+        /// The ticket presented by the client.
+        ticket: HandoverTicket,
+    },
+    /// A client presented a handover ticket which was not accepted.
+    ///
+    /// See [`WebTransportServer::accept_handover`].
+    HandoverRejected {
+        /// The key of the client.
+        client: ClientKey,
+        /// Why the ticket was not accepted.
+        error: HandoverError,
+    },
     /// A client has lost connection from this server, which could not be
     /// recovered from.
     ///
@@ -87,12 +394,28 @@ where
         /// The reason why the client lost connection.
         cause: WebTransportError<P>,
     },
+    /// A draining server has no clients left, or its drain deadline has
+    /// passed and all remaining clients have been disconnected.
+    ///
+    /// The server stays open, but keeps rejecting new sessions, so it is now
+    /// safe to close or restart it.
+    ///
+    /// See [`WebTransportServer::drain`].
+    Drained,
     /// The server backend has been shut down, all client connections have been
     /// dropped, and the backend must be re-opened.
     Closed {
         /// The reason why the backend was closed.
         cause: WebTransportError<P>,
     },
+    /// A user-defined event was injected using
+    /// [`TransportServer::push_event`].
+    ///
+    /// This is equivalent to [`aeronet::ServerEvent::Custom`].
+    Custom {
+        /// The event.
+        event: CustomEvent,
+    },
 }
 
 impl<P, T> From<ServerEvent<P>> for Option<aeronet::ServerEvent<P, T>>
@@ -109,14 +432,40 @@ where
             ServerEvent::Disconnected { client, cause } => {
                 Some(aeronet::ServerEvent::Disconnected { client, cause })
             }
+            ServerEvent::Custom { event } => Some(aeronet::ServerEvent::Custom { event }),
             ServerEvent::Opened
             | ServerEvent::Incoming { .. }
+            | ServerEvent::Requested { .. }
             | ServerEvent::Accepted { .. }
+            | ServerEvent::PendingConnected { .. }
+            | ServerEvent::RecvProgress { .. }
+            | ServerEvent::LaneStats { .. }
+            | ServerEvent::StreamClosed { .. }
+            | ServerEvent::LaneMigrated { .. }
+            | ServerEvent::LaneMigrationRejected { .. }
+            | ServerEvent::ChecksumMismatch { .. }
+            | ServerEvent::MessageRejected { .. }
+            | ServerEvent::EncodeError { .. }
+            | ServerEvent::MessageError { .. }
+            | ServerEvent::HandoverIssued { .. }
+            | ServerEvent::HandoverAccepted { .. }
+            | ServerEvent::HandoverRejected { .. }
+            | ServerEvent::LimitWarning { .. }
+            | ServerEvent::Drained
             | ServerEvent::Closed { .. } => None,
         }
     }
 }
 
+/// Result of [`WebTransportServer::broadcast_uncongested`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Broadcast {
+    /// Number of clients which the message was queued for.
+    pub sent: usize,
+    /// Number of clients which were skipped because they were congested.
+    pub skipped: usize,
+}
+
 // server states
 
 #[derive(Debug, Default)]
@@ -154,8 +503,11 @@ where
 {
     local_addr: Result<SocketAddr, io::Error>,
     clients: SlotMap<ClientKey, ClientState<P>>,
+    /// Time since which there have been no clients.
+    idle_since: Option<Instant>,
     #[derivative(Debug = "ignore")]
-    recv_client: mpsc::UnboundedReceiver<IncomingClient<P>>,
+    recv_client: mpsc::Receiver<IncomingClient<P>>,
+    drain: Option<Drain>,
     #[derivative(Debug = "ignore")]
     #[allow(dead_code)]
     send_closed: mpsc::Sender<()>,
@@ -163,6 +515,13 @@ where
 
 type OpenServerResult<P> = Result<OpenServer<P>, WebTransportError<P>>;
 
+#[derive(Debug, Clone, Copy)]
+struct Drain {
+    deadline: Instant,
+    /// Whether [`ServerEvent::Drained`] has been raised yet.
+    drained: bool,
+}
+
 // client states
 
 #[derive(Debug)]
@@ -174,8 +533,18 @@ where
 {
     Incoming(IncomingClient<P>),
     Accepted(AcceptedClient<P>),
+    /// Connection is established, but the client has not been admitted yet.
+    Pending {
+        connected: ConnectedClient<P>,
+        admitted: bool,
+    },
     Connected(ConnectedClient<P>),
     Disconnected,
+    /// Client was kicked by the app, or its identity was found to be banned.
+    Kicked {
+        reason: String,
+        banned: bool,
+    },
 }
 
 #[derive(Derivative)]
@@ -202,8 +571,30 @@ where
     path: String,
     origin: Option<String>,
     user_agent: Option<String>,
+    /// Time at which the session was accepted, if it has been yet.
+    handshake_since: Option<Instant>,
+    /// Sender for the response to the session request, if it has not been
+    /// responded to yet.
+    #[derivative(Debug = "ignore")]
+    send_response: Option<oneshot::Sender<SessionResponse>>,
+    /// Response given by the user which no event has been raised for yet.
+    responded: Option<SessionResponse>,
     #[derivative(Debug = "ignore")]
     recv_connected: oneshot::Receiver<ConnectedClientResult<P>>,
+    /// Index of the lane which messages can be sent and received on before the
+    /// client is connected, if fast-start is enabled.
+    fast_start: Option<usize>,
+    /// Queue of the connection, which only takes messages on the fast-start
+    /// lane until the client is connected.
+    #[derivative(Debug = "ignore")]
+    send_s2c: mpsc::UnboundedSender<Outgoing>,
+    /// Messages received on the fast-start lane.
+    #[derivative(Debug = "ignore")]
+    recv_early: mpsc::UnboundedReceiver<Incoming<P::C2S>>,
+    #[derivative(Debug = "ignore")]
+    counters: SharedCounters,
+    #[derivative(Debug = "ignore")]
+    encode_order: SharedEncodeOrder,
 }
 
 type AcceptedClientResult<P> = Result<AcceptedClient<P>, WebTransportError<P>>;
@@ -220,11 +611,37 @@ where
     #[derivative(Debug = "ignore")]
     recv_info: mpsc::UnboundedReceiver<EndpointInfo>,
     #[derivative(Debug = "ignore")]
-    recv_c2s: mpsc::UnboundedReceiver<P::C2S>,
+    recv_c2s: mpsc::UnboundedReceiver<Incoming<P::C2S>>,
+    /// Messages received while the client was pending admission, which have
+    /// not been raised as events yet.
+    #[derivative(Debug = "ignore")]
+    early: Vec<Incoming<P::C2S>>,
+    #[derivative(Debug = "ignore")]
+    recv_lane_events: mpsc::UnboundedReceiver<LaneEvent<P::Channel>>,
+    #[derivative(Debug = "ignore")]
+    send_s2c: mpsc::UnboundedSender<Outgoing>,
     #[derivative(Debug = "ignore")]
-    send_s2c: mpsc::UnboundedSender<P::S2C>,
+    replace_s2c: SharedReplaceQueue,
+    /// Keeps messages in order while some of them are serialized on an
+    /// [`EncodePool`].
+    #[derivative(Debug = "ignore")]
+    encode_order: SharedEncodeOrder,
     #[derivative(Debug = "ignore")]
     recv_err: oneshot::Receiver<WebTransportError<P>>,
+    #[derivative(Debug = "ignore")]
+    counters: SharedCounters,
+    last_lane_stats: Option<Instant>,
+    #[derivative(Debug = "ignore")]
+    send_quality: mpsc::UnboundedSender<QualitySample>,
+    quality: QualitySender,
+    #[derivative(Debug = "ignore")]
+    send_migrate: mpsc::UnboundedSender<MigrateLane>,
+    limits: LimitsState,
+    /// Identity set by the app using [`WebTransportServer::set_identity`].
+    identity: Option<String>,
+    /// Number of broadcasts which skipped this client because it was
+    /// congested.
+    skipped_broadcasts: usize,
 }
 
 type ConnectedClientResult<P> = Result<ConnectedClient<P>, WebTransportError<P>>;
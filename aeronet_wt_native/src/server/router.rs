@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use wtransport::endpoint::SessionRequest;
+
+/// Decides which incoming sessions are handled by a [`WebTransportServer`],
+/// allowing the server's endpoint to be shared with other handlers.
+///
+/// Only sessions with a path claimed by this router will become clients of the
+/// server. All other session requests are forwarded, without being accepted or
+/// rejected, to the receiver returned by [`SessionRouter::new`]. This lets you
+/// serve the game on e.g. `/game`, and handle other WebTransport sessions on
+/// the same port yourself.
+///
+/// HTTP/1.1 and HTTP/2 traffic uses TCP rather than QUIC, so a regular HTTP
+/// server (e.g. `axum` with `hyper`) can listen on the same port number as the
+/// server alongside it, using the same TLS certificate and key as the server.
+///
+/// When a router is used, a client is only reported as
+/// [`ServerEvent::Incoming`] once its session request has been received and
+/// claimed, so [`ServerEvent::Incoming`] is immediately followed by
+/// [`ServerEvent::Accepted`].
+///
+/// Use this with [`WebTransportServer::opening_routed`] or
+/// [`WebTransportServer::open_routed`].
+///
+/// [`WebTransportServer`]: crate::WebTransportServer
+/// [`WebTransportServer::opening_routed`]: crate::WebTransportServer::opening_routed
+/// [`WebTransportServer::open_routed`]: crate::WebTransportServer::open_routed
+/// [`ServerEvent::Incoming`]: crate::ServerEvent::Incoming
+/// [`ServerEvent::Accepted`]: crate::ServerEvent::Accepted
+#[derive(Debug, Clone)]
+pub struct SessionRouter {
+    paths: Arc<[String]>,
+    send_unclaimed: mpsc::UnboundedSender<SessionRequest>,
+}
+
+impl SessionRouter {
+    /// Creates a router which claims sessions on any of the given paths.
+    ///
+    /// A path is claimed if it is equal to one of `paths`, or if it starts with
+    /// one of `paths` followed by `/` or `?`.
+    ///
+    /// This returns the router, and the receiver for all unclaimed session
+    /// requests. If the receiver is dropped, unclaimed session requests are
+    /// dropped as well, which closes them.
+    #[must_use]
+    pub fn new(
+        paths: impl IntoIterator<Item = impl Into<String>>,
+    ) -> (Self, mpsc::UnboundedReceiver<SessionRequest>) {
+        let (send_unclaimed, recv_unclaimed) = mpsc::unbounded_channel();
+        let router = Self {
+            paths: paths.into_iter().map(Into::into).collect(),
+            send_unclaimed,
+        };
+        (router, recv_unclaimed)
+    }
+
+    /// Gets the paths claimed by this router.
+    #[must_use]
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Gets if a session on the given path is handled by the server.
+    #[must_use]
+    pub fn claims(&self, path: &str) -> bool {
+        self.paths.iter().any(|claimed| {
+            path.strip_prefix(claimed.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+        })
+    }
+
+    pub(super) fn forward(&self, request: SessionRequest) {
+        let _ = self.send_unclaimed.send(request);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_paths_and_subpaths() {
+        let (router, _) = SessionRouter::new(["/game"]);
+        assert!(router.claims("/game"));
+        assert!(router.claims("/game/lobby"));
+        assert!(router.claims("/game?region=eu"));
+    }
+
+    #[test]
+    fn does_not_claim_other_paths() {
+        let (router, _) = SessionRouter::new(["/game"]);
+        // a claimed path must end at a path segment boundary
+        assert!(!router.claims("/gameplay"));
+        assert!(!router.claims("/"));
+        assert!(!router.claims(""));
+        assert!(!router.claims("/chat/game"));
+        assert!(!router.claims("/GAME"));
+
+        let (router, _) = SessionRouter::new(Vec::<String>::new());
+        assert!(!router.claims("/game"));
+    }
+
+    #[test]
+    fn any_matching_path_claims() {
+        // overlapping paths do not take precedence over each other, so the
+        // order they are given in does not matter
+        for paths in [["/game", "/game/admin"], ["/game/admin", "/game"]] {
+            let (router, _) = SessionRouter::new(paths);
+            assert!(router.claims("/game/admin/kick"));
+            assert!(router.claims("/game/lobby"));
+            assert!(!router.claims("/admin"));
+        }
+
+        let (router, _) = SessionRouter::new(["/game", "/chat"]);
+        assert!(router.claims("/chat/general"));
+        assert!(router.claims("/game"));
+        assert_eq!(["/game", "/chat"], router.paths());
+    }
+}
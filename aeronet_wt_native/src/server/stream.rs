@@ -0,0 +1,147 @@
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use aeronet::{OnChannel, TransportServer, TryFromBytes, TryIntoBytes};
+use derivative::Derivative;
+use futures::Stream;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::{ServerEvent, WebTransportProtocol, WebTransportServer};
+
+use super::State;
+
+/// Adapter which exposes the events of a [`WebTransportServer`] as an async
+/// [`Stream`].
+///
+/// This is useful for tokio-first apps which do not have a main loop to poll
+/// the server in:
+///
+/// ```ignore
+/// use futures::StreamExt;
+///
+/// let mut events = ServerEventStream::new(server, Duration::from_millis(5));
+/// while let Some(event) = events.next().await {
+///     if let ServerEvent::Recv { client, msg } = event {
+///         events.send(client, msg)?;
+///     }
+/// }
+/// ```
+///
+/// The frontend has no way to be woken up by the backend, so while there are
+/// no events, the server is polled again every poll interval. The stream ends
+/// once the server is closed and all of its events have been returned.
+///
+/// This derefs to the wrapped server, so it can still be used to e.g. send
+/// messages between events.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    server: WebTransportServer<P>,
+    #[derivative(Debug = "ignore")]
+    buf: VecDeque<ServerEvent<P>>,
+    #[derivative(Debug = "ignore")]
+    interval: Interval,
+}
+
+impl<P> ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    /// Wraps a server, polling it every `poll_interval` while there are no
+    /// events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll_interval` is zero, or if this is not called from inside
+    /// a tokio runtime with the time driver enabled.
+    #[must_use]
+    pub fn new(server: WebTransportServer<P>, poll_interval: Duration) -> Self {
+        let mut interval = time::interval(poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            server,
+            buf: VecDeque::new(),
+            interval,
+        }
+    }
+
+    /// Unwraps the wrapped server, dropping all events which have been polled
+    /// but not returned from the stream yet.
+    #[must_use]
+    pub fn into_inner(self) -> WebTransportServer<P> {
+        self.server
+    }
+}
+
+impl<P> Deref for ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    type Target = WebTransportServer<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.server
+    }
+}
+
+impl<P> DerefMut for ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.server
+    }
+}
+
+// no fields are structurally pinned
+impl<P> Unpin for ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+}
+
+impl<P> Stream for ServerEventStream<P>
+where
+    P: WebTransportProtocol,
+    P::C2S: TryFromBytes,
+    P::S2C: TryIntoBytes + OnChannel<Channel = P::Channel>,
+{
+    type Item = ServerEvent<P>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.buf.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+
+            this.buf.extend(this.server.recv());
+            if !this.buf.is_empty() {
+                continue;
+            }
+
+            if matches!(this.server.state, State::Closed) {
+                return Poll::Ready(None);
+            }
+            ready!(this.interval.poll_tick(cx));
+        }
+    }
+}
@@ -1,10 +1,1502 @@
-use aeronet::{ChannelKey, ChannelKind, Message, OnChannel, TryFromBytes, TryIntoBytes};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    marker::PhantomData,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use aeronet::{
+    ChannelKey, ChannelKind, Features, Message, OnChannel, QualityReport, TryFromBytes,
+    TryIntoBytes,
+};
+use derivative::Derivative;
 use futures::future::try_join_all;
-use tokio::sync::mpsc;
+use tokio::{
+    sync::{mpsc, Notify},
+    time::{self, MissedTickBehavior},
+};
 use tracing::debug;
 use wtransport::{datagram::Datagram, error::ConnectionError, Connection, RecvStream, SendStream};
 
-use crate::{ChannelError, EndpointInfo, WebTransportError, WebTransportProtocol};
+use crate::{
+    security::LaneCipher,
+    wire::{self, FrameDecoder, LaneMigration, Ping, QualitySample},
+    ChannelError, ChecksumMismatch, EndpointInfo, EpochStats, LaneStats, MemoryUsage,
+    RecvBufferCap, RecvBufferCaps, RecvBufferPolicy, WebTransportError, WebTransportProtocol,
+};
+
+#[cfg(feature = "codec-timing")]
+use crate::CodecTimings;
+
+// lane stats
+
+/// Counters for a single lane, shared between the frontend and backend of a
+/// connection.
+#[derive(Debug, Default)]
+pub(super) struct LaneCounter {
+    queued: AtomicUsize,
+    queued_bytes: AtomicUsize,
+    dropped: AtomicUsize,
+    expired: AtomicUsize,
+    sent: AtomicU64,
+    send_nanos: AtomicU64,
+    delay_nanos: AtomicU64,
+    max_delay_nanos: AtomicU64,
+    /// Incremented every time the send queue of this lane is cleared.
+    ///
+    /// Messages queued under an older generation are discarded by the backend
+    /// instead of being sent.
+    generation: AtomicU64,
+    /// Cap on the partially received message buffered on this lane.
+    recv_cap: Option<RecvBufferCap>,
+    recv_cap_hits: AtomicUsize,
+    /// Progress of the partially received message on this lane, packed as
+    /// `(received << 32) | total`, or zero if there is no new progress.
+    recv_progress: AtomicU64,
+}
+
+/// Counters for a connection, shared between the frontend and backend.
+#[derive(Debug)]
+pub(super) struct Counters {
+    /// Counters for each lane, indexed by [`ChannelKey::index`].
+    pub lanes: Box<[LaneCounter]>,
+    /// Size in bytes of the messages received by the backend which have not
+    /// been taken by the frontend yet.
+    recv_queued_bytes: AtomicUsize,
+    /// Size in bytes of the partial frames buffered by stream decoders.
+    reassembly_bytes: AtomicUsize,
+    /// Total number of datagrams received since the connection was
+    /// established.
+    recv_datagrams: AtomicU64,
+    /// Total number of datagrams sent since the connection was established.
+    sent_datagrams: AtomicU64,
+    /// [`EpochStats::epoch`] of the current statistics epoch.
+    epoch: AtomicU64,
+    epoch_msgs_sent: AtomicU64,
+    epoch_bytes_sent: AtomicU64,
+    epoch_msgs_recv: AtomicU64,
+    epoch_bytes_recv: AtomicU64,
+    /// Whether the backend has stopped reading from the connection.
+    recv_paused: AtomicBool,
+    /// Notified when [`Counters::recv_paused`] is cleared.
+    recv_resumed: Notify,
+    /// Bits of the [`Features`] negotiated with the peer.
+    features: AtomicU32,
+}
+
+pub(super) type SharedCounters = Arc<Counters>;
+
+pub(super) fn counters<C: ChannelKey>(recv_caps: &RecvBufferCaps<C>) -> SharedCounters {
+    Arc::new(Counters {
+        lanes: C::ALL
+            .iter()
+            .map(|lane| LaneCounter {
+                recv_cap: recv_caps.cap(lane),
+                ..LaneCounter::default()
+            })
+            .collect(),
+        recv_queued_bytes: AtomicUsize::new(0),
+        reassembly_bytes: AtomicUsize::new(0),
+        recv_datagrams: AtomicU64::new(0),
+        sent_datagrams: AtomicU64::new(0),
+        epoch: AtomicU64::new(0),
+        epoch_msgs_sent: AtomicU64::new(0),
+        epoch_bytes_sent: AtomicU64::new(0),
+        epoch_msgs_recv: AtomicU64::new(0),
+        epoch_bytes_recv: AtomicU64::new(0),
+        recv_paused: AtomicBool::new(false),
+        recv_resumed: Notify::new(),
+        features: AtomicU32::new(0),
+    })
+}
+
+impl Counters {
+    fn on_recv(&self, size: usize) {
+        self.epoch_msgs_recv.fetch_add(1, Ordering::Relaxed);
+        self.epoch_bytes_recv
+            .fetch_add(as_u64(size), Ordering::Relaxed);
+    }
+
+    fn on_sent(&self, size: usize) {
+        self.epoch_msgs_sent.fetch_add(1, Ordering::Relaxed);
+        self.epoch_bytes_sent
+            .fetch_add(as_u64(size), Ordering::Relaxed);
+    }
+
+    fn on_recv_queued(&self, size: usize) {
+        self.recv_queued_bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Marks a message received by the backend as taken by the frontend.
+    pub fn on_recv_taken(&self, size: usize) {
+        self.recv_queued_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    fn on_reassembly(&self, before: usize, after: usize) {
+        if after > before {
+            self.reassembly_bytes
+                .fetch_add(after - before, Ordering::Relaxed);
+        } else {
+            self.reassembly_bytes
+                .fetch_sub(before - after, Ordering::Relaxed);
+        }
+    }
+
+    /// Gets the total number of datagrams received on this connection.
+    pub fn recv_datagrams(&self) -> u64 {
+        self.recv_datagrams.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of datagrams sent on this connection.
+    pub fn sent_datagrams(&self) -> u64 {
+        self.sent_datagrams.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of messages waiting in the send queue of a single lane.
+    pub fn lane_queued(&self, lane: usize) -> usize {
+        self.lanes[lane].queued.load(Ordering::Relaxed)
+    }
+
+    /// Gets the size in bytes of the messages waiting in the send queue of a
+    /// single lane.
+    pub fn lane_queued_bytes(&self, lane: usize) -> usize {
+        self.lanes[lane].queued_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of messages waiting in the send queue.
+    pub fn queued(&self) -> usize {
+        self.lanes
+            .iter()
+            .map(|lane| lane.queued.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Gets the total size in bytes of the messages waiting in the send queue.
+    pub fn queued_bytes(&self) -> usize {
+        self.lanes
+            .iter()
+            .map(|lane| lane.queued_bytes.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Gets the identifier of the current statistics epoch.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Gets the totals since the start of the current statistics epoch.
+    pub fn epoch_stats(&self) -> EpochStats {
+        EpochStats {
+            epoch: self.epoch(),
+            msgs_sent: self.epoch_msgs_sent.load(Ordering::Relaxed),
+            bytes_sent: self.epoch_bytes_sent.load(Ordering::Relaxed),
+            msgs_recv: self.epoch_msgs_recv.load(Ordering::Relaxed),
+            bytes_recv: self.epoch_bytes_recv.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Starts a new statistics epoch, resetting the epoch totals and the
+    /// values of every lane which are measured over a period.
+    ///
+    /// Messages sent or received while this runs may be counted in either
+    /// epoch. Returns the identifier of the new epoch.
+    pub fn reset_epoch(&self) -> u64 {
+        self.epoch_msgs_sent.store(0, Ordering::Relaxed);
+        self.epoch_bytes_sent.store(0, Ordering::Relaxed);
+        self.epoch_msgs_recv.store(0, Ordering::Relaxed);
+        self.epoch_bytes_recv.store(0, Ordering::Relaxed);
+        for lane in self.lanes.iter() {
+            lane.reset();
+        }
+        self.epoch.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Gets the features negotiated with the peer.
+    pub fn features(&self) -> Features {
+        Features::from_bits(self.features.load(Ordering::Relaxed))
+    }
+
+    fn set_features(&self, features: Features) {
+        self.features.store(features.bits(), Ordering::Relaxed);
+    }
+
+    /// Sets whether the backend reads from the connection.
+    pub fn set_recv_paused(&self, paused: bool) {
+        self.recv_paused.store(paused, Ordering::Release);
+        if !paused {
+            self.recv_resumed.notify_waiters();
+        }
+    }
+
+    /// Gets whether the backend has stopped reading from the connection.
+    pub fn recv_paused(&self) -> bool {
+        self.recv_paused.load(Ordering::Acquire)
+    }
+
+    /// Waits until the backend may read from the connection.
+    async fn recv_unpaused(&self) {
+        loop {
+            // registered before checking the flag, so a resume in between is
+            // not missed
+            let resumed = self.recv_resumed.notified();
+            if !self.recv_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+
+    /// Gets the approximate memory currently held for this connection.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            send_queue: self.queued_bytes(),
+            recv_queue: self.recv_queued_bytes.load(Ordering::Relaxed),
+            reassembly: self.reassembly_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl LaneCounter {
+    fn on_queued(&self, bytes: usize) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.queued_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn on_unqueued(&self, bytes: usize) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.queued_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn on_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_recv_cap_hit(&self) {
+        self.recv_cap_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stores how much of the message at the front of `decoder` has been
+    /// received, not counting the frame header.
+    fn set_recv_progress(&self, decoder: &FrameDecoder) {
+        let progress = decoder.pending_len().map_or(0, |len| {
+            let total = len - wire::FRAME_HEADER_LEN;
+            let received = decoder.buffered().min(len) - wire::FRAME_HEADER_LEN;
+            // frame lengths are encoded as a u32, so both values fit in 32 bits
+            (as_u64(received) << 32) | as_u64(total)
+        });
+        self.recv_progress.store(progress, Ordering::Relaxed);
+    }
+
+    /// Takes the `(received, total)` progress of the partially received
+    /// message on this lane, if it changed since the last call.
+    fn take_recv_progress(&self) -> Option<(usize, usize)> {
+        match self.recv_progress.swap(0, Ordering::Relaxed) {
+            0 => None,
+            packed => {
+                let half = |bits: u64| usize::try_from(bits & 0xFFFF_FFFF).unwrap_or(usize::MAX);
+                Some((half(packed >> 32), half(packed)))
+            }
+        }
+    }
+
+    /// Makes the backend discard all messages currently queued on this lane.
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn on_sent(&self, elapsed: Duration, delay: Duration) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.send_nanos
+            .fetch_add(as_nanos(elapsed), Ordering::Relaxed);
+        let delay = as_nanos(delay);
+        self.delay_nanos.fetch_add(delay, Ordering::Relaxed);
+        self.max_delay_nanos.fetch_max(delay, Ordering::Relaxed);
+    }
+
+    /// Resets the values which are measured over a period.
+    fn reset(&self) {
+        self.sent.store(0, Ordering::Relaxed);
+        self.send_nanos.store(0, Ordering::Relaxed);
+        self.delay_nanos.store(0, Ordering::Relaxed);
+        self.max_delay_nanos.store(0, Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+        self.expired.store(0, Ordering::Relaxed);
+        self.recv_cap_hits.store(0, Ordering::Relaxed);
+    }
+
+    /// Takes a snapshot of these counters, resetting the values which are
+    /// measured over a period.
+    pub fn take_stats<C>(&self, lane: C, epoch: u64) -> LaneStats<C> {
+        let sent = self.sent.swap(0, Ordering::Relaxed);
+        let send_nanos = self.send_nanos.swap(0, Ordering::Relaxed);
+        let delay_nanos = self.delay_nanos.swap(0, Ordering::Relaxed);
+        let max_delay_nanos = self.max_delay_nanos.swap(0, Ordering::Relaxed);
+        let average = |nanos: u64| {
+            nanos
+                .checked_div(sent)
+                .map_or(Duration::ZERO, Duration::from_nanos)
+        };
+        LaneStats {
+            lane,
+            epoch,
+            send_time: average(send_nanos),
+            send_delay: average(delay_nanos),
+            max_send_delay: Duration::from_nanos(max_delay_nanos),
+            dropped: self.dropped.swap(0, Ordering::Relaxed),
+            expired: self.expired.swap(0, Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            queued_bytes: self.queued_bytes.load(Ordering::Relaxed),
+            recv_cap_hits: self.recv_cap_hits.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+fn as_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn as_u64(size: usize) -> u64 {
+    u64::try_from(size).unwrap_or(u64::MAX)
+}
+
+/// Takes a snapshot of all lanes in `counters` if `interval` has elapsed
+/// since `last`, updating `last` if so.
+///
+/// If `last` is [`None`], the interval starts at `now`.
+pub(super) fn take_lane_stats<C: ChannelKey>(
+    counters: &Counters,
+    interval: Option<Duration>,
+    last: &mut Option<Instant>,
+    now: Instant,
+) -> Option<Vec<LaneStats<C>>> {
+    let interval = interval?;
+    let last = last.get_or_insert(now);
+    if now.duration_since(*last) < interval {
+        return None;
+    }
+    *last = now;
+    let epoch = counters.epoch();
+    Some(
+        C::ALL
+            .iter()
+            .zip(counters.lanes.iter())
+            .map(|(lane, counter)| counter.take_stats(lane.clone(), epoch))
+            .collect(),
+    )
+}
+
+/// Takes the progress of the messages partially received on each lane, as
+/// `(lane, received, total)`, skipping messages shorter than `min_len` bytes.
+///
+/// Returns nothing if `min_len` is [`None`].
+pub(super) fn take_recv_progress<C: ChannelKey>(
+    counters: &Counters,
+    min_len: Option<usize>,
+) -> Vec<(C, usize, usize)> {
+    let Some(min_len) = min_len else {
+        return Vec::new();
+    };
+    C::ALL
+        .iter()
+        .zip(counters.lanes.iter())
+        .filter_map(|(lane, counter)| {
+            let (received, total) = counter.take_recv_progress()?;
+            (total >= min_len).then(|| (lane.clone(), received, total))
+        })
+        .collect()
+}
+
+// rtt
+
+/// Minimum time between two samples taken by an [`RttEstimator`].
+const RTT_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of samples over which [`EndpointInfo::rtt_p95`] is computed.
+const RTT_WINDOW: usize = 64;
+
+/// Derives stable round-trip time estimates from samples of the RTT of a
+/// connection, in the backend.
+///
+/// The samples are taken from the instantaneous RTT of the connection, or
+/// from [`Ping`]s if they are negotiated.
+#[derive(Debug, Default)]
+pub(super) struct RttEstimator {
+    last_sample: Option<Instant>,
+    window: VecDeque<Duration>,
+    smoothed: Option<Duration>,
+    var: Duration,
+}
+
+impl RttEstimator {
+    /// Takes a sample of the RTT if enough time has passed since the last one.
+    pub fn on_rtt(&mut self, rtt: Duration, now: Instant) {
+        if self
+            .last_sample
+            .is_some_and(|last| now.duration_since(last) < RTT_SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        self.last_sample = Some(now);
+
+        if self.window.len() >= RTT_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(rtt);
+
+        // same smoothing as the TCP retransmission timer (RFC 6298)
+        match self.smoothed {
+            None => {
+                self.smoothed = Some(rtt);
+                self.var = rtt / 2;
+            }
+            Some(smoothed) => {
+                let diff = if rtt > smoothed {
+                    rtt - smoothed
+                } else {
+                    smoothed - rtt
+                };
+                self.var = self.var * 3 / 4 + diff / 4;
+                self.smoothed = Some(smoothed * 7 / 8 + rtt / 8);
+            }
+        }
+    }
+
+    /// Writes the current estimates into `info`.
+    pub fn fill(&self, info: &mut EndpointInfo) {
+        let Some(smoothed) = self.smoothed else {
+            return;
+        };
+        info.smoothed_rtt = smoothed;
+        info.rtt_var = self.var;
+
+        let mut sorted = self.window.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        // nearest-rank percentile
+        let rank = (sorted.len() * 95).div_ceil(100);
+        info.rtt_p95 = sorted[rank.saturating_sub(1)];
+    }
+}
+
+// pings
+
+/// Time between two [`Ping::Request`]s sent by a [`Pinger`].
+const PING_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum number of requests which a [`Pinger`] waits for replies to. Older
+/// requests are considered lost.
+const PINGS_IN_FLIGHT: usize = 8;
+
+/// Measures the round-trip time of a connection by exchanging [`Ping`]s with
+/// the peer on the features streams.
+#[derive(Default)]
+struct Pinger {
+    /// Our side of the features stream, once pings have been negotiated.
+    stream: Option<SendStream>,
+    next_seq: u32,
+    /// Sequence numbers of the requests which have not been replied to yet,
+    /// and when they were sent, oldest first.
+    in_flight: VecDeque<(u32, Instant)>,
+}
+
+impl Pinger {
+    fn is_active(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Writes a ping on the features stream.
+    ///
+    /// Returns `false` if pings are not active, or the stream failed, in which
+    /// case no more pings are sent.
+    async fn write(&mut self, ping: Ping) -> bool {
+        let Some(stream) = &mut self.stream else {
+            return false;
+        };
+        if let Err(err) = stream.write_all(&ping.to_bytes()).await {
+            debug!("Failed to write ping: {err:#}");
+            self.stream = None;
+            return false;
+        }
+        true
+    }
+
+    async fn send_request(&mut self, now: Instant) {
+        let seq = self.next_seq;
+        self.next_seq = seq.wrapping_add(1);
+        if !self.write(Ping::Request(seq)).await {
+            return;
+        }
+        if self.in_flight.len() >= PINGS_IN_FLIGHT {
+            self.in_flight.pop_front();
+        }
+        self.in_flight.push_back((seq, now));
+    }
+
+    /// Replies to a request, or returns the round-trip time of the request
+    /// which a reply answers.
+    async fn on_ping(&mut self, ping: Ping, now: Instant) -> Option<Duration> {
+        match ping {
+            Ping::Request(seq) => {
+                self.write(Ping::Reply(seq)).await;
+                None
+            }
+            Ping::Reply(seq) => {
+                let index = self.in_flight.iter().position(|(sent, _)| *sent == seq)?;
+                // replies arrive in order unless the network reorders them, so
+                // older requests were most likely lost
+                let (_, sent_at) = self.in_flight.drain(..=index).last()?;
+                Some(now.saturating_duration_since(sent_at))
+            }
+        }
+    }
+}
+
+// quality reports
+
+/// State of the side of a connection which sends [`QualitySample`]s to its
+/// peer.
+#[derive(Debug, Default)]
+pub(super) struct QualitySender {
+    last_sample: Option<Instant>,
+    last_rtt: Option<Duration>,
+    jitter: Duration,
+}
+
+impl QualitySender {
+    /// Updates the jitter estimate with a new round-trip time sample.
+    pub fn on_rtt(&mut self, rtt: Duration) {
+        let Some(last) = self.last_rtt.replace(rtt) else {
+            return;
+        };
+        if rtt == last {
+            // the connection info is polled much more often than the RTT
+            // estimate changes
+            return;
+        }
+        // same smoothing as the RTP interarrival jitter (RFC 3550)
+        let diff = if rtt > last { rtt - last } else { last - rtt };
+        self.jitter = self.jitter + diff / 16 - self.jitter / 16;
+    }
+
+    /// Takes a sample to send to the peer if `interval` has elapsed since the
+    /// last one.
+    pub fn take_sample(
+        &mut self,
+        counters: &Counters,
+        interval: Option<Duration>,
+        now: Instant,
+    ) -> Option<QualitySample> {
+        let interval = interval?;
+        let last = self.last_sample.get_or_insert(now);
+        if now.duration_since(*last) < interval {
+            return None;
+        }
+        *last = now;
+        Some(QualitySample {
+            recv_datagrams: counters.recv_datagrams(),
+            jitter: self.jitter,
+        })
+    }
+}
+
+/// State of the side of a connection which receives [`QualitySample`]s from
+/// its peer.
+#[derive(Debug, Default)]
+pub(super) struct QualityReceiver {
+    /// Datagrams sent by us and received by the peer at the last sample.
+    last_sample: Option<(u64, u64)>,
+    /// The last report built from a sample.
+    pub report: Option<QualityReport>,
+}
+
+impl QualityReceiver {
+    /// Builds a report from a sample sent by the peer.
+    ///
+    /// Loss is estimated by comparing the number of datagrams the peer
+    /// received since the last sample with the number we sent. Datagrams which
+    /// are still in flight when the sample is taken are counted as lost, and
+    /// are then counted as received in the next sample.
+    pub fn on_sample(&mut self, counters: &Counters, sample: QualitySample) {
+        let sent = counters.sent_datagrams();
+        let (last_sent, last_recv) = self
+            .last_sample
+            .replace((sent, sample.recv_datagrams))
+            .unwrap_or_default();
+        let sent = sent.saturating_sub(last_sent);
+        let recv = sample.recv_datagrams.saturating_sub(last_recv);
+        // precision loss is fine for a ratio
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let loss = if sent == 0 {
+            0.0
+        } else {
+            (1.0 - recv as f64 / sent as f64).clamp(0.0, 1.0) as f32
+        };
+        self.report = Some(QualityReport {
+            loss,
+            jitter: sample.jitter,
+        });
+    }
+}
+
+/// How a connection handles quality reports.
+pub(super) enum QualityLink {
+    /// Sends the samples passed by the frontend to the peer on a
+    /// unidirectional stream, opened when the first sample is sent.
+    Send(mpsc::UnboundedReceiver<QualitySample>),
+    /// Accepts the peer's report stream, and passes the samples read from it
+    /// to the frontend.
+    Recv(mpsc::UnboundedSender<QualitySample>),
+}
+
+/// Writes a sample to the report stream, opening it if needed.
+///
+/// Reports are best-effort, so errors are only logged, and stop any more
+/// reports from being sent.
+async fn send_quality_sample(
+    conn: &Connection,
+    stream: &mut Option<SendStream>,
+    closed: &mut bool,
+    sample: QualitySample,
+) {
+    if *closed {
+        return;
+    }
+    if stream.is_none() {
+        let opening = match conn.open_uni().await {
+            Ok(opening) => opening,
+            Err(err) => {
+                debug!("Failed to request quality report stream: {err:#}");
+                *closed = true;
+                return;
+            }
+        };
+        match opening.await {
+            Ok(send) => *stream = Some(send),
+            Err(err) => {
+                debug!("Failed to open quality report stream: {err:#}");
+                *closed = true;
+                return;
+            }
+        }
+    }
+    let Some(send) = stream else {
+        return;
+    };
+    if let Err(err) = send.write_all(&sample.to_bytes()).await {
+        debug!("Failed to write quality report: {err:#}");
+        *closed = true;
+    }
+}
+
+async fn next_sample(
+    recv: &mut Option<mpsc::UnboundedReceiver<QualitySample>>,
+) -> Option<QualitySample> {
+    recv.as_mut()?.recv().await
+}
+
+/// Reads samples from the peer's report stream until it is finished, given
+/// the bytes which have already been read from the stream.
+async fn recv_quality_samples(
+    mut stream: RecvStream,
+    prefix: &[u8],
+    send_sample: mpsc::UnboundedSender<QualitySample>,
+) {
+    let mut buf = [0; wire::QUALITY_SAMPLE_LEN];
+    buf[..prefix.len()].copy_from_slice(prefix);
+    let mut filled = prefix.len();
+    loop {
+        if filled == buf.len() {
+            filled = 0;
+            if send_sample.send(QualitySample::from_bytes(&buf)).is_err() {
+                return;
+            }
+        }
+        match stream.read(&mut buf[filled..]).await {
+            Ok(Some(bytes_read)) => filled += bytes_read,
+            Ok(None) => {
+                debug!("Peer finished quality report stream");
+                return;
+            }
+            Err(err) => {
+                debug!("Failed to read quality report: {err:#}");
+                return;
+            }
+        }
+    }
+}
+
+// feature negotiation
+
+/// How a connection negotiates optional [`Features`] with its peer.
+///
+/// The client offers its features on a unidirectional stream as soon as the
+/// connection is established, and the server answers with its own features on
+/// another unidirectional stream once it has received the offer. Servers
+/// never send features to a client which did not offer any, so clients which
+/// do not know about features never see an unexpected stream.
+///
+/// If [`Features::PING`] is negotiated, each peer keeps writing [`Ping`]s on
+/// its features stream after the features frame.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum FeatureLink {
+    /// Offers the local features, and waits for the peer's answer.
+    Offer(Features),
+    /// Waits for the peer's offer, and answers with the local features.
+    Answer(Features),
+}
+
+/// Frame read from the peer's features stream.
+#[derive(Debug, Clone, Copy)]
+enum FeatureFrame {
+    Features(Features),
+    Ping(Ping),
+}
+
+/// Writes a features frame on a new unidirectional stream, and returns the
+/// stream so that pings can be written on it later.
+///
+/// Features are best-effort, so errors are only logged, and leave the
+/// connection without any negotiated features.
+async fn send_features(conn: &Connection, features: Features) -> Option<SendStream> {
+    let opening = match conn.open_uni().await {
+        Ok(opening) => opening,
+        Err(err) => {
+            debug!("Failed to request features stream: {err:#}");
+            return None;
+        }
+    };
+    let mut send = match opening.await {
+        Ok(send) => send,
+        Err(err) => {
+            debug!("Failed to open features stream: {err:#}");
+            return None;
+        }
+    };
+    if let Err(err) = send.write_all(&features.to_frame()).await {
+        debug!("Failed to write features: {err:#}");
+        return None;
+    }
+    Some(send)
+}
+
+/// Reads from `stream` until `buf` is full.
+///
+/// Returns `false` if the stream finished or failed first.
+async fn read_full(stream: &mut RecvStream, buf: &mut [u8]) -> bool {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]).await {
+            Ok(Some(bytes_read)) => filled += bytes_read,
+            Ok(None) => {
+                debug!("Peer finished unidirectional stream");
+                return false;
+            }
+            Err(err) => {
+                debug!("Failed to read unidirectional stream: {err:#}");
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Handles a unidirectional stream opened by the peer, which is either a
+/// features stream, a lane migration stream or a quality report stream.
+///
+/// These are told apart by the first bytes of the stream: a features frame
+/// starts with [`wire::FEATURES_MAGIC`] and a lane migration frame with
+/// [`wire::LANE_MIGRATION_MAGIC`], while a quality report stream starts with
+/// the upper half of a datagram count, which never reaches these bytes.
+async fn recv_uni(
+    mut stream: RecvStream,
+    send_features: mpsc::UnboundedSender<FeatureFrame>,
+    send_migration: mpsc::UnboundedSender<LaneMigration>,
+    send_sample: Option<mpsc::UnboundedSender<QualitySample>>,
+) {
+    let mut magic = [0; wire::FEATURES_MAGIC.len()];
+    if !read_full(&mut stream, &mut magic).await {
+        return;
+    }
+
+    if magic == wire::FEATURES_MAGIC {
+        let mut frame = [0; wire::FEATURES_FRAME_LEN];
+        frame[..magic.len()].copy_from_slice(&magic);
+        if !read_full(&mut stream, &mut frame[magic.len()..]).await {
+            return;
+        }
+        let Some(features) = Features::from_frame(&frame) else {
+            return;
+        };
+        if send_features
+            .send(FeatureFrame::Features(features))
+            .is_err()
+        {
+            return;
+        }
+        recv_pings(stream, send_features).await;
+        return;
+    }
+
+    if magic == wire::LANE_MIGRATION_MAGIC {
+        recv_lane_migrations(stream, &magic, send_migration).await;
+        return;
+    }
+
+    match send_sample {
+        Some(send_sample) => recv_quality_samples(stream, &magic, send_sample).await,
+        None => debug!("Peer opened an unknown unidirectional stream"),
+    }
+}
+
+/// Reads [`Ping`]s from the peer's features stream, after its features frame.
+async fn recv_pings(mut stream: RecvStream, send_features: mpsc::UnboundedSender<FeatureFrame>) {
+    let mut buf = [0; wire::PING_LEN];
+    loop {
+        if !read_full(&mut stream, &mut buf).await {
+            return;
+        }
+        let Some(ping) = Ping::from_bytes(&buf) else {
+            debug!("Peer sent an invalid ping");
+            return;
+        };
+        if send_features.send(FeatureFrame::Ping(ping)).is_err() {
+            return;
+        }
+    }
+}
+
+// lane migration
+
+/// Request from the frontend to move the messages of a channel onto another
+/// lane, both given as indices into [`ChannelKey::ALL`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct MigrateLane {
+    pub channel: usize,
+    pub lane: usize,
+}
+
+/// Lanes which the messages of each channel are sent on, agreed with the peer
+/// using [`LaneMigration`] frames.
+struct LaneRoutes {
+    /// Index of the lane which each channel is sent on.
+    lanes: Vec<usize>,
+    next_seq: u32,
+    /// Migrations proposed to the peer which it has not answered yet, along
+    /// with their sequence numbers.
+    pending: Vec<(u32, MigrateLane)>,
+    /// Whether a proposal from the peer is rejected if it migrates a channel
+    /// which one of our own proposals also migrates.
+    ///
+    /// This is set on exactly one side of a connection, so that two proposals
+    /// crossing each other can not leave the peers on different lanes.
+    wins_conflicts: bool,
+    stream: Option<SendStream>,
+    closed: bool,
+}
+
+impl LaneRoutes {
+    fn new(lanes: usize, wins_conflicts: bool) -> Self {
+        Self {
+            lanes: (0..lanes).collect(),
+            next_seq: 0,
+            pending: Vec::new(),
+            wins_conflicts,
+            stream: None,
+            closed: false,
+        }
+    }
+
+    /// Gets the index of the lane which the messages of a channel are sent on.
+    fn lane(&self, channel: usize) -> usize {
+        self.lanes[channel]
+    }
+
+    /// Proposes a migration requested by the frontend to the peer.
+    ///
+    /// The messages of the channel stay on their current lane until the peer
+    /// accepts.
+    async fn propose<C: ChannelKey>(
+        &mut self,
+        conn: &Connection,
+        request: MigrateLane,
+        cipher: Option<&LaneCipher>,
+        send_lane_event: &mpsc::UnboundedSender<LaneEvent<C>>,
+    ) {
+        if !Self::allowed(cipher, request.channel, request.lane) {
+            debug!("Rejected lane migration: lanes have different security policies");
+            Self::reject(request, send_lane_event);
+            return;
+        }
+        let (Ok(channel), Ok(lane)) = (u16::try_from(request.channel), u16::try_from(request.lane))
+        else {
+            Self::reject(request, send_lane_event);
+            return;
+        };
+        let seq = self.next_seq;
+        self.next_seq = seq.wrapping_add(1);
+        if self
+            .write(conn, LaneMigration::Propose { seq, channel, lane })
+            .await
+        {
+            self.pending.push((seq, request));
+        } else {
+            Self::reject(request, send_lane_event);
+        }
+    }
+
+    /// Handles a frame sent by the peer.
+    async fn on_frame<C: ChannelKey>(
+        &mut self,
+        conn: &Connection,
+        frame: LaneMigration,
+        cipher: Option<&LaneCipher>,
+        send_lane_event: &mpsc::UnboundedSender<LaneEvent<C>>,
+    ) {
+        match frame {
+            LaneMigration::Propose { seq, channel, lane } => {
+                let request = MigrateLane {
+                    channel: usize::from(channel),
+                    lane: usize::from(lane),
+                };
+                let valid = request.channel < self.lanes.len()
+                    && request.lane < self.lanes.len()
+                    && Self::allowed(cipher, request.channel, request.lane);
+                let conflicts = self
+                    .pending
+                    .iter()
+                    .any(|(_, pending)| pending.channel == request.channel);
+                if !valid || (conflicts && self.wins_conflicts) {
+                    debug!("Rejected lane migration of channel {channel} onto lane {lane}");
+                    self.write(conn, LaneMigration::Reject(seq)).await;
+                    return;
+                }
+                // the peer rejects our own proposals for this channel, since
+                // it wins conflicts
+                self.pending.retain(|(_, pending)| {
+                    if pending.channel != request.channel {
+                        return true;
+                    }
+                    Self::reject(*pending, send_lane_event);
+                    false
+                });
+                self.lanes[request.channel] = request.lane;
+                self.write(conn, LaneMigration::Accept(seq)).await;
+                debug!("Migrated channel {channel} onto lane {lane}");
+                let _ = send_lane_event.send(LaneEvent::Migrated {
+                    channel: C::ALL[request.channel].clone(),
+                    lane: C::ALL[request.lane].clone(),
+                });
+            }
+            LaneMigration::Accept(seq) => {
+                if let Some(request) = self.take_pending(seq) {
+                    self.lanes[request.channel] = request.lane;
+                    debug!(
+                        "Migrated channel {} onto lane {}",
+                        request.channel, request.lane
+                    );
+                    let _ = send_lane_event.send(LaneEvent::Migrated {
+                        channel: C::ALL[request.channel].clone(),
+                        lane: C::ALL[request.lane].clone(),
+                    });
+                }
+            }
+            LaneMigration::Reject(seq) => {
+                if let Some(request) = self.take_pending(seq) {
+                    debug!(
+                        "Peer rejected lane migration of channel {} onto lane {}",
+                        request.channel, request.lane
+                    );
+                    Self::reject(request, send_lane_event);
+                }
+            }
+        }
+    }
+
+    /// Gets if the messages of a channel may be sent on a lane without
+    /// changing how they are protected.
+    fn allowed(cipher: Option<&LaneCipher>, channel: usize, lane: usize) -> bool {
+        cipher.map_or(true, |cipher| cipher.same_policy(channel, lane))
+    }
+
+    /// Tells the frontend that a migration which it requested was rejected.
+    fn reject<C: ChannelKey>(
+        request: MigrateLane,
+        send_lane_event: &mpsc::UnboundedSender<LaneEvent<C>>,
+    ) {
+        let _ = send_lane_event.send(LaneEvent::MigrationRejected {
+            channel: C::ALL[request.channel].clone(),
+            lane: C::ALL[request.lane].clone(),
+        });
+    }
+
+    fn take_pending(&mut self, seq: u32) -> Option<MigrateLane> {
+        let index = self
+            .pending
+            .iter()
+            .position(|(pending, _)| *pending == seq)?;
+        Some(self.pending.remove(index).1)
+    }
+
+    /// Writes a frame to the lane migration stream, opening it if needed.
+    ///
+    /// Returns `false` if the frame could not be written, after which no more
+    /// frames are sent.
+    async fn write(&mut self, conn: &Connection, frame: LaneMigration) -> bool {
+        if self.closed {
+            return false;
+        }
+        if self.stream.is_none() {
+            let opening = match conn.open_uni().await {
+                Ok(opening) => opening,
+                Err(err) => {
+                    debug!("Failed to request lane migration stream: {err:#}");
+                    self.closed = true;
+                    return false;
+                }
+            };
+            match opening.await {
+                Ok(send) => self.stream = Some(send),
+                Err(err) => {
+                    debug!("Failed to open lane migration stream: {err:#}");
+                    self.closed = true;
+                    return false;
+                }
+            }
+        }
+        let Some(send) = &mut self.stream else {
+            return false;
+        };
+        if let Err(err) = send.write_all(&frame.to_bytes()).await {
+            debug!("Failed to write lane migration: {err:#}");
+            self.closed = true;
+            return false;
+        }
+        true
+    }
+}
+
+/// Reads frames from the peer's lane migration stream until it is finished,
+/// given the bytes which have already been read from the stream.
+async fn recv_lane_migrations(
+    mut stream: RecvStream,
+    prefix: &[u8],
+    send_migration: mpsc::UnboundedSender<LaneMigration>,
+) {
+    let mut buf = [0; wire::LANE_MIGRATION_LEN];
+    buf[..prefix.len()].copy_from_slice(prefix);
+    let mut filled = prefix.len();
+    loop {
+        if !read_full(&mut stream, &mut buf[filled..]).await {
+            return;
+        }
+        filled = 0;
+        let Some(frame) = LaneMigration::from_bytes(&buf) else {
+            debug!("Peer sent an invalid lane migration frame");
+            return;
+        };
+        if send_migration.send(frame).is_err() {
+            return;
+        }
+    }
+}
+
+// sending
+
+/// A message which has been serialized by the frontend, waiting to be sent by
+/// the backend.
+#[derive(Debug, Clone)]
+pub(super) struct Outgoing {
+    /// Index of the lane that this message is sent on.
+    lane: usize,
+    bytes: Vec<u8>,
+    /// When this message was passed to the backend.
+    queued_at: Instant,
+    /// If this message is sent on an unreliable lane, the time after which it
+    /// is dropped instead of being sent.
+    deadline: Option<Instant>,
+    /// Generation of the lane when this message was queued.
+    generation: u64,
+}
+
+impl Outgoing {
+    /// Drops this message if it is on an unreliable lane, and the backend has
+    /// not sent it within `ttl` of it being serialized.
+    pub fn expire_after(&mut self, ttl: Duration) {
+        self.deadline = Some(self.queued_at + ttl);
+    }
+
+    /// Gets the index of the lane that this message is sent on.
+    pub fn lane(&self) -> usize {
+        self.lane
+    }
+}
+
+/// Serializes a message so that it can be passed to the backend.
+pub(super) fn serialize<P, S, R>(msg: &S) -> Result<Outgoing, WebTransportError<P, S, R>>
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes + OnChannel<Channel = P::Channel>,
+    R: Message + TryFromBytes,
+{
+    let channel = msg.channel();
+    let serialized = msg.try_into_bytes().map_err(|err| {
+        WebTransportError::OnChannel(channel.clone(), ChannelError::Serialize(err))
+    })?;
+    #[allow(unused_mut)] // mutated only with the `checksum` feature
+    let mut bytes = serialized.as_ref().to_vec();
+    #[cfg(feature = "checksum")]
+    checksum::append(&mut bytes);
+    Ok(Outgoing {
+        lane: channel.index(),
+        bytes,
+        queued_at: Instant::now(),
+        deadline: None,
+        generation: 0,
+    })
+}
+
+/// Hook for measuring how long messages take to serialize and deserialize.
+///
+/// This does nothing unless the `codec-timing` feature is enabled and
+/// [`CodecHook::timings`] is set.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Default(bound = ""))]
+pub(super) struct CodecHook<S, R> {
+    #[cfg(feature = "codec-timing")]
+    pub timings: Option<Arc<CodecTimings<S, R>>>,
+    _phantom: PhantomData<fn(&S, &R)>,
+}
+
+impl<S, R> CodecHook<S, R>
+where
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    /// Serializes a message using [`serialize`], recording how long it took.
+    pub fn serialize<P>(&self, msg: &S) -> Result<Outgoing, WebTransportError<P, S, R>>
+    where
+        P: WebTransportProtocol,
+        S: OnChannel<Channel = P::Channel>,
+    {
+        #[cfg(feature = "codec-timing")]
+        if let Some(timings) = &self.timings {
+            let start = Instant::now();
+            let serialized = serialize(msg)?;
+            timings.record_serialize(msg, start.elapsed(), serialized.bytes.len());
+            return Ok(serialized);
+        }
+        serialize(msg)
+    }
+
+    /// Records how long an [`Incoming`] message took to deserialize, once the
+    /// frontend has taken it.
+    #[allow(unused_variables, clippy::unused_self)] // used only with the `codec-timing` feature
+    pub fn on_recv(&self, incoming: &Incoming<R>) {
+        #[cfg(feature = "codec-timing")]
+        if let (Some(timings), Ok(msg)) = (&self.timings, &incoming.msg) {
+            timings.record_deserialize(msg, incoming.decode_time, incoming.size);
+        }
+    }
+}
+
+/// Passes a serialized message to the backend, updating the lane counters.
+///
+/// Returns `false` if the backend is closed.
+pub(super) fn queue(
+    send: &mpsc::UnboundedSender<Outgoing>,
+    lanes: &[LaneCounter],
+    mut msg: Outgoing,
+) -> bool {
+    let lane = &lanes[msg.lane];
+    msg.generation = lane.generation();
+    let len = msg.bytes.len();
+    lane.on_queued(len);
+    if send.send(msg).is_ok() {
+        true
+    } else {
+        lane.on_unqueued(len);
+        false
+    }
+}
+
+/// Messages sent using a replacement slot, waiting to be sent by the backend.
+///
+/// A message pushed into a slot which already holds an unsent message replaces
+/// that message, taking its position in the queue.
+///
+/// The backend takes messages from this queue independently of the main send
+/// queue, so the messages in it are not ordered relative to the messages in
+/// the main queue. Messages on ordered lanes must not be pushed into this
+/// queue.
+#[derive(Debug)]
+pub(super) struct ReplaceQueue {
+    msgs: Mutex<VecDeque<(u64, Outgoing)>>,
+    notify: Notify,
+}
+
+pub(super) type SharedReplaceQueue = Arc<ReplaceQueue>;
+
+pub(super) fn replace_queue() -> SharedReplaceQueue {
+    Arc::new(ReplaceQueue {
+        msgs: Mutex::new(VecDeque::new()),
+        notify: Notify::new(),
+    })
+}
+
+impl ReplaceQueue {
+    /// Queues a message in the given slot, updating the lane counters.
+    ///
+    /// If the slot already holds an unsent message, it is replaced and counted
+    /// as dropped.
+    pub fn push(&self, lanes: &[LaneCounter], slot: u64, mut msg: Outgoing) {
+        let lane = &lanes[msg.lane];
+        msg.generation = lane.generation();
+        lane.on_queued(msg.bytes.len());
+        let mut msgs = self.msgs.lock().unwrap_or_else(PoisonError::into_inner);
+        let replaced = match msgs
+            .iter_mut()
+            .find(|(queued_slot, _)| *queued_slot == slot)
+        {
+            Some((_, queued)) => Some(mem::replace(queued, msg)),
+            None => {
+                msgs.push_back((slot, msg));
+                None
+            }
+        };
+        drop(msgs);
+        self.notify.notify_one();
+
+        if let Some(replaced) = replaced {
+            let lane = &lanes[replaced.lane];
+            lane.on_unqueued(replaced.bytes.len());
+            lane.on_dropped();
+        }
+    }
+
+    /// Removes all messages queued on the given lane, counting them as
+    /// dropped.
+    pub fn clear_lane(&self, lanes: &[LaneCounter], index: usize) {
+        let mut msgs = self.msgs.lock().unwrap_or_else(PoisonError::into_inner);
+        let lane = &lanes[index];
+        msgs.retain(|(_, msg)| {
+            if msg.lane != index {
+                return true;
+            }
+            lane.on_unqueued(msg.bytes.len());
+            lane.on_dropped();
+            false
+        });
+    }
+
+    fn pop(&self) -> Option<Outgoing> {
+        self.msgs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front()
+            .map(|(_, msg)| msg)
+    }
+}
+
+// receiving
+
+/// Event on a single lane, passed from the backend to the frontend alongside
+/// the received messages.
+#[derive(Debug)]
+pub(super) enum LaneEvent<C> {
+    /// The peer finished the stream of this lane.
+    Closed(C),
+    /// A message was dropped because its checksum did not match.
+    ChecksumMismatch(ChecksumMismatch<C>),
+    /// A message was dropped because it failed lane security verification.
+    ///
+    /// Contains the lane of the message, or [`None`] if it was a datagram.
+    Rejected(Option<C>),
+    /// Both peers agreed to send the messages of `channel` on `lane`.
+    Migrated { channel: C, lane: C },
+    /// A migration requested by the frontend was not agreed to, and the
+    /// messages of `channel` stay on their current lane.
+    MigrationRejected { channel: C, lane: C },
+}
+
+#[cfg(feature = "checksum")]
+mod checksum {
+    use crate::ChecksumMismatch;
+
+    /// Length of the checksum appended to every message.
+    const LEN: usize = 4;
+
+    /// Maximum number of bytes of a mismatched message included in the
+    /// [`ChecksumMismatch`].
+    const HEAD_LEN: usize = 32;
+
+    /// Appends the checksum of `bytes` to the end of it.
+    pub fn append(bytes: &mut Vec<u8>) {
+        let checksum = crc32fast::hash(bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+    }
+
+    /// Validates and strips the checksum at the end of a received message.
+    pub fn verify<C: Clone>(
+        bytes: &[u8],
+        channel: Option<&C>,
+    ) -> Result<&[u8], ChecksumMismatch<C>> {
+        let (payload, received) = match bytes.len().checked_sub(LEN) {
+            Some(split) => {
+                let (payload, trailer) = bytes.split_at(split);
+                let received = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+                (payload, Some(received))
+            }
+            None => (bytes, None),
+        };
+        let computed = crc32fast::hash(payload);
+        if received == Some(computed) {
+            return Ok(payload);
+        }
+        Err(ChecksumMismatch {
+            channel: channel.cloned(),
+            len: bytes.len(),
+            received,
+            computed,
+            head: bytes[..bytes.len().min(HEAD_LEN)].to_vec(),
+        })
+    }
+}
+
+/// Strips the checksum off of a received message, and passes a mismatch to
+/// `send_event` if it does not match.
+///
+/// Returns [`None`] if the message should be dropped.
+#[cfg(feature = "checksum")]
+fn verify_checksum<'a, C: Clone>(
+    bytes: &'a [u8],
+    channel: Option<&C>,
+    send_event: &mpsc::UnboundedSender<LaneEvent<C>>,
+) -> Option<&'a [u8]> {
+    match checksum::verify(bytes, channel) {
+        Ok(payload) => Some(payload),
+        Err(mismatch) => {
+            debug!(
+                "Dropped message of {} bytes: checksum mismatch",
+                bytes.len()
+            );
+            let _ = send_event.send(LaneEvent::ChecksumMismatch(mismatch));
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "checksum"))]
+#[allow(clippy::unnecessary_wraps)] // only fails with the `checksum` feature
+fn verify_checksum<'a, C>(
+    bytes: &'a [u8],
+    _: Option<&C>,
+    _: &mpsc::UnboundedSender<LaneEvent<C>>,
+) -> Option<&'a [u8]> {
+    Some(bytes)
+}
+
+/// Strips the lane security off of a received message, and passes a rejection
+/// to `send_event` if it fails verification.
+///
+/// Returns [`None`] if the message should be dropped.
+fn open_message<'a, C: ChannelKey>(
+    cipher: Option<&LaneCipher>,
+    bytes: &'a [u8],
+    channel: Option<&C>,
+    send_event: &mpsc::UnboundedSender<LaneEvent<C>>,
+) -> Option<Cow<'a, [u8]>> {
+    let Some(cipher) = cipher else {
+        return Some(Cow::Borrowed(bytes));
+    };
+    let opened = cipher.open(channel.map(ChannelKey::index), bytes);
+    if opened.is_none() {
+        debug!(
+            "Dropped message of {} bytes: failed verification",
+            bytes.len()
+        );
+        let _ = send_event.send(LaneEvent::Rejected(channel.cloned()));
+    }
+    opened
+}
+
+/// A message which has been received and deserialized by the backend.
+///
+/// Deserialization errors are passed to the frontend as well, so that it can
+/// handle them according to its [`OnMessageError`](aeronet::OnMessageError)
+/// policy.
+#[derive(Debug)]
+pub(super) struct Incoming<R: TryFromBytes> {
+    pub msg: Result<R, R::Error>,
+    /// Index of the reliable lane that this message was received on, or
+    /// [`None`] if it was received as a datagram.
+    pub lane: Option<usize>,
+    /// Size in bytes of the serialized message, used for memory accounting.
+    ///
+    /// The frontend must pass this to [`Counters::on_recv_taken`] once it has
+    /// taken the message.
+    pub size: usize,
+    /// Time taken to deserialize the message.
+    #[cfg(feature = "codec-timing")]
+    pub decode_time: Duration,
+}
+
+impl<R: TryFromBytes> Incoming<R> {
+    /// Deserializes a message received by the backend.
+    pub fn deserialize(payload: &[u8], lane: Option<usize>, size: usize) -> Self {
+        #[cfg(feature = "codec-timing")]
+        let start = Instant::now();
+        let msg = R::try_from_bytes(payload);
+        Self {
+            msg,
+            lane,
+            size,
+            #[cfg(feature = "codec-timing")]
+            decode_time: start.elapsed(),
+        }
+    }
+}
+
+/// Wraps an error from deserializing an [`Incoming`] message.
+pub(super) fn deserialize_error<P, S, R>(
+    lane: Option<usize>,
+    err: R::Error,
+) -> WebTransportError<P, S, R>
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let err = ChannelError::Deserialize(err);
+    match lane {
+        Some(index) => WebTransportError::OnChannel(P::Channel::ALL[index].clone(), err),
+        None => WebTransportError::OnDatagram(err),
+    }
+}
+
+/// Gets if `err` was caused by a message failing to serialize.
+pub(super) fn is_serialize_error<P, S, R>(err: &WebTransportError<P, S, R>) -> bool
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    matches!(
+        err,
+        WebTransportError::OnChannel(_, ChannelError::Serialize(_))
+    )
+}
 
 // establishing channels
 
@@ -15,8 +1507,10 @@ where
     R: Message + TryFromBytes,
 {
     channels: Vec<ChannelState<P>>,
-    recv_streams: mpsc::UnboundedReceiver<R>,
+    recv_streams: mpsc::UnboundedReceiver<Incoming<R>>,
+    send_lane_event: mpsc::UnboundedSender<LaneEvent<P::Channel>>,
     recv_err: mpsc::UnboundedReceiver<WebTransportError<P, S, R>>,
+    cipher: Option<Arc<LaneCipher>>,
 }
 
 enum ChannelState<P>
@@ -34,6 +1528,9 @@ where
 
 pub(super) async fn establish_channels<P, S, R, const OPENS: bool>(
     conn: &Connection,
+    counters: &SharedCounters,
+    send_lane_event: &mpsc::UnboundedSender<LaneEvent<P::Channel>>,
+    cipher: Option<Arc<LaneCipher>>,
 ) -> Result<ChannelsState<P, S, R>, WebTransportError<P, S, R>>
 where
     P: WebTransportProtocol,
@@ -43,10 +1540,14 @@ where
     let (send_streams, recv_streams) = mpsc::unbounded_channel();
     let (send_err, recv_err) = mpsc::unbounded_channel();
     let channels = P::Channel::ALL.iter().map(|channel| {
-        let send_r = send_streams.clone();
-        let send_err = send_err.clone();
+        let senders = StreamSenders {
+            send_r: send_streams.clone(),
+            send_lane_event: send_lane_event.clone(),
+            send_err: send_err.clone(),
+            cipher: cipher.clone(),
+        };
         async move {
-            establish_channel::<P, S, R, OPENS>(conn, channel.clone(), send_r, send_err)
+            establish_channel::<P, S, R, OPENS>(conn, channel.clone(), counters, senders)
                 .await
                 .map_err(|err| WebTransportError::<P, S, R>::OnChannel(channel.clone(), err))
         }
@@ -55,15 +1556,30 @@ where
     Ok(ChannelsState {
         channels,
         recv_streams,
+        send_lane_event: send_lane_event.clone(),
         recv_err,
+        cipher,
     })
 }
 
+/// Senders used by the task receiving on a stream.
+struct StreamSenders<P, S, R>
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    send_r: mpsc::UnboundedSender<Incoming<R>>,
+    send_lane_event: mpsc::UnboundedSender<LaneEvent<P::Channel>>,
+    send_err: mpsc::UnboundedSender<WebTransportError<P, S, R>>,
+    cipher: Option<Arc<LaneCipher>>,
+}
+
 async fn establish_channel<P, S, R, const OPENS: bool>(
     conn: &Connection,
     channel: P::Channel,
-    send_r: mpsc::UnboundedSender<R>,
-    send_err: mpsc::UnboundedSender<WebTransportError<P, S, R>>,
+    counters: &SharedCounters,
+    senders: StreamSenders<P, S, R>,
 ) -> Result<ChannelState<P>, ChannelError<S, R>>
 where
     P: WebTransportProtocol,
@@ -73,7 +1589,7 @@ where
     match channel.kind() {
         ChannelKind::Unreliable => Ok(ChannelState::Datagram { channel }),
         ChannelKind::ReliableUnordered | ChannelKind::ReliableOrdered => {
-            establish_stream::<P, S, R, OPENS>(conn, channel, send_r, send_err).await
+            establish_stream::<P, S, R, OPENS>(conn, channel, counters, senders).await
         }
     }
 }
@@ -81,8 +1597,8 @@ where
 async fn establish_stream<P, S, R, const OPENS: bool>(
     conn: &Connection,
     channel: P::Channel,
-    send_r: mpsc::UnboundedSender<R>,
-    send_err: mpsc::UnboundedSender<WebTransportError<P, S, R>>,
+    counters: &SharedCounters,
+    senders: StreamSenders<P, S, R>,
 ) -> Result<ChannelState<P>, ChannelError<S, R>>
 where
     P: WebTransportProtocol,
@@ -101,10 +1617,32 @@ where
 
     {
         let channel = channel.clone();
+        let counters = counters.clone();
+        let StreamSenders {
+            send_r,
+            send_lane_event,
+            send_err,
+            cipher,
+        } = senders;
         tokio::spawn(async move {
             #[allow(clippy::large_futures)] // this future is going on the heap anyway
-            if let Err(err) = handle_stream::<S, R>(recv_stream, send_r).await {
-                let _ = send_err.send(WebTransportError::<P, S, R>::OnChannel(channel, err));
+            match handle_stream::<S, R, _>(
+                recv_stream,
+                &channel,
+                &counters,
+                cipher.as_deref(),
+                send_r,
+                &send_lane_event,
+            )
+            .await
+            {
+                Ok(()) => {
+                    debug!("Peer finished stream on {channel:?}");
+                    let _ = send_lane_event.send(LaneEvent::Closed(channel));
+                }
+                Err(err) => {
+                    let _ = send_err.send(WebTransportError::<P, S, R>::OnChannel(channel, err));
+                }
             }
         });
     }
@@ -115,29 +1653,91 @@ where
     })
 }
 
-async fn handle_stream<S, R>(
+/// Receives messages on a stream until the peer finishes it.
+async fn handle_stream<S, R, C>(
     mut recv_stream: RecvStream,
-    send_r: mpsc::UnboundedSender<R>,
+    channel: &C,
+    counters: &Counters,
+    cipher: Option<&LaneCipher>,
+    send_r: mpsc::UnboundedSender<Incoming<R>>,
+    send_lane_event: &mpsc::UnboundedSender<LaneEvent<C>>,
 ) -> Result<(), ChannelError<S, R>>
 where
     S: Message + TryIntoBytes,
     R: Message + TryFromBytes,
+    C: ChannelKey,
 {
     // TOOD: what is a good value for this?
     const RECV_CAP: usize = 0x1000;
 
     let mut buf = [0u8; RECV_CAP];
+    let max_len = counters.lanes[channel.index()]
+        .recv_cap
+        .map_or(wire::DEFAULT_MAX_FRAME_LEN, |cap| {
+            cap.max_bytes.max(wire::DEFAULT_MAX_FRAME_LEN)
+        });
+    let mut decoder = FrameDecoder::with_max_len(max_len);
     loop {
+        // while paused, the stream is not read, so QUIC flow control stops
+        // the peer from sending more
+        counters.recv_unpaused().await;
         tokio::select! {
             result = recv_stream.read(&mut buf) => {
                 let Some(bytes_read) = result.map_err(ChannelError::ReadStream)? else {
-                    // TODO error here?
-                    continue;
+                    // the peer closed this lane, but the rest of the
+                    // connection is still usable; a partial frame left in the
+                    // decoder can never be completed, so it is discarded
+                    counters.on_reassembly(decoder.buffered(), 0);
+                    counters.lanes[channel.index()].recv_progress.store(0, Ordering::Relaxed);
+                    return Ok(());
                 };
 
-                let bytes = &buf[..bytes_read];
-                let msg = R::try_from_bytes(bytes).map_err(ChannelError::Deserialize)?;
-                let _ = send_r.send(msg);
+                let before = decoder.buffered();
+                decoder.push(&buf[..bytes_read]);
+                loop {
+                    let frame = match decoder.next_frame() {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) => break,
+                        Err(err) => {
+                            counters.on_reassembly(before, 0);
+                            return Err(ChannelError::RecvFrameTooLong(err));
+                        }
+                    };
+                    counters.on_recv(frame.len());
+                    let Some(opened) = open_message(cipher, &frame, Some(channel), send_lane_event)
+                    else {
+                        continue;
+                    };
+                    let Some(payload) = verify_checksum(&opened, Some(channel), send_lane_event)
+                    else {
+                        continue;
+                    };
+                    counters.on_recv_queued(frame.len());
+                    let _ = send_r.send(Incoming::deserialize(
+                        payload,
+                        Some(channel.index()),
+                        frame.len(),
+                    ));
+                }
+
+                let lane = &counters.lanes[channel.index()];
+                if let Some(cap) = lane.recv_cap {
+                    if let Some(len) = decoder.pending_len().filter(|len| *len > cap.max_bytes) {
+                        lane.on_recv_cap_hit();
+                        match cap.policy {
+                            RecvBufferPolicy::DropBuffer => {
+                                debug!("Dropped message of {len} bytes on {channel:?}: over cap");
+                                decoder.discard_pending();
+                            }
+                            RecvBufferPolicy::Disconnect => {
+                                counters.on_reassembly(before, 0);
+                                return Err(ChannelError::RecvBufferFull(len, cap.max_bytes));
+                            }
+                        }
+                    }
+                }
+                lane.set_recv_progress(&decoder);
+                counters.on_reassembly(before, decoder.buffered());
             }
         }
     }
@@ -149,8 +1749,13 @@ pub(super) async fn handle_connection<P, S, R>(
     conn: Connection,
     channels: ChannelsState<P, S, R>,
     send_info: mpsc::UnboundedSender<EndpointInfo>,
-    send_r: mpsc::UnboundedSender<R>,
-    mut recv_s: mpsc::UnboundedReceiver<S>,
+    send_r: mpsc::UnboundedSender<Incoming<R>>,
+    mut recv_s: mpsc::UnboundedReceiver<Outgoing>,
+    replace_s: SharedReplaceQueue,
+    counters: SharedCounters,
+    quality: QualityLink,
+    features: FeatureLink,
+    mut recv_migrate: mpsc::UnboundedReceiver<MigrateLane>,
 ) -> Result<(), WebTransportError<P, S, R>>
 where
     P: WebTransportProtocol,
@@ -160,14 +1765,53 @@ where
     let ChannelsState {
         mut channels,
         mut recv_streams,
+        send_lane_event,
         mut recv_err,
+        cipher,
     } = channels;
+    let (mut recv_sample, send_sample) = match quality {
+        QualityLink::Send(recv) => (Some(recv), None),
+        QualityLink::Recv(send) => (None, Some(send)),
+    };
+    let mut report_stream = None;
+    let mut report_closed = false;
+    let mut rtt = RttEstimator::default();
+    let mut pinger = Pinger::default();
+    // once a ping has been answered, RTT samples only come from pings
+    let mut ping_sampled = false;
+    let mut ping_interval = time::interval(PING_INTERVAL);
+    ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let (send_remote_features, mut recv_remote_features) = mpsc::unbounded_channel();
+    let mut negotiated = false;
+    // the side which receives quality reports also accepts the report stream
+    let mut uni_streams = if send_sample.is_some() { 3 } else { 2 };
+    // pings and lane migrations are implemented by the transport itself, so
+    // they are always supported
+    let builtin = Features::PING | Features::LANE_MIGRATION;
+    let features = match features {
+        FeatureLink::Offer(local) => FeatureLink::Offer(local | builtin),
+        FeatureLink::Answer(local) => FeatureLink::Answer(local | builtin),
+    };
+
+    let (send_migration, mut recv_migration) = mpsc::unbounded_channel();
+    let mut migrating = false;
+    let mut routes = LaneRoutes::new(
+        P::Channel::ALL.len(),
+        matches!(features, FeatureLink::Answer(_)),
+    );
+    let mut features_stream = None;
+    if let FeatureLink::Offer(local) = features {
+        features_stream = send_features(&conn, local).await;
+    }
 
     loop {
-        if send_info
-            .send(EndpointInfo::from_connection(&conn))
-            .is_err()
-        {
+        if !ping_sampled {
+            rtt.on_rtt(conn.rtt(), Instant::now());
+        }
+        let mut info = EndpointInfo::from_connection(&conn);
+        rtt.fill(&mut info);
+        if send_info.send(info).is_err() {
             debug!("Frontend closed");
             return Ok(());
         }
@@ -178,15 +1822,86 @@ where
                     debug!("Frontend closed");
                     return Ok(());
                 };
-                send::<P, S, R>(&conn, &mut channels, msg).await?;
+                send::<P, S, R>(&conn, &mut channels, &routes, &counters, cipher.as_deref(), msg)
+                    .await?;
+            }
+            () = replace_s.notify.notified() => {
+                // messages are only taken out of the queue right before they are
+                // sent, so that newer messages can replace them while we are
+                // busy sending
+                while let Some(msg) = replace_s.pop() {
+                    let cipher = cipher.as_deref();
+                    send::<P, S, R>(&conn, &mut channels, &routes, &counters, cipher, msg).await?;
+                }
             }
-            result = conn.receive_datagram() => {
-                recv_datagram(result, &send_r)
+            result = conn.receive_datagram(), if !counters.recv_paused() => {
+                recv_datagram(result, &counters, cipher.as_deref(), &send_r, &send_lane_event)
                     .map_err(|err| WebTransportError::<P, S, R>::OnDatagram(err))?;
             }
+            () = counters.recv_unpaused(), if counters.recv_paused() => {}
             Some(msg) = recv_streams.recv() => {
                 let _ = send_r.send(msg);
             }
+            _ = ping_interval.tick(), if pinger.is_active() => {
+                pinger.send_request(Instant::now()).await;
+            }
+            Some(sample) = next_sample(&mut recv_sample), if recv_sample.is_some() => {
+                send_quality_sample(&conn, &mut report_stream, &mut report_closed, sample).await;
+            }
+            result = conn.accept_uni(), if uni_streams > 0 => {
+                // the peer opens at most a features stream, a lane migration
+                // stream and a report stream
+                uni_streams -= 1;
+                match result {
+                    Ok(stream) => {
+                        tokio::spawn(recv_uni(
+                            stream,
+                            send_remote_features.clone(),
+                            send_migration.clone(),
+                            send_sample.clone(),
+                        ));
+                    }
+                    Err(err) => debug!("Failed to accept unidirectional stream: {err:#}"),
+                }
+            }
+            Some(frame) = recv_remote_features.recv() => match frame {
+                FeatureFrame::Features(remote) if !negotiated => {
+                    negotiated = true;
+                    let local = match features {
+                        FeatureLink::Offer(local) => local,
+                        FeatureLink::Answer(local) => {
+                            features_stream = send_features(&conn, local).await;
+                            local
+                        }
+                    };
+                    let agreed = local.negotiate(remote);
+                    debug!("Negotiated features {agreed:?}");
+                    counters.set_features(agreed);
+                    if agreed.contains(Features::PING) {
+                        pinger.stream = features_stream.take();
+                    }
+                    migrating = agreed.contains(Features::LANE_MIGRATION);
+                }
+                FeatureFrame::Features(_) => {}
+                FeatureFrame::Ping(ping) => {
+                    let now = Instant::now();
+                    if let Some(sample) = pinger.on_ping(ping, now).await {
+                        rtt.on_rtt(sample, now);
+                        ping_sampled = true;
+                    }
+                }
+            },
+            Some(request) = recv_migrate.recv() => {
+                if migrating {
+                    routes.propose(&conn, request, cipher.as_deref(), &send_lane_event).await;
+                } else {
+                    debug!("Rejected lane migration: not negotiated with the peer");
+                    LaneRoutes::reject(request, &send_lane_event);
+                }
+            }
+            Some(frame) = recv_migration.recv() => {
+                routes.on_frame(&conn, frame, cipher.as_deref(), &send_lane_event).await;
+            }
             Some(err) = recv_err.recv() => {
                 return Err(err);
             }
@@ -194,60 +1909,281 @@ where
     }
 }
 
+/// Sends and receives datagrams on the fast-start lane of a connection whose
+/// channels are still being established.
+///
+/// Only messages on the fast-start lane are queued before the connection is
+/// established, so every message in `recv_s` is sent as a datagram. This only
+/// returns if the connection is lost, so it should be raced against
+/// [`establish_channels`].
+pub(super) async fn pump_fast_start<P, S, R>(
+    conn: &Connection,
+    counters: &Counters,
+    cipher: Option<&LaneCipher>,
+    recv_s: &mut mpsc::UnboundedReceiver<Outgoing>,
+    send_r: &mpsc::UnboundedSender<Incoming<R>>,
+    send_lane_event: &mpsc::UnboundedSender<LaneEvent<P::Channel>>,
+) -> WebTransportError<P, S, R>
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let mut sending = true;
+    loop {
+        tokio::select! {
+            result = recv_s.recv(), if sending => {
+                // if the frontend is closed, establishing the channels notices
+                // it as well
+                let Some(msg) = result else {
+                    sending = false;
+                    continue;
+                };
+                if let Err(err) = send_fast_start::<P, S, R>(conn, counters, cipher, msg) {
+                    return err;
+                }
+            }
+            result = conn.receive_datagram(), if !counters.recv_paused() => {
+                if let Err(err) = recv_datagram(result, counters, cipher, send_r, send_lane_event) {
+                    return WebTransportError::OnDatagram(err);
+                }
+            }
+            () = counters.recv_unpaused(), if counters.recv_paused() => {}
+        }
+    }
+}
+
+fn send_fast_start<P, S, R>(
+    conn: &Connection,
+    counters: &Counters,
+    cipher: Option<&LaneCipher>,
+    msg: Outgoing,
+) -> Result<(), WebTransportError<P, S, R>>
+where
+    P: WebTransportProtocol,
+    S: Message + TryIntoBytes,
+    R: Message + TryFromBytes,
+{
+    let Outgoing {
+        lane: index,
+        bytes,
+        queued_at,
+        deadline,
+        ..
+    } = msg;
+    let lane = &counters.lanes[index];
+    lane.on_unqueued(bytes.len());
+
+    let start = Instant::now();
+    if deadline.is_some_and(|deadline| start > deadline) {
+        debug!("Dropped datagram of {} bytes: expired", bytes.len());
+        lane.on_expired();
+        return Ok(());
+    }
+    // lanes cannot be migrated before the connection is established
+    let bytes = match cipher {
+        None => bytes,
+        Some(cipher) => {
+            let len = bytes.len();
+            let Some(sealed) = cipher.seal(index, bytes) else {
+                debug!("Dropped message of {len} bytes: failed to seal");
+                lane.on_dropped();
+                return Ok(());
+            };
+            sealed
+        }
+    };
+
+    let result = send_datagram::<S, R>(conn, counters, &bytes);
+    let now = Instant::now();
+    lane.on_sent(now - start, now - queued_at);
+    result.map_err(|err| WebTransportError::OnChannel(P::Channel::ALL[index].clone(), err))
+}
+
 async fn send<P, S, R>(
     conn: &Connection,
     channels: &mut [ChannelState<P>],
-    msg: S,
+    routes: &LaneRoutes,
+    counters: &Counters,
+    cipher: Option<&LaneCipher>,
+    msg: Outgoing,
 ) -> Result<(), WebTransportError<P, S, R>>
 where
     P: WebTransportProtocol,
-    S: Message + TryIntoBytes + OnChannel<Channel = P::Channel>,
+    S: Message + TryIntoBytes,
     R: Message + TryFromBytes,
 {
-    let (channel, result) = match &mut channels[msg.channel().index()] {
-        ChannelState::Datagram { channel } => (channel.clone(), send_datagram::<S, R>(conn, &msg)),
+    let Outgoing {
+        lane: index,
+        bytes,
+        queued_at,
+        deadline,
+        generation,
+    } = msg;
+    let lane = &counters.lanes[index];
+    lane.on_unqueued(bytes.len());
+    if generation < lane.generation() {
+        debug!("Dropped message of {} bytes: lane cleared", bytes.len());
+        lane.on_dropped();
+        return Ok(());
+    }
+
+    // counters stay with the channel, but the message is sent on the lane
+    // which the channel is currently migrated onto
+    let route = routes.lane(index);
+    let bytes = match cipher {
+        None => bytes,
+        Some(cipher) => {
+            let len = bytes.len();
+            let Some(sealed) = cipher.seal(route, bytes) else {
+                debug!("Dropped message of {len} bytes: failed to seal");
+                lane.on_dropped();
+                return Ok(());
+            };
+            sealed
+        }
+    };
+
+    let start = Instant::now();
+    let (channel, result) = match &mut channels[route] {
+        ChannelState::Datagram { .. } if deadline.is_some_and(|deadline| start > deadline) => {
+            debug!("Dropped datagram of {} bytes: expired", bytes.len());
+            lane.on_expired();
+            return Ok(());
+        }
+        ChannelState::Datagram { channel } => (
+            channel.clone(),
+            send_datagram::<S, R>(conn, counters, &bytes),
+        ),
         ChannelState::Stream {
             channel,
             send_stream: send,
-        } => (channel.clone(), send_stream::<S, R>(send, msg).await),
+        } => {
+            let result = send_stream::<S, R>(send, &bytes).await;
+            if result.is_ok() {
+                counters.on_sent(bytes.len());
+            }
+            (channel.clone(), result)
+        }
     };
+    let now = Instant::now();
+    lane.on_sent(now - start, now - queued_at);
 
     result.map_err(|err| WebTransportError::<P, S, R>::OnChannel(channel, err))
 }
 
-fn send_datagram<S, R>(conn: &Connection, msg: &S) -> Result<(), ChannelError<S, R>>
+fn send_datagram<S, R>(
+    conn: &Connection,
+    counters: &Counters,
+    bytes: &[u8],
+) -> Result<(), ChannelError<S, R>>
 where
     S: Message + TryIntoBytes,
     R: Message + TryFromBytes,
 {
-    let serialized = msg.try_into_bytes().map_err(ChannelError::Serialize)?;
-    let bytes = serialized.as_ref();
     conn.send_datagram(bytes)
-        .map_err(ChannelError::SendDatagram)
+        .map_err(ChannelError::SendDatagram)?;
+    counters.sent_datagrams.fetch_add(1, Ordering::Relaxed);
+    counters.on_sent(bytes.len());
+    Ok(())
 }
 
-async fn send_stream<S, R>(send: &mut SendStream, msg: S) -> Result<(), ChannelError<S, R>>
+async fn send_stream<S, R>(send: &mut SendStream, bytes: &[u8]) -> Result<(), ChannelError<S, R>>
 where
     S: Message + TryIntoBytes,
     R: Message + TryFromBytes,
 {
-    let serialized = msg.try_into_bytes().map_err(ChannelError::Serialize)?;
-    let bytes = serialized.as_ref();
+    let header = wire::frame_header(bytes.len()).ok_or(ChannelError::FrameTooLarge(bytes.len()))?;
+    send.write_all(&header)
+        .await
+        .map_err(ChannelError::WriteStream)?;
     send.write_all(bytes)
         .await
         .map_err(ChannelError::WriteStream)
 }
 
-fn recv_datagram<S, R>(
+fn recv_datagram<S, R, C>(
     result: Result<Datagram, ConnectionError>,
-    send_r: &mpsc::UnboundedSender<R>,
+    counters: &Counters,
+    cipher: Option<&LaneCipher>,
+    send_r: &mpsc::UnboundedSender<Incoming<R>>,
+    send_lane_event: &mpsc::UnboundedSender<LaneEvent<C>>,
 ) -> Result<(), ChannelError<S, R>>
 where
     S: Message + TryIntoBytes,
     R: Message + TryFromBytes,
+    C: ChannelKey,
 {
     let datagram = result.map_err(ChannelError::RecvDatagram)?;
-    let msg = R::try_from_bytes(&datagram).map_err(ChannelError::Deserialize)?;
-    let _ = send_r.send(msg);
+    counters.on_recv(datagram.len());
+    counters.recv_datagrams.fetch_add(1, Ordering::Relaxed);
+    let Some(opened) = open_message(cipher, &datagram, None, send_lane_event) else {
+        return Ok(());
+    };
+    let Some(payload) = verify_checksum(&opened, None, send_lane_event) else {
+        return Ok(());
+    };
+    counters.on_recv_queued(datagram.len());
+    let _ = send_r.send(Incoming::deserialize(payload, None, datagram.len()));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outgoing(lane: usize, byte: u8) -> Outgoing {
+        Outgoing {
+            lane,
+            bytes: vec![byte],
+            queued_at: Instant::now(),
+            deadline: None,
+            generation: 0,
+        }
+    }
+
+    fn pop_all(queue: &ReplaceQueue) -> Vec<(usize, u8)> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|msg| (msg.lane, msg.bytes[0]))
+            .collect()
+    }
+
+    #[test]
+    fn replaced_message_takes_position() {
+        let lanes = [LaneCounter::default(), LaneCounter::default()];
+        let queue = replace_queue();
+        queue.push(&lanes, 0, outgoing(0, 1));
+        queue.push(&lanes, 1, outgoing(1, 2));
+        queue.push(&lanes, 0, outgoing(0, 3));
+
+        assert_eq!(vec![(0, 3), (1, 2)], pop_all(&queue));
+        assert_eq!(1, lanes[0].dropped.load(Ordering::Relaxed));
+        assert_eq!(0, lanes[1].dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn distinct_slots_keep_order() {
+        let lanes = [LaneCounter::default()];
+        let queue = replace_queue();
+        for (slot, byte) in [(3, 1), (1, 2), (2, 3)] {
+            queue.push(&lanes, slot, outgoing(0, byte));
+        }
+
+        assert_eq!(vec![(0, 1), (0, 2), (0, 3)], pop_all(&queue));
+        assert_eq!(0, lanes[0].dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn clear_lane_drops_only_that_lane() {
+        let lanes = [LaneCounter::default(), LaneCounter::default()];
+        let queue = replace_queue();
+        queue.push(&lanes, 0, outgoing(0, 1));
+        queue.push(&lanes, 1, outgoing(1, 2));
+        queue.push(&lanes, 2, outgoing(0, 3));
+        queue.clear_lane(&lanes, 0);
+
+        assert_eq!(vec![(1, 2)], pop_all(&queue));
+        assert_eq!(2, lanes[0].dropped.load(Ordering::Relaxed));
+        assert_eq!(0, lanes[0].queued.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Options of the UDP socket that an endpoint sends and receives on.
+///
+/// The default OS buffer sizes are usually too small for servers with many
+/// clients, where a burst of datagrams arriving between two reads of the
+/// socket overflows the receive buffer and is dropped by the OS. By default,
+/// every option is left at the OS default.
+///
+/// The options are applied to a socket created by [`SocketOptions::bind`],
+/// which is then passed to the `wtransport` config builder instead of a bind
+/// address:
+///
+/// ```ignore
+/// let socket = SocketOptions {
+///     recv_buffer_size: Some(8 * 1024 * 1024),
+///     send_buffer_size: Some(8 * 1024 * 1024),
+///     tos: Some(SocketOptions::TOS_EXPEDITED_FORWARDING),
+///     ..Default::default()
+/// }
+/// .bind("[::]:25565".parse()?)?;
+/// let config = ServerConfig::builder()
+///     .with_bind_socket(socket)
+///     .with_certificate(cert)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SocketOptions {
+    /// Size in bytes of the receive buffer (`SO_RCVBUF`).
+    ///
+    /// The OS may round this value, or cap it to a system-wide maximum, e.g.
+    /// `net.core.rmem_max` on Linux.
+    pub recv_buffer_size: Option<usize>,
+    /// Size in bytes of the send buffer (`SO_SNDBUF`).
+    ///
+    /// The OS may round this value, or cap it to a system-wide maximum, e.g.
+    /// `net.core.wmem_max` on Linux.
+    pub send_buffer_size: Option<usize>,
+    /// Value of the type-of-service field of every packet sent (`IP_TOS`),
+    /// which routers may use to prioritize the packets.
+    ///
+    /// The upper 6 bits are the DSCP code point. This is only applied to IPv4
+    /// sockets.
+    pub tos: Option<u8>,
+    /// Whether an IPv6 socket only accepts IPv6 traffic (`IPV6_V6ONLY`), or
+    /// also IPv4 traffic using IPv4-mapped addresses.
+    ///
+    /// This is only applied to IPv6 sockets.
+    pub ipv6_only: Option<bool>,
+}
+
+impl SocketOptions {
+    /// [`SocketOptions::tos`] value of the Expedited Forwarding DSCP class
+    /// (46), intended for low-latency traffic such as game state.
+    pub const TOS_EXPEDITED_FORWARDING: u8 = 46 << 2;
+
+    /// Creates a UDP socket with these options, bound to the given address.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the socket could not be created or bound, or an option could
+    /// not be set.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let (Some(tos), SocketAddr::V4(_)) = (self.tos, addr) {
+            set_tos(&socket, tos)?;
+        }
+        if let (Some(only_v6), SocketAddr::V6(_)) = (self.ipv6_only, addr) {
+            socket.set_only_v6(only_v6)?;
+        }
+        socket.bind(&addr.into())?;
+        Ok(socket.into())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+)))]
+fn set_tos(socket: &Socket, tos: u8) -> io::Result<()> {
+    socket.set_tos(u32::from(tos))
+}
+
+#[cfg(any(
+    target_os = "fuchsia",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "illumos",
+))]
+fn set_tos(_: &Socket, _: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IP_TOS is not supported on this platform",
+    ))
+}
@@ -0,0 +1,307 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use derivative::Derivative;
+
+/// Number of buckets in a [`DurationHistogram`].
+const BUCKETS: usize = 24;
+
+/// Upper bound of the first bucket of a [`DurationHistogram`] is
+/// `2^MIN_BUCKET_SHIFT` nanoseconds.
+const MIN_BUCKET_SHIFT: u32 = 7;
+
+/// Histogram of durations, with buckets whose upper bounds are powers of two.
+///
+/// The first bucket holds durations below 128ns, each following bucket
+/// doubles the upper bound of the previous one, and the last bucket holds
+/// everything above ~537ms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl DurationHistogram {
+    /// Records a single duration.
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let bits = u64::BITS - nanos.leading_zeros();
+        let bucket = (bits.saturating_sub(MIN_BUCKET_SHIFT) as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Gets the number of durations recorded.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Gets the sum of all durations recorded.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Gets the longest duration recorded.
+    #[must_use]
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Gets the mean of all durations recorded, or zero if none were.
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = self.total.as_nanos() / u128::from(self.count);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// Gets an upper bound of the duration which the given fraction of
+    /// durations are at or below, e.g. `0.99` for the 99th percentile.
+    ///
+    /// This is the upper bound of the bucket which the quantile falls into,
+    /// capped at [`DurationHistogram::max`], so it overestimates by at most a
+    /// factor of two.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Duration {
+        // the product is clamped to the count, which is a u64
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let target = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return bucket_upper_bound(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Gets the upper bound and count of each bucket, in increasing order.
+    ///
+    /// The upper bound of the last bucket is [`Duration::MAX`].
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| (bucket_upper_bound(bucket), *count))
+    }
+}
+
+fn bucket_upper_bound(bucket: usize) -> Duration {
+    if bucket == BUCKETS - 1 {
+        Duration::MAX
+    } else {
+        Duration::from_nanos(1 << (bucket + MIN_BUCKET_SHIFT as usize))
+    }
+}
+
+/// Statistics of serializing or deserializing a single kind of message.
+///
+/// See [`CodecTimings`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodecStats {
+    /// Time taken for each message.
+    pub time: DurationHistogram,
+    /// Sum of the serialized sizes of all messages, in bytes.
+    pub total_bytes: u64,
+    /// Largest serialized size of a message, in bytes.
+    pub max_bytes: usize,
+}
+
+impl CodecStats {
+    fn record(&mut self, time: Duration, bytes: usize) {
+        self.time.record(time);
+        self.total_bytes += bytes as u64;
+        self.max_bytes = self.max_bytes.max(bytes);
+    }
+}
+
+/// Records how long each kind of message takes to serialize and deserialize,
+/// used for finding message types which are slow to encode or oversized
+/// without an external profiler.
+///
+/// `S` is the type of message sent, and `R` the type of message received, so
+/// a server uses `CodecTimings<P::S2C, P::C2S>` and a client uses
+/// `CodecTimings<P::C2S, P::S2C>`. Since a protocol only has one message type
+/// in each direction, messages are grouped into kinds using a function which
+/// names the kind of each message, e.g. its enum variant:
+///
+/// ```ignore
+/// let timings = Arc::new(CodecTimings::new(
+///     |msg: &S2C| match msg {
+///         S2C::Snapshot(_) => "snapshot",
+///         S2C::Chat(_) => "chat",
+///     },
+///     |msg: &C2S| match msg {
+///         C2S::Input(_) => "input",
+///         C2S::Chat(_) => "chat",
+///     },
+/// ));
+/// server.set_codec_timings(Some(timings.clone()));
+///
+/// for (kind, stats) in timings.top_serialize(3) {
+///     println!("{kind}: {:?} total over {} msgs", stats.time.total(), stats.time.count());
+/// }
+/// ```
+///
+/// Serialization is timed on the thread which sends the message, and
+/// deserialization is timed in the backend task which received it, and
+/// recorded once the frontend receives the message. Messages which fail to
+/// serialize or deserialize are not recorded.
+///
+/// The timings are behind a lock, so they can be shared with e.g. a metrics
+/// endpoint on another thread.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct CodecTimings<S, R> {
+    #[derivative(Debug = "ignore")]
+    sent_kind: fn(&S) -> &'static str,
+    #[derivative(Debug = "ignore")]
+    recv_kind: fn(&R) -> &'static str,
+    serialize: Mutex<HashMap<&'static str, CodecStats>>,
+    deserialize: Mutex<HashMap<&'static str, CodecStats>>,
+}
+
+impl<S, R> CodecTimings<S, R> {
+    /// Creates empty timings, using `sent_kind` and `recv_kind` to name the
+    /// kind of messages sent and received.
+    #[must_use]
+    pub fn new(sent_kind: fn(&S) -> &'static str, recv_kind: fn(&R) -> &'static str) -> Self {
+        Self {
+            sent_kind,
+            recv_kind,
+            serialize: Mutex::default(),
+            deserialize: Mutex::default(),
+        }
+    }
+
+    /// Gets the serialization stats of a kind of message, if any have been
+    /// recorded.
+    #[must_use]
+    pub fn serialize_stats(&self, kind: &str) -> Option<CodecStats> {
+        lock(&self.serialize).get(kind).cloned()
+    }
+
+    /// Gets the deserialization stats of a kind of message, if any have been
+    /// recorded.
+    #[must_use]
+    pub fn deserialize_stats(&self, kind: &str) -> Option<CodecStats> {
+        lock(&self.deserialize).get(kind).cloned()
+    }
+
+    /// Gets the `n` kinds of message which took the most time to serialize in
+    /// total, most expensive first.
+    #[must_use]
+    pub fn top_serialize(&self, n: usize) -> Vec<(&'static str, CodecStats)> {
+        top(&lock(&self.serialize), n)
+    }
+
+    /// Gets the `n` kinds of message which took the most time to deserialize
+    /// in total, most expensive first.
+    #[must_use]
+    pub fn top_deserialize(&self, n: usize) -> Vec<(&'static str, CodecStats)> {
+        top(&lock(&self.deserialize), n)
+    }
+
+    /// Removes all recorded stats.
+    pub fn reset(&self) {
+        lock(&self.serialize).clear();
+        lock(&self.deserialize).clear();
+    }
+
+    pub(crate) fn record_serialize(&self, msg: &S, time: Duration, bytes: usize) {
+        let kind = (self.sent_kind)(msg);
+        lock(&self.serialize)
+            .entry(kind)
+            .or_default()
+            .record(time, bytes);
+    }
+
+    pub(crate) fn record_deserialize(&self, msg: &R, time: Duration, bytes: usize) {
+        let kind = (self.recv_kind)(msg);
+        lock(&self.deserialize)
+            .entry(kind)
+            .or_default()
+            .record(time, bytes);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn top(stats: &HashMap<&'static str, CodecStats>, n: usize) -> Vec<(&'static str, CodecStats)> {
+    let mut top = stats
+        .iter()
+        .map(|(kind, stats)| (*kind, stats.clone()))
+        .collect::<Vec<_>>();
+    top.sort_by_key(|(kind, stats)| (Reverse(stats.time.total()), *kind));
+    top.truncate(n);
+    top
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut hist = DurationHistogram::default();
+        for _ in 0..99 {
+            hist.record(Duration::from_nanos(100));
+        }
+        hist.record(Duration::from_micros(10));
+
+        assert_eq!(100, hist.count());
+        assert_eq!(Duration::from_micros(10), hist.max());
+        assert_eq!(Duration::from_nanos(128), hist.quantile(0.5));
+        assert_eq!(Duration::from_nanos(128), hist.quantile(0.99));
+        assert_eq!(Duration::from_micros(10), hist.quantile(1.0));
+        assert_eq!(Duration::from_nanos(199), hist.mean());
+    }
+
+    #[test]
+    fn top_orders_by_total_time() {
+        let timings = CodecTimings::<&'static str, ()>::new(|msg| msg, |()| "unit");
+        timings.record_serialize(&"small", Duration::from_micros(1), 8);
+        timings.record_serialize(&"small", Duration::from_micros(1), 8);
+        timings.record_serialize(&"large", Duration::from_micros(5), 4096);
+        timings.record_serialize(&"rare", Duration::from_nanos(10), 1);
+
+        let top = timings.top_serialize(2);
+        assert_eq!(
+            vec!["large", "small"],
+            top.iter().map(|(kind, _)| *kind).collect::<Vec<_>>()
+        );
+        assert_eq!(4096, top[0].1.max_bytes);
+        assert_eq!(16, timings.serialize_stats("small").unwrap().total_bytes);
+        assert!(timings.deserialize_stats("unit").is_none());
+    }
+}
@@ -1,7 +1,8 @@
-use std::{fmt::Debug, io, net::SocketAddr, time::Duration};
+use std::{fmt::Debug, io, marker::PhantomData, net::SocketAddr, time::Duration};
 
 use aeronet::{
-    ChannelKey, Message, RemoteAddr, Rtt, TransportProtocol, TryFromBytes, TryIntoBytes,
+    ChannelKey, ConnectionExtensions, Extensions, Features, Message, NegotiatedFeatures,
+    QualityReport, RemoteAddr, RemoteQuality, Rtt, TransportProtocol, TryFromBytes, TryIntoBytes,
 };
 use derivative::Derivative;
 use wtransport::{
@@ -12,6 +13,8 @@ use wtransport::{
     Connection,
 };
 
+use crate::{wire::FrameTooLong, ServerConfigError};
+
 slotmap::new_key_type! {
     /// Key type used to uniquely identify a client connected to a
     /// [`WebTransportServer`].
@@ -28,27 +31,95 @@ pub trait WebTransportProtocol: TransportProtocol {
 /// Statistics on the network state of a [`Connection`] managed by an endpoint.
 ///
 /// This serves as a snapshot of network stats, not a live updating value.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EndpointInfo {
     /// The round-trip time of the connection as defined by [`Rtt`].
     ///
     /// See [`Connection::rtt`]
     pub rtt: Duration,
+    /// Exponential moving average of [`EndpointInfo::rtt`], smoothed in the
+    /// same way as the TCP retransmission timer (RFC 6298).
+    ///
+    /// This changes much less between snapshots than the instantaneous RTT,
+    /// so prefer it for logic such as lag compensation.
+    ///
+    /// If both peers support it, this is measured using ping datagrams which
+    /// bypass the message lanes, so it is not inflated by congested lanes.
+    /// See [`wire`](crate::wire).
+    pub smoothed_rtt: Duration,
+    /// Mean deviation of the RTT samples from [`EndpointInfo::smoothed_rtt`].
+    pub rtt_var: Duration,
+    /// 95th percentile of the RTT samples taken over the last few seconds.
+    pub rtt_p95: Duration,
     /// The remote address of this connection as defined by [`RemoteAddr`].
     ///
     /// See [`Connection::remote_address`].
     pub remote_addr: SocketAddr,
     /// See [`Connection::max_datagram_size`].
     pub max_datagram_size: Option<usize>,
+    /// The last quality report received from the peer, as defined by
+    /// [`RemoteQuality`].
+    ///
+    /// This is only set on clients, and only if the server sends reports (see
+    /// [`WebTransportServer::set_quality_report_interval`]).
+    ///
+    /// [`WebTransportServer::set_quality_report_interval`]: crate::WebTransportServer::set_quality_report_interval
+    pub quality: Option<QualityReport>,
+    /// The optional protocol features negotiated with the peer, as defined by
+    /// [`NegotiatedFeatures`].
+    ///
+    /// This is [`Features::NONE`] until the peers have exchanged their
+    /// features, which happens shortly after the connection is established,
+    /// and stays so if the peer is built from a revision which does not
+    /// advertise its features.
+    ///
+    /// See [`WebTransportServer::set_features`] and
+    /// [`WebTransportClient::set_features`].
+    ///
+    /// [`WebTransportServer::set_features`]: crate::WebTransportServer::set_features
+    /// [`WebTransportClient::set_features`]: crate::WebTransportClient::set_features
+    pub features: Features,
+    /// Message and byte counts since the start of the current statistics
+    /// epoch.
+    pub stats: EpochStats,
+    /// Transport-specific data of this connection, as defined by
+    /// [`ConnectionExtensions`].
+    ///
+    /// This always contains the [`StableId`] of the connection.
+    pub extensions: Extensions,
 }
 
+/// Identifier of a [`Connection`] which stays the same for its entire
+/// lifetime, and is unique among the connections of the same endpoint.
+///
+/// This is available in [`EndpointInfo::extensions`].
+///
+/// See [`Connection::stable_id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StableId(pub usize);
+
 impl EndpointInfo {
     /// Creates a snapshot of network stats from a given connection.
+    ///
+    /// Since a single connection snapshot has no RTT history,
+    /// [`EndpointInfo::smoothed_rtt`] and [`EndpointInfo::rtt_p95`] are set to
+    /// the current RTT, and [`EndpointInfo::rtt_var`] to zero.
+    /// [`EndpointInfo::features`] and [`EndpointInfo::stats`] are left empty.
     pub fn from_connection(conn: &Connection) -> Self {
+        let rtt = conn.rtt();
+        let mut extensions = Extensions::new();
+        extensions.insert(StableId(conn.stable_id()));
         Self {
-            rtt: conn.rtt(),
+            rtt,
+            smoothed_rtt: rtt,
+            rtt_var: Duration::ZERO,
+            rtt_p95: rtt,
             remote_addr: conn.remote_address(),
             max_datagram_size: conn.max_datagram_size(),
+            quality: None,
+            features: Features::NONE,
+            stats: EpochStats::default(),
+            extensions,
         }
     }
 }
@@ -65,6 +136,447 @@ impl RemoteAddr for EndpointInfo {
     }
 }
 
+impl RemoteQuality for EndpointInfo {
+    fn remote_quality(&self) -> Option<QualityReport> {
+        self.quality
+    }
+}
+
+impl NegotiatedFeatures for EndpointInfo {
+    fn negotiated_features(&self) -> Features {
+        self.features
+    }
+}
+
+impl ConnectionExtensions for EndpointInfo {
+    fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+}
+
+/// Totals of the messages sent and received on a connection since the start
+/// of a statistics epoch.
+///
+/// An epoch starts when the connection is established, and a new one is
+/// started every time the stats are reset, e.g. using
+/// [`WebTransportServer::reset_stats`] at the start of a match. This lets you
+/// measure the traffic of a single match without keeping and diffing the
+/// totals yourself.
+///
+/// Messages are counted once they have been written to or read from the
+/// connection. Sizes include the checksum and lane security overhead, but not
+/// the framing of stream lanes.
+///
+/// [`WebTransportServer::reset_stats`]: crate::WebTransportServer::reset_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct EpochStats {
+    /// Identifier of the epoch, starting at 0 and incremented on every reset.
+    pub epoch: u64,
+    /// Number of messages sent.
+    pub msgs_sent: u64,
+    /// Size in bytes of the messages sent.
+    pub bytes_sent: u64,
+    /// Number of messages received.
+    pub msgs_recv: u64,
+    /// Size in bytes of the messages received.
+    pub bytes_recv: u64,
+}
+
+/// Statistics on a single lane (a variant of the protocol's [`ChannelKey`])
+/// of a connection, emitted periodically by an endpoint.
+///
+/// These can be used to adapt the app's behaviour to the network conditions of
+/// a specific lane, e.g. sending snapshots less frequently if the lane that
+/// they are sent on is congested.
+///
+/// Unless otherwise stated, values are measured over the period since the last
+/// [`LaneStats`] for this lane was emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaneStats<C> {
+    /// The lane that these stats are for.
+    pub lane: C,
+    /// [`EpochStats::epoch`] that these stats were measured in.
+    ///
+    /// Resetting the stats also resets the values measured over a period, so
+    /// the first [`LaneStats`] of a new epoch only covers the time since the
+    /// reset.
+    pub epoch: u64,
+    /// Average time taken for the backend to write a single message on this
+    /// lane to the connection, after it has been taken out of the send queue.
+    ///
+    /// This is measured locally, and does not include the time taken for the
+    /// message to reach the peer - see [`EndpointInfo::rtt`] for that. For
+    /// stream-based lanes, this will increase when the stream is blocked by
+    /// flow control or retransmissions.
+    pub send_time: Duration,
+    /// Average time between a message on this lane being sent by the app, and
+    /// the backend finishing writing it to the connection.
+    ///
+    /// This includes the time spent waiting in the send queue behind other
+    /// messages. For reliable lanes, a message can not be written until all
+    /// messages before it on the same stream have been written, so this shows
+    /// how much head-of-line blocking messages on this lane suffer. If this is
+    /// consistently high for a reliable lane, consider moving messages which
+    /// do not need reliability to an unreliable lane.
+    pub send_delay: Duration,
+    /// Longest [`LaneStats::send_delay`] of a single message on this lane.
+    pub max_send_delay: Duration,
+    /// Number of messages on this lane which were discarded without being
+    /// sent.
+    ///
+    /// Messages are dropped e.g. if they are replaced in a replacement slot
+    /// before being sent. Any lane drops the messages which were waiting in
+    /// its send queue when the queue was cleared.
+    pub dropped: usize,
+    /// Number of messages on this lane which were discarded because they were
+    /// not sent before their deadline.
+    ///
+    /// Only messages on unreliable lanes sent with a TTL can expire, e.g.
+    /// using [`WebTransportServer::send_with_ttl`]. These are not included in
+    /// [`LaneStats::dropped`].
+    ///
+    /// [`WebTransportServer::send_with_ttl`]: crate::WebTransportServer::send_with_ttl
+    pub expired: usize,
+    /// Number of messages on this lane which are currently waiting in the send
+    /// queue.
+    ///
+    /// This is the current value at the time of emitting the stats, not a value
+    /// measured over a period.
+    pub queued: usize,
+    /// Total size in bytes of the serialized messages on this lane which are
+    /// currently waiting in the send queue.
+    ///
+    /// This is the current value at the time of emitting the stats, not a value
+    /// measured over a period.
+    pub queued_bytes: usize,
+    /// Number of times that a message received on this lane exceeded the
+    /// [`RecvBufferCap`] of the lane.
+    pub recv_cap_hits: usize,
+}
+
+/// A limit on a resource used by a single connection.
+///
+/// When the usage of the resource reaches the soft limit, a warning event is
+/// raised, allowing you to find out about (and alert on) clients approaching
+/// the hard limit. When the usage exceeds the hard limit, the client is
+/// disconnected.
+///
+/// A warning is only raised once when the usage crosses the soft limit, and
+/// will only be raised again after the usage has dropped back below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Limit {
+    /// Usage at which a warning is raised, or [`None`] to never warn.
+    pub soft: Option<usize>,
+    /// Usage above which the client is disconnected, or [`None`] for no
+    /// limit.
+    pub hard: Option<usize>,
+}
+
+/// Limits on the resources used by each client connected to a server.
+///
+/// By default, no limits are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Size in bytes of a single received message.
+    pub recv_msg_size: Limit,
+    /// Number of messages received within a second.
+    pub recv_rate: Limit,
+    /// Number of messages waiting in the send queue, across all lanes.
+    pub send_queue: Limit,
+    /// Number of messages received from a client before it is admitted.
+    ///
+    /// With manual admission enabled, a client can send messages as soon as
+    /// its connection is established, and these are held by the server until
+    /// the client is admitted. Unlike the other limits, messages received
+    /// after the soft limit is reached are dropped instead of held, so that
+    /// a client can not make the server buffer an unbounded amount of data
+    /// before the application has decided whether to let it in.
+    ///
+    /// See [`WebTransportServer::set_manual_admit`].
+    ///
+    /// [`WebTransportServer::set_manual_admit`]: crate::WebTransportServer::set_manual_admit
+    pub early_msgs: Limit,
+    /// Total size in bytes of the messages received from a client before it
+    /// is admitted.
+    ///
+    /// See [`ConnectionLimits::early_msgs`].
+    pub early_bytes: Limit,
+}
+
+/// Kind of resource that a [`Limit`] applies to.
+///
+/// See [`ConnectionLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    /// See [`ConnectionLimits::recv_msg_size`].
+    RecvMsgSize,
+    /// See [`ConnectionLimits::recv_rate`].
+    RecvRate,
+    /// See [`ConnectionLimits::send_queue`].
+    SendQueue,
+    /// See [`ConnectionLimits::early_msgs`].
+    EarlyMsgs,
+    /// See [`ConnectionLimits::early_bytes`].
+    EarlyBytes,
+}
+
+/// Usage of a resource which has reached one of its limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LimitUsage {
+    /// The kind of resource.
+    pub kind: LimitKind,
+    /// The current usage of the resource.
+    pub usage: usize,
+    /// The limit which was reached.
+    pub limit: usize,
+}
+
+/// Approximate memory held by the transport for a single connection.
+///
+/// This only accounts for the buffers managed by this crate, not for the
+/// internal buffers of the QUIC implementation. Received messages are measured
+/// by their serialized size, which may differ from the size of the
+/// deserialized message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MemoryUsage {
+    /// Size in bytes of the serialized messages waiting in the send queue,
+    /// across all lanes.
+    pub send_queue: usize,
+    /// Size in bytes of the messages which have been received, but not yet
+    /// taken out of the transport by polling it.
+    pub recv_queue: usize,
+    /// Size in bytes of partially received stream frames, buffered until the
+    /// rest of the frame arrives.
+    pub reassembly: usize,
+}
+
+impl MemoryUsage {
+    /// Gets the total size in bytes of all buffers.
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.send_queue + self.recv_queue + self.reassembly
+    }
+}
+
+/// Cap on the total memory held by a server for all of its clients.
+///
+/// See [`WebTransportServer::set_memory_cap`].
+///
+/// [`WebTransportServer::set_memory_cap`]: crate::WebTransportServer::set_memory_cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryCap {
+    /// Total [`MemoryUsage`] in bytes across all clients, above which new
+    /// sessions are rejected.
+    pub limit: usize,
+    /// Whether to also disconnect clients when the limit is exceeded, starting
+    /// with the client using the most memory, until the total usage is back
+    /// under the limit.
+    pub shed: bool,
+}
+
+/// What happens when the receive buffer of a lane exceeds its
+/// [`RecvBufferCap`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RecvBufferPolicy {
+    /// The buffered part of the message is discarded, and the rest of the
+    /// message is skipped as it arrives. The lane stays open, and the messages
+    /// after it are received as normal.
+    #[default]
+    DropBuffer,
+    /// The client is disconnected with [`ChannelError::RecvBufferFull`].
+    Disconnect,
+}
+
+/// Cap on the data received on a single lane which is buffered but can not be
+/// delivered yet.
+///
+/// Messages on a stream-based lane are buffered until the whole message has
+/// arrived. A client which sends the start of a large message and then holds
+/// back the rest can make the server hold on to the buffered part
+/// indefinitely. Since the frame header declares the size of the whole
+/// message, a message is caught as soon as its header arrives, without waiting
+/// for the rest of it.
+///
+/// Every time a cap is hit, [`LaneStats::recv_cap_hits`] is incremented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecvBufferCap {
+    /// Maximum number of bytes of a partially received message, including its
+    /// frame header.
+    ///
+    /// If this is larger than [`DEFAULT_MAX_FRAME_LEN`], it also raises the
+    /// maximum length of a frame accepted on the lane.
+    ///
+    /// [`DEFAULT_MAX_FRAME_LEN`]: crate::wire::DEFAULT_MAX_FRAME_LEN
+    pub max_bytes: usize,
+    /// What happens when the cap is exceeded.
+    pub policy: RecvBufferPolicy,
+}
+
+/// Configuration of the [`RecvBufferCap`] of every lane of a protocol.
+///
+/// Unreliable lanes receive every message in a single datagram, so they never
+/// buffer partial messages and their caps are ignored. By default, no lane has
+/// a cap.
+///
+/// See [`WebTransportServer::set_recv_buffer_caps`].
+///
+/// [`WebTransportServer::set_recv_buffer_caps`]: crate::WebTransportServer::set_recv_buffer_caps
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""), Default(bound = ""))]
+pub struct RecvBufferCaps<C> {
+    caps: Box<[Option<RecvBufferCap>]>,
+    #[derivative(Debug = "ignore")]
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<C: ChannelKey> RecvBufferCaps<C> {
+    /// Creates a configuration from a function giving the cap of each lane, or
+    /// [`None`] for no cap.
+    pub fn new(cap: impl Fn(&C) -> Option<RecvBufferCap>) -> Self {
+        Self {
+            caps: C::ALL.iter().map(cap).collect(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates a configuration which uses the same cap for every lane.
+    #[must_use]
+    pub fn uniform(cap: RecvBufferCap) -> Self {
+        Self::new(|_| Some(cap))
+    }
+
+    /// Gets the cap of a lane.
+    #[must_use]
+    pub fn cap(&self, lane: &C) -> Option<RecvBufferCap> {
+        self.caps.get(lane.index()).copied().flatten()
+    }
+}
+
+/// Controls how a server allocates and releases the storage for its clients.
+///
+/// Clients are stored in an arena, where the slot of a disconnected client is
+/// reused by the next client which connects, with a [`ClientKey`] of a newer
+/// version. By default, the arena grows as clients connect and never shrinks.
+///
+/// See [`WebTransportServer::set_arena`].
+///
+/// [`WebTransportServer::set_arena`]: crate::WebTransportServer::set_arena
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ClientArena {
+    /// Number of client slots allocated up front when the server opens.
+    ///
+    /// Servers which expect many clients to connect and leave can set this
+    /// to their expected peak, so that the arena does not have to reallocate
+    /// while clients are connecting.
+    pub initial_capacity: usize,
+    /// When the arena gives memory back after clients have left.
+    pub shrink: ArenaShrink,
+}
+
+/// When a server's [`ClientArena`] gives memory back after clients have left.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ArenaShrink {
+    /// The arena keeps all of its slots, and never reallocates once it has
+    /// grown to the peak number of clients.
+    #[default]
+    Never,
+    /// Once the server has had no clients for the given duration, the arena
+    /// is compacted back to [`ClientArena::initial_capacity`].
+    ///
+    /// Compacting resets the versions of the arena's keys, so a [`ClientKey`]
+    /// of a client which disconnected before the compaction may be given to a
+    /// new client afterwards. Keys must not be used after a
+    /// [`ServerEvent::Disconnected`] has been raised for them.
+    ///
+    /// [`ServerEvent::Disconnected`]: crate::ServerEvent::Disconnected
+    OnIdle(Duration),
+}
+
+/// Controls how a server queues and takes in new sessions.
+///
+/// The backend accepts incoming sessions as they arrive, and queues them until
+/// the frontend takes them in when the server is polled. During a flood of
+/// connection attempts, an unbounded queue lets the flood use an unbounded
+/// amount of memory, and taking in every queued session at once makes a single
+/// poll spend most of its time on new sessions instead of the clients which
+/// are already connected. By default, the queue is unbounded and every queued
+/// session is taken in on each poll.
+///
+/// See [`WebTransportServer::set_incoming_queue`].
+///
+/// [`WebTransportServer::set_incoming_queue`]: crate::WebTransportServer::set_incoming_queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct IncomingQueue {
+    /// Maximum number of sessions waiting to be taken in by the frontend, or
+    /// [`None`] for no limit.
+    ///
+    /// Sessions which arrive while the queue is full are dropped by the
+    /// backend without a response, and no event is raised for them.
+    pub capacity: Option<usize>,
+    /// Maximum number of sessions taken in each time the server is polled,
+    /// or [`None`] to take in all queued sessions.
+    ///
+    /// Sessions which are not taken in stay queued until the next poll.
+    pub per_poll: Option<usize>,
+}
+
+/// Details of a received message whose checksum did not match its contents.
+///
+/// This is only detected with the `checksum` feature enabled. The message is
+/// dropped instead of being deserialized, but the connection is kept open.
+///
+/// If mismatches show up, the bytes were corrupted somewhere between the
+/// peer's serializer and this endpoint's deserializer, i.e. in the transport.
+/// If a message fails to deserialize but its checksum matches, the bytes
+/// arrived exactly as they were sent, so the bug is in the serialization code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch<C> {
+    /// The lane that the message was received on, if known.
+    ///
+    /// Datagrams do not carry their lane, so this is [`None`] for messages
+    /// received on unreliable lanes.
+    pub channel: Option<C>,
+    /// Length in bytes of the message, including the checksum.
+    pub len: usize,
+    /// Checksum which was sent along with the message, or [`None`] if the
+    /// message was too short to contain one.
+    pub received: Option<u32>,
+    /// Checksum computed over the contents of the message.
+    pub computed: u32,
+    /// The first bytes of the message, for inspection.
+    pub head: Vec<u8>,
+}
+
+/// Response of a server to a client's request to open a session.
+///
+/// The browser is sent the HTTP status code of the response, which web clients
+/// can use to show a more specific error than a generic network failure.
+///
+/// Only `403` and `404` can be sent when rejecting a session, since these are
+/// the only status codes supported by [`wtransport::endpoint::SessionRequest`].
+/// Other status codes, such as `429 Too Many Requests` or `503 Service
+/// Unavailable`, and response headers, such as `Retry-After`, can not be sent.
+/// If the client needs to know why it was rejected, or when to retry, accept
+/// the session and send this in a message before disconnecting it instead.
+///
+/// See [`WebTransportServer::respond`].
+///
+/// [`WebTransportServer::respond`]: crate::WebTransportServer::respond
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionResponse {
+    /// Accept the session, and start establishing the connection.
+    Accept,
+    /// Reject the session with HTTP status `403 Forbidden`.
+    ///
+    /// Use this when e.g. the client failed authentication, or the server is
+    /// full.
+    Forbidden,
+    /// Reject the session with HTTP status `404 Not Found`.
+    ///
+    /// Use this when e.g. the client requested a path which is not served.
+    NotFound,
+}
+
 /// Error that occurs when processing a WebTransport transport implementation.
 #[derive(Derivative, thiserror::Error)]
 #[derivative(Debug(bound = "P::C2S: Debug, P::S2C: Debug, P::Channel: Debug, S: Debug, R: Debug"))]
@@ -87,6 +599,19 @@ where
     /// Failed to connect the endpoint to the given URL.
     #[error("failed to connect to URL")]
     Connect(#[source] ConnectingError),
+    /// An attempt to connect to a URL did not complete within the attempt
+    /// timeout.
+    #[error("connection attempt timed out after {0:?}")]
+    ConnectTimeout(Duration),
+    /// Attempted to connect using an empty list of URLs.
+    #[error("no URLs to connect to")]
+    NoUrls,
+    /// Attempted to open a sharded server using an empty list of shards.
+    #[error("no shards to open")]
+    NoShards,
+    /// Attempted to open a server using an invalid config.
+    #[error("invalid server config")]
+    ServerConfig(#[source] ServerConfigError),
     /// Failed to receive an incoming session.
     #[error("failed to receive incoming session")]
     IncomingSession(#[source] ConnectionError),
@@ -110,6 +635,68 @@ where
     /// The client was forcefully disconnected by the server.
     #[error("force disconnect")]
     ForceDisconnect,
+    /// The client was kicked by the server, with the given reason.
+    ///
+    /// See [`WebTransportServer::kick`].
+    ///
+    /// [`WebTransportServer::kick`]: crate::WebTransportServer::kick
+    #[error("kicked: {0}")]
+    Kicked(String),
+    /// The client is banned from the server, for the given reason.
+    ///
+    /// See [`BanList`](crate::BanList).
+    #[error("banned: {0}")]
+    Banned(String),
+    /// The client exceeded a hard limit set on the server.
+    #[error("exceeded {:?} limit: {} > {}", .0.kind, .0.usage, .0.limit)]
+    LimitExceeded(LimitUsage),
+    /// Attempted to respond to the session request of a client which is not
+    /// awaiting a response.
+    #[error("client {0:?} is not awaiting a session response")]
+    NotAwaitingResponse(ClientKey),
+    /// Attempted to admit or refuse a client which is not pending admission.
+    #[error("client {0:?} is not pending admission")]
+    NotPending(ClientKey),
+    /// Attempted to migrate a lane on a connection which did not negotiate
+    /// [`Features::LANE_MIGRATION`] with its peer.
+    #[error("lane migration was not negotiated with the peer")]
+    LaneMigrationNotNegotiated,
+    /// Attempted to use a lane which is not unreliable as the fast-start lane
+    /// of a server.
+    ///
+    /// See [`WebTransportServer::set_fast_start_lane`][fast_start].
+    ///
+    /// [fast_start]: crate::WebTransportServer::set_fast_start_lane
+    #[error("fast-start lane {0:?} is not unreliable")]
+    FastStartLaneReliable(P::Channel),
+    /// The client was rejected or disconnected because the server's total
+    /// memory usage exceeded its [`MemoryCap`], with the given usage in
+    /// bytes.
+    #[error("server memory usage of {0} bytes exceeds the cap")]
+    MemoryCapExceeded(usize),
+    /// The client was rejected or disconnected because the server is draining.
+    ///
+    /// See [`WebTransportServer::drain`].
+    ///
+    /// [`WebTransportServer::drain`]: crate::WebTransportServer::drain
+    #[error("server is draining")]
+    Draining,
+    /// The server rejected the client's session request.
+    #[error("session rejected with {0:?}")]
+    SessionRejected(SessionResponse),
+    /// The client did not finish opening its channels within the server's
+    /// handshake timeout.
+    ///
+    /// See [`WebTransportServer::set_handshake_timeout`][timeout].
+    ///
+    /// [timeout]: crate::WebTransportServer::set_handshake_timeout
+    #[error("handshake did not complete within {0:?}")]
+    HandshakeTimeout(Duration),
+    /// The client was disconnected to relieve an overloaded server.
+    ///
+    /// See [`EvictionPolicy`](crate::EvictionPolicy).
+    #[error("evicted from overloaded server: {0:?}")]
+    Evicted(crate::Overload),
 }
 
 /// Error that occurs while processing a channel, either datagrams or QUIC
@@ -138,6 +725,12 @@ where
     /// Failed to write into a bidirectional stream.
     #[error("failed to write stream")]
     WriteStream(#[source] StreamWriteError),
+    /// Attempted to send a message on a stream which is larger than the
+    /// maximum size of a stream frame.
+    ///
+    /// See [`wire`](crate::wire).
+    #[error("message of {0} bytes is too large for a stream frame")]
+    FrameTooLarge(usize),
     /// Failed to serialize data using [`TryIntoBytes::try_into_bytes`].
     #[error("failed to serialize data")]
     Serialize(#[source] S::Error),
@@ -149,7 +742,22 @@ where
     /// Failed to read from a bidirectional stream.
     #[error("failed to read stream")]
     ReadStream(#[source] StreamReadError),
+    /// The header of a frame received on a stream gave a length longer than
+    /// the maximum, so the stream can not be decoded any further.
+    ///
+    /// The maximum is [`DEFAULT_MAX_FRAME_LEN`], or the [`RecvBufferCap`] of
+    /// the lane if that is larger.
+    ///
+    /// [`DEFAULT_MAX_FRAME_LEN`]: crate::wire::DEFAULT_MAX_FRAME_LEN
+    #[error("{0}")]
+    RecvFrameTooLong(FrameTooLong),
     /// Failed to deserialize data using [`TryFromBytes::try_from_bytes`].
     #[error("failed to deserialize data")]
     Deserialize(#[source] R::Error),
+    /// A partially received message exceeded the [`RecvBufferCap`] of its
+    /// lane, which uses [`RecvBufferPolicy::Disconnect`].
+    ///
+    /// Contains the size of the message and the cap, in bytes.
+    #[error("message of {0} bytes exceeds receive buffer cap of {1} bytes")]
+    RecvBufferFull(usize, usize),
 }
@@ -0,0 +1,304 @@
+//! Description of the wire format used by the WebTransport transports.
+//!
+//! This allows third-party implementations (e.g. a server written in another
+//! language, or a browser client written in plain JS) to interoperate with the
+//! transports in this crate.
+//!
+//! # Format
+//!
+//! On connection, the server opens one bidirectional stream for every channel
+//! of a reliable [`ChannelKind`], in the order that the channels appear in
+//! [`ChannelKey::ALL`]. The client accepts these streams in the same order.
+//! Unreliable channels do not use a stream, and instead send messages as
+//! datagrams.
+//!
+//! * **Streams** - each message is sent as a frame, consisting of the length of
+//!   the message as a big-endian `u32`, followed by the message bytes.
+//! * **Datagrams** - each datagram contains exactly one message, with no
+//!   header.
+//!
+//! Messages are never fragmented across multiple datagrams, and multiple
+//! messages are never coalesced into a single datagram.
+//!
+//! If the server sends quality reports, it opens a single unidirectional
+//! stream after the channel streams, on which it writes a [`QualitySample`]
+//! every time a report is due. Samples are fixed-size and have no frame
+//! header. Clients which do not use the reports may ignore this stream.
+//!
+//! After the channel streams, the client may open a unidirectional stream on
+//! which it writes a single features frame, advertising the optional
+//! [`Features`] that it supports. A server which receives this frame answers
+//! by opening its own unidirectional stream with a single features frame. The
+//! features of the connection are those in both frames. Servers only answer
+//! clients which sent a frame, so clients which do not negotiate features
+//! never see this stream. Features frames start with [`FEATURES_MAGIC`], which
+//! tells them apart from the quality report stream.
+//!
+//! Both peers always advertise [`Features::PING`]. If it is negotiated, each
+//! peer periodically writes a [`Ping::Request`] on its own features stream,
+//! after the features frame, and answers every request it receives with a
+//! [`Ping::Reply`] on the same stream. Pings are [`PING_LEN`] bytes long and
+//! start with [`PING_MAGIC`], and are used to measure the round-trip time
+//! independently of how congested the channels are. Pings never use datagrams,
+//! so every datagram carries a message.
+//!
+//! Both peers also always advertise [`Features::LANE_MIGRATION`]. If it is
+//! negotiated, either peer may move the messages of a channel onto the lane of
+//! another channel while the connection is open, e.g. to move a chat channel
+//! onto an unreliable lane while bandwidth is scarce. The first peer to do so
+//! opens a unidirectional stream, on which it writes [`LaneMigration`] frames,
+//! and the other peer answers on its own stream, opened the same way. A
+//! channel is only moved once the peer has accepted the proposal, and a peer
+//! which receives a proposal for a channel that it is itself migrating
+//! rejects it if it is the server, or accepts it and drops its own proposal if
+//! it is the client. Frames start with [`LANE_MIGRATION_MAGIC`], which tells
+//! this stream apart from the others. Migrations are refused between lanes
+//! with different lane security policies.
+//!
+//! The stream frames and quality samples are encoded using the types of
+//! [`aeronet_proto`], which are re-exported here. That crate is `no_std`, so
+//! that peers without `tokio` or the standard library can implement this
+//! format.
+//!
+//! Use [`describe`] to get a machine-readable description of this format for a
+//! specific protocol, and [`WireDescription::to_typescript`] to generate a
+//! browser client for it which does not need WASM.
+//!
+//! # Fuzzing
+//!
+//! Bytes read from a connection are untrusted, so the decoders in this module
+//! are fuzzed by the targets in the crate's `fuzz` directory. Run them with
+//! [`cargo-fuzz`](https://github.com/rust-fuzz/cargo-fuzz), e.g.
+//! `cargo fuzz run frame_decoder` from the crate directory.
+
+use std::fmt::Write;
+
+use aeronet::{ChannelKey, ChannelKind};
+
+pub use aeronet_proto::{
+    encode_frame, frame_header, parse_frame, Features, FrameDecoder, FrameTooLong, LaneMigration,
+    Ping, QualitySample, DEFAULT_MAX_FRAME_LEN, FEATURES_FRAME_LEN, FEATURES_MAGIC,
+    FRAME_HEADER_LEN, LANE_MIGRATION_LEN, LANE_MIGRATION_MAGIC, PING_LEN, PING_MAGIC,
+    QUALITY_SAMPLE_LEN, WIRE_VERSION,
+};
+
+/// Description of the wire format used for a specific protocol.
+///
+/// See the [module-level docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireDescription {
+    /// See [`WIRE_VERSION`].
+    pub version: u32,
+    /// How each channel of the protocol is transported.
+    pub channels: Vec<ChannelDescription>,
+    /// Example messages encoded in the wire format.
+    pub samples: Vec<WireSample>,
+}
+
+/// Description of how a single channel is transported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelDescription {
+    /// The index of the channel in [`ChannelKey::ALL`].
+    pub index: usize,
+    /// The [`Debug`] name of the channel.
+    pub name: String,
+    /// The kind of the channel.
+    pub kind: ChannelKind,
+    /// The position of this channel's stream in the order that streams are
+    /// opened, or [`None`] if this channel uses datagrams.
+    pub stream: Option<usize>,
+}
+
+/// An example message encoded in the wire format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireSample {
+    /// The serialized message.
+    pub payload: Vec<u8>,
+    /// The message when sent on a stream.
+    pub stream_frame: Vec<u8>,
+    /// The message when sent as a datagram.
+    pub datagram: Vec<u8>,
+}
+
+/// Payloads used to create the [`WireDescription::samples`].
+const SAMPLE_PAYLOADS: &[&[u8]] = &[b"", b"hello", &[0x00, 0xff, 0x10]];
+
+/// Describes the wire format used for a protocol with the channels `C`.
+#[must_use]
+pub fn describe<C: ChannelKey>() -> WireDescription {
+    let kinds = C::ALL.iter().map(ChannelKey::kind).collect::<Vec<_>>();
+    let channels = C::ALL
+        .iter()
+        .map(|channel| ChannelDescription {
+            index: channel.index(),
+            name: format!("{channel:?}"),
+            kind: channel.kind(),
+            stream: aeronet_proto::stream_index(&kinds, channel.index()),
+        })
+        .collect();
+
+    let samples = SAMPLE_PAYLOADS
+        .iter()
+        .map(|payload| WireSample {
+            payload: payload.to_vec(),
+            stream_frame: encode_frame(payload).expect("sample payloads should be small"),
+            datagram: payload.to_vec(),
+        })
+        .collect();
+
+    WireDescription {
+        version: WIRE_VERSION,
+        channels,
+        samples,
+    }
+}
+
+impl WireDescription {
+    /// Formats this description as a pretty-printed JSON document.
+    ///
+    /// Byte sequences are written as lowercase hex strings.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\n");
+        let _ = writeln!(out, "  \"version\": {},", self.version);
+        out.push_str("  \"stream_framing\": {\n");
+        out.push_str("    \"length_prefix\": \"u32_be\",\n");
+        let _ = writeln!(out, "    \"header_len\": {FRAME_HEADER_LEN}");
+        out.push_str("  },\n");
+        out.push_str("  \"datagram_framing\": {\n");
+        out.push_str("    \"messages_per_datagram\": 1,\n");
+        out.push_str("    \"header_len\": 0\n");
+        out.push_str("  },\n");
+        out.push_str("  \"fragmentation\": false,\n");
+        out.push_str("  \"coalescing\": false,\n");
+
+        out.push_str("  \"channels\": [");
+        for (i, channel) in self.channels.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            out.push_str("    {\n");
+            let _ = writeln!(out, "      \"index\": {},", channel.index);
+            let _ = writeln!(out, "      \"name\": {},", json_string(&channel.name));
+            let _ = writeln!(out, "      \"kind\": \"{}\",", kind_name(channel.kind));
+            match channel.stream {
+                Some(stream) => {
+                    out.push_str("      \"transport\": \"stream\",\n");
+                    let _ = writeln!(out, "      \"stream_order\": {stream}");
+                }
+                None => out.push_str("      \"transport\": \"datagram\"\n"),
+            }
+            out.push_str("    }");
+        }
+        out.push_str(if self.channels.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        out.push_str("  \"samples\": [");
+        for (i, sample) in self.samples.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            out.push_str("    {\n");
+            let _ = writeln!(out, "      \"payload\": \"{}\",", hex(&sample.payload));
+            let _ = writeln!(
+                out,
+                "      \"stream_frame\": \"{}\",",
+                hex(&sample.stream_frame)
+            );
+            let _ = writeln!(out, "      \"datagram\": \"{}\"", hex(&sample.datagram));
+            out.push_str("    }");
+        }
+        out.push_str(if self.samples.is_empty() {
+            "]\n"
+        } else {
+            "\n  ]\n"
+        });
+        out.push_str("}\n");
+        out
+    }
+
+    /// Generates a TypeScript module containing a client for this wire
+    /// format.
+    ///
+    /// The module uses the browser's `WebTransport` API directly, so it can
+    /// connect to a [`WebTransportServer`] from plain JS without a WASM
+    /// bundle. It exports:
+    /// * `CHANNELS` - the channels of this protocol, in [`ChannelKey::ALL`]
+    ///   order
+    /// * `CHANNEL` - the same channels keyed by their name
+    /// * `encodeFrame` and `FrameDecoder` - stream framing helpers
+    /// * `AeronetClient` - a client which establishes all channels on connect,
+    ///   and sends and receives already-serialized messages
+    ///
+    /// Messages are passed to and from the client as byte arrays; serializing
+    /// them is up to the user.
+    ///
+    /// [`WebTransportServer`]: crate::WebTransportServer
+    #[must_use]
+    pub fn to_typescript(&self) -> String {
+        let mut out = String::new();
+        out.push_str("// Generated by aeronet_wt_native - do not edit.\n\n");
+        out.push_str(&TS_CLIENT.replace(
+            &format!("export const WIRE_VERSION = {WIRE_VERSION};"),
+            &format!("export const WIRE_VERSION = {};", self.version),
+        ));
+
+        out.push_str("\nexport const CHANNELS: readonly Channel[] = [\n");
+        for channel in &self.channels {
+            let stream_order = channel
+                .stream
+                .map_or_else(|| "null".to_owned(), |stream| stream.to_string());
+            let _ = writeln!(
+                out,
+                "  {{ index: {}, name: {}, kind: \"{}\", streamOrder: {} }},",
+                channel.index,
+                json_string(&channel.name),
+                kind_name(channel.kind),
+                stream_order,
+            );
+        }
+        out.push_str("];\n");
+
+        out.push_str("\nexport const CHANNEL: { readonly [name: string]: Channel } = {\n");
+        for (i, channel) in self.channels.iter().enumerate() {
+            let _ = writeln!(out, "  {}: CHANNELS[{i}],", json_string(&channel.name));
+        }
+        out.push_str("};\n");
+        out
+    }
+}
+
+/// Handwritten part of the module generated by
+/// [`WireDescription::to_typescript`].
+const TS_CLIENT: &str = include_str!("wire_client.ts");
+
+fn kind_name(kind: ChannelKind) -> &'static str {
+    match kind {
+        ChannelKind::Unreliable => "unreliable",
+        ChannelKind::ReliableUnordered => "reliable_unordered",
+        ChannelKind::ReliableOrdered => "reliable_ordered",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
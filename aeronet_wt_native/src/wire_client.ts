@@ -0,0 +1,142 @@
+export const WIRE_VERSION = 6;
+export const FRAME_HEADER_LEN = 4;
+
+export type ChannelKind = "unreliable" | "reliable_unordered" | "reliable_ordered";
+
+export interface Channel {
+  index: number;
+  name: string;
+  kind: ChannelKind;
+  /** Position of this channel's stream in the order streams are opened, or `null` for datagrams. */
+  streamOrder: number | null;
+}
+
+/** Encodes a message as a stream frame: a big-endian u32 length followed by the payload. */
+export function encodeFrame(payload: Uint8Array): Uint8Array {
+  const frame = new Uint8Array(FRAME_HEADER_LEN + payload.length);
+  new DataView(frame.buffer).setUint32(0, payload.length, false);
+  frame.set(payload, FRAME_HEADER_LEN);
+  return frame;
+}
+
+/** Splits a byte stream into the messages sent in it as stream frames. */
+export class FrameDecoder {
+  private buf = new Uint8Array(0);
+
+  push(bytes: Uint8Array): void {
+    const buf = new Uint8Array(this.buf.length + bytes.length);
+    buf.set(this.buf);
+    buf.set(bytes, this.buf.length);
+    this.buf = buf;
+  }
+
+  nextFrame(): Uint8Array | null {
+    if (this.buf.length < FRAME_HEADER_LEN) {
+      return null;
+    }
+    const len = new DataView(this.buf.buffer, this.buf.byteOffset).getUint32(0, false);
+    const end = FRAME_HEADER_LEN + len;
+    if (this.buf.length < end) {
+      return null;
+    }
+    const frame = this.buf.slice(FRAME_HEADER_LEN, end);
+    this.buf = this.buf.slice(end);
+    return frame;
+  }
+}
+
+export interface ClientHandlers {
+  /** Called for every received message. `channel` is `null` for messages received as datagrams. */
+  onMessage(payload: Uint8Array, channel: Channel | null): void;
+  /** Called once when the connection is lost. */
+  onDisconnect?(cause: unknown): void;
+}
+
+/** Client for a server using the aeronet WebTransport wire format. */
+export class AeronetClient {
+  private constructor(
+    private readonly transport: WebTransport,
+    private readonly datagrams: WritableStreamDefaultWriter<Uint8Array>,
+    private readonly streams: Map<number, WritableStreamDefaultWriter<Uint8Array>>,
+  ) {}
+
+  /** Connects to a server and waits until all channels have been established. */
+  static async connect(
+    url: string,
+    handlers: ClientHandlers,
+    options?: WebTransportOptions,
+  ): Promise<AeronetClient> {
+    const transport = new WebTransport(url, options);
+    await transport.ready;
+
+    const incoming = transport.incomingBidirectionalStreams.getReader();
+    const streams = new Map<number, WritableStreamDefaultWriter<Uint8Array>>();
+    const streamChannels = CHANNELS.filter((channel) => channel.streamOrder !== null).sort(
+      (a, b) => (a.streamOrder as number) - (b.streamOrder as number),
+    );
+    for (const channel of streamChannels) {
+      const { value: stream, done } = await incoming.read();
+      if (done) {
+        throw new Error(`connection closed before stream for ${channel.name} was opened`);
+      }
+      streams.set(channel.index, stream.writable.getWriter());
+      readStream(stream.readable, channel, handlers);
+    }
+    incoming.releaseLock();
+
+    readDatagrams(transport.datagrams.readable, handlers);
+    transport.closed.then(
+      () => handlers.onDisconnect?.(null),
+      (err) => handlers.onDisconnect?.(err),
+    );
+    return new AeronetClient(transport, transport.datagrams.writable.getWriter(), streams);
+  }
+
+  /** Sends a serialized message on the given channel. */
+  async send(channel: Channel, payload: Uint8Array): Promise<void> {
+    const stream = this.streams.get(channel.index);
+    if (stream === undefined) {
+      await this.datagrams.write(payload);
+    } else {
+      await stream.write(encodeFrame(payload));
+    }
+  }
+
+  /** Closes the connection. */
+  close(): void {
+    this.transport.close();
+  }
+}
+
+async function readStream(
+  readable: ReadableStream<Uint8Array>,
+  channel: Channel,
+  handlers: ClientHandlers,
+): Promise<void> {
+  const reader = readable.getReader();
+  const decoder = new FrameDecoder();
+  for (;;) {
+    const { value, done } = await reader.read();
+    if (done) {
+      return;
+    }
+    decoder.push(value);
+    for (let frame = decoder.nextFrame(); frame !== null; frame = decoder.nextFrame()) {
+      handlers.onMessage(frame, channel);
+    }
+  }
+}
+
+async function readDatagrams(
+  readable: ReadableStream<Uint8Array>,
+  handlers: ClientHandlers,
+): Promise<void> {
+  const reader = readable.getReader();
+  for (;;) {
+    const { value, done } = await reader.read();
+    if (done) {
+      return;
+    }
+    handlers.onMessage(value, null);
+  }
+}
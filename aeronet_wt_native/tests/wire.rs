@@ -0,0 +1,44 @@
+use aeronet::ChannelKey;
+use aeronet_wt_native::wire;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChannelKey)]
+enum AppChannel {
+    #[channel_kind(Unreliable)]
+    Input,
+    #[channel_kind(ReliableOrdered)]
+    Chat,
+    #[channel_kind(Unreliable)]
+    State,
+    #[channel_kind(ReliableUnordered)]
+    Events,
+}
+
+#[test]
+fn golden_description() {
+    let desc = wire::describe::<AppChannel>();
+    assert_eq!(include_str!("golden/wire.json"), desc.to_json());
+}
+
+#[test]
+fn golden_frames() {
+    assert_eq!(Some(vec![0, 0, 0, 0]), wire::encode_frame(b""));
+    assert_eq!(
+        Some(vec![0, 0, 0, 5, b'h', b'e', b'l', b'l', b'o']),
+        wire::encode_frame(b"hello")
+    );
+    assert_eq!(
+        Some(vec![0, 0, 0x01, 0x00]),
+        wire::frame_header(256).map(Vec::from)
+    );
+}
+
+#[test]
+fn typescript_channels() {
+    let ts = wire::describe::<AppChannel>().to_typescript();
+    assert!(ts.contains("export const WIRE_VERSION = 6;"));
+    assert!(
+        ts.contains(r#"  { index: 1, name: "Chat", kind: "reliable_ordered", streamOrder: 0 },"#)
+    );
+    assert!(ts.contains(r#"  { index: 2, name: "State", kind: "unreliable", streamOrder: null },"#));
+    assert!(ts.contains(r#"  "Events": CHANNELS[3],"#));
+}
@@ -169,7 +169,7 @@ where
             let msg: C2S = msg.into();
             let payload = msg
                 .try_into_bytes()
-                .map_err(|err| SessionError::Transport(anyhow::anyhow!("send err")))?; // TODO
+                .map_err(|err| SessionError::Transport(err.into()))?;
             let chunk = Uint8Array::new_with_length(payload.len().try_into().unwrap());
             chunk.copy_from(&payload);
 